@@ -0,0 +1,104 @@
+//! Benchmarks for `PrefixMap` lookups.
+//!
+//! Run with `cargo bench --bench prefix_map`. The benchmark fills a map
+//! with a number of random prefixes similar to a full set of route origins
+//! and measures the average time of the different kinds of lookups.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::Instant;
+use rpki::payload::Prefix;
+use rpki::resources::PrefixMap;
+
+/// The number of IPv4 prefixes in the map.
+const V4_PREFIXES: usize = 400_000;
+
+/// The number of IPv6 prefixes in the map.
+const V6_PREFIXES: usize = 100_000;
+
+/// The number of lookups per measurement.
+const LOOKUPS: usize = 1_000_000;
+
+/// A simple deterministic pseudo random number generator.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        // SplitMix64
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn v4_prefix(&mut self, min_len: u8, max_len: u8) -> Prefix {
+        let len = min_len + (self.next() % u64::from(max_len - min_len + 1))
+            as u8;
+        let bits = (self.next() as u32) & !(!0u32 >> len);
+        Prefix::new(IpAddr::from(Ipv4Addr::from(bits)), len).unwrap()
+    }
+
+    fn v6_prefix(&mut self, min_len: u8, max_len: u8) -> Prefix {
+        let len = min_len + (self.next() % u64::from(max_len - min_len + 1))
+            as u8;
+        let bits = (
+            u128::from(0x2000_0000 | (self.next() as u32 & 0x0FFF_FFFF))
+            << 96
+        ) | (u128::from(self.next()) << 32);
+        let bits = bits & !(!0u128 >> len);
+        Prefix::new(IpAddr::from(Ipv6Addr::from(bits)), len).unwrap()
+    }
+}
+
+fn measure<F: FnMut(Prefix) -> bool>(
+    name: &str, queries: &[Prefix], mut op: F
+) {
+    let start = Instant::now();
+    let mut hits = 0usize;
+    for query in queries.iter().cycle().take(LOOKUPS) {
+        if op(*query) {
+            hits += 1;
+        }
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:<16} {:>8.1} ns/lookup ({} hits)",
+        name,
+        elapsed.as_nanos() as f64 / LOOKUPS as f64,
+        hits
+    );
+}
+
+fn main() {
+    let mut rng = Rng(0x5EED);
+    let mut map = PrefixMap::new();
+    for i in 0..V4_PREFIXES {
+        map.insert(rng.v4_prefix(8, 24), i);
+    }
+    for i in 0..V6_PREFIXES {
+        map.insert(rng.v6_prefix(19, 48), i);
+    }
+    println!("{} prefixes in map", map.len());
+
+    let existing: Vec<_> = map.iter().map(|(prefix, _)| prefix)
+        .step_by(7).collect();
+    let random: Vec<_> = (0..LOOKUPS / 4).map(|i| {
+        if i % 5 == 0 {
+            rng.v6_prefix(48, 64)
+        }
+        else {
+            rng.v4_prefix(24, 32)
+        }
+    }).collect();
+
+    measure("exact", &existing, |prefix| map.get(prefix).is_some());
+    measure("longest match", &random, |prefix| {
+        map.longest_match(prefix).is_some()
+    });
+    measure("covering", &random, |prefix| {
+        map.covering(prefix).count() > 0
+    });
+    measure("covered", &existing, |prefix| {
+        map.covered(prefix).next().is_some()
+    });
+}
@@ -0,0 +1,723 @@
+//! AS Provider Attestations.
+//!
+//! An AS provider attestation (ASPA) is a signed object through which the
+//! holder of an AS number, the customer AS, authorizes a set of other ASes
+//! as its upstream providers. The object is defined in
+//! draft-ietf-sidrops-aspa-profile.
+//!
+//! Since the draft is not yet finished, this module is only available if
+//! the `aspa` feature is enabled. It implements the most recent version of
+//! the profile which uses version 1 of the content and a plain sequence of
+//! provider AS numbers.
+//!
+//! This module defines the type [`Aspa`] for a decoded object, the type
+//! [`AsProviderAttestation`] for its content, and [`AspaBuilder`] for
+//! creating new objects.
+//!
+//! In addition, the module implements the AS path verification procedure
+//! of draft-ietf-sidrops-aspa-verification via the [`verify_as_path`]
+//! function using validated ASPA payload collected into an [`AspaSet`].
+//!
+//! [`Aspa`]: struct.Aspa.html
+//! [`AsProviderAttestation`]: struct.AsProviderAttestation.html
+//! [`AspaBuilder`]: struct.AspaBuilder.html
+//! [`AspaSet`]: struct.AspaSet.html
+//! [`verify_as_path`]: fn.verify_as_path.html
+
+use std::slice;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+use bcder::{decode, encode};
+use bcder::{Captured, ConstOid, Mode, Tag, xerr};
+use bcder::encode::{PrimitiveContent, Values};
+use bytes::Bytes;
+use crate::{oid, payload};
+use crate::cert::{Cert, ResourceCert};
+use crate::crypto::{Signer, SigningError};
+use crate::resources::{AsBlock, AsBlocks, AsId, AsResources};
+use crate::sigobj::{SignedContent, SignedObject, SignedObjectBuilder};
+use crate::x509::{Time, ValidationError};
+
+
+//------------ Aspa ----------------------------------------------------------
+
+/// A decoded AS provider attestation.
+///
+/// In order to get to the content of the object, you need to validate it
+/// via the `validate` method.
+#[derive(Clone, Debug)]
+pub struct Aspa {
+    signed: SignedObject,
+    content: AsProviderAttestation,
+}
+
+impl Aspa {
+    /// Decodes an AS provider attestation from a source.
+    pub fn decode<S: decode::Source>(
+        source: S,
+        strict: bool
+    ) -> Result<Self, S::Err> {
+        let (signed, content) = SignedObject::decode_with_content(
+            source, strict
+        )?;
+        Ok(Aspa { signed, content })
+    }
+
+    /// Validates the object.
+    ///
+    /// You need to pass in the certificate of the issuing CA. If validation
+    /// succeeds, the result will be the EE certificate of the object and
+    /// its content.
+    pub fn validate(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+    ) -> Result<(ResourceCert, AsProviderAttestation), ValidationError> {
+        self.validate_at(cert, strict, Time::now())
+    }
+
+    /// Validates the object at the given time.
+    ///
+    /// In addition to the validation of the signed object, this checks
+    /// that the EE certificate contains the customer AS as its only
+    /// resource.
+    pub fn validate_at(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+        now: Time
+    ) -> Result<(ResourceCert, AsProviderAttestation), ValidationError> {
+        let cert = self.signed.validate_at(cert, strict, now)?;
+        self.content.validate(&cert)?;
+        Ok((cert, self.content))
+    }
+
+    /// Returns a value encoder for a reference to the object.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        self.signed.encode_ref()
+    }
+
+    /// Returns a DER encoded Captured for this.
+    pub fn to_captured(&self) -> Captured {
+        self.encode_ref().to_captured(Mode::Der)
+    }
+
+    /// Returns a reference to the EE certificate of this object.
+    pub fn cert(&self) -> &Cert {
+        self.signed.cert()
+    }
+
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signed.signing_time()
+    }
+
+    /// Returns the binary signing time attribute if present.
+    ///
+    /// The value is the number of seconds since the Unix epoch as defined
+    /// in RFC 6019.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.signed.binary_signing_time()
+    }
+
+    /// Returns a reference to the content of the object.
+    pub fn content(&self) -> &AsProviderAttestation {
+        &self.content
+    }
+}
+
+
+//------------ AsProviderAttestation -----------------------------------------
+
+/// The content of an AS provider attestation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AsProviderAttestation {
+    /// The customer AS.
+    customer_as: AsId,
+
+    /// The provider ASes in ascending order.
+    provider_as_set: Vec<AsId>,
+}
+
+impl AsProviderAttestation {
+    /// Returns the customer AS.
+    pub fn customer_as(&self) -> AsId {
+        self.customer_as
+    }
+
+    /// Returns an iterator over the provider ASes.
+    ///
+    /// The ASes are returned in ascending order.
+    pub fn provider_as_set(&self) -> slice::Iter<'_, AsId> {
+        self.provider_as_set.iter()
+    }
+
+    /// Returns whether the given AS is an authorized provider.
+    pub fn has_provider(&self, provider: AsId) -> bool {
+        self.provider_as_set.binary_search(&provider).is_ok()
+    }
+
+    /// Takes the content from the beginning of an encoded value.
+    ///
+    /// Apart from decoding, this checks that the provider AS set is not
+    /// empty, ordered, free of duplicates, and doesn’t contain the customer
+    /// AS.
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(|cons| {
+            // version [0] EXPLICIT INTEGER, must be 1
+            cons.take_constructed_if(Tag::CTX_0, |c| c.skip_u8_if(1))?;
+            let customer_as = AsId::take_from(cons)?;
+            let provider_as_set = cons.take_sequence(|cons| {
+                let mut res: Vec<AsId> = Vec::new();
+                while let Some(id) = cons.take_opt_u32()? {
+                    let id = AsId::from(id);
+                    if id == customer_as {
+                        xerr!(return Err(decode::Malformed.into()))
+                    }
+                    if let Some(last) = res.last() {
+                        if *last >= id {
+                            xerr!(return Err(decode::Malformed.into()))
+                        }
+                    }
+                    res.push(id);
+                }
+                Ok(res)
+            })?;
+            if provider_as_set.is_empty() {
+                xerr!(return Err(decode::Malformed.into()))
+            }
+            Ok(AsProviderAttestation { customer_as, provider_as_set })
+        })
+    }
+
+    /// Checks the content against the EE certificate.
+    ///
+    /// The certificate must not contain IP resources and its only AS
+    /// resource must be the customer AS.
+    fn validate(&self, cert: &ResourceCert) -> Result<(), ValidationError> {
+        if cert.as_cert().v4_resources().is_some()
+            || cert.as_cert().v6_resources().is_some()
+        {
+            return Err(ValidationError)
+        }
+        let claimed = AsBlocks::from_resources(
+            cert.as_cert().as_resources()
+        )?;
+        let customer: AsBlocks = Some(AsBlock::from(self.customer_as))
+            .into_iter().collect();
+        if !claimed.contains(&customer) || !customer.contains(&claimed)
+            || !cert.as_resources().contains(&customer)
+        {
+            return Err(ValidationError)
+        }
+        Ok(())
+    }
+
+    /// Returns a value encoder for a reference to the content.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        encode::sequence((
+            encode::sequence_as(Tag::CTX_0, 1u8.encode()),
+            self.customer_as.encode(),
+            encode::sequence(
+                encode::slice(
+                    self.provider_as_set.as_slice(), |id| id.encode()
+                )
+            )
+        ))
+    }
+}
+
+
+//--- SignedContent
+
+impl SignedContent for AsProviderAttestation {
+    const CONTENT_TYPE: ConstOid = oid::CT_ASPA;
+
+    fn take_content_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from(cons)
+    }
+
+    fn to_content_bytes(&self) -> Bytes {
+        self.encode_ref().to_captured(Mode::Der).into_bytes()
+    }
+}
+
+
+//------------ AspaBuilder ---------------------------------------------------
+
+/// A builder for AS provider attestations.
+#[derive(Clone, Debug)]
+pub struct AspaBuilder {
+    /// The customer AS.
+    customer_as: AsId,
+
+    /// The provider ASes.
+    ///
+    /// The set is kept ordered and free of duplicates.
+    providers: Vec<AsId>,
+}
+
+impl AspaBuilder {
+    /// Creates a new builder for the given customer AS.
+    pub fn new(customer_as: AsId) -> Self {
+        AspaBuilder { customer_as, providers: Vec::new() }
+    }
+
+    /// Returns the customer AS.
+    pub fn customer_as(&self) -> AsId {
+        self.customer_as
+    }
+
+    /// Returns an iterator over the provider ASes added so far.
+    pub fn providers(&self) -> slice::Iter<'_, AsId> {
+        self.providers.iter()
+    }
+
+    /// Adds a provider AS.
+    ///
+    /// Returns `false` if the AS is the customer AS and thus can’t be added
+    /// or if it was already present.
+    pub fn add_provider(&mut self, provider: AsId) -> bool {
+        if provider == self.customer_as {
+            return false
+        }
+        match self.providers.binary_search(&provider) {
+            Ok(_) => false,
+            Err(pos) => {
+                self.providers.insert(pos, provider);
+                true
+            }
+        }
+    }
+
+    /// Removes a provider AS.
+    ///
+    /// Returns whether the AS was present.
+    pub fn remove_provider(&mut self, provider: AsId) -> bool {
+        match self.providers.binary_search(&provider) {
+            Ok(pos) => {
+                self.providers.remove(pos);
+                true
+            }
+            Err(_) => false
+        }
+    }
+
+    /// Returns the content for the current state of the builder.
+    pub fn to_attestation(&self) -> AsProviderAttestation {
+        AsProviderAttestation {
+            customer_as: self.customer_as,
+            provider_as_set: self.providers.clone(),
+        }
+    }
+
+    /// Finalizes the builder into an AS provider attestation.
+    ///
+    /// The resources of the EE certificate are set to the customer AS.
+    ///
+    /// # Panic
+    ///
+    /// This method will panic if no provider AS has been added as that is
+    /// not allowed and would lead to a malformed object.
+    pub fn finalize<S: Signer>(
+        self,
+        mut sigobj: SignedObjectBuilder,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<Aspa, SigningError<S::Error>> {
+        assert!(!self.providers.is_empty());
+        let content = self.to_attestation();
+        sigobj.set_v4_resources(None);
+        sigobj.set_v6_resources(None);
+        sigobj.set_as_resources(Some(AsResources::blocks(
+            Some(AsBlock::from(self.customer_as)).into_iter().collect()
+        )));
+        let signed = sigobj.finalize_content(&content, signer, issuer_key)?;
+        Ok(Aspa { signed, content })
+    }
+}
+
+
+//------------ AspaSet -------------------------------------------------------
+
+/// A set of ASPA payload prepared for AS path verification.
+///
+/// The set keeps the provider ASes for each customer AS. If there are
+/// multiple ASPAs for the same customer AS, their providers are merged.
+#[derive(Clone, Debug, Default)]
+pub struct AspaSet {
+    /// The provider ASes of each customer AS in ascending order.
+    providers: HashMap<AsId, Vec<AsId>>,
+}
+
+impl AspaSet {
+    /// Creates a new, empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of customer ASes in the set.
+    pub fn len(&self) -> usize {
+        self.providers.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.providers.is_empty()
+    }
+
+    /// Adds providers for a customer AS.
+    pub fn insert<I: IntoIterator<Item = AsId>>(
+        &mut self, customer: AsId, providers: I
+    ) {
+        let item = self.providers.entry(customer).or_default();
+        item.extend(providers);
+        item.sort();
+        item.dedup();
+    }
+
+    /// Returns the provider ASes of a customer AS if it has an ASPA.
+    pub fn providers(&self, customer: AsId) -> Option<&[AsId]> {
+        self.providers.get(&customer).map(Vec::as_slice)
+    }
+
+    /// Determines the relationship between two neighbouring ASes.
+    fn hop(&self, customer: AsId, provider: AsId) -> Hop {
+        match self.providers.get(&customer) {
+            Some(providers) => {
+                if providers.binary_search(&provider).is_ok() {
+                    Hop::ProviderPlus
+                }
+                else {
+                    Hop::NotProviderPlus
+                }
+            }
+            None => Hop::NoAttestation
+        }
+    }
+}
+
+
+//--- FromIterator and Extend
+
+impl FromIterator<payload::Aspa> for AspaSet {
+    fn from_iter<I: IntoIterator<Item = payload::Aspa>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl Extend<payload::Aspa> for AspaSet {
+    fn extend<I: IntoIterator<Item = payload::Aspa>>(&mut self, iter: I) {
+        for aspa in iter {
+            self.insert(aspa.customer(), aspa.providers().iter().copied())
+        }
+    }
+}
+
+
+//------------ Hop -----------------------------------------------------------
+
+/// The authorization status of a single hop in an AS path.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Hop {
+    /// The customer AS has no ASPA.
+    NoAttestation,
+
+    /// The customer AS attests the other AS as a provider.
+    ProviderPlus,
+
+    /// The customer AS has an ASPA that doesn’t include the other AS.
+    NotProviderPlus,
+}
+
+
+//------------ Direction -----------------------------------------------------
+
+/// The direction in which a route was received.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// The route was received from a customer or a lateral peer.
+    ///
+    /// This is also used for routes received by a route server from its
+    /// clients.
+    Upstream,
+
+    /// The route was received from a provider.
+    Downstream,
+}
+
+
+//------------ AspaState -----------------------------------------------------
+
+/// The result of AS path verification.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum AspaState {
+    /// The AS path is consistent with the ASPAs of all its ASes.
+    Valid,
+
+    /// The AS path contradicts at least one ASPA.
+    Invalid,
+
+    /// Not enough ASPAs are available to come to a conclusion.
+    Unknown,
+}
+
+
+//------------ verify_as_path ------------------------------------------------
+
+/// Verifies an AS path using ASPA payload.
+///
+/// The AS path is given as a slice of AS numbers in the order they appear
+/// in the AS_PATH attribute, i.e., starting with the neighbour AS and ending
+/// with the origin AS. Prepended ASes are allowed. If the AS_PATH contains
+/// an AS_SET, the path is invalid and this function should not be called.
+/// An empty path is invalid, too.
+///
+/// The function implements the upstream and downstream path verification
+/// procedures of draft-ietf-sidrops-aspa-verification. It does not check
+/// that the first AS in the path is actually the neighbour AS the route
+/// was received from.
+pub fn verify_as_path(
+    path: &[AsId], direction: Direction, aspas: &AspaSet
+) -> AspaState {
+    // The draft numbers the ASes starting at the origin and with
+    // prepends removed. So do we, only starting at 0.
+    let mut hops: Vec<_> = path.iter().rev().copied().collect();
+    hops.dedup();
+    if hops.is_empty() {
+        return AspaState::Invalid
+    }
+    match direction {
+        Direction::Upstream => verify_upstream(&hops, aspas),
+        Direction::Downstream => verify_downstream(&hops, aspas),
+    }
+}
+
+/// Verifies a deduplicated path received from a customer or peer.
+fn verify_upstream(hops: &[AsId], aspas: &AspaSet) -> AspaState {
+    let mut res = AspaState::Valid;
+    for pair in hops.windows(2) {
+        match aspas.hop(pair[0], pair[1]) {
+            Hop::NotProviderPlus => return AspaState::Invalid,
+            Hop::NoAttestation => res = AspaState::Unknown,
+            Hop::ProviderPlus => { }
+        }
+    }
+    res
+}
+
+/// Verifies a deduplicated path received from a provider.
+fn verify_downstream(hops: &[AsId], aspas: &AspaSet) -> AspaState {
+    let len = hops.len();
+    if len <= 2 {
+        return AspaState::Valid
+    }
+
+    // The first AS where the up-ramp is broken and the last AS where the
+    // down-ramp is broken. If the former is at or before the latter, there
+    // is a valley in the path.
+    let up_break = (1..len).find(|&i| {
+        aspas.hop(hops[i - 1], hops[i]) == Hop::NotProviderPlus
+    }).unwrap_or(len);
+    let down_break = (0..len - 1).rev().find(|&i| {
+        aspas.hop(hops[i + 1], hops[i]) == Hop::NotProviderPlus
+    });
+    if let Some(down_break) = down_break {
+        if up_break <= down_break {
+            return AspaState::Invalid
+        }
+    }
+
+    // The last AS of the attested up-ramp and the first AS of the attested
+    // down-ramp. If they meet or are neighbours, the path is valid.
+    let mut up_ramp = 0;
+    while up_ramp + 1 < len
+        && aspas.hop(hops[up_ramp], hops[up_ramp + 1]) == Hop::ProviderPlus
+    {
+        up_ramp += 1;
+    }
+    let mut down_ramp = len - 1;
+    while down_ramp > 0
+        && aspas.hop(hops[down_ramp], hops[down_ramp - 1])
+            == Hop::ProviderPlus
+    {
+        down_ramp -= 1;
+    }
+    if down_ramp <= up_ramp + 1 {
+        AspaState::Valid
+    }
+    else {
+        AspaState::Unknown
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn decode(data: &[u8]) -> Result<AsProviderAttestation, decode::Error> {
+        Mode::Der.decode(data, AsProviderAttestation::take_from)
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut builder = AspaBuilder::new(64496.into());
+        assert!(builder.add_provider(64511.into()));
+        assert!(builder.add_provider(64500.into()));
+        assert!(!builder.add_provider(64500.into()));
+        assert!(!builder.add_provider(64496.into()));
+        let content = builder.to_attestation();
+        let decoded = decode(content.to_content_bytes().as_ref()).unwrap();
+        assert_eq!(decoded, content);
+        assert_eq!(decoded.customer_as(), 64496.into());
+        assert_eq!(
+            decoded.provider_as_set().cloned().collect::<Vec<_>>(),
+            [AsId::from(64500), AsId::from(64511)]
+        );
+        assert!(decoded.has_provider(64511.into()));
+        assert!(!decoded.has_provider(64496.into()));
+    }
+
+    #[test]
+    fn reject_bad_content() {
+        fn encode(version: u8, customer: u32, providers: &[u32]) -> Bytes {
+            encode::sequence((
+                encode::sequence_as(Tag::CTX_0, version.encode()),
+                customer.encode(),
+                encode::sequence(
+                    encode::slice(providers, |id: &u32| (*id).encode())
+                )
+            )).to_captured(Mode::Der).into_bytes()
+        }
+
+        assert!(decode(&encode(1, 10, &[11, 12])).is_ok());
+        assert!(decode(&encode(0, 10, &[11, 12])).is_err());
+        assert!(decode(&encode(1, 10, &[])).is_err());
+        assert!(decode(&encode(1, 10, &[12, 11])).is_err());
+        assert!(decode(&encode(1, 10, &[11, 11])).is_err());
+        assert!(decode(&encode(1, 10, &[10, 11])).is_err());
+    }
+
+    fn aspas(items: &[(u32, &[u32])]) -> AspaSet {
+        items.iter().map(|&(customer, providers)| {
+            payload::Aspa::new(
+                customer.into(),
+                providers.iter().map(|&id| id.into()).collect()
+            )
+        }).collect()
+    }
+
+    fn verify(path: &[u32], direction: Direction, set: &AspaSet) -> AspaState {
+        let path: Vec<AsId> = path.iter().map(|&id| id.into()).collect();
+        verify_as_path(&path, direction, set)
+    }
+
+    #[test]
+    fn verify_upstream() {
+        use self::AspaState::*;
+        use self::Direction::Upstream;
+
+        let set = aspas(&[(1, &[2]), (2, &[3])]);
+        assert_eq!(verify(&[3, 2, 1], Upstream, &set), Valid);
+        assert_eq!(verify(&[3, 3, 2, 1, 1, 1], Upstream, &set), Valid);
+        assert_eq!(verify(&[1], Upstream, &set), Valid);
+        assert_eq!(verify(&[4, 3, 2, 1], Upstream, &set), Unknown);
+        assert_eq!(verify(&[3, 5, 1], Upstream, &set), Invalid);
+        assert_eq!(verify(&[], Upstream, &set), Invalid);
+
+        let set = aspas(&[(1, &[0])]);
+        assert_eq!(verify(&[2, 1], Upstream, &set), Invalid);
+
+        let mut set = aspas(&[(1, &[2])]);
+        set.insert(1.into(), Some(5.into()));
+        assert_eq!(set.len(), 1);
+        assert_eq!(verify(&[5, 1], Upstream, &set), Valid);
+        assert_eq!(verify(&[2, 1], Upstream, &set), Valid);
+    }
+
+    #[test]
+    fn verify_downstream() {
+        use self::AspaState::*;
+        use self::Direction::Downstream;
+
+        // 1 -> 2 -> 3 up, then down to 4.
+        let set = aspas(&[(1, &[2]), (2, &[3]), (4, &[3])]);
+        assert_eq!(verify(&[4, 3, 2, 1], Downstream, &set), Valid);
+        assert_eq!(verify(&[3, 2, 1], Downstream, &set), Valid);
+        assert_eq!(verify(&[5, 4, 3, 2, 1], Downstream, &set), Unknown);
+
+        // A lateral peering between 2 and 3 at the top is fine.
+        let set = aspas(&[(1, &[2]), (2, &[9]), (3, &[9]), (4, &[3])]);
+        assert_eq!(verify(&[4, 3, 2, 1], Downstream, &set), Valid);
+
+        // But a valley is not.
+        let set = aspas(&[(1, &[2]), (2, &[9]), (4, &[9])]);
+        assert_eq!(verify(&[4, 3, 2, 1], Downstream, &set), Invalid);
+
+        // Without attestations, we don’t know.
+        let set = aspas(&[(2, &[3])]);
+        assert_eq!(verify(&[4, 3, 2, 1], Downstream, &set), Unknown);
+        assert_eq!(verify(&[2, 1], Downstream, &set), Valid);
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use crate::cert::{KeyUsage, Overclaim, TbsCert};
+    use crate::crypto::{PublicKeyFormat, Signer};
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::resources::Prefix;
+    use crate::uri;
+    use crate::tal::TalInfo;
+    use crate::x509::Validity;
+    use super::*;
+
+    #[test]
+    fn encode_aspa() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+
+        let mut cert = TbsCert::new(
+            12u64.into(), pubkey.to_subject_name(),
+            Validity::from_secs(86400), None, pubkey, KeyUsage::Ca,
+            Overclaim::Trim
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(uri.clone()));
+        cert.set_rpki_manifest(Some(uri.clone()));
+        cert.build_v4_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_v6_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_as_resource_blocks(|b| b.push((AsId::MIN, AsId::MAX)));
+        let cert = cert.into_cert(&signer, &key).unwrap();
+
+        let mut builder = AspaBuilder::new(64496.into());
+        builder.add_provider(64497.into());
+        let aspa = builder.finalize(
+            SignedObjectBuilder::new(
+                12u64.into(), Validity::from_secs(86400), uri.clone(),
+                uri.clone(), uri.clone()
+            ),
+            &signer, &key
+        ).unwrap();
+        let aspa = aspa.to_captured();
+
+        let aspa = Aspa::decode(aspa.as_slice(), true).unwrap();
+        let cert = cert.validate_ta(
+            TalInfo::from_name("foo".into()).into_arc(), true
+        ).unwrap();
+        let (_, content) = aspa.validate(&cert, true).unwrap();
+        assert_eq!(content.customer_as(), 64496.into());
+    }
+}
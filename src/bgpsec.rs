@@ -0,0 +1,594 @@
+//! BGPsec path signatures.
+//!
+//! BGPsec, defined in [RFC 8205], protects the AS path of BGP updates by
+//! having every AS along the path sign the path so far together with the
+//! AS it sends the update to. The signatures are made with the router keys
+//! certified by BGPsec router certificates which are handled by the
+//! [`cert::router`] module. The only algorithm suite currently defined by
+//! [RFC 8208] uses ECDSA with the P-256 curve and SHA-256.
+//!
+//! This module provides the [`BgpsecPath`] type that represents the value
+//! of the BGPsec_PATH attribute, i.e., a sequence of secure path segments
+//! and their signatures. It can be extended by signing it with a router
+//! key via any [`Signer`] and be verified against a set of [`RouterKey`]s
+//! as produced by validation. Only what is necessary for creating and
+//! checking signatures is implemented, all other aspects of BGPsec are
+//! left to the BGP implementation.
+//!
+//! [`BgpsecPath`]: struct.BgpsecPath.html
+//! [`cert::router`]: ../cert/router/index.html
+//! [`RouterKey`]: ../cert/router/struct.RouterKey.html
+//! [`Signer`]: ../crypto/signer/trait.Signer.html
+//! [RFC 8205]: https://tools.ietf.org/html/rfc8205
+//! [RFC 8208]: https://tools.ietf.org/html/rfc8208
+
+use std::{error, fmt};
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use bytes::Bytes;
+use crate::cert::RouterKey;
+use crate::crypto::{
+    KeyIdentifier, PublicKey, Signature, SignatureAlgorithm, Signer,
+    SigningError
+};
+use crate::payload::Prefix;
+use crate::resources::AsId;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The algorithm suite identifier for ECDSA P-256 with SHA-256.
+///
+/// This is the only suite defined in [RFC 8208].
+///
+/// [RFC 8208]: https://tools.ietf.org/html/rfc8208
+pub const ALGORITHM_SUITE: u8 = 1;
+
+/// The SAFI for unicast routes, the only SAFI BGPsec is defined for.
+const SAFI_UNICAST: u8 = 1;
+
+/// The length of a subject key identifier.
+const SKI_LEN: usize = 20;
+
+
+//------------ BgpsecPath ----------------------------------------------------
+
+/// The content of a BGPsec_PATH attribute.
+///
+/// The path consists of pairs of secure path segments and signature
+/// segments. As on the wire, the most recently added pair comes first,
+/// i.e., the first segment belongs to the AS that sent the update and the
+/// last segment to the origin AS.
+///
+/// Only a single signature block for the algorithm suite defined by
+/// [`ALGORITHM_SUITE`] is supported. When parsing an attribute with
+/// additional blocks for other suites, these are ignored.
+///
+/// [`ALGORITHM_SUITE`]: constant.ALGORITHM_SUITE.html
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct BgpsecPath {
+    /// The secure path and signature segments, most recent first.
+    segments: Vec<(SecurePathSegment, SignatureSegment)>,
+}
+
+impl BgpsecPath {
+    /// Creates a new, empty path to be signed by the origin AS.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of segments in the path.
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    /// Returns whether the path is empty.
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    /// Returns an iterator over the segments, most recent first.
+    pub fn iter(
+        &self
+    ) -> impl Iterator<Item = (&SecurePathSegment, &SignatureSegment)> {
+        self.segments.iter().map(|(path, sig)| (path, sig))
+    }
+
+    /// Adds a new segment by signing the path.
+    ///
+    /// The new secure path segment `segment` is signed together with the
+    /// existing path, the AS the update will be sent to, and the prefix of
+    /// the route using the router key `key` held by `signer`. The key must
+    /// be an ECDSA P-256 key.
+    pub fn sign<S: Signer>(
+        &mut self,
+        segment: SecurePathSegment,
+        target: AsId,
+        prefix: Prefix,
+        signer: &S,
+        key: &S::KeyId,
+    ) -> Result<(), SigningError<S::Error>> {
+        let key_identifier = signer.get_key_info(key)?.key_identifier();
+        let data = signed_data(target, &segment, &self.segments, prefix);
+        let signature = signer.sign(
+            key, SignatureAlgorithm::EcdsaP256Sha256, &data
+        )?.value().clone();
+        self.segments.insert(0, (
+            segment, SignatureSegment { key_identifier, signature }
+        ));
+        Ok(())
+    }
+
+    /// Verifies all signatures of the path.
+    ///
+    /// The path is verified as received by the AS `target` for a route
+    /// with the given prefix. The keys of the signing ASes are taken from
+    /// `keys`. For each segment, all keys with the segment’s AS number and
+    /// key identifier are tried.
+    ///
+    /// If verification fails, the error contains the index of the first
+    /// segment, counting from the most recent one, that failed.
+    pub fn verify(
+        &self, target: AsId, prefix: Prefix, keys: &[RouterKey]
+    ) -> Result<(), VerifyError> {
+        if self.segments.is_empty() {
+            return Err(VerifyError::Empty)
+        }
+        let mut target = target;
+        for (index, (path, sig)) in self.segments.iter().enumerate() {
+            let data = signed_data(
+                target, path, &self.segments[index + 1..], prefix
+            );
+            let signature = Signature::new(
+                SignatureAlgorithm::EcdsaP256Sha256, sig.signature.clone()
+            );
+            let verified = keys.iter().filter(|key| {
+                key.asn() == path.asn
+                    && key.key_identifier() == sig.key_identifier
+            }).any(|key| {
+                PublicKey::decode(key.key_info().as_ref()).map(|pubkey| {
+                    pubkey.verify(&data, &signature).is_ok()
+                }).unwrap_or(false)
+            });
+            if !verified {
+                return Err(VerifyError::BadSignature(index))
+            }
+            target = path.asn;
+        }
+        Ok(())
+    }
+
+    /// Parses the value of a BGPsec_PATH attribute.
+    pub fn parse(data: &[u8]) -> Result<Self, ParseError> {
+        let (mut path_data, mut data) = take_block(data)?;
+        let mut path = Vec::new();
+        while !path_data.is_empty() {
+            path.push(SecurePathSegment::parse(&mut path_data)?);
+        }
+        if path.is_empty() {
+            return Err(ParseError::Malformed)
+        }
+
+        let mut sigs = None;
+        while !data.is_empty() {
+            let (block, tail) = take_block(data)?;
+            data = tail;
+            let (&suite, mut block) = block.split_first().ok_or(
+                ParseError::Malformed
+            )?;
+            if suite != ALGORITHM_SUITE {
+                continue
+            }
+            if sigs.is_some() {
+                return Err(ParseError::Malformed)
+            }
+            let mut res = Vec::with_capacity(path.len());
+            while !block.is_empty() {
+                res.push(SignatureSegment::parse(&mut block)?);
+            }
+            sigs = Some(res);
+        }
+        let sigs = sigs.ok_or(ParseError::UnsupportedAlgorithm)?;
+        if sigs.len() != path.len() {
+            return Err(ParseError::Malformed)
+        }
+        Ok(BgpsecPath { segments: path.into_iter().zip(sigs).collect() })
+    }
+
+    /// Returns the value of a BGPsec_PATH attribute for the path.
+    pub fn to_bytes(&self) -> Bytes {
+        let mut res = Vec::new();
+        let path_len = 2 + 6 * self.segments.len();
+        res.extend_from_slice(&(path_len as u16).to_be_bytes());
+        for (path, _) in &self.segments {
+            path.compose(&mut res);
+        }
+        let start = res.len();
+        res.extend_from_slice(&[0, 0, ALGORITHM_SUITE]);
+        for (_, sig) in &self.segments {
+            sig.compose(&mut res);
+        }
+        let block_len = ((res.len() - start) as u16).to_be_bytes();
+        res[start..start + 2].copy_from_slice(&block_len);
+        res.into()
+    }
+}
+
+
+//------------ SecurePathSegment ---------------------------------------------
+
+/// A secure path segment of a BGPsec path.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SecurePathSegment {
+    /// The number of times the AS appears in the equivalent AS path.
+    pcount: u8,
+
+    /// The flags of the segment.
+    flags: u8,
+
+    /// The AS number.
+    asn: AsId,
+}
+
+impl SecurePathSegment {
+    /// The flag marking a confederation segment.
+    const CONFED_SEGMENT: u8 = 0x80;
+
+    /// Creates a new segment for an AS.
+    ///
+    /// The `pcount` is the number of times the AS is to appear in the AS
+    /// path. It is normally 1 and larger when prepending. It can be 0 for
+    /// transparent route servers.
+    pub fn new(asn: AsId, pcount: u8) -> Self {
+        SecurePathSegment { pcount, flags: 0, asn }
+    }
+
+    /// Returns the AS number.
+    pub fn asn(&self) -> AsId {
+        self.asn
+    }
+
+    /// Returns the number of times the AS appears in the AS path.
+    pub fn pcount(&self) -> u8 {
+        self.pcount
+    }
+
+    /// Returns the raw flags.
+    pub fn flags(&self) -> u8 {
+        self.flags
+    }
+
+    /// Returns whether the segment was added inside a confederation.
+    pub fn is_confed_segment(&self) -> bool {
+        self.flags & Self::CONFED_SEGMENT != 0
+    }
+
+    /// Sets whether the segment was added inside a confederation.
+    pub fn set_confed_segment(&mut self, confed: bool) {
+        if confed {
+            self.flags |= Self::CONFED_SEGMENT
+        }
+        else {
+            self.flags &= !Self::CONFED_SEGMENT
+        }
+    }
+
+    /// Takes a segment from the beginning of `data`.
+    fn parse(data: &mut &[u8]) -> Result<Self, ParseError> {
+        if data.len() < 6 {
+            return Err(ParseError::Malformed)
+        }
+        let res = SecurePathSegment {
+            pcount: data[0],
+            flags: data[1],
+            asn: u32::from_be_bytes(
+                [data[2], data[3], data[4], data[5]]
+            ).into(),
+        };
+        *data = &data[6..];
+        Ok(res)
+    }
+
+    /// Appends the encoded segment to `target`.
+    fn compose(&self, target: &mut Vec<u8>) {
+        target.push(self.pcount);
+        target.push(self.flags);
+        target.extend_from_slice(&u32::from(self.asn).to_be_bytes());
+    }
+}
+
+
+//------------ SignatureSegment ----------------------------------------------
+
+/// A signature segment of a BGPsec path.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignatureSegment {
+    /// The subject key identifier of the router key used for signing.
+    key_identifier: KeyIdentifier,
+
+    /// The DER encoded ECDSA signature.
+    signature: Bytes,
+}
+
+impl SignatureSegment {
+    /// Returns the key identifier of the router key used for signing.
+    pub fn key_identifier(&self) -> KeyIdentifier {
+        self.key_identifier
+    }
+
+    /// Returns the signature.
+    pub fn signature(&self) -> &Bytes {
+        &self.signature
+    }
+
+    /// Takes a segment from the beginning of `data`.
+    fn parse(data: &mut &[u8]) -> Result<Self, ParseError> {
+        if data.len() < SKI_LEN + 2 {
+            return Err(ParseError::Malformed)
+        }
+        let key_identifier = KeyIdentifier::try_from(&data[..SKI_LEN])
+            .map_err(|_| ParseError::Malformed)?;
+        let len = usize::from(
+            u16::from_be_bytes([data[SKI_LEN], data[SKI_LEN + 1]])
+        );
+        let data_len = SKI_LEN + 2 + len;
+        if len == 0 || data.len() < data_len {
+            return Err(ParseError::Malformed)
+        }
+        let signature = Bytes::copy_from_slice(&data[SKI_LEN + 2..data_len]);
+        *data = &data[data_len..];
+        Ok(SignatureSegment { key_identifier, signature })
+    }
+
+    /// Appends the encoded segment to `target`.
+    fn compose(&self, target: &mut Vec<u8>) {
+        target.extend_from_slice(self.key_identifier.as_slice());
+        target.extend_from_slice(
+            &(self.signature.len() as u16).to_be_bytes()
+        );
+        target.extend_from_slice(self.signature.as_ref());
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the data to be signed for a new segment.
+///
+/// The new segment `segment` is added to the front of `segments` which
+/// contains the existing segments, most recent first. The data is
+/// assembled as described in section 4.2 of RFC 8205: the target AS, then
+/// alternately the signature segments and secure path segments with the
+/// signature segment of each AS preceding the secure path segment of the
+/// next AS, the secure path segment of the origin AS, and finally the
+/// algorithm suite, AFI, SAFI, and NLRI.
+fn signed_data(
+    target: AsId,
+    segment: &SecurePathSegment,
+    segments: &[(SecurePathSegment, SignatureSegment)],
+    prefix: Prefix,
+) -> Vec<u8> {
+    let mut res = Vec::new();
+    res.extend_from_slice(&u32::from(target).to_be_bytes());
+    let mut path = segment;
+    for (next_path, sig) in segments {
+        sig.compose(&mut res);
+        path.compose(&mut res);
+        path = next_path;
+    }
+    path.compose(&mut res);
+    res.push(ALGORITHM_SUITE);
+    let (afi, octets) = match prefix.addr() {
+        IpAddr::V4(addr) => (1u16, addr.octets().to_vec()),
+        IpAddr::V6(addr) => (2u16, addr.octets().to_vec()),
+    };
+    res.extend_from_slice(&afi.to_be_bytes());
+    res.push(SAFI_UNICAST);
+    let len = prefix.prefix_len();
+    res.push(len);
+    let octets_len = usize::from(len / 8) + usize::from(len & 7 != 0);
+    res.extend_from_slice(&octets[..octets_len]);
+    res
+}
+
+/// Takes a block with a two octet length including itself from `data`.
+///
+/// Returns the content of the block and the remaining data.
+fn take_block(data: &[u8]) -> Result<(&[u8], &[u8]), ParseError> {
+    if data.len() < 2 {
+        return Err(ParseError::Malformed)
+    }
+    let len = usize::from(u16::from_be_bytes([data[0], data[1]]));
+    if len < 2 || data.len() < len {
+        return Err(ParseError::Malformed)
+    }
+    Ok((&data[2..len], &data[len..]))
+}
+
+
+//------------ ParseError ----------------------------------------------------
+
+/// An error happened while parsing a BGPsec_PATH attribute.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The attribute was not correctly encoded.
+    Malformed,
+
+    /// There was no signature block for a supported algorithm suite.
+    UnsupportedAlgorithm,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseError::Malformed => "malformed BGPsec_PATH attribute",
+            ParseError::UnsupportedAlgorithm => {
+                "no supported algorithm suite in BGPsec_PATH attribute"
+            }
+        })
+    }
+}
+
+impl error::Error for ParseError { }
+
+
+//------------ VerifyError ---------------------------------------------------
+
+/// Verifying a BGPsec path failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VerifyError {
+    /// The path was empty.
+    Empty,
+
+    /// The signature of the segment with the given index was invalid.
+    ///
+    /// This includes the case where no key was available for the segment.
+    /// Segments are counted from the most recent one.
+    BadSignature(usize),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::Empty => f.write_str("empty BGPsec path"),
+            VerifyError::BadSignature(index) => {
+                write!(f, "invalid signature in BGPsec path segment {}", index)
+            }
+        }
+    }
+}
+
+impl error::Error for VerifyError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    fn segment(asn: u32, ski: u8, sig: &'static [u8]) -> (
+        SecurePathSegment, SignatureSegment
+    ) {
+        (
+            SecurePathSegment::new(asn.into(), 1),
+            SignatureSegment {
+                key_identifier: KeyIdentifier::try_from(
+                    [ski; 20].as_ref()
+                ).unwrap(),
+                signature: Bytes::from_static(sig),
+            }
+        )
+    }
+
+    #[test]
+    fn signed_data_layout() {
+        let prefix = Prefix::from_str("192.0.2.0/23").unwrap();
+        let segments = [
+            segment(65002, 2, b"\x02"), segment(65001, 1, b"\x01")
+        ];
+        let data = signed_data(
+            65004.into(), &SecurePathSegment::new(65003.into(), 2),
+            &segments, prefix
+        );
+        let mut expected = vec![0, 0, 0xfd, 0xec];
+        expected.extend_from_slice(&[2; 20]);
+        expected.extend_from_slice(b"\0\x01\x02");
+        expected.extend_from_slice(b"\x02\0\0\0\xfd\xeb");
+        expected.extend_from_slice(&[1; 20]);
+        expected.extend_from_slice(b"\0\x01\x01");
+        expected.extend_from_slice(b"\x01\0\0\0\xfd\xea");
+        expected.extend_from_slice(b"\x01\0\0\0\xfd\xe9");
+        expected.extend_from_slice(b"\x01\0\x01\x01\x17\xc0\0\x02");
+        assert_eq!(data, expected);
+    }
+
+    #[test]
+    fn encode_parse() {
+        let mut path = BgpsecPath {
+            segments: vec![
+                segment(65002, 2, b"\x30\x02"), segment(65001, 1, b"\x30")
+            ]
+        };
+        path.segments[0].0.set_confed_segment(true);
+        let data = path.to_bytes();
+        assert_eq!(
+            &data[..14], b"\0\x0e\x01\x80\0\0\xfd\xea\x01\0\0\0\xfd\xe9"
+        );
+        assert_eq!(BgpsecPath::parse(&data), Ok(path.clone()));
+        assert!(path.iter().next().unwrap().0.is_confed_segment());
+
+        // Additional block for an unknown suite is ignored.
+        let mut extra = data.to_vec();
+        extra.extend_from_slice(b"\0\x03\x02");
+        assert_eq!(BgpsecPath::parse(&extra), Ok(path));
+
+        assert_eq!(
+            BgpsecPath::parse(&data[..data.len() - 1]),
+            Err(ParseError::Malformed)
+        );
+        assert_eq!(
+            BgpsecPath::parse(&data[..14]),
+            Err(ParseError::UnsupportedAlgorithm)
+        );
+        assert_eq!(BgpsecPath::parse(b"\0\x02"), Err(ParseError::Malformed));
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use crate::crypto::PublicKeyFormat;
+    use crate::crypto::softsigner::OpenSslSigner;
+    use super::*;
+
+    #[test]
+    fn sign_and_verify() {
+        let mut signer = OpenSslSigner::new();
+        let prefix = Prefix::from_str("2001:db8::/32").unwrap();
+        let mut keys = Vec::new();
+        let mut key_ids = Vec::new();
+        for asn in 65001..65003 {
+            let key = signer.create_key(PublicKeyFormat::EcdsaP256).unwrap();
+            let pubkey = signer.get_key_info(&key).unwrap();
+            keys.push(RouterKey::new(
+                asn.into(), pubkey.key_identifier(), pubkey.to_info_bytes()
+            ));
+            key_ids.push(key);
+        }
+
+        let mut path = BgpsecPath::new();
+        path.sign(
+            SecurePathSegment::new(65001.into(), 1), 65002.into(), prefix,
+            &signer, &key_ids[0]
+        ).unwrap();
+        path.sign(
+            SecurePathSegment::new(65002.into(), 1), 65003.into(), prefix,
+            &signer, &key_ids[1]
+        ).unwrap();
+        let path = BgpsecPath::parse(&path.to_bytes()).unwrap();
+        assert_eq!(path.len(), 2);
+        assert_eq!(path.verify(65003.into(), prefix, &keys), Ok(()));
+
+        assert_eq!(
+            path.verify(65004.into(), prefix, &keys),
+            Err(VerifyError::BadSignature(0))
+        );
+        assert_eq!(
+            path.verify(
+                65003.into(), Prefix::from_str("2001:db8::/33").unwrap(),
+                &keys
+            ),
+            Err(VerifyError::BadSignature(0))
+        );
+        assert_eq!(
+            path.verify(65003.into(), prefix, &keys[1..]),
+            Err(VerifyError::BadSignature(1))
+        );
+        assert_eq!(
+            BgpsecPath::new().verify(65003.into(), prefix, &keys),
+            Err(VerifyError::Empty)
+        );
+    }
+}
@@ -1,7 +1,9 @@
 extern crate rpki;
 
 use std::{env, fs};
+use bytes::Bytes;
 use rpki::cert::Cert;
+use rpki::x509::DecodeMode;
 
 
 fn main() {
@@ -19,7 +21,9 @@ fn main() {
             return;
         }
     };
-    let _cert = match Cert::decode(data.as_ref()) {
+    let _cert = match Cert::decode_located(
+        Bytes::from(data), DecodeMode::default()
+    ) {
         Ok(cert) => cert,
         Err(err) => {
             println!("Can’t decode cert: {}", err);
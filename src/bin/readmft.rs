@@ -1,7 +1,9 @@
 extern crate rpki;
 
 use std::{env, fs};
+use bytes::Bytes;
 use rpki::manifest::Manifest;
+use rpki::x509::DecodeMode;
 
 
 fn main() {
@@ -19,7 +21,9 @@ fn main() {
             return;
         }
     };
-    let _cert = match Manifest::decode(data.as_ref(), false) {
+    let _cert = match Manifest::decode_located(
+        Bytes::from(data), DecodeMode::Lenient
+    ) {
         Ok(cert) => cert,
         Err(err) => {
             println!("Can’t decode manifest: {}", err);
@@ -1,7 +1,9 @@
 extern crate rpki;
 
 use std::{env, fs};
+use bytes::Bytes;
 use rpki::roa::Roa;
+use rpki::x509::DecodeMode;
 
 
 fn main() {
@@ -20,7 +22,9 @@ fn main() {
         }
     };
 
-    let _cert = match Roa::decode(data.as_ref(), true) {
+    let _cert = match Roa::decode_located(
+        Bytes::from(data), DecodeMode::Strict
+    ) {
         Ok(cert) => cert,
         Err(err) => {
             println!("Can’t decode roa: {}", err);
@@ -0,0 +1,913 @@
+//! Keeping track of the children of a CA.
+//!
+//! A CA issues certificates to its children via the provisioning protocol
+//! defined in [RFC 6492]. For each child, it needs to remember the
+//! child’s identity certificate used to authenticate its requests, the
+//! resources the child is entitled to, and the certificates issued to it.
+//! This module provides [`Children`] for this bookkeeping.
+//!
+//! Requests received from a child are turned into a [`ChildRequest`] and
+//! processed into a [`ChildDecision`] that tells the CA what to do. The
+//! actual signing and revoking as well as the encoding of responses are
+//! left to the CA. Once a certificate has been issued or revoked, the
+//! CA records this via [`Child::add_issued`] or
+//! [`Child::remove_issued`], respectively.
+//!
+//! [RFC 6492]: https://tools.ietf.org/html/rfc6492
+//! [`Children`]: struct.Children.html
+//! [`ChildRequest`]: enum.ChildRequest.html
+//! [`ChildDecision`]: enum.ChildDecision.html
+//! [`Child::add_issued`]: struct.Child.html#method.add_issued
+//! [`Child::remove_issued`]: struct.Child.html#method.remove_issued
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::str::FromStr;
+use bytes::Bytes;
+use crate::uri;
+use crate::cert::{Cert, KeyUsage};
+use crate::crypto::{KeyIdentifier, PublicKey};
+use crate::csr::Csr;
+use crate::resources::{AsBlocks, IpBlocks};
+use crate::x509::{Serial, Time};
+
+
+//------------ ChildHandle ---------------------------------------------------
+
+/// The handle of a child.
+///
+/// Handles are the names the CA and the child use for each other. As
+/// defined in [RFC 8183], they consist of between 1 and 255 ASCII letters,
+/// digits, and the characters `-`, `_`, and `/`.
+///
+/// [RFC 8183]: https://tools.ietf.org/html/rfc8183
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct ChildHandle(String);
+
+impl ChildHandle {
+    /// Returns the handle as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FromStr for ChildHandle {
+    type Err = InvalidHandle;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.is_empty() || s.len() > 255 || !s.bytes().all(|ch| {
+            ch.is_ascii_alphanumeric() || ch == b'-' || ch == b'_'
+                || ch == b'/'
+        }) {
+            return Err(InvalidHandle)
+        }
+        Ok(ChildHandle(s.into()))
+    }
+}
+
+impl fmt::Display for ChildHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+
+//------------ Entitlements --------------------------------------------------
+
+/// The resources a child is entitled to.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Entitlements {
+    /// The AS resources.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources.
+    v6_resources: IpBlocks,
+}
+
+impl Entitlements {
+    /// Creates new entitlements from the resources.
+    pub fn new(
+        as_resources: AsBlocks, v4_resources: IpBlocks, v6_resources: IpBlocks
+    ) -> Self {
+        Entitlements { as_resources, v4_resources, v6_resources }
+    }
+
+    /// Creates empty entitlements.
+    pub fn empty() -> Self {
+        Self::new(AsBlocks::empty(), IpBlocks::empty(), IpBlocks::empty())
+    }
+
+    /// Returns the AS resources.
+    pub fn as_resources(&self) -> &AsBlocks {
+        &self.as_resources
+    }
+
+    /// Returns the IPv4 resources.
+    pub fn v4_resources(&self) -> &IpBlocks {
+        &self.v4_resources
+    }
+
+    /// Returns the IPv6 resources.
+    pub fn v6_resources(&self) -> &IpBlocks {
+        &self.v6_resources
+    }
+
+    /// Returns whether there are no resources at all.
+    pub fn is_empty(&self) -> bool {
+        self.as_resources.is_empty() && self.v4_resources.is_empty()
+            && self.v6_resources.is_empty()
+    }
+
+    /// Returns whether all resources of `other` are contained in `self`.
+    pub fn contains(&self, other: &Self) -> bool {
+        self.as_resources.contains(&other.as_resources)
+            && self.v4_resources.contains(&other.v4_resources)
+            && self.v6_resources.contains(&other.v6_resources)
+    }
+
+    /// Returns the resources contained in both `self` and `other`.
+    pub fn intersection(&self, other: &Self) -> Self {
+        Entitlements {
+            as_resources: self.as_resources.intersection(
+                &other.as_resources
+            ),
+            v4_resources: self.v4_resources.intersection(
+                &other.v4_resources
+            ),
+            v6_resources: self.v6_resources.intersection(
+                &other.v6_resources
+            ),
+        }
+    }
+}
+
+impl Default for Entitlements {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+
+//------------ IssuedCert ----------------------------------------------------
+
+/// A certificate issued to a child.
+#[derive(Clone, Debug)]
+pub struct IssuedCert {
+    /// The certificate.
+    cert: Cert,
+}
+
+impl IssuedCert {
+    /// Creates a new value from the certificate.
+    pub fn new(cert: Cert) -> Self {
+        IssuedCert { cert }
+    }
+
+    /// Returns the certificate.
+    pub fn cert(&self) -> &Cert {
+        &self.cert
+    }
+
+    /// Returns the identifier of the certified key.
+    pub fn key(&self) -> KeyIdentifier {
+        self.cert.subject_key_identifier()
+    }
+
+    /// Returns the serial number of the certificate.
+    pub fn serial(&self) -> Serial {
+        self.cert.serial_number()
+    }
+
+    /// Returns the time the certificate expires.
+    pub fn expires(&self) -> Time {
+        self.cert.validity().not_after()
+    }
+
+    /// Returns whether the certificate has expired at the given time.
+    pub fn is_expired(&self, now: Time) -> bool {
+        self.expires() < now
+    }
+}
+
+
+//------------ Child ---------------------------------------------------------
+
+/// A child of a CA.
+#[derive(Clone, Debug)]
+pub struct Child {
+    /// The handle of the child.
+    handle: ChildHandle,
+
+    /// The DER encoded identity certificate of the child.
+    id_cert: Bytes,
+
+    /// The resources the child is entitled to.
+    entitlements: Entitlements,
+
+    /// The certificates currently issued to the child.
+    issued: HashMap<KeyIdentifier, IssuedCert>,
+}
+
+impl Child {
+    /// Creates a new child without issued certificates.
+    ///
+    /// The `id_cert` is the DER encoded BPKI identity certificate the
+    /// child uses to sign its requests as exchanged per [RFC 8183]. It can
+    /// be decoded and validated via [`IdCert`].
+    ///
+    /// [RFC 8183]: https://tools.ietf.org/html/rfc8183
+    /// [`IdCert`]: ../../idcert/struct.IdCert.html
+    pub fn new(
+        handle: ChildHandle, id_cert: Bytes, entitlements: Entitlements
+    ) -> Self {
+        Child {
+            handle,
+            id_cert,
+            entitlements,
+            issued: HashMap::new(),
+        }
+    }
+
+    /// Returns the handle of the child.
+    pub fn handle(&self) -> &ChildHandle {
+        &self.handle
+    }
+
+    /// Returns the DER encoded identity certificate of the child.
+    pub fn id_cert(&self) -> &Bytes {
+        &self.id_cert
+    }
+
+    /// Sets the identity certificate of the child.
+    pub fn set_id_cert(&mut self, id_cert: Bytes) {
+        self.id_cert = id_cert
+    }
+
+    /// Returns the resources the child is entitled to.
+    pub fn entitlements(&self) -> &Entitlements {
+        &self.entitlements
+    }
+
+    /// Sets the resources the child is entitled to.
+    ///
+    /// Certificates already issued are not changed. Use [`overclaiming`]
+    /// to find those that need to be reissued or revoked.
+    ///
+    /// [`overclaiming`]: #method.overclaiming
+    pub fn set_entitlements(&mut self, entitlements: Entitlements) {
+        self.entitlements = entitlements
+    }
+
+    /// Returns an iterator over the certificates issued to the child.
+    pub fn issued(&self) -> impl Iterator<Item = &IssuedCert> {
+        self.issued.values()
+    }
+
+    /// Returns the certificate issued for the given key if present.
+    pub fn issued_cert(&self, key: KeyIdentifier) -> Option<&IssuedCert> {
+        self.issued.get(&key)
+    }
+
+    /// Records a certificate issued to the child.
+    ///
+    /// Returns the certificate previously issued for the same key if
+    /// present.
+    pub fn add_issued(&mut self, cert: Cert) -> Option<IssuedCert> {
+        let cert = IssuedCert::new(cert);
+        self.issued.insert(cert.key(), cert)
+    }
+
+    /// Removes the certificate issued for the given key.
+    pub fn remove_issued(
+        &mut self, key: KeyIdentifier
+    ) -> Option<IssuedCert> {
+        self.issued.remove(&key)
+    }
+
+    /// Removes all certificates that have expired at the given time.
+    ///
+    /// Returns the removed certificates.
+    pub fn remove_expired(&mut self, now: Time) -> Vec<IssuedCert> {
+        let expired: Vec<_> = self.issued.iter().filter_map(|(key, cert)| {
+            if cert.is_expired(now) { Some(*key) } else { None }
+        }).collect();
+        expired.into_iter().filter_map(|key| {
+            self.issued.remove(&key)
+        }).collect()
+    }
+
+    /// Returns an iterator over the issued certificates exceeding the
+    /// entitlements.
+    ///
+    /// These certificates need to be reissued with reduced resources or
+    /// revoked.
+    pub fn overclaiming(&self) -> impl Iterator<Item = &IssuedCert> {
+        self.issued.values().filter(move |cert| {
+            !self.entitlements.contains(&cert_resources(cert.cert()))
+        })
+    }
+
+    /// Processes a request received from the child.
+    ///
+    /// The method only decides what to do and doesn’t change the child.
+    /// Use [`Children::process`] in order to also check that a requested
+    /// key isn’t used by another child.
+    ///
+    /// [`Children::process`]: struct.Children.html#method.process
+    pub fn process(
+        &self, request: &ChildRequest
+    ) -> Result<ChildDecision, ChildError> {
+        match *request {
+            ChildRequest::List => {
+                let mut issued: Vec<_> = self.issued().cloned().collect();
+                issued.sort_by_key(IssuedCert::key);
+                Ok(ChildDecision::List {
+                    entitlements: self.entitlements.clone(),
+                    issued
+                })
+            }
+            ChildRequest::Issue { ref csr, ref limit } => {
+                self.process_issue(csr, limit.as_ref())
+            }
+            ChildRequest::Revoke { key } => {
+                match self.issued.get(&key) {
+                    Some(cert) => Ok(ChildDecision::Revoke(cert.clone())),
+                    None => Err(ChildError::UnknownKey)
+                }
+            }
+        }
+    }
+
+    /// Processes an issuance request.
+    fn process_issue(
+        &self, csr: &Csr, limit: Option<&Entitlements>
+    ) -> Result<ChildDecision, ChildError> {
+        // RFC 6492, section 3.4.1: The request must be for a CA
+        // certificate with the SIA pointers of the child.
+        if csr.validate().is_err() || !csr.basic_ca()
+            || csr.key_usage() != KeyUsage::Ca
+        {
+            return Err(ChildError::BadCsr)
+        }
+        let (ca_repository, rpki_manifest) = match (
+            csr.ca_repository(), csr.rpki_manifest()
+        ) {
+            (Some(repo), Some(mft)) => (repo.clone(), mft.clone()),
+            _ => return Err(ChildError::BadCsr)
+        };
+
+        let resources = match limit {
+            Some(limit) => self.entitlements.intersection(limit),
+            None => self.entitlements.clone()
+        };
+        if resources.is_empty() {
+            return Err(ChildError::NoResources)
+        }
+        let key = csr.public_key().clone();
+        let replaces = self.issued.get(&key.key_identifier()).cloned();
+        Ok(ChildDecision::Issue(Issuance {
+            key,
+            resources,
+            ca_repository,
+            rpki_manifest,
+            rpki_notify: csr.rpki_notify().cloned(),
+            replaces,
+        }))
+    }
+}
+
+/// Returns the resources of a certificate.
+///
+/// Inherited resources are treated as empty.
+fn cert_resources(cert: &Cert) -> Entitlements {
+    Entitlements {
+        as_resources: AsBlocks::from_resources(
+            cert.as_resources()
+        ).unwrap_or_else(|_| AsBlocks::empty()),
+        v4_resources: IpBlocks::from_resources(
+            cert.v4_resources()
+        ).unwrap_or_else(|_| IpBlocks::empty()),
+        v6_resources: IpBlocks::from_resources(
+            cert.v6_resources()
+        ).unwrap_or_else(|_| IpBlocks::empty()),
+    }
+}
+
+
+//------------ Children ------------------------------------------------------
+
+/// The children of a CA.
+#[derive(Clone, Debug, Default)]
+pub struct Children {
+    /// The children by their handle.
+    children: HashMap<ChildHandle, Child>,
+}
+
+impl Children {
+    /// Creates a new, empty set of children.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of children.
+    pub fn len(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Returns whether there are no children.
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Adds a new child.
+    ///
+    /// Fails if there already is a child with the same handle.
+    pub fn add(&mut self, child: Child) -> Result<(), ChildError> {
+        match self.children.entry(child.handle.clone()) {
+            hash_map::Entry::Occupied(_) => Err(ChildError::DuplicateChild),
+            hash_map::Entry::Vacant(entry) => {
+                entry.insert(child);
+                Ok(())
+            }
+        }
+    }
+
+    /// Removes a child.
+    ///
+    /// The certificates issued to the returned child need to be revoked.
+    pub fn remove(&mut self, handle: &ChildHandle) -> Option<Child> {
+        self.children.remove(handle)
+    }
+
+    /// Returns a reference to the child with the given handle.
+    pub fn get(&self, handle: &ChildHandle) -> Option<&Child> {
+        self.children.get(handle)
+    }
+
+    /// Returns a mutable reference to the child with the given handle.
+    pub fn get_mut(&mut self, handle: &ChildHandle) -> Option<&mut Child> {
+        self.children.get_mut(handle)
+    }
+
+    /// Returns an iterator over all children.
+    pub fn iter(&self) -> impl Iterator<Item = &Child> {
+        self.children.values()
+    }
+
+    /// Returns an iterator over the certificates expiring before `until`.
+    pub fn expiring(
+        &self, until: Time
+    ) -> impl Iterator<Item = (&ChildHandle, &IssuedCert)> {
+        self.children.values().flat_map(move |child| {
+            child.issued().filter(move |cert| {
+                cert.expires() < until
+            }).map(move |cert| (&child.handle, cert))
+        })
+    }
+
+    /// Processes a request received from the child with the given handle.
+    ///
+    /// In addition to what [`Child::process`] does, this checks that the
+    /// key of an issuance request isn’t used in a certificate issued to
+    /// any other child.
+    ///
+    /// [`Child::process`]: struct.Child.html#method.process
+    pub fn process(
+        &self, handle: &ChildHandle, request: &ChildRequest
+    ) -> Result<ChildDecision, ChildError> {
+        let child = self.get(handle).ok_or(ChildError::UnknownChild)?;
+        if let ChildRequest::Issue { ref csr, .. } = *request {
+            let key = csr.public_key().key_identifier();
+            if self.children.values().any(|other| {
+                other.handle != *handle && other.issued.contains_key(&key)
+            }) {
+                return Err(ChildError::KeyInUse)
+            }
+        }
+        child.process(request)
+    }
+}
+
+
+//------------ ChildRequest --------------------------------------------------
+
+/// A request received from a child.
+///
+/// These are the requests of [RFC 6492] for a CA with a single resource
+/// class.
+///
+/// [RFC 6492]: https://tools.ietf.org/html/rfc6492
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum ChildRequest {
+    /// A resource class list query.
+    List,
+
+    /// A certificate issuance request.
+    Issue {
+        /// The certificate request.
+        csr: Csr,
+
+        /// The resource set limiting the requested resources if present.
+        limit: Option<Entitlements>,
+    },
+
+    /// A certificate revocation request.
+    Revoke {
+        /// The key of the certificate to be revoked.
+        key: KeyIdentifier,
+    },
+}
+
+
+//------------ ChildDecision -------------------------------------------------
+
+/// What the CA needs to do in response to a child’s request.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug)]
+pub enum ChildDecision {
+    /// Respond with the entitlements and issued certificates.
+    ///
+    /// The certificates are ordered by key identifier.
+    List {
+        /// The resources the child is entitled to.
+        entitlements: Entitlements,
+
+        /// The certificates issued to the child.
+        issued: Vec<IssuedCert>,
+    },
+
+    /// Issue a certificate and respond with it.
+    Issue(Issuance),
+
+    /// Revoke the given certificate and confirm the revocation.
+    Revoke(IssuedCert),
+}
+
+
+//------------ Issuance ------------------------------------------------------
+
+/// The content of a certificate to be issued to a child.
+#[derive(Clone, Debug)]
+pub struct Issuance {
+    /// The key to be certified.
+    key: PublicKey,
+
+    /// The resources to be included.
+    resources: Entitlements,
+
+    /// The CA repository URI of the child.
+    ca_repository: uri::Rsync,
+
+    /// The manifest URI of the child.
+    rpki_manifest: uri::Rsync,
+
+    /// The RRDP notification URI of the child if present.
+    rpki_notify: Option<uri::Https>,
+
+    /// The certificate previously issued for the key if present.
+    replaces: Option<IssuedCert>,
+}
+
+impl Issuance {
+    /// Returns the key to be certified.
+    pub fn key(&self) -> &PublicKey {
+        &self.key
+    }
+
+    /// Returns the resources to be included.
+    pub fn resources(&self) -> &Entitlements {
+        &self.resources
+    }
+
+    /// Returns the CA repository URI of the child.
+    pub fn ca_repository(&self) -> &uri::Rsync {
+        &self.ca_repository
+    }
+
+    /// Returns the manifest URI of the child.
+    pub fn rpki_manifest(&self) -> &uri::Rsync {
+        &self.rpki_manifest
+    }
+
+    /// Returns the RRDP notification URI of the child if present.
+    pub fn rpki_notify(&self) -> Option<&uri::Https> {
+        self.rpki_notify.as_ref()
+    }
+
+    /// Returns the certificate previously issued for the key if present.
+    ///
+    /// If the new certificate differs from it, the previous certificate
+    /// should be revoked once the new one has been published.
+    pub fn replaces(&self) -> Option<&IssuedCert> {
+        self.replaces.as_ref()
+    }
+}
+
+
+//------------ ChildError ----------------------------------------------------
+
+/// A request of a child could not be processed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ChildError {
+    /// There is no child with the given handle.
+    UnknownChild,
+
+    /// There already is a child with the given handle.
+    DuplicateChild,
+
+    /// The child isn’t entitled to any of the requested resources.
+    NoResources,
+
+    /// The certificate request is invalid.
+    BadCsr,
+
+    /// The requested key is used by another child.
+    KeyInUse,
+
+    /// No certificate was issued for the key to be revoked.
+    UnknownKey,
+}
+
+impl ChildError {
+    /// Returns the RFC 6492 error code for the error if there is one.
+    ///
+    /// `UnknownChild` and `DuplicateChild` are management errors that
+    /// don’t appear in the provisioning protocol.
+    pub fn error_code(self) -> Option<u16> {
+        match self {
+            ChildError::UnknownChild => None,
+            ChildError::DuplicateChild => None,
+            ChildError::NoResources => Some(1202),
+            ChildError::BadCsr => Some(1203),
+            ChildError::KeyInUse => Some(1204),
+            ChildError::UnknownKey => Some(1302),
+        }
+    }
+}
+
+impl fmt::Display for ChildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ChildError::UnknownChild => "unknown child",
+            ChildError::DuplicateChild => "duplicate child",
+            ChildError::NoResources => "no resources allocated",
+            ChildError::BadCsr => "badly formed certificate request",
+            ChildError::KeyInUse => "already used key in request",
+            ChildError::UnknownKey => "no such key",
+        })
+    }
+}
+
+impl error::Error for ChildError { }
+
+
+//------------ InvalidHandle -------------------------------------------------
+
+/// A string was not a valid child handle.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct InvalidHandle;
+
+impl fmt::Display for InvalidHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid child handle")
+    }
+}
+
+impl error::Error for InvalidHandle { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+    use crate::resources::{AsBlock, AsId, Prefix};
+    use super::*;
+
+    #[test]
+    fn handle() {
+        assert_eq!(
+            ChildHandle::from_str("ca-1/child_2").unwrap().as_str(),
+            "ca-1/child_2"
+        );
+        assert!(ChildHandle::from_str("").is_err());
+        assert!(ChildHandle::from_str("ca 1").is_err());
+        assert!(ChildHandle::from_str(&"a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn entitlements() {
+        let all = Entitlements::new(
+            std::iter::once(
+                AsBlock::from((AsId::from(64496), AsId::from(64511)))
+            ).collect(),
+            std::iter::once(
+                Prefix::new(Ipv4Addr::new(192, 0, 2, 0), 24).into()
+            ).collect(),
+            IpBlocks::empty()
+        );
+        assert!(all.contains(&Entitlements::empty()));
+        assert!(!Entitlements::empty().contains(&all));
+        assert!(all.intersection(&Entitlements::empty()).is_empty());
+        assert_eq!(all.intersection(&all), all);
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use crate::cert::{Overclaim, TbsCert};
+    use crate::crypto::{PublicKeyFormat, Signer};
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::resources::{AsBlock, AsId};
+    use crate::x509::Validity;
+    use super::*;
+
+    fn as_entitlements(min: u32, max: u32) -> Entitlements {
+        Entitlements::new(
+            std::iter::once(AsBlock::from((AsId::from(min), AsId::from(max))))
+                .collect(),
+            IpBlocks::empty(), IpBlocks::empty()
+        )
+    }
+
+    #[test]
+    fn process_requests() {
+        let mut signer = OpenSslSigner::new();
+        let ca_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let child_key = signer.create_key(
+            PublicKeyFormat::default()
+        ).unwrap();
+        let child_pubkey = signer.get_key_info(&child_key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/c/").unwrap();
+        let csr = Csr::decode(
+            Csr::construct(
+                &signer, &child_key, &uri, &uri, None
+            ).unwrap().into_bytes()
+        ).unwrap();
+
+        let handle = ChildHandle::from_str("child").unwrap();
+        let other = ChildHandle::from_str("other").unwrap();
+        let mut children = Children::new();
+        children.add(Child::new(
+            handle.clone(), Bytes::new(), as_entitlements(64496, 64511)
+        )).unwrap();
+        children.add(Child::new(
+            other.clone(), Bytes::new(), Entitlements::empty()
+        )).unwrap();
+        assert_eq!(
+            children.add(Child::new(
+                handle.clone(), Bytes::new(), Entitlements::empty()
+            )),
+            Err(ChildError::DuplicateChild)
+        );
+
+        // Issuance is limited to the entitlements.
+        let request = ChildRequest::Issue {
+            csr: csr.clone(), limit: Some(as_entitlements(64500, 65000))
+        };
+        let issuance = match children.process(&handle, &request).unwrap() {
+            ChildDecision::Issue(issuance) => issuance,
+            _ => panic!("expected issuance")
+        };
+        assert_eq!(issuance.resources(), &as_entitlements(64500, 64511));
+        assert_eq!(issuance.key(), &child_pubkey);
+        assert_eq!(issuance.ca_repository(), &uri);
+        assert!(issuance.replaces().is_none());
+
+        let request = ChildRequest::Issue {
+            csr: csr.clone(), limit: Some(as_entitlements(1, 2))
+        };
+        assert_eq!(
+            children.process(&handle, &request).unwrap_err(),
+            ChildError::NoResources
+        );
+        assert_eq!(
+            children.process(&other, &ChildRequest::Issue {
+                csr: csr.clone(), limit: None
+            }).unwrap_err().error_code(),
+            Some(1202)
+        );
+
+        // Record the issued certificate.
+        let mut cert = TbsCert::new(
+            12u64.into(), child_pubkey.to_subject_name(),
+            Validity::new(
+                Time::utc(2021, 1, 1, 0, 0, 0),
+                Time::utc(2022, 1, 1, 0, 0, 0)
+            ),
+            None, child_pubkey.clone(), KeyUsage::Ca, Overclaim::Trim
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(uri.clone()));
+        cert.set_rpki_manifest(Some(uri.clone()));
+        cert.build_as_resource_blocks(|b| {
+            b.push((AsId::from(64500), AsId::from(64511)))
+        });
+        let cert = cert.into_cert(&signer, &ca_key).unwrap();
+        children.get_mut(&handle).unwrap().add_issued(cert);
+
+        // The key is now used by the child.
+        assert_eq!(
+            children.process(&other, &ChildRequest::Issue {
+                csr: csr.clone(), limit: None
+            }).unwrap_err(),
+            ChildError::KeyInUse
+        );
+        match children.process(&handle, &ChildRequest::Issue {
+            csr, limit: None
+        }).unwrap() {
+            ChildDecision::Issue(issuance) => {
+                assert_eq!(
+                    issuance.replaces().map(IssuedCert::serial),
+                    Some(12u64.into())
+                );
+            }
+            _ => panic!("expected issuance")
+        }
+
+        match children.process(&handle, &ChildRequest::List).unwrap() {
+            ChildDecision::List { entitlements, issued } => {
+                assert_eq!(entitlements, as_entitlements(64496, 64511));
+                assert_eq!(issued.len(), 1);
+            }
+            _ => panic!("expected list")
+        }
+
+        // Expiry and overclaiming.
+        assert_eq!(
+            children.expiring(Time::utc(2022, 6, 1, 0, 0, 0)).count(), 1
+        );
+        assert_eq!(
+            children.expiring(Time::utc(2021, 6, 1, 0, 0, 0)).count(), 0
+        );
+        let child = children.get_mut(&handle).unwrap();
+        assert_eq!(child.overclaiming().count(), 0);
+        child.set_entitlements(as_entitlements(64496, 64505));
+        assert_eq!(child.overclaiming().count(), 1);
+
+        // Revocation.
+        let key = child_pubkey.key_identifier();
+        match children.process(
+            &handle, &ChildRequest::Revoke { key }
+        ).unwrap() {
+            ChildDecision::Revoke(cert) => assert_eq!(cert.key(), key),
+            _ => panic!("expected revocation")
+        }
+        let child = children.get_mut(&handle).unwrap();
+        assert!(child.remove_issued(key).is_some());
+        assert_eq!(
+            children.process(
+                &handle, &ChildRequest::Revoke { key }
+            ).unwrap_err(),
+            ChildError::UnknownKey
+        );
+        assert_eq!(
+            children.process(
+                &ChildHandle::from_str("none").unwrap(), &ChildRequest::List
+            ).unwrap_err(),
+            ChildError::UnknownChild
+        );
+    }
+
+    #[test]
+    fn remove_expired() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/c/").unwrap();
+        let mut cert = TbsCert::new(
+            12u64.into(), pubkey.to_subject_name(),
+            Validity::new(
+                Time::utc(2021, 1, 1, 0, 0, 0),
+                Time::utc(2022, 1, 1, 0, 0, 0)
+            ),
+            None, pubkey, KeyUsage::Ca, Overclaim::Trim
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(uri.clone()));
+        cert.set_rpki_manifest(Some(uri));
+        cert.build_as_resource_blocks(|b| {
+            b.push((AsId::from(64500), AsId::from(64511)))
+        });
+        let cert = cert.into_cert(&signer, &key).unwrap();
+
+        let mut child = Child::new(
+            ChildHandle::from_str("child").unwrap(), Bytes::new(),
+            as_entitlements(64496, 64511)
+        );
+        assert!(child.add_issued(cert).is_none());
+        assert!(
+            child.remove_expired(Time::utc(2021, 6, 1, 0, 0, 0)).is_empty()
+        );
+        assert_eq!(
+            child.remove_expired(Time::utc(2022, 6, 1, 0, 0, 0)).len(), 1
+        );
+        assert_eq!(child.issued().count(), 0);
+    }
+}
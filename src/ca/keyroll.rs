@@ -0,0 +1,437 @@
+//! The key rollover procedure for CAs.
+//!
+//! This module implements the staged procedure for replacing the key of a
+//! CA defined in section 2 of [RFC 6489]. See [`KeyRoll`] for details.
+//!
+//! [RFC 6489]: https://tools.ietf.org/html/rfc6489
+//! [`KeyRoll`]: struct.KeyRoll.html
+
+use std::{error, fmt};
+use crate::crypto::KeyIdentifier;
+use crate::x509::Time;
+
+
+//------------ KeyRoll -------------------------------------------------------
+
+/// The state of a key rollover of a CA.
+///
+/// A key rollover replaces the current key of a CA, the _old key,_ with a
+/// _new key._ In order to not disrupt relying parties, [RFC 6489] defines
+/// a procedure with the following steps:
+///
+/// 1. The CA generates the new key and requests a certificate for it from
+///    its parent.
+/// 2. Once it received the certificate, the CA publishes a CRL and
+///    manifest for the new key. Objects issued under the old key remain
+///    published, so both keys are now in use. This staging period lasts at
+///    least 24 hours to give relying parties time to pick up the new
+///    certificate.
+/// 3. After the staging period, the CA reissues all its products under
+///    the new key, withdraws those issued under the old key, and requests
+///    the revocation of the certificate for the old key from its parent.
+/// 4. Once the parent has revoked the old certificate, the CA withdraws
+///    the CRL and manifest of the old key and destroys it.
+///
+/// A value of this type keeps track of the progress of the rollover. It is
+/// created via [`start`] once the new key has been generated and advanced
+/// via [`cert_received`], [`activate`], and [`revoke_confirmed`]. Each of
+/// these returns the [operations][`KeyRollOp`] the CA needs to perform
+/// next. Before the new key is activated, the rollover can be abandoned
+/// via [`abort`].
+///
+/// Until [`activate`] has been called, the CA keeps publishing a CRL and
+/// manifest under the old key. Between [`cert_received`] and the
+/// completion of the rollover, it needs to keep both the CRL and manifest
+/// of the old and new key current.
+///
+/// [RFC 6489]: https://tools.ietf.org/html/rfc6489
+/// [`start`]: #method.start
+/// [`cert_received`]: #method.cert_received
+/// [`activate`]: #method.activate
+/// [`revoke_confirmed`]: #method.revoke_confirmed
+/// [`abort`]: #method.abort
+/// [`KeyRollOp`]: enum.KeyRollOp.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KeyRoll {
+    /// The key identifier of the old key.
+    old_key: KeyIdentifier,
+
+    /// The key identifier of the new key.
+    new_key: KeyIdentifier,
+
+    /// The current phase of the rollover.
+    phase: KeyRollPhase,
+
+    /// The minimum duration of the staging period.
+    staging: chrono::Duration,
+
+    /// The time the staging period ends.
+    ///
+    /// This is `None` until the certificate for the new key was received.
+    staged_until: Option<Time>,
+}
+
+impl KeyRoll {
+    /// The minimum duration of the staging period required by RFC 6489.
+    pub fn default_staging() -> chrono::Duration {
+        chrono::Duration::hours(24)
+    }
+
+    /// Starts a rollover from `old_key` to the freshly created `new_key`.
+    ///
+    /// Returns the rollover state and the operations to perform, which
+    /// is requesting a certificate for the new key from the parent.
+    pub fn start(
+        old_key: KeyIdentifier, new_key: KeyIdentifier
+    ) -> (Self, Vec<KeyRollOp>) {
+        (
+            KeyRoll {
+                old_key,
+                new_key,
+                phase: KeyRollPhase::Pending,
+                staging: Self::default_staging(),
+                staged_until: None,
+            },
+            vec![KeyRollOp::RequestCert { key: new_key }]
+        )
+    }
+
+    /// Returns the key identifier of the old key.
+    pub fn old_key(&self) -> KeyIdentifier {
+        self.old_key
+    }
+
+    /// Returns the key identifier of the new key.
+    pub fn new_key(&self) -> KeyIdentifier {
+        self.new_key
+    }
+
+    /// Returns the current phase of the rollover.
+    pub fn phase(&self) -> KeyRollPhase {
+        self.phase
+    }
+
+    /// Returns the minimum duration of the staging period.
+    pub fn staging(&self) -> chrono::Duration {
+        self.staging
+    }
+
+    /// Sets the minimum duration of the staging period.
+    ///
+    /// RFC 6489 requires at least 24 hours, but shorter periods may be
+    /// useful for testing. The duration is only taken into account if set
+    /// before [`cert_received`] is called.
+    ///
+    /// [`cert_received`]: #method.cert_received
+    pub fn set_staging(&mut self, staging: chrono::Duration) {
+        self.staging = staging
+    }
+
+    /// Returns the time the staging period ends.
+    ///
+    /// Returns `None` if the certificate for the new key hasn’t been
+    /// received yet.
+    pub fn staged_until(&self) -> Option<Time> {
+        self.staged_until
+    }
+
+    /// Returns whether the new key can be activated at the given time.
+    pub fn can_activate(&self, now: Time) -> bool {
+        self.phase == KeyRollPhase::Staging
+            && self.staged_until.map(|until| now >= until).unwrap_or(false)
+    }
+
+    /// Records that the certificate for the new key has been received.
+    ///
+    /// This starts the staging period at `now`. Returns the operations for
+    /// publishing the CRL and manifest of the new key.
+    pub fn cert_received(
+        &mut self, now: Time
+    ) -> Result<Vec<KeyRollOp>, KeyRollError> {
+        self.check_phase(KeyRollPhase::Pending)?;
+        self.phase = KeyRollPhase::Staging;
+        self.staged_until = Some(now + self.staging);
+        Ok(vec![KeyRollOp::PublishKeyObjects { key: self.new_key }])
+    }
+
+    /// Activates the new key after the staging period.
+    ///
+    /// Returns the operations for moving all products to the new key and
+    /// for requesting the revocation of the old key’s certificate.
+    pub fn activate(
+        &mut self, now: Time
+    ) -> Result<Vec<KeyRollOp>, KeyRollError> {
+        self.check_phase(KeyRollPhase::Staging)?;
+        if let Some(until) = self.staged_until {
+            if now < until {
+                return Err(KeyRollError::Staging(until))
+            }
+        }
+        self.phase = KeyRollPhase::Revoking;
+        Ok(vec![
+            KeyRollOp::ReissueProducts {
+                old_key: self.old_key, new_key: self.new_key
+            },
+            KeyRollOp::RequestRevoke { key: self.old_key },
+        ])
+    }
+
+    /// Records that the parent has revoked the old key’s certificate.
+    ///
+    /// This completes the rollover. Returns the operations for retiring
+    /// the old key.
+    pub fn revoke_confirmed(
+        &mut self
+    ) -> Result<Vec<KeyRollOp>, KeyRollError> {
+        self.check_phase(KeyRollPhase::Revoking)?;
+        self.phase = KeyRollPhase::Done;
+        Ok(vec![
+            KeyRollOp::WithdrawKeyObjects { key: self.old_key },
+            KeyRollOp::DestroyKey { key: self.old_key },
+        ])
+    }
+
+    /// Abandons the rollover.
+    ///
+    /// This is only possible before the new key has been activated. The
+    /// old key remains the current key. Returns the operations for
+    /// retiring the new key.
+    pub fn abort(&mut self) -> Result<Vec<KeyRollOp>, KeyRollError> {
+        let res = match self.phase {
+            KeyRollPhase::Pending => {
+                vec![KeyRollOp::DestroyKey { key: self.new_key }]
+            }
+            KeyRollPhase::Staging => {
+                vec![
+                    KeyRollOp::WithdrawKeyObjects { key: self.new_key },
+                    KeyRollOp::RequestRevoke { key: self.new_key },
+                    KeyRollOp::DestroyKey { key: self.new_key },
+                ]
+            }
+            phase => return Err(KeyRollError::Phase(phase))
+        };
+        self.phase = KeyRollPhase::Aborted;
+        Ok(res)
+    }
+
+    /// Returns the key the CA should use for issuing new products.
+    ///
+    /// This is the old key until the new key is activated and the new key
+    /// thereafter.
+    pub fn current_key(&self) -> KeyIdentifier {
+        match self.phase {
+            KeyRollPhase::Revoking | KeyRollPhase::Done => self.new_key,
+            _ => self.old_key
+        }
+    }
+
+    /// Checks that the rollover is in the expected phase.
+    fn check_phase(&self, phase: KeyRollPhase) -> Result<(), KeyRollError> {
+        if self.phase == phase {
+            Ok(())
+        }
+        else {
+            Err(KeyRollError::Phase(self.phase))
+        }
+    }
+}
+
+
+//------------ KeyRollPhase --------------------------------------------------
+
+/// The phase of a key rollover.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyRollPhase {
+    /// A certificate for the new key has been requested.
+    Pending,
+
+    /// Both keys are published and the staging period is running.
+    Staging,
+
+    /// The new key is active and the old key is being revoked.
+    Revoking,
+
+    /// The rollover has been completed.
+    Done,
+
+    /// The rollover has been abandoned.
+    Aborted,
+}
+
+impl fmt::Display for KeyRollPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            KeyRollPhase::Pending => "pending",
+            KeyRollPhase::Staging => "staging",
+            KeyRollPhase::Revoking => "revoking",
+            KeyRollPhase::Done => "done",
+            KeyRollPhase::Aborted => "aborted",
+        })
+    }
+}
+
+
+//------------ KeyRollOp -----------------------------------------------------
+
+/// An operation a CA needs to perform during a key rollover.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyRollOp {
+    /// Request a certificate for the key from the parent.
+    ///
+    /// This is an issuance request of the provisioning protocol.
+    RequestCert {
+        /// The key to be certified.
+        key: KeyIdentifier
+    },
+
+    /// Publish a CRL and manifest issued under the key.
+    PublishKeyObjects {
+        /// The key issuing the CRL and manifest.
+        key: KeyIdentifier
+    },
+
+    /// Reissue all products of the old key under the new key.
+    ///
+    /// The products issued under the new key are published and those
+    /// issued under the old key are withdrawn. The CRL and manifest of the
+    /// old key are updated accordingly.
+    ReissueProducts {
+        /// The key that issued the products so far.
+        old_key: KeyIdentifier,
+
+        /// The key to issue the products from now on.
+        new_key: KeyIdentifier,
+    },
+
+    /// Request the revocation of the key’s certificate from the parent.
+    ///
+    /// This is a revocation request of the provisioning protocol.
+    RequestRevoke {
+        /// The key whose certificate is to be revoked.
+        key: KeyIdentifier
+    },
+
+    /// Withdraw the CRL and manifest issued under the key.
+    WithdrawKeyObjects {
+        /// The key that issued the CRL and manifest.
+        key: KeyIdentifier
+    },
+
+    /// Destroy the key.
+    DestroyKey {
+        /// The key to destroy.
+        key: KeyIdentifier
+    },
+}
+
+
+//------------ KeyRollError --------------------------------------------------
+
+/// A key rollover could not be advanced.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KeyRollError {
+    /// The step isn’t possible in the given current phase.
+    Phase(KeyRollPhase),
+
+    /// The staging period lasts until the given time.
+    Staging(Time),
+}
+
+impl fmt::Display for KeyRollError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyRollError::Phase(phase) => {
+                write!(f, "not possible in key roll phase {}", phase)
+            }
+            KeyRollError::Staging(until) => {
+                write!(f, "key roll staging period lasts until {}", *until)
+            }
+        }
+    }
+}
+
+impl error::Error for KeyRollError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+    use super::*;
+
+    fn keys() -> (KeyIdentifier, KeyIdentifier) {
+        (
+            KeyIdentifier::try_from([1u8; 20].as_ref()).unwrap(),
+            KeyIdentifier::try_from([2u8; 20].as_ref()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn rollover() {
+        let (old, new) = keys();
+        let now = Time::utc(2021, 1, 1, 0, 0, 0);
+        let (mut roll, ops) = KeyRoll::start(old, new);
+        assert_eq!(ops, [KeyRollOp::RequestCert { key: new }]);
+        assert_eq!(roll.phase(), KeyRollPhase::Pending);
+        assert_eq!(roll.current_key(), old);
+        assert_eq!(
+            roll.activate(now),
+            Err(KeyRollError::Phase(KeyRollPhase::Pending))
+        );
+
+        assert_eq!(
+            roll.cert_received(now).unwrap(),
+            [KeyRollOp::PublishKeyObjects { key: new }]
+        );
+        let until = now + chrono::Duration::hours(24);
+        assert_eq!(roll.staged_until(), Some(until));
+        assert!(!roll.can_activate(now));
+        assert_eq!(roll.activate(now), Err(KeyRollError::Staging(until)));
+        assert_eq!(roll.current_key(), old);
+
+        assert!(roll.can_activate(until));
+        assert_eq!(
+            roll.activate(until).unwrap(),
+            [
+                KeyRollOp::ReissueProducts { old_key: old, new_key: new },
+                KeyRollOp::RequestRevoke { key: old },
+            ]
+        );
+        assert_eq!(roll.current_key(), new);
+        assert!(roll.abort().is_err());
+
+        assert_eq!(
+            roll.revoke_confirmed().unwrap(),
+            [
+                KeyRollOp::WithdrawKeyObjects { key: old },
+                KeyRollOp::DestroyKey { key: old },
+            ]
+        );
+        assert_eq!(roll.phase(), KeyRollPhase::Done);
+        assert!(roll.revoke_confirmed().is_err());
+    }
+
+    #[test]
+    fn abort() {
+        let (old, new) = keys();
+        let (mut roll, _) = KeyRoll::start(old, new);
+        assert_eq!(
+            roll.abort().unwrap(), [KeyRollOp::DestroyKey { key: new }]
+        );
+        assert_eq!(roll.phase(), KeyRollPhase::Aborted);
+
+        let (mut roll, _) = KeyRoll::start(old, new);
+        roll.set_staging(chrono::Duration::zero());
+        roll.cert_received(Time::utc(2021, 1, 1, 0, 0, 0)).unwrap();
+        assert_eq!(
+            roll.abort().unwrap(),
+            [
+                KeyRollOp::WithdrawKeyObjects { key: new },
+                KeyRollOp::RequestRevoke { key: new },
+                KeyRollOp::DestroyKey { key: new },
+            ]
+        );
+        assert_eq!(roll.current_key(), old);
+    }
+}
@@ -0,0 +1,24 @@
+//! Support for operating a certification authority.
+//!
+//! This module contains bookkeeping types for the processes a CA goes
+//! through: the key rollover procedure of [RFC 6489] modelled by
+//! [`KeyRoll`] and the management of the CA’s children and their requests
+//! received via [RFC 6492] provided by [`Children`].
+//!
+//! The types in here don’t perform any operations themselves. Instead,
+//! they tell the CA implementation which operations need to happen next,
+//! leaving signing, publication, and communication with the parent to it.
+//!
+//! [RFC 6489]: https://tools.ietf.org/html/rfc6489
+//! [RFC 6492]: https://tools.ietf.org/html/rfc6492
+//! [`KeyRoll`]: keyroll/struct.KeyRoll.html
+//! [`Children`]: children/struct.Children.html
+
+pub use self::children::{
+    Child, ChildDecision, ChildError, ChildHandle, ChildRequest, Children,
+    Entitlements, Issuance, IssuedCert,
+};
+pub use self::keyroll::{KeyRoll, KeyRollError, KeyRollOp, KeyRollPhase};
+
+pub mod children;
+pub mod keyroll;
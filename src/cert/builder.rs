@@ -2,7 +2,10 @@
 use bcder::encode;
 use bcder::{BitString, Captured, ConstOid, Mode, OctetString, Tag};
 use bcder::encode::PrimitiveContent;
-use crate::crypto::{PublicKey, SignatureAlgorithm, Signer, SigningError};
+use crate::crypto::{
+    KeyIdentifier, PublicKey, Signature, SignatureAlgorithm, Signer,
+    SigningError
+};
 use crate::oid;
 use crate::resources::{
     AsBlocksBuilder, AsResourcesBuilder, IpBlocksBuilder, IpResources,
@@ -13,6 +16,21 @@ use crate::x509::Name;
 use super::Validity;
 
 
+//------------ CertBuilder ---------------------------------------------------
+
+/// A builder for resource certificates.
+///
+/// The builder collects all the information necessary for a CA or EE
+/// certificate following the profile of [RFC 6487]. The certificate is
+/// then signed via a [`Signer`] and DER encoded by the [`encode`] method.
+/// For EE certificates of signed objects which use a one-off key, the
+/// [`encode_one_off`] method creates the key, signs the object’s data with
+/// it, and encodes the certificate for it in one go.
+///
+/// [RFC 6487]: https://tools.ietf.org/html/rfc6487
+/// [`Signer`]: ../../crypto/signer/trait.Signer.html
+/// [`encode`]: #method.encode
+/// [`encode_one_off`]: #method.encode_one_off
 #[derive(Clone, Debug)]
 pub struct CertBuilder {
     //  The following lists how all the parts to go into the final certificate
@@ -91,7 +109,7 @@ pub struct CertBuilder {
     ///
     /// Must be present except in trust-anchor certificates and non-critical.
     /// It must contain the subject key identifier of issuing certificate.
-    authority_key_identifier: Option<OctetString>, 
+    authority_key_identifier: Option<KeyIdentifier>,
 
     //  Key Usage.
     //
@@ -194,7 +212,7 @@ impl CertBuilder {
     }
 
     pub fn authority_key_identifier(
-        &mut self, id: OctetString
+        &mut self, id: KeyIdentifier
     ) -> &mut Self {
         self.authority_key_identifier = Some(id);
         self
@@ -280,6 +298,24 @@ impl CertBuilder {
         )))
     }
 
+    /// Finalizes an EE certificate with a one-off key.
+    ///
+    /// A new key is created via the signer’s `sign_one_off` method and used
+    /// to sign `data`. The certificate is issued for this key and signed
+    /// with the issuer’s key `key`. Returns an encoder for the certificate
+    /// and the signature over `data`.
+    pub fn encode_one_off<S: Signer, D: AsRef<[u8]> + ?Sized>(
+        self,
+        signer: &S,
+        key: &S::KeyId,
+        alg: SignatureAlgorithm,
+        data: &D,
+    ) -> Result<(impl encode::Values, Signature), SigningError<S::Error>> {
+        let (signature, public_key) = signer.sign_one_off(alg, data)?;
+        let cert = self.encode(signer, key, alg, &public_key)?;
+        Ok((cert, signature))
+    }
+
     fn encode_tbs_cert(
         mut self,
         alg: SignatureAlgorithm,
@@ -317,9 +353,7 @@ impl CertBuilder {
                 // Subject Key Identifier
                 extension(
                     &oid::CE_SUBJECT_KEY_IDENTIFIER, false,
-                    OctetString::encode_slice(
-                        public_key.key_identifier()
-                    )
+                    public_key.key_identifier().encode()
                 ),
 
                 // Authority Key Identifier
@@ -353,9 +387,7 @@ impl CertBuilder {
                             encode::sequence( // DistributionPoint
                                 encode::sequence_as(Tag::CTX_0, // distrib.Pt.
                                     encode::sequence_as(Tag::CTX_0, // fullName
-                                        encode::sequence( // GeneralNames
-                                            uri.encode_general_name()
-                                        )
+                                        uri.encode_general_name()
                                     )
                                 )
                             )
@@ -487,5 +519,63 @@ mod signer_test {
         let talinfo = TalInfo::from_name("foo".into()).into_arc();
         cert.validate_ta(talinfo, true).unwrap();
     }
+
+    #[test]
+    fn ca_and_ee_cert() {
+        let mut signer = OpenSslSigner::new();
+        let ta_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let ta_pub = signer.get_key_info(&ta_key).unwrap();
+        let ca_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let ca_pub = signer.get_key_info(&ca_key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+        let talinfo = TalInfo::from_name("foo".into()).into_arc();
+
+        let mut builder = CertBuilder::new(
+            1, ta_pub.to_subject_name(), Validity::from_secs(86400), true
+        );
+        builder
+            .ca_repository(uri.clone())
+            .rpki_manifest(uri.clone())
+            .v4_blocks(|blocks| blocks.push(Prefix::new(0, 0)))
+            .as_blocks(|blocks| blocks.push((AsId::MIN, AsId::MAX)));
+        let ta = builder.encode(
+            &signer, &ta_key, SignatureAlgorithm::default(), &ta_pub
+        ).unwrap().to_captured(Mode::Der);
+        let ta = Cert::decode(ta.as_slice()).unwrap();
+        let ta = ta.validate_ta(talinfo, true).unwrap();
+
+        let mut builder = CertBuilder::new(
+            2, ta_pub.to_subject_name(), Validity::from_secs(86400), true
+        );
+        builder
+            .authority_key_identifier(ta_pub.key_identifier())
+            .crl_distribution(uri.clone())
+            .authority_info_access(uri.clone())
+            .ca_repository(uri.clone())
+            .rpki_manifest(uri.clone())
+            .v4_blocks(|blocks| blocks.push(Prefix::new(0, 8)))
+            .inherit_as();
+        let ca = builder.encode(
+            &signer, &ta_key, SignatureAlgorithm::default(), &ca_pub
+        ).unwrap().to_captured(Mode::Der);
+        let ca = Cert::decode(ca.as_slice()).unwrap();
+        let ca = ca.validate_ca(&ta, true).unwrap();
+
+        let mut builder = CertBuilder::new(
+            3, ca_pub.to_subject_name(), Validity::from_secs(86400), false
+        );
+        builder
+            .authority_key_identifier(ca_pub.key_identifier())
+            .crl_distribution(uri.clone())
+            .authority_info_access(uri.clone())
+            .signed_object(uri.clone())
+            .inherit_v4();
+        let (ee, signature) = builder.encode_one_off(
+            &signer, &ca_key, SignatureAlgorithm::default(), b"data"
+        ).unwrap();
+        let ee = Cert::decode(ee.to_captured(Mode::Der).as_slice()).unwrap();
+        ee.subject_public_key_info().verify(b"data", &signature).unwrap();
+        ee.validate_ee(&ca, true).unwrap();
+    }
 }
 
@@ -8,7 +8,7 @@ use bcder::{
 };
 use bcder::encode::PrimitiveContent;
 use bytes::Bytes;
-use crate::crypto::PublicKey;
+use crate::crypto::{KeyIdentifier, PublicKey};
 use crate::oid;
 use crate::resources::{AsResources, IpResources};
 use crate::uri;
@@ -52,8 +52,8 @@ pub struct Extensions {
     /// Subject Information Access
     ///
     /// This value contains the content of the SubjectInfoAccessSyntax
-    /// sequence.
-    subject_info_access: SubjectInfoAccess,
+    /// sequence. It is absent in BGPsec router certificates.
+    subject_info_access: Option<SubjectInfoAccess>,
 
     /// Certificate Policies
     ///
@@ -72,6 +72,9 @@ pub struct Extensions {
 
     /// AS Resources
     as_resources: Option<AsResources>,
+
+    /// Non-critical extensions not defined by the RPKI profile.
+    unknown: Vec<UnknownExtension>,
 }
 
 
@@ -85,19 +88,16 @@ impl Extensions {
         }
     }
 
-    pub fn subject_key_id(&self) -> &OctetString {
-        &self.subject_key_id.subject_key_id()
+    pub fn subject_key_id(&self) -> KeyIdentifier {
+        self.subject_key_id.subject_key_id()
     }
 
     pub fn crl_distribution(&self) -> Option<&UriGeneralNames> {
         self.crl_distribution.as_ref()
     }
 
-    pub fn authority_key_id(&self) -> Option<&OctetString> {
-        match &self.authority_key_id {
-            Some(a) => Some(a.authority_key_id()),
-            None => None
-        }
+    pub fn authority_key_id(&self) -> Option<KeyIdentifier> {
+        self.authority_key_id.as_ref().map(|a| a.authority_key_id())
     }
 
     pub fn authority_info_access(&self) -> Option<&UriGeneralName> {
@@ -112,24 +112,24 @@ impl Extensions {
         self.extended_key_usage.as_ref()
     }
 
-    pub fn subject_info_access(&self) -> &SubjectInfoAccess {
-        &self.subject_info_access
+    pub fn subject_info_access(&self) -> Option<&SubjectInfoAccess> {
+        self.subject_info_access.as_ref()
     }
 
     pub fn ca_repository_uri(&self) -> Option<&uri::Rsync> {
-        self.subject_info_access.ca_repository()
+        self.subject_info_access.as_ref()?.ca_repository()
     }
 
     pub fn manifest_uri(&self) -> Option<&uri::Rsync> {
-        self.subject_info_access.rpki_manifest()
+        self.subject_info_access.as_ref()?.rpki_manifest()
     }
 
     pub fn signed_object_uri(&self) -> Option<&uri::Rsync> {
-        self.subject_info_access.signed_object()
+        self.subject_info_access.as_ref()?.signed_object()
     }
 
     pub fn rpki_notify_uri(&self) -> Option<&uri::Https> {
-        self.subject_info_access.rpki_notify()
+        self.subject_info_access.as_ref()?.rpki_notify()
     }
 
     pub fn overclaim(&self) -> Overclaim {
@@ -147,6 +147,14 @@ impl Extensions {
     pub fn as_resources(&self) -> Option<&AsResources> {
         self.as_resources.as_ref()
     }
+
+    /// Returns the non-critical extensions not defined by the RPKI profile.
+    ///
+    /// The extensions are returned in the order they appear in the
+    /// certificate.
+    pub fn unknown(&self) -> &[UnknownExtension] {
+        &self.unknown
+    }
 }
 
 
@@ -170,6 +178,7 @@ impl Extensions {
             let mut ip_overclaim = None;
             let mut as_resources = None;
             let mut as_overclaim = None;
+            let mut unknown = Vec::new();
             while let Some(()) = cons.take_opt_sequence(|cons| {
                 let id = Oid::take_from(cons)?;
                 let critical = cons.take_opt_bool()?.unwrap_or(false);
@@ -220,7 +229,11 @@ impl Extensions {
                     } else {
                         // RFC 5280 says we can ignore non-critical
                         // extensions we don’t know of. RFC 6487
-                        // agrees. So let’s do that.
+                        // agrees. We keep them for inspection, though.
+                        unknown.push(UnknownExtension {
+                            id: id.clone(),
+                            value: value.to_bytes(),
+                        });
                         Ok(())
                     }
                 })?;
@@ -247,12 +260,12 @@ impl Extensions {
                 extended_key_usage,
                 crl_distribution,
                 authority_info_access,
-                subject_info_access:
-                    subject_info_access.ok_or(decode::Malformed)?,
+                subject_info_access,
                 overclaim: overclaim.ok_or(decode::Malformed)?,
                 v4_resources: ip_resources.0,
                 v6_resources: ip_resources.1,
                 as_resources,
+                unknown,
             })
         })
     }
@@ -458,6 +471,34 @@ impl Extensions {
 }
 
 
+//------------ UnknownExtension ----------------------------------------------
+
+/// A non-critical extension not defined by the RPKI profile.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnknownExtension {
+    /// The object identifier of the extension.
+    id: Oid,
+
+    /// The content of the extension’s value octet string.
+    value: Bytes,
+}
+
+impl UnknownExtension {
+    /// Returns the object identifier of the extension.
+    pub fn id(&self) -> &Oid {
+        &self.id
+    }
+
+    /// Returns the raw content of the extension’s value.
+    ///
+    /// This is the DER encoding of the extension’s value without the
+    /// wrapping octet string.
+    pub fn value(&self) -> &Bytes {
+        &self.value
+    }
+}
+
+
 //------------ BasicCa -------------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -527,46 +568,6 @@ impl BasicCa {
 }
 
 
-//------------ KeyIdentifier -------------------------------------------------
-
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct KeyIdentifier(OctetString);
-
-impl KeyIdentifier {
-    pub fn new(key_info: &PublicKey) -> Self {
-        let ki = key_info.key_identifier();
-        let b = Bytes::copy_from_slice(ki.as_ref());
-        KeyIdentifier(OctetString::new(b))
-    }
-
-    pub fn key_id(&self) -> &OctetString {
-        &self.0
-    }
-
-    pub fn encode(self) -> impl encode::Values {
-        self.0.encode()
-    }
-
-    pub fn encode_as(self, tag: Tag) -> impl encode::Values {
-        self.0.encode_as(tag)
-    }
-
-    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
-        self.0.encode_ref()
-    }
-
-    pub fn encode_ref_as<'a>(&'a self, tag: Tag) -> impl encode::Values + 'a {
-        self.0.encode_ref_as(tag)
-    }
-}
-
-impl From<OctetString> for KeyIdentifier {
-    fn from(o: OctetString) -> Self {
-        KeyIdentifier(o)
-    }
-}
-
-
 //------------ SubjectKeyIdentifier ------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -578,11 +579,11 @@ pub struct SubjectKeyIdentifier {
 ///
 impl SubjectKeyIdentifier {
     pub fn new(key_info: &PublicKey) -> Self {
-        Self{subject_key_id: KeyIdentifier::new(key_info)}
+        Self{subject_key_id: key_info.key_identifier()}
     }
 
-    pub fn subject_key_id(&self) -> &OctetString {
-        self.subject_key_id.key_id()
+    pub fn subject_key_id(&self) -> KeyIdentifier {
+        self.subject_key_id
     }
 }
 
@@ -607,12 +608,12 @@ impl SubjectKeyIdentifier {
         subject_key_id: &mut Option<Self>
     ) -> Result<(), S::Err> {
         update_once(subject_key_id, || {
-            let subject_key_id = OctetString::take_from(cons)?;
-            if critical || subject_key_id.len() != 20 {
+            let subject_key_id = KeyIdentifier::take_from(cons)?;
+            if critical {
                 xerr!(Err(decode::Malformed.into()))
             }
             else {
-                Ok(Self{subject_key_id: subject_key_id.into()} )
+                Ok(Self{subject_key_id})
             }
         })
     }
@@ -639,21 +640,18 @@ impl SubjectKeyIdentifier {
 
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct AuthorityKeyIdentifier {
-    authority_key_id: OctetString
+    authority_key_id: KeyIdentifier
 }
 
 /// # Creating and Data Access
 ///
 impl AuthorityKeyIdentifier {
     pub fn new(key_info: &PublicKey) -> Self {
-        let ki = key_info.key_identifier();
-        let b = Bytes::copy_from_slice(ki.as_ref());
-
-        Self{authority_key_id: OctetString::new(b)}
+        Self{authority_key_id: key_info.key_identifier()}
     }
 
-    pub fn authority_key_id(&self) -> &OctetString {
-        &self.authority_key_id
+    pub fn authority_key_id(&self) -> KeyIdentifier {
+        self.authority_key_id
     }
 
 }
@@ -684,9 +682,9 @@ impl AuthorityKeyIdentifier {
     ) -> Result<(), S::Err> {
         update_once(authority_key_id, || {
             let authority_key_id = cons.take_sequence(|cons| {
-                cons.take_value_if(Tag::CTX_0, OctetString::from_content)
+                cons.take_value_if(Tag::CTX_0, KeyIdentifier::from_content)
             })?;
-            if critical || authority_key_id.len() != 20 {
+            if critical {
                 Err(decode::Malformed.into())
             }
             else {
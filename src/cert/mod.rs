@@ -18,10 +18,13 @@
 //! [RFC 6487]: https://tools.ietf.org/html/rfc5487
 
 pub use self::builder::CertBuilder;
+pub use self::router::{RouterCertBuilder, RouterKey};
+pub use crate::x509::Name;
 
 
 pub mod builder;
 pub mod ext;
+pub mod router;
 
 use std::{borrow, ops};
 use std::iter::FromIterator;
@@ -33,17 +36,19 @@ use bcder::{
     BitString, Captured, ConstOid, Ia5String, Mode, OctetString, Oid, Tag
 };
 use bytes::Bytes;
+use log::debug;
 use serde::{Serialize, Serializer, Deserialize, Deserializer};
-use crate::oid;
+use crate::{locate, oid};
 use crate::resources::{AsBlocks, IpBlocks};
 use crate::tal::TalInfo;
 use crate::uri;
 use crate::x509::{
-    Name, SignedData, Serial, Time, Validity, ValidationError,
+    DecodeMode, SignedData, Serial, Time, Validity, ValidationError,
     encode_extension, update_first, update_once
 };
 use crate::crypto::{
-    KeyIdentifier, PublicKey, SignatureAlgorithm, Signer, SigningError
+    AlgorithmPolicy, KeyIdentifier, PublicKey, SignatureAlgorithm, Signer,
+    SigningError
 };
 use crate::resources::{
     AddressFamily, AsBlock, AsBlocksBuilder, AsResources, AsResourcesBuilder,
@@ -102,8 +107,33 @@ pub struct Cert {
 ///
 impl Cert {
     /// Decodes a source as a certificate.
+    ///
+    /// Uses the default decoding mode.
     pub fn decode<S: decode::Source>(source: S) -> Result<Self, S::Err> {
-        Mode::Der.decode(source, Self::take_from)
+        Self::decode_with_mode(source, DecodeMode::default())
+    }
+
+    /// Decodes a source as a certificate using the given decoding mode.
+    pub fn decode_with_mode<S: decode::Source>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        Mode::Der.decode(source, |cons| Self::take_from_with_mode(cons, mode))
+    }
+
+    /// Decodes a certificate, locating any decoding error.
+    ///
+    /// This is the same as [`decode_with_mode`] but the error includes the
+    /// byte offset and ASN.1 path at which decoding failed.
+    ///
+    /// [`decode_with_mode`]: #method.decode_with_mode
+    pub fn decode_located(
+        data: Bytes,
+        mode: DecodeMode,
+    ) -> Result<Self, locate::Error> {
+        locate::decode_cert(data, |source| {
+            Self::decode_with_mode(source, mode)
+        })
     }
 
     /// Takes an encoded certificate from the beginning of a value.
@@ -113,16 +143,31 @@ impl Cert {
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
-        cons.take_sequence(Self::from_constructed)
+        Self::take_from_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Takes an encoded certificate using the given decoding mode.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(|cons| Self::from_constructed_with_mode(cons, mode))
     }
 
     /// Parses the content of a Certificate sequence.
     pub fn from_constructed<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
-        let signed_data = SignedData::from_constructed(cons)?;
-        let tbs = signed_data.data().clone().decode(
-            TbsCert::from_constructed
+        Self::from_constructed_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Parses the content of a Certificate sequence using the given mode.
+    pub fn from_constructed_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        let (signed_data, tbs) = SignedData::from_constructed_with(
+            cons, |cons| TbsCert::from_constructed_with_mode(cons, mode)
         )?;
         Ok(Self { signed_data, tbs })
     }
@@ -136,6 +181,47 @@ impl Cert {
     pub fn to_captured(&self) -> Captured {
         Captured::from_values(Mode::Der, self.encode_ref())
     }
+
+    /// Returns the canonical encoding of the certificate.
+    ///
+    /// Certificates are always decoded as DER and those created by this
+    /// crate are in canonical form: resources are ordered and merged and
+    /// extensions are always encoded in the same order. Thus, two
+    /// certificates with identical content signed with the same key
+    /// result in the same octets.
+    pub fn canonical(&self) -> Bytes {
+        self.to_captured().into_bytes()
+    }
+
+    /// Returns all extensions of the certificate.
+    ///
+    /// Unlike the certificate itself, which only keeps the information
+    /// needed for validation, the returned value provides every extension
+    /// including the raw content of non-critical extensions not defined
+    /// by the RPKI profile. Since [`ResourceCert`] dereferences to a
+    /// certificate, this is available for validated certificates, too.
+    ///
+    /// The extensions are decoded anew from the encoded certificate on
+    /// every call. Decoding may fail for certificates that don’t follow
+    /// the RPKI profile even if they were accepted when decoding the
+    /// certificate.
+    ///
+    /// [`ResourceCert`]: struct.ResourceCert.html
+    pub fn extensions(&self) -> Result<ext::Extensions, decode::Error> {
+        self.signed_data.data().clone().decode(|cons| {
+            cons.take_sequence(|cons| {
+                cons.take_constructed_if(Tag::CTX_0, |c| c.skip_u8_if(2))?;
+                // serialNumber, signature, issuer, validity, subject, and
+                // subjectPublicKeyInfo.
+                for _ in 0..6 {
+                    cons.skip_one()?;
+                }
+                cons.take_constructed_if(
+                    Tag::CTX_3, ext::Extensions::take_from
+                )
+            })
+        })
+    }
 }
 
 
@@ -161,7 +247,26 @@ impl Cert {
         strict: bool,
         now: Time,
     ) -> Result<ResourceCert, ValidationError> {
-        self.validate_basics(strict, now)?;
+        self.validate_ta_with_policy_at(
+            tal, Arc::new(AlgorithmPolicy::default()), strict, now
+        )
+    }
+
+    /// Validates the certificate as a trust anchor using an algorithm
+    /// policy.
+    ///
+    /// The trust anchor certificate and all objects validated with the
+    /// returned resource certificate or its descendants are checked
+    /// against the algorithms allowed by `policy`. The other validation
+    /// methods use the default policy for trust anchors.
+    pub fn validate_ta_with_policy_at(
+        self,
+        tal: Arc<TalInfo>,
+        policy: Arc<AlgorithmPolicy>,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, ValidationError> {
+        self.validate_basics(&policy, strict, now)?;
         self.validate_ca_basics(strict)?;
 
         // 4.8.3. Authority Key Identifier. May be present, if so, must be
@@ -206,7 +311,8 @@ impl Cert {
             v4_resources,
             v6_resources,
             as_resources,
-            tal
+            tal,
+            algorithms: policy,
         })
     }
 
@@ -230,7 +336,7 @@ impl Cert {
         strict: bool,
         now: Time,
     ) -> Result<ResourceCert, ValidationError> {
-        self.validate_basics(strict, now)?;
+        self.validate_basics(issuer.algorithm_policy(), strict, now)?;
         self.validate_ca_basics(strict)?;
         self.validate_issued(issuer, strict)?;
         self.validate_signature(issuer, strict)?;
@@ -257,31 +363,137 @@ impl Cert {
         strict: bool,
         now: Time,
     ) -> Result<ResourceCert, ValidationError>  {
-        self.validate_basics(strict, now)?;
+        self.validate_ee_basics(issuer, strict, now)?;
+
+        // 4.8.8.  Subject Information Access. We need the signed object
+        // but not the other ones.
+        if self.ca_repository.is_some() || self.rpki_manifest.is_some()
+            || self.signed_object.is_none()
+        {
+            return Err(ValidationError)
+        }
+        if self.rpki_notify.is_some() && strict {
+            return Err(ValidationError)
+        }
+
+        self.validate_signature(issuer, strict)?;
+        self.validate_resources(issuer, strict)
+    }
+
+    /// Validates the certificate as an EE certificate of a detached object.
+    ///
+    /// Detached objects such as signed checklists defined in [RFC 9323]
+    /// are not published in the RPKI repository. Their EE certificates
+    /// differ from those of other signed objects in that the Subject
+    /// Information Access extension is omitted.
+    ///
+    /// For validation to succeed, the certificate needs to have been signed
+    /// by the provided `issuer` certificate.
+    ///
+    /// Note that this does _not_ check the CRL.
+    ///
+    /// [RFC 9323]: https://tools.ietf.org/html/rfc9323
+    pub fn validate_detached_ee(
+        self,
+        issuer: &ResourceCert,
+        strict: bool
+    ) -> Result<ResourceCert, ValidationError>  {
+        self.validate_detached_ee_at(issuer, strict, Time::now())
+    }
+
+    pub fn validate_detached_ee_at(
+        self,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, ValidationError>  {
+        self.validate_ee_basics(issuer, strict, now)?;
+
+        // RFC 9323, section 2: Subject Information Access is omitted.
+        if self.ca_repository.is_some() || self.rpki_manifest.is_some()
+            || self.signed_object.is_some() || self.rpki_notify.is_some()
+        {
+            return Err(ValidationError)
+        }
+
+        self.validate_signature(issuer, strict)?;
+        self.validate_resources(issuer, strict)
+    }
+
+    /// Validates the certificate as a BGPsec router certificate.
+    ///
+    /// Router certificates are defined in [RFC 8209]. They are EE
+    /// certificates that certify a router’s ECDSA P-256 key for a set of
+    /// autonomous systems. They carry the BGPsec router extended key usage,
+    /// no subject information access, and AS resources only.
+    ///
+    /// For validation to succeed, the certificate needs to have been signed
+    /// by the provided `issuer` certificate. The router keys can be taken
+    /// from the returned certificate via [`RouterKey::from_cert`].
+    ///
+    /// Note that this does _not_ check the CRL.
+    ///
+    /// [RFC 8209]: https://tools.ietf.org/html/rfc8209
+    /// [`RouterKey::from_cert`]: router/struct.RouterKey.html#method.from_cert
+    pub fn validate_router(
+        self,
+        issuer: &ResourceCert,
+        strict: bool
+    ) -> Result<ResourceCert, ValidationError>  {
+        self.validate_router_at(issuer, strict, Time::now())
+    }
+
+    pub fn validate_router_at(
+        self,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, ValidationError>  {
+        self.validate_common(issuer.algorithm_policy(), strict, now)?;
         self.validate_issued(issuer, strict)?;
 
-        // 4.8.1. Basic Constraints: Must not be present.
-        if self.basic_ca.is_some(){
+        // RFC 8208, section 3.1: The key must be an ECDSA P-256 key. This
+        // is the default for the algorithm policy.
+        issuer.algorithm_policy().check_router_key(
+            &self.subject_public_key_info
+        )?;
+
+        // RFC 8209, section 3.1.3.1: Basic Constraints must not be present.
+        if self.basic_ca.is_some() {
             return Err(ValidationError)
         }
 
-        // 4.8.4. Key Usage. Bits for CA or not CA have been checked during
-        // parsing already.
+        // Key Usage: digitalSignature only, i.e., same as EE certificates.
         if self.key_usage != KeyUsage::Ee {
             return Err(ValidationError)
         }
 
-        // 4.8.8.  Subject Information Access. We need the signed object
-        // but not the other ones.
+        // RFC 8209, section 3.1.3.2: Extended Key Usage must be present and
+        // contain id-kp-bgpsec-router.
+        if !self.is_bgpsec_router() {
+            return Err(ValidationError)
+        }
+
+        // RFC 8209, section 3.1.3: Subject Information Access is not
+        // included.
         if self.ca_repository.is_some() || self.rpki_manifest.is_some()
-            || self.signed_object.is_none()
+            || self.signed_object.is_some() || self.rpki_notify.is_some()
         {
             return Err(ValidationError)
         }
-        if self.rpki_notify.is_some() && strict {
+
+        // RFC 8209, section 3.1.3.4: IP Resources must not be present.
+        if self.v4_resources.is_some() || self.v6_resources.is_some() {
             return Err(ValidationError)
         }
 
+        // RFC 8209, section 3.1.3.5: AS Resources must be present and
+        // must not be “inherit”.
+        match self.as_resources {
+            Some(ref res) if !res.is_inherited() => { }
+            _ => return Err(ValidationError)
+        }
+
         self.validate_signature(issuer, strict)?;
         self.validate_resources(issuer, strict)
     }
@@ -292,6 +504,35 @@ impl Cert {
     /// Validates basic compliance with section 4 of RFC 6487.
     fn validate_basics(
         &self,
+        policy: &AlgorithmPolicy,
+        strict: bool,
+        now: Time
+    ) -> Result<(), ValidationError> {
+        self.validate_common(policy, strict, now)?;
+
+        // 4.7 Subject Public Key Info: limited algorithms. RFC 7935 only
+        // allows RSA keys and further limits their size to 2048 bits. In
+        // relaxed mode, we allow any size we can verify signatures for.
+        // The algorithms are taken from the policy which defaults to RFC
+        // 7935.
+        policy.check_key(&self.subject_public_key_info, strict)?;
+
+        // 4.8.5. Extended Key Usage. Must not be present for the kind of
+        // certificates we use here.
+        if self.extended_key_usage().is_some() {
+            return Err(ValidationError)
+        }
+
+        Ok(())
+    }
+
+    /// Validates the parts of section 4 of RFC 6487 shared by all certs.
+    ///
+    /// This excludes the subject public key info and the extended key
+    /// usage which differ for BGPsec router certificates.
+    fn validate_common(
+        &self,
+        policy: &AlgorithmPolicy,
         strict: bool,
         now: Time
     ) -> Result<(), ValidationError> {
@@ -309,6 +550,7 @@ impl Cert {
         if self.signature != self.signed_data.signature().algorithm() {
             return Err(ValidationError)
         }
+        policy.check_signature(self.signature)?;
 
         // 4.4 Issuer: must have certain format. 
         Name::validate_rpki(&self.issuer, strict)?;
@@ -319,8 +561,8 @@ impl Cert {
         // 4.6 Validity. Check according to RFC 5280.
         self.validity.validate_at(now)?;
 
-        // 4.7 Subject Public Key Info: limited algorithms. Already checked
-        // during parsing.
+        // 4.7 Subject Public Key Info: limited algorithms. Differs between
+        // router certificates and all others.
 
         // 4.8.1. Basic Constraints. Differing requirements for CA and EE
         // certificates.
@@ -337,11 +579,8 @@ impl Cert {
 
         // 4.8.4. Key Usage. Differs between CA and EE certificates.
 
-        // 4.8.5. Extended Key Usage. Must not be present for the kind of
-        // certificates we use here.
-        if self.extended_key_usage().is_some() {
-            return Err(ValidationError)
-        }
+        // 4.8.5. Extended Key Usage. Differs between router certificates
+        // and all others.
 
         // 4.8.6. CRL Distribution Points. Differs between TA and other
         // certificates.
@@ -394,6 +633,30 @@ impl Cert {
         Ok(())
     }
 
+    /// Validates the parts common to all EE certificates of signed objects.
+    fn validate_ee_basics(
+        &self,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<(), ValidationError> {
+        self.validate_basics(issuer.algorithm_policy(), strict, now)?;
+        self.validate_issued(issuer, strict)?;
+
+        // 4.8.1. Basic Constraints: Must not be present.
+        if self.basic_ca.is_some(){
+            return Err(ValidationError)
+        }
+
+        // 4.8.4. Key Usage. Bits for CA or not CA have been checked during
+        // parsing already.
+        if self.key_usage != KeyUsage::Ee {
+            return Err(ValidationError)
+        }
+
+        Ok(())
+    }
+
     /// Validates that the certificate is a valid CA certificate.
     ///
     /// Checks the parts that are common in normal and trust anchor CA
@@ -459,6 +722,7 @@ impl Cert {
             )?,
             cert: self,
             tal: issuer.tal.clone(),
+            algorithms: issuer.algorithms.clone(),
         })
     }
 }
@@ -653,11 +917,17 @@ impl TbsCert {
     }
 
     /// Converts the value into a signed certificate.
+    ///
+    /// Returns an incompatible key error if the signer doesn’t support the
+    /// certificate’s signature algorithm.
     pub fn into_cert<S: Signer>(
         self,
         signer: &S,
         key: &S::KeyId,
     ) -> Result<Cert, SigningError<S::Error>> {
+        if !signer.supports(self.signature) {
+            return Err(SigningError::IncompatibleKey)
+        }
         let data = Captured::from_values(Mode::Der, self.encode_ref());
         let signature = signer.sign(key, self.signature, &data)?;
         Ok(Cert {
@@ -676,6 +946,11 @@ impl TbsCert {
         self.serial_number
     }
 
+    /// Returns the algorithm used for signing the certificate.
+    pub fn signature(&self) -> SignatureAlgorithm {
+        self.signature
+    }
+
     /// Set the serial number of the certificate.
     pub fn set_serial_number<S: Into<Serial>>(&mut self, serial: S) {
         self.serial_number = serial.into()
@@ -774,12 +1049,32 @@ impl TbsCert {
 
     /// Returns a reference to the extended key usage if present.
     ///
-    /// Since this field isn’t allowed in any certificate used for RPKI
-    /// objects directly, we do not currently support setting this field.
+    /// This field is only allowed in BGPsec router certificates. The
+    /// captured value contains the encoded key purpose identifiers without
+    /// the surrounding sequence.
     pub fn extended_key_usage(&self) -> Option<&Captured> {
         self.extended_key_usage.as_ref()
     }
 
+    /// Sets the extended key usage.
+    ///
+    /// The captured value must contain the encoded key purpose identifiers
+    /// without the surrounding sequence.
+    pub fn set_extended_key_usage(&mut self, eku: Option<Captured>) {
+        self.extended_key_usage = eku
+    }
+
+    /// Returns whether this is a BGPsec router certificate.
+    ///
+    /// This is the case if the extended key usage contains the key purpose
+    /// `id-kp-bgpsec-router` defined in RFC 8209.
+    pub fn is_bgpsec_router(&self) -> bool {
+        match self.extended_key_usage {
+            Some(ref eku) => eku_has_bgpsec_router(eku),
+            None => false
+        }
+    }
+
     /// Returns a reference to the certificate’s CRL distribution point.
     pub fn crl_uri(&self) -> Option<&uri::Rsync> {
         self.crl_uri.as_ref()
@@ -956,6 +1251,17 @@ impl TbsCert {
     /// Parses the content of a Certificate sequence.
     pub fn from_constructed<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::from_constructed_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Parses the content of a Certificate sequence using the given mode.
+    ///
+    /// The mode decides how to deal with names and validity times that
+    /// violate the RPKI profile.
+    pub fn from_constructed_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Self, S::Err> {
         cons.take_sequence(|cons| {
             // version [0] EXPLICIT Version DEFAULT v1.
@@ -965,9 +1271,9 @@ impl TbsCert {
 
             let serial_number = Serial::take_from(cons)?;
             let signature = SignatureAlgorithm::x509_take_from(cons)?;
-            let issuer = Name::take_from(cons)?;
-            let validity = Validity::take_from(cons)?;
-            let subject = Name::take_from(cons)?;
+            let issuer = Name::take_from_with_mode(cons, mode)?;
+            let validity = Validity::take_from_with_mode(cons, mode)?;
+            let subject = Name::take_from_with_mode(cons, mode)?;
             let subject_public_key_info = PublicKey::take_from(cons)?;
 
             // issuerUniqueID and subjectUniqueID must not be present in
@@ -992,6 +1298,11 @@ impl TbsCert {
                     let id = Oid::take_from(cons)?;
                     let critical = cons.take_opt_bool()?.unwrap_or(false);
                     let value = OctetString::take_from(cons)?;
+                    if let Some(required) = Self::profile_criticality(&id) {
+                        if critical != required {
+                            xerr!(return Err(decode::Malformed.into()))
+                        }
+                    }
                     Mode::Der.decode(value.to_source(), |content| {
                         if id == oid::CE_BASIC_CONSTRAINTS {
                             Self::take_basic_constraints(
@@ -1036,6 +1347,10 @@ impl TbsCert {
                             as_overclaim = Some(m);
                             Self::take_as_resources(content, &mut as_resources)
                         } else if critical {
+                            debug!(
+                                "unknown critical extension {}",
+                                oid::named(&id)
+                            );
                             xerr!(Err(decode::Malformed))
                         } else {
                             // RFC 5280 says we can ignore non-critical
@@ -1062,15 +1377,12 @@ impl TbsCert {
                 Some(res) => res,
                 None => (None, None)
             };
-            let (ca_repository, rpki_manifest, signed_object, rpki_notify) = {
-                match sia {
-                    Some(sia) => (
-                        sia.ca_repository, sia.rpki_manifest,
-                        sia.signed_object, sia.rpki_notify
-                    ),
-                    None => (None, None, None, None)
-                }
-            };
+            // 4.8.8. Subject Information Access. Must be present in CA
+            // certificates and EE certificates of published objects but
+            // is absent in BGPsec router certificates (RFC 8209) and EE
+            // certificates of signed checklists (RFC 9323). The required
+            // access descriptions are checked during validation.
+            let sia = sia.unwrap_or_default();
 
             Ok(Self {
                 serial_number,
@@ -1087,10 +1399,10 @@ impl TbsCert {
                 extended_key_usage,
                 crl_uri,
                 ca_issuer,
-                ca_repository,
-                rpki_manifest,
-                signed_object,
-                rpki_notify,
+                ca_repository: sia.ca_repository,
+                rpki_manifest: sia.rpki_manifest,
+                signed_object: sia.signed_object,
+                rpki_notify: sia.rpki_notify,
                 overclaim: overclaim.ok_or(decode::Malformed)?,
                 v4_resources,
                 v6_resources,
@@ -1099,6 +1411,14 @@ impl TbsCert {
         })
     }
 
+    /// Returns the criticality RFC 6487 requires for an extension.
+    ///
+    /// Returns `None` for extensions not covered by the profile. These are
+    /// either accepted if not critical or rejected if critical.
+    fn profile_criticality(id: &Oid<Bytes>) -> Option<bool> {
+        PROFILE_CRITICALITY.get(id).cloned()
+    }
+
     /// Parses the Basic Constraints extension.
     ///
     /// ```text
@@ -1137,7 +1457,7 @@ impl TbsCert {
     /// the value of the DER-encoded bit string of the subject public key.
     ///
     /// Conforming CAs MUST mark this extension as non-critical.
-    fn take_subject_key_identifier<S: decode::Source>(
+    pub(crate) fn take_subject_key_identifier<S: decode::Source>(
         cons: &mut decode::Constructed<S>,
         subject_key_id: &mut Option<KeyIdentifier>,
     ) -> Result<(), S::Err> {
@@ -1156,7 +1476,7 @@ impl TbsCert {
     /// Must be present except in self-signed CA certificates where it is
     /// optional. The keyIdentifier field must be present, the other must not
     /// be.
-    fn take_authority_key_identifier<S: decode::Source>(
+    pub(crate) fn take_authority_key_identifier<S: decode::Source>(
         cons: &mut decode::Constructed<S>,
         authority_key_id: &mut Option<KeyIdentifier>,
     ) -> Result<(), S::Err> {
@@ -1646,6 +1966,20 @@ where F: FnMut(Bytes) -> Result<T, E> {
     })
 }
 
+/// Returns whether an extended key usage contains the BGPsec router purpose.
+fn eku_has_bgpsec_router(eku: &Captured) -> bool {
+    eku.clone().decode(|cons| {
+        let mut res = false;
+        while let Some(purpose) = Oid::take_opt_from(cons)? {
+            if purpose == oid::KP_BGPSEC_ROUTER {
+                res = true
+            }
+        }
+        Ok(res)
+    }).unwrap_or(false)
+}
+
+
 /// Internal helper type for parsing Subject Information Access.
 #[derive(Clone, Debug, Default)]
 pub(crate) struct Sia {
@@ -1668,6 +2002,26 @@ impl Sia {
 }
 
 
+//------------ PROFILE_CRITICALITY -------------------------------------------
+
+/// The criticality RFC 6487 requires for the extensions it defines.
+const PROFILE_CRITICALITY: oid::OidTable<bool> = oid::OidTable::new(&[
+    (oid::PE_AUTHORITY_INFO_ACCESS, false),
+    (oid::PE_IP_ADDR_BLOCK, true),
+    (oid::PE_AUTONOMOUS_SYS_IDS, true),
+    (oid::PE_SUBJECT_INFO_ACCESS, false),
+    (oid::PE_IP_ADDR_BLOCK_V2, true),
+    (oid::PE_AUTONOMOUS_SYS_IDS_V2, true),
+    (oid::CE_SUBJECT_KEY_IDENTIFIER, false),
+    (oid::CE_KEY_USAGE, true),
+    (oid::CE_BASIC_CONSTRAINTS, true),
+    (oid::CE_CRL_DISTRIBUTION_POINTS, false),
+    (oid::CE_CERTIFICATE_POLICIES, true),
+    (oid::CE_AUTHORITY_KEY_IDENTIFIER, false),
+    (oid::CE_EXTENDED_KEY_USAGE, false),
+]);
+
+
 //------------ ResourceCert --------------------------------------------------
 
 /// A validated resource certificate.
@@ -1690,6 +2044,9 @@ pub struct ResourceCert {
 
     /// The TAL this is based on.
     tal: Arc<TalInfo>,
+
+    /// The algorithms allowed for objects issued under this certificate.
+    algorithms: Arc<AlgorithmPolicy>,
 }
 
 impl ResourceCert {
@@ -1713,6 +2070,29 @@ impl ResourceCert {
         &self.as_resources
     }
 
+    /// Returns the effective resources of this certificate.
+    ///
+    /// These are the resources after resolving “inherit” elements via the
+    /// certificate’s ancestors and, if the certificate uses the reconsidered
+    /// validation algorithm, after trimming them to its issuer’s
+    /// resources. Checks of objects issued under the certificate, such as
+    /// ROAs or ASPAs, need to use these rather than the resources as
+    /// encoded in the certificate.
+    pub fn effective_resources(&self) -> EffectiveResources {
+        let cert = self.as_cert();
+        EffectiveResources {
+            v4: self.v4_resources.clone(),
+            v6: self.v6_resources.clone(),
+            asn: self.as_resources.clone(),
+            v4_inherited: cert.v4_resources().map(IpResources::is_inherited)
+                .unwrap_or(false),
+            v6_inherited: cert.v6_resources().map(IpResources::is_inherited)
+                .unwrap_or(false),
+            as_inherited: cert.as_resources().map(AsResources::is_inherited)
+                .unwrap_or(false),
+        }
+    }
+
     /// Returns information about the TAL this certificate is based on.
     pub fn tal(&self) -> &Arc<TalInfo> {
         &self.tal
@@ -1722,6 +2102,13 @@ impl ResourceCert {
     pub fn into_tal(self) -> Arc<TalInfo> {
         self.tal
     }
+
+    /// Returns the algorithm policy for objects issued under the cert.
+    ///
+    /// The policy is inherited from the trust anchor certificate.
+    pub fn algorithm_policy(&self) -> &AlgorithmPolicy {
+        &self.algorithms
+    }
 }
 
 
@@ -1748,6 +2135,75 @@ impl AsRef<TbsCert> for ResourceCert {
 }
 
 
+//------------ EffectiveResources --------------------------------------------
+
+/// The resources a validated certificate is effectively entitled to.
+///
+/// Values of this type are returned by
+/// [`ResourceCert::effective_resources`]. In addition to the resolved
+/// resources, they keep track of which of them have been inherited from
+/// the certificate’s issuer.
+///
+/// [`ResourceCert::effective_resources`]: struct.ResourceCert.html#method.effective_resources
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EffectiveResources {
+    /// The IPv4 resources.
+    v4: IpBlocks,
+
+    /// The IPv6 resources.
+    v6: IpBlocks,
+
+    /// The AS resources.
+    asn: AsBlocks,
+
+    /// Whether the IPv4 resources were inherited.
+    v4_inherited: bool,
+
+    /// Whether the IPv6 resources were inherited.
+    v6_inherited: bool,
+
+    /// Whether the AS resources were inherited.
+    as_inherited: bool,
+}
+
+impl EffectiveResources {
+    /// Returns the IPv4 resources.
+    pub fn v4(&self) -> &IpBlocks {
+        &self.v4
+    }
+
+    /// Returns the IPv6 resources.
+    pub fn v6(&self) -> &IpBlocks {
+        &self.v6
+    }
+
+    /// Returns the AS resources.
+    pub fn asn(&self) -> &AsBlocks {
+        &self.asn
+    }
+
+    /// Returns whether the IPv4 resources were inherited from the issuer.
+    pub fn v4_inherited(&self) -> bool {
+        self.v4_inherited
+    }
+
+    /// Returns whether the IPv6 resources were inherited from the issuer.
+    pub fn v6_inherited(&self) -> bool {
+        self.v6_inherited
+    }
+
+    /// Returns whether the AS resources were inherited from the issuer.
+    pub fn as_inherited(&self) -> bool {
+        self.as_inherited
+    }
+
+    /// Returns whether there are no resources at all.
+    pub fn is_empty(&self) -> bool {
+        self.v4.is_empty() && self.v6.is_empty() && self.asn.is_empty()
+    }
+}
+
+
 //------------ KeyUsage ------------------------------------------------------
 
 /// The allowed key usages of a resource certificate.
@@ -1874,6 +2330,77 @@ mod test {
         ).unwrap();
     }
 
+    #[test]
+    fn extensions() {
+        let cert = Cert::decode(
+            include_bytes!("../../test-data/ca1.cer").as_ref()
+        ).unwrap();
+        let ext = cert.extensions().unwrap();
+        assert_eq!(ext.subject_key_id(), cert.subject_key_identifier());
+        assert_eq!(ext.authority_key_id(), cert.authority_key_identifier());
+        assert_eq!(ext.basic_ca(), Some(true));
+        assert_eq!(ext.manifest_uri(), cert.rpki_manifest());
+        assert!(ext.unknown().is_empty());
+
+        // Add an unknown extension to the extensions of the certificate.
+        let known = cert.signed_data.data().clone().decode(|cons| {
+            cons.take_sequence(|cons| {
+                cons.take_constructed_if(Tag::CTX_0, |c| c.skip_u8_if(2))?;
+                for _ in 0..6 {
+                    cons.skip_one()?;
+                }
+                cons.take_constructed_if(Tag::CTX_3, |cons| {
+                    cons.take_sequence(|cons| cons.capture_all())
+                })
+            })
+        }).unwrap();
+        let encoded = Captured::from_values(Mode::Der, encode::sequence((
+            known,
+            encode::sequence((
+                Oid(&[42, 3, 4][..]).encode(),
+                b"\x05\x00".as_ref().encode(),
+            ))
+        )));
+        let ext = encoded.decode(ext::Extensions::take_from).unwrap();
+        assert_eq!(ext.unknown().len(), 1);
+        assert_eq!(
+            ext.unknown()[0].id(), &Oid(Bytes::from_static(&[42, 3, 4]))
+        );
+        assert_eq!(ext.unknown()[0].value().as_ref(), b"\x05\x00");
+    }
+
+    #[test]
+    fn algorithm_policy() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let ta = || {
+            Cert::decode(
+                include_bytes!("../../test-data/ta.cer").as_ref()
+            ).unwrap()
+        };
+        let tal = TalInfo::from_name("ripe".into()).into_arc();
+
+        let mut policy = AlgorithmPolicy::default();
+        policy.deny_signature_algorithm(SignatureAlgorithm::RsaSha256);
+        assert!(
+            ta().validate_ta_with_policy_at(
+                tal.clone(), Arc::new(policy), false, at
+            ).is_err()
+        );
+
+        let mut policy = AlgorithmPolicy::default();
+        policy.allow_signature_algorithm(
+            SignatureAlgorithm::EcdsaP256Sha256
+        );
+        let ta = ta().validate_ta_with_policy_at(
+            tal, Arc::new(policy.clone()), false, at
+        ).unwrap();
+        assert_eq!(*ta.algorithm_policy(), policy);
+        let ca = Cert::decode(
+            include_bytes!("../../test-data/ca1.cer").as_ref()
+        ).unwrap().validate_ca_at(&ta, false, at).unwrap();
+        assert_eq!(*ca.algorithm_policy(), policy);
+    }
+
     #[test]
     fn serde_cert() {
         let der = include_bytes!("../../test-data/ta.cer");
@@ -1885,6 +2412,51 @@ mod test {
         assert_eq!(cert.to_captured().into_bytes(), des_cert.to_captured().into_bytes());
 
     }
+
+    #[test]
+    fn profile() {
+        let cert = Cert::decode(
+            include_bytes!("../../test-data/ta.cer").as_ref()
+        ).unwrap();
+        assert_eq!(cert.subject_public_key_info().key_size(), Some(2048));
+
+        // Key Usage must be critical.
+        let mut der = include_bytes!("../../test-data/ta.cer").to_vec();
+        let pos = der.windows(8).position(|window| {
+            window == b"\x06\x03\x55\x1d\x0f\x01\x01\xff"
+        }).unwrap();
+        der[pos + 7] = 0;
+        assert!(Cert::decode(der.as_slice()).is_err());
+    }
+
+    #[test]
+    fn decode_mode() {
+        // Change the string type of the issuer’s common name to
+        // UTF8String.
+        let mut der = include_bytes!("../../test-data/ta.cer").to_vec();
+        let pos = der.windows(5).position(|window| {
+            window == b"\x06\x03\x55\x04\x03"
+        }).unwrap();
+        assert_eq!(der[pos + 5], 0x13);
+        der[pos + 5] = 0x0c;
+        assert!(
+            Cert::decode_with_mode(der.as_slice(), DecodeMode::Strict).is_err()
+        );
+        assert!(
+            Cert::decode_with_mode(der.as_slice(), DecodeMode::Warn).is_ok()
+        );
+        assert!(
+            Cert::decode_with_mode(
+                der.as_slice(), DecodeMode::Lenient
+            ).is_ok()
+        );
+        assert!(Cert::decode(der.as_slice()).is_ok());
+    }
+
+    #[test]
+    fn profile_criticality_sorted() {
+        assert!(PROFILE_CRITICALITY.is_sorted());
+    }
 }
 
 #[cfg(all(test, feature="softkeys"))]
@@ -1920,5 +2492,59 @@ mod signer_test {
         let talinfo = TalInfo::from_name("foo".into()).into_arc();
         cert.validate_ta(talinfo, true).unwrap();
     }
+
+    #[test]
+    fn effective_resources() {
+        let mut signer = OpenSslSigner::new();
+        let ta_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let ta_pubkey = signer.get_key_info(&ta_key).unwrap();
+        let ca_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let ca_pubkey = signer.get_key_info(&ca_key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+
+        let mut ta = TbsCert::new(
+            12u64.into(), ta_pubkey.to_subject_name(),
+            Validity::from_secs(86400), None, ta_pubkey.clone(), KeyUsage::Ca,
+            Overclaim::Trim
+        );
+        ta.set_basic_ca(Some(true));
+        ta.set_ca_repository(Some(uri.clone()));
+        ta.set_rpki_manifest(Some(uri.clone()));
+        ta.build_v4_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        ta.build_v6_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        ta.build_as_resource_blocks(|b| b.push((AsId::MIN, AsId::MAX)));
+        let ta = ta.into_cert(&signer, &ta_key).unwrap().validate_ta(
+            TalInfo::from_name("foo".into()).into_arc(), true
+        ).unwrap();
+        let res = ta.effective_resources();
+        assert!(!res.v4_inherited() && !res.is_empty());
+
+        let mut ca = TbsCert::new(
+            13u64.into(), ta_pubkey.to_subject_name(),
+            Validity::from_secs(86400), None, ca_pubkey, KeyUsage::Ca,
+            Overclaim::Trim
+        );
+        ca.set_basic_ca(Some(true));
+        ca.set_authority_key_identifier(Some(ta_pubkey.key_identifier()));
+        ca.set_crl_uri(Some(uri.clone()));
+        ca.set_ca_issuer(Some(uri.clone()));
+        ca.set_ca_repository(Some(uri.clone()));
+        ca.set_rpki_manifest(Some(uri));
+        ca.set_v4_resources_inherit();
+        ca.set_v6_resources(Some(
+            IpResources::from_str("2001:db8::/32").unwrap()
+        ));
+        let ca = ca.into_cert(&signer, &ta_key).unwrap();
+        let ca = Cert::decode(ca.to_captured().as_slice()).unwrap();
+        let ca = ca.validate_ca(&ta, true).unwrap();
+
+        let res = ca.effective_resources();
+        assert!(res.v4_inherited());
+        assert_eq!(res.v4(), ta.v4_resources());
+        assert!(!res.v6_inherited());
+        assert_eq!(res.v6(), &IpBlocks::from_str("2001:db8::/32").unwrap());
+        assert!(!res.as_inherited());
+        assert!(res.asn().is_empty());
+    }
 }
 
@@ -0,0 +1,344 @@
+//! BGPsec router certificates.
+//!
+//! Router certificates are defined in [RFC 8209] as a profile of resource
+//! certificates. They are EE certificates that bind an ECDSA P-256 key held
+//! by a BGPsec speaking router to one or more autonomous systems. Unlike
+//! all other certificates in the RPKI, they are not used to sign any RPKI
+//! objects and are therefore published directly by their CA.
+//!
+//! The certificates themselves are represented by the ordinary [`Cert`]
+//! type and validated via [`Cert::validate_router`]. This module provides
+//! [`RouterCertBuilder`] for issuing router certificates and [`RouterKey`]
+//! for the information relying parties hand to routers, e.g., via the
+//! router key PDU of the RPKI-to-Router protocol.
+//!
+//! [`Cert`]: ../struct.Cert.html
+//! [`Cert::validate_router`]: ../struct.Cert.html#method.validate_router
+//! [`RouterCertBuilder`]: struct.RouterCertBuilder.html
+//! [`RouterKey`]: struct.RouterKey.html
+//! [RFC 8209]: https://tools.ietf.org/html/rfc8209
+
+use bcder::Mode;
+use bcder::encode::{PrimitiveContent, Values};
+use bytes::Bytes;
+use crate::oid;
+use crate::uri;
+use crate::crypto::{
+    KeyIdentifier, PublicKey, PublicKeyFormat, Signer, SigningError
+};
+use crate::resources::{AsBlocks, AsId, AsResources};
+use crate::x509::{Name, Serial, Validity};
+use super::{Cert, KeyUsage, Overclaim, ResourceCert, TbsCert};
+
+
+//------------ RouterKey -----------------------------------------------------
+
+/// A router key for a single autonomous system.
+///
+/// This is the output of validating a BGPsec router certificate. It
+/// contains the same information as the router key PDU of the
+/// RPKI-to-Router protocol defined in [RFC 8210]: the AS number, the
+/// subject key identifier of the router certificate, and the router’s
+/// public key as an encoded *SubjectPublicKeyInfo*.
+///
+/// [RFC 8210]: https://tools.ietf.org/html/rfc8210
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RouterKey {
+    /// The AS number the key is valid for.
+    asn: AsId,
+
+    /// The subject key identifier of the router certificate.
+    key_identifier: KeyIdentifier,
+
+    /// The encoded subject public key info of the router key.
+    key_info: Bytes,
+}
+
+impl RouterKey {
+    /// Creates a new router key from its components.
+    pub fn new(
+        asn: AsId, key_identifier: KeyIdentifier, key_info: Bytes
+    ) -> Self {
+        RouterKey { asn, key_identifier, key_info }
+    }
+
+    /// Returns the router keys certified by a validated router certificate.
+    ///
+    /// The certificate should have been validated via
+    /// `Cert::validate_router`. One router key is returned for every AS
+    /// number in the certificate’s AS resources.
+    pub fn from_cert<'a>(
+        cert: &'a ResourceCert
+    ) -> impl Iterator<Item = RouterKey> + 'a {
+        let key_identifier = cert.subject_key_identifier();
+        let key_info = cert.subject_public_key_info().to_info_bytes();
+        cert.as_resources().iter().flat_map(move |block| {
+            let key_info = key_info.clone();
+            (u32::from(block.min())..=u32::from(block.max())).map(move |asn| {
+                RouterKey::new(asn.into(), key_identifier, key_info.clone())
+            })
+        })
+    }
+
+    /// Returns the AS number the key is valid for.
+    pub fn asn(&self) -> AsId {
+        self.asn
+    }
+
+    /// Returns the subject key identifier of the router certificate.
+    pub fn key_identifier(&self) -> KeyIdentifier {
+        self.key_identifier
+    }
+
+    /// Returns the encoded *SubjectPublicKeyInfo* of the router key.
+    pub fn key_info(&self) -> &Bytes {
+        &self.key_info
+    }
+}
+
+
+//------------ RouterCertBuilder ---------------------------------------------
+
+/// A builder for BGPsec router certificates.
+///
+/// The builder is created with the mandatory information of the
+/// certificate. The issuer and subject names can be changed from their
+/// defaults, which are the names derived from the issuer’s and router’s
+/// public keys, respectively. The certificate is created via
+/// [`finalize`].
+///
+/// [`finalize`]: #method.finalize
+#[derive(Clone, Debug)]
+pub struct RouterCertBuilder {
+    /// The serial number of the certificate.
+    serial_number: Serial,
+
+    /// The validity of the certificate.
+    validity: Validity,
+
+    /// The issuer name or `None` to derive it from the issuer key.
+    issuer: Option<Name>,
+
+    /// The subject name or `None` to derive it from the router key.
+    subject: Option<Name>,
+
+    /// The URI of the CRL of the issuing CA.
+    crl_uri: uri::Rsync,
+
+    /// The URI of the issuing CA’s certificate.
+    ca_issuer: uri::Rsync,
+
+    /// The AS numbers the router key is certified for.
+    as_resources: AsBlocks,
+}
+
+impl RouterCertBuilder {
+    /// Creates a new builder.
+    pub fn new(
+        serial_number: Serial,
+        validity: Validity,
+        crl_uri: uri::Rsync,
+        ca_issuer: uri::Rsync,
+        as_resources: AsBlocks,
+    ) -> Self {
+        RouterCertBuilder {
+            serial_number,
+            validity,
+            issuer: None,
+            subject: None,
+            crl_uri,
+            ca_issuer,
+            as_resources,
+        }
+    }
+
+    /// Returns the serial number of the certificate.
+    pub fn serial_number(&self) -> Serial {
+        self.serial_number
+    }
+
+    /// Sets the serial number of the certificate.
+    pub fn set_serial_number(&mut self, serial: Serial) {
+        self.serial_number = serial
+    }
+
+    /// Returns the validity of the certificate.
+    pub fn validity(&self) -> Validity {
+        self.validity
+    }
+
+    /// Sets the validity of the certificate.
+    pub fn set_validity(&mut self, validity: Validity) {
+        self.validity = validity
+    }
+
+    /// Returns the issuer name if it has been set.
+    pub fn issuer(&self) -> Option<&Name> {
+        self.issuer.as_ref()
+    }
+
+    /// Sets the issuer name.
+    pub fn set_issuer(&mut self, name: Option<Name>) {
+        self.issuer = name
+    }
+
+    /// Returns the subject name if it has been set.
+    pub fn subject(&self) -> Option<&Name> {
+        self.subject.as_ref()
+    }
+
+    /// Sets the subject name.
+    pub fn set_subject(&mut self, name: Option<Name>) {
+        self.subject = name
+    }
+
+    /// Returns the CRL URI.
+    pub fn crl_uri(&self) -> &uri::Rsync {
+        &self.crl_uri
+    }
+
+    /// Sets the CRL URI.
+    pub fn set_crl_uri(&mut self, uri: uri::Rsync) {
+        self.crl_uri = uri
+    }
+
+    /// Returns the URI of the issuer’s certificate.
+    pub fn ca_issuer(&self) -> &uri::Rsync {
+        &self.ca_issuer
+    }
+
+    /// Sets the URI of the issuer’s certificate.
+    pub fn set_ca_issuer(&mut self, uri: uri::Rsync) {
+        self.ca_issuer = uri
+    }
+
+    /// Returns the AS numbers the router key is certified for.
+    pub fn as_resources(&self) -> &AsBlocks {
+        &self.as_resources
+    }
+
+    /// Sets the AS numbers the router key is certified for.
+    pub fn set_as_resources(&mut self, resources: AsBlocks) {
+        self.as_resources = resources
+    }
+
+    /// Creates the router certificate for the given router key.
+    ///
+    /// The certificate is signed by `issuer_key` of `signer`. Returns an
+    /// incompatible key error if `router_key` isn’t an ECDSA P-256 key.
+    pub fn finalize<S: Signer>(
+        self,
+        router_key: PublicKey,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<Cert, SigningError<S::Error>> {
+        if *router_key.algorithm() != PublicKeyFormat::EcdsaP256 {
+            return Err(SigningError::IncompatibleKey)
+        }
+        let issuer_pub = signer.get_key_info(issuer_key)?;
+        let mut cert = TbsCert::new(
+            self.serial_number,
+            self.issuer.unwrap_or_else(|| issuer_pub.to_subject_name()),
+            self.validity,
+            self.subject,
+            router_key,
+            KeyUsage::Ee,
+            Overclaim::Refuse,
+        );
+        cert.set_authority_key_identifier(Some(issuer_pub.key_identifier()));
+        cert.set_extended_key_usage(Some(
+            oid::KP_BGPSEC_ROUTER.encode().to_captured(Mode::Der)
+        ));
+        cert.set_crl_uri(Some(self.crl_uri));
+        cert.set_ca_issuer(Some(self.ca_issuer));
+        cert.set_as_resources(Some(AsResources::blocks(self.as_resources)));
+        cert.into_cert(signer, issuer_key)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use bcder::encode::Values;
+    use crate::cert::CertBuilder;
+    use crate::crypto::SignatureAlgorithm;
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::resources::{AsBlock, Prefix};
+    use crate::tal::TalInfo;
+    use super::*;
+
+    fn make_ta(
+        signer: &OpenSslSigner,
+        key: &<OpenSslSigner as Signer>::KeyId
+    ) -> ResourceCert {
+        let pubkey = signer.get_key_info(key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+        let mut builder = CertBuilder::new(
+            1, pubkey.to_subject_name(), Validity::from_secs(86400), true
+        );
+        builder
+            .ca_repository(uri.clone())
+            .rpki_manifest(uri)
+            .v4_blocks(|blocks| blocks.push(Prefix::new(0, 0)))
+            .as_blocks(|blocks| blocks.push((AsId::MIN, AsId::MAX)));
+        let captured = builder.encode(
+            signer, key, SignatureAlgorithm::default(), &pubkey
+        ).unwrap().to_captured(Mode::Der);
+        let cert = Cert::decode(captured.as_slice()).unwrap();
+        let talinfo = TalInfo::from_name("foo".into()).into_arc();
+        cert.validate_ta(talinfo, true).unwrap()
+    }
+
+    #[test]
+    fn router_cert() {
+        let mut signer = OpenSslSigner::new();
+        let ta_key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let ta = make_ta(&signer, &ta_key);
+        let router_key = signer.create_key(
+            PublicKeyFormat::EcdsaP256
+        ).unwrap();
+        let router_pub = signer.get_key_info(&router_key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+
+        let builder = RouterCertBuilder::new(
+            12u64.into(), Validity::from_secs(86400), uri.clone(), uri,
+            vec![
+                AsBlock::from(AsId::from(64496)),
+                AsBlock::from((AsId::from(64510), AsId::from(64511))),
+            ].into_iter().collect()
+        );
+        let cert = builder.finalize(
+            router_pub.clone(), &signer, &ta_key
+        ).unwrap();
+        let cert = Cert::decode(
+            cert.to_captured().as_slice()
+        ).unwrap();
+        assert!(cert.is_bgpsec_router());
+        assert!(cert.clone().validate_ee(&ta, true).is_err());
+
+        let cert = cert.validate_router(&ta, true).unwrap();
+        let keys: Vec<_> = RouterKey::from_cert(&cert).collect();
+        assert_eq!(
+            keys.iter().map(RouterKey::asn).collect::<Vec<_>>(),
+            [64496.into(), 64510.into(), 64511.into()]
+        );
+        assert_eq!(keys[0].key_identifier(), router_pub.key_identifier());
+        assert_eq!(*keys[0].key_info(), router_pub.to_info_bytes());
+
+        // An RSA key must not be certified as a router key.
+        let builder = RouterCertBuilder::new(
+            13u64.into(), Validity::from_secs(86400),
+            uri::Rsync::from_str("rsync://example.com/m/p").unwrap(),
+            uri::Rsync::from_str("rsync://example.com/m/p").unwrap(),
+            vec![AsBlock::from(AsId::from(64496))].into_iter().collect()
+        );
+        assert!(
+            builder.finalize(
+                signer.get_key_info(&ta_key).unwrap(), &signer, &ta_key
+            ).is_err()
+        );
+    }
+}
@@ -0,0 +1,494 @@
+//! IPFS content identifiers.
+//!
+//! Objects stored in IPFS are identified by a content identifier or CID.
+//! A CID contains a multihash, i.e., a hash value prefixed with the hash
+//! algorithm used, plus, for version 1 CIDs, the version and a code for the
+//! format of the content. The textual form of version 1 CIDs starts with a
+//! multibase prefix determining the encoding of the rest. Version 0 CIDs
+//! always use base58btc and are recognizable by their "Qm" prefix.
+//!
+//! This module provides the types [`Cid`] and [`Multihash`] for dealing
+//! with CIDs properly rather than comparing their string representations.
+//! Only SHA-256 multihashes are supported since this is the only hash used
+//! for RPKI data in IPFS.
+//!
+//! [`Cid`]: struct.Cid.html
+//! [`Multihash`]: struct.Multihash.html
+
+use std::{error, fmt, str};
+use std::convert::TryFrom;
+use crate::crypto::DigestAlgorithm;
+
+
+//------------ Cid -----------------------------------------------------------
+
+/// An IPFS content identifier.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Cid {
+    /// The CID version.
+    version: Version,
+
+    /// The multicodec code of the content type.
+    codec: u64,
+
+    /// The hash of the content.
+    hash: Multihash,
+}
+
+impl Cid {
+    /// The multicodec code for MerkleDAG protobuf content.
+    pub const DAG_PB: u64 = 0x70;
+
+    /// The multicodec code for raw binary content.
+    pub const RAW: u64 = 0x55;
+
+    /// The multicodec code for libp2p public keys used by IPNS.
+    pub const LIBP2P_KEY: u64 = 0x72;
+
+    /// Creates a version 0 CID from a multihash.
+    ///
+    /// Version 0 CIDs always refer to MerkleDAG protobuf content.
+    pub fn v0(hash: Multihash) -> Self {
+        Cid { version: Version::V0, codec: Self::DAG_PB, hash }
+    }
+
+    /// Creates a version 1 CID from a content type and a multihash.
+    pub fn v1(codec: u64, hash: Multihash) -> Self {
+        Cid { version: Version::V1, codec, hash }
+    }
+
+    /// Returns the version of the CID.
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    /// Returns the multicodec code of the content type.
+    pub fn codec(&self) -> u64 {
+        self.codec
+    }
+
+    /// Returns the multihash of the content.
+    pub fn hash(&self) -> &Multihash {
+        &self.hash
+    }
+
+    /// Returns a version 1 CID for the same content.
+    pub fn to_v1(self) -> Self {
+        Cid { version: Version::V1, ..self }
+    }
+
+    /// Returns whether two CIDs refer to the same content.
+    ///
+    /// This is the case if their content type and multihash are identical.
+    /// Their version and encoding may differ.
+    pub fn same_content(&self, other: &Self) -> bool {
+        self.codec == other.codec && self.hash == other.hash
+    }
+
+    /// Decodes a CID from its binary representation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        // A version 0 CID is a bare SHA-256 multihash.
+        if bytes.len() == Multihash::LEN && bytes[0] == 0x12 {
+            return Multihash::from_bytes(bytes).map(Cid::v0)
+        }
+        let mut bytes = bytes;
+        if read_varint(&mut bytes)? != 1 {
+            return Err(Error::UnsupportedVersion)
+        }
+        let codec = read_varint(&mut bytes)?;
+        Ok(Cid::v1(codec, Multihash::from_bytes(bytes)?))
+    }
+
+    /// Returns the binary representation of the CID.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        if self.version == Version::V1 {
+            write_varint(1, &mut res);
+            write_varint(self.codec, &mut res);
+        }
+        res.extend_from_slice(&self.hash.to_bytes());
+        res
+    }
+}
+
+
+//--- FromStr and TryFrom
+
+impl str::FromStr for Cid {
+    type Err = Error;
+
+    /// Parses a CID from its textual representation.
+    ///
+    /// Version 0 CIDs are accepted in base58btc. Version 1 CIDs are
+    /// accepted with the multibase prefixes for base32, base36, base58btc,
+    /// and base16.
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if s.len() == 46 && s.starts_with("Qm") {
+            return Cid::from_bytes(&base58_decode(s.as_bytes())?)
+        }
+        let res = Cid::from_bytes(&multibase_decode(s)?)?;
+        if res.version == Version::V0 {
+            // Version 0 CIDs must not carry a multibase prefix.
+            return Err(Error::BadEncoding)
+        }
+        Ok(res)
+    }
+}
+
+impl<'a> TryFrom<&'a [u8]> for Cid {
+    type Error = Error;
+
+    fn try_from(bytes: &'a [u8]) -> Result<Self, Error> {
+        Cid::from_bytes(bytes)
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Cid {
+    /// Formats the CID in its canonical textual representation.
+    ///
+    /// This is base58btc without a prefix for version 0 and lower case
+    /// base32 with the prefix `b` for version 1.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.version {
+            Version::V0 => f.write_str(&base58_encode(&self.to_bytes())),
+            Version::V1 => {
+                write!(f, "b{}", base32_encode(&self.to_bytes()))
+            }
+        }
+    }
+}
+
+
+//------------ Version -------------------------------------------------------
+
+/// The version of a CID.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Version {
+    /// Version 0, a bare base58btc encoded SHA-256 multihash.
+    V0,
+
+    /// Version 1 with explicit version, content type, and multibase.
+    V1,
+}
+
+
+//------------ Multihash -----------------------------------------------------
+
+/// A SHA-256 multihash.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Multihash {
+    /// The SHA-256 digest.
+    digest: [u8; 32],
+}
+
+impl Multihash {
+    /// The multihash code for SHA-256.
+    pub const SHA2_256: u64 = 0x12;
+
+    /// The length of the binary representation.
+    const LEN: usize = 34;
+
+    /// Creates a multihash from a SHA-256 digest.
+    pub fn new(digest: [u8; 32]) -> Self {
+        Multihash { digest }
+    }
+
+    /// Creates the multihash for the given data.
+    pub fn sha256(data: &[u8]) -> Self {
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(
+            DigestAlgorithm::default().digest(data).as_ref()
+        );
+        Multihash { digest }
+    }
+
+    /// Returns the SHA-256 digest.
+    pub fn digest(&self) -> &[u8; 32] {
+        &self.digest
+    }
+
+    /// Returns whether the multihash matches the given data.
+    pub fn verify(&self, data: &[u8]) -> bool {
+        ring::constant_time::verify_slices_are_equal(
+            &self.digest,
+            DigestAlgorithm::default().digest(data).as_ref()
+        ).is_ok()
+    }
+
+    /// Decodes a multihash from its binary representation.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut bytes = bytes;
+        if read_varint(&mut bytes)? != Self::SHA2_256 {
+            return Err(Error::UnsupportedHash)
+        }
+        if read_varint(&mut bytes)? != 32 || bytes.len() != 32 {
+            return Err(Error::BadLength)
+        }
+        let mut digest = [0u8; 32];
+        digest.copy_from_slice(bytes);
+        Ok(Multihash { digest })
+    }
+
+    /// Returns the binary representation of the multihash.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(Self::LEN);
+        write_varint(Self::SHA2_256, &mut res);
+        write_varint(32, &mut res);
+        res.extend_from_slice(&self.digest);
+        res
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Reads an unsigned varint from the beginning of a slice.
+pub(crate) fn read_varint(bytes: &mut &[u8]) -> Result<u64, Error> {
+    let mut res = 0u64;
+    for i in 0..9 {
+        let (&byte, rest) = bytes.split_first().ok_or(Error::BadLength)?;
+        *bytes = rest;
+        res |= u64::from(byte & 0x7f) << (i * 7);
+        if byte & 0x80 == 0 {
+            return Ok(res)
+        }
+    }
+    Err(Error::BadEncoding)
+}
+
+/// Appends an unsigned varint to a vec.
+pub(crate) fn write_varint(mut value: u64, target: &mut Vec<u8>) {
+    while value >= 0x80 {
+        target.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    target.push(value as u8)
+}
+
+/// Decodes a string with a multibase prefix.
+///
+/// Supports the prefixes for base32, base36, base58btc, and base16.
+pub(crate) fn multibase_decode(s: &str) -> Result<Vec<u8>, Error> {
+    let (prefix, data) = match s.as_bytes().split_first() {
+        Some((prefix, data)) => (*prefix, data),
+        None => return Err(Error::BadEncoding)
+    };
+    match prefix {
+        b'b' => base32_decode(data),
+        b'B' => base32_decode(&data.to_ascii_lowercase()),
+        b'k' => radix_decode(data, BASE36),
+        b'K' => radix_decode(&data.to_ascii_lowercase(), BASE36),
+        b'z' => base58_decode(data),
+        b'f' | b'F' => base16_decode(data),
+        _ => Err(Error::BadEncoding)
+    }
+}
+
+/// The alphabet of base58btc.
+const BASE58: &[u8] =
+    b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The alphabet of lower case base36.
+const BASE36: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+
+pub(crate) fn base58_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    radix_decode(data, BASE58)
+}
+
+/// Decodes a big number using the given alphabet.
+///
+/// Leading zero digits are preserved as leading zero octets.
+fn radix_decode(data: &[u8], alphabet: &[u8]) -> Result<Vec<u8>, Error> {
+    // Little-endian big number arithmetic.
+    let radix = alphabet.len() as u32;
+    let mut res: Vec<u8> = Vec::new();
+    for ch in data {
+        let mut carry = alphabet.iter().position(|x| x == ch)
+            .ok_or(Error::BadEncoding)? as u32;
+        for byte in res.iter_mut() {
+            carry += u32::from(*byte) * radix;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            res.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    res.extend(
+        data.iter().take_while(|&&ch| ch == alphabet[0]).map(|_| 0)
+    );
+    res.reverse();
+    Ok(res)
+}
+
+pub(crate) fn base58_encode(data: &[u8]) -> String {
+    let mut digits: Vec<u8> = Vec::new();
+    for &byte in data {
+        let mut carry = u32::from(byte);
+        for digit in digits.iter_mut() {
+            carry += u32::from(*digit) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+    data.iter().take_while(|&&byte| byte == 0).map(|_| '1').chain(
+        digits.iter().rev().map(|&digit| BASE58[digit as usize] as char)
+    ).collect()
+}
+
+/// The alphabet of lower case base32 as defined in RFC 4648.
+const BASE32: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+fn base32_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut res = Vec::with_capacity(data.len() * 5 / 8);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for ch in data {
+        let value = BASE32.iter().position(|x| x == ch)
+            .ok_or(Error::BadEncoding)? as u32;
+        buf = (buf << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            res.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Ok(res)
+}
+
+fn base32_encode(data: &[u8]) -> String {
+    let mut res = String::with_capacity(data.len() * 8 / 5 + 1);
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for &byte in data {
+        buf = (buf << 8) | u32::from(byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            res.push(BASE32[(buf >> bits) as usize & 0x1f] as char);
+        }
+        buf &= (1 << bits) - 1;
+    }
+    if bits > 0 {
+        res.push(BASE32[(buf << (5 - bits)) as usize & 0x1f] as char);
+    }
+    res
+}
+
+fn base16_decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+    fn nibble(ch: u8) -> Result<u8, Error> {
+        (ch as char).to_digit(16).map(|v| v as u8).ok_or(Error::BadEncoding)
+    }
+
+    if data.len() & 1 != 0 {
+        return Err(Error::BadEncoding)
+    }
+    data.chunks(2).map(|pair| {
+        Ok(nibble(pair[0])? << 4 | nibble(pair[1])?)
+    }).collect()
+}
+
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened while decoding a CID.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The textual or binary encoding is invalid.
+    BadEncoding,
+
+    /// The CID is of an unsupported version.
+    UnsupportedVersion,
+
+    /// The multihash uses a hash algorithm other than SHA-256.
+    UnsupportedHash,
+
+    /// The length of the data is wrong.
+    BadLength,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::BadEncoding => "invalid CID encoding",
+            Error::UnsupportedVersion => "unsupported CID version",
+            Error::UnsupportedHash => "unsupported multihash algorithm",
+            Error::BadLength => "invalid CID length",
+        })
+    }
+}
+
+impl error::Error for Error { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    // The CIDs of the empty UnixFS directory in both versions.
+    const EMPTY_DIR_V0: &str =
+        "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn";
+    const EMPTY_DIR_V1: &str =
+        "bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354";
+
+    #[test]
+    fn parse_and_display() {
+        let v0 = Cid::from_str(EMPTY_DIR_V0).unwrap();
+        assert_eq!(v0.version(), Version::V0);
+        assert_eq!(v0.codec(), Cid::DAG_PB);
+        assert_eq!(v0.to_string(), EMPTY_DIR_V0);
+
+        let v1 = Cid::from_str(EMPTY_DIR_V1).unwrap();
+        assert_eq!(v1.version(), Version::V1);
+        assert_eq!(v1.to_string(), EMPTY_DIR_V1);
+        assert_eq!(v0.to_v1(), v1);
+        assert!(v0.same_content(&v1));
+        assert_ne!(v0, v1);
+
+        let upper = format!("B{}", EMPTY_DIR_V1[1..].to_ascii_uppercase());
+        assert_eq!(Cid::from_str(&upper).unwrap(), v1);
+        let bytes = v1.to_bytes();
+        let mut buf = vec![0u8; bytes.len() * 2];
+        let hex = format!("f{}", crate::util::hex::encode(&bytes, &mut buf));
+        assert_eq!(Cid::from_str(&hex).unwrap(), v1);
+        let b58 = format!("z{}", base58_encode(&v1.to_bytes()));
+        assert_eq!(Cid::from_str(&b58).unwrap(), v1);
+        assert_eq!(
+            Cid::from_str(
+                "k2jmtxtlhjl3fhmgndf92e48by79ryjuvqp3y2qgehpao6v3lurvnmcv"
+            ).unwrap(),
+            v1
+        );
+
+        assert!(Cid::from_str("").is_err());
+        assert!(Cid::from_str(&EMPTY_DIR_V0[1..]).is_err());
+        let bad = format!("{}0", &EMPTY_DIR_V0[..45]);
+        assert!(Cid::from_str(&bad).is_err());
+    }
+
+    #[test]
+    fn multihash() {
+        let hash = Multihash::sha256(b"foo");
+        assert!(hash.verify(b"foo"));
+        assert!(!hash.verify(b"bar"));
+        assert_eq!(Multihash::from_bytes(&hash.to_bytes()).unwrap(), hash);
+        let cid = Cid::v1(Cid::RAW, hash);
+        assert_eq!(Cid::from_str(&cid.to_string()).unwrap(), cid);
+        assert_eq!(
+            Multihash::from_bytes(b"\x11\x14abcdefghijklmnopqrst"),
+            Err(Error::UnsupportedHash)
+        );
+    }
+}
@@ -6,29 +6,31 @@
 //!
 //! This module implements the CRLs themselves via the type [`Crl`] as well
 //! as a [`CrlStore`] that can keep several CRLs which may be helpful during
-//! validation.
+//! validation. CAs can use the [`CrlBuilder`] to maintain the list of
+//! revoked certificates and issue new CRLs.
 //!
 //! The RPKI CRL profile is defined in RFC 6487 based on the Internet RPIX
 //! profile defined in RFC 5280.
 //!
 //! [`Crl`]: struct.Crl.html
 //! [`CrlStore`]: struct.CrlStore.html
+//! [`CrlBuilder`]: struct.CrlBuilder.html
 
 use std::ops;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::str::FromStr;
 use bcder::{decode, encode};
 use bcder::{Captured, Mode, OctetString, Oid, Tag, xerr};
 use bcder::encode::PrimitiveContent;
 use bytes::Bytes;
 use serde::{Serialize, Serializer, Deserialize, Deserializer};
-use crate::{oid, uri};
+use crate::{locate, oid, uri};
 use crate::crypto::{
     KeyIdentifier, PublicKey, SignatureAlgorithm, Signer, SigningError
 };
 use crate::x509::{
-    Name, RepresentationError, Serial, SignedData, Time, ValidationError,
-    encode_extension, update_once
+    DecodeMode, Name, RepresentationError, Serial, SignedData, Time,
+    ValidationError, encode_extension, update_once
 };
 
 
@@ -84,6 +86,21 @@ impl Crl {
             None => self.tbs.revoked_certs.contains(serial)
         }
     }
+
+    /// Returns whether the given serial number was revoked at a time.
+    ///
+    /// Entries with a revocation date after `at` are ignored. This is
+    /// necessary when validating as of a past time using a CRL issued
+    /// later.
+    pub fn contains_at(&self, serial: Serial, at: Time) -> bool {
+        if !self.contains(serial) {
+            return false
+        }
+        self.tbs.revoked_certs.iter().any(|entry| {
+            entry.user_certificate() == serial
+                && entry.revocation_date() <= at
+        })
+    }
 }
 
 
@@ -91,23 +108,65 @@ impl Crl {
 ///
 impl Crl {
     /// Parses a source as a certificate revocation list.
+    ///
+    /// Uses the default decoding mode.
     pub fn decode<S: decode::Source>(source: S) -> Result<Self, S::Err> {
-        Mode::Der.decode(source, Self::take_from)
+        Self::decode_with_mode(source, DecodeMode::default())
+    }
+
+    /// Parses a source as a CRL using the given decoding mode.
+    pub fn decode_with_mode<S: decode::Source>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        Mode::Der.decode(source, |cons| Self::take_from_with_mode(cons, mode))
+    }
+
+    /// Parses a CRL, locating any decoding error.
+    ///
+    /// This is the same as [`decode_with_mode`] but the error includes the
+    /// byte offset and ASN.1 path at which decoding failed.
+    ///
+    /// [`decode_with_mode`]: #method.decode_with_mode
+    pub fn decode_located(
+        data: Bytes,
+        mode: DecodeMode,
+    ) -> Result<Self, locate::Error> {
+        locate::decode_crl(data, |source| {
+            Self::decode_with_mode(source, mode)
+        })
     }
 
     /// Takes an encoded CRL from the beginning of a constructed value.
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
-        cons.take_sequence(Self::from_constructed)
+        Self::take_from_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Takes an encoded CRL using the given decoding mode.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(|cons| Self::from_constructed_with_mode(cons, mode))
     }
 
     /// Parses the content of a certificate revocation list.
     pub fn from_constructed<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
-        let signed_data = SignedData::from_constructed(cons)?;
-        let tbs = signed_data.data().clone().decode(TbsCertList::take_from)?;
+        Self::from_constructed_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Parses the content of a CRL using the given decoding mode.
+    pub fn from_constructed_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        let (signed_data, tbs) = SignedData::from_constructed_with(
+            cons, |cons| TbsCertList::take_from_with_mode(cons, mode)
+        )?;
         Ok(Self { signed_data, tbs, serials: None })
     }
 
@@ -132,6 +191,17 @@ impl Crl {
     pub fn to_captured(&self) -> Captured {
         Captured::from_values(Mode::Der, self.encode_ref())
     }
+
+    /// Returns the canonical encoding of the CRL.
+    ///
+    /// CRLs created by this crate always are in canonical form: they are
+    /// DER encoded, the revoked certificates are ordered by serial number
+    /// and the list is left out entirely if it is empty. Thus, two CRLs
+    /// with identical content signed with the same key result in the same
+    /// octets.
+    pub fn canonical(&self) -> Bytes {
+        self.to_captured().into_bytes()
+    }
 }
 
 
@@ -341,15 +411,30 @@ impl TbsCertList<RevokedCertificates> {
     /// Takes a value from the beginning of a encoded constructed value.
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Takes a value using the given decoding mode.
+    ///
+    /// The mode decides how to deal with an issuer name and update times
+    /// that violate the RPKI profile. The revocation dates of the entries
+    /// are always decoded strictly.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Self, S::Err> {
         cons.take_sequence(|cons| {
             // version. Technically it is optional but we need v2, so it must
             // actually be there. v2 is encoded as an integer of value 1.
             cons.skip_u8_if(1)?;
             let signature = SignatureAlgorithm::x509_take_from(cons)?;
-            let issuer = Name::take_from(cons)?;
-            let this_update = Time::take_from(cons)?;
-            let next_update = Time::take_from(cons)?;
+            let issuer = Name::take_from_with_mode(cons, mode)?;
+            let this_update = Time::take_from_with_mode(cons, mode)?;
+            let next_update = Time::take_from_with_mode(cons, mode)?;
+            if this_update > next_update {
+                xerr!(return Err(decode::Malformed.into()))
+            }
             let revoked_certs = RevokedCertificates::take_from(cons)?;
             let mut authority_key_id = None;
             let mut crl_number = None;
@@ -357,7 +442,11 @@ impl TbsCertList<RevokedCertificates> {
                 cons.take_sequence(|cons| {
                     while let Some(()) = cons.take_opt_sequence(|cons| {
                         let id = Oid::take_from(cons)?;
-                        let _critical = cons.take_opt_bool()?.unwrap_or(false);
+                        // RFC 6487, section 5: the extensions must not
+                        // be critical.
+                        if cons.take_opt_bool()?.unwrap_or(false) {
+                            xerr!(return Err(decode::Malformed.into()))
+                        }
                         let value = OctetString::take_from(cons)?;
                         Mode::Der.decode(value.to_source(), |content| {
                             if id == oid::CE_AUTHORITY_KEY_IDENTIFIER {
@@ -425,7 +514,13 @@ impl TbsCertList<RevokedCertificates> {
             self.issuer.encode_ref(),
             self.this_update.encode_varied(),
             self.next_update.encode_varied(),
-            self.revoked_certs.encode_ref(),
+            // RFC 5280 requires an empty list to be absent.
+            if self.revoked_certs.0.is_empty() {
+                None
+            }
+            else {
+                Some(self.revoked_certs.encode_ref())
+            },
             encode::sequence_as(Tag::CTX_0, 
                 encode::sequence((
                     encode_extension(
@@ -519,6 +614,9 @@ impl RevokedCertificates {
 
     /// Create a value from an iterator over CRL entries.
     ///
+    /// The entries are ordered by serial number so that the same set of
+    /// entries always results in the same encoding.
+    ///
     /// This can’t be the `FromIterator` trait because of the `Clone`
     /// requirement on `I::IntoIter`
     fn from_iter<I>(iter: I) -> Self
@@ -526,9 +624,11 @@ impl RevokedCertificates {
         I: IntoIterator<Item = CrlEntry>,
         <I as IntoIterator>::IntoIter: Clone
     {
+        let mut entries: Vec<_> = iter.into_iter().collect();
+        entries.sort_by_key(|entry| entry.user_certificate);
         RevokedCertificates(Captured::from_values(
             Mode::Der, encode::iter(
-                iter.into_iter().map(CrlEntry::encode)
+                entries.into_iter().map(CrlEntry::encode)
             )
         ))
     }
@@ -568,6 +668,16 @@ impl CrlEntry {
         CrlEntry { user_certificate, revocation_date }
     }
 
+    /// Returns the serial number of the revoked certificate.
+    pub fn user_certificate(self) -> Serial {
+        self.user_certificate
+    }
+
+    /// Returns the time of revocation.
+    pub fn revocation_date(self) -> Time {
+        self.revocation_date
+    }
+
     /// Takes a single CRL entry from the beginning of a constructed value.
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
@@ -624,6 +734,141 @@ impl FromStr for CrlEntry {
 }
 
 
+//------------ CrlBuilder ----------------------------------------------------
+
+/// A builder for the CRLs of a CA.
+///
+/// The builder keeps the set of revoked certificates of a CA. Certificates
+/// can be added to it when they are revoked and removed again once they
+/// have expired. Whenever necessary, a new CRL can be signed from the
+/// current state. The revoked certificates are always ordered by serial
+/// number.
+#[derive(Clone, Debug)]
+pub struct CrlBuilder {
+    /// The time the next CRL is created.
+    this_update: Time,
+
+    /// The time the following CRL is likely to be created.
+    next_update: Time,
+
+    /// The CRL number of the next CRL.
+    crl_number: Serial,
+
+    /// The revoked certificates with their revocation time.
+    revoked: BTreeMap<Serial, Time>,
+}
+
+impl CrlBuilder {
+    /// Creates a new builder without revoked certificates.
+    pub fn new(
+        this_update: Time,
+        next_update: Time,
+        crl_number: Serial
+    ) -> Self {
+        CrlBuilder {
+            this_update,
+            next_update,
+            crl_number,
+            revoked: BTreeMap::new(),
+        }
+    }
+
+    /// Creates a new builder with the revoked certificates of a CRL.
+    ///
+    /// The times and CRL number are taken from the CRL as well and should
+    /// be updated before creating the next CRL.
+    pub fn from_crl(crl: &Crl) -> Self {
+        CrlBuilder {
+            this_update: crl.this_update(),
+            next_update: crl.next_update(),
+            crl_number: crl.crl_number(),
+            revoked: crl.revoked_certs().iter().map(|entry| {
+                (entry.user_certificate, entry.revocation_date)
+            }).collect(),
+        }
+    }
+
+    /// Returns the thisUpdate time of the next CRL.
+    pub fn this_update(&self) -> Time {
+        self.this_update
+    }
+
+    /// Sets the thisUpdate time of the next CRL.
+    pub fn set_this_update(&mut self, this_update: Time) {
+        self.this_update = this_update
+    }
+
+    /// Returns the nextUpdate time of the next CRL.
+    pub fn next_update(&self) -> Time {
+        self.next_update
+    }
+
+    /// Sets the nextUpdate time of the next CRL.
+    pub fn set_next_update(&mut self, next_update: Time) {
+        self.next_update = next_update
+    }
+
+    /// Returns the CRL number of the next CRL.
+    pub fn crl_number(&self) -> Serial {
+        self.crl_number
+    }
+
+    /// Sets the CRL number of the next CRL.
+    ///
+    /// The CRL number must increase with every CRL issued.
+    pub fn set_crl_number(&mut self, crl_number: Serial) {
+        self.crl_number = crl_number
+    }
+
+    /// Revokes the certificate with the given serial number.
+    ///
+    /// If the certificate has been revoked before, the revocation time is
+    /// left unchanged.
+    pub fn revoke(&mut self, serial: Serial, revocation_date: Time) {
+        self.revoked.entry(serial).or_insert(revocation_date);
+    }
+
+    /// Removes a certificate from the list of revoked certificates.
+    ///
+    /// This should only be done once the certificate has expired. Returns
+    /// whether the certificate was on the list.
+    pub fn unrevoke(&mut self, serial: Serial) -> bool {
+        self.revoked.remove(&serial).is_some()
+    }
+
+    /// Returns whether the certificate with the given serial is revoked.
+    pub fn is_revoked(&self, serial: Serial) -> bool {
+        self.revoked.contains_key(&serial)
+    }
+
+    /// Returns an iterator over the entries of the next CRL.
+    pub fn iter(&self) -> impl Iterator<Item = CrlEntry> + Clone + '_ {
+        self.revoked.iter().map(|(serial, time)| CrlEntry::new(*serial, *time))
+    }
+
+    /// Signs a new CRL with the given key.
+    ///
+    /// The issuer name and authority key identifier are derived from the
+    /// public key of `key`.
+    pub fn to_crl<S: Signer>(
+        &self,
+        signer: &S,
+        key: &S::KeyId
+    ) -> Result<Crl, SigningError<S::Error>> {
+        let pubkey = signer.get_key_info(key)?;
+        TbsCertList::new(
+            SignatureAlgorithm::default(),
+            pubkey.to_subject_name(),
+            self.this_update,
+            self.next_update,
+            self.iter(),
+            KeyIdentifier::from_public_key(&pubkey),
+            self.crl_number
+        ).into_crl(signer, key)
+    }
+}
+
+
 //------------ CrlStore ------------------------------------------------------
 
 /// A place to cache CRLs for reuse.
@@ -738,5 +983,67 @@ mod signer_test {
         let crl = crl.into_crl(&signer, &key).unwrap().to_captured();
         let _crl = Crl::decode(crl.as_slice()).unwrap();
     }
+
+    #[test]
+    fn canonical() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        let crl = |serials: &[u64]| {
+            TbsCertList::new(
+                Default::default(),
+                pubkey.to_subject_name(),
+                now, now + chrono::Duration::days(1),
+                serials.iter().map(|&serial| {
+                    CrlEntry::new(serial.into(), now)
+                }).collect::<Vec<_>>(),
+                KeyIdentifier::from_public_key(&pubkey),
+                12u64.into()
+            ).into_crl(&signer, &key).unwrap()
+        };
+        assert_eq!(
+            crl(&[12, 14, 13]).canonical(), crl(&[13, 12, 14]).canonical()
+        );
+
+        let empty = crl(&[]);
+        let decoded = Crl::decode(empty.canonical()).unwrap();
+        assert_eq!(decoded.revoked_certs().iter().count(), 0);
+        assert_eq!(decoded.canonical(), empty.canonical());
+    }
+
+    #[test]
+    fn crl_builder() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let mut builder = CrlBuilder::new(
+            Time::now(), Time::tomorrow(), 1u64.into()
+        );
+        builder.revoke(14u64.into(), Time::now());
+        builder.revoke(12u64.into(), Time::now());
+        builder.revoke(13u64.into(), Time::now());
+        assert!(builder.unrevoke(13u64.into()));
+        assert!(!builder.unrevoke(13u64.into()));
+
+        let crl = builder.to_crl(&signer, &key).unwrap().to_captured();
+        let crl = Crl::decode(crl.as_slice()).unwrap();
+        crl.validate(&pubkey).unwrap();
+        assert!(crl.contains(12u64.into()));
+        assert!(!crl.contains(13u64.into()));
+        assert!(crl.contains_at(12u64.into(), Time::tomorrow()));
+        assert!(!crl.contains_at(12u64.into(), Time::five_minutes_ago()));
+        assert!(!crl.contains_at(13u64.into(), Time::tomorrow()));
+        let serials: Vec<_> = crl.revoked_certs().iter().map(
+            CrlEntry::user_certificate
+        ).collect();
+        assert_eq!(serials, [Serial::from(12u64), Serial::from(14u64)]);
+
+        let mut builder = CrlBuilder::from_crl(&crl);
+        assert!(builder.is_revoked(14u64.into()));
+        builder.set_crl_number(2u64.into());
+        let crl = builder.to_crl(&signer, &key).unwrap();
+        assert_eq!(crl.crl_number(), Serial::from(2u64));
+    }
 }
 
@@ -0,0 +1,279 @@
+//! Requiring approval before signing.
+//!
+//! Some signing operations, most notably those done with the key of a
+//! trust anchor, may need to be approved out-of-band before they are
+//! performed, for instance as part of a key ceremony. This module provides
+//! the [`SigningApproval`] trait for such approval processes and the
+//! [`ApprovingSigner`] that wraps an existing [`Signer`] and asks for
+//! approval before handing over to it.
+//!
+//! Since `ApprovingSigner` is itself a `Signer`, it can be used with all
+//! builders in this crate without changing them.
+//!
+//! [`SigningApproval`]: trait.SigningApproval.html
+//! [`ApprovingSigner`]: struct.ApprovingSigner.html
+//! [`Signer`]: ../signer/trait.Signer.html
+
+use std::{error, fmt};
+use super::keys::{PublicKey, PublicKeyFormat};
+use super::signature::{Signature, SignatureAlgorithm};
+use super::signer::{KeyError, Signer, SignerInfo, SigningError};
+
+
+//------------ SigningApproval -----------------------------------------------
+
+/// A type that approves or denies operations of a signer.
+pub trait SigningApproval<K> {
+    /// The error returned when an operation is denied.
+    type Error: fmt::Debug + fmt::Display;
+
+    /// Decides whether the operation described by `request` may proceed.
+    ///
+    /// The method may block for as long as the approval process takes.
+    fn approve(&self, request: &SigningRequest<K>) -> Result<(), Self::Error>;
+}
+
+
+//------------ SigningRequest ------------------------------------------------
+
+/// Description of an operation that needs approval.
+#[derive(Clone, Copy, Debug)]
+pub enum SigningRequest<'a, K> {
+    /// A new key is to be created.
+    CreateKey {
+        /// The format of the new key.
+        format: PublicKeyFormat,
+    },
+
+    /// A key is to be destroyed.
+    DestroyKey {
+        /// The key to be destroyed.
+        key: &'a K,
+    },
+
+    /// Data is to be signed with an existing key.
+    Sign {
+        /// The key to sign with.
+        key: &'a K,
+
+        /// The signature algorithm to use.
+        algorithm: SignatureAlgorithm,
+
+        /// The data to be signed.
+        data: &'a [u8],
+    },
+
+    /// Data is to be signed with a one-off key.
+    SignOneOff {
+        /// The signature algorithm to use.
+        algorithm: SignatureAlgorithm,
+
+        /// The data to be signed.
+        data: &'a [u8],
+    },
+}
+
+
+//------------ ApprovingSigner -----------------------------------------------
+
+/// A signer that requires approval before performing operations.
+///
+/// The signer wraps another signer and an approval process. Before each
+/// operation that creates, destroys, or uses a key, the approval process is
+/// asked to approve the operation. If it does, the operation is performed by
+/// the wrapped signer. Otherwise, the operation fails with
+/// `ApprovalError::Denied`.
+///
+/// Requests for key information and random data are passed through without
+/// asking for approval.
+#[derive(Clone, Debug)]
+pub struct ApprovingSigner<S, A> {
+    /// The signer performing the actual operations.
+    signer: S,
+
+    /// The approval process.
+    approval: A,
+}
+
+impl<S, A> ApprovingSigner<S, A> {
+    /// Creates a new signer from a signer and an approval process.
+    pub fn new(signer: S, approval: A) -> Self {
+        ApprovingSigner { signer, approval }
+    }
+
+    /// Returns a reference to the wrapped signer.
+    pub fn signer(&self) -> &S {
+        &self.signer
+    }
+
+    /// Returns a reference to the approval process.
+    pub fn approval(&self) -> &A {
+        &self.approval
+    }
+
+    /// Converts the value into the wrapped signer and approval process.
+    pub fn into_parts(self) -> (S, A) {
+        (self.signer, self.approval)
+    }
+}
+
+impl<S: Signer, A: SigningApproval<S::KeyId>> ApprovingSigner<S, A> {
+    fn approve(
+        &self,
+        request: &SigningRequest<S::KeyId>
+    ) -> Result<(), ApprovalError<S::Error, A::Error>> {
+        self.approval.approve(request).map_err(ApprovalError::Denied)
+    }
+}
+
+impl<S: Signer, A: SigningApproval<S::KeyId>> Signer for ApprovingSigner<S, A> {
+    type KeyId = S::KeyId;
+    type Error = ApprovalError<S::Error, A::Error>;
+
+    fn create_key(
+        &mut self,
+        format: PublicKeyFormat
+    ) -> Result<Self::KeyId, Self::Error> {
+        self.approve(&SigningRequest::CreateKey { format })?;
+        self.signer.create_key(format).map_err(ApprovalError::Signer)
+    }
+
+    fn get_key_info(
+        &self,
+        key: &Self::KeyId
+    ) -> Result<PublicKey, KeyError<Self::Error>> {
+        self.signer.get_key_info(key).map_err(convert_key_error)
+    }
+
+    fn destroy_key(
+        &mut self,
+        key: &Self::KeyId
+    ) -> Result<(), KeyError<Self::Error>> {
+        self.approve(&SigningRequest::DestroyKey { key })?;
+        self.signer.destroy_key(key).map_err(convert_key_error)
+    }
+
+    fn sign<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        key: &Self::KeyId,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<Signature, SigningError<Self::Error>> {
+        self.approve(&SigningRequest::Sign {
+            key, algorithm, data: data.as_ref()
+        })?;
+        self.signer.sign(key, algorithm, data).map_err(|err| match err {
+            SigningError::KeyNotFound => SigningError::KeyNotFound,
+            SigningError::IncompatibleKey => SigningError::IncompatibleKey,
+            SigningError::Signer(err) => {
+                SigningError::Signer(ApprovalError::Signer(err))
+            }
+        })
+    }
+
+    fn sign_one_off<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<(Signature, PublicKey), Self::Error> {
+        self.approve(&SigningRequest::SignOneOff {
+            algorithm, data: data.as_ref()
+        })?;
+        self.signer.sign_one_off(algorithm, data)
+            .map_err(ApprovalError::Signer)
+    }
+
+    fn rand(&self, target: &mut [u8]) -> Result<(), Self::Error> {
+        self.signer.rand(target).map_err(ApprovalError::Signer)
+    }
+
+    fn supports(&self, algorithm: SignatureAlgorithm) -> bool {
+        self.signer.supports(algorithm)
+    }
+
+    fn info(&self) -> SignerInfo {
+        self.signer.info()
+    }
+}
+
+fn convert_key_error<S, A>(err: KeyError<S>) -> KeyError<ApprovalError<S, A>> {
+    match err {
+        KeyError::KeyNotFound => KeyError::KeyNotFound,
+        KeyError::Signer(err) => KeyError::Signer(ApprovalError::Signer(err))
+    }
+}
+
+
+//------------ ApprovalError -------------------------------------------------
+
+/// An error happened in an approving signer.
+#[derive(Clone, Debug)]
+pub enum ApprovalError<S, A> {
+    /// The operation was denied by the approval process.
+    Denied(A),
+
+    /// The wrapped signer failed.
+    Signer(S),
+}
+
+impl<S: fmt::Display, A: fmt::Display> fmt::Display for ApprovalError<S, A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ApprovalError::Denied(ref err) => {
+                write!(f, "operation not approved: {}", err)
+            }
+            ApprovalError::Signer(ref err) => err.fmt(f)
+        }
+    }
+}
+
+impl<S, A> error::Error for ApprovalError<S, A>
+where S: fmt::Debug + fmt::Display, A: fmt::Debug + fmt::Display { }
+
+
+//============ Tests =========================================================
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::cell::Cell;
+    use crate::crypto::softsigner::{KeyId, OpenSslSigner};
+    use super::*;
+
+    /// An approval process that allows a fixed number of signatures.
+    struct Quota(Cell<usize>);
+
+    impl SigningApproval<KeyId> for Quota {
+        type Error = &'static str;
+
+        fn approve(
+            &self, request: &SigningRequest<KeyId>
+        ) -> Result<(), Self::Error> {
+            match *request {
+                SigningRequest::Sign { .. } => {
+                    if self.0.get() == 0 {
+                        return Err("quota exceeded")
+                    }
+                    self.0.set(self.0.get() - 1);
+                    Ok(())
+                }
+                SigningRequest::DestroyKey { .. } => Err("never"),
+                _ => Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn approval() {
+        let mut signer = ApprovingSigner::new(
+            OpenSslSigner::new(), Quota(Cell::new(1))
+        );
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        signer.sign(&key, SignatureAlgorithm::default(), b"1").unwrap();
+        match signer.sign(&key, SignatureAlgorithm::default(), b"2") {
+            Err(SigningError::Signer(ApprovalError::Denied(_))) => { }
+            _ => panic!("signing not denied")
+        }
+        assert!(signer.destroy_key(&key).is_err());
+        signer.get_key_info(&key).unwrap();
+    }
+}
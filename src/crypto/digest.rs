@@ -4,7 +4,7 @@ use std::io;
 use ring::digest;
 use bcder::{decode, encode};
 use bcder::encode::PrimitiveContent;
-use bcder::Tag;
+use bcder::{ConstOid, Oid, Tag};
 use crate::oid;
 
 // Re-export the things from ring for actual digest generation.
@@ -15,27 +15,102 @@ pub use ring::digest::Digest;
 
 /// The digest algorithms used by RPKI.
 ///
-/// These are the algorithms used by the signature algorithms. For use in
-/// RPKI, [RFC 7935] limits them to exactly one, SHA-256. Because of
-/// that, this type is currently a zero-sized struct. If additional
-/// algorithms are ever introduced in the future, it will change into an enum.
+/// For use in signed objects and as the hash algorithm of manifests and
+/// signed checklists, [RFC 7935] limits digest algorithms to exactly one,
+/// SHA-256. RPKI also uses SHA-1 in one place: key identifiers are the
+/// SHA-1 hash of the public key bits as required by [RFC 6487]. Both
+/// algorithms are available here so that all hashing can be done through
+/// this type.
 ///
+/// Note that the ASN.1 decoding functions of this type only ever accept
+/// SHA-256 since SHA-1 is not allowed in any RPKI object.
+///
+/// [RFC 6487]: https://tools.ietf.org/html/rfc6487
 /// [RFC 7935]: https://tools.ietf.org/html/rfc7935
-#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
-pub struct DigestAlgorithm(());
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DigestAlgorithm {
+    /// The SHA-256 algorithm.
+    Sha256,
+
+    /// The SHA-1 algorithm.
+    ///
+    /// This is only used for key identifiers.
+    Sha1,
+}
 
 
 /// # Creating Digest Values
 ///
 impl DigestAlgorithm {
+    /// Returns the ring algorithm for this algorithm.
+    fn ring_algorithm(self) -> &'static digest::Algorithm {
+        match self {
+            DigestAlgorithm::Sha256 => &digest::SHA256,
+            DigestAlgorithm::Sha1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        }
+    }
+
     /// Returns the digest of `data` using this algorithm.
     pub fn digest(self, data: &[u8]) -> Digest {
-        digest::digest(&digest::SHA256, data)
+        digest::digest(self.ring_algorithm(), data)
+    }
+
+    /// Returns the digest of all data read from `reader`.
+    ///
+    /// The data is processed in chunks, so this can be used for large
+    /// files without reading them into memory first.
+    pub fn digest_reader<R: io::Read>(
+        self, reader: &mut R
+    ) -> Result<Digest, io::Error> {
+        let mut context = self.start();
+        io::copy(reader, &mut context)?;
+        Ok(context.finish())
     }
 
     /// Returns a digest context for multi-step calculation of the digest.
     pub fn start(self) -> Context {
-        Context(digest::Context::new(&digest::SHA256))
+        Context {
+            context: digest::Context::new(self.ring_algorithm()),
+            algorithm: self,
+        }
+    }
+
+    /// Returns the length in octets of a digest produced by this algorithm.
+    pub fn digest_len(self) -> usize {
+        self.ring_algorithm().output_len
+    }
+
+    /// Returns the object identifier of the algorithm.
+    pub fn oid(self) -> ConstOid {
+        match self {
+            DigestAlgorithm::Sha256 => oid::SHA256,
+            DigestAlgorithm::Sha1 => oid::SHA1,
+        }
+    }
+
+    /// Returns the algorithm identified by an object identifier.
+    ///
+    /// Returns `None` if the identifier doesn’t refer to a known algorithm.
+    pub fn from_oid<T: AsRef<[u8]>>(oid: &Oid<T>) -> Option<Self> {
+        if *oid == oid::SHA256 {
+            Some(DigestAlgorithm::Sha256)
+        }
+        else if *oid == oid::SHA1 {
+            Some(DigestAlgorithm::Sha1)
+        }
+        else {
+            None
+        }
+    }
+}
+
+
+//--- Default
+
+#[allow(clippy::derivable_impls)] // #[default] requires Rust 1.62.
+impl Default for DigestAlgorithm {
+    fn default() -> Self {
+        DigestAlgorithm::Sha256
     }
 }
 
@@ -103,7 +178,7 @@ impl DigestAlgorithm {
     ) -> Result<Self, S::Err> {
         oid::SHA256.skip_if(cons)?;
         cons.take_opt_null()?;
-        Ok(DigestAlgorithm::default())
+        Ok(DigestAlgorithm::Sha256)
     }
 
     /// Parses a SET OF DigestAlgorithmIdentifiers.
@@ -129,13 +204,13 @@ impl DigestAlgorithm {
         cons: &mut decode::Constructed<S>,
     ) -> Result<Self, S::Err> {
         oid::SHA256.skip_if(cons)?;
-        Ok(Self::default())
+        Ok(DigestAlgorithm::Sha256)
     }
 
     /// Provides an encoder for a single algorithm identifier.
     pub fn encode(self) -> impl encode::Values {
         encode::sequence((
-            oid::SHA256.encode(),
+            self.oid().encode(),
             ().encode(),
         ))
     }
@@ -149,34 +224,52 @@ impl DigestAlgorithm {
 
     /// Provides an encoder for just the object identifier of the algorithm.
     pub fn encode_oid(self) -> impl encode::Values {
-        oid::SHA256.encode()
+        self.oid().encode()
     }
 }
 
 
 //------------ Sha1 ----------------------------------------------------------
 
+/// Returns the SHA-1 digest of `data`.
 pub fn sha1_digest(data: &[u8]) -> Digest {
-    digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, data)
+    DigestAlgorithm::Sha1.digest(data)
 }
 
+/// Returns a digest context for calculating a SHA-1 digest.
 pub fn start_sha1() -> Context {
-    Context(digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY))
+    DigestAlgorithm::Sha1.start()
 }
 
 
 //------------ Context -------------------------------------------------------
 
+/// A context for multi-step calculation of a digest.
+///
+/// The context also implements `io::Write`, so data can be copied into it.
 #[derive(Clone)]
-pub struct Context(digest::Context);
+pub struct Context {
+    /// The ring digest context.
+    context: digest::Context,
+
+    /// The algorithm used by the context.
+    algorithm: DigestAlgorithm,
+}
 
 impl Context {
+    /// Returns the algorithm used by the context.
+    pub fn algorithm(&self) -> DigestAlgorithm {
+        self.algorithm
+    }
+
+    /// Adds `data` to the digest.
     pub fn update(&mut self, data: &[u8]) {
-        self.0.update(data)
+        self.context.update(data)
     }
 
+    /// Finishes the calculation and returns the digest.
     pub fn finish(self) -> Digest {
-        self.0.finish()
+        self.context.finish()
     }
 }
 
@@ -191,3 +284,43 @@ impl io::Write for Context {
     }
 }
 
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn algorithms() {
+        assert_eq!(DigestAlgorithm::default(), DigestAlgorithm::Sha256);
+        assert_eq!(DigestAlgorithm::Sha256.digest_len(), 32);
+        assert_eq!(DigestAlgorithm::Sha1.digest_len(), 20);
+        assert_eq!(
+            DigestAlgorithm::from_oid(&oid::SHA1), Some(DigestAlgorithm::Sha1)
+        );
+        assert_eq!(
+            DigestAlgorithm::from_oid(&DigestAlgorithm::Sha256.oid()),
+            Some(DigestAlgorithm::Sha256)
+        );
+        assert_eq!(DigestAlgorithm::from_oid(&oid::SIGNED_DATA), None);
+    }
+
+    #[test]
+    fn streaming() {
+        let data = vec![0x5a; 100_000];
+        for &alg in &[DigestAlgorithm::Sha256, DigestAlgorithm::Sha1] {
+            let mut context = alg.start();
+            for chunk in data.chunks(333) {
+                context.update(chunk);
+            }
+            assert_eq!(context.algorithm(), alg);
+            let expected = alg.digest(&data);
+            assert_eq!(context.finish().as_ref(), expected.as_ref());
+            assert_eq!(
+                alg.digest_reader(&mut data.as_slice()).unwrap().as_ref(),
+                expected.as_ref()
+            );
+        }
+    }
+}
@@ -1,13 +1,13 @@
 //! Types and parameters of keys.
 
-use std::{error, fmt, io, str};
+use std::{cmp, error, fmt, io, str};
 use std::convert::{TryFrom, TryInto};
 use std::str::FromStr;
 use bcder::{decode, encode};
-use bcder::{BitString, Mode, OctetString, Tag};
+use bcder::{BitString, Mode, OctetString, Oid, Tag, Unsigned, xerr};
 use bcder::encode::{PrimitiveContent, Values};
 use bytes::Bytes;
-use ring::{digest, signature};
+use ring::signature;
 use ring::error::Unspecified;
 use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -15,6 +15,7 @@ use untrusted::Input;
 use crate::oid;
 use crate::util::hex;
 use crate::x509::{Name, RepresentationError};
+use super::digest::DigestAlgorithm;
 use super::signature::Signature;
 use ring::signature::VerificationAlgorithm;
 
@@ -23,12 +24,24 @@ use ring::signature::VerificationAlgorithm;
 
 /// The formats of public keys used by RPKI.
 ///
-/// Currently, RPKI uses exactly one type of public keys, RSA keys with a size
-/// of 2048 bits. However, as that might change in the future, we are not
-/// hard-coding that format but rather use this type – which for the time
-/// being is zero-sized.
-#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
-pub struct PublicKeyFormat(());
+/// For all its own objects, RPKI uses exactly one type of public keys, RSA
+/// keys with a size of 2048 bits. BGPsec router certificates defined in
+/// [RFC 8209], however, certify ECDSA keys using the P-256 curve as
+/// required by [RFC 8208]. These keys are only ever used by routers and
+/// never for signing RPKI objects.
+///
+/// [RFC 8208]: https://tools.ietf.org/html/rfc8208
+/// [RFC 8209]: https://tools.ietf.org/html/rfc8209
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PublicKeyFormat {
+    /// An RSA key.
+    Rsa,
+
+    /// An ECDSA key using the P-256 curve.
+    ///
+    /// This format is only allowed in BGPsec router certificates.
+    EcdsaP256,
+}
 
 /// # ASN.1 Algorithm Identifiers
 ///
@@ -41,14 +54,18 @@ pub struct PublicKeyFormat(());
 ///      parameters         ANY DEFINED BY algorithm OPTIONAL }
 /// ```
 ///
-/// Right now, the object identifier needs to be that of `rsaEncryption`
+/// For RSA keys, the object identifier needs to be that of `rsaEncryption`
 /// defined by [RFC 4055] and the parameters must be present and NULL.
 /// Then parsing, we generously also allow it to be absent altogether.
 ///
+/// For ECDSA keys, the object identifier is `id-ecPublicKey` from
+/// [RFC 5480] and the parameters must be the named curve `secp256r1`.
+///
 /// The functions and methods in this section allow decoding and encoding of
 /// these identifiers.
 ///
 /// [RFC 4055]: https://tools.ietf.org/html/rfc4055
+/// [RFC 5480]: https://tools.ietf.org/html/rfc5480
 impl PublicKeyFormat{
     /// Takes and returns a algorithm identifier.
     ///
@@ -64,17 +81,46 @@ impl PublicKeyFormat{
     fn from_constructed<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
-        oid::RSA_ENCRYPTION.skip_if(cons)?;
-        cons.take_opt_null()?;
-        Ok(PublicKeyFormat::default())
+        let algorithm = Oid::take_from(cons)?;
+        if algorithm == oid::RSA_ENCRYPTION {
+            cons.take_opt_null()?;
+            Ok(PublicKeyFormat::Rsa)
+        }
+        else if algorithm == oid::EC_PUBLIC_KEY {
+            oid::SECP256R1.skip_if(cons)?;
+            Ok(PublicKeyFormat::EcdsaP256)
+        }
+        else {
+            xerr!(Err(decode::Malformed.into()))
+        }
     }
 
     /// Provides an encoder for the algorihm identifier.
     pub fn encode(self) -> impl encode::Values {
-        encode::sequence((
-            oid::RSA_ENCRYPTION.encode(),
-            ().encode(),
-        ))
+        match self {
+            PublicKeyFormat::Rsa => {
+                encode::Choice2::One(encode::sequence((
+                    oid::RSA_ENCRYPTION.encode(),
+                    ().encode(),
+                )))
+            }
+            PublicKeyFormat::EcdsaP256 => {
+                encode::Choice2::Two(encode::sequence((
+                    oid::EC_PUBLIC_KEY.encode(),
+                    oid::SECP256R1.encode(),
+                )))
+            }
+        }
+    }
+}
+
+
+//--- Default
+
+#[allow(clippy::derivable_impls)] // #[default] requires Rust 1.62.
+impl Default for PublicKeyFormat {
+    fn default() -> Self {
+        PublicKeyFormat::Rsa
     }
 }
 
@@ -90,28 +136,76 @@ pub struct PublicKey {
 
 
 impl PublicKey {
+    /// Returns the format of the public key.
     pub fn algorithm(&self) -> &PublicKeyFormat {
         &self.algorithm
     }
 
+    /// Returns whether this is an RSA key.
+    pub fn is_rsa(&self) -> bool {
+        self.algorithm == PublicKeyFormat::Rsa
+    }
+
+    /// Returns whether this is an ECDSA P-256 key.
+    pub fn is_ecdsa_p256(&self) -> bool {
+        self.algorithm == PublicKeyFormat::EcdsaP256
+    }
+
+    /// Returns the bits of the subject public key.
+    ///
+    /// This is the content of the *subjectPublicKey* bit string.
     pub fn bits(&self) -> &[u8] {
         self.bits.octet_slice().unwrap()
     }
 
+    /// Returns the key identifier of the key.
+    ///
+    /// As described in section 4.8.2 of [RFC 6487], this is the SHA-1 hash
+    /// of the bits of the subject public key.
+    ///
+    /// [RFC 6487]: https://tools.ietf.org/html/rfc6487
     pub fn key_identifier(&self) -> KeyIdentifier {
         KeyIdentifier::try_from(
-            digest::digest(
-                &digest::SHA1_FOR_LEGACY_USE_ONLY,
-                self.bits.octet_slice().unwrap()
-            ).as_ref()
+            DigestAlgorithm::Sha1.digest(self.bits()).as_ref()
         ).unwrap()
     }
 
+    /// Returns the size of the key in bits.
+    ///
+    /// For RSA keys, this is the size of the modulus. Returns `None` if the
+    /// key isn’t a correctly encoded RSA public key.
+    pub fn key_size(&self) -> Option<usize> {
+        let modulus = Mode::Der.decode(self.bits.octet_slice()?, |cons| {
+            cons.take_sequence(|cons| {
+                let modulus = Unsigned::take_from(cons)?;
+                Unsigned::take_from(cons)?; // publicExponent
+                Ok(modulus)
+            })
+        }).ok()?;
+        let modulus = modulus.as_slice();
+        let first = modulus.iter().position(|&octet| octet != 0)?;
+        Some(
+            (modulus.len() - first) * 8
+            - modulus[first].leading_zeros() as usize
+        )
+    }
+
     /// Verifies a signature using this public key.
+    ///
+    /// Fails if the signature’s algorithm doesn’t match the key format.
     pub fn verify(
         &self, message: &[u8], signature: &Signature
     ) -> Result<(), VerificationError> {
-        signature::RSA_PKCS1_2048_8192_SHA256.verify(
+        if signature.algorithm().public_key_format() != self.algorithm {
+            return Err(VerificationError)
+        }
+        let algorithm: &dyn VerificationAlgorithm = match self.algorithm {
+            PublicKeyFormat::Rsa => &signature::RSA_PKCS1_2048_8192_SHA256,
+            PublicKeyFormat::EcdsaP256 => {
+                &signature::ECDSA_P256_SHA256_ASN1
+            }
+        };
+        algorithm.verify(
             Input::from(self.bits()),
             Input::from(message),
             Input::from(signature.value().as_ref())
@@ -120,6 +214,19 @@ impl PublicKey {
 }
 
 
+/// # Creation
+///
+impl PublicKey {
+    /// Creates a public key from its format and the subject public key.
+    ///
+    /// For RSA keys, `bits` contains the DER encoded `RSAPublicKey`. For
+    /// ECDSA keys, it contains the uncompressed elliptic curve point.
+    pub fn new(algorithm: PublicKeyFormat, bits: Bytes) -> Self {
+        PublicKey { algorithm, bits: BitString::new(0, bits) }
+    }
+}
+
+
 /// # As `SubjectPublicKeyInfo`
 ///
 /// Public keys are included in X.509 certificates as `SubjectPublicKeyInfo`
@@ -235,6 +342,24 @@ impl KeyIdentifier {
         res
     }
 
+    /// Returns the base64url representation of the identifier.
+    ///
+    /// The representation uses the URL-safe alphabet without padding as
+    /// defined in section 5 of [RFC 4648]. It is used, e.g., for file
+    /// names derived from a key.
+    ///
+    /// [RFC 4648]: https://tools.ietf.org/html/rfc4648
+    pub fn to_base64url(&self) -> String {
+        base64::encode_config(self.as_slice(), base64::URL_SAFE_NO_PAD)
+    }
+
+    /// Creates an identifier from its base64url representation.
+    pub fn from_base64url(value: &str) -> Result<Self, RepresentationError> {
+        let value = base64::decode_config(value, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| RepresentationError)?;
+        Self::try_from(value.as_slice())
+    }
+
     /// Takes an encoded key identifier from a constructed value.
     ///
     /// ```text
@@ -319,6 +444,21 @@ impl<T: AsRef<[u8]>> PartialEq<T> for KeyIdentifier {
 }
 
 
+//--- PartialOrd and Ord
+
+impl PartialOrd for KeyIdentifier {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for KeyIdentifier {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+
 //--- Display and Debug
 
 impl fmt::Display for KeyIdentifier {
@@ -420,3 +560,44 @@ impl fmt::Display for VerificationError {
 }
 
 impl error::Error for VerificationError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TA_CERT: &[u8] = include_bytes!("../../test-data/ta.cer");
+
+    #[test]
+    fn public_key_round_trip() {
+        let cert = crate::cert::Cert::decode(TA_CERT).unwrap();
+        let key = cert.subject_public_key_info();
+        assert!(key.is_rsa());
+        assert!(!key.is_ecdsa_p256());
+        assert_eq!(key.key_size(), Some(2048));
+        let decoded = PublicKey::decode(key.to_info_bytes()).unwrap();
+        assert_eq!(&decoded, key);
+        assert_eq!(
+            decoded.key_identifier(), cert.subject_key_identifier()
+        );
+    }
+
+    #[test]
+    fn key_identifier_representations() {
+        let id = KeyIdentifier::try_from(&[
+            0xfb, 0xff, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07,
+            0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11,
+        ][..]).unwrap();
+        assert_eq!(
+            id.to_string(), "FBFF000102030405060708090A0B0C0D0E0F1011"
+        );
+        assert_eq!(KeyIdentifier::from_str(&id.to_string()).unwrap(), id);
+        assert_eq!(id.to_base64url(), "-_8AAQIDBAUGBwgJCgsMDQ4PEBE");
+        assert_eq!(
+            KeyIdentifier::from_base64url(&id.to_base64url()).unwrap(), id
+        );
+        assert!(KeyIdentifier::from_base64url("AAEC").is_err());
+    }
+}
@@ -1,16 +1,24 @@
 //! Signing related implementations.
 //!
 
+pub use self::approval::{
+    ApprovalError, ApprovingSigner, SigningApproval, SigningRequest
+};
 pub use self::digest::{Digest, DigestAlgorithm};
 pub use self::keys::{
     KeyIdentifier, PublicKey, PublicKeyFormat, VerificationError
 };
-pub use self::signer::{Signer, SigningError};
+pub use self::policy::AlgorithmPolicy;
+pub use self::signer::{Signer, SignerInfo, SigningError};
 pub use self::signature::{Signature, SignatureAlgorithm};
 
+pub mod approval;
 pub mod digest;
 pub mod keys;
+pub mod pkcs11;
+pub mod policy;
 pub mod signer;
 pub mod signature;
+#[cfg(feature = "ringkeys")] pub mod ringsigner;
 #[cfg(feature = "softkeys")] pub mod softsigner;
 
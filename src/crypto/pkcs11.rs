@@ -0,0 +1,949 @@
+//! Keeping keys in a hardware security module via PKCS #11.
+//!
+//! Production CAs will want to keep their private keys in a hardware
+//! security module (HSM) rather than in memory. Most HSMs are accessed via
+//! the PKCS #11 API. This module provides the [`Pkcs11Signer`], an
+//! implementation of [`Signer`] that uses such a device.
+//!
+//! This crate does not bind to the C API itself. Instead, the few
+//! operations needed by the signer are described by the [`Token`] trait
+//! which can be implemented atop whatever binding is used for the device’s
+//! PKCS #11 library. The signer takes care of identifying keys by their
+//! label or ID, of pooling sessions with the token, of picking the
+//! mechanisms supported by the token, and of converting keys and
+//! signatures into the formats used by the RPKI.
+//!
+//! [`Pkcs11Signer`]: struct.Pkcs11Signer.html
+//! [`Signer`]: ../signer/trait.Signer.html
+//! [`Token`]: trait.Token.html
+
+use std::{error, fmt};
+use std::sync::Mutex;
+use bcder::{Mode, Tag};
+use bcder::encode::{self, PrimitiveContent, Values};
+use bytes::Bytes;
+use super::digest::DigestAlgorithm;
+use super::keys::{PublicKey, PublicKeyFormat};
+use super::signature::{Signature, SignatureAlgorithm};
+use super::signer::{KeyError, Signer, SignerInfo, SigningError};
+
+
+//------------ Token ---------------------------------------------------------
+
+/// The operations of a PKCS #11 token needed by the signer.
+///
+/// All operations except for querying the mechanisms happen within a
+/// session. Sessions are opened by the signer as needed and kept for
+/// later use. A session for which an operation failed is dropped, so
+/// implementations should close the session when dropping it.
+pub trait Token {
+    /// A session with the token.
+    ///
+    /// The session must be logged in and allow read-write access.
+    type Session;
+
+    /// A handle for an object on the token.
+    type Object;
+
+    /// An error happened while talking to the token.
+    type Error: fmt::Debug + fmt::Display;
+
+    /// Opens a new session with the token.
+    fn open_session(&self) -> Result<Self::Session, Self::Error>;
+
+    /// Returns the codes of the mechanisms supported by the token.
+    fn mechanisms(&self) -> Result<Vec<u64>, Self::Error>;
+
+    /// Finds the key object of the given class.
+    ///
+    /// The object has to match all the attributes present in `key`.
+    /// Returns `Ok(None)` if there is no such object.
+    fn find_object(
+        &self,
+        session: &mut Self::Session,
+        class: ObjectClass,
+        key: &KeyId,
+    ) -> Result<Option<Self::Object>, Self::Error>;
+
+    /// Generates a new key pair according to the template.
+    ///
+    /// Returns the public and private key objects in this order.
+    fn generate_key_pair(
+        &self,
+        session: &mut Self::Session,
+        template: &KeyTemplate,
+    ) -> Result<(Self::Object, Self::Object), Self::Error>;
+
+    /// Returns the format of a key object.
+    ///
+    /// Returns `Ok(None)` if the key is of a type not supported by the
+    /// signer.
+    fn key_format(
+        &self,
+        session: &mut Self::Session,
+        object: &Self::Object,
+    ) -> Result<Option<PublicKeyFormat>, Self::Error>;
+
+    /// Returns the public key stored in a public key object.
+    fn public_key(
+        &self,
+        session: &mut Self::Session,
+        object: &Self::Object,
+    ) -> Result<RawPublicKey, Self::Error>;
+
+    /// Signs data with a private key object using the given mechanism.
+    fn sign(
+        &self,
+        session: &mut Self::Session,
+        mechanism: Mechanism,
+        key: &Self::Object,
+        data: &[u8],
+    ) -> Result<Vec<u8>, Self::Error>;
+
+    /// Destroys an object.
+    fn destroy_object(
+        &self,
+        session: &mut Self::Session,
+        object: Self::Object,
+    ) -> Result<(), Self::Error>;
+
+    /// Fills `target` with random data generated by the token.
+    fn generate_random(
+        &self,
+        session: &mut Self::Session,
+        target: &mut [u8],
+    ) -> Result<(), Self::Error>;
+
+    /// Returns the model of the token if known.
+    fn model(&self) -> Option<String> {
+        None
+    }
+
+    /// Returns whether the token is a hardware device.
+    fn is_hardware(&self) -> bool {
+        true
+    }
+}
+
+
+//------------ Pkcs11Signer --------------------------------------------------
+
+/// A signer keeping its keys on a PKCS #11 token.
+///
+/// When created, the signer determines which of the mechanisms it can use
+/// are supported by the token. For signing, mechanisms that do the hashing
+/// on the token are preferred. If only the plain RSA PKCS #1 or ECDSA
+/// mechanisms are available, the data is hashed by the signer instead.
+///
+/// Keys are identified by their ID and label attributes. Keys created by
+/// the signer receive a random ID and, optionally, a label. Existing keys
+/// on the token can be used by creating a [`KeyId`] from their ID or
+/// label.
+///
+/// [`KeyId`]: struct.KeyId.html
+pub struct Pkcs11Signer<T: Token> {
+    /// The token.
+    token: T,
+
+    /// The mechanisms supported by both the token and us.
+    mechanisms: Vec<Mechanism>,
+
+    /// Currently unused sessions.
+    sessions: Mutex<Vec<T::Session>>,
+
+    /// The maximum number of unused sessions to keep.
+    max_idle_sessions: usize,
+}
+
+impl<T: Token> Pkcs11Signer<T> {
+    /// Creates a new signer for the given token.
+    pub fn new(token: T) -> Result<Self, T::Error> {
+        let mechanisms = token.mechanisms()?.into_iter().filter_map(
+            Mechanism::from_code
+        ).collect();
+        Ok(Pkcs11Signer {
+            token,
+            mechanisms,
+            sessions: Mutex::new(Vec::new()),
+            max_idle_sessions: 4,
+        })
+    }
+
+    /// Returns a reference to the token.
+    pub fn token(&self) -> &T {
+        &self.token
+    }
+
+    /// Returns the mechanisms supported by the token that the signer knows.
+    pub fn mechanisms(&self) -> &[Mechanism] {
+        &self.mechanisms
+    }
+
+    /// Returns the maximum number of unused sessions kept by the signer.
+    pub fn max_idle_sessions(&self) -> usize {
+        self.max_idle_sessions
+    }
+
+    /// Sets the maximum number of unused sessions kept by the signer.
+    ///
+    /// The default is four sessions.
+    pub fn set_max_idle_sessions(&mut self, max: usize) {
+        self.max_idle_sessions = max;
+        self.sessions.get_mut().unwrap().truncate(max);
+    }
+
+    /// Returns the number of unused sessions currently kept.
+    pub fn idle_sessions(&self) -> usize {
+        self.sessions.lock().unwrap().len()
+    }
+
+    /// Creates a new key with an optional label.
+    ///
+    /// The key will also receive a random ID. The returned key identifier
+    /// contains both the ID and the label.
+    pub fn create_key_with_label(
+        &self,
+        format: PublicKeyFormat,
+        label: Option<String>,
+    ) -> Result<KeyId, Error<T::Error>> {
+        let mut template = KeyTemplate::new(
+            format, self.generation_mechanism(format)?
+        );
+        template.label = label;
+        self.with_session(|token, session| {
+            let mut id = [0u8; 20];
+            token.generate_random(session, &mut id)?;
+            template.id = Bytes::copy_from_slice(&id);
+            token.generate_key_pair(session, &template).map(|_| ())
+        }).map_err(Error::Token)?;
+        Ok(KeyId::new(Some(template.id), template.label))
+    }
+
+    /// Returns the mechanism for generating keys of the given format.
+    fn generation_mechanism(
+        &self, format: PublicKeyFormat
+    ) -> Result<Mechanism, Error<T::Error>> {
+        let mechanism = match format {
+            PublicKeyFormat::Rsa => Mechanism::RsaPkcsKeyPairGen,
+            PublicKeyFormat::EcdsaP256 => Mechanism::EcKeyPairGen,
+        };
+        if self.mechanisms.contains(&mechanism) {
+            Ok(mechanism)
+        }
+        else {
+            Err(Error::UnsupportedMechanism)
+        }
+    }
+
+    /// Returns the mechanism for the given signature algorithm.
+    fn signing_mechanism(
+        &self, algorithm: SignatureAlgorithm
+    ) -> Option<Mechanism> {
+        let (hashing, plain) = match algorithm {
+            SignatureAlgorithm::RsaSha256 => {
+                (Mechanism::Sha256RsaPkcs, Mechanism::RsaPkcs)
+            }
+            SignatureAlgorithm::EcdsaP256Sha256 => {
+                (Mechanism::EcdsaSha256, Mechanism::Ecdsa)
+            }
+        };
+        if self.mechanisms.contains(&hashing) {
+            Some(hashing)
+        }
+        else if self.mechanisms.contains(&plain) {
+            Some(plain)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Runs an operation with a session.
+    ///
+    /// Uses an unused session if available or opens a new one. The session
+    /// is kept for later use unless the operation failed.
+    fn with_session<R, F>(&self, op: F) -> Result<R, T::Error>
+    where F: FnOnce(&T, &mut T::Session) -> Result<R, T::Error> {
+        let session = self.sessions.lock().unwrap().pop();
+        let mut session = match session {
+            Some(session) => session,
+            None => self.token.open_session()?,
+        };
+        let res = op(&self.token, &mut session)?;
+        let mut sessions = self.sessions.lock().unwrap();
+        if sessions.len() < self.max_idle_sessions {
+            sessions.push(session)
+        }
+        Ok(res)
+    }
+
+    /// Signs data with a key object.
+    fn sign_object(
+        token: &T,
+        session: &mut T::Session,
+        mechanism: Mechanism,
+        key: &T::Object,
+        data: &[u8],
+    ) -> Result<Vec<u8>, T::Error> {
+        match mechanism {
+            Mechanism::RsaPkcs => {
+                let digest = DigestAlgorithm::default().digest(data);
+                let mut info = SHA256_DIGEST_INFO_PREFIX.to_vec();
+                info.extend_from_slice(digest.as_ref());
+                token.sign(session, mechanism, key, &info)
+            }
+            Mechanism::Ecdsa => {
+                let digest = DigestAlgorithm::default().digest(data);
+                token.sign(session, mechanism, key, digest.as_ref())
+            }
+            _ => token.sign(session, mechanism, key, data)
+        }
+    }
+}
+
+impl<T: Token> Signer for Pkcs11Signer<T> {
+    type KeyId = KeyId;
+    type Error = Error<T::Error>;
+
+    fn create_key(
+        &mut self, algorithm: PublicKeyFormat
+    ) -> Result<Self::KeyId, Self::Error> {
+        self.create_key_with_label(algorithm, None)
+    }
+
+    fn get_key_info(
+        &self,
+        key: &Self::KeyId
+    ) -> Result<PublicKey, KeyError<Self::Error>> {
+        let raw = self.with_session(|token, session| {
+            match token.find_object(session, ObjectClass::PublicKey, key)? {
+                Some(object) => token.public_key(session, &object).map(Some),
+                None => Ok(None)
+            }
+        }).map_err(Error::Token)?;
+        match raw {
+            Some(raw) => Ok(raw.to_public_key()?),
+            None => Err(KeyError::KeyNotFound)
+        }
+    }
+
+    fn destroy_key(
+        &mut self, key: &Self::KeyId
+    ) -> Result<(), KeyError<Self::Error>> {
+        let found = self.with_session(|token, session| {
+            let private = token.find_object(
+                session, ObjectClass::PrivateKey, key
+            )?;
+            let private = match private {
+                Some(private) => private,
+                None => return Ok(false)
+            };
+            token.destroy_object(session, private)?;
+            let public = token.find_object(
+                session, ObjectClass::PublicKey, key
+            )?;
+            if let Some(public) = public {
+                token.destroy_object(session, public)?;
+            }
+            Ok(true)
+        }).map_err(Error::Token)?;
+        if found {
+            Ok(())
+        }
+        else {
+            Err(KeyError::KeyNotFound)
+        }
+    }
+
+    fn sign<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        key: &Self::KeyId,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<Signature, SigningError<Self::Error>> {
+        let mechanism = match self.signing_mechanism(algorithm) {
+            Some(mechanism) => mechanism,
+            None => return Err(Error::UnsupportedMechanism.into())
+        };
+        let res = self.with_session(|token, session| {
+            let object = token.find_object(
+                session, ObjectClass::PrivateKey, key
+            )?;
+            let object = match object {
+                Some(object) => object,
+                None => return Ok(Err(SigningError::KeyNotFound))
+            };
+            let format = token.key_format(session, &object)?;
+            if format != Some(algorithm.public_key_format()) {
+                return Ok(Err(SigningError::IncompatibleKey))
+            }
+            Self::sign_object(
+                token, session, mechanism, &object, data.as_ref()
+            ).map(Ok)
+        }).map_err(Error::Token)??;
+        Ok(Signature::new(algorithm, mechanism.finish_signature(res)?))
+    }
+
+    fn sign_one_off<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<(Signature, PublicKey), Self::Error> {
+        let mechanism = match self.signing_mechanism(algorithm) {
+            Some(mechanism) => mechanism,
+            None => return Err(Error::UnsupportedMechanism)
+        };
+        let format = algorithm.public_key_format();
+        let mut template = KeyTemplate::new(
+            format, self.generation_mechanism(format)?
+        );
+        template.persistent = false;
+        let (sig, raw) = self.with_session(|token, session| {
+            let (public, private) = token.generate_key_pair(
+                session, &template
+            )?;
+            let sig = Self::sign_object(
+                token, session, mechanism, &private, data.as_ref()
+            );
+            let raw = token.public_key(session, &public);
+            token.destroy_object(session, private)?;
+            token.destroy_object(session, public)?;
+            Ok((sig?, raw?))
+        }).map_err(Error::Token)?;
+        Ok((
+            Signature::new(algorithm, mechanism.finish_signature(sig)?),
+            raw.to_public_key()?
+        ))
+    }
+
+    fn rand(&self, target: &mut [u8]) -> Result<(), Self::Error> {
+        self.with_session(|token, session| {
+            token.generate_random(session, target)
+        }).map_err(Error::Token)
+    }
+
+    fn supports(&self, algorithm: SignatureAlgorithm) -> bool {
+        self.signing_mechanism(algorithm).is_some()
+    }
+
+    fn info(&self) -> SignerInfo {
+        let mut res = SignerInfo::new("PKCS#11");
+        res.set_model(self.token.model());
+        res.set_hardware(self.token.is_hardware());
+        res
+    }
+}
+
+
+//------------ KeyId ---------------------------------------------------------
+
+/// Identifies a key on the token.
+///
+/// A key is identified by its ID or label attribute or both. If both are
+/// given, a key has to match both.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct KeyId {
+    /// The value of the key’s ID attribute.
+    id: Option<Bytes>,
+
+    /// The value of the key’s label attribute.
+    label: Option<String>,
+}
+
+impl KeyId {
+    /// Creates a new key identifier from an ID and a label.
+    pub fn new(id: Option<Bytes>, label: Option<String>) -> Self {
+        KeyId { id, label }
+    }
+
+    /// Creates a new key identifier from the ID attribute only.
+    pub fn from_id(id: Bytes) -> Self {
+        Self::new(Some(id), None)
+    }
+
+    /// Creates a new key identifier from the label attribute only.
+    pub fn from_label(label: String) -> Self {
+        Self::new(None, Some(label))
+    }
+
+    /// Returns the ID attribute if present.
+    pub fn id(&self) -> Option<&Bytes> {
+        self.id.as_ref()
+    }
+
+    /// Returns the label attribute if present.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+
+//------------ ObjectClass ---------------------------------------------------
+
+/// The class of a key object.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ObjectClass {
+    /// A public key object, `CKO_PUBLIC_KEY`.
+    PublicKey,
+
+    /// A private key object, `CKO_PRIVATE_KEY`.
+    PrivateKey,
+}
+
+
+//------------ Mechanism -----------------------------------------------------
+
+/// The PKCS #11 mechanisms used by the signer.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Mechanism {
+    /// RSA key pair generation, `CKM_RSA_PKCS_KEY_PAIR_GEN`.
+    RsaPkcsKeyPairGen,
+
+    /// RSA PKCS #1 v1.5 signing of a prepared digest, `CKM_RSA_PKCS`.
+    RsaPkcs,
+
+    /// RSA PKCS #1 v1.5 signing with SHA-256, `CKM_SHA256_RSA_PKCS`.
+    Sha256RsaPkcs,
+
+    /// EC key pair generation, `CKM_EC_KEY_PAIR_GEN`.
+    EcKeyPairGen,
+
+    /// ECDSA signing of a digest, `CKM_ECDSA`.
+    Ecdsa,
+
+    /// ECDSA signing with SHA-256, `CKM_ECDSA_SHA256`.
+    EcdsaSha256,
+}
+
+impl Mechanism {
+    /// Returns the mechanism for a PKCS #11 mechanism type code.
+    pub fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0x0000 => Some(Mechanism::RsaPkcsKeyPairGen),
+            0x0001 => Some(Mechanism::RsaPkcs),
+            0x0040 => Some(Mechanism::Sha256RsaPkcs),
+            0x1040 => Some(Mechanism::EcKeyPairGen),
+            0x1041 => Some(Mechanism::Ecdsa),
+            0x1044 => Some(Mechanism::EcdsaSha256),
+            _ => None
+        }
+    }
+
+    /// Returns the PKCS #11 mechanism type code.
+    pub fn code(self) -> u64 {
+        match self {
+            Mechanism::RsaPkcsKeyPairGen => 0x0000,
+            Mechanism::RsaPkcs => 0x0001,
+            Mechanism::Sha256RsaPkcs => 0x0040,
+            Mechanism::EcKeyPairGen => 0x1040,
+            Mechanism::Ecdsa => 0x1041,
+            Mechanism::EcdsaSha256 => 0x1044,
+        }
+    }
+
+    /// Converts a signature produced by the mechanism into RPKI format.
+    ///
+    /// PKCS #11 produces ECDSA signatures as the concatenation of the two
+    /// integers while the RPKI uses DER encoded signature values.
+    fn finish_signature<E>(self, sig: Vec<u8>) -> Result<Bytes, Error<E>> {
+        match self {
+            Mechanism::Ecdsa | Mechanism::EcdsaSha256 => {
+                if sig.len() != 64 {
+                    return Err(Error::InvalidSignature)
+                }
+                let (r, s) = sig.split_at(32);
+                let (r, s) = (unsigned_content(r), unsigned_content(s));
+                let res = encode::sequence((
+                    r.as_slice().encode_as(Tag::INTEGER),
+                    s.as_slice().encode_as(Tag::INTEGER),
+                )).to_captured(Mode::Der).into_bytes();
+                Ok(res)
+            }
+            _ => Ok(sig.into())
+        }
+    }
+}
+
+
+//------------ KeyTemplate ---------------------------------------------------
+
+/// The attributes for generating a new key pair.
+#[derive(Clone, Debug)]
+pub struct KeyTemplate {
+    /// The format of the key.
+    format: PublicKeyFormat,
+
+    /// The mechanism to use for generating the key pair.
+    mechanism: Mechanism,
+
+    /// The value of the ID attribute of both keys.
+    id: Bytes,
+
+    /// The value of the label attribute of both keys.
+    label: Option<String>,
+
+    /// Whether the keys should be token objects.
+    persistent: bool,
+}
+
+impl KeyTemplate {
+    fn new(format: PublicKeyFormat, mechanism: Mechanism) -> Self {
+        KeyTemplate {
+            format, mechanism,
+            id: Bytes::new(),
+            label: None,
+            persistent: true,
+        }
+    }
+
+    /// Returns the format of the key.
+    pub fn format(&self) -> PublicKeyFormat {
+        self.format
+    }
+
+    /// Returns the key pair generation mechanism.
+    pub fn mechanism(&self) -> Mechanism {
+        self.mechanism
+    }
+
+    /// Returns the value of the ID attribute for both keys.
+    ///
+    /// This is empty for one-off keys.
+    pub fn id(&self) -> &Bytes {
+        &self.id
+    }
+
+    /// Returns the value of the label attribute for both keys if any.
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns whether the keys should be stored on the token.
+    ///
+    /// If this is `false`, the keys should be session objects, i.e., the
+    /// `CKA_TOKEN` attribute should be false.
+    pub fn is_persistent(&self) -> bool {
+        self.persistent
+    }
+
+    /// Returns the modulus length in bits for RSA keys.
+    pub fn modulus_bits(&self) -> Option<u64> {
+        match self.format {
+            PublicKeyFormat::Rsa => Some(2048),
+            PublicKeyFormat::EcdsaP256 => None,
+        }
+    }
+
+    /// Returns the public exponent for RSA keys.
+    pub fn public_exponent(&self) -> Option<&'static [u8]> {
+        match self.format {
+            PublicKeyFormat::Rsa => Some(&[0x01, 0x00, 0x01]),
+            PublicKeyFormat::EcdsaP256 => None,
+        }
+    }
+
+    /// Returns the DER encoded EC parameters for EC keys.
+    ///
+    /// This is the value of the `CKA_EC_PARAMS` attribute, the object
+    /// identifier of the curve.
+    pub fn ec_params(&self) -> Option<&'static [u8]> {
+        match self.format {
+            PublicKeyFormat::Rsa => None,
+            PublicKeyFormat::EcdsaP256 => Some(&[
+                0x06, 0x08, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07
+            ]),
+        }
+    }
+}
+
+
+//------------ RawPublicKey --------------------------------------------------
+
+/// A public key as stored in the attributes of a public key object.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RawPublicKey {
+    /// An RSA public key.
+    Rsa {
+        /// The value of the `CKA_MODULUS` attribute.
+        modulus: Bytes,
+
+        /// The value of the `CKA_PUBLIC_EXPONENT` attribute.
+        exponent: Bytes,
+    },
+
+    /// An EC public key on the P-256 curve.
+    Ec {
+        /// The value of the `CKA_EC_POINT` attribute.
+        ///
+        /// This should be the DER encoded octet string containing the
+        /// uncompressed point. Since some tokens omit the encoding, the
+        /// bare point is accepted, too.
+        point: Bytes,
+    },
+}
+
+impl RawPublicKey {
+    /// Converts the raw key into a public key.
+    pub fn to_public_key<E>(&self) -> Result<PublicKey, Error<E>> {
+        match *self {
+            RawPublicKey::Rsa { ref modulus, ref exponent } => {
+                let modulus = unsigned_content(modulus);
+                let exponent = unsigned_content(exponent);
+                let bits = encode::sequence((
+                    modulus.as_slice().encode_as(Tag::INTEGER),
+                    exponent.as_slice().encode_as(Tag::INTEGER),
+                )).to_captured(Mode::Der).into_bytes();
+                Ok(PublicKey::new(PublicKeyFormat::Rsa, bits))
+            }
+            RawPublicKey::Ec { ref point } => {
+                let point = match point.len() {
+                    65 => point.clone(),
+                    67 if point[0] == 0x04 && point[1] == 65 => {
+                        point.slice(2..)
+                    }
+                    _ => return Err(Error::InvalidPublicKey)
+                };
+                if point[0] != 0x04 {
+                    return Err(Error::InvalidPublicKey)
+                }
+                Ok(PublicKey::new(PublicKeyFormat::EcdsaP256, point))
+            }
+        }
+    }
+}
+
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened in the PKCS #11 signer.
+#[derive(Clone, Debug)]
+pub enum Error<E> {
+    /// The token reported an error.
+    Token(E),
+
+    /// The token doesn’t support a mechanism needed for the operation.
+    UnsupportedMechanism,
+
+    /// The token provided a public key the signer doesn’t understand.
+    InvalidPublicKey,
+
+    /// The token produced a signature the signer doesn’t understand.
+    InvalidSignature,
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Token(ref err) => err.fmt(f),
+            Error::UnsupportedMechanism => {
+                f.write_str("mechanism not supported by token")
+            }
+            Error::InvalidPublicKey => f.write_str("invalid public key"),
+            Error::InvalidSignature => f.write_str("invalid signature"),
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for Error<E> { }
+
+
+//------------ Helpers -------------------------------------------------------
+
+/// The DER encoded DigestInfo for SHA-256 without the digest.
+const SHA256_DIGEST_INFO_PREFIX: &[u8] = &[
+    0x30, 0x31, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+    0x04, 0x02, 0x01, 0x05, 0x00, 0x04, 0x20
+];
+
+/// Returns the content of an INTEGER for a big-endian unsigned integer.
+fn unsigned_content(value: &[u8]) -> Vec<u8> {
+    let start = value.iter().position(|&x| x != 0).unwrap_or(value.len());
+    let value = &value[start..];
+    let mut res = Vec::with_capacity(value.len() + 1);
+    if value.first().map(|&x| x & 0x80 != 0).unwrap_or(true) {
+        res.push(0);
+    }
+    res.extend_from_slice(value);
+    res
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::cell::{Cell, RefCell};
+    use ring::rand::SystemRandom;
+    use ring::rand::SecureRandom;
+    use ring::signature::{
+        ECDSA_P256_SHA256_FIXED_SIGNING, EcdsaKeyPair, KeyPair
+    };
+    use super::*;
+
+    struct MockKey {
+        id: Bytes,
+        label: Option<String>,
+        pair: EcdsaKeyPair,
+    }
+
+    #[derive(Default)]
+    struct MockToken {
+        keys: RefCell<Vec<Option<MockKey>>>,
+        opened: Cell<usize>,
+    }
+
+    impl Token for MockToken {
+        type Session = ();
+        type Object = usize;
+        type Error = &'static str;
+
+        fn open_session(&self) -> Result<(), Self::Error> {
+            self.opened.set(self.opened.get() + 1);
+            Ok(())
+        }
+
+        fn mechanisms(&self) -> Result<Vec<u64>, Self::Error> {
+            Ok(vec![0x1040, 0x1044, 0x8000_0000])
+        }
+
+        fn find_object(
+            &self, _: &mut (), _: ObjectClass, key: &KeyId
+        ) -> Result<Option<usize>, Self::Error> {
+            Ok(self.keys.borrow().iter().position(|item| {
+                match *item {
+                    Some(ref item) => {
+                        key.id().map(|id| *id == item.id).unwrap_or(true)
+                        && key.label().map(|label| {
+                            item.label.as_deref()
+                                == Some(label)
+                        }).unwrap_or(true)
+                    }
+                    None => false
+                }
+            }))
+        }
+
+        fn generate_key_pair(
+            &self, _: &mut (), template: &KeyTemplate
+        ) -> Result<(usize, usize), Self::Error> {
+            assert_eq!(template.mechanism(), Mechanism::EcKeyPairGen);
+            let rng = SystemRandom::new();
+            let doc = EcdsaKeyPair::generate_pkcs8(
+                &ECDSA_P256_SHA256_FIXED_SIGNING, &rng
+            ).unwrap();
+            let pair = EcdsaKeyPair::from_pkcs8(
+                &ECDSA_P256_SHA256_FIXED_SIGNING, doc.as_ref()
+            ).unwrap();
+            let mut keys = self.keys.borrow_mut();
+            keys.push(Some(MockKey {
+                id: template.id().clone(),
+                label: template.label().map(Into::into),
+                pair
+            }));
+            Ok((keys.len() - 1, keys.len() - 1))
+        }
+
+        fn key_format(
+            &self, _: &mut (), _: &usize
+        ) -> Result<Option<PublicKeyFormat>, Self::Error> {
+            Ok(Some(PublicKeyFormat::EcdsaP256))
+        }
+
+        fn public_key(
+            &self, _: &mut (), object: &usize
+        ) -> Result<RawPublicKey, Self::Error> {
+            let keys = self.keys.borrow();
+            let key = keys[*object].as_ref().ok_or("no such object")?;
+            let mut point = vec![0x04, 65];
+            point.extend_from_slice(key.pair.public_key().as_ref());
+            Ok(RawPublicKey::Ec { point: point.into() })
+        }
+
+        fn sign(
+            &self, _: &mut (), mechanism: Mechanism, key: &usize,
+            data: &[u8]
+        ) -> Result<Vec<u8>, Self::Error> {
+            assert_eq!(mechanism, Mechanism::EcdsaSha256);
+            let keys = self.keys.borrow();
+            let key = keys[*key].as_ref().ok_or("no such object")?;
+            let sig = key.pair.sign(&SystemRandom::new(), data).unwrap();
+            Ok(sig.as_ref().into())
+        }
+
+        fn destroy_object(
+            &self, _: &mut (), object: usize
+        ) -> Result<(), Self::Error> {
+            self.keys.borrow_mut()[object] = None;
+            Ok(())
+        }
+
+        fn generate_random(
+            &self, _: &mut (), target: &mut [u8]
+        ) -> Result<(), Self::Error> {
+            SystemRandom::new().fill(target).map_err(|_| "rng failed")
+        }
+    }
+
+    #[test]
+    fn negotiation() {
+        let mut signer = Pkcs11Signer::new(MockToken::default()).unwrap();
+        assert_eq!(
+            signer.mechanisms(),
+            [Mechanism::EcKeyPairGen, Mechanism::EcdsaSha256]
+        );
+        assert!(signer.supports(SignatureAlgorithm::EcdsaP256Sha256));
+        assert!(!signer.supports(SignatureAlgorithm::RsaSha256));
+        assert!(signer.create_key(PublicKeyFormat::Rsa).is_err());
+        assert!(signer.info().hardware());
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let mut signer = Pkcs11Signer::new(MockToken::default()).unwrap();
+        let key = signer.create_key_with_label(
+            PublicKeyFormat::EcdsaP256, Some("ca".into())
+        ).unwrap();
+        assert_eq!(key.id().unwrap().len(), 20);
+
+        // The key can be found via its label only.
+        let by_label = KeyId::from_label("ca".into());
+        let info = signer.get_key_info(&by_label).unwrap();
+        assert_eq!(signer.get_key_info(&key).unwrap(), info);
+        let sig = signer.sign(
+            &by_label, SignatureAlgorithm::EcdsaP256Sha256, b"data"
+        ).unwrap();
+        info.verify(b"data", &sig).unwrap();
+        assert!(info.verify(b"other", &sig).is_err());
+
+        let (sig, info) = signer.sign_one_off(
+            SignatureAlgorithm::EcdsaP256Sha256, b"data"
+        ).unwrap();
+        info.verify(b"data", &sig).unwrap();
+
+        signer.destroy_key(&key).unwrap();
+        match signer.get_key_info(&key) {
+            Err(KeyError::KeyNotFound) => { }
+            _ => panic!("destroyed key still present")
+        }
+
+        // All operations shared a single session.
+        assert_eq!(signer.token().opened.get(), 1);
+        assert_eq!(signer.idle_sessions(), 1);
+    }
+
+    #[test]
+    fn raw_public_key() {
+        let rsa = RawPublicKey::Rsa {
+            modulus: Bytes::from_static(&[0x00, 0xc0, 0x01]),
+            exponent: Bytes::from_static(&[0x01, 0x00, 0x01]),
+        };
+        assert_eq!(
+            rsa.to_public_key::<()>().unwrap().bits(),
+            b"\x30\x0a\x02\x03\x00\xc0\x01\x02\x03\x01\x00\x01"
+        );
+        let point = RawPublicKey::Ec { point: Bytes::from(vec![4; 65]) };
+        assert!(point.to_public_key::<()>().is_ok());
+        let point = RawPublicKey::Ec { point: Bytes::from(vec![3; 65]) };
+        assert!(point.to_public_key::<()>().is_err());
+    }
+}
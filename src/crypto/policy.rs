@@ -0,0 +1,187 @@
+//! Policies for the algorithms acceptable during validation.
+
+use crate::x509::ValidationError;
+use super::keys::{PublicKey, PublicKeyFormat};
+use super::signature::SignatureAlgorithm;
+
+
+//------------ AlgorithmPolicy -----------------------------------------------
+
+/// The algorithms acceptable when validating RPKI objects.
+///
+/// The policy lists the signature algorithms allowed for signatures on
+/// certificates, CRLs, and signed objects, the formats allowed for the
+/// subject keys of CA and EE certificates, and the formats allowed for the
+/// keys of BGPsec router certificates.
+///
+/// The default policy follows [RFC 7935] and [RFC 8208]: signatures must
+/// use RSA with SHA-256, CA and EE certificates must certify RSA keys, and
+/// router certificates must certify ECDSA P-256 keys. When a transition to
+/// different algorithms happens, additional algorithms can be allowed
+/// without changing the validation API.
+///
+/// The policy is determined when validating a trust anchor certificate via
+/// [`Cert::validate_ta_with_policy_at`] and is then used for all objects
+/// issued under it.
+///
+/// [RFC 7935]: https://tools.ietf.org/html/rfc7935
+/// [RFC 8208]: https://tools.ietf.org/html/rfc8208
+/// [`Cert::validate_ta_with_policy_at`]: ../cert/struct.Cert.html#method.validate_ta_with_policy_at
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AlgorithmPolicy {
+    /// The allowed signature algorithms.
+    signature_algorithms: Vec<SignatureAlgorithm>,
+
+    /// The allowed formats for keys of CA and EE certificates.
+    key_formats: Vec<PublicKeyFormat>,
+
+    /// The allowed formats for keys of router certificates.
+    router_key_formats: Vec<PublicKeyFormat>,
+}
+
+impl AlgorithmPolicy {
+    /// Creates the default policy.
+    pub fn new() -> Self {
+        AlgorithmPolicy {
+            signature_algorithms: vec![SignatureAlgorithm::RsaSha256],
+            key_formats: vec![PublicKeyFormat::Rsa],
+            router_key_formats: vec![PublicKeyFormat::EcdsaP256],
+        }
+    }
+
+    /// Returns the allowed signature algorithms.
+    pub fn signature_algorithms(&self) -> &[SignatureAlgorithm] {
+        &self.signature_algorithms
+    }
+
+    /// Allows the given signature algorithm.
+    pub fn allow_signature_algorithm(&mut self, alg: SignatureAlgorithm) {
+        if !self.signature_algorithms.contains(&alg) {
+            self.signature_algorithms.push(alg)
+        }
+    }
+
+    /// Disallows the given signature algorithm.
+    pub fn deny_signature_algorithm(&mut self, alg: SignatureAlgorithm) {
+        self.signature_algorithms.retain(|item| *item != alg)
+    }
+
+    /// Returns the allowed formats for keys of CA and EE certificates.
+    pub fn key_formats(&self) -> &[PublicKeyFormat] {
+        &self.key_formats
+    }
+
+    /// Allows the given format for keys of CA and EE certificates.
+    pub fn allow_key_format(&mut self, format: PublicKeyFormat) {
+        if !self.key_formats.contains(&format) {
+            self.key_formats.push(format)
+        }
+    }
+
+    /// Disallows the given format for keys of CA and EE certificates.
+    pub fn deny_key_format(&mut self, format: PublicKeyFormat) {
+        self.key_formats.retain(|item| *item != format)
+    }
+
+    /// Returns the allowed formats for keys of router certificates.
+    pub fn router_key_formats(&self) -> &[PublicKeyFormat] {
+        &self.router_key_formats
+    }
+
+    /// Allows the given format for keys of router certificates.
+    pub fn allow_router_key_format(&mut self, format: PublicKeyFormat) {
+        if !self.router_key_formats.contains(&format) {
+            self.router_key_formats.push(format)
+        }
+    }
+
+    /// Disallows the given format for keys of router certificates.
+    pub fn deny_router_key_format(&mut self, format: PublicKeyFormat) {
+        self.router_key_formats.retain(|item| *item != format)
+    }
+
+    /// Checks that a signature algorithm is allowed.
+    pub fn check_signature(
+        &self, alg: SignatureAlgorithm
+    ) -> Result<(), ValidationError> {
+        if self.signature_algorithms.contains(&alg) {
+            Ok(())
+        }
+        else {
+            Err(ValidationError)
+        }
+    }
+
+    /// Checks that the key of a CA or EE certificate is allowed.
+    ///
+    /// In strict mode, RSA keys also need to have the size of 2048 bits
+    /// required by RFC 7935. Otherwise, any size we can verify signatures
+    /// for is fine.
+    pub fn check_key(
+        &self, key: &PublicKey, strict: bool
+    ) -> Result<(), ValidationError> {
+        if !self.key_formats.contains(key.algorithm()) {
+            return Err(ValidationError)
+        }
+        if strict && key.is_rsa() && key.key_size() != Some(2048) {
+            return Err(ValidationError)
+        }
+        Ok(())
+    }
+
+    /// Checks that the key of a router certificate is allowed.
+    pub fn check_router_key(
+        &self, key: &PublicKey
+    ) -> Result<(), ValidationError> {
+        if self.router_key_formats.contains(key.algorithm()) {
+            Ok(())
+        }
+        else {
+            Err(ValidationError)
+        }
+    }
+}
+
+
+//--- Default
+
+impl Default for AlgorithmPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_policy() {
+        let mut policy = AlgorithmPolicy::default();
+        assert!(policy.check_signature(SignatureAlgorithm::RsaSha256).is_ok());
+        assert!(
+            policy.check_signature(
+                SignatureAlgorithm::EcdsaP256Sha256
+            ).is_err()
+        );
+        assert_eq!(policy.key_formats(), [PublicKeyFormat::Rsa]);
+        assert_eq!(
+            policy.router_key_formats(), [PublicKeyFormat::EcdsaP256]
+        );
+
+        policy.allow_signature_algorithm(SignatureAlgorithm::EcdsaP256Sha256);
+        policy.allow_signature_algorithm(SignatureAlgorithm::EcdsaP256Sha256);
+        assert_eq!(policy.signature_algorithms().len(), 2);
+        policy.deny_signature_algorithm(SignatureAlgorithm::RsaSha256);
+        assert_eq!(
+            policy.signature_algorithms(),
+            [SignatureAlgorithm::EcdsaP256Sha256]
+        );
+        policy.allow_key_format(PublicKeyFormat::EcdsaP256);
+        policy.deny_key_format(PublicKeyFormat::Rsa);
+        assert_eq!(policy.key_formats(), [PublicKeyFormat::EcdsaP256]);
+    }
+}
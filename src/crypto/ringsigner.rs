@@ -0,0 +1,333 @@
+//! A signer atop the ring library.
+//!
+//! Because ring is used for verifying signatures anyway, this signer
+//! doesn’t add any additional dependencies. It is, however, limited by
+//! what ring supports: while ECDSA P-256 keys can be created, ring cannot
+//! generate RSA keys. RSA keys have to be created elsewhere and imported
+//! as PKCS #8 documents via [`RingSigner::import_pkcs8`]. This also means
+//! that the signer cannot create the one-off RSA keys used by signed
+//! objects.
+//!
+//! The signer is only available if the `ringkeys` feature is enabled.
+//!
+//! [`RingSigner::import_pkcs8`]: struct.RingSigner.html#method.import_pkcs8
+
+use std::{error, fmt};
+use bytes::Bytes;
+use ring::rand;
+use ring::rand::SecureRandom;
+use ring::signature::{
+    ECDSA_P256_SHA256_ASN1_SIGNING, RSA_PKCS1_SHA256, EcdsaKeyPair,
+    KeyPair as _, RsaKeyPair
+};
+use slab::Slab;
+use super::keys::{PublicKey, PublicKeyFormat};
+use super::signature::{Signature, SignatureAlgorithm};
+use super::signer::{KeyError, Signer, SignerInfo, SigningError};
+
+
+//------------ RingSigner ----------------------------------------------------
+
+/// A ring based signer.
+///
+/// Keeps the keys in memory.
+pub struct RingSigner {
+    keys: Slab<KeyPair>,
+    rng: rand::SystemRandom,
+}
+
+impl RingSigner {
+    /// Creates a new signer without any keys.
+    pub fn new() -> Self {
+        RingSigner {
+            keys: Default::default(),
+            rng: rand::SystemRandom::new(),
+        }
+    }
+
+    /// Imports a key from a PKCS #8 document.
+    ///
+    /// The document must contain an unencrypted private key of the given
+    /// format.
+    pub fn import_pkcs8(
+        &mut self, format: PublicKeyFormat, der: &[u8]
+    ) -> Result<KeyId, Error> {
+        Ok(KeyId(self.keys.insert(KeyPair::from_pkcs8(format, der)?)))
+    }
+}
+
+impl Signer for RingSigner {
+    type KeyId = KeyId;
+    type Error = Error;
+
+    fn create_key(
+        &mut self, algorithm: PublicKeyFormat
+    ) -> Result<Self::KeyId, Self::Error> {
+        let key = KeyPair::generate(algorithm, &self.rng)?;
+        Ok(KeyId(self.keys.insert(key)))
+    }
+
+    fn get_key_info(
+        &self,
+        id: &Self::KeyId
+    ) -> Result<PublicKey, KeyError<Self::Error>> {
+        match self.keys.get(id.0) {
+            Some(key) => Ok(key.get_key_info()),
+            None => Err(KeyError::KeyNotFound),
+        }
+    }
+
+    fn destroy_key(
+        &mut self, key: &Self::KeyId
+    ) -> Result<(), KeyError<Self::Error>> {
+        if self.keys.contains(key.0) {
+            self.keys.remove(key.0);
+            Ok(())
+        }
+        else {
+            Err(KeyError::KeyNotFound)
+        }
+    }
+
+    fn sign<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        key: &Self::KeyId,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<Signature, SigningError<Self::Error>> {
+        match self.keys.get(key.0) {
+            Some(key) => {
+                if key.format() != algorithm.public_key_format() {
+                    return Err(SigningError::IncompatibleKey)
+                }
+                key.sign(algorithm, data.as_ref(), &self.rng)
+                    .map_err(Into::into)
+            }
+            None => Err(SigningError::KeyNotFound)
+        }
+    }
+
+    fn sign_one_off<D: AsRef<[u8]> + ?Sized>(
+        &self,
+        algorithm: SignatureAlgorithm,
+        data: &D
+    ) -> Result<(Signature, PublicKey), Self::Error> {
+        let key = KeyPair::generate(
+            algorithm.public_key_format(), &self.rng
+        )?;
+        let sig = key.sign(algorithm, data.as_ref(), &self.rng)?;
+        Ok((sig, key.get_key_info()))
+    }
+
+    fn rand(&self, target: &mut [u8]) -> Result<(), Self::Error> {
+        self.rng.fill(target).map_err(|_| Error::Crypto)
+    }
+
+    fn supports(&self, _algorithm: SignatureAlgorithm) -> bool {
+        true
+    }
+
+    fn info(&self) -> SignerInfo {
+        SignerInfo::new("ring")
+    }
+}
+
+
+impl Default for RingSigner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//------------ KeyId ---------------------------------------------------------
+
+/// This signer’s key identifier.
+//
+//  We wrap this in a newtype so that people won’t start mucking about with
+//  the integers.
+#[derive(Clone, Copy, Debug)]
+pub struct KeyId(usize);
+
+
+//------------ KeyPair -------------------------------------------------------
+
+/// A key pair kept by the signer.
+enum KeyPair {
+    Rsa(RsaKeyPair),
+    Ecdsa(EcdsaKeyPair),
+}
+
+impl KeyPair {
+    fn generate(
+        format: PublicKeyFormat, rng: &dyn SecureRandom
+    ) -> Result<Self, Error> {
+        match format {
+            PublicKeyFormat::Rsa => Err(Error::Unsupported),
+            PublicKeyFormat::EcdsaP256 => {
+                let doc = EcdsaKeyPair::generate_pkcs8(
+                    &ECDSA_P256_SHA256_ASN1_SIGNING, rng
+                ).map_err(|_| Error::Crypto)?;
+                Self::from_pkcs8(format, doc.as_ref())
+            }
+        }
+    }
+
+    fn from_pkcs8(format: PublicKeyFormat, der: &[u8]) -> Result<Self, Error> {
+        match format {
+            PublicKeyFormat::Rsa => {
+                RsaKeyPair::from_pkcs8(der).map(KeyPair::Rsa)
+            }
+            PublicKeyFormat::EcdsaP256 => {
+                EcdsaKeyPair::from_pkcs8(
+                    &ECDSA_P256_SHA256_ASN1_SIGNING, der
+                ).map(KeyPair::Ecdsa)
+            }
+        }.map_err(|_| Error::InvalidKey)
+    }
+
+    fn format(&self) -> PublicKeyFormat {
+        match *self {
+            KeyPair::Rsa(_) => PublicKeyFormat::Rsa,
+            KeyPair::Ecdsa(_) => PublicKeyFormat::EcdsaP256,
+        }
+    }
+
+    fn get_key_info(&self) -> PublicKey {
+        let bits = match *self {
+            KeyPair::Rsa(ref key) => key.public_key().as_ref(),
+            KeyPair::Ecdsa(ref key) => key.public_key().as_ref(),
+        };
+        PublicKey::new(self.format(), Bytes::copy_from_slice(bits))
+    }
+
+    fn sign(
+        &self,
+        algorithm: SignatureAlgorithm,
+        data: &[u8],
+        rng: &dyn SecureRandom,
+    ) -> Result<Signature, Error> {
+        let value = match *self {
+            KeyPair::Rsa(ref key) => {
+                let mut res = vec![0; key.public_modulus_len()];
+                key.sign(&RSA_PKCS1_SHA256, rng, data, &mut res)
+                    .map_err(|_| Error::Crypto)?;
+                Bytes::from(res)
+            }
+            KeyPair::Ecdsa(ref key) => {
+                Bytes::copy_from_slice(
+                    key.sign(rng, data).map_err(|_| Error::Crypto)?.as_ref()
+                )
+            }
+        };
+        Ok(Signature::new(algorithm, value))
+    }
+}
+
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened in the ring signer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The operation isn’t supported by ring.
+    ///
+    /// This happens when trying to create an RSA key.
+    Unsupported,
+
+    /// An imported key was rejected.
+    InvalidKey,
+
+    /// A cryptographic operation failed.
+    Crypto,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Error::Unsupported => "operation not supported by ring",
+            Error::InvalidKey => "invalid key",
+            Error::Crypto => "cryptographic operation failed",
+        })
+    }
+}
+
+impl error::Error for Error { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ecdsa_keys() {
+        let mut signer = RingSigner::new();
+        let key = signer.create_key(PublicKeyFormat::EcdsaP256).unwrap();
+        let info = signer.get_key_info(&key).unwrap();
+        assert_eq!(*info.algorithm(), PublicKeyFormat::EcdsaP256);
+        let decoded = PublicKey::decode(info.to_info_bytes()).unwrap();
+        assert_eq!(decoded, info);
+
+        let sig = signer.sign(
+            &key, SignatureAlgorithm::EcdsaP256Sha256, b"data"
+        ).unwrap();
+        info.verify(b"data", &sig).unwrap();
+        assert!(info.verify(b"other", &sig).is_err());
+        match signer.sign(&key, SignatureAlgorithm::RsaSha256, b"data") {
+            Err(SigningError::IncompatibleKey) => { }
+            _ => panic!("signed with incompatible key")
+        }
+
+        let (sig, info) = signer.sign_one_off(
+            SignatureAlgorithm::EcdsaP256Sha256, b"data"
+        ).unwrap();
+        info.verify(b"data", &sig).unwrap();
+        signer.destroy_key(&key).unwrap();
+    }
+
+    #[test]
+    fn no_rsa_generation() {
+        let mut signer = RingSigner::new();
+        assert_eq!(
+            signer.create_key(PublicKeyFormat::Rsa).unwrap_err(),
+            Error::Unsupported
+        );
+        assert_eq!(
+            signer.sign_one_off(
+                SignatureAlgorithm::RsaSha256, b""
+            ).unwrap_err(),
+            Error::Unsupported
+        );
+        assert_eq!(
+            signer.import_pkcs8(PublicKeyFormat::Rsa, b"foo").unwrap_err(),
+            Error::InvalidKey
+        );
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod softkeys_test {
+    use openssl::rsa::Rsa;
+    use openssl::pkey::PKey;
+    use super::*;
+
+    #[test]
+    fn import_rsa() {
+        let pkey = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let der = pkey.private_key_to_pkcs8().unwrap();
+        let mut signer = RingSigner::new();
+        let key = signer.import_pkcs8(PublicKeyFormat::Rsa, &der).unwrap();
+        let info = signer.get_key_info(&key).unwrap();
+        assert_eq!(info.key_size(), Some(2048));
+        assert_eq!(
+            info.to_info_bytes().as_ref(),
+            pkey.public_key_to_der().unwrap().as_slice()
+        );
+        let sig = signer.sign(
+            &key, SignatureAlgorithm::RsaSha256, b"data"
+        ).unwrap();
+        info.verify(b"data", &sig).unwrap();
+    }
+}
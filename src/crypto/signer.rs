@@ -8,6 +8,11 @@ use super::signature::{Signature, SignatureAlgorithm};
 //------------ Signer --------------------------------------------------------
 
 /// A type that allow creating signatures.
+///
+/// Implementations of this trait are provided for OpenSSL by
+/// `softsigner::OpenSslSigner` if the `softkeys` feature is enabled, for
+/// ring by `ringsigner::RingSigner` if the `ringkeys` feature is enabled,
+/// and for hardware security modules by `pkcs11::Pkcs11Signer`.
 pub trait Signer {
     /// The type used for identifying keys.
     type KeyId;
@@ -59,6 +64,111 @@ pub trait Signer {
     ///
     /// The method fills the provide bytes slice with random data.
     fn rand(&self, target: &mut [u8]) -> Result<(), Self::Error>;
+
+    /// Returns whether the signer supports the given signature algorithm.
+    ///
+    /// Builders can use this to pick an algorithm the signer can actually
+    /// create signatures with. The default implementation only claims
+    /// support for RSA with SHA-256, the only algorithm used for RPKI
+    /// objects.
+    fn supports(&self, algorithm: SignatureAlgorithm) -> bool {
+        algorithm == SignatureAlgorithm::RsaSha256
+    }
+
+    /// Returns information about the signer.
+    ///
+    /// This allows operators to record where the keys of the signer live.
+    /// The default implementation returns information for an unspecified
+    /// software signer.
+    fn info(&self) -> SignerInfo {
+        SignerInfo::new("unspecified")
+    }
+}
+
+
+//------------ SignerInfo ----------------------------------------------------
+
+/// Information about a signer.
+///
+/// This is mostly useful for hardware security modules where it tells which
+/// device a key is kept in and how the device is configured.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SignerInfo {
+    /// The name of the backend used by the signer.
+    backend: String,
+
+    /// The model or version of the backend if known.
+    model: Option<String>,
+
+    /// Whether the backend is a hardware device.
+    hardware: bool,
+
+    /// Whether the backend operates in FIPS mode if known.
+    fips_mode: Option<bool>,
+}
+
+impl SignerInfo {
+    /// Creates new information for a software backend with the given name.
+    pub fn new<S: Into<String>>(backend: S) -> Self {
+        SignerInfo {
+            backend: backend.into(),
+            model: None,
+            hardware: false,
+            fips_mode: None,
+        }
+    }
+
+    /// Returns the name of the backend.
+    pub fn backend(&self) -> &str {
+        &self.backend
+    }
+
+    /// Returns the model or version of the backend if known.
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    /// Sets the model or version of the backend.
+    pub fn set_model<S: Into<String>>(&mut self, model: Option<S>) {
+        self.model = model.map(Into::into)
+    }
+
+    /// Returns whether the backend is a hardware device.
+    pub fn hardware(&self) -> bool {
+        self.hardware
+    }
+
+    /// Sets whether the backend is a hardware device.
+    pub fn set_hardware(&mut self, hardware: bool) {
+        self.hardware = hardware
+    }
+
+    /// Returns whether the backend operates in FIPS mode if known.
+    pub fn fips_mode(&self) -> Option<bool> {
+        self.fips_mode
+    }
+
+    /// Sets whether the backend operates in FIPS mode.
+    pub fn set_fips_mode(&mut self, fips_mode: Option<bool>) {
+        self.fips_mode = fips_mode
+    }
+}
+
+impl fmt::Display for SignerInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.backend)?;
+        if let Some(ref model) = self.model {
+            write!(f, " ({})", model)?;
+        }
+        if self.hardware {
+            f.write_str(", hardware")?;
+        }
+        match self.fips_mode {
+            Some(true) => f.write_str(", FIPS mode"),
+            Some(false) => f.write_str(", non-FIPS mode"),
+            None => Ok(())
+        }
+    }
 }
 
 
@@ -6,15 +6,19 @@
 //! Publication Server. In particular, this is not required when validating.
 
 use std::io;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::nid::Nid;
 use openssl::rsa::Rsa;
+use openssl::pkcs5::pbkdf2_hmac;
 use openssl::pkey::{PKey, Private};
 use openssl::hash::MessageDigest;
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
 use ring::rand;
 use ring::rand::SecureRandom;
 use slab::Slab;
 use super::keys::{PublicKey, PublicKeyFormat};
 use super::signature::{Signature, SignatureAlgorithm};
-use super::signer::{KeyError, Signer, SigningError};
+use super::signer::{KeyError, Signer, SignerInfo, SigningError};
 
 
 
@@ -43,6 +47,83 @@ impl OpenSslSigner {
     pub fn key_from_pem(&mut self, pem: &[u8]) -> Result<KeyId, io::Error> {
         Ok(KeyId(self.keys.insert(KeyPair::from_pem(pem)?)))
     }
+
+    /// Returns an iterator over the identifiers of all keys in the signer.
+    pub fn key_ids<'a>(&'a self) -> impl Iterator<Item = KeyId> + 'a {
+        self.keys.iter().map(|(idx, _)| KeyId(idx))
+    }
+
+    /// Exports a key in encrypted form.
+    ///
+    /// The private key is encoded as PKCS#8 and encrypted with AES-256-GCM
+    /// using a key derived from `passphrase` via PBKDF2 with HMAC-SHA256.
+    /// Because GCM authenticates the data, any modification of the exported
+    /// key will be detected when importing it again via [`import_key`].
+    ///
+    /// [`import_key`]: #method.import_key
+    pub fn export_key(
+        &self,
+        key: &KeyId,
+        passphrase: &[u8]
+    ) -> Result<Vec<u8>, KeyError<io::Error>> {
+        let key = self.keys.get(key.0).ok_or(KeyError::KeyNotFound)?;
+        let der = key.0.private_key_to_pkcs8().map_err(io::Error::from)?;
+
+        let mut res = Vec::with_capacity(EXPORT_HEADER_LEN + der.len() + 16);
+        res.extend_from_slice(EXPORT_MAGIC);
+        res.extend_from_slice(&EXPORT_ITERATIONS.to_be_bytes());
+        let mut salt_nonce = [0u8; EXPORT_SALT_LEN + EXPORT_NONCE_LEN];
+        self.rand(&mut salt_nonce)?;
+        res.extend_from_slice(&salt_nonce);
+        let (salt, nonce) = salt_nonce.split_at(EXPORT_SALT_LEN);
+
+        let mut tag = [0u8; EXPORT_TAG_LEN];
+        let data = encrypt_aead(
+            Cipher::aes_256_gcm(),
+            &export_key_derive(passphrase, salt, EXPORT_ITERATIONS)?,
+            Some(nonce), &res, &der, &mut tag
+        ).map_err(io::Error::from)?;
+        res.extend_from_slice(&tag);
+        res.extend_from_slice(&data);
+        Ok(res)
+    }
+
+    /// Imports a key previously exported via [`export_key`].
+    ///
+    /// Returns an error if the passphrase is wrong or the data has been
+    /// modified. Since the number of key derivation iterations is taken from
+    /// the data before it can be authenticated, it is limited to at most
+    /// ten times the number used by [`export_key`].
+    ///
+    /// [`export_key`]: #method.export_key
+    pub fn import_key(
+        &mut self,
+        data: &[u8],
+        passphrase: &[u8]
+    ) -> Result<KeyId, io::Error> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        if data.len() < EXPORT_HEADER_LEN + EXPORT_TAG_LEN
+            || !data.starts_with(EXPORT_MAGIC)
+        {
+            return Err(invalid("not an exported key"))
+        }
+        let (header, rest) = data.split_at(EXPORT_HEADER_LEN);
+        let (tag, data) = rest.split_at(EXPORT_TAG_LEN);
+        let params = &header[EXPORT_MAGIC.len()..];
+        let mut iterations = [0u8; 4];
+        iterations.copy_from_slice(&params[..4]);
+        let iterations = u32::from_be_bytes(iterations);
+        if iterations == 0 || iterations > MAX_IMPORT_ITERATIONS {
+            return Err(invalid("invalid number of iterations"))
+        }
+        let (salt, nonce) = params[4..].split_at(EXPORT_SALT_LEN);
+        let der = decrypt_aead(
+            Cipher::aes_256_gcm(),
+            &export_key_derive(passphrase, salt, iterations)?,
+            Some(nonce), header, data, tag
+        ).map_err(|_| invalid("wrong passphrase or corrupt data"))?;
+        self.key_from_der(&der)
+    }
 }
 
 impl Signer for OpenSslSigner {
@@ -86,7 +167,12 @@ impl Signer for OpenSslSigner {
         data: &D
     ) -> Result<Signature, SigningError<Self::Error>> {
         match self.keys.get(key.0) {
-            Some(key) => key.sign(algorithm, data.as_ref()).map_err(Into::into),
+            Some(key) => {
+                if !key.is_compatible(algorithm) {
+                    return Err(SigningError::IncompatibleKey)
+                }
+                key.sign(algorithm, data.as_ref()).map_err(Into::into)
+            }
             None => Err(SigningError::KeyNotFound)
         }
     }
@@ -107,6 +193,16 @@ impl Signer for OpenSslSigner {
             io::Error::new(io::ErrorKind::Other, "rng error")
         )
     }
+
+    fn supports(&self, _algorithm: SignatureAlgorithm) -> bool {
+        true
+    }
+
+    fn info(&self) -> SignerInfo {
+        let mut res = SignerInfo::new("OpenSSL");
+        res.set_model(Some(openssl::version::version()));
+        res
+    }
 }
 
 
@@ -117,6 +213,51 @@ impl Default for OpenSslSigner {
 }
 
 
+//------------ Key Export ----------------------------------------------------
+
+/// The magic octets at the start of an exported key.
+///
+/// This also serves as a version number of the format.
+const EXPORT_MAGIC: &[u8] = b"RPKI-KEY\x01";
+
+/// The number of PBKDF2 iterations for newly exported keys.
+const EXPORT_ITERATIONS: u32 = 100_000;
+
+/// The maximum number of PBKDF2 iterations accepted for imported keys.
+const MAX_IMPORT_ITERATIONS: u32 = 10 * EXPORT_ITERATIONS;
+
+/// The length of the salt for key derivation.
+const EXPORT_SALT_LEN: usize = 16;
+
+/// The length of the AES-GCM nonce.
+const EXPORT_NONCE_LEN: usize = 12;
+
+/// The length of the AES-GCM authentication tag.
+const EXPORT_TAG_LEN: usize = 16;
+
+/// The length of the unencrypted header of an exported key.
+///
+/// The header consists of the magic octets, the number of iterations as a
+/// big-endian 32 bit integer, the salt, and the nonce. It is included as
+/// additional authenticated data.
+const EXPORT_HEADER_LEN: usize
+    = EXPORT_MAGIC.len() + 4 + EXPORT_SALT_LEN + EXPORT_NONCE_LEN;
+
+/// Derives the encryption key for key export from a passphrase.
+fn export_key_derive(
+    passphrase: &[u8],
+    salt: &[u8],
+    iterations: u32,
+) -> Result<[u8; 32], io::Error> {
+    let mut res = [0u8; 32];
+    pbkdf2_hmac(
+        passphrase, salt, iterations as usize, MessageDigest::sha256(),
+        &mut res
+    )?;
+    Ok(res)
+}
+
+
 //------------ KeyId ---------------------------------------------------------
 
 /// This signer’s key identifier.
@@ -133,11 +274,16 @@ pub struct KeyId(usize);
 struct KeyPair(PKey<Private>);
 
 impl KeyPair {
-    fn new(_algorithm: PublicKeyFormat) -> Result<Self, io::Error> {
-        // Issues unwrapping this indicate a bug in the openssl library.
-        // So, there is no way to recover.
-        let rsa = Rsa::generate(2048)?;
-        let pkey = PKey::from_rsa(rsa)?;
+    fn new(algorithm: PublicKeyFormat) -> Result<Self, io::Error> {
+        let pkey = match algorithm {
+            PublicKeyFormat::Rsa => PKey::from_rsa(Rsa::generate(2048)?)?,
+            PublicKeyFormat::EcdsaP256 => {
+                let group = EcGroup::from_curve_name(
+                    Nid::X9_62_PRIME256V1
+                )?;
+                PKey::from_ec_key(EcKey::generate(&group)?)?
+            }
+        };
         Ok(KeyPair(pkey))
     }
 
@@ -167,23 +313,28 @@ impl KeyPair {
     {
         // Issues unwrapping this indicate a bug in the openssl
         // library. So, there is no way to recover.
-        let der = self.0.rsa().unwrap().public_key_to_der()?;
+        let der = self.0.public_key_to_der()?;
         Ok(PublicKey::decode(der.as_ref()).unwrap())
     }
 
+    /// Returns whether the key can be used with the signature algorithm.
+    fn is_compatible(&self, algorithm: SignatureAlgorithm) -> bool {
+        match algorithm.public_key_format() {
+            PublicKeyFormat::Rsa => self.0.rsa().is_ok(),
+            PublicKeyFormat::EcdsaP256 => self.0.ec_key().is_ok(),
+        }
+    }
+
     fn sign(
         &self,
-        _algorithm: SignatureAlgorithm,
+        algorithm: SignatureAlgorithm,
         data: &[u8]
     ) -> Result<Signature, io::Error> {
         let mut signer = ::openssl::sign::Signer::new(
             MessageDigest::sha256(), &self.0
         )?;
         signer.update(data)?;
-        Ok(Signature::new(
-            SignatureAlgorithm::default(),
-            signer.sign_to_vec()?.into()
-        ))
+        Ok(Signature::new(algorithm, signer.sign_to_vec()?.into()))
     }
 }
 
@@ -204,7 +355,58 @@ pub mod tests {
         let _ = s.sign(&ki, SignatureAlgorithm::default(), data).unwrap();
         s.destroy_key(&ki).unwrap();
     }
+
+    #[test]
+    fn info() {
+        let s = OpenSslSigner::new();
+        assert!(s.supports(SignatureAlgorithm::default()));
+        assert_eq!(s.info().backend(), "OpenSSL");
+        assert!(!s.info().hardware());
+    }
     
+    #[test]
+    fn export_import() {
+        let mut s = OpenSslSigner::new();
+        let ki = s.create_key(PublicKeyFormat::default()).unwrap();
+        let exported = s.export_key(&ki, b"secret").unwrap();
+
+        let mut other = OpenSslSigner::new();
+        assert!(other.import_key(&exported, b"wrong").is_err());
+        let mut tampered = exported.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(other.import_key(&tampered, b"secret").is_err());
+        let iterations = EXPORT_MAGIC.len()..EXPORT_MAGIC.len() + 4;
+        for &count in &[0, MAX_IMPORT_ITERATIONS + 1, u32::MAX] {
+            let mut tampered = exported.clone();
+            tampered[iterations.clone()].copy_from_slice(
+                &count.to_be_bytes()
+            );
+            assert_eq!(
+                other.import_key(&tampered, b"secret").unwrap_err()
+                    .to_string(),
+                "invalid number of iterations"
+            );
+        }
+        let imported = other.import_key(&exported, b"secret").unwrap();
+        assert_eq!(
+            s.get_key_info(&ki).unwrap(),
+            other.get_key_info(&imported).unwrap()
+        );
+        assert_eq!(other.key_ids().count(), 1);
+    }
+
+    #[test]
+    fn ecdsa() {
+        let mut s = OpenSslSigner::new();
+        let ki = s.create_key(PublicKeyFormat::EcdsaP256).unwrap();
+        let info = s.get_key_info(&ki).unwrap();
+        let sig = s.sign(
+            &ki, SignatureAlgorithm::EcdsaP256Sha256, b"foobar"
+        ).unwrap();
+        info.verify(b"foobar", &sig).unwrap();
+        assert!(s.sign(&ki, SignatureAlgorithm::RsaSha256, b"foo").is_err());
+    }
+
     #[test]
     fn one_off() {
         let s = OpenSslSigner::new();
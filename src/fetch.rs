@@ -0,0 +1,571 @@
+//! Classification of failures when fetching repository data.
+//!
+//! Whatever transport is used to retrieve RPKI data from a publication
+//! point, a failed attempt ends up in one of a small number of categories.
+//! This module provides the typed error [`FetchError`] for these failures.
+//! Each error can suggest a [`RetryPolicy`] describing when the next
+//! attempt should happen.
+//!
+//! The suggestions are consumed by the [`HealthTracker`]. It remembers the
+//! outcome of fetch attempts per repository and keeps a set of
+//! [`RepositoryMetrics`] for each of them.
+//!
+//! [`FetchError`]: enum.FetchError.html
+//! [`RetryPolicy`]: enum.RetryPolicy.html
+//! [`HealthTracker`]: struct.HealthTracker.html
+//! [`RepositoryMetrics`]: struct.RepositoryMetrics.html
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use chrono::{DateTime, Duration, Utc};
+use crate::x509::Time;
+
+
+//------------ FetchError ----------------------------------------------------
+
+/// An error happened while fetching data from a repository.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum FetchError {
+    /// Resolving the host name failed.
+    Dns(String),
+
+    /// Establishing a connection to the server failed.
+    Connect(String),
+
+    /// The TLS handshake or certificate verification failed.
+    Tls(String),
+
+    /// The server responded with an unexpected HTTP status code.
+    ///
+    /// If the server included a Retry-After header, the duration it asked
+    /// us to wait is included.
+    HttpStatus {
+        /// The status code of the response.
+        status: u16,
+
+        /// The duration requested via the Retry-After header, if any.
+        retry_after: Option<Duration>,
+    },
+
+    /// The response was larger than the configured limit in octets.
+    SizeExceeded {
+        /// The limit that was exceeded.
+        limit: u64,
+    },
+
+    /// The data did not match the hash it was published with.
+    HashMismatch,
+
+    /// The data could not be parsed.
+    Malformed,
+
+    /// The operation did not complete in time.
+    Timeout,
+}
+
+impl FetchError {
+    /// Creates an HTTP status error from a status and Retry-After header.
+    ///
+    /// The header value is parsed via [`parse_retry_after`] relative to the
+    /// current time. An unparseable value is silently ignored.
+    ///
+    /// [`parse_retry_after`]: fn.parse_retry_after.html
+    pub fn http_status(status: u16, retry_after: Option<&str>) -> Self {
+        FetchError::HttpStatus {
+            status,
+            retry_after: retry_after.and_then(|value| {
+                parse_retry_after(value, Time::now())
+            })
+        }
+    }
+
+    /// Returns the class of the failure.
+    pub fn class(&self) -> FailureClass {
+        match *self {
+            FetchError::Dns(_) | FetchError::Connect(_)
+                | FetchError::Timeout => FailureClass::Network,
+            FetchError::Tls(_) => FailureClass::Security,
+            FetchError::HttpStatus { status, .. } => {
+                if status >= 500 || status == 429 {
+                    FailureClass::Server
+                }
+                else {
+                    FailureClass::Client
+                }
+            }
+            FetchError::SizeExceeded { .. }
+                | FetchError::HashMismatch
+                | FetchError::Malformed => FailureClass::Content,
+        }
+    }
+
+    /// Returns the retry policy suggested for this error.
+    ///
+    /// Transient network and server problems suggest backing off. A server
+    /// that asked for a specific delay via Retry-After gets exactly that.
+    /// Problems with the data itself or with the TLS setup will not go away
+    /// by trying again soon, so these suggest falling back to a different
+    /// transport.
+    pub fn retry_policy(&self) -> RetryPolicy {
+        match *self {
+            FetchError::HttpStatus { retry_after: Some(delay), .. } => {
+                RetryPolicy::After(delay)
+            }
+            _ => match self.class() {
+                FailureClass::Network | FailureClass::Server => {
+                    RetryPolicy::Backoff
+                }
+                FailureClass::Client => RetryPolicy::NextRun,
+                FailureClass::Security | FailureClass::Content => {
+                    RetryPolicy::Fallback
+                }
+            }
+        }
+    }
+
+    /// Returns whether the error suggests falling back to another transport.
+    ///
+    /// For RRDP, this means that the data should be fetched via rsync
+    /// instead.
+    #[allow(clippy::match_like_matches_macro)] // matches! needs Rust 1.42.
+    pub fn should_fall_back(&self) -> bool {
+        match self.retry_policy() {
+            RetryPolicy::Fallback => true,
+            _ => false
+        }
+    }
+}
+
+
+//--- Display and Error
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FetchError::Dns(ref msg)
+                => write!(f, "name resolution failed: {}", msg),
+            FetchError::Connect(ref msg)
+                => write!(f, "connection failed: {}", msg),
+            FetchError::Tls(ref msg)
+                => write!(f, "TLS error: {}", msg),
+            FetchError::HttpStatus { status, .. }
+                => write!(f, "unexpected HTTP status {}", status),
+            FetchError::SizeExceeded { limit }
+                => write!(f, "size limit of {} octets exceeded", limit),
+            FetchError::HashMismatch
+                => f.write_str("hash mismatch"),
+            FetchError::Malformed
+                => f.write_str("malformed data"),
+            FetchError::Timeout
+                => f.write_str("operation timed out"),
+        }
+    }
+}
+
+impl error::Error for FetchError { }
+
+
+//------------ FailureClass --------------------------------------------------
+
+/// The broad class a fetch failure belongs to.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FailureClass {
+    /// The server could not be reached.
+    Network,
+
+    /// A secure connection to the server could not be established.
+    Security,
+
+    /// The server reported a problem on its side.
+    Server,
+
+    /// The server rejected our request.
+    Client,
+
+    /// The data received was unacceptable.
+    Content,
+}
+
+
+//------------ RetryPolicy ---------------------------------------------------
+
+/// The suggested approach for retrying after a failed fetch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RetryPolicy {
+    /// Retry once the given duration has passed.
+    After(Duration),
+
+    /// Retry with an exponentially increasing delay.
+    Backoff,
+
+    /// Don’t retry until the next regular validation run.
+    NextRun,
+
+    /// Don’t retry with this transport but fall back to another one.
+    Fallback,
+}
+
+
+//------------ parse_retry_after ---------------------------------------------
+
+/// Parses the value of an HTTP Retry-After header.
+///
+/// The value can either be a number of seconds or an HTTP date. In the
+/// latter case, the duration is calculated relative to `now`. A date in the
+/// past results in a zero duration.
+pub fn parse_retry_after(value: &str, now: Time) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<u32>() {
+        return Some(Duration::seconds(secs.into()))
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let delay = date.with_timezone(&Utc) - *now;
+    if delay < Duration::zero() {
+        Some(Duration::zero())
+    }
+    else {
+        Some(delay)
+    }
+}
+
+
+//------------ add_delay -----------------------------------------------------
+
+/// Returns the time `delay` after `now`.
+///
+/// If that time can’t be represented, returns the latest time that can.
+#[allow(deprecated)] // DateTime::MAX_UTC requires chrono 0.4.20.
+fn add_delay(now: Time, delay: Duration) -> Time {
+    Time::new(now.checked_add_signed(delay).unwrap_or(chrono::MAX_DATETIME))
+}
+
+
+//------------ HealthTracker -------------------------------------------------
+
+/// Keeps track of the health of repositories.
+///
+/// Repositories are identified by a string, typically the URI of the
+/// RRDP notification file or the rsync module. For each of them, the
+/// tracker records the outcome of fetch attempts and uses the retry
+/// policy of failures to determine when the next attempt should be made.
+#[derive(Clone, Debug)]
+pub struct HealthTracker {
+    /// The health of each repository.
+    repositories: HashMap<String, RepositoryHealth>,
+
+    /// The initial delay for the backoff policy.
+    base_delay: Duration,
+
+    /// The maximum delay for the backoff policy.
+    max_delay: Duration,
+}
+
+impl HealthTracker {
+    /// Creates a new tracker with default backoff parameters.
+    ///
+    /// The backoff starts at one minute and is capped at one hour.
+    pub fn new() -> Self {
+        Self::with_backoff(Duration::minutes(1), Duration::hours(1))
+    }
+
+    /// Creates a new tracker with the given backoff parameters.
+    pub fn with_backoff(base_delay: Duration, max_delay: Duration) -> Self {
+        HealthTracker {
+            repositories: HashMap::new(),
+            base_delay,
+            max_delay,
+        }
+    }
+
+    /// Records a successful fetch from a repository.
+    pub fn success(&mut self, repository: &str, now: Time) {
+        let health = self.entry(repository);
+        health.consecutive_failures = 0;
+        health.next_attempt = None;
+        health.fallback = false;
+        health.metrics.successes += 1;
+        health.metrics.last_success = Some(now);
+    }
+
+    /// Records a failed fetch from a repository.
+    ///
+    /// Returns the retry policy that was applied.
+    pub fn failure(
+        &mut self,
+        repository: &str,
+        err: &FetchError,
+        now: Time
+    ) -> RetryPolicy {
+        let (base_delay, max_delay) = (self.base_delay, self.max_delay);
+        let health = self.entry(repository);
+        let policy = err.retry_policy();
+        health.consecutive_failures += 1;
+        health.metrics.failures += 1;
+        health.metrics.last_failure = Some(now);
+        health.metrics.last_error = Some(err.clone());
+        *health.metrics.classes.entry(err.class()).or_insert(0) += 1;
+        match policy {
+            RetryPolicy::After(delay) => {
+                health.next_attempt = Some(add_delay(now, delay));
+            }
+            RetryPolicy::Backoff => {
+                // If the doubled delay would exceed the maximum, use the
+                // maximum instead so the multiplication can’t overflow.
+                let shift = (health.consecutive_failures - 1).min(16);
+                let factor = 1i32 << shift;
+                let delay = if base_delay > max_delay / factor {
+                    max_delay
+                }
+                else {
+                    base_delay * factor
+                };
+                health.next_attempt = Some(add_delay(now, delay));
+            }
+            RetryPolicy::NextRun => {
+                health.next_attempt = None;
+            }
+            RetryPolicy::Fallback => {
+                health.next_attempt = None;
+                health.fallback = true;
+            }
+        }
+        policy
+    }
+
+    /// Returns whether a fetch from the repository should be attempted.
+    ///
+    /// This is the case if there is no pending backoff delay for the
+    /// repository or that delay has passed.
+    pub fn may_fetch(&self, repository: &str, now: Time) -> bool {
+        match self.repositories.get(repository) {
+            Some(health) => match health.next_attempt {
+                Some(next) => next <= now,
+                None => true,
+            }
+            None => true
+        }
+    }
+
+    /// Returns whether fetching should fall back to another transport.
+    pub fn should_fall_back(&self, repository: &str) -> bool {
+        self.repositories.get(repository).map(|health| {
+            health.fallback
+        }).unwrap_or(false)
+    }
+
+    /// Returns the health information for a repository if available.
+    pub fn get(&self, repository: &str) -> Option<&RepositoryHealth> {
+        self.repositories.get(repository)
+    }
+
+    /// Returns an iterator over all repositories and their health.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &RepositoryHealth)> {
+        self.repositories.iter().map(|(key, value)| (key.as_str(), value))
+    }
+
+    fn entry(&mut self, repository: &str) -> &mut RepositoryHealth {
+        if !self.repositories.contains_key(repository) {
+            self.repositories.insert(
+                repository.into(), RepositoryHealth::default()
+            );
+        }
+        self.repositories.get_mut(repository).unwrap()
+    }
+}
+
+impl Default for HealthTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//------------ RepositoryHealth ----------------------------------------------
+
+/// The health of a single repository.
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryHealth {
+    /// The number of failures since the last success.
+    consecutive_failures: u32,
+
+    /// The earliest time for the next attempt.
+    next_attempt: Option<Time>,
+
+    /// Whether the last failure suggested using another transport.
+    fallback: bool,
+
+    /// The metrics for the repository.
+    metrics: RepositoryMetrics,
+}
+
+impl RepositoryHealth {
+    /// Returns the number of failures since the last success.
+    pub fn consecutive_failures(&self) -> u32 {
+        self.consecutive_failures
+    }
+
+    /// Returns the earliest time for the next fetch attempt if restricted.
+    pub fn next_attempt(&self) -> Option<Time> {
+        self.next_attempt
+    }
+
+    /// Returns a reference to the metrics of the repository.
+    pub fn metrics(&self) -> &RepositoryMetrics {
+        &self.metrics
+    }
+}
+
+
+//------------ RepositoryMetrics ---------------------------------------------
+
+/// Metrics about fetching from a single repository.
+#[derive(Clone, Debug, Default)]
+pub struct RepositoryMetrics {
+    /// The number of successful fetches.
+    successes: u64,
+
+    /// The number of failed fetches.
+    failures: u64,
+
+    /// The number of failures per failure class.
+    classes: HashMap<FailureClass, u64>,
+
+    /// The time of the last successful fetch.
+    last_success: Option<Time>,
+
+    /// The time of the last failed fetch.
+    last_failure: Option<Time>,
+
+    /// The error of the last failed fetch.
+    last_error: Option<FetchError>,
+}
+
+impl RepositoryMetrics {
+    /// Returns the number of successful fetches.
+    pub fn successes(&self) -> u64 {
+        self.successes
+    }
+
+    /// Returns the number of failed fetches.
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    /// Returns the number of failures of the given class.
+    pub fn failures_of(&self, class: FailureClass) -> u64 {
+        self.classes.get(&class).copied().unwrap_or(0)
+    }
+
+    /// Returns the time of the last successful fetch.
+    pub fn last_success(&self) -> Option<Time> {
+        self.last_success
+    }
+
+    /// Returns the time of the last failed fetch.
+    pub fn last_failure(&self) -> Option<Time> {
+        self.last_failure
+    }
+
+    /// Returns the error of the last failed fetch.
+    pub fn last_error(&self) -> Option<&FetchError> {
+        self.last_error.as_ref()
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn retry_after() {
+        let now = Time::utc(2015, 10, 21, 7, 28, 0);
+        assert_eq!(
+            parse_retry_after("120", now),
+            Some(Duration::seconds(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:30:00 GMT", now),
+            Some(Duration::seconds(120))
+        );
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:00:00 GMT", now),
+            Some(Duration::zero())
+        );
+        assert_eq!(parse_retry_after("soon", now), None);
+    }
+
+    #[test]
+    fn policies() {
+        assert_eq!(FetchError::Timeout.retry_policy(), RetryPolicy::Backoff);
+        assert_eq!(
+            FetchError::HashMismatch.retry_policy(), RetryPolicy::Fallback
+        );
+        assert_eq!(
+            FetchError::http_status(404, None).retry_policy(),
+            RetryPolicy::NextRun
+        );
+        assert_eq!(
+            FetchError::http_status(503, Some("30")).retry_policy(),
+            RetryPolicy::After(Duration::seconds(30))
+        );
+    }
+
+    #[test]
+    fn health_tracker() {
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        let mut tracker = HealthTracker::new();
+        let repo = "https://rrdp.example.net/notification.xml";
+        assert!(tracker.may_fetch(repo, now));
+
+        tracker.failure(repo, &FetchError::Timeout, now);
+        assert!(!tracker.may_fetch(repo, now));
+        assert!(tracker.may_fetch(repo, now + Duration::minutes(1)));
+        tracker.failure(repo, &FetchError::Timeout, now);
+        assert!(!tracker.may_fetch(repo, now + Duration::minutes(1)));
+        assert!(tracker.may_fetch(repo, now + Duration::minutes(2)));
+
+        tracker.failure(repo, &FetchError::HashMismatch, now);
+        assert!(tracker.should_fall_back(repo));
+
+        tracker.success(repo, now);
+        assert!(!tracker.should_fall_back(repo));
+        let health = tracker.get(repo).unwrap();
+        assert_eq!(health.consecutive_failures(), 0);
+        assert_eq!(health.metrics().failures(), 3);
+        assert_eq!(health.metrics().successes(), 1);
+        assert_eq!(
+            health.metrics().failures_of(FailureClass::Network), 2
+        );
+        assert_eq!(
+            health.metrics().last_error(), Some(&FetchError::HashMismatch)
+        );
+    }
+
+    #[test]
+    fn health_tracker_overflow() {
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        let huge = Duration::seconds(i64::MAX / 1000);
+        let mut tracker = HealthTracker::with_backoff(huge, huge);
+        let repo = "https://rrdp.example.net/notification.xml";
+        for _ in 0..20 {
+            tracker.failure(repo, &FetchError::Timeout, now);
+            assert!(
+                !tracker.may_fetch(repo, Time::utc(9999, 1, 1, 0, 0, 0))
+            );
+        }
+
+        let mut tracker = HealthTracker::with_backoff(
+            Duration::days(1_000_000), Duration::days(2_000_000)
+        );
+        for _ in 0..20 {
+            tracker.failure(repo, &FetchError::Timeout, now);
+        }
+        assert_eq!(
+            tracker.get(repo).unwrap().next_attempt(),
+            Some(now + Duration::days(2_000_000))
+        );
+    }
+}
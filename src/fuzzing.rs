@@ -0,0 +1,630 @@
+//! Support for fuzzing RPKI objects.
+//!
+//! Fuzzers that mutate the raw bytes of an RPKI object rarely produce
+//! input that makes it past decoding the outer CMS or certificate
+//! structure. This module provides structured mutations instead: they
+//! keep the DER encoding intact and change exactly one aspect of an
+//! object, so the mutated object is decoded completely and the deeper
+//! validation steps are exercised.
+//!
+//! A [`Mutation`] is applied to an encoded object via [`Mutation::apply`].
+//! Fuzzers that only provide random numbers or bytes can create mutations
+//! via [`Mutation::from_u64`]. A set of valid objects to start from is
+//! available via [`seeds`].
+//!
+//! The module is only available if the `fuzzing` feature is enabled.
+//!
+//! [`Mutation`]: enum.Mutation.html
+//! [`Mutation::apply`]: enum.Mutation.html#method.apply
+//! [`Mutation::from_u64`]: enum.Mutation.html#method.from_u64
+//! [`seeds`]: fn.seeds.html
+
+use std::mem;
+use bytes::Bytes;
+
+
+//------------ seeds ---------------------------------------------------------
+
+/// Returns a corpus of valid objects to use as fuzzing seeds.
+///
+/// The corpus contains a trust anchor and a CA certificate, their CRLs and
+/// manifests, and a ROA. Each object is given with its file name.
+pub fn seeds() -> &'static [(&'static str, &'static [u8])] {
+    &[
+        ("ta.cer", include_bytes!("../test-data/ta.cer")),
+        ("ta.crl", include_bytes!("../test-data/ta.crl")),
+        ("ta.mft", include_bytes!("../test-data/ta.mft")),
+        ("ca1.cer", include_bytes!("../test-data/ca1.cer")),
+        ("ca1.crl", include_bytes!("../test-data/ca1.crl")),
+        ("ca1.mft", include_bytes!("../test-data/ca1.mft")),
+        (
+            "example-ripe.roa",
+            include_bytes!("../test-data/example-ripe.roa")
+        ),
+    ]
+}
+
+
+//------------ Mutation ------------------------------------------------------
+
+/// A structured mutation of an RPKI object.
+///
+/// Each mutation takes an index or length that selects what exactly is
+/// changed. Since the number of possible targets depends on the object,
+/// these values are taken modulo the number of targets, so any value
+/// results in a mutation if the object has at least one target.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Mutation {
+    /// Flips a bit in the resources of a certificate.
+    ///
+    /// The bits considered are those of the content of all BIT STRINGs and
+    /// INTEGERs in the IP address and AS resources extensions of all
+    /// certificates in the object, including the EE certificate of a
+    /// signed object. The value selects the bit to flip.
+    FlipResourceBit(usize),
+
+    /// Truncates the signature.
+    ///
+    /// For certificates and CRLs, this is the signature of the object
+    /// itself. For signed objects, it is the signature of the first signer
+    /// info. The value selects the number of octets to keep.
+    TruncateSignature(usize),
+
+    /// Reorders the elements of a SET.
+    ///
+    /// The sets considered are all SETs and SET OFs with at least two
+    /// elements, including the signed attributes of signed objects. The
+    /// value selects the set whose elements are rotated by one.
+    ReorderSet(usize),
+}
+
+impl Mutation {
+    /// Creates a mutation from a random number.
+    pub fn from_u64(value: u64) -> Self {
+        let index = (value / 3) as usize;
+        match value % 3 {
+            0 => Mutation::FlipResourceBit(index),
+            1 => Mutation::TruncateSignature(index),
+            _ => Mutation::ReorderSet(index),
+        }
+    }
+
+    /// Applies the mutation to a DER encoded object.
+    ///
+    /// Returns the mutated object or `None` if the data isn’t valid DER
+    /// or the object doesn’t have a target for the mutation.
+    pub fn apply(self, data: &[u8]) -> Option<Bytes> {
+        let mut node = Node::decode(data)?;
+        let done = match self {
+            Mutation::FlipResourceBit(index) => {
+                let mut walk = Walk::default();
+                Self::resource_bits(&mut node, false, &mut walk);
+                walk.count > 0 && Self::resource_bits(
+                    &mut node, false, &mut Walk::target(index % walk.count)
+                )
+            }
+            Mutation::TruncateSignature(len) => {
+                Self::truncate_signature(&mut node, len)
+            }
+            Mutation::ReorderSet(index) => {
+                let mut walk = Walk::default();
+                Self::sets(&mut node, &mut walk);
+                walk.count > 0 && Self::sets(
+                    &mut node, &mut Walk::target(index % walk.count)
+                )
+            }
+        };
+        if done {
+            let mut res = Vec::new();
+            node.encode(&mut res);
+            Some(res.into())
+        }
+        else {
+            None
+        }
+    }
+
+    /// Walks over the resource bits of a node.
+    ///
+    /// Returns whether the target bit of `walk` was found and flipped.
+    fn resource_bits(
+        node: &mut Node, in_resources: bool, walk: &mut Walk
+    ) -> bool {
+        match node.content {
+            Content::Primitive(ref mut content) => {
+                if in_resources && (node.tag == [TAG_BIT_STRING]
+                    || node.tag == [TAG_INTEGER])
+                {
+                    // Skip the unused bits octet of BIT STRINGs.
+                    let start = if node.tag == [TAG_BIT_STRING] {
+                        1
+                    } else {
+                        0
+                    };
+                    let bits = content.len().saturating_sub(start) * 8;
+                    if let Some(index) = walk.hit(bits) {
+                        content[start + index / 8] ^= 0x80 >> (index % 8);
+                        return true
+                    }
+                }
+                else if node.tag == [TAG_OCTET_STRING] {
+                    return content.reencode(|inner| {
+                        Self::resource_bits(inner, in_resources, walk)
+                    })
+                }
+                false
+            }
+            Content::Constructed(ref mut children) => {
+                let in_resources = in_resources || is_resources_ext(children);
+                children.iter_mut().any(|child| {
+                    Self::resource_bits(child, in_resources, walk)
+                })
+            }
+        }
+    }
+
+    /// Truncates the signature of the object.
+    ///
+    /// Returns whether a signature was found.
+    fn truncate_signature(node: &mut Node, len: usize) -> bool {
+        let children = match node.content {
+            Content::Constructed(ref mut children) => children,
+            _ => return false
+        };
+
+        // Certificates and CRLs: SEQUENCE { tbs, algorithm, BIT STRING }.
+        if let Some(last) = children.last_mut() {
+            if last.tag == [TAG_BIT_STRING] {
+                if let Content::Primitive(ref mut content) = last.content {
+                    if content.len() > 1 {
+                        content.truncate(1 + len % (content.len() - 1));
+                        return true
+                    }
+                }
+                return false
+            }
+        }
+
+        // Signed objects: ContentInfo { OID, [0] SignedData }. The
+        // signer infos are the last element of SignedData and the
+        // signature is the last OCTET STRING of a signer info.
+        let signer_info = children.get_mut(1)
+            .and_then(Node::children_mut)
+            .and_then(|children| children.first_mut())
+            .and_then(Node::children_mut)
+            .and_then(|children| children.last_mut())
+            .and_then(Node::children_mut)
+            .and_then(|children| children.first_mut())
+            .and_then(Node::children_mut);
+        let signature = match signer_info {
+            Some(children) => {
+                children.iter_mut().rev().find(|child| {
+                    child.tag == [TAG_OCTET_STRING]
+                })
+            }
+            None => None
+        };
+        if let Some(signature) = signature {
+            if let Content::Primitive(ref mut content) = signature.content {
+                if !content.is_empty() {
+                    content.truncate(len % content.len());
+                    return true
+                }
+            }
+        }
+        false
+    }
+
+    /// Walks over the sets with at least two elements of a node.
+    ///
+    /// Returns whether the target set of `walk` was found and rotated.
+    fn sets(node: &mut Node, walk: &mut Walk) -> bool {
+        let is_set = node.is_set();
+        match node.content {
+            Content::Primitive(ref mut content) => {
+                if node.tag == [TAG_OCTET_STRING] {
+                    content.reencode(|inner| Self::sets(inner, walk))
+                }
+                else {
+                    false
+                }
+            }
+            Content::Constructed(ref mut children) => {
+                if is_set && children.len() > 1 && walk.hit(1).is_some() {
+                    children.rotate_left(1);
+                    return true
+                }
+                children.iter_mut().any(|child| Self::sets(child, walk))
+            }
+        }
+    }
+}
+
+/// Returns whether the elements are those of a resources extension.
+fn is_resources_ext(children: &[Node]) -> bool {
+    match children.first() {
+        Some(node) if node.tag == [TAG_OID] => {
+            match node.content {
+                Content::Primitive(ref oid) => {
+                    oid.as_slice() == OID_IP_RESOURCES
+                        || oid.as_slice() == OID_AS_RESOURCES
+                }
+                _ => false
+            }
+        }
+        _ => false
+    }
+}
+
+
+//------------ Walk ----------------------------------------------------------
+
+/// The state of a walk over the targets of a mutation.
+///
+/// A walk is done twice: first without a target for counting the targets
+/// and then with the selected target for applying the mutation.
+#[derive(Clone, Copy, Debug, Default)]
+struct Walk {
+    /// The index of the target if we are applying the mutation.
+    target: Option<usize>,
+
+    /// The number of targets seen so far.
+    count: usize,
+}
+
+impl Walk {
+    /// Creates a walk for applying the mutation to the given target.
+    fn target(index: usize) -> Self {
+        Walk { target: Some(index), count: 0 }
+    }
+
+    /// Processes a candidate with `len` targets.
+    ///
+    /// Returns the index of the target within the candidate if the
+    /// selected target is part of it.
+    fn hit(&mut self, len: usize) -> Option<usize> {
+        let res = match self.target {
+            Some(target) if target >= self.count
+                && target - self.count < len => Some(target - self.count),
+            _ => None
+        };
+        self.count += len;
+        res
+    }
+}
+
+
+//------------ Node ----------------------------------------------------------
+
+/// A generic DER encoded value.
+#[derive(Clone, Debug)]
+struct Node {
+    /// The encoded tag.
+    tag: Vec<u8>,
+
+    /// The content of the value.
+    content: Content,
+}
+
+/// The content of a DER encoded value.
+#[derive(Clone, Debug)]
+enum Content {
+    /// The content octets of a primitive value.
+    Primitive(Vec<u8>),
+
+    /// The elements of a constructed value.
+    Constructed(Vec<Node>),
+}
+
+impl Node {
+    /// Decodes a single value that must cover all of `data`.
+    ///
+    /// The value may be BER encoded. It will always be encoded as DER
+    /// again.
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut data = data;
+        let res = Self::take_from(&mut data, 0)?;
+        if data.is_empty() { Some(res) } else { None }
+    }
+
+    /// Takes a value from the beginning of `data`.
+    fn take_from(data: &mut &[u8], depth: usize) -> Option<Self> {
+        if depth > MAX_DEPTH {
+            return None
+        }
+
+        // Tag: the first octet and, for the long form, all following
+        // octets with the top bit set plus one.
+        let mut tag_len = 1;
+        if data.first()? & 0x1f == 0x1f {
+            while data.get(tag_len)? & 0x80 != 0 {
+                tag_len += 1;
+            }
+            tag_len += 1;
+        }
+        let tag = data.get(..tag_len)?.to_vec();
+        *data = &data[tag_len..];
+
+        // Length. The indefinite form is only allowed for constructed
+        // values.
+        let constructed = tag[0] & 0x20 != 0;
+        let first = *data.first()?;
+        *data = &data[1..];
+        let children = if first == 0x80 {
+            if !constructed {
+                return None
+            }
+            let mut children = Vec::new();
+            while !data.starts_with(b"\0\0") {
+                children.push(Self::take_from(data, depth + 1)?);
+            }
+            *data = &data[2..];
+            Some(children)
+        }
+        else {
+            let len = if first < 0x80 {
+                usize::from(first)
+            }
+            else {
+                let octets = usize::from(first & 0x7f);
+                if octets > 4 {
+                    return None
+                }
+                let mut len = 0usize;
+                for &octet in data.get(..octets)? {
+                    len = len << 8 | usize::from(octet);
+                }
+                *data = &data[octets..];
+                len
+            };
+            let mut content = data.get(..len)?;
+            *data = &data[len..];
+            if constructed {
+                let mut children = Vec::new();
+                while !content.is_empty() {
+                    children.push(Self::take_from(&mut content, depth + 1)?);
+                }
+                Some(children)
+            }
+            else {
+                return Some(Node {
+                    tag, content: Content::Primitive(content.to_vec())
+                })
+            }
+        };
+        let children = children?;
+
+        // Constructed OCTET STRINGs are turned into primitive ones so we
+        // can look into their content.
+        if tag == [TAG_OCTET_STRING | 0x20] {
+            let mut content = Vec::new();
+            for child in children {
+                match child.content {
+                    Content::Primitive(ref data)
+                        if child.tag == [TAG_OCTET_STRING] =>
+                    {
+                        content.extend_from_slice(data)
+                    }
+                    _ => return None
+                }
+            }
+            return Some(Node {
+                tag: vec![TAG_OCTET_STRING],
+                content: Content::Primitive(content)
+            })
+        }
+        Some(Node { tag, content: Content::Constructed(children) })
+    }
+
+    /// Appends the encoded value to `target`.
+    fn encode(&self, target: &mut Vec<u8>) {
+        let mut content = Vec::new();
+        let content = match self.content {
+            Content::Primitive(ref data) => data,
+            Content::Constructed(ref children) => {
+                for child in children {
+                    child.encode(&mut content)
+                }
+                &content
+            }
+        };
+        target.extend_from_slice(&self.tag);
+        let len = content.len();
+        if len < 0x80 {
+            target.push(len as u8)
+        }
+        else {
+            let octets = mem::size_of::<usize>()
+                - (len.leading_zeros() as usize / 8);
+            target.push(0x80 | octets as u8);
+            for i in (0..octets).rev() {
+                target.push((len >> (i * 8)) as u8)
+            }
+        }
+        target.extend_from_slice(content);
+    }
+
+    /// Returns the elements if the value is constructed.
+    fn children_mut(&mut self) -> Option<&mut Vec<Node>> {
+        match self.content {
+            Content::Constructed(ref mut children) => Some(children),
+            _ => None
+        }
+    }
+
+    /// Returns whether the value is a SET or the signed attributes.
+    ///
+    /// Signed attributes are an implicitly tagged SET OF Attribute, which
+    /// we recognize by all elements being a SEQUENCE of an OID and a SET.
+    fn is_set(&self) -> bool {
+        if self.tag == [TAG_SET] {
+            return true
+        }
+        if self.tag != [TAG_CTX_CONS_0] {
+            return false
+        }
+        match self.content {
+            Content::Constructed(ref children) => {
+                !children.is_empty() && children.iter().all(|child| {
+                    if child.tag != [TAG_SEQUENCE] {
+                        return false
+                    }
+                    match child.content {
+                        Content::Constructed(ref attr) => {
+                            attr.len() == 2 && attr[0].tag == [TAG_OID]
+                                && attr[1].tag == [TAG_SET]
+                        }
+                        _ => false
+                    }
+                })
+            }
+            _ => false
+        }
+    }
+}
+
+
+//------------ Reencode ------------------------------------------------------
+
+/// Changing the content of a primitive value as an encoded value.
+trait Reencode {
+    /// Applies `op` to the content decoded as a value.
+    ///
+    /// If the content is a value and `op` returns `true`, the content is
+    /// replaced with the encoding of the changed value. Returns the result
+    /// of `op` or `false` if the content isn’t a value.
+    fn reencode<F: FnOnce(&mut Node) -> bool>(&mut self, op: F) -> bool;
+}
+
+impl Reencode for Vec<u8> {
+    fn reencode<F: FnOnce(&mut Node) -> bool>(&mut self, op: F) -> bool {
+        let mut inner = match Node::decode(self) {
+            Some(inner) => inner,
+            None => return false
+        };
+        if !op(&mut inner) {
+            return false
+        }
+        self.clear();
+        inner.encode(self);
+        true
+    }
+}
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The maximum nesting of values we accept.
+const MAX_DEPTH: usize = 64;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_BIT_STRING: u8 = 0x03;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_CTX_CONS_0: u8 = 0xa0;
+
+/// The content of the OID of the IP resources extension.
+const OID_IP_RESOURCES: &[u8] = &[43, 6, 1, 5, 5, 7, 1, 7];
+
+/// The content of the OID of the AS resources extension.
+const OID_AS_RESOURCES: &[u8] = &[43, 6, 1, 5, 5, 7, 1, 8];
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use crate::cert::Cert;
+    use crate::crl::Crl;
+    use crate::roa::Roa;
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for (name, data) in seeds() {
+            let node = Node::decode(data).unwrap();
+            let mut encoded = Vec::new();
+            node.encode(&mut encoded);
+
+            // The signed objects are BER encoded and become DER.
+            if name.ends_with(".cer") || name.ends_with(".crl") {
+                assert_eq!(encoded.as_slice(), *data, "{}", name);
+            }
+            else {
+                assert!(encoded.len() < data.len());
+                let mut again = Vec::new();
+                Node::decode(&encoded).unwrap().encode(&mut again);
+                assert_eq!(again, encoded, "{}", name);
+            }
+        }
+        let roa = include_bytes!("../test-data/example-ripe.roa").as_ref();
+        let mut encoded = Vec::new();
+        Node::decode(roa).unwrap().encode(&mut encoded);
+        assert!(Roa::decode(Bytes::from(encoded), true).is_ok());
+    }
+
+    #[test]
+    fn flip_resource_bit() {
+        let data = include_bytes!("../test-data/ca1.cer").as_ref();
+        let orig = Cert::decode(data).unwrap();
+        let mutated = Mutation::FlipResourceBit(3).apply(data).unwrap();
+        assert_eq!(mutated.len(), data.len());
+        assert_ne!(mutated.as_ref(), data);
+        let cert = Cert::decode(mutated).unwrap();
+        assert_ne!(
+            format!(
+                "{:?}{:?}{:?}", cert.v4_resources(), cert.v6_resources(),
+                cert.as_resources()
+            ),
+            format!(
+                "{:?}{:?}{:?}", orig.v4_resources(), orig.v6_resources(),
+                orig.as_resources()
+            ),
+        );
+
+        let data = include_bytes!("../test-data/example-ripe.roa").as_ref();
+        let mutated = Mutation::FlipResourceBit(1_000_003).apply(
+            data
+        ).unwrap();
+        assert_ne!(mutated.as_ref(), data);
+        assert!(Roa::decode(mutated, true).is_ok());
+    }
+
+    #[test]
+    fn truncate_signature() {
+        let data = include_bytes!("../test-data/ca1.crl").as_ref();
+        let mutated = Mutation::TruncateSignature(10).apply(data).unwrap();
+        assert!(mutated.len() < data.len());
+        let crl = Crl::decode(mutated).unwrap();
+        assert_eq!(crl.signed_data().signature().value().len(), 10);
+
+        let data = include_bytes!("../test-data/example-ripe.roa").as_ref();
+        let mutated = Mutation::TruncateSignature(0).apply(data).unwrap();
+        assert!(mutated.len() < data.len());
+        assert!(Roa::decode(mutated, true).is_ok());
+    }
+
+    #[test]
+    fn reorder_set() {
+        let data = include_bytes!("../test-data/example-ripe.roa").as_ref();
+        let mutated = Mutation::ReorderSet(0).apply(data).unwrap();
+        let mut der = Vec::new();
+        Node::decode(data).unwrap().encode(&mut der);
+        assert_eq!(mutated.len(), der.len());
+        assert_ne!(mutated.as_ref(), der.as_slice());
+        assert!(Mutation::ReorderSet(1).apply(b"\x30\x00").is_none());
+        assert!(Mutation::ReorderSet(1).apply(b"\x30").is_none());
+    }
+
+    #[test]
+    fn from_u64() {
+        assert_eq!(Mutation::from_u64(7), Mutation::TruncateSignature(2));
+        for value in 0..30 {
+            for (_, data) in seeds() {
+                let _ = Mutation::from_u64(value).apply(data);
+            }
+        }
+    }
+}
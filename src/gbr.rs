@@ -0,0 +1,774 @@
+//! Ghostbusters records.
+//!
+//! Ghostbusters records allow a CA to publish contact information for the
+//! people responsible for it. They are defined in [RFC 6493].
+//!
+//! A Ghostbusters record is a signed object whose content is a vCard
+//! following a restricted profile: it must be a vCard version 4.0, must
+//! contain exactly one FN property, may contain an ORG property, and must
+//! contain at least one ADR, TEL, or EMAIL property. No other properties are
+//! allowed.
+//!
+//! This module defines the type [`Gbr`] for a decoded record and the type
+//! [`VCard`] for its validated content. New records are created via the
+//! [`GbrBuilder`].
+//!
+//! [RFC 6493]: https://tools.ietf.org/html/rfc6493
+//! [`Gbr`]: struct.Gbr.html
+//! [`VCard`]: struct.VCard.html
+//! [`GbrBuilder`]: struct.GbrBuilder.html
+
+use std::{error, fmt, slice, str};
+use bcder::{decode, encode};
+use bcder::{Captured, Mode, Oid};
+use bcder::encode::Values;
+use bytes::Bytes;
+use crate::oid;
+use crate::cert::{Cert, ResourceCert};
+use crate::crypto::{Signer, SigningError};
+use crate::sigobj::{SignedObject, SignedObjectBuilder};
+use crate::x509::{Time, ValidationError};
+
+
+//------------ Gbr -----------------------------------------------------------
+
+/// A decoded Ghostbusters record.
+///
+/// In order to get to the content of the record, you need to validate it
+/// via the `validate` method.
+#[derive(Clone, Debug)]
+pub struct Gbr {
+    signed: SignedObject,
+    content: VCard,
+}
+
+impl Gbr {
+    /// Decodes a Ghostbusters record from a source.
+    ///
+    /// Decoding fails if the content of the object is not a vCard that
+    /// follows the profile of RFC 6493.
+    pub fn decode<S: decode::Source>(
+        source: S,
+        strict: bool
+    ) -> Result<Self, S::Err> {
+        let signed = SignedObject::decode(source, strict)?;
+        if signed.content_type().ne(&oid::CT_RPKI_GHOSTBUSTERS) {
+            return Err(decode::Malformed.into())
+        }
+        let content = VCard::from_bytes(signed.content().to_bytes())
+            .map_err(|_| decode::Malformed)?;
+        Ok(Gbr { signed, content })
+    }
+
+    /// Validates the record.
+    ///
+    /// You need to pass in the certificate of the issuing CA. If validation
+    /// succeeds, the result will be the EE certificate of the record and
+    /// the vCard.
+    pub fn validate(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+    ) -> Result<(ResourceCert, VCard), ValidationError> {
+        self.validate_at(cert, strict, Time::now())
+    }
+
+    /// Validates the record at the given time.
+    pub fn validate_at(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+        now: Time
+    ) -> Result<(ResourceCert, VCard), ValidationError> {
+        let cert = self.signed.validate_at(cert, strict, now)?;
+        Ok((cert, self.content))
+    }
+
+    /// Returns a value encoder for a reference to the record.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        self.signed.encode_ref()
+    }
+
+    /// Returns a DER encoded Captured for this.
+    pub fn to_captured(&self) -> Captured {
+        self.encode_ref().to_captured(Mode::Der)
+    }
+
+    /// Returns a reference to the EE certificate of this record.
+    pub fn cert(&self) -> &Cert {
+        self.signed.cert()
+    }
+
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signed.signing_time()
+    }
+
+    /// Returns the binary signing time attribute if present.
+    ///
+    /// The value is the number of seconds since the Unix epoch as defined
+    /// in RFC 6019.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.signed.binary_signing_time()
+    }
+
+    /// Returns a reference to the vCard of this record.
+    pub fn content(&self) -> &VCard {
+        &self.content
+    }
+}
+
+
+//------------ VCard ---------------------------------------------------------
+
+/// The content of a Ghostbusters record.
+///
+/// Values of this type always follow the vCard profile of RFC 6493. Text
+/// values are stored unescaped.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VCard {
+    /// The formatted name from the FN property.
+    full_name: String,
+
+    /// The organization from the ORG property.
+    org: Option<String>,
+
+    /// The postal addresses from the ADR properties.
+    addresses: Vec<Address>,
+
+    /// The telephone numbers from the TEL properties.
+    tels: Vec<String>,
+
+    /// The email addresses from the EMAIL properties.
+    emails: Vec<String>,
+}
+
+impl VCard {
+    /// Parses a vCard and checks that it follows the profile.
+    ///
+    /// Lines must be terminated by CRLF as required by RFC 6350, but a
+    /// bare LF is accepted, too. Folded lines are unfolded. Property
+    /// parameters are ignored.
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, VCardError> {
+        let text = str::from_utf8(bytes.as_ref())
+            .map_err(|_| VCardError::Malformed)?;
+        let mut lines = unfold(text).into_iter();
+
+        if !lines.next().map(|line| line.eq_ignore_ascii_case("BEGIN:VCARD"))
+            .unwrap_or(false)
+        {
+            return Err(VCardError::Malformed)
+        }
+
+        let mut version = false;
+        let mut full_name = None;
+        let mut org = None;
+        let mut addresses = Vec::new();
+        let mut tels = Vec::new();
+        let mut emails = Vec::new();
+        let mut end = false;
+        for line in lines {
+            if end {
+                return Err(VCardError::Malformed)
+            }
+            if line.eq_ignore_ascii_case("END:VCARD") {
+                end = true;
+                continue
+            }
+            let colon = line.find(':').ok_or(VCardError::Malformed)?;
+            let (name, value) = (&line[..colon], &line[colon + 1..]);
+            let name = match name.find(';') {
+                Some(pos) => &name[..pos],
+                None => name
+            };
+            match name.to_ascii_uppercase().as_str() {
+                "VERSION" => {
+                    if version || value != "4.0" {
+                        return Err(VCardError::Version)
+                    }
+                    version = true;
+                }
+                "FN" => {
+                    if full_name.is_some() {
+                        return Err(VCardError::FullName)
+                    }
+                    full_name = Some(unescape(value));
+                }
+                "ORG" => {
+                    if org.is_some() {
+                        return Err(VCardError::Property("ORG".into()))
+                    }
+                    org = Some(unescape(value));
+                }
+                "ADR" => addresses.push(Address::parse(value)?),
+                "TEL" => tels.push(unescape(value)),
+                "EMAIL" => emails.push(unescape(value)),
+                _ => return Err(VCardError::Property(name.into()))
+            }
+        }
+        if !end {
+            return Err(VCardError::Malformed)
+        }
+        if !version {
+            return Err(VCardError::Version)
+        }
+        let full_name = full_name.ok_or(VCardError::FullName)?;
+        if addresses.is_empty() && tels.is_empty() && emails.is_empty() {
+            return Err(VCardError::NoContact)
+        }
+        Ok(VCard { full_name, org, addresses, tels, emails })
+    }
+
+    /// Returns the formatted name of the contact.
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    /// Returns the organization if present.
+    pub fn org(&self) -> Option<&str> {
+        self.org.as_ref().map(AsRef::as_ref)
+    }
+
+    /// Returns an iterator over the postal addresses.
+    pub fn addresses(&self) -> slice::Iter<'_, Address> {
+        self.addresses.iter()
+    }
+
+    /// Returns an iterator over the telephone numbers.
+    ///
+    /// The numbers are returned as they appear in the vCard. Usually, they
+    /// will be `tel:` URIs.
+    pub fn tels(&self) -> impl Iterator<Item = &str> {
+        self.tels.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns an iterator over the email addresses.
+    pub fn emails(&self) -> impl Iterator<Item = &str> {
+        self.emails.iter().map(AsRef::as_ref)
+    }
+
+    /// Returns the encoded vCard.
+    pub fn to_bytes(&self) -> Bytes {
+        Bytes::from(self.to_string())
+    }
+
+    /// Creates a signed Ghostbusters record with this vCard.
+    ///
+    /// The EE certificate of the record will inherit all resources as
+    /// required by RFC 6493.
+    pub fn into_gbr<S: Signer>(
+        self,
+        mut sigobj: SignedObjectBuilder,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<Gbr, SigningError<S::Error>> {
+        sigobj.set_v4_resources_inherit();
+        sigobj.set_v6_resources_inherit();
+        sigobj.set_as_resources_inherit();
+        let signed = sigobj.finalize(
+            Oid(oid::CT_RPKI_GHOSTBUSTERS.0.into()),
+            self.to_bytes(),
+            signer,
+            issuer_key,
+        )?;
+        Ok(Gbr { signed, content: self })
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for VCard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_line(f, "BEGIN:VCARD")?;
+        write_line(f, "VERSION:4.0")?;
+        write_line(f, &format!("FN:{}", escape(&self.full_name)))?;
+        if let Some(ref org) = self.org {
+            write_line(f, &format!("ORG:{}", escape(org)))?;
+        }
+        for addr in &self.addresses {
+            write_line(f, &format!("ADR:{}", addr))?;
+        }
+        for tel in &self.tels {
+            write_line(f, &format!("TEL:{}", escape(tel)))?;
+        }
+        for email in &self.emails {
+            write_line(f, &format!("EMAIL:{}", escape(email)))?;
+        }
+        write_line(f, "END:VCARD")
+    }
+}
+
+
+//------------ Address -------------------------------------------------------
+
+/// A postal address from an ADR property.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Address {
+    /// The components of the address.
+    ///
+    /// These are, in order: post office box, extended address, street
+    /// address, locality, region, postal code, and country.
+    components: [String; 7],
+}
+
+impl Address {
+    /// Creates a new address from street, locality, postal code, and country.
+    ///
+    /// The remaining components can be set via `set_component`.
+    pub fn new(
+        street: &str, locality: &str, postal_code: &str, country: &str
+    ) -> Self {
+        let mut res = Address::default();
+        res.components[2] = street.into();
+        res.components[3] = locality.into();
+        res.components[5] = postal_code.into();
+        res.components[6] = country.into();
+        res
+    }
+
+    /// Parses the value of an ADR property.
+    fn parse(value: &str) -> Result<Self, VCardError> {
+        let mut res = Address::default();
+        let mut parts = split_unescaped(value, ';').into_iter();
+        for component in res.components.iter_mut() {
+            match parts.next() {
+                Some(part) => *component = unescape(part),
+                None => return Err(VCardError::Malformed)
+            }
+        }
+        if parts.next().is_some() {
+            return Err(VCardError::Malformed)
+        }
+        Ok(res)
+    }
+
+    /// Returns the post office box.
+    pub fn po_box(&self) -> &str {
+        &self.components[0]
+    }
+
+    /// Returns the extended address, e.g., an apartment or suite number.
+    pub fn extended(&self) -> &str {
+        &self.components[1]
+    }
+
+    /// Returns the street address.
+    pub fn street(&self) -> &str {
+        &self.components[2]
+    }
+
+    /// Returns the locality, e.g., the city.
+    pub fn locality(&self) -> &str {
+        &self.components[3]
+    }
+
+    /// Returns the region, e.g., the state or province.
+    pub fn region(&self) -> &str {
+        &self.components[4]
+    }
+
+    /// Returns the postal code.
+    pub fn postal_code(&self) -> &str {
+        &self.components[5]
+    }
+
+    /// Returns the country name.
+    pub fn country(&self) -> &str {
+        &self.components[6]
+    }
+
+    /// Sets the post office box.
+    pub fn set_po_box(&mut self, value: &str) {
+        self.components[0] = value.into()
+    }
+
+    /// Sets the extended address.
+    pub fn set_extended(&mut self, value: &str) {
+        self.components[1] = value.into()
+    }
+
+    /// Sets the region.
+    pub fn set_region(&mut self, value: &str) {
+        self.components[4] = value.into()
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        for component in &self.components {
+            if first {
+                first = false;
+            }
+            else {
+                f.write_str(";")?;
+            }
+            f.write_str(&escape(component))?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ GbrBuilder ----------------------------------------------------
+
+/// A builder for Ghostbusters records.
+#[derive(Clone, Debug)]
+pub struct GbrBuilder {
+    /// The formatted name of the contact.
+    full_name: String,
+
+    /// The organization if any.
+    org: Option<String>,
+
+    /// The postal addresses.
+    addresses: Vec<Address>,
+
+    /// The telephone numbers.
+    tels: Vec<String>,
+
+    /// The email addresses.
+    emails: Vec<String>,
+}
+
+impl GbrBuilder {
+    /// Creates a new builder for a contact with the given formatted name.
+    pub fn new(full_name: &str) -> Self {
+        GbrBuilder {
+            full_name: full_name.into(),
+            org: None,
+            addresses: Vec::new(),
+            tels: Vec::new(),
+            emails: Vec::new(),
+        }
+    }
+
+    /// Sets the organization.
+    pub fn set_org(&mut self, org: Option<&str>) {
+        self.org = org.map(Into::into)
+    }
+
+    /// Adds a postal address.
+    pub fn add_address(&mut self, address: Address) {
+        self.addresses.push(address)
+    }
+
+    /// Adds a telephone number.
+    ///
+    /// RFC 6350 recommends to provide the number as a `tel:` URI.
+    pub fn add_tel(&mut self, tel: &str) {
+        self.tels.push(tel.into())
+    }
+
+    /// Adds an email address.
+    pub fn add_email(&mut self, email: &str) {
+        self.emails.push(email.into())
+    }
+
+    /// Creates the vCard for the current state of the builder.
+    ///
+    /// Fails if no address, telephone number, or email address has been
+    /// added.
+    pub fn to_vcard(&self) -> Result<VCard, VCardError> {
+        if self.addresses.is_empty() && self.tels.is_empty()
+            && self.emails.is_empty()
+        {
+            return Err(VCardError::NoContact)
+        }
+        Ok(VCard {
+            full_name: self.full_name.clone(),
+            org: self.org.clone(),
+            addresses: self.addresses.clone(),
+            tels: self.tels.clone(),
+            emails: self.emails.clone(),
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Splits the text into unfolded lines.
+fn unfold(text: &str) -> Vec<String> {
+    let mut res: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.trim_end_matches('\r');
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = res.last_mut() {
+                last.push_str(&line[1..]);
+                continue
+            }
+        }
+        if !line.is_empty() {
+            res.push(line.into())
+        }
+    }
+    res
+}
+
+/// Writes a line folding it after 75 octets.
+fn write_line(f: &mut fmt::Formatter, mut line: &str) -> fmt::Result {
+    let mut limit = 75;
+    while line.len() > limit {
+        let mut pos = limit;
+        while !line.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        f.write_str(&line[..pos])?;
+        f.write_str("\r\n ")?;
+        line = &line[pos..];
+        // The continuation line starts with a space.
+        limit = 74;
+    }
+    f.write_str(line)?;
+    f.write_str("\r\n")
+}
+
+/// Splits a value at all separators that aren’t escaped.
+fn split_unescaped(value: &str, sep: char) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+    for (pos, ch) in value.char_indices() {
+        if escaped {
+            escaped = false;
+        }
+        else if ch == '\\' {
+            escaped = true;
+        }
+        else if ch == sep {
+            res.push(&value[start..pos]);
+            start = pos + 1;
+        }
+    }
+    res.push(&value[start..]);
+    res
+}
+
+/// Removes the escaping from a text value.
+fn unescape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            res.push(ch);
+            continue
+        }
+        match chars.next() {
+            Some('n') | Some('N') => res.push('\n'),
+            Some(ch) => res.push(ch),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+/// Escapes a text value.
+fn escape(value: &str) -> String {
+    let mut res = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '\\' => res.push_str("\\\\"),
+            ',' => res.push_str("\\,"),
+            ';' => res.push_str("\\;"),
+            '\n' => res.push_str("\\n"),
+            _ => res.push(ch)
+        }
+    }
+    res
+}
+
+
+//------------ VCardError ----------------------------------------------------
+
+/// A vCard does not follow the profile of RFC 6493.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum VCardError {
+    /// The vCard is not correctly encoded.
+    Malformed,
+
+    /// The VERSION property is missing, duplicated, or not 4.0.
+    Version,
+
+    /// The FN property is missing or duplicated.
+    FullName,
+
+    /// There is no ADR, TEL, or EMAIL property.
+    NoContact,
+
+    /// The given property is not allowed or duplicated.
+    Property(String),
+}
+
+impl fmt::Display for VCardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VCardError::Malformed => f.write_str("malformed vCard"),
+            VCardError::Version => f.write_str("missing or invalid version"),
+            VCardError::FullName => f.write_str("missing or duplicate FN"),
+            VCardError::NoContact => {
+                f.write_str("no ADR, TEL, or EMAIL property")
+            }
+            VCardError::Property(ref name) => {
+                write!(f, "property {} not allowed", name)
+            }
+        }
+    }
+}
+
+impl error::Error for VCardError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The example from RFC 6493, section 7.
+    const EXAMPLE: &[u8] = b"BEGIN:VCARD\r\n\
+        VERSION:4.0\r\n\
+        FN:Human's Name\r\n\
+        ORG:Organizational Entity\r\n\
+        ADR;TYPE=WORK:;;42 Twisty Passage;Deep Cavern;WA;98666;U.S.A.\r\n\
+        TEL;TYPE=VOICE,TEXT,WORK;VALUE=uri:tel:+1-666-555-1212\r\n\
+        TEL;TYPE=FAX,WORK;VALUE=uri:tel:+1-666-555-1213\r\n\
+        EMAIL;TYPE=WORK:human@example.com\r\n\
+        END:VCARD\r\n";
+
+    #[test]
+    fn parse_example() {
+        let vcard = VCard::from_bytes(Bytes::from_static(EXAMPLE)).unwrap();
+        assert_eq!(vcard.full_name(), "Human's Name");
+        assert_eq!(vcard.org(), Some("Organizational Entity"));
+        let addr = vcard.addresses().next().unwrap();
+        assert_eq!(addr.street(), "42 Twisty Passage");
+        assert_eq!(addr.locality(), "Deep Cavern");
+        assert_eq!(addr.region(), "WA");
+        assert_eq!(addr.postal_code(), "98666");
+        assert_eq!(addr.country(), "U.S.A.");
+        assert_eq!(
+            vcard.tels().collect::<Vec<_>>(),
+            ["tel:+1-666-555-1212", "tel:+1-666-555-1213"]
+        );
+        assert_eq!(vcard.emails().collect::<Vec<_>>(), ["human@example.com"]);
+
+        // Parameters are dropped but everything else survives.
+        let again = VCard::from_bytes(vcard.to_bytes()).unwrap();
+        assert_eq!(vcard, again);
+    }
+
+    #[test]
+    fn profile() {
+        fn check(text: &str) -> Result<VCard, VCardError> {
+            VCard::from_bytes(Bytes::from(text.to_string()))
+        }
+
+        assert!(check(
+            "BEGIN:VCARD\nVERSION:4.0\nFN:Foo\n  Bar\nEMAIL:a@b\nEND:VCARD\n"
+        ).is_ok());
+        assert_eq!(
+            check("BEGIN:VCARD\nVERSION:3.0\nFN:X\nEMAIL:a@b\nEND:VCARD\n"),
+            Err(VCardError::Version)
+        );
+        assert_eq!(
+            check("BEGIN:VCARD\nVERSION:4.0\nEMAIL:a@b\nEND:VCARD\n"),
+            Err(VCardError::FullName)
+        );
+        assert_eq!(
+            check("BEGIN:VCARD\nVERSION:4.0\nFN:X\nEND:VCARD\n"),
+            Err(VCardError::NoContact)
+        );
+        assert_eq!(
+            check(
+                "BEGIN:VCARD\nVERSION:4.0\nFN:X\nEMAIL:a@b\n\
+                 NOTE:hi\nEND:VCARD\n"
+            ),
+            Err(VCardError::Property("NOTE".into()))
+        );
+        assert_eq!(
+            check("BEGIN:VCARD\nVERSION:4.0\nFN:X\nEMAIL:a@b\n"),
+            Err(VCardError::Malformed)
+        );
+    }
+
+    #[test]
+    fn build() {
+        let mut builder = GbrBuilder::new("Operator; On Call");
+        assert_eq!(builder.to_vcard(), Err(VCardError::NoContact));
+        builder.add_email("noc@example.com");
+        let mut addr = Address::new(
+            "1 Long Road", "Somewhere", "12345", "Nowhere"
+        );
+        addr.set_extended("Room 3, Floor 2");
+        builder.add_address(addr.clone());
+        let vcard = builder.to_vcard().unwrap();
+        let again = VCard::from_bytes(vcard.to_bytes()).unwrap();
+        assert_eq!(again.full_name(), "Operator; On Call");
+        assert_eq!(again.addresses().next(), Some(&addr));
+
+        // Long lines are folded.
+        let mut builder = GbrBuilder::new(&"x".repeat(200));
+        builder.add_tel("tel:+1-555-1234");
+        let vcard = builder.to_vcard().unwrap();
+        let bytes = vcard.to_bytes();
+        assert!(bytes.split(|&ch| ch == b'\n').all(|line| line.len() <= 76));
+        assert_eq!(VCard::from_bytes(bytes).unwrap(), vcard);
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use crate::cert::{KeyUsage, Overclaim, TbsCert};
+    use crate::crypto::{PublicKeyFormat, Signer};
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::resources::{AsId, Prefix};
+    use crate::uri;
+    use crate::tal::TalInfo;
+    use crate::x509::Validity;
+    use super::*;
+
+    #[test]
+    fn encode_gbr() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+
+        let mut cert = TbsCert::new(
+            12u64.into(), pubkey.to_subject_name(),
+            Validity::from_secs(86400), None, pubkey, KeyUsage::Ca,
+            Overclaim::Trim
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(uri.clone()));
+        cert.set_rpki_manifest(Some(uri.clone()));
+        cert.build_v4_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_v6_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_as_resource_blocks(|b| b.push((AsId::MIN, AsId::MAX)));
+        let cert = cert.into_cert(&signer, &key).unwrap();
+
+        let mut builder = GbrBuilder::new("Operator");
+        builder.add_email("noc@example.com");
+        let gbr = builder.to_vcard().unwrap().into_gbr(
+            SignedObjectBuilder::new(
+                12u64.into(), Validity::from_secs(86400), uri.clone(),
+                uri.clone(), uri.clone()
+            ),
+            &signer, &key
+        ).unwrap();
+        let gbr = gbr.to_captured();
+
+        let gbr = Gbr::decode(gbr.as_slice(), true).unwrap();
+        let cert = cert.validate_ta(
+            TalInfo::from_name("foo".into()).into_arc(), true
+        ).unwrap();
+        let (_, vcard) = gbr.validate(&cert, true).unwrap();
+        assert_eq!(vcard.full_name(), "Operator");
+    }
+}
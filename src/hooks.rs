@@ -0,0 +1,333 @@
+//! Policy hooks during validation.
+//!
+//! The validation rules of the RPKI leave little room for choice. However,
+//! research experiments and operators with specific requirements may want
+//! to deviate from them at certain points or at least learn about the
+//! decisions made. This module provides the [`ValidationHooks`] trait with
+//! callbacks for such decision points. Each callback returns a [`Verdict`]
+//! that either lets the outcome stand, lets it stand with an annotation, or
+//! vetoes it.
+//!
+//! The [`HookedValidation`] type wraps the validation steps provided
+//! elsewhere in this crate and invokes the hooks at the right time. It
+//! collects all annotations so they can be reported later.
+//!
+//! [`ValidationHooks`]: trait.ValidationHooks.html
+//! [`Verdict`]: enum.Verdict.html
+//! [`HookedValidation`]: struct.HookedValidation.html
+
+use std::{error, fmt};
+use crate::cert::{Cert, ResourceCert};
+use crate::manifest::ManifestContent;
+use crate::manifest::check::{
+    CrlCheck, FailedFetch, FailureReason, ManifestCheck
+};
+use crate::resources::{AsBlocks, IpBlocks};
+use crate::x509::{Time, ValidationError};
+
+
+//------------ ValidationHooks -----------------------------------------------
+
+/// Callbacks invoked at decision points during validation.
+///
+/// All methods have default implementations that keep the standard
+/// behaviour, so implementations only need to provide the callbacks they
+/// are interested in.
+pub trait ValidationHooks {
+    /// Called when a certificate has passed validation.
+    ///
+    /// For signed objects, `cert` is the EE certificate of the object.
+    /// Returning a veto causes the certificate to be rejected.
+    fn object_accepted(&self, cert: &ResourceCert) -> Verdict {
+        let _ = cert;
+        Verdict::Accept
+    }
+
+    /// Called when the resources of a certificate have been trimmed.
+    ///
+    /// This happens only for certificates using the ‘reconsidered’
+    /// validation algorithm of RFC 8360 that claim resources not held by
+    /// their issuer. Returning a veto causes the certificate to be rejected.
+    fn resources_trimmed(
+        &self, cert: &ResourceCert, trimmed: &TrimmedResources
+    ) -> Verdict {
+        let _ = (cert, trimmed);
+        Verdict::Accept
+    }
+
+    /// Called when a manifest is encountered whose nextUpdate has passed.
+    ///
+    /// RFC 9286 requires such a manifest to be rejected which is what the
+    /// default implementation does. Returning anything but a veto causes
+    /// the manifest to be used regardless.
+    fn stale_manifest(&self, manifest: &ManifestContent) -> Verdict {
+        let _ = manifest;
+        Verdict::Veto("manifest stale".into())
+    }
+}
+
+
+//------------ Verdict -------------------------------------------------------
+
+/// The decision of a hook.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Verdict {
+    /// The outcome stands.
+    Accept,
+
+    /// The outcome stands but the given annotation should be recorded.
+    Annotate(String),
+
+    /// The outcome is vetoed for the given reason.
+    Veto(String),
+}
+
+
+//------------ TrimmedResources ----------------------------------------------
+
+/// The resources removed from a certificate during validation.
+#[derive(Clone, Debug)]
+pub struct TrimmedResources {
+    /// The trimmed IPv4 resources.
+    v4: IpBlocks,
+
+    /// The trimmed IPv6 resources.
+    v6: IpBlocks,
+
+    /// The trimmed AS resources.
+    asn: AsBlocks,
+}
+
+impl TrimmedResources {
+    /// Determines the resources trimmed from a certificate.
+    ///
+    /// Returns `None` if nothing was trimmed.
+    pub(crate) fn from_cert(cert: &ResourceCert) -> Option<Self> {
+        // Inherited resources can’t have been trimmed.
+        let res = TrimmedResources {
+            v4: IpBlocks::from_resources(cert.as_cert().v4_resources())
+                .map(|claimed| claimed.difference(cert.v4_resources()))
+                .unwrap_or_else(|_| IpBlocks::empty()),
+            v6: IpBlocks::from_resources(cert.as_cert().v6_resources())
+                .map(|claimed| claimed.difference(cert.v6_resources()))
+                .unwrap_or_else(|_| IpBlocks::empty()),
+            asn: AsBlocks::from_resources(cert.as_cert().as_resources())
+                .map(|claimed| claimed.difference(cert.as_resources()))
+                .unwrap_or_else(|_| AsBlocks::empty()),
+        };
+        if res.v4.is_empty() && res.v6.is_empty() && res.asn.is_empty() {
+            None
+        }
+        else {
+            Some(res)
+        }
+    }
+
+    /// Returns the trimmed IPv4 resources.
+    pub fn v4(&self) -> &IpBlocks {
+        &self.v4
+    }
+
+    /// Returns the trimmed IPv6 resources.
+    pub fn v6(&self) -> &IpBlocks {
+        &self.v6
+    }
+
+    /// Returns the trimmed AS resources.
+    pub fn asn(&self) -> &AsBlocks {
+        &self.asn
+    }
+}
+
+
+//------------ HookedValidation ----------------------------------------------
+
+/// Validation steps with hooks.
+///
+/// A value of this type is used for the validation of a set of objects,
+/// for instance a publication point or an entire validation run. It
+/// collects the annotations produced by the hooks.
+#[derive(Debug)]
+pub struct HookedValidation<'a, H: ?Sized> {
+    /// The hooks to invoke.
+    hooks: &'a H,
+
+    /// The annotations collected so far.
+    annotations: Vec<String>,
+}
+
+impl<'a, H: ValidationHooks + ?Sized> HookedValidation<'a, H> {
+    /// Creates a new value using the given hooks.
+    pub fn new(hooks: &'a H) -> Self {
+        HookedValidation { hooks, annotations: Vec::new() }
+    }
+
+    /// Returns the annotations collected so far.
+    pub fn annotations(&self) -> &[String] {
+        &self.annotations
+    }
+
+    /// Converts the value into the collected annotations.
+    pub fn into_annotations(self) -> Vec<String> {
+        self.annotations
+    }
+
+    /// Validates a CA certificate at the given time.
+    ///
+    /// See `Cert::validate_ca_at` for details.
+    pub fn validate_ca_at(
+        &mut self,
+        cert: Cert,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, HookError> {
+        let cert = cert.validate_ca_at(issuer, strict, now)?;
+        self.accept_cert(cert)
+    }
+
+    /// Validates an EE certificate at the given time.
+    ///
+    /// See `Cert::validate_ee_at` for details.
+    pub fn validate_ee_at(
+        &mut self,
+        cert: Cert,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, HookError> {
+        let cert = cert.validate_ee_at(issuer, strict, now)?;
+        self.accept_cert(cert)
+    }
+
+    /// Checks a valid manifest.
+    ///
+    /// This is the hooked version of `ManifestCheck::valid`. If the
+    /// manifest is stale, the `stale_manifest` hook decides whether it is
+    /// used regardless.
+    pub fn check_manifest(
+        &mut self,
+        check: ManifestCheck,
+        manifest: &ManifestContent,
+    ) -> Result<CrlCheck, FailedFetch> {
+        let mut allow_stale = false;
+        if check.now() > manifest.next_update() {
+            match self.hooks.stale_manifest(manifest) {
+                Verdict::Accept => { }
+                Verdict::Annotate(text) => self.annotations.push(text),
+                Verdict::Veto(_) => {
+                    return Err(FailedFetch::new(FailureReason::Stale))
+                }
+            }
+            allow_stale = true;
+        }
+        check.check_manifest(manifest, allow_stale)
+    }
+
+    /// Runs the hooks for a validated certificate.
+    fn accept_cert(
+        &mut self, cert: ResourceCert
+    ) -> Result<ResourceCert, HookError> {
+        if let Some(trimmed) = TrimmedResources::from_cert(&cert) {
+            let verdict = self.hooks.resources_trimmed(&cert, &trimmed);
+            self.apply(verdict)?;
+        }
+        let verdict = self.hooks.object_accepted(&cert);
+        self.apply(verdict)?;
+        Ok(cert)
+    }
+
+    /// Applies a verdict.
+    fn apply(&mut self, verdict: Verdict) -> Result<(), HookError> {
+        match verdict {
+            Verdict::Accept => Ok(()),
+            Verdict::Annotate(text) => {
+                self.annotations.push(text);
+                Ok(())
+            }
+            Verdict::Veto(reason) => Err(HookError::Vetoed(reason))
+        }
+    }
+}
+
+
+//------------ HookError -----------------------------------------------------
+
+/// Validation with hooks has failed.
+#[derive(Clone, Debug)]
+pub enum HookError {
+    /// Regular validation has failed.
+    Invalid(ValidationError),
+
+    /// A hook vetoed an otherwise valid outcome.
+    Vetoed(String),
+}
+
+impl From<ValidationError> for HookError {
+    fn from(err: ValidationError) -> Self {
+        HookError::Invalid(err)
+    }
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            HookError::Invalid(ref err) => err.fmt(f),
+            HookError::Vetoed(ref reason) => {
+                write!(f, "vetoed by policy: {}", reason)
+            }
+        }
+    }
+}
+
+impl error::Error for HookError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use crate::manifest::ManifestBuilder;
+    use crate::manifest::check::ManifestPolicy;
+    use super::*;
+
+    struct Lenient;
+
+    impl ValidationHooks for Lenient {
+        fn stale_manifest(&self, _: &ManifestContent) -> Verdict {
+            Verdict::Annotate("stale manifest used".into())
+        }
+    }
+
+    struct Strict;
+
+    impl ValidationHooks for Strict { }
+
+    #[test]
+    fn stale_manifest() {
+        let mut builder = ManifestBuilder::new(
+            1u64.into(),
+            Time::utc(2020, 1, 1, 0, 0, 0),
+            Time::utc(2020, 1, 2, 0, 0, 0),
+        );
+        builder.insert(Bytes::from_static(b"ca.crl"), b"crl");
+        let manifest = builder.to_content();
+        let check = |day| ManifestCheck::new(
+            ManifestPolicy::default(), Time::utc(2020, 1, day, 12, 0, 0), None
+        );
+
+        let mut hooked = HookedValidation::new(&Strict);
+        assert!(hooked.check_manifest(check(1), &manifest).is_ok());
+        assert_eq!(
+            hooked.check_manifest(check(3), &manifest).unwrap_err().reason(),
+            FailureReason::Stale
+        );
+        assert!(hooked.annotations().is_empty());
+
+        let mut hooked = HookedValidation::new(&Lenient);
+        let crl = hooked.check_manifest(check(3), &manifest).unwrap();
+        assert_eq!(crl.crl_name().as_ref(), b"ca.crl");
+        assert_eq!(hooked.into_annotations(), ["stale manifest used"]);
+    }
+}
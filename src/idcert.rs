@@ -0,0 +1,581 @@
+//! BPKI identity certificates.
+//!
+//! The parties of the provisioning protocol of [RFC 6492] and the
+//! publication protocol of [RFC 8181] authenticate each other through a
+//! business PKI, or BPKI, that is separate from the RPKI. Each party has a
+//! self-signed identity certificate which is exchanged out of band in the
+//! base64 encoded XML messages defined in [RFC 8183]. Protocol messages
+//! are then signed with short-lived EE certificates issued under this
+//! identity certificate which are included in the message’s CMS.
+//!
+//! Identity certificates follow the generic X.509 profile rather than the
+//! resource certificate profile of RFC 6487 and thus can’t be represented
+//! by [`Cert`]. Instead, this module provides [`IdCert`] for both the
+//! self-signed identity certificates and the EE certificates issued under
+//! them.
+//!
+//! [`Cert`]: ../cert/struct.Cert.html
+//! [`IdCert`]: struct.IdCert.html
+//! [RFC 6492]: https://tools.ietf.org/html/rfc6492
+//! [RFC 8181]: https://tools.ietf.org/html/rfc8181
+//! [RFC 8183]: https://tools.ietf.org/html/rfc8183
+
+use std::{error, fmt, ops};
+use bcder::{decode, encode};
+use bcder::{Captured, Mode, OctetString, Oid, Tag, xerr};
+use bcder::encode::PrimitiveContent;
+use crate::oid;
+use crate::cert::{KeyUsage, TbsCert};
+use crate::crypto::{
+    KeyIdentifier, PublicKey, SignatureAlgorithm, Signer, SigningError
+};
+use crate::x509::{
+    Name, SignedData, Serial, Time, ValidationError, Validity,
+    encode_extension
+};
+
+
+//------------ IdCert --------------------------------------------------------
+
+/// A BPKI identity certificate or an EE certificate issued under it.
+///
+/// Identity certificates are created via [`new_ta`] and checked via
+/// [`validate_ta`]. The EE certificates used for signing protocol messages
+/// are created via [`new_ee`] and checked against the identity certificate
+/// of the sender via [`validate_ee`].
+///
+/// The type dereferences to [`TbsIdCert`] which provides access to the
+/// content of the certificate.
+///
+/// [`new_ta`]: #method.new_ta
+/// [`new_ee`]: #method.new_ee
+/// [`validate_ta`]: #method.validate_ta
+/// [`validate_ee`]: #method.validate_ee
+/// [`TbsIdCert`]: struct.TbsIdCert.html
+#[derive(Clone, Debug)]
+pub struct IdCert {
+    /// The outer structure of the certificate.
+    signed_data: SignedData,
+
+    /// The actual data of the certificate.
+    tbs: TbsIdCert,
+}
+
+/// # Creation
+///
+impl IdCert {
+    /// Creates a new self-signed identity certificate.
+    ///
+    /// The certificate is signed by and for the key `key` held by
+    /// `signer`. Its subject is derived from the key.
+    pub fn new_ta<S: Signer>(
+        validity: Validity,
+        key: &S::KeyId,
+        signer: &S,
+    ) -> Result<Self, SigningError<S::Error>> {
+        let public_key = signer.get_key_info(key)?;
+        let subject = public_key.to_subject_name();
+        let key_id = public_key.key_identifier();
+        TbsIdCert {
+            serial_number: Serial::random(signer)?,
+            signature: SignatureAlgorithm::default(),
+            issuer: subject.clone(),
+            validity,
+            subject,
+            subject_public_key_info: public_key,
+            basic_ca: Some(true),
+            subject_key_identifier: key_id,
+            authority_key_identifier: Some(key_id),
+            key_usage: Some(KeyUsage::Ca),
+        }.into_cert(signer, key)
+    }
+
+    /// Creates a new EE certificate for `public_key`.
+    ///
+    /// The certificate is issued under the identity certificate `issuer`
+    /// and signed with its key `issuer_key` held by `signer`.
+    pub fn new_ee<S: Signer>(
+        public_key: PublicKey,
+        validity: Validity,
+        issuer: &IdCert,
+        issuer_key: &S::KeyId,
+        signer: &S,
+    ) -> Result<Self, SigningError<S::Error>> {
+        TbsIdCert {
+            serial_number: Serial::random(signer)?,
+            signature: SignatureAlgorithm::default(),
+            issuer: issuer.subject.clone(),
+            validity,
+            subject: public_key.to_subject_name(),
+            subject_key_identifier: public_key.key_identifier(),
+            subject_public_key_info: public_key,
+            basic_ca: None,
+            authority_key_identifier: Some(issuer.subject_key_identifier),
+            key_usage: Some(KeyUsage::Ee),
+        }.into_cert(signer, issuer_key)
+    }
+}
+
+/// # Decoding and Encoding
+///
+impl IdCert {
+    /// Decodes a source as an identity certificate.
+    pub fn decode<S: decode::Source>(source: S) -> Result<Self, S::Err> {
+        Mode::Der.decode(source, Self::take_from)
+    }
+
+    /// Takes an encoded certificate from the beginning of a value.
+    pub fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(Self::from_constructed)
+    }
+
+    /// Parses the content of a Certificate sequence.
+    pub fn from_constructed<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        #[allow(clippy::redundant_closure)]
+        let (signed_data, tbs) = SignedData::from_constructed_with(
+            cons, |cons| TbsIdCert::take_from(cons)
+        )?;
+        Ok(IdCert { signed_data, tbs })
+    }
+
+    /// Decodes a certificate from its base64 encoding.
+    ///
+    /// This is the encoding used in the XML messages of RFC 8183. Any
+    /// white space in `data` is ignored.
+    pub fn from_base64(data: &str) -> Result<Self, ParseError> {
+        let data: String = data.chars().filter(|ch| {
+            !ch.is_whitespace()
+        }).collect();
+        let der = base64::decode(&data)?;
+        Ok(Self::decode(der.as_ref())?)
+    }
+
+    /// Returns a value encoder for a reference to the certificate.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        self.signed_data.encode_ref()
+    }
+
+    /// Returns a captured encoding of the certificate.
+    pub fn to_captured(&self) -> Captured {
+        Captured::from_values(Mode::Der, self.encode_ref())
+    }
+
+    /// Returns the base64 encoding of the certificate.
+    ///
+    /// This is the encoding used in the XML messages of RFC 8183.
+    pub fn to_base64(&self) -> String {
+        base64::encode(self.to_captured())
+    }
+}
+
+/// # Validation
+///
+impl IdCert {
+    /// Validates the certificate as a self-signed identity certificate.
+    pub fn validate_ta(&self) -> Result<(), ValidationError> {
+        self.validate_ta_at(Time::now())
+    }
+
+    /// Validates the certificate as an identity certificate at `now`.
+    ///
+    /// The certificate must be a CA certificate with the keyCertSign and
+    /// cRLSign key usages, be issued to itself, and be signed with its
+    /// own key.
+    pub fn validate_ta_at(&self, now: Time) -> Result<(), ValidationError> {
+        if self.basic_ca != Some(true)
+            || self.key_usage != Some(KeyUsage::Ca)
+            || self.issuer != self.subject
+        {
+            return Err(ValidationError)
+        }
+        if let Some(key_id) = self.authority_key_identifier {
+            if key_id != self.subject_key_identifier {
+                return Err(ValidationError)
+            }
+        }
+        self.validity.validate_at(now)?;
+        self.signed_data.verify_signature(&self.subject_public_key_info)
+    }
+
+    /// Validates the certificate as an EE certificate issued by `issuer`.
+    pub fn validate_ee(&self, issuer: &IdCert) -> Result<(), ValidationError> {
+        self.validate_ee_at(issuer, Time::now())
+    }
+
+    /// Validates the certificate as an EE certificate at `now`.
+    ///
+    /// The issuer should have been validated via [`validate_ta_at`]. This
+    /// is how the EE certificate included in the CMS of a protocol message
+    /// is checked against the identity certificate of the sender.
+    ///
+    /// [`validate_ta_at`]: #method.validate_ta_at
+    pub fn validate_ee_at(
+        &self, issuer: &IdCert, now: Time
+    ) -> Result<(), ValidationError> {
+        if self.basic_ca == Some(true)
+            || self.key_usage != Some(KeyUsage::Ee)
+            || self.issuer != issuer.subject
+            || issuer.basic_ca != Some(true)
+        {
+            return Err(ValidationError)
+        }
+        if let Some(key_id) = self.authority_key_identifier {
+            if key_id != issuer.subject_key_identifier {
+                return Err(ValidationError)
+            }
+        }
+        self.validity.validate_at(now)?;
+        self.signed_data.verify_signature(&issuer.subject_public_key_info)
+    }
+}
+
+
+//--- Deref and AsRef
+
+impl ops::Deref for IdCert {
+    type Target = TbsIdCert;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tbs
+    }
+}
+
+impl AsRef<TbsIdCert> for IdCert {
+    fn as_ref(&self) -> &TbsIdCert {
+        &self.tbs
+    }
+}
+
+
+//------------ TbsIdCert -----------------------------------------------------
+
+/// The data of an identity certificate.
+#[derive(Clone, Debug)]
+pub struct TbsIdCert {
+    /// The serial number.
+    serial_number: Serial,
+
+    /// The algorithm used for signing the certificate.
+    signature: SignatureAlgorithm,
+
+    /// The name of the issuer.
+    issuer: Name,
+
+    /// The validity of the certificate.
+    validity: Validity,
+
+    /// The name of the subject of this certificate.
+    subject: Name,
+
+    /// Information about the public key of this certificate.
+    subject_public_key_info: PublicKey,
+
+    /// Basic Constraints.
+    basic_ca: Option<bool>,
+
+    /// Subject Key Identifier.
+    subject_key_identifier: KeyIdentifier,
+
+    /// Authority Key Identifier.
+    authority_key_identifier: Option<KeyIdentifier>,
+
+    /// Key Usage.
+    key_usage: Option<KeyUsage>,
+}
+
+impl TbsIdCert {
+    /// Returns the serial number of the certificate.
+    pub fn serial_number(&self) -> Serial {
+        self.serial_number
+    }
+
+    /// Returns the issuer of the certificate.
+    pub fn issuer(&self) -> &Name {
+        &self.issuer
+    }
+
+    /// Returns the validity of the certificate.
+    pub fn validity(&self) -> Validity {
+        self.validity
+    }
+
+    /// Returns the subject of the certificate.
+    pub fn subject(&self) -> &Name {
+        &self.subject
+    }
+
+    /// Returns the public key of the certificate.
+    pub fn subject_public_key_info(&self) -> &PublicKey {
+        &self.subject_public_key_info
+    }
+
+    /// Returns the cA field of the basic constraints if present.
+    pub fn basic_ca(&self) -> Option<bool> {
+        self.basic_ca
+    }
+
+    /// Returns the subject key identifier.
+    pub fn subject_key_identifier(&self) -> KeyIdentifier {
+        self.subject_key_identifier
+    }
+
+    /// Returns the authority key identifier if present.
+    pub fn authority_key_identifier(&self) -> Option<KeyIdentifier> {
+        self.authority_key_identifier
+    }
+
+    /// Returns the key usage if present.
+    pub fn key_usage(&self) -> Option<KeyUsage> {
+        self.key_usage
+    }
+
+    /// Converts the value into a signed certificate.
+    fn into_cert<S: Signer>(
+        self,
+        signer: &S,
+        key: &S::KeyId,
+    ) -> Result<IdCert, SigningError<S::Error>> {
+        if !signer.supports(self.signature) {
+            return Err(SigningError::IncompatibleKey)
+        }
+        let data = Captured::from_values(Mode::Der, self.encode_ref());
+        let signature = signer.sign(key, self.signature, &data)?;
+        Ok(IdCert {
+            signed_data: SignedData::new(data, signature),
+            tbs: self
+        })
+    }
+
+    /// Takes an encoded TBSCertificate sequence.
+    ///
+    /// Only the extensions relevant for the BPKI are decoded. Other
+    /// extensions are ignored unless they are critical in which case the
+    /// certificate is rejected.
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(|cons| {
+            // version [0] EXPLICIT Version DEFAULT v1 -- must be v3 (2)
+            cons.take_constructed_if(Tag::CTX_0, |c| c.skip_u8_if(2))?;
+
+            let serial_number = Serial::take_from(cons)?;
+            let signature = SignatureAlgorithm::x509_take_from(cons)?;
+            let issuer = Name::take_from(cons)?;
+            let validity = Validity::take_from(cons)?;
+            let subject = Name::take_from(cons)?;
+            let subject_public_key_info = PublicKey::take_from(cons)?;
+
+            let mut basic_ca = None;
+            let mut subject_key_id = None;
+            let mut authority_key_id = None;
+            let mut key_usage = None;
+
+            cons.take_constructed_if(Tag::CTX_3, |c| c.take_sequence(|cons| {
+                while let Some(()) = cons.take_opt_sequence(|cons| {
+                    let id = Oid::take_from(cons)?;
+                    let critical = cons.take_opt_bool()?.unwrap_or(false);
+                    let value = OctetString::take_from(cons)?;
+                    Mode::Der.decode(value.to_source(), |content| {
+                        if id == oid::CE_BASIC_CONSTRAINTS {
+                            TbsCert::take_basic_constraints(
+                                content, &mut basic_ca
+                            )
+                        } else if id == oid::CE_SUBJECT_KEY_IDENTIFIER {
+                            TbsCert::take_subject_key_identifier(
+                                content, &mut subject_key_id
+                            )
+                        } else if id == oid::CE_AUTHORITY_KEY_IDENTIFIER {
+                            TbsCert::take_authority_key_identifier(
+                                content, &mut authority_key_id
+                            )
+                        } else if id == oid::CE_KEY_USAGE {
+                            TbsCert::take_key_usage(
+                                content, &mut key_usage
+                            )
+                        } else if critical {
+                            xerr!(Err(decode::Malformed))
+                        } else {
+                            content.skip_all()
+                        }
+                    })?;
+                    Ok(())
+                })? { }
+                Ok(())
+            }))?;
+
+            Ok(TbsIdCert {
+                serial_number,
+                signature,
+                issuer,
+                validity,
+                subject,
+                subject_public_key_info,
+                basic_ca,
+                subject_key_identifier:
+                    subject_key_id.ok_or(decode::Malformed)?,
+                authority_key_identifier: authority_key_id,
+                key_usage,
+            })
+        })
+    }
+
+    /// Returns a value encoder for a reference to the certificate data.
+    fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        encode::sequence((
+            encode::sequence_as(Tag::CTX_0, 2.encode()), // version
+            self.serial_number.encode(),
+            self.signature.x509_encode(),
+            self.issuer.encode_ref(),
+            self.validity.encode(),
+            self.subject.encode_ref(),
+            self.subject_public_key_info.encode_ref(),
+            encode::sequence_as(Tag::CTX_3, encode::sequence((
+                // Basic Constraints
+                self.basic_ca.map(|ca| {
+                    encode_extension(
+                        &oid::CE_BASIC_CONSTRAINTS, true,
+                        encode::sequence(
+                            if ca { Some(ca.encode()) } else { None }
+                        )
+                    )
+                }),
+
+                // Subject Key Identifier
+                encode_extension(
+                    &oid::CE_SUBJECT_KEY_IDENTIFIER, false,
+                    self.subject_key_identifier.encode_ref(),
+                ),
+
+                // Authority Key Identifier
+                self.authority_key_identifier.as_ref().map(|id| {
+                    encode_extension(
+                        &oid::CE_AUTHORITY_KEY_IDENTIFIER, false,
+                        encode::sequence(id.encode_ref_as(Tag::CTX_0))
+                    )
+                }),
+
+                // Key Usage
+                self.key_usage.map(|key_usage| {
+                    encode_extension(
+                        &oid::CE_KEY_USAGE, true, key_usage.encode()
+                    )
+                }),
+            )))
+        ))
+    }
+}
+
+
+//------------ ParseError ----------------------------------------------------
+
+/// An error happened while decoding a base64 encoded certificate.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError {
+    /// The base64 encoding was invalid.
+    Base64(base64::DecodeError),
+
+    /// The certificate was malformed.
+    Decode(decode::Error),
+}
+
+impl From<base64::DecodeError> for ParseError {
+    fn from(err: base64::DecodeError) -> Self {
+        ParseError::Base64(err)
+    }
+}
+
+impl From<decode::Error> for ParseError {
+    fn from(err: decode::Error) -> Self {
+        ParseError::Decode(err)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::Base64(ref err) => {
+                write!(f, "bad certificate encoding: {}", err)
+            }
+            ParseError::Decode(ref err) => {
+                write!(f, "bad certificate: {}", err)
+            }
+        }
+    }
+}
+
+impl error::Error for ParseError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_base64_errors() {
+        assert_eq!(
+            IdCert::from_base64("!!!!").unwrap_err(),
+            ParseError::Base64(base64::DecodeError::InvalidByte(0, b'!'))
+        );
+        assert_eq!(
+            IdCert::from_base64("MAMC\n AQE=").unwrap_err(),
+            ParseError::Decode(decode::Error::Malformed)
+        );
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use crate::crypto::PublicKeyFormat;
+    use crate::crypto::softsigner::OpenSslSigner;
+    use super::*;
+
+    #[test]
+    fn create_and_validate() {
+        let mut signer = OpenSslSigner::new();
+        let ta_key = signer.create_key(PublicKeyFormat::Rsa).unwrap();
+        let ta = IdCert::new_ta(
+            Validity::from_secs(86400), &ta_key, &signer
+        ).unwrap();
+        ta.validate_ta().unwrap();
+
+        // Round trip through the RFC 8183 encoding with line breaks.
+        let mut encoded = ta.to_base64();
+        encoded.insert(10, '\n');
+        encoded.insert_str(20, "\n  ");
+        let ta = IdCert::from_base64(&encoded).unwrap();
+        ta.validate_ta().unwrap();
+        assert_eq!(ta.basic_ca(), Some(true));
+        assert_eq!(ta.key_usage(), Some(KeyUsage::Ca));
+
+        let ee_key = signer.create_key(PublicKeyFormat::Rsa).unwrap();
+        let ee = IdCert::new_ee(
+            signer.get_key_info(&ee_key).unwrap(),
+            Validity::from_secs(300), &ta, &ta_key, &signer
+        ).unwrap();
+        let ee = IdCert::decode(ee.to_captured().into_bytes()).unwrap();
+        ee.validate_ee(&ta).unwrap();
+        assert!(ee.validate_ta().is_err());
+        assert!(ta.validate_ee(&ta).is_err());
+        assert_eq!(
+            ee.authority_key_identifier(), Some(ta.subject_key_identifier())
+        );
+
+        // An EE certificate doesn’t validate under a different TA.
+        let other_key = signer.create_key(PublicKeyFormat::Rsa).unwrap();
+        let other = IdCert::new_ta(
+            Validity::from_secs(86400), &other_key, &signer
+        ).unwrap();
+        assert!(ee.validate_ee(&other).is_err());
+
+        // Nor after it expired.
+        assert!(ee.validate_ee_at(
+            &ta, Time::now() + chrono::Duration::minutes(10)
+        ).is_err());
+    }
+}
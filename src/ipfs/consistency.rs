@@ -0,0 +1,435 @@
+//! Checking that IPFS and other transports publish the same content.
+//!
+//! A CA publishing via IPFS in addition to RRDP or rsync should publish the
+//! exact same set of objects via all transports. This module allows
+//! checking this. The objects retrieved via each transport are collected
+//! into an [`ObjectSet`] which only keeps the hash of each object. Two such
+//! sets are then compared via [`compare`] which results in a
+//! [`ConsistencyReport`] listing all diverging objects by URI and hash.
+//!
+//! An object set can be created from a [`Dag`] retrieved via IPFS, from an
+//! RRDP snapshot via its implementation of [`ProcessSnapshot`], or from a
+//! local directory as produced by rsync.
+//!
+//! [`ObjectSet`]: struct.ObjectSet.html
+//! [`compare`]: fn.compare.html
+//! [`ConsistencyReport`]: struct.ConsistencyReport.html
+//! [`Dag`]: ../dag/struct.Dag.html
+//! [`ProcessSnapshot`]: ../../rrdp/trait.ProcessSnapshot.html
+
+use std::{error, fmt, fs, io, slice};
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+use crate::{rrdp, uri, xml};
+use crate::cid::Multihash;
+use crate::util::hex;
+use super::dag::{Dag, DagError};
+
+
+//------------ ObjectSet -----------------------------------------------------
+
+/// The objects of a repository as retrieved via one transport.
+///
+/// For each object, only the URI and the SHA-256 hash of its content are
+/// kept.
+#[derive(Clone, Debug, Default)]
+pub struct ObjectSet {
+    /// The hashes of all objects.
+    objects: HashMap<uri::Rsync, Multihash>,
+}
+
+impl ObjectSet {
+    /// Creates a new, empty object set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates an object set from a UnixFS DAG.
+    ///
+    /// The root of the DAG is taken to be the directory of the given rsync
+    /// module, i.e., the path of each file in the DAG is appended to the
+    /// module’s URI.
+    pub fn from_dag(
+        dag: &Dag, module: &uri::RsyncModule
+    ) -> Result<Self, ConsistencyError> {
+        let mut res = Self::new();
+        for (path, content) in dag.files()? {
+            res.insert(module_uri(module, &path)?, &content);
+        }
+        Ok(res)
+    }
+
+    /// Creates an object set from a local directory.
+    ///
+    /// The directory is taken to be a copy of the given rsync module as,
+    /// for instance, created by running rsync. All regular files below the
+    /// directory are included. Symbolic links are not followed.
+    pub fn from_dir<P: AsRef<Path>>(
+        dir: P, module: &uri::RsyncModule
+    ) -> Result<Self, ConsistencyError> {
+        let mut res = Self::new();
+        res.read_dir(dir.as_ref(), "", module)?;
+        Ok(res)
+    }
+
+    /// Adds the content of a directory to the set.
+    fn read_dir(
+        &mut self, dir: &Path, path: &str, module: &uri::RsyncModule
+    ) -> Result<(), ConsistencyError> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => return Err(ConsistencyError::BadPath)
+            };
+            let path = if path.is_empty() {
+                name
+            }
+            else {
+                format!("{}/{}", path, name)
+            };
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                self.read_dir(&entry.path(), &path, module)?;
+            }
+            else if file_type.is_file() {
+                let content = fs::read(entry.path())?;
+                self.insert(module_uri(module, &path)?, &content);
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds an object to the set.
+    ///
+    /// If an object with the same URI is already present, it is replaced.
+    pub fn insert(&mut self, uri: uri::Rsync, content: &[u8]) {
+        self.objects.insert(uri, Multihash::sha256(content));
+    }
+
+    /// Removes an object from the set.
+    ///
+    /// Returns whether the object was present.
+    pub fn remove(&mut self, uri: &uri::Rsync) -> bool {
+        self.objects.remove(uri).is_some()
+    }
+
+    /// Returns the hash of the object with the given URI if present.
+    pub fn get(&self, uri: &uri::Rsync) -> Option<&Multihash> {
+        self.objects.get(uri)
+    }
+
+    /// Returns the number of objects in the set.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+}
+
+
+//--- ProcessSnapshot
+
+impl rrdp::ProcessSnapshot for ObjectSet {
+    type Err = xml::decode::Error;
+
+    fn meta(
+        &mut self,
+        _session_id: Uuid,
+        _serial: usize
+    ) -> Result<(), Self::Err> {
+        Ok(())
+    }
+
+    fn publish(
+        &mut self,
+        uri: uri::Rsync,
+        data: Vec<u8>,
+    ) -> Result<(), Self::Err> {
+        self.insert(uri, &data);
+        Ok(())
+    }
+}
+
+
+//------------ compare -------------------------------------------------------
+
+/// Compares the objects retrieved via IPFS with those of another transport.
+pub fn compare(ipfs: &ObjectSet, other: &ObjectSet) -> ConsistencyReport {
+    let mut divergences = Vec::new();
+    let mut matching = 0;
+    for (uri, hash) in &ipfs.objects {
+        match other.objects.get(uri) {
+            Some(other_hash) if other_hash == hash => matching += 1,
+            other_hash => {
+                divergences.push(Divergence {
+                    uri: uri.clone(),
+                    ipfs: Some(*hash),
+                    other: other_hash.cloned(),
+                })
+            }
+        }
+    }
+    for (uri, hash) in &other.objects {
+        if !ipfs.objects.contains_key(uri) {
+            divergences.push(Divergence {
+                uri: uri.clone(),
+                ipfs: None,
+                other: Some(*hash),
+            })
+        }
+    }
+    divergences.sort_by_cached_key(|item| item.uri.to_string());
+    ConsistencyReport { matching, divergences }
+}
+
+
+//------------ ConsistencyReport ---------------------------------------------
+
+/// The result of comparing the objects retrieved via two transports.
+#[derive(Clone, Debug)]
+pub struct ConsistencyReport {
+    /// The number of objects that are identical.
+    matching: usize,
+
+    /// The objects that differ, ordered by URI.
+    divergences: Vec<Divergence>,
+}
+
+impl ConsistencyReport {
+    /// Returns whether both transports published the same objects.
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+
+    /// Returns the number of objects that are identical.
+    pub fn matching(&self) -> usize {
+        self.matching
+    }
+
+    /// Returns an iterator over the diverging objects.
+    ///
+    /// The objects are ordered by their URI.
+    pub fn divergences(&self) -> slice::Iter<'_, Divergence> {
+        self.divergences.iter()
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f, "{} matching, {} diverging",
+            self.matching, self.divergences.len()
+        )?;
+        for item in &self.divergences {
+            writeln!(f, "{}", item)?;
+        }
+        Ok(())
+    }
+}
+
+
+//------------ Divergence ----------------------------------------------------
+
+/// An object that differs between two transports.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Divergence {
+    /// The URI of the object.
+    uri: uri::Rsync,
+
+    /// The hash of the object retrieved via IPFS if present.
+    ipfs: Option<Multihash>,
+
+    /// The hash of the object retrieved via the other transport if present.
+    other: Option<Multihash>,
+}
+
+impl Divergence {
+    /// Returns the URI of the object.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the hash of the object retrieved via IPFS.
+    ///
+    /// Returns `None` if the object was missing from IPFS.
+    pub fn ipfs_hash(&self) -> Option<&Multihash> {
+        self.ipfs.as_ref()
+    }
+
+    /// Returns the hash of the object retrieved via the other transport.
+    ///
+    /// Returns `None` if the object was missing from the other transport.
+    pub fn other_hash(&self) -> Option<&Multihash> {
+        self.other.as_ref()
+    }
+
+    /// Returns the kind of divergence.
+    pub fn kind(&self) -> DivergenceKind {
+        match (self.ipfs.is_some(), self.other.is_some()) {
+            (false, _) => DivergenceKind::MissingFromIpfs,
+            (_, false) => DivergenceKind::MissingFromOther,
+            _ => DivergenceKind::HashMismatch
+        }
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn write_hash(
+            f: &mut fmt::Formatter, hash: Option<&Multihash>
+        ) -> fmt::Result {
+            match hash {
+                Some(hash) => {
+                    let mut buf = [0u8; 64];
+                    f.write_str(hex::encode(hash.digest(), &mut buf))
+                }
+                None => f.write_str("missing")
+            }
+        }
+
+        write!(f, "{}: ipfs ", self.uri)?;
+        write_hash(f, self.ipfs.as_ref())?;
+        f.write_str(", other ")?;
+        write_hash(f, self.other.as_ref())
+    }
+}
+
+
+//------------ DivergenceKind ------------------------------------------------
+
+/// The ways an object can differ between two transports.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DivergenceKind {
+    /// The object was only published via the other transport.
+    MissingFromIpfs,
+
+    /// The object was only published via IPFS.
+    MissingFromOther,
+
+    /// The object was published via both with different content.
+    HashMismatch,
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Creates the URI for a path within an rsync module.
+fn module_uri(
+    module: &uri::RsyncModule, path: &str
+) -> Result<uri::Rsync, ConsistencyError> {
+    uri::Rsync::from_string(format!("{}{}", module, path))
+        .map_err(|_| ConsistencyError::BadPath)
+}
+
+
+//------------ ConsistencyError ----------------------------------------------
+
+/// The objects of a transport could not be collected.
+#[derive(Debug)]
+pub enum ConsistencyError {
+    /// The IPFS DAG could not be read.
+    Dag(DagError),
+
+    /// Reading a local directory failed.
+    Io(io::Error),
+
+    /// A path could not be converted into an rsync URI.
+    BadPath,
+}
+
+impl From<DagError> for ConsistencyError {
+    fn from(err: DagError) -> Self {
+        ConsistencyError::Dag(err)
+    }
+}
+
+impl From<io::Error> for ConsistencyError {
+    fn from(err: io::Error) -> Self {
+        ConsistencyError::Io(err)
+    }
+}
+
+impl fmt::Display for ConsistencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConsistencyError::Dag(ref err) => err.fmt(f),
+            ConsistencyError::Io(ref err) => err.fmt(f),
+            ConsistencyError::BadPath => {
+                f.write_str("path not usable in rsync URI")
+            }
+        }
+    }
+}
+
+impl error::Error for ConsistencyError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use bytes::Bytes;
+    use rrdp::ProcessSnapshot;
+    use super::super::dag::DagBuilder;
+    use super::*;
+
+    fn rsync(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn compare_transports() {
+        let mut builder = DagBuilder::new();
+        builder.insert("ca/a.roa", Bytes::from_static(b"a")).unwrap();
+        builder.insert("ca/b.roa", Bytes::from_static(b"b")).unwrap();
+        builder.insert("ca/c.roa", Bytes::from_static(b"c")).unwrap();
+        let module = rsync("rsync://example.com/repo/ca").to_module();
+        let ipfs = ObjectSet::from_dag(&builder.build(), &module).unwrap();
+        assert_eq!(ipfs.len(), 3);
+
+        let mut rrdp = ObjectSet::new();
+        rrdp.publish(rsync("rsync://example.com/repo/ca/a.roa"), b"a".to_vec())
+            .unwrap();
+        rrdp.publish(rsync("rsync://example.com/repo/ca/b.roa"), b"x".to_vec())
+            .unwrap();
+        rrdp.publish(rsync("rsync://example.com/repo/ca/d.roa"), b"d".to_vec())
+            .unwrap();
+
+        let report = compare(&ipfs, &rrdp);
+        assert!(!report.is_consistent());
+        assert_eq!(report.matching(), 1);
+        let kinds: Vec<_> = report.divergences().map(|item| {
+            (item.uri().path().to_string(), item.kind())
+        }).collect();
+        assert_eq!(
+            kinds,
+            [
+                ("ca/b.roa".into(), DivergenceKind::HashMismatch),
+                ("ca/c.roa".into(), DivergenceKind::MissingFromOther),
+                ("ca/d.roa".into(), DivergenceKind::MissingFromIpfs),
+            ]
+        );
+        let first = report.divergences().next().unwrap();
+        assert_eq!(first.ipfs_hash(), Some(&Multihash::sha256(b"b")));
+        assert_eq!(first.other_hash(), Some(&Multihash::sha256(b"x")));
+
+        assert!(rrdp.remove(&rsync("rsync://example.com/repo/ca/d.roa")));
+        rrdp.insert(rsync("rsync://example.com/repo/ca/b.roa"), b"b");
+        rrdp.insert(rsync("rsync://example.com/repo/ca/c.roa"), b"c");
+        let report = compare(&ipfs, &rrdp);
+        assert!(report.is_consistent());
+        assert_eq!(report.matching(), 3);
+    }
+}
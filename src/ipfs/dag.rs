@@ -0,0 +1,771 @@
+//! The canonical UnixFS layout of a repository.
+//!
+//! This module maps a set of files to a UnixFS DAG in a deterministic way,
+//! so that the same set of files always results in the same root CID. The
+//! mapping follows the default behaviour of the reference implementation
+//! of IPFS when adding a directory, i.e.:
+//!
+//! * all nodes are dag-pb nodes and are identified by version 0 CIDs,
+//! * files are split into chunks of exactly [`CHUNK_SIZE`] bytes with only
+//!   the last chunk being shorter; each chunk becomes a leaf node,
+//! * files with more than one chunk are arranged in a balanced tree with at
+//!   most [`MAX_LINKS`] links per node, filling subtrees from the left,
+//! * directories are plain UnixFS directories, i.e., they are never
+//!   sharded, and their entries are ordered by the bytes of their names.
+//!
+//! Because of this, the root CID can be checked with any IPFS node.
+//!
+//! In the other direction, a [`Dag`] assembled from blocks retrieved from
+//! IPFS can be turned back into the files it contains. Reading accepts any
+//! layout of files, including raw leaves, but no sharded directories.
+//! Whether such a DAG uses the canonical layout can be checked via
+//! [`Dag::is_canonical`].
+//!
+//! [`Dag`]: struct.Dag.html
+//! [`Dag::is_canonical`]: struct.Dag.html#method.is_canonical
+//! [`CHUNK_SIZE`]: constant.CHUNK_SIZE.html
+//! [`MAX_LINKS`]: constant.MAX_LINKS.html
+
+use std::{error, fmt};
+use std::collections::{BTreeMap, HashMap};
+use std::collections::hash_map;
+use bytes::Bytes;
+use crate::cid::{Cid, Multihash, read_varint, write_varint};
+use crate::uri;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The size of a file chunk in bytes.
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// The maximum number of links in a node of a file.
+pub const MAX_LINKS: usize = 174;
+
+
+//------------ DagBuilder ----------------------------------------------------
+
+/// A builder for the UnixFS DAG of a set of files.
+///
+/// Files are added with their path relative to the root of the DAG. The
+/// order in which files are added does not influence the result.
+#[derive(Clone, Debug, Default)]
+pub struct DagBuilder {
+    /// The root directory.
+    root: Directory,
+}
+
+impl DagBuilder {
+    /// Creates a new builder for an empty directory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a file at the given path.
+    ///
+    /// The path consists of segments separated by slashes. Missing
+    /// directories are created. If a file already exists at the path, it
+    /// is replaced.
+    ///
+    /// Returns an error if the path contains an empty segment or a segment
+    /// consisting of one or two dots, or if one of the directories on the
+    /// path already exists as a file or the path itself as a directory.
+    pub fn insert(
+        &mut self, path: &str, content: Bytes
+    ) -> Result<(), DagError> {
+        let mut segments = path.split('/').peekable();
+        let mut dir = &mut self.root;
+        while let Some(name) = segments.next() {
+            if name.is_empty() || name == "." || name == ".." {
+                return Err(DagError::BadPath)
+            }
+            if segments.peek().is_none() {
+                if let Some(Entry::Directory(_)) = dir.entries.get(name) {
+                    return Err(DagError::Conflict)
+                }
+                dir.entries.insert(name.into(), Entry::File(content));
+                return Ok(())
+            }
+            let entry = dir.entries.entry(name.into()).or_insert_with(|| {
+                Entry::Directory(Directory::default())
+            });
+            dir = match *entry {
+                Entry::Directory(ref mut dir) => dir,
+                Entry::File(_) => return Err(DagError::Conflict)
+            };
+        }
+        Err(DagError::BadPath)
+    }
+
+    /// Adds a file for the given rsync URI.
+    ///
+    /// The file is placed at the path of the URI relative to its module.
+    pub fn insert_rsync(
+        &mut self, uri: &uri::Rsync, content: Bytes
+    ) -> Result<(), DagError> {
+        self.insert(uri.path(), content)
+    }
+
+    /// Builds the DAG.
+    pub fn build(&self) -> Dag {
+        let mut blocks = HashMap::new();
+        let (root, _) = self.root.build(&mut blocks);
+        Dag { root, blocks }
+    }
+}
+
+
+//------------ Dag -----------------------------------------------------------
+
+/// A UnixFS DAG.
+///
+/// The DAG consists of its root CID and all the blocks making up the DAG.
+#[derive(Clone, Debug)]
+pub struct Dag {
+    /// The CID of the root directory.
+    root: Cid,
+
+    /// All blocks of the DAG.
+    blocks: HashMap<Cid, Bytes>,
+}
+
+impl Dag {
+    /// Creates a DAG from its root CID and a set of blocks.
+    ///
+    /// This is intended for DAGs retrieved from IPFS. The blocks are only
+    /// checked when the files are read via [`files`].
+    ///
+    /// [`files`]: #method.files
+    pub fn from_blocks(root: Cid, blocks: HashMap<Cid, Bytes>) -> Self {
+        Dag { root, blocks }
+    }
+
+    /// Returns the CID of the root directory.
+    pub fn root(&self) -> Cid {
+        self.root
+    }
+
+    /// Returns the block with the given CID if it is part of the DAG.
+    pub fn get(&self, cid: &Cid) -> Option<&Bytes> {
+        self.blocks.get(cid)
+    }
+
+    /// Returns an iterator over all the blocks of the DAG.
+    pub fn blocks(&self) -> hash_map::Iter<'_, Cid, Bytes> {
+        self.blocks.iter()
+    }
+
+    /// Returns the number of blocks in the DAG.
+    pub fn len(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns whether the DAG has no blocks.
+    ///
+    /// This never happens for a DAG created by a [`DagBuilder`] since
+    /// there always is at least the root.
+    ///
+    /// [`DagBuilder`]: struct.DagBuilder.html
+    pub fn is_empty(&self) -> bool {
+        self.blocks.is_empty()
+    }
+
+    /// Returns all files contained in the DAG.
+    ///
+    /// The files are returned with their path relative to the root
+    /// directory in the order of the directory entries. Each block is
+    /// checked against its CID before it is used.
+    ///
+    /// Fails if a block is missing, a block doesn’t match its CID or can’t
+    /// be decoded, or if an entry name can’t be used in a path.
+    pub fn files(&self) -> Result<Vec<(String, Bytes)>, DagError> {
+        let mut res = Vec::new();
+        self.read_directory(&self.root, "", &mut res)?;
+        Ok(res)
+    }
+
+    /// Returns the root CID of the canonical DAG for the files of the DAG.
+    ///
+    /// This is the root CID a [`DagBuilder`] produces for the files
+    /// returned by [`files`]. Fails if the files can’t be read.
+    ///
+    /// [`DagBuilder`]: struct.DagBuilder.html
+    /// [`files`]: #method.files
+    pub fn canonical_root(&self) -> Result<Cid, DagError> {
+        let mut builder = DagBuilder::new();
+        for (path, content) in self.files()? {
+            builder.insert(&path, content)?;
+        }
+        Ok(builder.build().root())
+    }
+
+    /// Returns whether the DAG uses the canonical layout.
+    ///
+    /// If this is the case, any publisher producing a DAG for the same
+    /// set of files arrives at the same root CID. A DAG retrieved via IPFS
+    /// can thus be cross-checked against a DAG built from the content of
+    /// the repository received via a different transport.
+    pub fn is_canonical(&self) -> Result<bool, DagError> {
+        Ok(self.canonical_root()? == self.root)
+    }
+
+    /// Returns the verified block for a CID.
+    fn block(&self, cid: &Cid) -> Result<&Bytes, DagError> {
+        let block = self.blocks.get(cid).ok_or(DagError::MissingBlock(*cid))?;
+        if !cid.hash().verify(block) {
+            return Err(DagError::Malformed)
+        }
+        Ok(block)
+    }
+
+    /// Reads the files of a directory and all its subdirectories.
+    fn read_directory(
+        &self, cid: &Cid, path: &str, res: &mut Vec<(String, Bytes)>
+    ) -> Result<(), DagError> {
+        let node = Node::decode(self.block(cid)?)?;
+        if node.data_type != DIRECTORY {
+            return Err(DagError::Malformed)
+        }
+        for (cid, name) in node.links {
+            if name.is_empty() || name.contains('/')
+                || name == "." || name == ".."
+            {
+                return Err(DagError::Malformed)
+            }
+            let path = if path.is_empty() {
+                name
+            }
+            else {
+                format!("{}/{}", path, name)
+            };
+            if cid.codec() != Cid::RAW
+                && Node::decode(self.block(&cid)?)?.data_type == DIRECTORY
+            {
+                self.read_directory(&cid, &path, res)?;
+            }
+            else {
+                let mut content = Vec::new();
+                self.read_file(&cid, &mut content)?;
+                res.push((path, content.into()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Appends the content of a file to `target`.
+    fn read_file(
+        &self, cid: &Cid, target: &mut Vec<u8>
+    ) -> Result<(), DagError> {
+        read_file_block(
+            cid, self.block(cid)?,
+            &mut |cid| Ok(self.block(cid)?.clone()),
+            target
+        )
+    }
+}
+
+
+//------------ read_path -----------------------------------------------------
+
+/// Reads a single file from a DAG whose blocks are retrieved on demand.
+///
+/// Starting at the root directory identified by `root`, follows the
+/// directory entries named by the components of `path` and returns the
+/// content of the file found there. Returns `Ok(None)` if there is no such
+/// file.
+///
+/// The blocks are requested from `op` as needed and checked against their
+/// CID before they are used. This way, only the blocks on the path and
+/// those of the file itself are retrieved.
+pub fn read_path<F, E>(
+    root: &Cid, path: &str, mut op: F
+) -> Result<Option<Bytes>, E>
+where F: FnMut(&Cid) -> Result<Bytes, E>, E: From<DagError> {
+    let mut op = |cid: &Cid| {
+        let block = op(cid)?;
+        if !cid.hash().verify(&block) {
+            return Err(E::from(DagError::Malformed))
+        }
+        Ok(block)
+    };
+    let mut cid = *root;
+    let mut block = op(&cid)?;
+    for name in path.split('/').filter(|name| !name.is_empty()) {
+        if name == "." || name == ".." || cid.codec() == Cid::RAW {
+            return Ok(None)
+        }
+        let node = Node::decode(&block)?;
+        if node.data_type != DIRECTORY {
+            return Ok(None)
+        }
+        cid = match node.links.into_iter().find(|link| link.1 == name) {
+            Some((cid, _)) => cid,
+            None => return Ok(None)
+        };
+        block = op(&cid)?;
+    }
+    if cid.codec() != Cid::RAW
+        && Node::decode(&block)?.data_type == DIRECTORY
+    {
+        return Ok(None)
+    }
+    let mut res = Vec::new();
+    read_file_block(&cid, &block, &mut op, &mut res)?;
+    Ok(Some(res.into()))
+}
+
+/// Appends the content of a file starting with `block` to `target`.
+///
+/// Further blocks of the file are requested from `op`.
+fn read_file_block<F, E>(
+    cid: &Cid, block: &Bytes, op: &mut F, target: &mut Vec<u8>
+) -> Result<(), E>
+where F: FnMut(&Cid) -> Result<Bytes, E>, E: From<DagError> {
+    if cid.codec() == Cid::RAW {
+        target.extend_from_slice(block);
+        return Ok(())
+    }
+    let node = Node::decode(block)?;
+    if node.data_type != FILE && node.data_type != RAW {
+        return Err(DagError::Malformed.into())
+    }
+    target.extend_from_slice(&node.data);
+    for (cid, _) in node.links {
+        let block = op(&cid)?;
+        read_file_block(&cid, &block, op, target)?;
+    }
+    Ok(())
+}
+
+
+//------------ Directory and Entry -------------------------------------------
+
+/// A directory in the tree to be converted.
+#[derive(Clone, Debug, Default)]
+struct Directory {
+    /// The entries of the directory.
+    ///
+    /// Strings are ordered by their bytes, so this is canonical ordering.
+    entries: BTreeMap<String, Entry>,
+}
+
+impl Directory {
+    /// Builds the directory, returning its CID and cumulative size.
+    fn build(&self, blocks: &mut HashMap<Cid, Bytes>) -> (Cid, u64) {
+        let links: Vec<_> = self.entries.iter().map(|(name, entry)| {
+            let (cid, tsize) = match *entry {
+                Entry::File(ref content) => build_file(content, blocks),
+                Entry::Directory(ref dir) => dir.build(blocks),
+            };
+            Link { cid, name, tsize }
+        }).collect();
+        add_node(&links, &unixfs_data(DIRECTORY, None, None, &[]), blocks)
+    }
+}
+
+/// An entry in a directory.
+#[derive(Clone, Debug)]
+enum Entry {
+    File(Bytes),
+    Directory(Directory),
+}
+
+
+//------------ Building Files ------------------------------------------------
+
+/// Builds a file, returning its CID and cumulative size.
+pub(super) fn build_file(
+    content: &[u8], blocks: &mut HashMap<Cid, Bytes>
+) -> (Cid, u64) {
+    if content.is_empty() {
+        return add_node(&[], &unixfs_data(FILE, None, Some(0), &[]), blocks)
+    }
+
+    // Each node is represented by its CID, cumulative size, and file size.
+    let mut nodes: Vec<_> = content.chunks(CHUNK_SIZE).map(|chunk| {
+        let len = chunk.len() as u64;
+        let (cid, tsize) = add_node(
+            &[], &unixfs_data(FILE, Some(chunk), Some(len), &[]), blocks
+        );
+        (cid, tsize, len)
+    }).collect();
+    while nodes.len() > 1 {
+        nodes = nodes.chunks(MAX_LINKS).map(|children| {
+            let links: Vec<_> = children.iter().map(|&(cid, tsize, _)| {
+                Link { cid, name: "", tsize }
+            }).collect();
+            let sizes: Vec<_> = children.iter().map(|item| item.2).collect();
+            let len = sizes.iter().sum();
+            let (cid, tsize) = add_node(
+                &links, &unixfs_data(FILE, None, Some(len), &sizes), blocks
+            );
+            (cid, tsize, len)
+        }).collect();
+    }
+    (nodes[0].0, nodes[0].1)
+}
+
+
+//------------ Encoding ------------------------------------------------------
+
+/// The UnixFS data type for raw data.
+const RAW: u64 = 0;
+
+/// The UnixFS data type for a directory.
+const DIRECTORY: u64 = 1;
+
+/// The UnixFS data type for a file.
+const FILE: u64 = 2;
+
+/// A link to another node.
+struct Link<'a> {
+    cid: Cid,
+    name: &'a str,
+    tsize: u64,
+}
+
+/// Encodes a dag-pb node, adds it to the blocks and returns its CID.
+///
+/// Returns the CID and the cumulative size of the node.
+fn add_node(
+    links: &[Link], data: &[u8], blocks: &mut HashMap<Cid, Bytes>
+) -> (Cid, u64) {
+    // PBNode: Links are field 2, Data is field 1 but links come first.
+    let mut node = Vec::new();
+    let mut tsize = 0;
+    for link in links {
+        let mut enc = Vec::new();
+        write_bytes(1, &link.cid.to_bytes(), &mut enc);
+        write_bytes(2, link.name.as_bytes(), &mut enc);
+        write_uint(3, link.tsize, &mut enc);
+        write_bytes(2, &enc, &mut node);
+        tsize += link.tsize;
+    }
+    write_bytes(1, data, &mut node);
+    let cid = Cid::v0(Multihash::sha256(&node));
+    tsize += node.len() as u64;
+    blocks.insert(cid, node.into());
+    (cid, tsize)
+}
+
+/// Encodes the UnixFS data message.
+fn unixfs_data(
+    data_type: u64,
+    data: Option<&[u8]>,
+    filesize: Option<u64>,
+    blocksizes: &[u64],
+) -> Vec<u8> {
+    let mut res = Vec::new();
+    write_uint(1, data_type, &mut res);
+    if let Some(data) = data {
+        write_bytes(2, data, &mut res);
+    }
+    if let Some(filesize) = filesize {
+        write_uint(3, filesize, &mut res);
+    }
+    for &size in blocksizes {
+        write_uint(4, size, &mut res);
+    }
+    res
+}
+
+/// Appends a protobuf varint field.
+pub(super) fn write_uint(field: u64, value: u64, target: &mut Vec<u8>) {
+    write_varint(field << 3, target);
+    write_varint(value, target);
+}
+
+/// Appends a protobuf length-delimited field.
+pub(super) fn write_bytes(field: u64, value: &[u8], target: &mut Vec<u8>) {
+    write_varint(field << 3 | 2, target);
+    write_varint(value.len() as u64, target);
+    target.extend_from_slice(value);
+}
+
+
+//------------ Decoding ------------------------------------------------------
+
+/// A decoded dag-pb node.
+struct Node {
+    /// The links of the node with their CIDs and names.
+    links: Vec<(Cid, String)>,
+
+    /// The UnixFS data type of the node.
+    data_type: u64,
+
+    /// The data stored in the node itself.
+    data: Bytes,
+}
+
+impl Node {
+    /// Decodes a dag-pb node containing UnixFS data.
+    fn decode(block: &Bytes) -> Result<Self, DagError> {
+        let mut links = Vec::new();
+        let mut unixfs = None;
+        let mut bytes = block.as_ref();
+        while !bytes.is_empty() {
+            match read_field(&mut bytes)? {
+                (1, Field::Bytes(data)) => unixfs = Some(data),
+                (2, Field::Bytes(mut link)) => {
+                    let mut cid = None;
+                    let mut name = String::new();
+                    while !link.is_empty() {
+                        match read_field(&mut link)? {
+                            (1, Field::Bytes(data)) => {
+                                cid = Some(
+                                    Cid::from_bytes(data)
+                                        .map_err(|_| DagError::Malformed)?
+                                );
+                            }
+                            (2, Field::Bytes(data)) => {
+                                name = String::from_utf8(data.into())
+                                    .map_err(|_| DagError::Malformed)?;
+                            }
+                            _ => { }
+                        }
+                    }
+                    links.push((cid.ok_or(DagError::Malformed)?, name));
+                }
+                _ => return Err(DagError::Malformed)
+            }
+        }
+
+        let mut unixfs = unixfs.ok_or(DagError::Malformed)?;
+        let mut data_type = None;
+        let mut data = Bytes::new();
+        while !unixfs.is_empty() {
+            match read_field(&mut unixfs)? {
+                (1, Field::Varint(value)) => data_type = Some(value),
+                (2, Field::Bytes(value)) => {
+                    data = block.slice_ref(value)
+                }
+                _ => { }
+            }
+        }
+        Ok(Node {
+            links,
+            data_type: data_type.ok_or(DagError::Malformed)?,
+            data
+        })
+    }
+}
+
+/// The value of a protobuf field.
+pub(super) enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+/// Reads a protobuf field from the beginning of a slice.
+///
+/// Returns the field number and the value.
+pub(super) fn read_field<'a>(
+    bytes: &mut &'a [u8]
+) -> Result<(u64, Field<'a>), DagError> {
+    let key = read_varint(bytes).map_err(|_| DagError::Malformed)?;
+    let value = read_varint(bytes).map_err(|_| DagError::Malformed)?;
+    match key & 0x07 {
+        0 => Ok((key >> 3, Field::Varint(value))),
+        2 => {
+            if value > bytes.len() as u64 {
+                return Err(DagError::Malformed)
+            }
+            let (value, rest) = bytes.split_at(value as usize);
+            *bytes = rest;
+            Ok((key >> 3, Field::Bytes(value)))
+        }
+        _ => Err(DagError::Malformed)
+    }
+}
+
+
+//------------ DagError ------------------------------------------------------
+
+/// Building or reading a DAG has failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DagError {
+    /// The path of the file is not acceptable.
+    BadPath,
+
+    /// The path conflicts with a file or directory already present.
+    Conflict,
+
+    /// The block with the given CID is not part of the DAG.
+    MissingBlock(Cid),
+
+    /// A block does not match its CID or can’t be decoded.
+    Malformed,
+}
+
+impl fmt::Display for DagError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DagError::BadPath => f.write_str("invalid path"),
+            DagError::Conflict => {
+                f.write_str("path conflicts with existing entry")
+            }
+            DagError::MissingBlock(ref cid) => {
+                write!(f, "missing block {}", cid)
+            }
+            DagError::Malformed => f.write_str("malformed block"),
+        }
+    }
+}
+
+impl error::Error for DagError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    fn cid(s: &str) -> Cid {
+        Cid::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn well_known() {
+        assert_eq!(
+            DagBuilder::new().build().root(),
+            cid("QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn")
+        );
+        let mut blocks = HashMap::new();
+        assert_eq!(
+            build_file(b"", &mut blocks).0,
+            cid("QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH")
+        );
+        assert_eq!(
+            build_file(b"hello\n", &mut blocks).0,
+            cid("QmZULkCELmmk5XNfCgTnCyFgAVxBRBXyDHGGMVoLFLiXEN")
+        );
+    }
+
+    #[test]
+    fn deterministic() {
+        let files = [
+            ("ca/a.roa", Bytes::from_static(b"a")),
+            ("ca/b.roa", Bytes::from_static(b"b")),
+            ("ta.cer", Bytes::from_static(b"ta")),
+        ];
+        let mut one = DagBuilder::new();
+        for (path, content) in files.iter() {
+            one.insert(path, content.clone()).unwrap();
+        }
+        let mut two = DagBuilder::new();
+        for (path, content) in files.iter().rev() {
+            two.insert(path, content.clone()).unwrap();
+        }
+        let one = one.build();
+        assert_eq!(one.root(), two.build().root());
+        // Root, ca directory, three files.
+        assert_eq!(one.len(), 5);
+        for (cid, block) in one.blocks() {
+            assert!(cid.hash().verify(block));
+        }
+
+        let mut builder = DagBuilder::new();
+        assert_eq!(
+            builder.insert("a//b", Bytes::new()), Err(DagError::BadPath)
+        );
+        assert_eq!(
+            builder.insert("a/../b", Bytes::new()), Err(DagError::BadPath)
+        );
+        builder.insert("a/b", Bytes::new()).unwrap();
+        assert_eq!(builder.insert("a", Bytes::new()), Err(DagError::Conflict));
+        assert_eq!(
+            builder.insert("a/b/c", Bytes::new()), Err(DagError::Conflict)
+        );
+    }
+
+    #[test]
+    fn canonical() {
+        let mut builder = DagBuilder::new();
+        builder.insert("a.roa", Bytes::from_static(b"a")).unwrap();
+        builder.insert("b.roa", Bytes::from_static(b"b")).unwrap();
+        let dag = builder.build();
+        assert_eq!(dag.is_canonical(), Ok(true));
+
+        // The same files with the directory entries in reverse order.
+        let mut blocks = HashMap::new();
+        let (a, a_size) = build_file(b"a", &mut blocks);
+        let (b, b_size) = build_file(b"b", &mut blocks);
+        let (root, _) = add_node(
+            &[
+                Link { cid: b, name: "b.roa", tsize: b_size },
+                Link { cid: a, name: "a.roa", tsize: a_size },
+            ],
+            &unixfs_data(DIRECTORY, None, None, &[]),
+            &mut blocks
+        );
+        let other = Dag::from_blocks(root, blocks);
+        assert_ne!(other.root(), dag.root());
+        assert_eq!(other.is_canonical(), Ok(false));
+        assert_eq!(other.canonical_root(), Ok(dag.root()));
+    }
+
+    #[test]
+    fn read_files() {
+        let big: Bytes = (0..CHUNK_SIZE * 3).map(|i| i as u8)
+            .collect::<Vec<_>>().into();
+        let mut builder = DagBuilder::new();
+        builder.insert("ca/big.roa", big.clone()).unwrap();
+        builder.insert("ca/sub/empty.crl", Bytes::new()).unwrap();
+        builder.insert("ta.cer", Bytes::from_static(b"ta")).unwrap();
+        let dag = builder.build();
+        assert_eq!(
+            dag.files().unwrap(),
+            [
+                ("ca/big.roa".into(), big.clone()),
+                ("ca/sub/empty.crl".into(), Bytes::new()),
+                ("ta.cer".into(), Bytes::from_static(b"ta")),
+            ]
+        );
+
+        let get = |cid: &Cid| {
+            dag.get(cid).cloned().ok_or(DagError::MissingBlock(*cid))
+        };
+        assert_eq!(read_path(&dag.root(), "ca/big.roa", get), Ok(Some(big)));
+        assert_eq!(
+            read_path(&dag.root(), "/ta.cer", get),
+            Ok(Some(Bytes::from_static(b"ta")))
+        );
+        assert_eq!(read_path(&dag.root(), "ca/sub", get), Ok(None));
+        assert_eq!(read_path(&dag.root(), "ca/other.roa", get), Ok(None));
+        assert_eq!(read_path(&dag.root(), "ta.cer/foo", get), Ok(None));
+        assert_eq!(read_path(&dag.root(), "ca/../ta.cer", get), Ok(None));
+
+        let mut blocks = dag.blocks.clone();
+        let mut files = HashMap::new();
+        let (ta, _) = build_file(b"ta", &mut files);
+        blocks.remove(&ta);
+        assert_eq!(
+            Dag::from_blocks(dag.root(), blocks.clone()).files(),
+            Err(DagError::MissingBlock(ta))
+        );
+        blocks.insert(ta, Bytes::from_static(b"forged"));
+        assert_eq!(
+            read_path(&dag.root(), "ta.cer", |cid: &Cid| {
+                blocks.get(cid).cloned().ok_or(DagError::MissingBlock(*cid))
+            }),
+            Err(DagError::Malformed)
+        );
+        assert_eq!(
+            Dag::from_blocks(dag.root(), blocks).files(),
+            Err(DagError::Malformed)
+        );
+    }
+
+    #[test]
+    fn chunked_file() {
+        let content = vec![7u8; CHUNK_SIZE * 2 + 10];
+        let mut blocks = HashMap::new();
+        let (root, tsize) = build_file(&content, &mut blocks);
+        // Two identical full chunks, one short chunk, and the root.
+        assert_eq!(blocks.len(), 3);
+        assert!(tsize > content.len() as u64);
+        assert!(blocks.get(&root).unwrap().len() < 200);
+    }
+}
@@ -0,0 +1,371 @@
+//! Retrieving repository objects via IPFS.
+//!
+//! The [`Fetcher`] is an [`ObjectSource`] for the validator that retrieves
+//! objects from the UnixFS DAGs of repositories published via IPFS. The
+//! path of an object within the DAG is the path of its rsync URI relative
+//! to the rsync module which is mapped to the DAG’s root. The current root
+//! CID of each module is typically obtained by resolving the module’s IPNS
+//! name.
+//!
+//! Blocks are retrieved on demand via a [`GatewayPool`], so only the
+//! blocks needed for an object are transferred. The actual requests are
+//! made through the [`BlockSource`] trait which allows using any HTTP
+//! client.
+//!
+//! Content retrieved via IPFS is only used if it agrees with the manifest
+//! of its publication point. If an object can’t be found via IPFS, if
+//! retrieving it fails, if its manifest is stale, or if its hash doesn’t
+//! match the manifest, the object is taken from a fallback source instead,
+//! typically a local copy of the repository’s rsync or RRDP content.
+//!
+//! [`Fetcher`]: struct.Fetcher.html
+//! [`ObjectSource`]: ../../validation/trait.ObjectSource.html
+//! [`GatewayPool`]: ../gateway/struct.GatewayPool.html
+//! [`BlockSource`]: trait.BlockSource.html
+
+use std::{fmt, io};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use bytes::Bytes;
+use log::{debug, info};
+use crate::uri;
+use crate::cid::Cid;
+use crate::fetch::FetchError;
+use crate::manifest::{Manifest, ManifestHash};
+use crate::validation::ObjectSource;
+use crate::x509::{Clock, SystemClock, Time};
+use super::dag::{DagError, read_path};
+use super::gateway::{Gateway, GatewayPool, PoolError};
+
+
+//------------ BlockSource ---------------------------------------------------
+
+/// A type that can retrieve raw blocks from an IPFS gateway.
+pub trait BlockSource {
+    /// Retrieves the block with the given CID via the given gateway.
+    ///
+    /// The URL for the request can be obtained via
+    /// [`Gateway::block_url`]. The block doesn’t need to be checked
+    /// against the CID as this is done by the fetcher.
+    ///
+    /// [`Gateway::block_url`]: ../gateway/struct.Gateway.html#method.block_url
+    fn block(&self, gateway: &Gateway, cid: &Cid) -> Result<Bytes, FetchError>;
+}
+
+
+//------------ Fetcher -------------------------------------------------------
+
+/// An object source retrieving objects via IPFS with a fallback.
+///
+/// The fetcher remembers the file hashes of every manifest it hands out,
+/// regardless of where the manifest came from. Objects retrieved via IPFS
+/// later are checked against these hashes.
+#[derive(Debug)]
+pub struct Fetcher<B, F> {
+    /// The source for blocks.
+    blocks: B,
+
+    /// The source for objects not available via IPFS.
+    fallback: F,
+
+    /// The gateways to use.
+    pool: Mutex<GatewayPool>,
+
+    /// The root CIDs of the rsync modules available via IPFS.
+    roots: HashMap<uri::RsyncModule, Cid>,
+
+    /// The hashes of the objects listed on the manifests seen so far.
+    hashes: Mutex<HashMap<uri::Rsync, ManifestHash>>,
+
+    /// The clock providing the time to check manifests against.
+    clock: Arc<dyn Clock>,
+}
+
+impl<B, F> Fetcher<B, F> {
+    /// Creates a new fetcher.
+    ///
+    /// Initially, no module is available via IPFS and all objects are
+    /// taken from `fallback`. Use [`set_root`] to make modules available.
+    ///
+    /// [`set_root`]: #method.set_root
+    pub fn new(blocks: B, pool: GatewayPool, fallback: F) -> Self {
+        Fetcher {
+            blocks,
+            fallback,
+            pool: Mutex::new(pool),
+            roots: HashMap::new(),
+            hashes: Mutex::new(HashMap::new()),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Returns a reference to the fallback source.
+    pub fn fallback(&self) -> &F {
+        &self.fallback
+    }
+
+    /// Returns the root CID currently used for an rsync module.
+    pub fn root(&self, module: &uri::RsyncModule) -> Option<Cid> {
+        self.roots.get(module).cloned()
+    }
+
+    /// Sets the root CID of the DAG for an rsync module.
+    pub fn set_root(&mut self, module: uri::RsyncModule, root: Cid) {
+        self.roots.insert(module, root);
+    }
+
+    /// Stops retrieving objects of an rsync module via IPFS.
+    pub fn remove_root(&mut self, module: &uri::RsyncModule) {
+        self.roots.remove(module);
+    }
+
+    /// Sets the time manifests are checked against for staleness.
+    ///
+    /// If this is `None`, which is the default, the current time is used.
+    pub fn set_now(&mut self, now: Option<Time>) {
+        self.clock = match now {
+            Some(now) => Arc::new(now),
+            None => Arc::new(SystemClock),
+        }
+    }
+
+    /// Sets the clock providing the time manifests are checked against.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock
+    }
+
+    /// Returns the time to check manifests against.
+    fn now(&self) -> Time {
+        self.clock.now()
+    }
+
+    /// Acquires the manifest hashes.
+    ///
+    /// The hashes are only ever updated in one go, so we can ignore lock
+    /// poisoning.
+    fn hashes(&self) -> MutexGuard<'_, HashMap<uri::Rsync, ManifestHash>> {
+        self.hashes.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Records the file hashes of a manifest.
+    ///
+    /// Returns `false` if the manifest can’t be decoded or is stale.
+    fn add_manifest(&self, uri: &uri::Rsync, content: &Bytes) -> bool {
+        let manifest = match Manifest::decode(content.clone(), false) {
+            Ok(manifest) => manifest,
+            Err(_) => return false
+        };
+        if manifest.content().is_stale_at(self.now()) {
+            return false
+        }
+        let base = match uri.parent() {
+            Some(base) => base,
+            None => return false
+        };
+        self.hashes().extend(manifest.content().iter_uris(&base));
+        true
+    }
+}
+
+impl<B: BlockSource, F> Fetcher<B, F> {
+    /// Returns the content of an object if it is usable from IPFS.
+    fn get_ipfs(&self, uri: &uri::Rsync) -> Option<Bytes> {
+        let root = self.roots.get(uri.module())?;
+        let content = match read_path(root, uri.path(), |cid| {
+            self.block(cid)
+        }) {
+            Ok(Some(content)) => content,
+            Ok(None) => {
+                debug!("{}: not available via IPFS", uri);
+                return None
+            }
+            Err(err) => {
+                info!("{}: retrieving via IPFS failed: {}", uri, err);
+                return None
+            }
+        };
+        if uri.ends_with(".mft") {
+            if !self.add_manifest(uri, &content) {
+                info!("{}: manifest via IPFS stale or broken", uri);
+                return None
+            }
+        }
+        else if let Some(hash) = self.hashes().get(uri) {
+            if hash.verify(&content).is_err() {
+                info!("{}: object via IPFS doesn’t match manifest", uri);
+                return None
+            }
+        }
+        Some(content)
+    }
+
+    /// Retrieves a block via the gateway pool.
+    fn block(&self, cid: &Cid) -> Result<Bytes, Failure> {
+        let now = Time::now();
+        self.pool.lock().unwrap_or_else(|err| err.into_inner()).fetch(
+            now, |gateway| self.blocks.block(gateway, cid)
+        ).map_err(Failure::Pool)
+    }
+}
+
+impl<B: BlockSource, F: ObjectSource> ObjectSource for Fetcher<B, F> {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        if let Some(content) = self.get_ipfs(uri) {
+            return Ok(Some(content))
+        }
+        let res = self.fallback.get(uri)?;
+        if let Some(ref content) = res {
+            if uri.ends_with(".mft") {
+                self.add_manifest(uri, content);
+            }
+        }
+        Ok(res)
+    }
+}
+
+
+//------------ Failure -------------------------------------------------------
+
+/// Retrieving an object via IPFS has failed.
+#[derive(Debug)]
+enum Failure {
+    /// The DAG is broken.
+    Dag(DagError),
+
+    /// All gateways failed.
+    Pool(PoolError),
+}
+
+impl From<DagError> for Failure {
+    fn from(err: DagError) -> Self {
+        Failure::Dag(err)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Failure::Dag(ref err) => err.fmt(f),
+            Failure::Pool(ref err) => err.fmt(f),
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::str::FromStr;
+    use crate::validation::Validator;
+    use crate::validation::test::{ripe_source, ripe_ta};
+    use super::super::dag::{Dag, DagBuilder};
+    use super::*;
+
+    /// A block source serving a DAG via the first gateway only.
+    struct DagSource {
+        dag: Dag,
+        requests: Cell<usize>,
+    }
+
+    impl BlockSource for DagSource {
+        fn block(
+            &self, gateway: &Gateway, cid: &Cid
+        ) -> Result<Bytes, FetchError> {
+            self.requests.set(self.requests.get() + 1);
+            if gateway.base() != "https://one.example" {
+                return Err(FetchError::Timeout)
+            }
+            self.dag.get(cid).cloned().ok_or_else(|| {
+                FetchError::http_status(404, None)
+            })
+        }
+    }
+
+    fn uri(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    fn ipfs_fetcher(
+        objects: &HashMap<uri::Rsync, Bytes>,
+    ) -> Fetcher<DagSource, HashMap<uri::Rsync, Bytes>> {
+        let mut builder = DagBuilder::new();
+        for (uri, content) in objects {
+            builder.insert_rsync(uri, content.clone()).unwrap();
+        }
+        let dag = builder.build();
+        let root = dag.root();
+        let mut res = Fetcher::new(
+            DagSource { dag, requests: Cell::new(0) },
+            GatewayPool::new(vec![
+                Gateway::http("https://two.example"),
+                Gateway::http("https://one.example"),
+            ]),
+            ripe_source()
+        );
+        res.set_root(
+            uri("rsync://rpki.ripe.net/repository/").to_module(), root
+        );
+        res.set_now(Some(Time::utc(2019, 4, 6, 12, 0, 0)));
+        res
+    }
+
+    #[test]
+    fn get() {
+        let crl = uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl");
+        let mft = uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.mft");
+        let source = ripe_source();
+
+        // Everything is available via IPFS.
+        let fetcher = ipfs_fetcher(&source);
+        assert_eq!(fetcher.get(&mft).unwrap(), source.get(&mft).cloned());
+        assert!(fetcher.blocks.requests.get() > 0);
+        assert_eq!(fetcher.get(&crl).unwrap(), source.get(&crl).cloned());
+        assert_eq!(
+            fetcher.get(&uri("rsync://rpki.ripe.net/repository/none.roa"))
+                .unwrap(),
+            None
+        );
+
+        // A forged CRL in IPFS is replaced by the fallback’s.
+        let mut forged = source.clone();
+        forged.insert(crl.clone(), Bytes::from_static(b"forged"));
+        let fetcher = ipfs_fetcher(&forged);
+        assert_eq!(fetcher.get(&mft).unwrap(), source.get(&mft).cloned());
+        assert_eq!(fetcher.get(&crl).unwrap(), source.get(&crl).cloned());
+
+        // A missing object is taken from the fallback.
+        let mut missing = source.clone();
+        missing.remove(&crl);
+        let fetcher = ipfs_fetcher(&missing);
+        assert_eq!(fetcher.get(&crl).unwrap(), source.get(&crl).cloned());
+
+        // A stale manifest is taken from the fallback, too.
+        let mut fetcher = ipfs_fetcher(&source);
+        fetcher.set_now(Some(Time::utc(2030, 1, 1, 0, 0, 0)));
+        fetcher.blocks.requests.set(0);
+        assert_eq!(fetcher.get(&mft).unwrap(), source.get(&mft).cloned());
+        assert!(fetcher.blocks.requests.get() > 0);
+
+        // Modules without a root aren’t tried via IPFS.
+        fetcher.remove_root(mft.module());
+        fetcher.blocks.requests.set(0);
+        assert_eq!(fetcher.get(&mft).unwrap(), source.get(&mft).cloned());
+        assert_eq!(fetcher.blocks.requests.get(), 0);
+    }
+
+    #[test]
+    fn validate() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(ripe_source());
+        validator.set_now(at);
+        let expected = validator.validate_ta(ripe_ta(at)).unwrap();
+
+        let mut validator = Validator::new(ipfs_fetcher(&ripe_source()));
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), expected.valid_points());
+        assert_eq!(outcome.failures(), expected.failures());
+    }
+}
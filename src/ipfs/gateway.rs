@@ -0,0 +1,389 @@
+//! Pools of IPFS gateways with failover.
+//!
+//! Content published via IPFS is typically retrieved through an HTTP
+//! gateway or the HTTP API of an IPFS node. Public gateways are notoriously
+//! unreliable, so a relying party should be able to use several of them
+//! and move on to the next one if a gateway fails.
+//!
+//! The [`GatewayPool`] provides this. It holds a list of [`Gateway`]s in
+//! order of preference and keeps track of their health using a
+//! [`HealthTracker`]. Each fetch is tried with the gateways that aren’t
+//! currently backing off, in order, until one succeeds. Gateways can be
+//! probed proactively via [`GatewayPool::health_check`] by fetching a block
+//! every IPFS node can provide.
+//!
+//! The pool does not perform any network operations itself. Instead, the
+//! actual request is provided by the caller as a closure, allowing the
+//! pool to be used with any HTTP client.
+//!
+//! [`GatewayPool`]: struct.GatewayPool.html
+//! [`Gateway`]: struct.Gateway.html
+//! [`HealthTracker`]: ../../fetch/struct.HealthTracker.html
+//! [`GatewayPool::health_check`]: struct.GatewayPool.html#method.health_check
+
+use std::{error, fmt, slice};
+use crate::cid::Cid;
+use crate::fetch::{FetchError, HealthTracker, RepositoryHealth};
+use crate::x509::Time;
+use super::dag::DagBuilder;
+
+
+//------------ Gateway -------------------------------------------------------
+
+/// A single IPFS gateway or API endpoint.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Gateway {
+    /// The base URL without a trailing slash.
+    base: String,
+
+    /// The kind of interface offered at the base URL.
+    kind: GatewayKind,
+}
+
+impl Gateway {
+    /// Creates a new gateway from a base URL and the kind of interface.
+    ///
+    /// The base URL should include the scheme and may include a path.
+    pub fn new(base: &str, kind: GatewayKind) -> Self {
+        Gateway { base: base.trim_end_matches('/').into(), kind }
+    }
+
+    /// Creates a new HTTP gateway.
+    pub fn http(base: &str) -> Self {
+        Self::new(base, GatewayKind::Http)
+    }
+
+    /// Creates a new endpoint for the HTTP API of an IPFS node.
+    pub fn api(base: &str) -> Self {
+        Self::new(base, GatewayKind::Api)
+    }
+
+    /// Returns the base URL of the gateway.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// Returns the kind of interface offered by the gateway.
+    pub fn kind(&self) -> GatewayKind {
+        self.kind
+    }
+
+    /// Returns the URL for retrieving the raw block with the given CID.
+    ///
+    /// The content returned by the URL should be checked against the CID.
+    pub fn block_url(&self, cid: &Cid) -> String {
+        match self.kind {
+            GatewayKind::Http => {
+                format!("{}/ipfs/{}?format=raw", self.base, cid)
+            }
+            GatewayKind::Api => {
+                format!("{}/api/v0/block/get?arg={}", self.base, cid)
+            }
+        }
+    }
+
+    /// Returns the URL for resolving the given IPNS name.
+    pub fn ipns_url(&self, name: &str) -> String {
+        match self.kind {
+            GatewayKind::Http => format!("{}/ipns/{}", self.base, name),
+            GatewayKind::Api => {
+                format!("{}/api/v0/name/resolve?arg={}", self.base, name)
+            }
+        }
+    }
+
+    /// Returns the URL for retrieving the IPNS record for the given name.
+    ///
+    /// The HTTP API expects a POST request for this URL. Gateways return
+    /// the record if it is requested with the content type
+    /// `application/vnd.ipfs.ipns-record`.
+    pub fn ipns_record_url(&self, name: &str) -> String {
+        match self.kind {
+            GatewayKind::Http => {
+                format!("{}/ipns/{}?format=ipns-record", self.base, name)
+            }
+            GatewayKind::Api => {
+                format!(
+                    "{}/api/v0/routing/get?arg=/ipns/{}", self.base, name
+                )
+            }
+        }
+    }
+
+    /// Returns the URL for recursively pinning the given CID.
+    ///
+    /// Returns `None` for HTTP gateways as they don’t allow pinning.
+    pub fn pin_add_url(&self, cid: &Cid) -> Option<String> {
+        match self.kind {
+            GatewayKind::Http => None,
+            GatewayKind::Api => {
+                Some(format!(
+                    "{}/api/v0/pin/add?arg={}&recursive=true", self.base, cid
+                ))
+            }
+        }
+    }
+
+    /// Returns the URL for removing the pin of the given CID.
+    ///
+    /// Returns `None` for HTTP gateways as they don’t allow pinning.
+    pub fn pin_rm_url(&self, cid: &Cid) -> Option<String> {
+        match self.kind {
+            GatewayKind::Http => None,
+            GatewayKind::Api => {
+                Some(format!("{}/api/v0/pin/rm?arg={}", self.base, cid))
+            }
+        }
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Gateway {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.base)
+    }
+}
+
+
+//------------ GatewayKind ---------------------------------------------------
+
+/// The kind of interface offered by a gateway.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum GatewayKind {
+    /// A read-only HTTP gateway serving `/ipfs/` and `/ipns/` paths.
+    Http,
+
+    /// The HTTP API of an IPFS node under `/api/v0/`.
+    Api,
+}
+
+
+//------------ GatewayPool ---------------------------------------------------
+
+/// A list of gateways used with automatic failover.
+#[derive(Clone, Debug)]
+pub struct GatewayPool {
+    /// The gateways in order of preference.
+    gateways: Vec<Gateway>,
+
+    /// The health of the gateways, keyed by their base URL.
+    health: HealthTracker,
+}
+
+impl GatewayPool {
+    /// Creates a new pool from a list of gateways.
+    ///
+    /// The gateways are tried in the given order. Failing gateways are
+    /// backed off using the default parameters of `HealthTracker`.
+    pub fn new(gateways: Vec<Gateway>) -> Self {
+        Self::with_tracker(gateways, HealthTracker::new())
+    }
+
+    /// Creates a new pool using the given health tracker.
+    pub fn with_tracker(
+        gateways: Vec<Gateway>, health: HealthTracker
+    ) -> Self {
+        GatewayPool { gateways, health }
+    }
+
+    /// Returns an iterator over all gateways in order of preference.
+    pub fn gateways(&self) -> slice::Iter<'_, Gateway> {
+        self.gateways.iter()
+    }
+
+    /// Returns the health information for a gateway.
+    ///
+    /// Returns `None` if the gateway hasn’t been used yet.
+    pub fn health(&self, gateway: &Gateway) -> Option<&RepositoryHealth> {
+        self.health.get(gateway.base())
+    }
+
+    /// Returns the gateways to try at the given time in order.
+    ///
+    /// These are all gateways that are not currently backing off in order
+    /// of preference. If all gateways are backing off, all of them are
+    /// returned ordered by the time they will become available again so
+    /// that fetching never stalls entirely.
+    pub fn candidates(&self, now: Time) -> Vec<&Gateway> {
+        let res: Vec<_> = self.gateways.iter().filter(|gateway| {
+            self.health.may_fetch(gateway.base(), now)
+        }).collect();
+        if !res.is_empty() {
+            return res
+        }
+        let mut res: Vec<_> = self.gateways.iter().collect();
+        res.sort_by_key(|gateway| {
+            self.health.get(gateway.base()).and_then(|health| {
+                health.next_attempt()
+            })
+        });
+        res
+    }
+
+    /// Performs a fetch operation with failover.
+    ///
+    /// The operation `op` is called with the candidate gateways in order
+    /// until it succeeds. The outcome of each attempt is recorded with the
+    /// gateway’s health. Since the problem may well be with the gateway,
+    /// content errors such as a hash mismatch lead to the next gateway
+    /// being tried, too.
+    ///
+    /// If all gateways fail, the errors of all attempts are returned.
+    pub fn fetch<T, F>(&mut self, now: Time, mut op: F) -> Result<T, PoolError>
+    where F: FnMut(&Gateway) -> Result<T, FetchError> {
+        let candidates: Vec<_> = self.candidates(now).into_iter()
+            .cloned().collect();
+        let mut attempts = Vec::new();
+        for gateway in candidates {
+            match op(&gateway) {
+                Ok(res) => {
+                    self.health.success(gateway.base(), now);
+                    return Ok(res)
+                }
+                Err(err) => {
+                    self.health.failure(gateway.base(), &err, now);
+                    attempts.push((gateway, err));
+                }
+            }
+        }
+        Err(PoolError { attempts })
+    }
+
+    /// Probes all gateways.
+    ///
+    /// The probe `op` is called for every gateway, regardless of whether
+    /// it is currently backing off, and the outcome is recorded with the
+    /// gateway’s health. Typically, the probe fetches the block identified
+    /// by [`probe_cid`] and checks that it matches.
+    ///
+    /// Returns the number of gateways that passed the probe.
+    ///
+    /// [`probe_cid`]: fn.probe_cid.html
+    pub fn health_check<F>(&mut self, now: Time, mut op: F) -> usize
+    where F: FnMut(&Gateway) -> Result<(), FetchError> {
+        let mut res = 0;
+        for gateway in &self.gateways {
+            match op(gateway) {
+                Ok(()) => {
+                    self.health.success(gateway.base(), now);
+                    res += 1;
+                }
+                Err(err) => {
+                    self.health.failure(gateway.base(), &err, now);
+                }
+            }
+        }
+        res
+    }
+}
+
+
+//------------ probe_cid -----------------------------------------------------
+
+/// Returns the CID of a block every IPFS node can provide.
+///
+/// This is the CID of an empty UnixFS directory which is part of every
+/// node’s default content.
+pub fn probe_cid() -> Cid {
+    DagBuilder::new().build().root()
+}
+
+
+//------------ PoolError -----------------------------------------------------
+
+/// All gateways of a pool failed.
+#[derive(Clone, Debug)]
+pub struct PoolError {
+    /// The gateways tried and the errors they produced.
+    attempts: Vec<(Gateway, FetchError)>,
+}
+
+impl PoolError {
+    /// Returns an iterator over the gateways tried and their errors.
+    pub fn attempts(&self) -> slice::Iter<'_, (Gateway, FetchError)> {
+        self.attempts.iter()
+    }
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.attempts.is_empty() {
+            return f.write_str("no gateways available")
+        }
+        f.write_str("all gateways failed")?;
+        for (gateway, err) in &self.attempts {
+            write!(f, "; {}: {}", gateway, err)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for PoolError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use super::*;
+
+    #[test]
+    fn urls() {
+        let cid = probe_cid();
+        assert_eq!(
+            Gateway::http("https://ipfs.io/").block_url(&cid),
+            "https://ipfs.io/ipfs/\
+             QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn?format=raw"
+        );
+        assert_eq!(
+            Gateway::api("http://127.0.0.1:5001").ipns_url("k51"),
+            "http://127.0.0.1:5001/api/v0/name/resolve?arg=k51"
+        );
+        assert_eq!(Gateway::http("https://ipfs.io").pin_rm_url(&cid), None);
+        assert_eq!(
+            Gateway::http("https://ipfs.io").ipns_record_url("k51"),
+            "https://ipfs.io/ipns/k51?format=ipns-record"
+        );
+        assert_eq!(
+            Gateway::api("http://127.0.0.1:5001").ipns_record_url("k51"),
+            "http://127.0.0.1:5001/api/v0/routing/get?arg=/ipns/k51"
+        );
+    }
+
+    #[test]
+    fn failover() {
+        let one = Gateway::http("https://one.example");
+        let two = Gateway::http("https://two.example");
+        let mut pool = GatewayPool::new(vec![one.clone(), two.clone()]);
+        let now = Time::utc(2021, 1, 1, 0, 0, 0);
+
+        // The first gateway times out, so the second one is used.
+        let res = pool.fetch(now, |gateway| {
+            if *gateway == one { Err(FetchError::Timeout) }
+            else { Ok(gateway.base().to_string()) }
+        });
+        assert_eq!(res.unwrap(), "https://two.example");
+        assert_eq!(pool.health(&one).unwrap().metrics().failures(), 1);
+        assert_eq!(pool.health(&two).unwrap().metrics().successes(), 1);
+
+        // While the first gateway backs off, only the second is tried.
+        assert_eq!(pool.candidates(now), [&two]);
+        let later = now + Duration::minutes(2);
+        assert_eq!(pool.candidates(later), [&one, &two]);
+
+        // If both fail, all errors are reported and the gateway backing
+        // off for the shortest time comes first.
+        let err = pool.fetch(now, |_| -> Result<(), _> {
+            Err(FetchError::Timeout)
+        }).unwrap_err();
+        assert_eq!(err.attempts().count(), 1);
+        assert_eq!(pool.candidates(now), [&one, &two]);
+
+        // A successful health check clears the backoff.
+        assert_eq!(pool.health_check(now, |_| Ok(())), 2);
+        assert_eq!(pool.candidates(now), [&one, &two]);
+    }
+}
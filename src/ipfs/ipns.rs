@@ -0,0 +1,644 @@
+//! IPNS records.
+//!
+//! An IPNS name is the hash of a public key. The content currently
+//! published under the name is announced through an IPNS record signed
+//! with the corresponding private key. The record contains the path of
+//! the content, a sequence number that increases with every update, an
+//! end of validity, and a time-to-live for caching.
+//!
+//! This module provides [`IpnsRecord`] which decodes a record as defined
+//! in the [IPNS record specification] and verifies that it was signed by
+//! the key of the name it was retrieved for. Only records with a version 2
+//! signature are accepted. Ed25519 and RSA keys are supported.
+//!
+//! [`IpnsRecord`]: struct.IpnsRecord.html
+//! [IPNS record specification]: https://specs.ipfs.tech/ipns/ipns-record/
+
+use std::{error, fmt, str};
+use std::time::Duration;
+use bytes::Bytes;
+use ring::signature;
+use crate::cid;
+use crate::cid::{Cid, Multihash, read_varint};
+use crate::crypto::PublicKey;
+use crate::x509::Time;
+use super::dag::{Field, read_field};
+
+
+//------------ IpnsRecord ----------------------------------------------------
+
+/// A verified IPNS record.
+#[derive(Clone, Debug)]
+pub struct IpnsRecord {
+    /// The root CID of the published content.
+    value: Cid,
+
+    /// The end of validity of the record.
+    validity: Time,
+
+    /// The sequence number of the record.
+    sequence: u64,
+
+    /// The time-to-live of the record.
+    ttl: Duration,
+
+    /// The protobuf encoded libp2p public key of the name.
+    public_key: Bytes,
+}
+
+impl IpnsRecord {
+    /// The maximum size of an encoded record.
+    pub const MAX_SIZE: usize = 10 * 1024;
+
+    /// Decodes and verifies a record retrieved for the IPNS name `name`.
+    ///
+    /// The name can be given as a base58btc encoded multihash or as a
+    /// version 1 CID with the libp2p-key content type in any of the
+    /// multibase encodings supported by [`Cid`].
+    ///
+    /// The record is only accepted if its version 2 signature was made
+    /// with the key identified by the name and its value is a plain
+    /// `/ipfs/` path to a CID. The end of validity is not checked. Use
+    /// [`validate_at`] for that.
+    ///
+    /// [`Cid`]: ../../cid/struct.Cid.html
+    /// [`validate_at`]: #method.validate_at
+    pub fn decode(name: &str, record: &[u8]) -> Result<Self, IpnsError> {
+        if record.len() > Self::MAX_SIZE {
+            return Err(IpnsError::TooLarge)
+        }
+        let fields = ProtoFields::decode(record)?;
+        let public_key = name_key(name, fields.public_key)?;
+        let (signature, data) = match (fields.signature, fields.data) {
+            (Some(signature), Some(data)) => (signature, data),
+            _ => return Err(IpnsError::BadSignature)
+        };
+        let mut message = Vec::with_capacity(
+            SIGNATURE_PREFIX.len() + data.len()
+        );
+        message.extend_from_slice(SIGNATURE_PREFIX);
+        message.extend_from_slice(data);
+        verify_signature(&public_key, &message, signature)?;
+
+        let data = CborFields::decode(data)?;
+        fields.check_legacy(&data)?;
+        if data.validity_type != Some(0) {
+            return Err(IpnsError::Malformed)
+        }
+        let value = data.value.ok_or(IpnsError::Malformed)?;
+        if !value.starts_with(b"/ipfs/") {
+            return Err(IpnsError::BadValue)
+        }
+        let value = str::from_utf8(&value[6..]).ok().and_then(|s| {
+            s.parse::<Cid>().ok()
+        }).ok_or(IpnsError::BadValue)?;
+        let validity = str::from_utf8(
+            data.validity.ok_or(IpnsError::Malformed)?
+        ).ok().and_then(|s| s.parse::<Time>().ok()).ok_or(
+            IpnsError::Malformed
+        )?;
+        Ok(IpnsRecord {
+            value,
+            validity,
+            sequence: data.sequence.ok_or(IpnsError::Malformed)?,
+            ttl: Duration::from_nanos(data.ttl.unwrap_or(0)),
+            public_key: public_key.into(),
+        })
+    }
+
+    /// Returns the root CID of the published content.
+    pub fn value(&self) -> Cid {
+        self.value
+    }
+
+    /// Returns the end of validity of the record.
+    pub fn validity(&self) -> Time {
+        self.validity
+    }
+
+    /// Returns the sequence number of the record.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns the time-to-live given in the record.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Returns the protobuf encoded public key the record was signed with.
+    pub fn public_key(&self) -> &Bytes {
+        &self.public_key
+    }
+
+    /// Checks that the record is still valid at the given time.
+    pub fn validate_at(&self, now: Time) -> Result<(), IpnsError> {
+        if *now > *self.validity {
+            Err(IpnsError::Expired)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Returns how long the record may be cached at the given time.
+    ///
+    /// This is the record’s time-to-live but no longer than the remaining
+    /// validity.
+    pub fn ttl_at(&self, now: Time) -> Duration {
+        let remaining = (*self.validity - *now).to_std().unwrap_or_default();
+        self.ttl.min(remaining)
+    }
+}
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The prefix of the message signed by a version 2 signature.
+const SIGNATURE_PREFIX: &[u8] = b"ipns-signature:";
+
+/// The multihash code for the identity hash.
+const IDENTITY: u64 = 0x00;
+
+/// The libp2p key type for RSA keys.
+const KEY_TYPE_RSA: u64 = 0;
+
+/// The libp2p key type for Ed25519 keys.
+const KEY_TYPE_ED25519: u64 = 1;
+
+
+//------------ ProtoFields ---------------------------------------------------
+
+/// The fields of the protobuf encoded `IpnsEntry` we care about.
+#[derive(Default)]
+struct ProtoFields<'a> {
+    value: Option<&'a [u8]>,
+    validity_type: Option<u64>,
+    validity: Option<&'a [u8]>,
+    sequence: Option<u64>,
+    ttl: Option<u64>,
+    public_key: Option<&'a [u8]>,
+    signature: Option<&'a [u8]>,
+    data: Option<&'a [u8]>,
+}
+
+impl<'a> ProtoFields<'a> {
+    fn decode(mut record: &'a [u8]) -> Result<Self, IpnsError> {
+        let mut res = ProtoFields::default();
+        while !record.is_empty() {
+            match read_field(&mut record)
+                .map_err(|_| IpnsError::Malformed)?
+            {
+                (1, Field::Bytes(value)) => res.value = Some(value),
+                (3, Field::Varint(value)) => res.validity_type = Some(value),
+                (4, Field::Bytes(value)) => res.validity = Some(value),
+                (5, Field::Varint(value)) => res.sequence = Some(value),
+                (6, Field::Varint(value)) => res.ttl = Some(value),
+                (7, Field::Bytes(value)) => res.public_key = Some(value),
+                (8, Field::Bytes(value)) => res.signature = Some(value),
+                (9, Field::Bytes(value)) => res.data = Some(value),
+                _ => { }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Checks that legacy fields present agree with the signed data.
+    fn check_legacy(&self, data: &CborFields) -> Result<(), IpnsError> {
+        fn check<T: PartialEq>(
+            legacy: Option<T>, data: Option<T>
+        ) -> Result<(), IpnsError> {
+            if legacy.is_some() && legacy != data {
+                Err(IpnsError::Malformed)
+            }
+            else {
+                Ok(())
+            }
+        }
+
+        check(self.value, data.value)?;
+        check(self.validity_type, data.validity_type)?;
+        check(self.validity, data.validity)?;
+        check(self.sequence, data.sequence)?;
+        check(self.ttl, data.ttl)
+    }
+}
+
+
+//------------ CborFields ----------------------------------------------------
+
+/// The fields of the signed DAG-CBOR data of a record.
+#[derive(Default)]
+struct CborFields<'a> {
+    value: Option<&'a [u8]>,
+    validity_type: Option<u64>,
+    validity: Option<&'a [u8]>,
+    sequence: Option<u64>,
+    ttl: Option<u64>,
+}
+
+impl<'a> CborFields<'a> {
+    /// The maximum nesting depth of values we skip over.
+    const MAX_DEPTH: usize = 16;
+
+    fn decode(mut data: &'a [u8]) -> Result<Self, IpnsError> {
+        let mut res = CborFields::default();
+        let len = match read_head(&mut data)? {
+            (5, len) => len,
+            _ => return Err(IpnsError::Malformed)
+        };
+        for _ in 0..len {
+            let key = match read_head(&mut data)? {
+                (3, len) => read_slice(&mut data, len)?,
+                _ => return Err(IpnsError::Malformed)
+            };
+            match key {
+                b"Value" => res.value = Some(read_bytes(&mut data)?),
+                b"Validity" => res.validity = Some(read_bytes(&mut data)?),
+                b"ValidityType" => {
+                    res.validity_type = Some(read_uint(&mut data)?)
+                }
+                b"Sequence" => res.sequence = Some(read_uint(&mut data)?),
+                b"TTL" => res.ttl = Some(read_uint(&mut data)?),
+                _ => skip_item(&mut data, Self::MAX_DEPTH)?
+            }
+        }
+        if !data.is_empty() {
+            return Err(IpnsError::Malformed)
+        }
+        Ok(res)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns the public key for an IPNS name.
+///
+/// If the name is the identity hash of the key, the key is taken from the
+/// name. Otherwise the key must be included in the record.
+fn name_key(
+    name: &str, record_key: Option<&[u8]>
+) -> Result<Vec<u8>, IpnsError> {
+    let bytes = if name.starts_with("Qm") || name.starts_with('1') {
+        cid::base58_decode(name.as_bytes())?
+    }
+    else {
+        let bytes = cid::multibase_decode(name)?;
+        let mut bytes = bytes.as_slice();
+        if read_varint(&mut bytes)? != 1
+            || read_varint(&mut bytes)? != Cid::LIBP2P_KEY
+        {
+            return Err(IpnsError::BadName)
+        }
+        bytes.into()
+    };
+    let mut bytes = bytes.as_slice();
+    let code = read_varint(&mut bytes)?;
+    if read_varint(&mut bytes)? != bytes.len() as u64 {
+        return Err(IpnsError::BadName)
+    }
+    match code {
+        IDENTITY => {
+            match record_key {
+                Some(key) if key != bytes => Err(IpnsError::BadSignature),
+                _ => Ok(bytes.into())
+            }
+        }
+        Multihash::SHA2_256 => {
+            let key = record_key.ok_or(IpnsError::BadSignature)?;
+            if Multihash::sha256(key).digest()[..] == *bytes {
+                Ok(key.into())
+            }
+            else {
+                Err(IpnsError::BadSignature)
+            }
+        }
+        _ => Err(IpnsError::BadName)
+    }
+}
+
+/// Verifies a signature with a protobuf encoded libp2p public key.
+fn verify_signature(
+    key: &[u8], message: &[u8], signature: &[u8]
+) -> Result<(), IpnsError> {
+    let mut key = key;
+    let mut key_type = None;
+    let mut data = None;
+    while !key.is_empty() {
+        match read_field(&mut key).map_err(|_| IpnsError::Malformed)? {
+            (1, Field::Varint(value)) => key_type = Some(value),
+            (2, Field::Bytes(value)) => data = Some(value),
+            _ => return Err(IpnsError::Malformed)
+        }
+    }
+    let data = data.ok_or(IpnsError::Malformed)?;
+    let res = match key_type {
+        Some(KEY_TYPE_ED25519) => {
+            signature::UnparsedPublicKey::new(
+                &signature::ED25519, data
+            ).verify(message, signature)
+        }
+        Some(KEY_TYPE_RSA) => {
+            let key = PublicKey::decode(data).map_err(|_| {
+                IpnsError::Malformed
+            })?;
+            if !key.is_rsa() {
+                return Err(IpnsError::Malformed)
+            }
+            signature::UnparsedPublicKey::new(
+                &signature::RSA_PKCS1_2048_8192_SHA256, key.bits()
+            ).verify(message, signature)
+        }
+        _ => return Err(IpnsError::UnsupportedKey)
+    };
+    res.map_err(|_| IpnsError::BadSignature)
+}
+
+/// Reads the head of a CBOR item.
+///
+/// Returns the major type and the argument.
+fn read_head(data: &mut &[u8]) -> Result<(u8, u64), IpnsError> {
+    let (&first, rest) = data.split_first().ok_or(IpnsError::Malformed)?;
+    *data = rest;
+    let len = match first & 0x1f {
+        info @ 0..=23 => return Ok((first >> 5, u64::from(info))),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        _ => return Err(IpnsError::Malformed)
+    };
+    let value = read_slice(data, len)?.iter().fold(0u64, |res, &byte| {
+        res << 8 | u64::from(byte)
+    });
+    Ok((first >> 5, value))
+}
+
+/// Reads `len` octets.
+fn read_slice<'a>(
+    data: &mut &'a [u8], len: u64
+) -> Result<&'a [u8], IpnsError> {
+    if len > data.len() as u64 {
+        return Err(IpnsError::Malformed)
+    }
+    let (res, rest) = data.split_at(len as usize);
+    *data = rest;
+    Ok(res)
+}
+
+/// Reads a CBOR byte string.
+fn read_bytes<'a>(data: &mut &'a [u8]) -> Result<&'a [u8], IpnsError> {
+    match read_head(data)? {
+        (2, len) => read_slice(data, len),
+        _ => Err(IpnsError::Malformed)
+    }
+}
+
+/// Reads a CBOR unsigned integer.
+fn read_uint(data: &mut &[u8]) -> Result<u64, IpnsError> {
+    match read_head(data)? {
+        (0, value) => Ok(value),
+        _ => Err(IpnsError::Malformed)
+    }
+}
+
+/// Skips over a CBOR item of any type.
+fn skip_item(data: &mut &[u8], depth: usize) -> Result<(), IpnsError> {
+    let depth = depth.checked_sub(1).ok_or(IpnsError::Malformed)?;
+    match read_head(data)? {
+        (0, _) | (1, _) | (7, _) => Ok(()),
+        (2, len) | (3, len) => read_slice(data, len).map(|_| ()),
+        (4, len) => {
+            for _ in 0..len {
+                skip_item(data, depth)?
+            }
+            Ok(())
+        }
+        (5, len) => {
+            for _ in 0..len {
+                skip_item(data, depth)?;
+                skip_item(data, depth)?;
+            }
+            Ok(())
+        }
+        _ => skip_item(data, depth)
+    }
+}
+
+
+//------------ IpnsError -----------------------------------------------------
+
+/// An IPNS record was not acceptable.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IpnsError {
+    /// The record is larger than allowed.
+    TooLarge,
+
+    /// The record is not correctly encoded.
+    Malformed,
+
+    /// The IPNS name is not a valid key hash.
+    BadName,
+
+    /// The key of the name is of an unsupported type.
+    UnsupportedKey,
+
+    /// The signature is missing or wasn’t made with the key of the name.
+    BadSignature,
+
+    /// The record’s value is not an `/ipfs/` path to a CID.
+    BadValue,
+
+    /// The record is past its end of validity.
+    Expired,
+}
+
+impl From<cid::Error> for IpnsError {
+    fn from(_: cid::Error) -> Self {
+        IpnsError::BadName
+    }
+}
+
+impl fmt::Display for IpnsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            IpnsError::TooLarge => "IPNS record too large",
+            IpnsError::Malformed => "malformed IPNS record",
+            IpnsError::BadName => "invalid IPNS name",
+            IpnsError::UnsupportedKey => "unsupported IPNS key type",
+            IpnsError::BadSignature => "invalid IPNS record signature",
+            IpnsError::BadValue => "IPNS record value is not a CID",
+            IpnsError::Expired => "IPNS record expired",
+        })
+    }
+}
+
+impl error::Error for IpnsError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+pub(crate) mod test {
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+    use crate::cid::base58_encode;
+    use super::super::dag::{write_bytes, write_uint};
+    use super::*;
+
+    /// Appends the head of a CBOR item.
+    fn write_head(major: u8, value: u64, target: &mut Vec<u8>) {
+        let major = major << 5;
+        if value < 24 {
+            target.push(major | value as u8)
+        }
+        else if value < 0x100 {
+            target.push(major | 24);
+            target.push(value as u8);
+        }
+        else if value < 0x10000 {
+            target.push(major | 25);
+            target.extend_from_slice(&(value as u16).to_be_bytes());
+        }
+        else if value < 0x1_0000_0000 {
+            target.push(major | 26);
+            target.extend_from_slice(&(value as u32).to_be_bytes());
+        }
+        else {
+            target.push(major | 27);
+            target.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+
+    fn write_cbor_key(key: &str, target: &mut Vec<u8>) {
+        write_head(3, key.len() as u64, target);
+        target.extend_from_slice(key.as_bytes());
+    }
+
+    /// A test key with its IPNS name.
+    pub(crate) struct TestKey {
+        pair: Ed25519KeyPair,
+        public_key: Vec<u8>,
+    }
+
+    impl TestKey {
+        pub(crate) fn new(seed: u8) -> Self {
+            let pair = Ed25519KeyPair::from_seed_unchecked(
+                &[seed; 32]
+            ).unwrap();
+            let mut public_key = Vec::new();
+            write_uint(1, KEY_TYPE_ED25519, &mut public_key);
+            write_bytes(2, pair.public_key().as_ref(), &mut public_key);
+            TestKey { pair, public_key }
+        }
+
+        /// Returns the name as a base58btc encoded identity multihash.
+        pub(crate) fn name(&self) -> String {
+            let mut hash = vec![0, self.public_key.len() as u8];
+            hash.extend_from_slice(&self.public_key);
+            base58_encode(&hash)
+        }
+
+        /// Returns the name as a base58btc encoded SHA-256 multihash.
+        fn hashed_name(&self) -> String {
+            Cid::v0(Multihash::sha256(&self.public_key)).to_string()
+        }
+
+        /// Creates a signed record.
+        pub(crate) fn record(
+            &self, value: &str, validity: &str, sequence: u64, ttl: u64,
+        ) -> Vec<u8> {
+            let mut data = Vec::new();
+            write_head(5, 5, &mut data);
+            write_cbor_key("TTL", &mut data);
+            write_head(0, ttl, &mut data);
+            write_cbor_key("Value", &mut data);
+            write_head(2, value.len() as u64, &mut data);
+            data.extend_from_slice(value.as_bytes());
+            write_cbor_key("Sequence", &mut data);
+            write_head(0, sequence, &mut data);
+            write_cbor_key("Validity", &mut data);
+            write_head(2, validity.len() as u64, &mut data);
+            data.extend_from_slice(validity.as_bytes());
+            write_cbor_key("ValidityType", &mut data);
+            write_head(0, 0, &mut data);
+
+            let mut message = SIGNATURE_PREFIX.to_vec();
+            message.extend_from_slice(&data);
+            let signature = self.pair.sign(&message);
+
+            let mut res = Vec::new();
+            write_bytes(1, value.as_bytes(), &mut res);
+            write_uint(5, sequence, &mut res);
+            write_bytes(7, &self.public_key, &mut res);
+            write_bytes(8, signature.as_ref(), &mut res);
+            write_bytes(9, &data, &mut res);
+            res
+        }
+    }
+
+    const ROOT: &str = "QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn";
+
+    #[test]
+    fn decode() {
+        let key = TestKey::new(1);
+        assert!(key.name().starts_with("12D3KooW"));
+        let data = key.record(
+            &format!("/ipfs/{}", ROOT), "2020-01-01T00:00:00.000000000Z",
+            12, 3_600_000_000_000
+        );
+        let record = IpnsRecord::decode(&key.name(), &data).unwrap();
+        assert_eq!(record.value().to_string(), ROOT);
+        assert_eq!(record.sequence(), 12);
+        assert_eq!(record.ttl(), Duration::from_secs(3600));
+        assert_eq!(record.validity(), Time::utc(2020, 1, 1, 0, 0, 0));
+        assert!(record.validate_at(Time::utc(2019, 12, 31, 0, 0, 0)).is_ok());
+        assert_eq!(
+            record.validate_at(Time::utc(2020, 1, 2, 0, 0, 0)),
+            Err(IpnsError::Expired)
+        );
+        assert_eq!(
+            record.ttl_at(Time::utc(2019, 12, 31, 23, 59, 0)),
+            Duration::from_secs(60)
+        );
+        assert!(IpnsRecord::decode(&key.hashed_name(), &data).is_ok());
+
+        // The same name in CID form.
+        let mut cid = vec![1, Cid::LIBP2P_KEY as u8];
+        cid.extend_from_slice(
+            &cid::base58_decode(key.name().as_bytes()).unwrap()
+        );
+        let cid = format!("z{}", base58_encode(&cid));
+        assert!(IpnsRecord::decode(&cid, &data).is_ok());
+
+        // Another key’s name.
+        assert_eq!(
+            IpnsRecord::decode(&TestKey::new(2).name(), &data).unwrap_err(),
+            IpnsError::BadSignature
+        );
+        assert_eq!(
+            IpnsRecord::decode(&TestKey::new(2).hashed_name(), &data)
+                .unwrap_err(),
+            IpnsError::BadSignature
+        );
+
+        // Tampered data.
+        let mut bad = data.clone();
+        let len = bad.len();
+        bad[len - 5] ^= 0x01;
+        assert!(IpnsRecord::decode(&key.name(), &bad).is_err());
+
+        // Not a CID.
+        let data = key.record(
+            "/ipns/example.net", "2020-01-01T00:00:00Z", 12, 0
+        );
+        assert_eq!(
+            IpnsRecord::decode(&key.name(), &data).unwrap_err(),
+            IpnsError::BadValue
+        );
+        assert_eq!(
+            IpnsRecord::decode("foo", &data).unwrap_err(),
+            IpnsError::BadName
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Publishing RPKI repositories via IPFS.
+//!
+//! In addition to rsync and RRDP, RPKI repositories can be published via
+//! IPFS. The content of a repository is placed into a UnixFS directory tree
+//! that mirrors the path of the rsync URIs of the objects. The root of this
+//! tree is then published under an IPNS name given in an [`uri::Ipns`].
+//!
+//! In order for independent publishers to arrive at the same root CID for
+//! the same set of objects, the mapping from objects to the UnixFS DAG must
+//! be fully deterministic. The sub-module [`dag`] implements this mapping.
+//!
+//! The sub-module [`consistency`] checks that the objects published via
+//! IPFS are the same as those published via RRDP or rsync. The sub-module
+//! [`gateway`] provides failover between multiple IPFS gateways when
+//! retrieving content. The sub-module [`pin`] keeps the content currently
+//! in use pinned on a local IPFS node. The sub-module [`writer`] maintains
+//! the DAG of a repository on the publishing side.
+//!
+//! The sub-module [`fetcher`] provides an object source for the validator
+//! that retrieves objects via IPFS and falls back to another source if
+//! needed. The sub-module [`ipns`] decodes and verifies the signed IPNS
+//! records announcing the current root of a repository. If the
+//! `ipfs-fetch` feature is enabled, the sub-module `resolver` retrieves
+//! these records via a gateway.
+//!
+//! The sub-module [`publication`] calculates the CIDs of the objects of a
+//! publication point from their content or from their manifest.
+//!
+//! [`uri::Ipns`]: ../uri/struct.Ipns.html
+//! [`dag`]: dag/index.html
+//! [`consistency`]: consistency/index.html
+//! [`fetcher`]: fetcher/index.html
+//! [`gateway`]: gateway/index.html
+//! [`ipns`]: ipns/index.html
+//! [`pin`]: pin/index.html
+//! [`publication`]: publication/index.html
+//! [`writer`]: writer/index.html
+
+pub use self::dag::{Dag, DagBuilder, DagError};
+pub use self::fetcher::{BlockSource, Fetcher};
+pub use self::gateway::{Gateway, GatewayPool};
+pub use self::ipns::{IpnsError, IpnsRecord};
+pub use self::pin::{PinManager, PinMark, PinStore};
+pub use self::publication::{
+    PublicationError, cid_for, manifest_cids, object_cids
+};
+#[cfg(feature = "ipfs-fetch")]
+pub use self::resolver::{Resolution, Resolver, ResolveError};
+pub use self::writer::{
+    PublishElement, RepositoryWriter, UnixFsWriter, WriteError
+};
+
+pub mod consistency;
+pub mod dag;
+pub mod fetcher;
+pub mod gateway;
+pub mod ipns;
+pub mod pin;
+pub mod publication;
+#[cfg(feature = "ipfs-fetch")]
+pub mod resolver;
+pub mod writer;
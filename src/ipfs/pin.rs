@@ -0,0 +1,321 @@
+//! Managing pins on a local IPFS node.
+//!
+//! An IPFS node will eventually garbage collect all blocks that aren’t
+//! pinned. A relying party using a local node to retrieve repository
+//! content therefore wants to pin the content it currently uses and unpin
+//! content it doesn’t need anymore so that the node’s disk usage doesn’t
+//! grow indefinitely.
+//!
+//! This module implements this via mark-and-sweep. After each validation
+//! run, the CIDs of all repository content still referenced are marked in
+//! a [`PinMark`]. The [`PinManager`] then pins everything marked that
+//! isn’t pinned yet and unpins everything it pinned earlier that isn’t
+//! marked anymore. Pins created by others are never touched.
+//!
+//! The actual pinning is done through the [`PinStore`] trait so that the
+//! manager can be used with any client for the node’s API.
+//!
+//! [`PinMark`]: struct.PinMark.html
+//! [`PinManager`]: struct.PinManager.html
+//! [`PinStore`]: trait.PinStore.html
+
+use std::slice;
+use std::collections::{hash_set, HashSet};
+use crate::cid::Cid;
+use super::dag::Dag;
+
+
+//------------ PinStore ------------------------------------------------------
+
+/// A type that can pin and unpin content on an IPFS node.
+///
+/// Pins are expected to be recursive, i.e., pinning the root of a DAG keeps
+/// the entire DAG.
+pub trait PinStore {
+    /// The error type returned when pinning or unpinning fails.
+    type Error;
+
+    /// Pins the DAG with the given root.
+    fn pin(&mut self, cid: &Cid) -> Result<(), Self::Error>;
+
+    /// Removes the pin for the DAG with the given root.
+    fn unpin(&mut self, cid: &Cid) -> Result<(), Self::Error>;
+}
+
+
+//------------ PinMark -------------------------------------------------------
+
+/// The set of CIDs marked as referenced during a validation run.
+#[derive(Clone, Debug, Default)]
+pub struct PinMark {
+    marked: HashSet<Cid>,
+}
+
+impl PinMark {
+    /// Creates a new, empty mark set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a CID as referenced.
+    ///
+    /// Returns whether the CID was newly marked.
+    pub fn mark(&mut self, cid: Cid) -> bool {
+        self.marked.insert(cid)
+    }
+
+    /// Marks the root of a DAG as referenced.
+    ///
+    /// Since pins are recursive, this keeps all blocks of the DAG.
+    pub fn mark_dag(&mut self, dag: &Dag) -> bool {
+        self.mark(dag.root())
+    }
+
+    /// Returns whether a CID has been marked.
+    pub fn contains(&self, cid: &Cid) -> bool {
+        self.marked.contains(cid)
+    }
+
+    /// Returns an iterator over all marked CIDs.
+    pub fn iter(&self) -> hash_set::Iter<'_, Cid> {
+        self.marked.iter()
+    }
+
+    /// Returns the number of marked CIDs.
+    pub fn len(&self) -> usize {
+        self.marked.len()
+    }
+
+    /// Returns whether no CIDs have been marked.
+    pub fn is_empty(&self) -> bool {
+        self.marked.is_empty()
+    }
+}
+
+
+//--- Extend and FromIterator
+
+impl Extend<Cid> for PinMark {
+    fn extend<T: IntoIterator<Item = Cid>>(&mut self, iter: T) {
+        self.marked.extend(iter)
+    }
+}
+
+impl std::iter::FromIterator<Cid> for PinMark {
+    fn from_iter<T: IntoIterator<Item = Cid>>(iter: T) -> Self {
+        PinMark { marked: iter.into_iter().collect() }
+    }
+}
+
+
+//------------ PinManager ----------------------------------------------------
+
+/// Keeps track of the pins created by a relying party.
+///
+/// The manager only ever unpins CIDs it has pinned itself. In order to
+/// continue after a restart, the set of managed pins can be retrieved via
+/// [`pinned`] and restored via [`from_pinned`].
+///
+/// [`pinned`]: #method.pinned
+/// [`from_pinned`]: #method.from_pinned
+#[derive(Clone, Debug, Default)]
+pub struct PinManager {
+    /// The CIDs currently pinned by us.
+    pinned: HashSet<Cid>,
+}
+
+impl PinManager {
+    /// Creates a new manager that hasn’t pinned anything yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a manager from a previously saved set of pins.
+    pub fn from_pinned<I: IntoIterator<Item = Cid>>(iter: I) -> Self {
+        PinManager { pinned: iter.into_iter().collect() }
+    }
+
+    /// Returns an iterator over the CIDs currently pinned by the manager.
+    pub fn pinned(&self) -> hash_set::Iter<'_, Cid> {
+        self.pinned.iter()
+    }
+
+    /// Returns whether the manager has pinned the given CID.
+    pub fn is_pinned(&self, cid: &Cid) -> bool {
+        self.pinned.contains(cid)
+    }
+
+    /// Returns the number of CIDs pinned by the manager.
+    pub fn len(&self) -> usize {
+        self.pinned.len()
+    }
+
+    /// Returns whether the manager hasn’t pinned anything.
+    pub fn is_empty(&self) -> bool {
+        self.pinned.is_empty()
+    }
+
+    /// Updates the pins on the node to match the marked CIDs.
+    ///
+    /// All marked CIDs not yet pinned are pinned first so that content
+    /// shared between old and new DAGs never becomes unpinned. Then all
+    /// pinned CIDs that aren’t marked anymore are unpinned.
+    ///
+    /// Failures don’t abort the sweep. A CID that failed to be pinned is
+    /// tried again during the next sweep if it is still marked. A CID that
+    /// failed to be unpinned is kept as pinned and tried again, too. All
+    /// failures are returned in the report.
+    pub fn sweep<S: PinStore>(
+        &mut self, mark: &PinMark, store: &mut S
+    ) -> SweepReport<S::Error> {
+        let mut report = SweepReport::default();
+        for cid in mark.iter() {
+            if self.pinned.contains(cid) {
+                continue
+            }
+            match store.pin(cid) {
+                Ok(()) => {
+                    self.pinned.insert(*cid);
+                    report.pinned.push(*cid);
+                }
+                Err(err) => report.failed.push((*cid, err))
+            }
+        }
+        let stale: Vec<_> = self.pinned.iter().filter(|cid| {
+            !mark.contains(cid)
+        }).cloned().collect();
+        for cid in stale {
+            match store.unpin(&cid) {
+                Ok(()) => {
+                    self.pinned.remove(&cid);
+                    report.unpinned.push(cid);
+                }
+                Err(err) => report.failed.push((cid, err))
+            }
+        }
+        report
+    }
+}
+
+
+//------------ SweepReport ---------------------------------------------------
+
+/// The outcome of a sweep.
+#[derive(Clone, Debug)]
+pub struct SweepReport<E> {
+    /// The CIDs that have been newly pinned.
+    pinned: Vec<Cid>,
+
+    /// The CIDs that have been unpinned.
+    unpinned: Vec<Cid>,
+
+    /// The CIDs for which pinning or unpinning failed.
+    failed: Vec<(Cid, E)>,
+}
+
+impl<E> SweepReport<E> {
+    /// Returns an iterator over the newly pinned CIDs.
+    pub fn pinned(&self) -> slice::Iter<'_, Cid> {
+        self.pinned.iter()
+    }
+
+    /// Returns an iterator over the unpinned CIDs.
+    pub fn unpinned(&self) -> slice::Iter<'_, Cid> {
+        self.unpinned.iter()
+    }
+
+    /// Returns an iterator over the failed operations and their errors.
+    pub fn failed(&self) -> slice::Iter<'_, (Cid, E)> {
+        self.failed.iter()
+    }
+
+    /// Returns whether all operations succeeded.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl<E> Default for SweepReport<E> {
+    fn default() -> Self {
+        SweepReport {
+            pinned: Vec::new(),
+            unpinned: Vec::new(),
+            failed: Vec::new(),
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use super::*;
+    use super::super::dag::DagBuilder;
+
+    #[derive(Default)]
+    struct Node {
+        pins: HashSet<Cid>,
+        broken: bool,
+    }
+
+    impl PinStore for Node {
+        type Error = ();
+
+        fn pin(&mut self, cid: &Cid) -> Result<(), ()> {
+            if self.broken { return Err(()) }
+            self.pins.insert(*cid);
+            Ok(())
+        }
+
+        fn unpin(&mut self, cid: &Cid) -> Result<(), ()> {
+            if self.broken { return Err(()) }
+            self.pins.remove(cid);
+            Ok(())
+        }
+    }
+
+    fn dag(content: &'static [u8]) -> Dag {
+        let mut builder = DagBuilder::new();
+        builder.insert("a.roa", Bytes::from_static(content)).unwrap();
+        builder.build()
+    }
+
+    #[test]
+    fn mark_and_sweep() {
+        let foreign = dag(b"foreign").root();
+        let mut node = Node::default();
+        node.pins.insert(foreign);
+        let mut manager = PinManager::new();
+
+        let old = dag(b"old");
+        let mut mark = PinMark::new();
+        mark.mark_dag(&old);
+        let report = manager.sweep(&mark, &mut node);
+        assert!(report.is_success());
+        assert_eq!(report.pinned().collect::<Vec<_>>(), [&old.root()]);
+        assert!(node.pins.contains(&old.root()));
+
+        // A new version replaces the old one, foreign pins are kept.
+        let new = dag(b"new");
+        let mark: PinMark = vec![new.root()].into_iter().collect();
+        let report = manager.sweep(&mark, &mut node);
+        assert_eq!(report.unpinned().collect::<Vec<_>>(), [&old.root()]);
+        assert_eq!(node.pins.len(), 2);
+        assert!(node.pins.contains(&foreign));
+        assert!(manager.is_pinned(&new.root()));
+        assert!(!manager.is_pinned(&old.root()));
+
+        // Failed unpins are retried during the next sweep.
+        node.broken = true;
+        let report = manager.sweep(&PinMark::new(), &mut node);
+        assert_eq!(report.failed().count(), 1);
+        assert!(manager.is_pinned(&new.root()));
+        node.broken = false;
+        let report = manager.sweep(&PinMark::new(), &mut node);
+        assert!(report.is_success());
+        assert!(manager.is_empty());
+        assert_eq!(node.pins.len(), 1);
+    }
+}
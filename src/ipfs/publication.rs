@@ -0,0 +1,251 @@
+//! The CIDs of the objects of a publication point.
+//!
+//! When a repository is published via IPFS, each object becomes a file in
+//! the repository’s UnixFS DAG. Its CID can be calculated from its content
+//! alone via [`cid_for`] without the help of an IPFS node. A publisher can
+//! use this to pin the content of a publication point while a relying
+//! party can check blocks it retrieved before processing them.
+//!
+//! Since a manifest lists the SHA-256 hash of each object, the raw CIDs of
+//! the objects are known as soon as the manifest has been received. They
+//! are provided by [`manifest_cids`]. The CIDs of the objects as files of
+//! a UnixFS DAG depend on their complete content and are provided by
+//! [`object_cids`] which also checks the objects against the manifest.
+//!
+//! [`cid_for`]: fn.cid_for.html
+//! [`manifest_cids`]: fn.manifest_cids.html
+//! [`object_cids`]: fn.object_cids.html
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use bytes::Bytes;
+use crate::cid::{Cid, Multihash};
+use crate::manifest::{ManifestContent, ManifestHash};
+use super::dag::build_file;
+
+
+//------------ cid_for -------------------------------------------------------
+
+/// Returns the CID of an object with the given content.
+///
+/// If `codec` is [`Cid::DAG_PB`], the content is taken to be a file of a
+/// UnixFS DAG laid out as described in the [`dag`] module and the version 0
+/// CID of the file’s root node is returned. For any other codec, the
+/// content is taken to be a single block and a version 1 CID using that
+/// codec and a SHA-256 hash of the content is returned.
+///
+/// [`Cid::DAG_PB`]: ../../cid/struct.Cid.html#associatedconstant.DAG_PB
+/// [`dag`]: ../dag/index.html
+pub fn cid_for(content: &[u8], codec: u64) -> Cid {
+    if codec == Cid::DAG_PB {
+        build_file(content, &mut HashMap::new()).0
+    }
+    else {
+        Cid::v1(codec, Multihash::sha256(content))
+    }
+}
+
+
+//------------ manifest_cids -------------------------------------------------
+
+/// Returns the raw CIDs of all files listed on a manifest.
+///
+/// The iterator returns the file name and the version 1 CID with the
+/// [`Cid::RAW`] codec of each file. Entries whose hash does not have the
+/// length of a SHA-256 hash are skipped as no object can match them.
+///
+/// [`Cid::RAW`]: ../../cid/struct.Cid.html#associatedconstant.RAW
+pub fn manifest_cids(
+    manifest: &ManifestContent
+) -> impl Iterator<Item = (Bytes, Cid)> + '_ {
+    manifest.iter().filter_map(|item| {
+        let (file, hash) = item.into_pair();
+        let digest = <[u8; 32]>::try_from(hash.as_ref()).ok()?;
+        Some((file, Cid::v1(Cid::RAW, Multihash::new(digest))))
+    })
+}
+
+
+//------------ object_cids ---------------------------------------------------
+
+/// Returns the CIDs of the objects of a publication point.
+///
+/// The objects are given as pairs of their file name and content. The
+/// CID of each object is calculated via [`cid_for`] using `codec` and
+/// returned together with the file name in the order the objects were
+/// given.
+///
+/// Every file listed on the manifest must be present and match its hash.
+/// Objects not listed on the manifest, such as the manifest itself, are
+/// included without a check.
+///
+/// [`cid_for`]: fn.cid_for.html
+pub fn object_cids<I, F, D>(
+    manifest: &ManifestContent, objects: I, codec: u64
+) -> Result<Vec<(Bytes, Cid)>, PublicationError>
+where
+    I: IntoIterator<Item = (F, D)>,
+    F: AsRef<[u8]>,
+    D: AsRef<[u8]>,
+{
+    let mut listed: HashMap<_, _> = manifest.iter().map(|item| {
+        let (file, hash) = item.into_pair();
+        (file, ManifestHash::new(hash, manifest.file_hash_alg()))
+    }).collect();
+    let mut res = Vec::new();
+    for (name, content) in objects {
+        let name = Bytes::copy_from_slice(name.as_ref());
+        let content = content.as_ref();
+        if let Some(hash) = listed.remove(&name) {
+            if hash.verify(content).is_err() {
+                return Err(PublicationError::Mismatch(name))
+            }
+        }
+        res.push((name, cid_for(content, codec)));
+    }
+    match listed.into_iter().map(|item| item.0).min() {
+        Some(name) => Err(PublicationError::Missing(name)),
+        None => Ok(res)
+    }
+}
+
+
+//------------ PublicationError ----------------------------------------------
+
+/// The objects of a publication point don’t agree with its manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum PublicationError {
+    /// A file listed on the manifest is not present.
+    Missing(Bytes),
+
+    /// A file doesn’t match the hash listed on the manifest.
+    Mismatch(Bytes),
+}
+
+impl fmt::Display for PublicationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PublicationError::Missing(ref name) => {
+                write!(
+                    f, "{}: listed on manifest but not present",
+                    String::from_utf8_lossy(name)
+                )
+            }
+            PublicationError::Mismatch(ref name) => {
+                write!(
+                    f, "{}: hash does not match manifest",
+                    String::from_utf8_lossy(name)
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for PublicationError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use crate::manifest::ManifestBuilder;
+    use crate::x509::Time;
+    use super::super::dag::DagBuilder;
+    use super::*;
+
+    fn manifest() -> ManifestContent {
+        let at = Time::utc(2019, 5, 1, 0, 0, 0);
+        let mut builder = ManifestBuilder::new(
+            1u64.into(), at, at + chrono::Duration::hours(24)
+        );
+        builder.set_files(vec![
+            (Bytes::from_static(b"a.crl"), b"a".as_ref()),
+            (Bytes::from_static(b"b.roa"), b"b".as_ref()),
+        ]);
+        builder.to_content()
+    }
+
+    #[test]
+    fn cid_for() {
+        assert_eq!(
+            super::cid_for(b"hello world\n", Cid::DAG_PB).to_string(),
+            "QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o"
+        );
+        assert_eq!(
+            super::cid_for(b"hello world\n", Cid::RAW),
+            Cid::v1(Cid::RAW, Multihash::sha256(b"hello world\n"))
+        );
+
+        let mut builder = DagBuilder::new();
+        builder.insert("a.roa", Bytes::from_static(b"a")).unwrap();
+        let dag = builder.build();
+        assert!(dag.get(&super::cid_for(b"a", Cid::DAG_PB)).is_some());
+    }
+
+    #[test]
+    fn manifest_cids() {
+        let cids: Vec<_> = super::manifest_cids(&manifest()).collect();
+        assert_eq!(
+            cids,
+            [
+                (
+                    Bytes::from_static(b"a.crl"),
+                    super::cid_for(b"a", Cid::RAW)
+                ),
+                (
+                    Bytes::from_static(b"b.roa"),
+                    super::cid_for(b"b", Cid::RAW)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn object_cids() {
+        let manifest = manifest();
+        assert_eq!(
+            super::object_cids(
+                &manifest,
+                vec![
+                    (b"mft.mft".as_ref(), b"m".as_ref()),
+                    (b"b.roa".as_ref(), b"b".as_ref()),
+                    (b"a.crl".as_ref(), b"a".as_ref()),
+                ],
+                Cid::DAG_PB
+            ).unwrap(),
+            [
+                (
+                    Bytes::from_static(b"mft.mft"),
+                    super::cid_for(b"m", Cid::DAG_PB)
+                ),
+                (
+                    Bytes::from_static(b"b.roa"),
+                    super::cid_for(b"b", Cid::DAG_PB)
+                ),
+                (
+                    Bytes::from_static(b"a.crl"),
+                    super::cid_for(b"a", Cid::DAG_PB)
+                ),
+            ]
+        );
+        assert_eq!(
+            super::object_cids(
+                &manifest, vec![(b"a.crl".as_ref(), b"a".as_ref())],
+                Cid::RAW
+            ),
+            Err(PublicationError::Missing(Bytes::from_static(b"b.roa")))
+        );
+        assert_eq!(
+            super::object_cids(
+                &manifest,
+                vec![
+                    (b"a.crl".as_ref(), b"a".as_ref()),
+                    (b"b.roa".as_ref(), b"a".as_ref()),
+                ],
+                Cid::RAW
+            ),
+            Err(PublicationError::Mismatch(Bytes::from_static(b"b.roa")))
+        );
+    }
+}
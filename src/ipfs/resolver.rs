@@ -0,0 +1,265 @@
+//! Resolving IPNS names.
+//!
+//! This module is only available if the `ipfs-fetch` feature is enabled.
+//!
+//! The [`Resolver`] retrieves the IPNS record for a name from an IPFS HTTP
+//! gateway or the HTTP API of an IPFS node and verifies it. Since the
+//! record is signed, the gateway doesn’t need to be trusted. However, a
+//! gateway could still present an outdated record. The resolver therefore
+//! remembers the highest sequence number it has seen for each name and
+//! rejects records with a lower one.
+//!
+//! [`Resolver`]: struct.Resolver.html
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use bytes::Bytes;
+use reqwest::{header, Client};
+use crate::cid::Cid;
+use crate::fetch::FetchError;
+use crate::uri;
+use crate::util::http::{check_status, convert_error, read_body};
+use crate::x509::Time;
+use super::gateway::{Gateway, GatewayKind};
+use super::ipns::{IpnsError, IpnsRecord};
+
+
+//------------ Resolver ------------------------------------------------------
+
+/// An asynchronous resolver for IPNS names.
+///
+/// The resolver can be cloned cheaply. All clones share the known
+/// sequence numbers.
+#[derive(Clone, Debug)]
+pub struct Resolver {
+    /// The HTTP client to use.
+    client: Client,
+
+    /// The highest sequence number seen for each public key.
+    sequences: Arc<Mutex<HashMap<Bytes, u64>>>,
+}
+
+impl Resolver {
+    /// The default timeout for a request.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+    /// The content type for requesting a record from a gateway.
+    const RECORD_TYPE: &'static str = "application/vnd.ipfs.ipns-record";
+
+    /// Creates a new resolver with the default settings.
+    pub fn new() -> Result<Self, FetchError> {
+        Client::builder()
+            .timeout(Self::DEFAULT_TIMEOUT)
+            .build()
+            .map(Self::with_client)
+            .map_err(convert_error)
+    }
+
+    /// Creates a new resolver using the given HTTP client.
+    pub fn with_client(client: Client) -> Self {
+        Resolver {
+            client,
+            sequences: Default::default(),
+        }
+    }
+
+    /// Resolves an IPNS name using the given gateway.
+    ///
+    /// The name is the bare key hash without an `/ipns/` prefix. Returns
+    /// the root CID currently published under the name.
+    pub async fn resolve(
+        &self, gateway: &Gateway, name: &str
+    ) -> Result<Resolution, ResolveError> {
+        let url = gateway.ipns_record_url(name);
+        let request = match gateway.kind() {
+            GatewayKind::Http => {
+                self.client.get(&url).header(
+                    header::ACCEPT, Self::RECORD_TYPE
+                )
+            }
+            GatewayKind::Api => self.client.post(&url),
+        };
+        let mut response = request.send().await.map_err(convert_error)?;
+        check_status(&response)?;
+        let data = read_body(
+            &mut response, IpnsRecord::MAX_SIZE as u64
+        ).await?;
+        self.accept(IpnsRecord::decode(name, &data)?, Time::now())
+    }
+
+    /// Resolves the trust anchor publish key of an IPNS URI.
+    pub async fn resolve_ta(
+        &self, gateway: &Gateway, uri: &uri::Ipns
+    ) -> Result<Resolution, ResolveError> {
+        self.resolve(gateway, uri.get_ta_publish_key()).await
+    }
+
+    /// Resolves the repository publish key of an IPNS URI.
+    pub async fn resolve_repo(
+        &self, gateway: &Gateway, uri: &uri::Ipns
+    ) -> Result<Resolution, ResolveError> {
+        self.resolve(gateway, uri.get_repo_publish_key()).await
+    }
+
+    /// Checks the validity and sequence number of a record.
+    fn accept(
+        &self, record: IpnsRecord, now: Time
+    ) -> Result<Resolution, ResolveError> {
+        record.validate_at(now)?;
+        let mut sequences = self.sequences.lock().unwrap_or_else(|err| {
+            err.into_inner()
+        });
+        if let Some(&known) = sequences.get(record.public_key()) {
+            if record.sequence() < known {
+                return Err(ResolveError::SequenceRegression {
+                    known, received: record.sequence()
+                })
+            }
+        }
+        sequences.insert(record.public_key().clone(), record.sequence());
+        Ok(Resolution {
+            root: record.value(),
+            sequence: record.sequence(),
+            ttl: record.ttl_at(now),
+        })
+    }
+}
+
+
+//------------ Resolution ----------------------------------------------------
+
+/// The result of resolving an IPNS name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Resolution {
+    /// The root CID currently published under the name.
+    root: Cid,
+
+    /// The sequence number of the record.
+    sequence: u64,
+
+    /// How long the result may be cached.
+    ttl: Duration,
+}
+
+impl Resolution {
+    /// Returns the root CID currently published under the name.
+    pub fn root(&self) -> Cid {
+        self.root
+    }
+
+    /// Returns the sequence number of the record.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Returns how long the result may be cached.
+    ///
+    /// This is the time-to-live of the record but no longer than its
+    /// remaining validity.
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+}
+
+
+//------------ ResolveError --------------------------------------------------
+
+/// Resolving an IPNS name failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ResolveError {
+    /// Retrieving the record failed.
+    Fetch(FetchError),
+
+    /// The record was not acceptable.
+    Record(IpnsError),
+
+    /// The record is older than one seen before.
+    SequenceRegression {
+        /// The highest sequence number seen so far.
+        known: u64,
+
+        /// The sequence number of the record received.
+        received: u64,
+    },
+}
+
+impl From<FetchError> for ResolveError {
+    fn from(err: FetchError) -> Self {
+        ResolveError::Fetch(err)
+    }
+}
+
+impl From<IpnsError> for ResolveError {
+    fn from(err: IpnsError) -> Self {
+        ResolveError::Record(err)
+    }
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ResolveError::Fetch(ref err) => err.fmt(f),
+            ResolveError::Record(ref err) => err.fmt(f),
+            ResolveError::SequenceRegression { known, received } => {
+                write!(
+                    f, "IPNS record sequence number {} lower than {}",
+                    received, known
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for ResolveError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::super::ipns::test::TestKey;
+    use super::*;
+
+    const ROOT: &str = "/ipfs/QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn";
+    const VALIDITY: &str = "2020-01-01T00:00:00Z";
+
+    #[test]
+    fn accept() {
+        let resolver = Resolver::with_client(Client::new());
+        let key = TestKey::new(1);
+        let record = |sequence| {
+            IpnsRecord::decode(
+                &key.name(),
+                &key.record(ROOT, VALIDITY, sequence, 3_600_000_000_000)
+            ).unwrap()
+        };
+        let now = Time::utc(2019, 12, 31, 0, 0, 0);
+
+        let res = resolver.accept(record(5), now).unwrap();
+        assert_eq!(res.root().to_string(), &ROOT[6..]);
+        assert_eq!(res.sequence(), 5);
+        assert_eq!(res.ttl(), Duration::from_secs(3600));
+        assert!(resolver.accept(record(5), now).is_ok());
+        assert!(resolver.accept(record(6), now).is_ok());
+        assert_eq!(
+            resolver.clone().accept(record(5), now),
+            Err(ResolveError::SequenceRegression { known: 6, received: 5 })
+        );
+
+        // Other keys are tracked separately.
+        let other = TestKey::new(2);
+        assert!(resolver.accept(
+            IpnsRecord::decode(
+                &other.name(), &other.record(ROOT, VALIDITY, 1, 0)
+            ).unwrap(),
+            now
+        ).is_ok());
+
+        assert_eq!(
+            resolver.accept(record(7), Time::utc(2020, 1, 2, 0, 0, 0)),
+            Err(ResolveError::Record(IpnsError::Expired))
+        );
+    }
+}
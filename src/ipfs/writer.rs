@@ -0,0 +1,297 @@
+//! Republishing a repository via IPFS.
+//!
+//! A publication server receives changes to a repository as a sequence of
+//! transactions, each consisting of a number of publish and withdraw
+//! elements as defined in RFC 8181. In order to make the repository
+//! available via IPFS, the server needs to turn the repository content into
+//! a UnixFS DAG after each transaction and publish the DAG’s root CID under
+//! the repository’s IPNS name.
+//!
+//! The [`RepositoryWriter`] trait describes a type that applies the
+//! elements of a transaction, given as [`PublishElement`]s, and returns the
+//! new root CID. The [`UnixFsWriter`] implements it by keeping the content
+//! of an rsync module in a [`Repository`] and building its canonical DAG as
+//! defined by the [`dag`] module.
+//!
+//! [`RepositoryWriter`]: trait.RepositoryWriter.html
+//! [`PublishElement`]: ../../publication/enum.PublishElement.html
+//! [`Repository`]: ../../publication/struct.Repository.html
+//! [`UnixFsWriter`]: struct.UnixFsWriter.html
+//! [`dag`]: ../dag/index.html
+
+use std::{error, fmt};
+use std::path::PathBuf;
+use bytes::Bytes;
+use crate::uri;
+use crate::cid::Cid;
+use crate::publication::{ApplyError, Repository};
+use super::dag::{Dag, DagBuilder, DagError};
+
+pub use crate::publication::PublishElement;
+
+
+//------------ RepositoryWriter ----------------------------------------------
+
+/// A type that maintains the IPFS representation of a repository.
+pub trait RepositoryWriter {
+    /// The error type returned when a transaction fails.
+    type Error;
+
+    /// Applies the elements of one transaction.
+    ///
+    /// Either all elements are applied or, if an error is returned, none
+    /// of them. Returns the root CID of the updated repository which should
+    /// then be published under the repository’s IPNS name.
+    fn apply(
+        &mut self, elements: &[PublishElement]
+    ) -> Result<Cid, Self::Error>;
+
+    /// Returns the root CID of the current repository content.
+    fn root(&self) -> Cid;
+}
+
+
+//------------ UnixFsWriter --------------------------------------------------
+
+/// A repository writer producing the canonical UnixFS DAG of an rsync module.
+///
+/// The writer keeps the content of all objects of the module in memory.
+/// After each transaction, the DAG for the complete module is rebuilt. It
+/// is available via [`dag`] so its blocks can be added to an IPFS node
+/// before the new root is published.
+///
+/// [`dag`]: #method.dag
+#[derive(Clone, Debug)]
+pub struct UnixFsWriter {
+    /// The rsync module represented by the root of the DAG.
+    module: uri::RsyncModule,
+
+    /// The IPNS name the repository is published under.
+    ipns: uri::Ipns,
+
+    /// The current objects.
+    repository: Repository,
+
+    /// The DAG of the current objects.
+    dag: Dag,
+}
+
+impl UnixFsWriter {
+    /// Creates a new writer for an empty module.
+    pub fn new(module: uri::RsyncModule, ipns: uri::Ipns) -> Self {
+        UnixFsWriter {
+            module,
+            ipns,
+            repository: Repository::new(),
+            dag: DagBuilder::new().build(),
+        }
+    }
+
+    /// Returns the rsync module represented by the writer.
+    pub fn module(&self) -> &uri::RsyncModule {
+        &self.module
+    }
+
+    /// Returns the IPNS name the repository is published under.
+    pub fn ipns(&self) -> &uri::Ipns {
+        &self.ipns
+    }
+
+    /// Returns the DAG of the current content.
+    pub fn dag(&self) -> &Dag {
+        &self.dag
+    }
+
+    /// Returns the current content of the repository.
+    pub fn repository(&self) -> &Repository {
+        &self.repository
+    }
+
+    /// Returns the content of the object with the given URI.
+    pub fn get(&self, uri: &uri::Rsync) -> Option<&Bytes> {
+        self.repository.get(uri)
+    }
+
+    /// Returns the number of objects in the repository.
+    pub fn len(&self) -> usize {
+        self.repository.len()
+    }
+
+    /// Returns whether the repository is empty.
+    pub fn is_empty(&self) -> bool {
+        self.repository.is_empty()
+    }
+
+    /// Returns the MFS path for an object.
+    ///
+    /// When the repository is kept in the mutable file system of an IPFS
+    /// node, each IPNS name has its own directory named after its
+    /// repository publish key. This returns the path of the object within
+    /// the MFS relative to its root.
+    pub fn mfs_path(&self, uri: &uri::Rsync) -> PathBuf {
+        uri.to_ipns_repo_path(&self.ipns)
+    }
+}
+
+impl RepositoryWriter for UnixFsWriter {
+    type Error = WriteError;
+
+    fn apply(
+        &mut self, elements: &[PublishElement]
+    ) -> Result<Cid, Self::Error> {
+        for element in elements {
+            if *element.uri().module() != self.module {
+                return Err(WriteError::OutsideModule(element.uri().clone()))
+            }
+        }
+        let repository = self.repository.apply(elements)?;
+        let mut builder = DagBuilder::new();
+        for (uri, _, content) in repository.iter() {
+            builder.insert_rsync(uri, content.clone())?;
+        }
+        self.repository = repository;
+        self.dag = builder.build();
+        Ok(self.dag.root())
+    }
+
+    fn root(&self) -> Cid {
+        self.dag.root()
+    }
+}
+
+
+//------------ WriteError ----------------------------------------------------
+
+/// A transaction could not be applied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum WriteError {
+    /// The object is not within the writer’s rsync module.
+    OutsideModule(uri::Rsync),
+
+    /// Elements conflict with the content of the repository.
+    Conflict(ApplyError),
+
+    /// The objects can’t be arranged in a directory tree.
+    Dag(DagError),
+}
+
+impl From<ApplyError> for WriteError {
+    fn from(err: ApplyError) -> Self {
+        WriteError::Conflict(err)
+    }
+}
+
+impl From<DagError> for WriteError {
+    fn from(err: DagError) -> Self {
+        WriteError::Dag(err)
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            WriteError::OutsideModule(ref uri) => {
+                write!(f, "{}: outside of repository module", uri)
+            }
+            WriteError::Conflict(ref err) => err.fmt(f),
+            WriteError::Dag(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for WriteError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use crate::publication::ConflictKind;
+    use crate::rrdp::Hash;
+    use super::*;
+
+    fn rsync(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    fn writer() -> UnixFsWriter {
+        UnixFsWriter::new(
+            rsync("rsync://example.net/repo/").module().clone(),
+            uri::Ipns::from_str("ipns/ta-key/repo-key").unwrap()
+        )
+    }
+
+    fn publish(uri: &str, content: &'static [u8]) -> PublishElement {
+        PublishElement::Publish {
+            uri: rsync(uri), content: Bytes::from_static(content)
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let mut writer = writer();
+        let empty = writer.root();
+        let root = writer.apply(&[
+            publish("rsync://example.net/repo/ca/a.roa", b"a"),
+            publish("rsync://example.net/repo/ca/b.roa", b"b"),
+        ]).unwrap();
+        assert_eq!(writer.root(), root);
+        assert_eq!(writer.len(), 2);
+
+        let mut builder = DagBuilder::new();
+        builder.insert("ca/a.roa", Bytes::from_static(b"a")).unwrap();
+        builder.insert("ca/b.roa", Bytes::from_static(b"b")).unwrap();
+        assert_eq!(builder.build().root(), root);
+
+        // A failing element leaves the repository unchanged.
+        match writer.apply(&[
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/ca/a.roa"),
+                hash: Hash::from_data(b"a"),
+            },
+            publish("rsync://example.net/repo/ca/b.roa", b"b"),
+        ]) {
+            Err(WriteError::Conflict(err)) => {
+                assert_eq!(err.conflicts().len(), 1);
+                assert_eq!(err.conflicts()[0].index(), 1);
+                assert_eq!(
+                    err.conflicts()[0].kind(),
+                    ConflictKind::ObjectAlreadyPresent
+                );
+            }
+            res => panic!("unexpected result {:?}", res)
+        }
+        assert_eq!(writer.root(), root);
+        assert_eq!(writer.len(), 2);
+        assert_eq!(
+            writer.apply(&[publish("rsync://example.net/other/a.roa", b"a")]),
+            Err(WriteError::OutsideModule(
+                rsync("rsync://example.net/other/a.roa")
+            ))
+        );
+
+        writer.apply(&[
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/ca/a.roa"),
+                hash: Hash::from_data(b"a"),
+            },
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/ca/b.roa"),
+                hash: Hash::from_data(b"b"),
+            },
+        ]).unwrap();
+        assert_eq!(writer.root(), empty);
+        assert!(writer.is_empty());
+    }
+
+    #[test]
+    fn mfs_path() {
+        assert_eq!(
+            writer().mfs_path(
+                &rsync("rsync://example.net/repo/ca/a.roa")
+            ).to_str(),
+            Some("repo-key/ca/a.roa")
+        );
+    }
+}
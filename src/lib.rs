@@ -15,18 +15,52 @@
 //! Documentation for the items in this crate is currently somewhat sparse.
 //! This will be rectified in upcoming releases.
 
+pub use self::pipeline::run_validation;
+#[cfg(feature = "rtr")]
+pub use self::pipeline::serve_rtr;
+
+#[cfg(feature = "aspa")]
+pub mod aspa;
+pub mod bgpsec;
+pub mod ca;
 pub mod cert;
+pub mod cid;
 pub mod crl;
 pub mod crypto;
 pub mod csr;
+pub mod fetch;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+pub mod gbr;
+pub mod hooks;
+pub mod idcert;
+pub mod ipfs;
+pub mod locate;
 pub mod manifest;
 pub mod oid;
+pub mod payload;
+pub mod pipeline;
+pub mod publication;
 pub mod resources;
 pub mod roa;
 pub mod rrdp;
+pub mod rsc;
+#[cfg(feature = "rsync-fetch")]
+pub mod rsync;
+#[cfg(feature = "rtr")]
+pub mod rtr;
+pub mod sigmsg;
 pub mod sigobj;
+#[cfg(feature = "slurm")]
+pub mod slurm;
+pub mod stabilize;
+pub mod store;
+pub mod ta;
 pub mod tal;
+#[cfg(feature = "testbed")]
+pub mod testbed;
 pub mod uri;
+pub mod validation;
 pub mod x509;
 pub mod xml;
 
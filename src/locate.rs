@@ -0,0 +1,797 @@
+//! Locating decoding errors.
+//!
+//! The errors produced by the decoders in this crate only state that
+//! something was wrong with the data but not where. This module provides
+//! decoding functions that additionally determine the byte offset at which
+//! decoding failed and the path to the ASN.1 element found there, e.g.,
+//! `ContentInfo.content.certificates[0].tbsCertificate.extensions[3]`.
+//!
+//! These functions are used by the `decode_located` methods of the object
+//! types such as [`Cert::decode_located`] and [`Roa::decode_located`].
+//! Since locating an error needs the complete encoded object, they only
+//! accept a `Bytes` value.
+//!
+//! The offset is the number of bytes the decoder had read when it detected
+//! the error. The path names the element containing the last of these
+//! bytes. Since decoders often have to read a complete value before
+//! they can determine that it is wrong, this is typically the offending
+//! element itself. The names of the elements are those used by the ASN.1
+//! modules of the RFCs defining the objects. Elements not described by
+//! these modules, such as the content of extensions, are not descended
+//! into.
+//!
+//! [`Cert::decode_located`]: ../cert/struct.Cert.html#method.decode_located
+//! [`Roa::decode_located`]: ../roa/struct.Roa.html#method.decode_located
+
+use std::{cmp, error, fmt};
+use bcder::decode;
+use bcder::{Mode, Tag};
+use bytes::Bytes;
+use crate::sigobj::{SignedContent, SignedObject};
+use crate::x509::DecodeMode;
+
+
+//------------ Error ---------------------------------------------------------
+
+/// A decoding error together with its location in the data.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    /// The actual decoding error.
+    error: decode::Error,
+
+    /// The offset in the data where the error was detected.
+    offset: usize,
+
+    /// The path to the ASN.1 element at the offset.
+    path: String,
+}
+
+impl Error {
+    /// Creates a new error, determining the path for the offset.
+    fn new(
+        error: decode::Error, offset: usize, data: &[u8], schema: &Schema
+    ) -> Self {
+        let path = match offset.checked_sub(1) {
+            Some(last) => schema.path(data, last),
+            None => String::new()
+        };
+        Error { error, offset, path }
+    }
+
+    /// Returns the decoding error.
+    pub fn error(&self) -> decode::Error {
+        self.error
+    }
+
+    /// Returns the byte offset at which the error was detected.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the path to the ASN.1 element the error was detected in.
+    ///
+    /// The path is empty if the error was detected before any data was
+    /// read.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl From<Error> for decode::Error {
+    fn from(err: Error) -> Self {
+        err.error
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.error, self.offset)?;
+        if !self.path.is_empty() {
+            write!(f, " in {}", self.path)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for Error { }
+
+
+//------------ Decoding Functions --------------------------------------------
+
+/// Decodes a certificate.
+pub(crate) fn decode_cert<T, F>(data: Bytes, op: F) -> Result<T, Error>
+where F: FnOnce(&mut Tracker<Bytes>) -> Result<T, decode::Error> {
+    decode(data, &Schema::new("Certificate", &CERTIFICATE), op)
+}
+
+/// Decodes a CRL.
+pub(crate) fn decode_crl<T, F>(data: Bytes, op: F) -> Result<T, Error>
+where F: FnOnce(&mut Tracker<Bytes>) -> Result<T, decode::Error> {
+    decode(data, &Schema::new("CertificateList", &CERTIFICATE_LIST), op)
+}
+
+/// Decodes a signed object and its content.
+///
+/// The `content` node describes the structure of the content, i.e., the
+/// eContent of the object.
+pub(crate) fn decode_signed<T: SignedContent>(
+    data: Bytes, mode: DecodeMode, content: &'static Node
+) -> Result<(SignedObject, T), Error> {
+    let schema = Schema::signed_object(content);
+    let signed = decode(data.clone(), &schema, |source| {
+        SignedObject::decode_with_mode(source, mode)
+    })?;
+
+    // ContentInfo.content.encapContentInfo
+    let encap = child(&data, 0, 1).and_then(|pos| child(&data, pos, 0))
+        .and_then(|pos| child(&data, pos, 2));
+    if signed.content_type() != &T::CONTENT_TYPE {
+        let offset = encap.and_then(|pos| child(&data, pos, 0))
+            .and_then(|pos| end(&data, pos)).unwrap_or(0);
+        return Err(Error::new(decode::Malformed, offset, &data, &schema))
+    }
+
+    // eContent is [0] EXPLICIT OCTET STRING. The octet string may be
+    // broken up into segments, so we need to translate offsets in the
+    // content into offsets in the segments.
+    let mut parts = Vec::new();
+    if let Some(pos) = encap.and_then(|pos| child(&data, pos, 1))
+        .and_then(|pos| child(&data, pos, 0))
+    {
+        segments(&data, pos, &mut parts)
+    }
+    let mut source = Tracker::new(signed.content().to_bytes());
+    match Mode::Der.decode(&mut source, T::take_content_from) {
+        Ok(content) => Ok((signed, content)),
+        Err(err) => {
+            let mut pos = source.position();
+            let mut offset = data.len();
+            for &(start, len) in &parts {
+                if pos <= len {
+                    offset = start + pos;
+                    break
+                }
+                pos -= len;
+            }
+            Err(Error::new(err, offset, &data, &schema))
+        }
+    }
+}
+
+/// Decodes data using `op` and locates any error using `schema`.
+fn decode<T, F>(data: Bytes, schema: &Schema, op: F) -> Result<T, Error>
+where F: FnOnce(&mut Tracker<Bytes>) -> Result<T, decode::Error> {
+    let mut source = Tracker::new(data.clone());
+    op(&mut source).map_err(|err| {
+        Error::new(err, source.position(), &data, schema)
+    })
+}
+
+
+//------------ Tracker -------------------------------------------------------
+
+/// A decoding source that keeps track of how far it has been read.
+///
+/// Because captured values are only advanced over once capturing has
+/// finished, the number of bytes advanced isn’t a good measure. Instead,
+/// the tracker keeps the end of the furthest range of data requested.
+#[derive(Clone, Debug)]
+pub(crate) struct Tracker<S> {
+    /// The underlying source.
+    source: S,
+
+    /// The number of bytes advanced so far.
+    pos: usize,
+
+    /// The end of the furthest range of data requested.
+    end: usize,
+}
+
+impl<S> Tracker<S> {
+    /// Creates a new tracker for the given source.
+    pub fn new(source: S) -> Self {
+        Tracker { source, pos: 0, end: 0 }
+    }
+
+    /// Returns the number of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.end
+    }
+}
+
+impl<S: decode::Source> decode::Source for Tracker<S> {
+    type Err = S::Err;
+
+    fn request(&mut self, len: usize) -> Result<usize, Self::Err> {
+        let res = self.source.request(len)?;
+        self.end = cmp::max(self.end, self.pos + cmp::min(len, res));
+        Ok(res)
+    }
+
+    fn advance(&mut self, len: usize) -> Result<(), Self::Err> {
+        self.source.advance(len)?;
+        self.pos += len;
+        self.end = cmp::max(self.end, self.pos);
+        Ok(())
+    }
+
+    fn slice(&self) -> &[u8] {
+        self.source.slice()
+    }
+
+    fn bytes(&self, start: usize, end: usize) -> Bytes {
+        self.source.bytes(start, end)
+    }
+}
+
+
+//------------ Schema --------------------------------------------------------
+
+/// A description of the structure of an encoded object.
+struct Schema {
+    /// The name of the outermost type.
+    name: &'static str,
+
+    /// The structure of the outermost value.
+    root: &'static Node,
+
+    /// The structure of the content of a signed object.
+    content: &'static Node,
+}
+
+impl Schema {
+    /// Creates a schema for an object that isn’t a signed object.
+    fn new(name: &'static str, root: &'static Node) -> Self {
+        Schema { name, root, content: &Node::Leaf }
+    }
+
+    /// Creates a schema for a signed object with the given content.
+    fn signed_object(content: &'static Node) -> Self {
+        Schema { name: "ContentInfo", root: &CONTENT_INFO, content }
+    }
+
+    /// Returns the path to the element containing the byte at `target`.
+    fn path(&self, data: &[u8], target: usize) -> String {
+        let mut path = String::new();
+        if target < data.len() {
+            path.push_str(self.name);
+            self.descend(data, 0, self.root, target, &mut path);
+        }
+        path
+    }
+
+    /// Descends into the element at `pos` described by `node`.
+    fn descend(
+        &self,
+        data: &[u8],
+        pos: usize,
+        node: &Node,
+        target: usize,
+        path: &mut String
+    ) {
+        let header = match Header::parse(data, pos) {
+            Some(header) => header,
+            None => return
+        };
+        let children = Children::new(data, pos, header);
+        if let Node::Content = *node {
+            // The content may be broken up into segments. We can only
+            // descend into the first one.
+            if header.constructed {
+                self.descend_first(children, node, target, path)
+            }
+            else {
+                self.descend_first(children, self.content, target, path)
+            }
+            return
+        }
+        if !header.constructed {
+            return
+        }
+        match *node {
+            Node::Leaf => { }
+            Node::Struct(fields) => {
+                let mut fields = fields.iter();
+                for (start, end, tag) in children {
+                    let field = loop {
+                        let field = match fields.next() {
+                            Some(field) => field,
+                            None => return
+                        };
+                        if field.matches(tag) {
+                            break field
+                        }
+                        if !field.optional {
+                            return
+                        }
+                    };
+                    if target >= start && target < end {
+                        path.push('.');
+                        path.push_str(field.name);
+                        self.descend(data, start, field.node, target, path);
+                        return
+                    }
+                }
+            }
+            Node::List(inner) => {
+                for (idx, (start, end, _)) in children.enumerate() {
+                    if target >= start && target < end {
+                        path.push_str(&format!("[{}]", idx));
+                        self.descend(data, start, inner, target, path);
+                        return
+                    }
+                }
+            }
+            Node::Wrapped(inner) => {
+                self.descend_first(children, inner, target, path)
+            }
+            Node::Content => { }
+        }
+    }
+
+    /// Descends into the first value of `children` if it contains `target`.
+    fn descend_first(
+        &self,
+        mut children: Children,
+        node: &Node,
+        target: usize,
+        path: &mut String
+    ) {
+        if let Some((start, end, _)) = children.next() {
+            if target >= start && target < end {
+                self.descend(children.data, start, node, target, path)
+            }
+        }
+    }
+}
+
+
+//------------ Node and Field ------------------------------------------------
+
+/// The structure of an encoded value.
+#[derive(Debug)]
+pub(crate) enum Node {
+    /// A value whose content we don’t descend into.
+    Leaf,
+
+    /// A SEQUENCE or SET with the given fields.
+    Struct(&'static [Field]),
+
+    /// A SEQUENCE OF or SET OF the given element.
+    List(&'static Node),
+
+    /// A constructed value containing a single value.
+    ///
+    /// This is used for explicitly tagged values.
+    Wrapped(&'static Node),
+
+    /// An octet string containing the content of a signed object.
+    Content,
+}
+
+/// A field of a SEQUENCE or SET.
+#[derive(Debug)]
+pub(crate) struct Field {
+    /// The name of the field.
+    name: &'static str,
+
+    /// The tag if the field always has a specific one.
+    tag: Option<Tag>,
+
+    /// Whether the field may be missing.
+    optional: bool,
+
+    /// The structure of the field’s value.
+    node: &'static Node,
+}
+
+impl Field {
+    /// Creates a mandatory field that may have any tag.
+    const fn any(name: &'static str, node: &'static Node) -> Self {
+        Field { name, tag: None, optional: false, node }
+    }
+
+    /// Creates an optional field with the given tag.
+    const fn opt(
+        name: &'static str, tag: Tag, node: &'static Node
+    ) -> Self {
+        Field { name, tag: Some(tag), optional: true, node }
+    }
+
+    /// Returns whether a value with the given tag can be this field.
+    fn matches(&self, tag: Tag) -> bool {
+        match self.tag {
+            Some(field_tag) => field_tag == tag,
+            None => true
+        }
+    }
+}
+
+
+//------------ Header and Children -------------------------------------------
+
+/// The identifier and length octets of an encoded value.
+#[derive(Clone, Copy, Debug)]
+struct Header {
+    /// The tag of the value.
+    tag: Tag,
+
+    /// Whether the value is constructed.
+    constructed: bool,
+
+    /// The length of the header.
+    len: usize,
+
+    /// The length of the content or `None` for indefinite length.
+    content_len: Option<usize>,
+}
+
+impl Header {
+    /// Parses the header of the value starting at `pos`.
+    fn parse(data: &[u8], pos: usize) -> Option<Self> {
+        let mut source = data.get(pos..)?;
+        let (tag, constructed) = Tag::take_from(&mut source).ok()?;
+        let first = *source.first()?;
+        let mut len = data.len() - pos - source.len() + 1;
+        let content_len = if first < 0x80 {
+            Some(usize::from(first))
+        }
+        else if first == 0x80 {
+            None
+        }
+        else {
+            let octets = usize::from(first & 0x7F);
+            if octets > 4 {
+                return None
+            }
+            let mut content_len = 0usize;
+            for &octet in source.get(1..=octets)? {
+                content_len = (content_len << 8) | usize::from(octet);
+            }
+            len += octets;
+            Some(content_len)
+        };
+        Some(Header { tag, constructed, len, content_len })
+    }
+
+    /// Returns the end of the value starting at `pos` with this header.
+    ///
+    /// For values of indefinite length, this includes the end-of-contents
+    /// octets. Returns `None` if these cannot be found.
+    fn end(self, data: &[u8], pos: usize) -> Option<usize> {
+        let mut pos = pos + self.len;
+        match self.content_len {
+            Some(len) => Some(pos.saturating_add(len).min(data.len())),
+            None => {
+                loop {
+                    if data.get(pos..pos + 2)? == [0, 0] {
+                        return Some(pos + 2)
+                    }
+                    pos = Header::parse(data, pos)?.end(data, pos)?;
+                }
+            }
+        }
+    }
+}
+
+/// An iterator over the values contained in another value.
+///
+/// Each item is the start and end offset and the tag of a value.
+struct Children<'a> {
+    /// The complete data.
+    data: &'a [u8],
+
+    /// The start of the next value.
+    pos: usize,
+
+    /// The end of the containing value’s content.
+    end: usize,
+}
+
+impl<'a> Children<'a> {
+    /// Creates an iterator over the content of the value at `pos`.
+    fn new(data: &'a [u8], pos: usize, header: Header) -> Self {
+        let end = match header.content_len {
+            Some(_) => header.end(data, pos),
+            None => header.end(data, pos).map(|end| end - 2),
+        };
+        Children {
+            data,
+            pos: pos + header.len,
+            end: end.unwrap_or(data.len())
+        }
+    }
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = (usize, usize, Tag);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.end {
+            return None
+        }
+        let header = Header::parse(self.data, self.pos)?;
+        let start = self.pos;
+        let end = header.end(self.data, start)?.min(self.end);
+        self.pos = end;
+        Some((start, end, header.tag))
+    }
+}
+
+/// Returns the start of the `idx`th value inside the value at `pos`.
+fn child(data: &[u8], pos: usize, idx: usize) -> Option<usize> {
+    let header = Header::parse(data, pos)?;
+    if !header.constructed {
+        return None
+    }
+    Children::new(data, pos, header).nth(idx).map(|(start, _, _)| start)
+}
+
+/// Returns the end of the value starting at `pos`.
+fn end(data: &[u8], pos: usize) -> Option<usize> {
+    Header::parse(data, pos)?.end(data, pos)
+}
+
+/// Appends the content of the primitive segments of a string value.
+///
+/// Each item added to `segments` is the start and length of the content of
+/// one segment.
+fn segments(data: &[u8], pos: usize, segments: &mut Vec<(usize, usize)>) {
+    let header = match Header::parse(data, pos) {
+        Some(header) => header,
+        None => return
+    };
+    if header.constructed {
+        for (start, _, _) in Children::new(data, pos, header) {
+            self::segments(data, start, segments)
+        }
+    }
+    else if let Some(end) = header.end(data, pos) {
+        segments.push((pos + header.len, end - pos - header.len))
+    }
+}
+
+
+//------------ Schemas -------------------------------------------------------
+
+/// ContentInfo as defined in RFC 5652.
+static CONTENT_INFO: Node = Node::Struct(&[
+    Field::any("contentType", &Node::Leaf),
+    Field::any("content", &Node::Wrapped(&SIGNED_DATA)),
+]);
+
+/// SignedData as defined in RFC 5652.
+static SIGNED_DATA: Node = Node::Struct(&[
+    Field::any("version", &Node::Leaf),
+    Field::any("digestAlgorithms", &Node::Leaf),
+    Field::any("encapContentInfo", &Node::Struct(&[
+        Field::any("eContentType", &Node::Leaf),
+        Field::opt("eContent", Tag::CTX_0, &Node::Wrapped(&Node::Content)),
+    ])),
+    Field::opt("certificates", Tag::CTX_0, &Node::List(&CERTIFICATE)),
+    Field::opt("crls", Tag::CTX_1, &Node::Leaf),
+    Field::any("signerInfos", &Node::List(&SIGNER_INFO)),
+]);
+
+/// SignerInfo as defined in RFC 5652.
+static SIGNER_INFO: Node = Node::Struct(&[
+    Field::any("version", &Node::Leaf),
+    Field::any("sid", &Node::Leaf),
+    Field::any("digestAlgorithm", &Node::Leaf),
+    Field::opt("signedAttrs", Tag::CTX_0, &Node::List(&ATTRIBUTE)),
+    Field::any("signatureAlgorithm", &Node::Leaf),
+    Field::any("signature", &Node::Leaf),
+    Field::opt("unsignedAttrs", Tag::CTX_1, &Node::Leaf),
+]);
+
+/// Attribute as defined in RFC 5652.
+static ATTRIBUTE: Node = Node::Struct(&[
+    Field::any("attrType", &Node::Leaf),
+    Field::any("attrValues", &Node::List(&Node::Leaf)),
+]);
+
+/// Certificate as defined in RFC 5280.
+static CERTIFICATE: Node = Node::Struct(&[
+    Field::any("tbsCertificate", &TBS_CERTIFICATE),
+    Field::any("signatureAlgorithm", &Node::Leaf),
+    Field::any("signatureValue", &Node::Leaf),
+]);
+
+/// TBSCertificate as defined in RFC 5280.
+static TBS_CERTIFICATE: Node = Node::Struct(&[
+    Field::opt("version", Tag::CTX_0, &Node::Leaf),
+    Field::any("serialNumber", &Node::Leaf),
+    Field::any("signature", &Node::Leaf),
+    Field::any("issuer", &Node::Leaf),
+    Field::any("validity", &Node::Struct(&[
+        Field::any("notBefore", &Node::Leaf),
+        Field::any("notAfter", &Node::Leaf),
+    ])),
+    Field::any("subject", &Node::Leaf),
+    Field::any("subjectPublicKeyInfo", &Node::Leaf),
+    Field::opt("issuerUniqueID", Tag::CTX_1, &Node::Leaf),
+    Field::opt("subjectUniqueID", Tag::CTX_2, &Node::Leaf),
+    Field::opt(
+        "extensions", Tag::CTX_3, &Node::Wrapped(&Node::List(&EXTENSION))
+    ),
+]);
+
+/// Extension as defined in RFC 5280.
+static EXTENSION: Node = Node::Struct(&[
+    Field::any("extnID", &Node::Leaf),
+    Field::opt("critical", Tag::BOOLEAN, &Node::Leaf),
+    Field::any("extnValue", &Node::Leaf),
+]);
+
+/// CertificateList as defined in RFC 5280.
+static CERTIFICATE_LIST: Node = Node::Struct(&[
+    Field::any("tbsCertList", &TBS_CERT_LIST),
+    Field::any("signatureAlgorithm", &Node::Leaf),
+    Field::any("signatureValue", &Node::Leaf),
+]);
+
+/// TBSCertList as defined in RFC 5280.
+static TBS_CERT_LIST: Node = Node::Struct(&[
+    Field::opt("version", Tag::INTEGER, &Node::Leaf),
+    Field::any("signature", &Node::Leaf),
+    Field::any("issuer", &Node::Leaf),
+    Field::any("thisUpdate", &Node::Leaf),
+    Field::any("nextUpdate", &Node::Leaf),
+    Field::opt(
+        "revokedCertificates", Tag::SEQUENCE,
+        &Node::List(&Node::Struct(&[
+            Field::any("userCertificate", &Node::Leaf),
+            Field::any("revocationDate", &Node::Leaf),
+            Field::opt(
+                "crlEntryExtensions", Tag::SEQUENCE,
+                &Node::List(&EXTENSION)
+            ),
+        ]))
+    ),
+    Field::opt(
+        "crlExtensions", Tag::CTX_0, &Node::Wrapped(&Node::List(&EXTENSION))
+    ),
+]);
+
+/// RouteOriginAttestation as defined in RFC 6482.
+pub(crate) static ROUTE_ORIGIN_ATTESTATION: Node = Node::Struct(&[
+    Field::opt("version", Tag::CTX_0, &Node::Leaf),
+    Field::any("asID", &Node::Leaf),
+    Field::any("ipAddrBlocks", &Node::List(&Node::Struct(&[
+        Field::any("addressFamily", &Node::Leaf),
+        Field::any("addresses", &Node::List(&Node::Struct(&[
+            Field::any("address", &Node::Leaf),
+            Field::opt("maxLength", Tag::INTEGER, &Node::Leaf),
+        ]))),
+    ]))),
+]);
+
+/// Manifest as defined in RFC 9286.
+pub(crate) static MANIFEST: Node = Node::Struct(&[
+    Field::opt("version", Tag::CTX_0, &Node::Leaf),
+    Field::any("manifestNumber", &Node::Leaf),
+    Field::any("thisUpdate", &Node::Leaf),
+    Field::any("nextUpdate", &Node::Leaf),
+    Field::any("fileHashAlg", &Node::Leaf),
+    Field::any("fileList", &Node::List(&Node::Struct(&[
+        Field::any("file", &Node::Leaf),
+        Field::any("hash", &Node::Leaf),
+    ]))),
+]);
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use crate::cert::Cert;
+    use crate::crl::Crl;
+    use crate::manifest::Manifest;
+    use crate::roa::Roa;
+    use super::*;
+
+    /// Returns the position of the value reached from `pos` via `path`.
+    fn find(data: &[u8], pos: usize, path: &[usize]) -> usize {
+        path.iter().fold(pos, |pos, &idx| child(data, pos, idx).unwrap())
+    }
+
+    /// Returns the position of the last value inside the value at `pos`.
+    fn last(data: &[u8], pos: usize) -> usize {
+        let header = Header::parse(data, pos).unwrap();
+        Children::new(data, pos, header).last().unwrap().0
+    }
+
+    /// Returns the position of the first content octet of a value.
+    fn content(data: &[u8], pos: usize) -> usize {
+        pos + Header::parse(data, pos).unwrap().len
+    }
+
+    #[test]
+    fn decode_valid() {
+        let mode = DecodeMode::default();
+        Cert::decode_located(
+            Bytes::from_static(include_bytes!("../test-data/ta.cer")), mode
+        ).unwrap();
+        Crl::decode_located(
+            Bytes::from_static(include_bytes!("../test-data/ta.crl")), mode
+        ).unwrap();
+        Manifest::decode_located(
+            Bytes::from_static(include_bytes!("../test-data/ta.mft")), mode
+        ).unwrap();
+        Roa::decode_located(
+            Bytes::from_static(
+                include_bytes!("../test-data/example-ripe.roa")
+            ),
+            mode
+        ).unwrap();
+    }
+
+    #[test]
+    fn locate_cert_error() {
+        let mut data = include_bytes!("../test-data/ta.cer").to_vec();
+
+        // Replace the value of the first extension with a NULL.
+        let extensions = last(&data, find(&data, 0, &[0]));
+        let value = last(&data, find(&data, extensions, &[0, 0]));
+        let inner = content(&data, value);
+        data[inner] = 0x05;
+
+        let err = Cert::decode_located(
+            Bytes::from(data), DecodeMode::default()
+        ).unwrap_err();
+        assert_eq!(err.error(), decode::Malformed);
+        assert!(err.offset() > value);
+        assert_eq!(
+            err.path(), "Certificate.tbsCertificate.extensions[0].extnValue"
+        );
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "malformed data at offset {} in \
+                 Certificate.tbsCertificate.extensions[0].extnValue",
+                err.offset()
+            )
+        );
+    }
+
+    #[test]
+    fn locate_content_error() {
+        let mut data = include_bytes!("../test-data/ta.mft").to_vec();
+
+        // Replace the first file name of the manifest with a NULL. The
+        // manifest is BER encoded, so the content is in a segment of a
+        // constructed octet string.
+        let manifest = content(&data, find(&data, 0, &[1, 0, 2, 1, 0, 0]));
+        let file = find(&data, last(&data, manifest), &[0, 0]);
+        data[file] = 0x05;
+        let data = Bytes::from(data);
+
+        let err = Manifest::decode_located(
+            data.clone(), DecodeMode::default()
+        ).unwrap_err();
+        assert!(err.offset() > file);
+        assert_eq!(
+            err.path(),
+            "ContentInfo.content.encapContentInfo.eContent.fileList[0].file"
+        );
+
+        let err = Roa::decode_located(
+            data, DecodeMode::default()
+        ).unwrap_err();
+        assert_eq!(
+            err.path(),
+            "ContentInfo.content.encapContentInfo.eContentType"
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let data = include_bytes!("../test-data/ta.cer");
+        let len = data.len() - 10;
+        let err = Cert::decode_located(
+            Bytes::from_static(&data[..len]), DecodeMode::default()
+        ).unwrap_err();
+        assert!(err.offset() <= len);
+        assert!(err.path().starts_with("Certificate"));
+    }
+}
@@ -0,0 +1,572 @@
+//! Processing a publication point via its manifest.
+//!
+//! Section 6 of [RFC 9286] describes in detail how a relying party should
+//! process the objects of a CA’s publication point starting with its
+//! manifest and what to do if anything goes wrong. This module implements
+//! this decision tree as an explicit state machine.
+//!
+//! Processing starts with a [`ManifestCheck`] that receives the outcome of
+//! acquiring and validating the manifest. If successful, it turns into a
+//! [`CrlCheck`] which in turn receives the outcome for the CRL and turns
+//! into a [`FilesCheck`]. This one collects the outcome for all the other
+//! files listed on the manifest before finally producing the list of files
+//! that may be used.
+//!
+//! Each step may fail, producing a [`FailedFetch`]. In this case, no data
+//! from the current fetch must be used. Instead, the relying party
+//! continues to use the data from the last successful fetch, if it is still
+//! available, as described in section 6.6 of the RFC.
+//!
+//! The RFC leaves only one choice to relying parties: what to do if the
+//! manifest number did not increase. This choice is available via
+//! [`ManifestPolicy`].
+//!
+//! [RFC 9286]: https://tools.ietf.org/html/rfc9286
+//! [`ManifestCheck`]: struct.ManifestCheck.html
+//! [`CrlCheck`]: struct.CrlCheck.html
+//! [`FilesCheck`]: struct.FilesCheck.html
+//! [`FailedFetch`]: struct.FailedFetch.html
+//! [`ManifestPolicy`]: struct.ManifestPolicy.html
+
+use std::{error, fmt};
+use std::collections::HashSet;
+use bytes::Bytes;
+use serde::Serialize;
+use crate::x509::{Serial, Time};
+use super::ManifestContent;
+
+
+//------------ ManifestPolicy ------------------------------------------------
+
+/// The choices a relying party can make when processing manifests.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ManifestPolicy {
+    /// What to do if the manifest number did not increase.
+    number_regression: NumberRegression,
+}
+
+impl ManifestPolicy {
+    /// Returns the policy for manifests without an increased number.
+    pub fn number_regression(self) -> NumberRegression {
+        self.number_regression
+    }
+
+    /// Sets the policy for manifests without an increased number.
+    pub fn set_number_regression(&mut self, value: NumberRegression) {
+        self.number_regression = value
+    }
+}
+
+
+//------------ NumberRegression ----------------------------------------------
+
+/// What to do if a manifest number is not larger than the previous one.
+///
+/// Section 4.2.1 of RFC 9286 requires a relying party to check that a new
+/// manifest has a larger manifest number than the previously validated
+/// manifest. If it doesn’t, the relying party _should_ continue to use the
+/// cached objects.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NumberRegression {
+    /// Treat the fetch as failed and continue using the cached objects.
+    ///
+    /// This is what the RFC recommends and the default.
+    UseCache,
+
+    /// Accept the new manifest regardless.
+    Accept,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] requires Rust 1.62.
+impl Default for NumberRegression {
+    fn default() -> Self {
+        NumberRegression::UseCache
+    }
+}
+
+
+//------------ ObjectStatus --------------------------------------------------
+
+/// The outcome of acquiring and validating an object listed on a manifest.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ObjectStatus {
+    /// The object could not be found at the publication point.
+    Missing,
+
+    /// The hash of the object does not match the manifest.
+    HashMismatch,
+
+    /// The object is present and its hash matches but it is invalid.
+    Invalid,
+
+    /// The object is present, its hash matches, and it is valid.
+    Valid,
+}
+
+
+//------------ ManifestCheck -------------------------------------------------
+
+/// The first step: acquiring and checking the manifest.
+///
+/// This covers sections 6.1 to 6.3 of RFC 9286.
+#[derive(Clone, Debug)]
+pub struct ManifestCheck {
+    /// The policy to use.
+    policy: ManifestPolicy,
+
+    /// The time to check the manifest against.
+    now: Time,
+
+    /// The manifest number of the last validated manifest if there is one.
+    previous: Option<Serial>,
+}
+
+impl ManifestCheck {
+    /// Starts processing a publication point.
+    ///
+    /// The manifest will be checked against the time `now`. If a manifest
+    /// for the publication point has been successfully processed before,
+    /// its manifest number needs to be given in `previous`.
+    pub fn new(
+        policy: ManifestPolicy,
+        now: Time,
+        previous: Option<Serial>
+    ) -> Self {
+        ManifestCheck { policy, now, previous }
+    }
+
+    /// Returns the time the manifest is checked against.
+    pub fn now(&self) -> Time {
+        self.now
+    }
+
+    /// No manifest was found at the publication point.
+    ///
+    /// Per section 6.1, this is a failed fetch.
+    pub fn missing(self) -> FailedFetch {
+        FailedFetch::new(FailureReason::ManifestMissing)
+    }
+
+    /// The manifest or its EE certificate is invalid.
+    ///
+    /// Per section 6.2, this is a failed fetch.
+    pub fn invalid(self) -> FailedFetch {
+        FailedFetch::new(FailureReason::ManifestInvalid)
+    }
+
+    /// A valid manifest was found.
+    ///
+    /// The caller must have checked the manifest’s signed object and EE
+    /// certificate already.
+    ///
+    /// Per section 6.3, the fetch has failed if the current time is before
+    /// the manifest’s thisUpdate or after its nextUpdate time. If the
+    /// manifest number isn’t larger than the previous one, the outcome
+    /// depends on the policy. Per section 6.4, the fetch has also failed if
+    /// the manifest doesn’t list exactly one CRL.
+    ///
+    /// Otherwise, processing continues with the CRL.
+    pub fn valid(
+        self,
+        manifest: &ManifestContent
+    ) -> Result<CrlCheck, FailedFetch> {
+        self.check_manifest(manifest, false)
+    }
+
+    /// Checks a valid manifest, optionally accepting it if stale.
+    pub(crate) fn check_manifest(
+        self,
+        manifest: &ManifestContent,
+        allow_stale: bool,
+    ) -> Result<CrlCheck, FailedFetch> {
+        if self.now < manifest.this_update() {
+            return Err(FailedFetch::new(FailureReason::Premature))
+        }
+        if !allow_stale && self.now > manifest.next_update() {
+            return Err(FailedFetch::new(FailureReason::Stale))
+        }
+        if let Some(previous) = self.previous {
+            let use_cache = self.policy.number_regression
+                == NumberRegression::UseCache;
+            if use_cache && manifest.manifest_number() <= previous {
+                return Err(FailedFetch::new(
+                    FailureReason::NumberRegression
+                ))
+            }
+        }
+
+        let mut crl = None;
+        let mut files = HashSet::new();
+        for item in manifest.iter() {
+            let (file, _) = item.into_pair();
+            if file.ends_with(b".crl") {
+                if crl.is_some() {
+                    return Err(FailedFetch::new(FailureReason::CrlCount))
+                }
+                crl = Some(file);
+            }
+            else {
+                files.insert(file);
+            }
+        }
+        match crl {
+            Some(crl) => Ok(CrlCheck { crl, files }),
+            None => Err(FailedFetch::new(FailureReason::CrlCount))
+        }
+    }
+}
+
+
+//------------ CrlCheck ------------------------------------------------------
+
+/// The second step: acquiring and checking the CRL.
+///
+/// This covers section 6.4 of RFC 9286.
+#[derive(Clone, Debug)]
+pub struct CrlCheck {
+    /// The file name of the CRL.
+    crl: Bytes,
+
+    /// The file names of all other files on the manifest.
+    files: HashSet<Bytes>,
+}
+
+impl CrlCheck {
+    /// Returns the file name of the CRL listed on the manifest.
+    pub fn crl_name(&self) -> &Bytes {
+        &self.crl
+    }
+
+    /// Processes the outcome of acquiring and validating the CRL.
+    ///
+    /// Per section 6.4, the fetch has failed unless the CRL is present,
+    /// matches the manifest hash, and is valid. Otherwise, processing
+    /// continues with the remaining files.
+    pub fn crl(self, status: ObjectStatus) -> Result<FilesCheck, FailedFetch> {
+        match status {
+            ObjectStatus::Valid => {
+                Ok(FilesCheck {
+                    crl: self.crl,
+                    pending: self.files,
+                    accepted: Vec::new(),
+                })
+            }
+            ObjectStatus::Missing => {
+                Err(FailedFetch::new(FailureReason::CrlMissing))
+            }
+            ObjectStatus::HashMismatch => {
+                Err(FailedFetch::new(FailureReason::CrlHashMismatch))
+            }
+            ObjectStatus::Invalid => {
+                Err(FailedFetch::new(FailureReason::CrlInvalid))
+            }
+        }
+    }
+}
+
+
+//------------ FilesCheck ----------------------------------------------------
+
+/// The final step: acquiring and checking all other files.
+///
+/// This covers section 6.5 of RFC 9286.
+#[derive(Clone, Debug)]
+pub struct FilesCheck {
+    /// The file name of the CRL.
+    crl: Bytes,
+
+    /// The files listed on the manifest without an outcome yet.
+    pending: HashSet<Bytes>,
+
+    /// The files that have been found valid.
+    accepted: Vec<Bytes>,
+}
+
+impl FilesCheck {
+    /// Returns an iterator over the names of files still to be processed.
+    pub fn pending(&self) -> impl Iterator<Item = &Bytes> {
+        self.pending.iter()
+    }
+
+    /// Processes the outcome of acquiring and validating a file.
+    ///
+    /// Per section 6.5, the fetch has failed unless the file is present,
+    /// matches the manifest hash, and is valid. Files not listed on the
+    /// manifest are ignored and are never part of the outcome.
+    pub fn file(
+        mut self,
+        name: &[u8],
+        status: ObjectStatus
+    ) -> Result<Self, FailedFetch> {
+        if !self.pending.remove(name) {
+            return Ok(self)
+        }
+        let reason = match status {
+            ObjectStatus::Valid => {
+                self.accepted.push(Bytes::copy_from_slice(name));
+                return Ok(self)
+            }
+            ObjectStatus::Missing => FailureReason::FileMissing,
+            ObjectStatus::HashMismatch => FailureReason::FileHashMismatch,
+            ObjectStatus::Invalid => FailureReason::FileInvalid,
+        };
+        Err(FailedFetch::with_file(reason, name))
+    }
+
+    /// Ignores a file listed on the manifest.
+    ///
+    /// The file will not be part of the outcome but also doesn’t cause
+    /// the fetch to fail. This deviates from RFC 9286 and should only be
+    /// used if local policy asks for it.
+    pub fn ignore(mut self, name: &[u8]) -> Self {
+        self.pending.remove(name);
+        self
+    }
+
+    /// Finishes processing.
+    ///
+    /// If there are still files listed on the manifest that haven’t been
+    /// processed, they are considered missing and the fetch has failed per
+    /// section 6.5. Otherwise, returns the names of the files that can be
+    /// used, starting with the CRL.
+    pub fn finish(self) -> Result<Vec<Bytes>, FailedFetch> {
+        if let Some(name) = self.pending.iter().next() {
+            return Err(FailedFetch::with_file(
+                FailureReason::FileMissing, name
+            ))
+        }
+        let mut res = self.accepted;
+        res.insert(0, self.crl);
+        Ok(res)
+    }
+}
+
+
+//------------ FailedFetch ---------------------------------------------------
+
+/// Processing of a publication point has failed.
+///
+/// Per section 6.6 of RFC 9286, no data from the failed fetch must be used.
+/// Instead, the relying party should continue to use the cached objects
+/// from the last successful fetch as long as they are valid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FailedFetch {
+    /// The reason for the failure.
+    reason: FailureReason,
+
+    /// The name of the file that caused the failure, if applicable.
+    file: Option<Bytes>,
+}
+
+impl FailedFetch {
+    pub(crate) fn new(reason: FailureReason) -> Self {
+        FailedFetch { reason, file: None }
+    }
+
+    fn with_file(reason: FailureReason, file: &[u8]) -> Self {
+        FailedFetch { reason, file: Some(Bytes::copy_from_slice(file)) }
+    }
+
+    /// Returns the reason for the failure.
+    pub fn reason(&self) -> FailureReason {
+        self.reason
+    }
+
+    /// Returns the name of the file that caused the failure, if applicable.
+    pub fn file(&self) -> Option<&Bytes> {
+        self.file.as_ref()
+    }
+}
+
+impl fmt::Display for FailedFetch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.file {
+            Some(ref file) => {
+                write!(
+                    f, "{}: {}", self.reason, String::from_utf8_lossy(file)
+                )
+            }
+            None => self.reason.fmt(f)
+        }
+    }
+}
+
+impl error::Error for FailedFetch { }
+
+
+//------------ FailureReason -------------------------------------------------
+
+/// The reason why processing a publication point failed.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureReason {
+    /// There is no manifest (section 6.1).
+    ManifestMissing,
+
+    /// The manifest or its EE certificate is invalid (section 6.2).
+    ManifestInvalid,
+
+    /// The manifest’s thisUpdate time is in the future (section 6.3).
+    Premature,
+
+    /// The manifest’s nextUpdate time has passed (section 6.3).
+    Stale,
+
+    /// The manifest number did not increase (section 4.2.1).
+    NumberRegression,
+
+    /// The manifest doesn’t list exactly one CRL (section 6.4).
+    CrlCount,
+
+    /// The CRL is missing (section 6.4).
+    CrlMissing,
+
+    /// The CRL’s hash doesn’t match the manifest (section 6.4).
+    CrlHashMismatch,
+
+    /// The CRL is invalid (section 6.4).
+    CrlInvalid,
+
+    /// A file listed on the manifest is missing (section 6.5).
+    FileMissing,
+
+    /// A file’s hash doesn’t match the manifest (section 6.5).
+    FileHashMismatch,
+
+    /// A file listed on the manifest is invalid (section 6.5).
+    FileInvalid,
+}
+
+impl fmt::Display for FailureReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            FailureReason::ManifestMissing => "manifest missing",
+            FailureReason::ManifestInvalid => "manifest invalid",
+            FailureReason::Premature => "manifest not yet valid",
+            FailureReason::Stale => "manifest stale",
+            FailureReason::NumberRegression => {
+                "manifest number did not increase"
+            }
+            FailureReason::CrlCount => "manifest must list exactly one CRL",
+            FailureReason::CrlMissing => "CRL missing",
+            FailureReason::CrlHashMismatch => "CRL hash mismatch",
+            FailureReason::CrlInvalid => "CRL invalid",
+            FailureReason::FileMissing => "file missing",
+            FailureReason::FileHashMismatch => "file hash mismatch",
+            FailureReason::FileInvalid => "file invalid",
+        })
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use chrono::Duration;
+    use crate::crypto::DigestAlgorithm;
+    use super::*;
+    use super::super::FileAndHash;
+
+    fn manifest(number: u64, files: &[&'static str]) -> ManifestContent {
+        ManifestContent::new(
+            number.into(),
+            Time::utc(2020, 1, 1, 0, 0, 0),
+            Time::utc(2020, 1, 2, 0, 0, 0),
+            DigestAlgorithm::default(),
+            files.iter().map(|name| {
+                FileAndHash::new(name.as_bytes(), [0u8; 32])
+            })
+        )
+    }
+
+    fn check(previous: Option<u64>) -> ManifestCheck {
+        ManifestCheck::new(
+            ManifestPolicy::default(),
+            Time::utc(2020, 1, 1, 12, 0, 0),
+            previous.map(Into::into),
+        )
+    }
+
+    fn reason<T: fmt::Debug>(res: Result<T, FailedFetch>) -> FailureReason {
+        res.unwrap_err().reason()
+    }
+
+    #[test]
+    fn manifest_step() {
+        let mft = manifest(2, &["a.crl", "b.roa"]);
+        assert_eq!(
+            check(None).missing().reason(), FailureReason::ManifestMissing
+        );
+        assert!(check(None).valid(&mft).is_ok());
+        assert!(check(Some(1)).valid(&mft).is_ok());
+        assert_eq!(
+            reason(check(Some(2)).valid(&mft)),
+            FailureReason::NumberRegression
+        );
+        let mut policy = ManifestPolicy::default();
+        policy.set_number_regression(NumberRegression::Accept);
+        let now = Time::utc(2020, 1, 1, 12, 0, 0);
+        assert!(
+            ManifestCheck::new(
+                policy, now, Some(3u64.into())
+            ).valid(&mft).is_ok()
+        );
+        assert_eq!(
+            reason(
+                ManifestCheck::new(
+                    policy, now + Duration::days(2), None
+                ).valid(&mft)
+            ),
+            FailureReason::Stale
+        );
+        assert_eq!(
+            reason(
+                ManifestCheck::new(
+                    policy, now - Duration::days(1), None
+                ).valid(&mft)
+            ),
+            FailureReason::Premature
+        );
+        assert_eq!(
+            reason(check(None).valid(&manifest(2, &["b.roa"]))),
+            FailureReason::CrlCount
+        );
+        assert_eq!(
+            reason(check(None).valid(&manifest(2, &["a.crl", "b.crl"]))),
+            FailureReason::CrlCount
+        );
+    }
+
+    #[test]
+    fn crl_and_files_steps() {
+        let mft = manifest(2, &["a.crl", "b.roa", "c.cer"]);
+        let crl = check(None).valid(&mft).unwrap();
+        assert_eq!(crl.crl_name().as_ref(), b"a.crl");
+        assert_eq!(
+            reason(crl.clone().crl(ObjectStatus::HashMismatch)),
+            FailureReason::CrlHashMismatch
+        );
+
+        let files = crl.crl(ObjectStatus::Valid).unwrap();
+        let files = files.file(b"b.roa", ObjectStatus::Valid).unwrap();
+        let files = files.file(b"x.roa", ObjectStatus::Invalid).unwrap();
+        assert_eq!(
+            files.clone().finish().unwrap_err(),
+            FailedFetch::with_file(FailureReason::FileMissing, b"c.cer")
+        );
+        assert_eq!(
+            reason(files.clone().file(b"c.cer", ObjectStatus::Invalid)),
+            FailureReason::FileInvalid
+        );
+        let files = files.file(b"c.cer", ObjectStatus::Valid).unwrap();
+        assert_eq!(
+            files.finish().unwrap(),
+            [
+                Bytes::from_static(b"a.crl"), Bytes::from_static(b"b.roa"),
+                Bytes::from_static(b"c.cer")
+            ]
+        );
+    }
+}
@@ -7,22 +7,33 @@
 //! manifest and the type [`ManifestContent`] for the content of a validated
 //! manifest, as well as some helper types for accessing the content.
 //!
+//! The sub-module [`check`] implements the rules for processing a
+//! publication point based on its manifest.
+//!
 //! [`Manifest`]: struct.Manifest.html
 //! [`ManifestContent`]: struct.ManifestContent.html
-
-use std::{borrow, ops};
+//! [`verify_objects`]: struct.ManifestContent.html#method.verify_objects
+//! [`verify_dir`]: struct.ManifestContent.html#method.verify_dir
+//! [`ManifestBuilder`]: struct.ManifestBuilder.html
+//! [`check`]: check/index.html
+
+use std::{borrow, fs, io, ops, slice};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
 use bcder::{decode, encode};
 use bcder::{
-    BitString, Captured, Ia5String, Mode, OctetString, Oid, Tag, xerr
+    BitString, Captured, ConstOid, Ia5String, Mode, OctetString, Tag, xerr
 };
 use bcder::encode::{PrimitiveContent, Values};
 use bytes::Bytes;
 use serde::{Serialize, Serializer, Deserialize, Deserializer};
-use crate::{oid, uri};
+use crate::{locate, oid, uri};
 use crate::cert::{Cert, ResourceCert};
 use crate::crypto::{DigestAlgorithm, Signer, SigningError};
-use crate::sigobj::{SignedObject, SignedObjectBuilder};
-use crate::x509::{Serial, Time, ValidationError};
+use crate::sigobj::{SignedContent, SignedObject, SignedObjectBuilder};
+use crate::x509::{Clock, DecodeMode, Serial, Time, ValidationError};
+
+pub mod check;
 
 
 //------------ Manifest ------------------------------------------------------
@@ -44,12 +55,32 @@ impl Manifest {
         source: S,
         strict: bool
     ) -> Result<Self, S::Err> {
-        let signed = SignedObject::decode(source, strict)?;
-        if signed.content_type().ne(&oid::CT_RPKI_MANIFEST) {
-            return Err(decode::Malformed.into())
-        }
-        let content = signed.decode_content(
-            |cons| ManifestContent::take_from(cons)
+        Self::decode_with_mode(source, DecodeMode::from_strict(strict))
+    }
+
+    /// Decodes a manifest from a source using the given decoding mode.
+    pub fn decode_with_mode<S: decode::Source>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        let (signed, content) = SignedObject::decode_with_content_and_mode(
+            source, mode
+        )?;
+        Ok(Manifest { signed, content })
+    }
+
+    /// Decodes a manifest, locating any decoding error.
+    ///
+    /// This is the same as [`decode_with_mode`] but the error includes the
+    /// byte offset and ASN.1 path at which decoding failed.
+    ///
+    /// [`decode_with_mode`]: #method.decode_with_mode
+    pub fn decode_located(
+        data: Bytes,
+        mode: DecodeMode,
+    ) -> Result<Self, locate::Error> {
+        let (signed, content) = locate::decode_signed(
+            data, mode, &locate::MANIFEST
         )?;
         Ok(Manifest { signed, content })
     }
@@ -92,10 +123,34 @@ impl Manifest {
         self.signed.cert()
     }
 
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signed.signing_time()
+    }
+
+    /// Returns the binary signing time attribute if present.
+    ///
+    /// The value is the number of seconds since the Unix epoch as defined
+    /// in RFC 6019.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.signed.binary_signing_time()
+    }
+
     /// Returns a reference to the manifest content.
     pub fn content(&self) -> &ManifestContent {
         &self.content
     }
+
+    /// Returns the canonical encoding of the manifest content.
+    ///
+    /// Since every manifest is signed with a new one-off key, the signed
+    /// object itself can’t be reproduced. Instead, this returns the DER
+    /// encoded content. See [`ManifestContent::canonical`] for details.
+    ///
+    /// [`ManifestContent::canonical`]: struct.ManifestContent.html#method.canonical
+    pub fn canonical(&self) -> Bytes {
+        self.content.canonical()
+    }
 }
 
 
@@ -215,6 +270,21 @@ impl ManifestContent {
         }
     }
 
+    /// Returns the canonical encoding of the content.
+    ///
+    /// The content is DER encoded with the file list ordered by file
+    /// name. Thus, manifests with the same number, times, and set of
+    /// files result in the same octets no matter the order of the file
+    /// list.
+    pub fn canonical(&self) -> Bytes {
+        let mut files: Vec<_> = self.iter().collect();
+        files.sort_by(|left, right| left.file().cmp(right.file()));
+        Self::new(
+            self.manifest_number, self.this_update, self.next_update,
+            self.file_hash_alg, files
+        ).to_content_bytes()
+    }
+
     pub fn into_manifest<S: Signer>(
         self,
         mut sigobj: SignedObjectBuilder,
@@ -224,12 +294,7 @@ impl ManifestContent {
         sigobj.set_v4_resources_inherit();
         sigobj.set_v6_resources_inherit();
         sigobj.set_as_resources_inherit();
-        let signed = sigobj.finalize(
-            Oid(oid::CT_RPKI_MANIFEST.0.into()),
-            self.encode_ref().to_captured(Mode::Der).into_bytes(),
-            signer,
-            issuer_key,
-        )?;
+        let signed = sigobj.finalize_content(&self, signer, issuer_key)?;
         Ok(Manifest { signed, content: self })
     }
 }
@@ -295,7 +360,105 @@ impl ManifestContent {
     ///
     /// A manifest is stale if it’s nextUpdate time has passed.
     pub fn is_stale(&self) -> bool {
-        self.next_update < Time::now()
+        self.is_stale_at(Time::now())
+    }
+
+    /// Returns whether the manifest is stale at the given time.
+    pub fn is_stale_at(&self, now: Time) -> bool {
+        self.next_update < now
+    }
+
+    /// Returns whether the manifest is stale according to `clock`.
+    pub fn is_stale_with<C: Clock + ?Sized>(&self, clock: &C) -> bool {
+        self.is_stale_at(clock.now())
+    }
+
+    /// Returns whether the manifest was issued after the given time.
+    pub fn is_premature_at(&self, now: Time) -> bool {
+        self.this_update > now
+    }
+
+    /// Returns whether a new manifest is overdue at the given time.
+    ///
+    /// A CA should issue a new manifest well before the nextUpdate time of
+    /// its current manifest. The manifest is considered overdue if less
+    /// than `margin` is left until its nextUpdate time.
+    pub fn is_overdue_at(
+        &self, now: Time, margin: chrono::Duration
+    ) -> bool {
+        self.next_update < now + margin
+    }
+}
+
+/// # Verification
+///
+impl ManifestContent {
+    /// Verifies a set of objects against the manifest.
+    ///
+    /// The objects are given as pairs of their file name and content. Each
+    /// file listed on the manifest is reported in the result with its
+    /// status. Objects not listed on the manifest are reported separately.
+    pub fn verify_objects<I, F, D>(&self, objects: I) -> ManifestVerification
+    where
+        I: IntoIterator<Item = (F, D)>,
+        F: AsRef<[u8]>,
+        D: AsRef<[u8]>,
+    {
+        let mut files: Vec<_> = self.iter().map(|item| {
+            let (name, hash) = item.into_pair();
+            (
+                VerifiedFile { name, status: VerifiedStatus::Missing },
+                ManifestHash::new(hash, self.file_hash_alg)
+            )
+        }).collect();
+        let index: HashMap<_, _> = files.iter().enumerate().map(|(idx, item)| {
+            (item.0.name.clone(), idx)
+        }).collect();
+        let mut unlisted = Vec::new();
+        for (name, content) in objects {
+            let name = name.as_ref();
+            let (file, hash) = match index.get(name) {
+                Some(idx) => &mut files[*idx],
+                None => {
+                    unlisted.push(Bytes::copy_from_slice(name));
+                    continue
+                }
+            };
+            file.status = if hash.verify(content).is_ok() {
+                VerifiedStatus::Match
+            }
+            else {
+                VerifiedStatus::Mismatch
+            };
+        }
+        ManifestVerification {
+            files: files.into_iter().map(|item| item.0).collect(),
+            unlisted
+        }
+    }
+
+    /// Verifies the files in a local directory against the manifest.
+    ///
+    /// All regular files directly in `dir` are considered. Files whose
+    /// names are not valid UTF-8 are reported as unlisted. Names listed on
+    /// the manifest are never used to construct paths, so the manifest
+    /// can’t be used to access files outside of `dir`.
+    pub fn verify_dir<P: AsRef<Path>>(
+        &self,
+        dir: P
+    ) -> Result<ManifestVerification, io::Error> {
+        let mut objects = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_file() {
+                continue
+            }
+            // A lossy name can never match a manifest entry since these
+            // are IA5 strings.
+            let name = entry.file_name().to_string_lossy().into_owned();
+            objects.push((name, fs::read(entry.path())?));
+        }
+        Ok(self.verify_objects(objects))
     }
 }
 
@@ -353,6 +516,157 @@ impl ManifestContent {
 }
 
 
+//--- SignedContent
+
+impl SignedContent for ManifestContent {
+    const CONTENT_TYPE: ConstOid = oid::CT_RPKI_MANIFEST;
+
+    fn take_content_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from(cons)
+    }
+
+    fn to_content_bytes(&self) -> Bytes {
+        self.encode_ref().to_captured(Mode::Der).into_bytes()
+    }
+}
+
+
+//------------ ManifestBuilder -----------------------------------------------
+
+/// A builder for the content of a manifest.
+///
+/// The builder keeps the set of files published by a CA together with the
+/// hashes of their content. Whenever the set changes, a new manifest can be
+/// produced from it. The file list is always ordered by file name, so the
+/// same set of files results in the same file list.
+#[derive(Clone, Debug)]
+pub struct ManifestBuilder {
+    /// The number of the next manifest.
+    manifest_number: Serial,
+
+    /// The thisUpdate time of the next manifest.
+    this_update: Time,
+
+    /// The nextUpdate time of the next manifest.
+    next_update: Time,
+
+    /// The digest algorithm used for the file hashes.
+    file_hash_alg: DigestAlgorithm,
+
+    /// The files and their hashes.
+    files: BTreeMap<Bytes, Bytes>,
+}
+
+impl ManifestBuilder {
+    /// Creates a new builder with an empty set of files.
+    pub fn new(
+        manifest_number: Serial,
+        this_update: Time,
+        next_update: Time,
+    ) -> Self {
+        ManifestBuilder {
+            manifest_number,
+            this_update,
+            next_update,
+            file_hash_alg: DigestAlgorithm::default(),
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the number of the next manifest.
+    pub fn manifest_number(&self) -> Serial {
+        self.manifest_number
+    }
+
+    /// Sets the number of the next manifest.
+    pub fn set_manifest_number(&mut self, number: Serial) {
+        self.manifest_number = number
+    }
+
+    /// Returns the thisUpdate time of the next manifest.
+    pub fn this_update(&self) -> Time {
+        self.this_update
+    }
+
+    /// Sets the thisUpdate time of the next manifest.
+    pub fn set_this_update(&mut self, this_update: Time) {
+        self.this_update = this_update
+    }
+
+    /// Returns the nextUpdate time of the next manifest.
+    pub fn next_update(&self) -> Time {
+        self.next_update
+    }
+
+    /// Sets the nextUpdate time of the next manifest.
+    pub fn set_next_update(&mut self, next_update: Time) {
+        self.next_update = next_update
+    }
+
+    /// Returns the digest algorithm used for the file hashes.
+    pub fn file_hash_alg(&self) -> DigestAlgorithm {
+        self.file_hash_alg
+    }
+
+    /// Adds a file or replaces the content of an existing file.
+    pub fn insert(&mut self, name: Bytes, content: &[u8]) {
+        let hash = Bytes::copy_from_slice(
+            self.file_hash_alg.digest(content).as_ref()
+        );
+        self.files.insert(name, hash);
+    }
+
+    /// Removes a file.
+    ///
+    /// Returns whether the file was present.
+    pub fn remove(&mut self, name: &[u8]) -> bool {
+        self.files.remove(name).is_some()
+    }
+
+    /// Replaces the set of files with the given name and content pairs.
+    pub fn set_files<I, D>(&mut self, files: I)
+    where I: IntoIterator<Item = (Bytes, D)>, D: AsRef<[u8]> {
+        self.files.clear();
+        for (name, content) in files {
+            self.insert(name, content.as_ref())
+        }
+    }
+
+    /// Returns the number of files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns whether there are no files.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Returns the manifest content for the current state of the builder.
+    pub fn to_content(&self) -> ManifestContent {
+        ManifestContent::new(
+            self.manifest_number,
+            self.this_update,
+            self.next_update,
+            self.file_hash_alg,
+            self.files.iter().map(|(name, hash)| FileAndHash::new(name, hash))
+        )
+    }
+
+    /// Creates a signed manifest for the current state of the builder.
+    pub fn to_manifest<S: Signer>(
+        &self,
+        sigobj: SignedObjectBuilder,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<Manifest, SigningError<S::Error>> {
+        self.to_content().into_manifest(sigobj, signer, issuer_key)
+    }
+}
+
+
 //------------ FileListIter --------------------------------------------------
 
 /// An iterator over the content of a file list.
@@ -490,6 +804,80 @@ impl ManifestHash {
 }
 
 
+//------------ ManifestVerification ------------------------------------------
+
+/// The result of verifying a set of objects against a manifest.
+#[derive(Clone, Debug)]
+pub struct ManifestVerification {
+    /// The files listed on the manifest in manifest order.
+    files: Vec<VerifiedFile>,
+
+    /// The names of objects not listed on the manifest.
+    unlisted: Vec<Bytes>,
+}
+
+impl ManifestVerification {
+    /// Returns an iterator over the files listed on the manifest.
+    pub fn files(&self) -> slice::Iter<'_, VerifiedFile> {
+        self.files.iter()
+    }
+
+    /// Returns the names of the objects not listed on the manifest.
+    pub fn unlisted(&self) -> &[Bytes] {
+        &self.unlisted
+    }
+
+    /// Returns whether all files listed on the manifest are present.
+    ///
+    /// This is true if all files were present and matched their hash.
+    /// Objects not listed on the manifest are not considered.
+    pub fn is_complete(&self) -> bool {
+        self.files.iter().all(|file| file.status == VerifiedStatus::Match)
+    }
+}
+
+
+//------------ VerifiedFile --------------------------------------------------
+
+/// The outcome of verifying a single file listed on a manifest.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifiedFile {
+    /// The name of the file.
+    name: Bytes,
+
+    /// The outcome of the verification.
+    status: VerifiedStatus,
+}
+
+impl VerifiedFile {
+    /// Returns the name of the file.
+    pub fn name(&self) -> &Bytes {
+        &self.name
+    }
+
+    /// Returns the outcome of the verification.
+    pub fn status(&self) -> VerifiedStatus {
+        self.status
+    }
+}
+
+
+//------------ VerifiedStatus ------------------------------------------------
+
+/// The status of a file listed on a manifest.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum VerifiedStatus {
+    /// The file is present and matches its hash.
+    Match,
+
+    /// The file is present but doesn’t match its hash.
+    Mismatch,
+
+    /// The file is not present.
+    Missing,
+}
+
+
 //============ Tests =========================================================
 
 #[cfg(test)]
@@ -503,20 +891,89 @@ mod test {
         let talinfo = TalInfo::from_name("foo".into()).into_arc();
         let at = Time::utc(2019, 5, 1, 0, 0, 0);
         let issuer = Cert::decode(
-            include_bytes!("../test-data/ta.cer").as_ref()
+            include_bytes!("../../test-data/ta.cer").as_ref()
         ).unwrap();
         let issuer = issuer.validate_ta_at(talinfo, false, at).unwrap();
         let obj = Manifest::decode(
-            include_bytes!("../test-data/ta.mft").as_ref(),
+            include_bytes!("../../test-data/ta.mft").as_ref(),
             false
         ).unwrap();
         obj.validate_at(&issuer, false, at).unwrap();
         let obj = Manifest::decode(
-            include_bytes!("../test-data/ca1.mft").as_ref(),
+            include_bytes!("../../test-data/ca1.mft").as_ref(),
             false
         ).unwrap();
         assert!(obj.validate_at(&issuer, false, at).is_err());
     }
+
+    #[test]
+    fn stale_and_overdue() {
+        let at = Time::utc(2019, 5, 1, 0, 0, 0);
+        let content = ManifestBuilder::new(
+            1u64.into(), at, at + chrono::Duration::hours(24)
+        ).to_content();
+        assert!(!content.is_stale_at(at));
+        assert!(!content.is_premature_at(at));
+        assert!(content.is_premature_at(at - chrono::Duration::hours(1)));
+        assert!(content.is_stale_at(at + chrono::Duration::hours(25)));
+        assert!(
+            !content.is_overdue_at(at, chrono::Duration::hours(8))
+        );
+        assert!(
+            content.is_overdue_at(
+                at + chrono::Duration::hours(20), chrono::Duration::hours(8)
+            )
+        );
+    }
+
+    #[test]
+    fn builder_and_verify() {
+        let at = Time::utc(2019, 5, 1, 0, 0, 0);
+        let mut builder = ManifestBuilder::new(
+            1u64.into(), at, at + chrono::Duration::hours(24)
+        );
+        builder.set_files(vec![
+            (Bytes::from_static(b"b.roa"), b"b".as_ref()),
+            (Bytes::from_static(b"a.crl"), b"a".as_ref()),
+            (Bytes::from_static(b"c.roa"), b"c".as_ref()),
+        ]);
+        assert!(builder.remove(b"c.roa"));
+        let content = builder.to_content();
+        assert_eq!(content.len(), 2);
+        let names: Vec<_> = content.iter().map(|item| {
+            item.file().clone()
+        }).collect();
+        assert_eq!(names, [b"a.crl".as_ref(), b"b.roa".as_ref()]);
+
+        let unordered = ManifestContent::new(
+            1u64.into(), at, at + chrono::Duration::hours(24),
+            DigestAlgorithm::default(),
+            content.iter().collect::<Vec<_>>().into_iter().rev()
+        );
+        assert_eq!(unordered.canonical(), content.canonical());
+        assert_eq!(content.canonical(), content.to_content_bytes());
+
+        let res = content.verify_objects(vec![
+            (b"a.crl".as_ref(), b"a".as_ref()),
+            (b"b.roa".as_ref(), b"a".as_ref()),
+            (b"d.roa".as_ref(), b"d".as_ref()),
+        ]);
+        assert!(!res.is_complete());
+        let status: Vec<_> = res.files().map(VerifiedFile::status).collect();
+        assert_eq!(status, [VerifiedStatus::Match, VerifiedStatus::Mismatch]);
+        assert_eq!(res.unlisted(), [Bytes::from_static(b"d.roa")]);
+
+        let dir = std::env::temp_dir().join(
+            format!("rpki-manifest-test-{}", std::process::id())
+        );
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("a.crl"), b"a").unwrap();
+        let res = content.verify_dir(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let status: Vec<_> = res.files().map(VerifiedFile::status).collect();
+        assert_eq!(status, [VerifiedStatus::Match, VerifiedStatus::Missing]);
+        assert!(res.unlisted().is_empty());
+    }
 }
 
 #[cfg(all(test, feature="softkeys"))]
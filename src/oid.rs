@@ -3,7 +3,19 @@
 //! This module collects all the object indentifiers used at various places
 //! in this crate in one central place. They are public so you can refer to
 //! them should that ever become necessary.
+//!
+//! In addition, the module provides [`OidTable`], a static table for quickly
+//! mapping object identifiers to values, and [`NAMES`], a table of the
+//! ASN.1 names of all the identifiers defined here. The latter can be used
+//! via [`name`] and [`named`] to refer to identifiers symbolically in
+//! diagnostic output.
+//!
+//! [`OidTable`]: struct.OidTable.html
+//! [`NAMES`]: constant.NAMES.html
+//! [`name`]: fn.name.html
+//! [`named`]: fn.named.html
 
+use std::fmt;
 use bcder::{ConstOid, Oid};
 
 /// [RFC 4055](https://tools.ietf.org/html/rfc4055) `id-sha256`
@@ -12,6 +24,11 @@ use bcder::{ConstOid, Oid};
 pub const SHA256: ConstOid
     = Oid(&[96, 134, 72, 1, 101, 3, 4, 2, 1]);
 
+/// [RFC 3279](https://tools.ietf.org/html/rfc3279) `id-sha1`
+///
+/// Identifies the SHA-1 one-way hash function.
+pub const SHA1: ConstOid = Oid(&[43, 14, 3, 2, 26]);
+
 /// [RFC 4055](https://tools.ietf.org/html/rfc4055) `rsaEncryption`
 ///
 /// Identifies an RSA public key with no limitation to either RSASSA-PSS or
@@ -25,6 +42,24 @@ pub const RSA_ENCRYPTION: ConstOid
 pub const SHA256_WITH_RSA_ENCRYPTION: ConstOid
     = Oid(&[42, 134, 72, 134, 247, 13, 1, 1, 11]);
 
+/// [RFC 5480](https://tools.ietf.org/html/rfc5480) `id-ecPublicKey`
+///
+/// Identifies an elliptic curve public key.
+pub const EC_PUBLIC_KEY: ConstOid
+    = Oid(&[42, 134, 72, 206, 61, 2, 1]);
+
+/// [RFC 5480](https://tools.ietf.org/html/rfc5480) `secp256r1`
+///
+/// Identifies the P-256 elliptic curve.
+pub const SECP256R1: ConstOid
+    = Oid(&[42, 134, 72, 206, 61, 3, 1, 7]);
+
+/// [RFC 5758](https://tools.ietf.org/html/rfc5758) `ecdsa-with-SHA256`
+///
+/// Identifies the ECDSA signature algorithm with SHA-256.
+pub const ECDSA_WITH_SHA256: ConstOid
+    = Oid(&[42, 134, 72, 206, 61, 4, 3, 2]);
+
 
 pub const SIGNED_DATA: Oid<&[u8]>
     = Oid(&[42, 134, 72, 134, 247, 13, 1, 7, 2]);
@@ -58,12 +93,27 @@ pub const CE_EXTENDED_KEY_USAGE: Oid<&[u8]> = Oid(&[85, 29, 37]);
 pub const CE_KEY_USAGE: Oid<&[u8]> = Oid(&[85, 29, 15]);
 pub const CE_SUBJECT_KEY_IDENTIFIER: Oid<&[u8]> = Oid(&[85, 29, 14]);
 
+/// [RFC 8209](https://tools.ietf.org/html/rfc8209) `id-kp-bgpsec-router`
+pub const KP_BGPSEC_ROUTER: Oid<&[u8]> = Oid(&[43, 6, 1, 5, 5, 7, 3, 30]);
+
 pub const CP_IPADDR_ASNUMBER: Oid<&[u8]> = Oid(&[43, 6, 1, 5, 5, 7, 14, 2]);
 pub const CP_IPADDR_ASNUMBER_V2: Oid<&[u8]> = Oid(&[43, 6, 1, 5, 5, 7, 14, 3]);
 
 pub const CT_RPKI_MANIFEST: ConstOid
     = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 26]);
 
+/// [RFC 6493](https://tools.ietf.org/html/rfc6493) `id-ct-rpkiGhostbusters`
+pub const CT_RPKI_GHOSTBUSTERS: ConstOid
+    = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 35]);
+
+/// [RFC 9323](https://tools.ietf.org/html/rfc9323) `id-ct-signedChecklist`
+pub const CT_SIGNED_CHECKLIST: ConstOid
+    = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 48]);
+
+/// draft-ietf-sidrops-aspa-profile `id-ct-ASPA`
+pub const CT_ASPA: ConstOid
+    = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 16, 1, 49]);
+
 pub const PE_AUTHORITY_INFO_ACCESS: Oid<&[u8]>
     = Oid(&[43, 6, 1, 5, 5, 7, 1, 1]);
 pub const PE_IP_ADDR_BLOCK: Oid<&[u8]> = Oid(&[43, 6, 1, 5, 5, 7, 1, 7]);
@@ -82,3 +132,159 @@ pub const ROUTE_ORIGIN_AUTHZ: ConstOid
 pub const EXTENSION_REQUEST: ConstOid
     = Oid(&[42, 134, 72, 134, 247, 13, 1, 9, 14]);
 
+
+//------------ NAMES ---------------------------------------------------------
+
+/// The ASN.1 names of all object identifiers defined in this module.
+pub const NAMES: OidTable<&str> = OidTable::new(&[
+    (RSA_ENCRYPTION, "rsaEncryption"),
+    (SHA256_WITH_RSA_ENCRYPTION, "sha256WithRSAEncryption"),
+    (SIGNED_DATA, "id-signedData"),
+    (CONTENT_TYPE, "id-contentType"),
+    (MESSAGE_DIGEST, "id-messageDigest"),
+    (SIGNING_TIME, "id-signingTime"),
+    (EXTENSION_REQUEST, "extensionRequest"),
+    (ROUTE_ORIGIN_AUTHZ, "id-ct-routeOriginAuthz"),
+    (CT_RPKI_MANIFEST, "id-ct-rpkiManifest"),
+    (PROTOCOL_CONTENT_TYPE, "id-ct-xml"),
+    (CT_RPKI_GHOSTBUSTERS, "id-ct-rpkiGhostbusters"),
+    (CT_SIGNED_CHECKLIST, "id-ct-signedChecklist"),
+    (CT_ASPA, "id-ct-ASPA"),
+    (AA_BINARY_SIGNING_TIME, "id-aa-binarySigningTime"),
+    (EC_PUBLIC_KEY, "id-ecPublicKey"),
+    (SECP256R1, "secp256r1"),
+    (ECDSA_WITH_SHA256, "ecdsa-with-SHA256"),
+    (PE_AUTHORITY_INFO_ACCESS, "id-pe-authorityInfoAccess"),
+    (PE_IP_ADDR_BLOCK, "id-pe-ipAddrBlocks"),
+    (PE_AUTONOMOUS_SYS_IDS, "id-pe-autonomousSysIds"),
+    (PE_SUBJECT_INFO_ACCESS, "id-pe-subjectInfoAccess"),
+    (PE_IP_ADDR_BLOCK_V2, "id-pe-ipAddrBlocks-v2"),
+    (PE_AUTONOMOUS_SYS_IDS_V2, "id-pe-autonomousSysIds-v2"),
+    (KP_BGPSEC_ROUTER, "id-kp-bgpsec-router"),
+    (CP_IPADDR_ASNUMBER, "id-cp-ipAddr-asNumber"),
+    (CP_IPADDR_ASNUMBER_V2, "id-cp-ipAddr-asNumber-v2"),
+    (AD_CA_ISSUERS, "id-ad-caIssuers"),
+    (AD_CA_REPOSITORY, "id-ad-caRepository"),
+    (AD_RPKI_MANIFEST, "id-ad-rpkiManifest"),
+    (AD_SIGNED_OBJECT, "id-ad-signedObject"),
+    (AD_RPKI_NOTIFY, "id-ad-rpkiNotify"),
+    (SHA1, "id-sha1"),
+    (AT_COMMON_NAME, "id-at-commonName"),
+    (AT_SERIAL_NUMBER, "id-at-serialNumber"),
+    (CE_SUBJECT_KEY_IDENTIFIER, "id-ce-subjectKeyIdentifier"),
+    (CE_KEY_USAGE, "id-ce-keyUsage"),
+    (CE_BASIC_CONSTRAINTS, "id-ce-basicConstraints"),
+    (CE_CRL_NUMBER, "id-ce-cRLNumber"),
+    (CE_CRL_DISTRIBUTION_POINTS, "id-ce-cRLDistributionPoints"),
+    (CE_CERTIFICATE_POLICIES, "id-ce-certificatePolicies"),
+    (CE_AUTHORITY_KEY_IDENTIFIER, "id-ce-authorityKeyIdentifier"),
+    (CE_EXTENDED_KEY_USAGE, "id-ce-extKeyUsage"),
+    (SHA256, "id-sha256"),
+]);
+
+/// Returns the ASN.1 name of an object identifier if it is known.
+pub fn name<T: AsRef<[u8]>>(oid: &Oid<T>) -> Option<&'static str> {
+    NAMES.get(oid).cloned()
+}
+
+/// Returns a value displaying an object identifier symbolically.
+///
+/// If the identifier is one of those defined in this module, the value
+/// displays as its ASN.1 name. Otherwise it displays in dotted notation.
+pub fn named<T: AsRef<[u8]>>(oid: &Oid<T>) -> Named<'_, T> {
+    Named(oid)
+}
+
+
+//------------ OidTable ------------------------------------------------------
+
+/// A static table mapping object identifiers to values.
+///
+/// The entries of the table must be sorted by the encoded octets of their
+/// object identifiers so lookups can use a binary search. Each identifier
+/// must only appear once.
+#[derive(Clone, Copy, Debug)]
+pub struct OidTable<T: 'static>(&'static [(ConstOid, T)]);
+
+impl<T: 'static> OidTable<T> {
+    /// Creates a new table from a sorted slice of entries.
+    pub const fn new(entries: &'static [(ConstOid, T)]) -> Self {
+        OidTable(entries)
+    }
+
+    /// Returns the value for the given object identifier.
+    pub fn get<U: AsRef<[u8]>>(&self, oid: &Oid<U>) -> Option<&'static T> {
+        let table: &'static [(ConstOid, T)] = self.0;
+        table.binary_search_by(|entry| {
+            (entry.0).0.cmp(oid.0.as_ref())
+        }).ok().map(|idx| &table[idx].1)
+    }
+
+    /// Returns whether the table contains the given object identifier.
+    pub fn contains<U: AsRef<[u8]>>(&self, oid: &Oid<U>) -> bool {
+        self.get(oid).is_some()
+    }
+
+    /// Returns an iterator over the entries of the table.
+    pub fn iter(
+        &self
+    ) -> impl Iterator<Item = (&'static ConstOid, &'static T)> {
+        let table: &'static [(ConstOid, T)] = self.0;
+        table.iter().map(|entry| (&entry.0, &entry.1))
+    }
+
+    /// Returns whether the entries are correctly sorted.
+    #[cfg(test)]
+    pub(crate) fn is_sorted(&self) -> bool {
+        self.0.windows(2).all(|pair| (pair[0].0).0 < (pair[1].0).0)
+    }
+}
+
+
+//------------ Named ---------------------------------------------------------
+
+/// An object identifier displayed symbolically.
+///
+/// A value of this type is returned by [`named`].
+///
+/// [`named`]: fn.named.html
+#[derive(Debug)]
+pub struct Named<'a, T: AsRef<[u8]>>(&'a Oid<T>);
+
+impl<'a, T: AsRef<[u8]>> fmt::Display for Named<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match name(self.0) {
+            Some(name) => f.write_str(name),
+            None => self.0.fmt(f)
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn names_sorted() {
+        assert!(NAMES.is_sorted());
+    }
+
+    #[test]
+    fn name_lookup() {
+        for (oid, name) in NAMES.iter() {
+            assert_eq!(super::name(oid), Some(*name));
+        }
+        assert_eq!(name(&Oid(&[85u8, 29, 36][..])), None);
+        assert_eq!(
+            format!("{}", named(&CE_KEY_USAGE)),
+            "id-ce-keyUsage"
+        );
+        assert_eq!(
+            format!("{}", named(&Oid(&[85u8, 29, 36][..]))),
+            "2.5.29.36"
+        );
+    }
+}
@@ -0,0 +1,326 @@
+//! Differences between snapshots of validated payload.
+
+use std::{cmp, fmt, ops};
+#[cfg(feature = "rtr")] use crate::rtr::payload::Action;
+use super::{Payload, RouteOrigin, RouterKey, Snapshot};
+
+
+//------------ PayloadDiff ---------------------------------------------------
+
+/// The difference between two snapshots of validated payload.
+///
+/// The diff contains the route origins and router keys that have to be
+/// announced and withdrawn to get from an older snapshot to a newer one.
+/// All four lists are sorted and free of duplicates. ASPAs are not
+/// included.
+///
+/// A diff can be tagged with the serial number of the data set it leads
+/// to. This is what an RTR cache needs to keep a history of changes and
+/// answer serial queries.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PayloadDiff {
+    /// The serial number of the resulting data set if known.
+    serial: Option<Serial>,
+
+    /// The route origins to announce.
+    announced_origins: Vec<RouteOrigin>,
+
+    /// The route origins to withdraw.
+    withdrawn_origins: Vec<RouteOrigin>,
+
+    /// The router keys to announce.
+    announced_router_keys: Vec<RouterKey>,
+
+    /// The router keys to withdraw.
+    withdrawn_router_keys: Vec<RouterKey>,
+}
+
+impl PayloadDiff {
+    /// Creates the diff between an old and a new snapshot.
+    pub(super) fn from_snapshots(old: &Snapshot, new: &Snapshot) -> Self {
+        let mut res = PayloadDiff::default();
+        diff_sorted(
+            old.origins(), new.origins(),
+            &mut res.announced_origins, &mut res.withdrawn_origins
+        );
+        diff_sorted(
+            old.router_keys(), new.router_keys(),
+            &mut res.announced_router_keys, &mut res.withdrawn_router_keys
+        );
+        res
+    }
+
+    /// Returns the serial number of the resulting data set if tagged.
+    pub fn serial(&self) -> Option<Serial> {
+        self.serial
+    }
+
+    /// Tags the diff with the serial number of the resulting data set.
+    pub fn set_serial(&mut self, serial: Serial) {
+        self.serial = Some(serial)
+    }
+
+    /// Returns the diff tagged with the given serial number.
+    pub fn with_serial(mut self, serial: Serial) -> Self {
+        self.set_serial(serial);
+        self
+    }
+
+    /// Returns the route origins that have been added.
+    pub fn announced_origins(&self) -> &[RouteOrigin] {
+        &self.announced_origins
+    }
+
+    /// Returns the route origins that have been removed.
+    pub fn withdrawn_origins(&self) -> &[RouteOrigin] {
+        &self.withdrawn_origins
+    }
+
+    /// Returns the router keys that have been added.
+    pub fn announced_router_keys(&self) -> &[RouterKey] {
+        &self.announced_router_keys
+    }
+
+    /// Returns the router keys that have been removed.
+    pub fn withdrawn_router_keys(&self) -> &[RouterKey] {
+        &self.withdrawn_router_keys
+    }
+
+    /// Returns the number of changed items.
+    pub fn len(&self) -> usize {
+        self.announced_origins.len() + self.withdrawn_origins.len()
+        + self.announced_router_keys.len()
+        + self.withdrawn_router_keys.len()
+    }
+
+    /// Returns whether there are no changes at all.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over all items that have been added.
+    pub fn announced(&self) -> impl Iterator<Item = Payload> + '_ {
+        self.announced_origins.iter().copied().map(Payload::Origin).chain(
+            self.announced_router_keys.iter().cloned()
+                .map(Payload::RouterKey)
+        )
+    }
+
+    /// Returns an iterator over all items that have been removed.
+    pub fn withdrawn(&self) -> impl Iterator<Item = Payload> + '_ {
+        self.withdrawn_origins.iter().copied().map(Payload::Origin).chain(
+            self.withdrawn_router_keys.iter().cloned()
+                .map(Payload::RouterKey)
+        )
+    }
+
+    /// Returns an iterator over the changes as RTR actions.
+    ///
+    /// All withdrawals are returned before the announcements.
+    #[cfg(feature = "rtr")]
+    pub fn actions(&self) -> impl Iterator<Item = (Action, Payload)> + '_ {
+        self.withdrawn().map(|item| (Action::Withdraw, item)).chain(
+            self.announced().map(|item| (Action::Announce, item))
+        )
+    }
+}
+
+/// Adds the differences between two sorted slices to two vecs.
+fn diff_sorted<T: Clone + Ord>(
+    old: &[T], new: &[T],
+    announced: &mut Vec<T>, withdrawn: &mut Vec<T>,
+) {
+    let mut old = old.iter().peekable();
+    let mut new = new.iter().peekable();
+    loop {
+        match (old.peek(), new.peek()) {
+            (Some(left), Some(right)) => {
+                match left.cmp(right) {
+                    cmp::Ordering::Less => {
+                        withdrawn.push((*left).clone());
+                        old.next();
+                    }
+                    cmp::Ordering::Greater => {
+                        announced.push((*right).clone());
+                        new.next();
+                    }
+                    cmp::Ordering::Equal => {
+                        old.next();
+                        new.next();
+                    }
+                }
+            }
+            (Some(_), None) => {
+                withdrawn.extend(old.cloned());
+                break
+            }
+            (None, Some(_)) => {
+                announced.extend(new.cloned());
+                break
+            }
+            (None, None) => break
+        }
+    }
+}
+
+
+//------------ Serial --------------------------------------------------------
+
+/// A serial number.
+///
+/// Serial numbers wrap around and are compared using the serial number
+/// arithmetic defined in [RFC 1982]. Because of this, two serial numbers
+/// may not be comparable, so `Serial` only implements `PartialOrd`.
+///
+/// [RFC 1982]: https://tools.ietf.org/html/rfc1982
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Serial(pub u32);
+
+
+//--- From
+
+impl From<u32> for Serial {
+    fn from(value: u32) -> Self {
+        Serial(value)
+    }
+}
+
+impl From<Serial> for u32 {
+    fn from(serial: Serial) -> Self {
+        serial.0
+    }
+}
+
+
+//--- Add
+
+impl ops::Add<u32> for Serial {
+    type Output = Self;
+
+    /// Returns the serial number increased by `n`.
+    ///
+    /// # Panics
+    ///
+    /// RFC 1982 only defines addition of values up to 2^31 - 1. The method
+    /// panics if `n` is larger than that.
+    fn add(self, n: u32) -> Self {
+        assert!(n <= 0x7FFF_FFFF);
+        Serial(self.0.wrapping_add(n))
+    }
+}
+
+
+//--- PartialOrd
+
+impl PartialOrd for Serial {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        let (left, right) = (self.0, other.0);
+        if left == right {
+            Some(cmp::Ordering::Equal)
+        }
+        else if left.wrapping_sub(right) == 0x8000_0000 {
+            None
+        }
+        else if right.wrapping_sub(left) < 0x8000_0000 {
+            Some(cmp::Ordering::Less)
+        }
+        else {
+            Some(cmp::Ordering::Greater)
+        }
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+    use super::super::Prefix;
+
+    fn origin(prefix: &str, asn: u32) -> Payload {
+        let prefix = Prefix::from_str(prefix).unwrap();
+        RouteOrigin::new(
+            prefix, prefix.prefix_len(), asn.into()
+        ).unwrap().into()
+    }
+
+    #[test]
+    fn snapshot_diff() {
+        let old: Snapshot = vec![
+            origin("192.0.2.0/24", 64496),
+            origin("198.51.100.0/24", 64496),
+            origin("2001:db8::/32", 64497),
+        ].into_iter().collect();
+        let new: Snapshot = vec![
+            origin("192.0.2.0/24", 64496),
+            origin("203.0.113.0/24", 64498),
+            origin("2001:db8::/32", 64498),
+        ].into_iter().collect();
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.serial(), None);
+        assert_eq!(diff.len(), 4);
+        assert_eq!(
+            diff.announced().collect::<Vec<_>>(),
+            vec![
+                origin("203.0.113.0/24", 64498),
+                origin("2001:db8::/32", 64498),
+            ]
+        );
+        assert_eq!(
+            diff.withdrawn().collect::<Vec<_>>(),
+            vec![
+                origin("198.51.100.0/24", 64496),
+                origin("2001:db8::/32", 64497),
+            ]
+        );
+        assert!(diff.withdrawn_router_keys().is_empty());
+
+        let diff = diff.with_serial(Serial(12));
+        assert_eq!(diff.serial(), Some(Serial(12)));
+
+        assert!(new.diff(&new).is_empty());
+        assert_eq!(
+            Snapshot::new().diff(&new).announced_origins(), new.origins()
+        );
+        assert_eq!(
+            new.diff(&Snapshot::new()).withdrawn_origins(), new.origins()
+        );
+    }
+
+    #[cfg(feature = "rtr")]
+    #[test]
+    fn actions() {
+        let old: Snapshot = vec![origin("192.0.2.0/24", 64496)]
+            .into_iter().collect();
+        let new: Snapshot = vec![origin("192.0.2.0/24", 64497)]
+            .into_iter().collect();
+        assert_eq!(
+            old.diff(&new).actions().collect::<Vec<_>>(),
+            vec![
+                (Action::Withdraw, origin("192.0.2.0/24", 64496)),
+                (Action::Announce, origin("192.0.2.0/24", 64497)),
+            ]
+        );
+    }
+
+    #[test]
+    fn serial_arithmetic() {
+        assert!(Serial(1) < Serial(2));
+        assert!(Serial(0xFFFF_FFFF) < Serial(0));
+        assert!(Serial(0) > Serial(0xFFFF_FFFF));
+        assert_eq!(Serial(0xFFFF_FFFF) + 2, Serial(1));
+        assert_eq!(Serial(0).partial_cmp(&Serial(0x8000_0000)), None);
+    }
+}
@@ -0,0 +1,705 @@
+//! Validated payload.
+//!
+//! The output of RPKI validation is a set of payload items that are handed
+//! to routers or used directly for routing decisions. This module defines
+//! the types for these items: [`RouteOrigin`]s derived from ROAs,
+//! [`RouterKey`]s derived from BGPsec router certificates, and [`Aspa`]s
+//! derived from AS provider attestations. All of them implement `Ord` and
+//! `Hash`, so sets of payload can be sorted and freed of duplicates.
+//!
+//! In addition, the module implements route origin validation as defined
+//! in [RFC 6811] via the [`VrpSet`] type and the [`validate`] function.
+//! Complete sets of payload can be collected into a [`Snapshot`] which
+//! can be exported and imported in a number of common formats and
+//! compared with each other, resulting in a [`PayloadDiff`].
+//!
+//! [`RouteOrigin`]: struct.RouteOrigin.html
+//! [`RouterKey`]: struct.RouterKey.html
+//! [`Aspa`]: struct.Aspa.html
+//! [`VrpSet`]: struct.VrpSet.html
+//! [`validate`]: fn.validate.html
+//! [`Snapshot`]: struct.Snapshot.html
+//! [`PayloadDiff`]: struct.PayloadDiff.html
+//! [RFC 6811]: https://tools.ietf.org/html/rfc6811
+
+use std::{error, fmt};
+use std::iter::FromIterator;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::resources::AsId;
+
+pub use crate::cert::RouterKey;
+pub use self::diff::{PayloadDiff, Serial};
+pub use self::snapshot::{ReadError, Snapshot};
+
+mod diff;
+mod snapshot;
+
+
+//------------ Prefix --------------------------------------------------------
+
+/// An IPv4 or IPv6 prefix.
+///
+/// IPv4 prefixes order before IPv6 prefixes.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Prefix {
+    /// The address of the prefix.
+    ///
+    /// All bits beyond the prefix length are zero.
+    addr: IpAddr,
+
+    /// The length of the prefix.
+    len: u8,
+}
+
+impl Prefix {
+    /// Creates a new prefix.
+    ///
+    /// Returns an error if the length is too long for the address or if
+    /// any bits beyond the length are set.
+    pub fn new(addr: IpAddr, len: u8) -> Result<Self, PrefixError> {
+        if len > addr_bits(addr) || to_bits(addr) & !mask(len) != 0 {
+            return Err(PrefixError)
+        }
+        Ok(Prefix { addr, len })
+    }
+
+    /// Creates a prefix from the raw bits as returned by `to_bits`.
+    pub(crate) fn from_bits(v4: bool, bits: u128, len: u8) -> Self {
+        let bits = bits & mask(len);
+        let addr = if v4 {
+            IpAddr::from(Ipv4Addr::from((bits >> 96) as u32))
+        }
+        else {
+            IpAddr::from(Ipv6Addr::from(bits))
+        };
+        Prefix { addr, len }
+    }
+
+    /// Returns the bits of the address with IPv4 addresses in the top bits.
+    pub(crate) fn to_bits(self) -> u128 {
+        to_bits(self.addr)
+    }
+
+    /// Returns the address of the prefix.
+    pub fn addr(self) -> IpAddr {
+        self.addr
+    }
+
+    /// Returns the length of the prefix.
+    pub fn prefix_len(self) -> u8 {
+        self.len
+    }
+
+    /// Returns whether this is an IPv4 prefix.
+    pub fn is_v4(self) -> bool {
+        self.addr.is_ipv4()
+    }
+
+    /// Returns whether the prefix covers another prefix.
+    ///
+    /// A prefix covers another prefix of the same address family if it is
+    /// equal or less specific.
+    pub fn covers(self, other: Prefix) -> bool {
+        self.is_v4() == other.is_v4()
+            && self.len <= other.len
+            && to_bits(other.addr) & mask(self.len) == to_bits(self.addr)
+    }
+}
+
+/// Returns the number of bits in an address.
+fn addr_bits(addr: IpAddr) -> u8 {
+    if addr.is_ipv4() { 32 } else { 128 }
+}
+
+/// Returns the bits of an address with IPv4 addresses in the top bits.
+fn to_bits(addr: IpAddr) -> u128 {
+    match addr {
+        IpAddr::V4(addr) => u128::from(u32::from(addr)) << 96,
+        IpAddr::V6(addr) => u128::from(addr),
+    }
+}
+
+/// Returns a mask with the top `len` bits set.
+fn mask(len: u8) -> u128 {
+    if len == 0 {
+        0
+    }
+    else if len >= 128 {
+        !0
+    }
+    else {
+        !(!0 >> len)
+    }
+}
+
+
+//--- FromStr and Display
+
+impl FromStr for Prefix {
+    type Err = PrefixError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let slash = s.find('/').ok_or(PrefixError)?;
+        let len = u8::from_str(&s[slash + 1..]).map_err(|_| PrefixError)?;
+        let addr = &s[..slash];
+        let addr = match Ipv4Addr::from_str(addr) {
+            Ok(addr) => addr.into(),
+            Err(_) => {
+                IpAddr::from(
+                    Ipv6Addr::from_str(addr).map_err(|_| PrefixError)?
+                )
+            }
+        };
+        Self::new(addr, len)
+    }
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.len)
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for Prefix {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Prefix {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(de::Error::custom)
+    }
+}
+
+
+//------------ RouteOrigin ---------------------------------------------------
+
+/// A validated route origin.
+///
+/// This is a single entry of a ROA, also known as a validated ROA payload
+/// or VRP: an IP prefix with a maximum length and the AS number authorized
+/// to originate routes for it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct RouteOrigin {
+    /// The prefix.
+    prefix: Prefix,
+
+    /// The maximum length of announced prefixes.
+    max_len: u8,
+
+    /// The AS number authorized to originate the prefix.
+    asn: AsId,
+}
+
+impl RouteOrigin {
+    /// Creates a new route origin from its components.
+    ///
+    /// Returns `None` if the prefix length is longer than the maximum
+    /// length or the maximum length is longer than the address.
+    pub fn new(prefix: Prefix, max_len: u8, asn: AsId) -> Option<Self> {
+        if prefix.prefix_len() > max_len || max_len > addr_bits(prefix.addr)
+        {
+            return None
+        }
+        Some(RouteOrigin { prefix, max_len, asn })
+    }
+
+    /// Returns the prefix.
+    pub fn prefix(&self) -> Prefix {
+        self.prefix
+    }
+
+    /// Returns the maximum length of announced prefixes.
+    pub fn max_len(&self) -> u8 {
+        self.max_len
+    }
+
+    /// Returns the AS number authorized to originate the prefix.
+    pub fn asn(&self) -> AsId {
+        self.asn
+    }
+
+    /// Returns whether this is an origin for an IPv4 prefix.
+    pub fn is_v4(&self) -> bool {
+        self.prefix.is_v4()
+    }
+
+    /// Returns whether the route origin covers a route’s prefix.
+    pub fn covers(&self, prefix: Prefix) -> bool {
+        self.prefix.covers(prefix)
+    }
+
+    /// Returns whether the route origin matches a route.
+    ///
+    /// This is the case if the origin covers the route’s prefix, the
+    /// prefix is not longer than the maximum length, and the route’s
+    /// origin AS is that of the route origin. A route origin for AS 0
+    /// never matches.
+    pub fn matches(&self, prefix: Prefix, origin: AsId) -> bool {
+        self.covers(prefix)
+            && prefix.prefix_len() <= self.max_len
+            && origin == self.asn
+            && self.asn != AsId::from(0)
+    }
+}
+
+
+//------------ Aspa ----------------------------------------------------------
+
+/// A validated AS provider authorization.
+///
+/// This lists the AS numbers that are authorized to act as upstream
+/// providers for a customer AS.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Aspa {
+    /// The customer AS.
+    customer: AsId,
+
+    /// The provider ASes in ascending order without duplicates.
+    providers: Vec<AsId>,
+}
+
+impl Aspa {
+    /// Creates a new ASPA from the customer and its providers.
+    ///
+    /// The providers are sorted and duplicates removed.
+    pub fn new(customer: AsId, mut providers: Vec<AsId>) -> Self {
+        providers.sort();
+        providers.dedup();
+        Aspa { customer, providers }
+    }
+
+    /// Returns the customer AS.
+    pub fn customer(&self) -> AsId {
+        self.customer
+    }
+
+    /// Returns the provider ASes in ascending order.
+    pub fn providers(&self) -> &[AsId] {
+        &self.providers
+    }
+}
+
+
+//------------ Payload -------------------------------------------------------
+
+/// A single item of validated payload.
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Payload {
+    /// A route origin.
+    Origin(RouteOrigin),
+
+    /// A BGPsec router key.
+    RouterKey(RouterKey),
+
+    /// An AS provider authorization.
+    Aspa(Aspa),
+}
+
+impl Payload {
+    /// Returns the lowest RTR protocol version that can carry this item.
+    pub fn min_version(&self) -> u8 {
+        match *self {
+            Payload::Origin(_) => 0,
+            Payload::RouterKey(_) => 1,
+            Payload::Aspa(_) => 2,
+        }
+    }
+
+    /// Returns whether two items describe the same thing.
+    ///
+    /// This is the case if the items are equal or if both are ASPAs for
+    /// the same customer AS. A newer ASPA replaces the older one and an
+    /// ASPA is withdrawn by customer AS only.
+    pub fn same_item(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Payload::Aspa(left), Payload::Aspa(right)) => {
+                left.customer() == right.customer()
+            }
+            (left, right) => left == right
+        }
+    }
+}
+
+
+//--- From
+
+impl From<RouteOrigin> for Payload {
+    fn from(origin: RouteOrigin) -> Self {
+        Payload::Origin(origin)
+    }
+}
+
+impl From<RouterKey> for Payload {
+    fn from(key: RouterKey) -> Self {
+        Payload::RouterKey(key)
+    }
+}
+
+impl From<Aspa> for Payload {
+    fn from(aspa: Aspa) -> Self {
+        Payload::Aspa(aspa)
+    }
+}
+
+
+//------------ RpkiState -----------------------------------------------------
+
+/// The result of route origin validation.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RpkiState {
+    /// At least one route origin matches the route.
+    Valid,
+
+    /// Route origins cover the route’s prefix but none matches.
+    Invalid,
+
+    /// No route origin covers the route’s prefix.
+    NotFound,
+}
+
+
+//------------ VrpSet --------------------------------------------------------
+
+/// A set of route origins prepared for route origin validation.
+///
+/// The route origins are kept in a binary trie per address family, so
+/// all origins covering a prefix can be found by walking down the bits of
+/// the prefix once.
+#[derive(Clone, Debug, Default)]
+pub struct VrpSet {
+    /// The trie for IPv4 route origins.
+    v4: Node,
+
+    /// The trie for IPv6 route origins.
+    v6: Node,
+
+    /// The number of route origins in the set.
+    len: usize,
+}
+
+impl VrpSet {
+    /// Creates a new, empty set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of route origins in the set.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Adds a route origin to the set.
+    ///
+    /// Returns whether the origin was added, i.e., wasn’t already present.
+    pub fn insert(&mut self, origin: RouteOrigin) -> bool {
+        let prefix = origin.prefix();
+        let bits = to_bits(prefix.addr());
+        let mut node = if prefix.is_v4() {
+            &mut self.v4
+        }
+        else {
+            &mut self.v6
+        };
+        for depth in 0..prefix.prefix_len() {
+            node = node.children[bit(bits, depth)].get_or_insert_with(
+                Default::default
+            );
+        }
+        let item = (origin.max_len(), origin.asn());
+        if node.origins.contains(&item) {
+            return false
+        }
+        node.origins.push(item);
+        self.len += 1;
+        true
+    }
+
+    /// Returns an iterator over all route origins covering a prefix.
+    ///
+    /// The origins are returned from the least to the most specific.
+    pub fn covering(&self, prefix: Prefix) -> Covering<'_> {
+        Covering {
+            node: Some(if prefix.is_v4() { &self.v4 } else { &self.v6 }),
+            v4: prefix.is_v4(),
+            bits: to_bits(prefix.addr()),
+            len: prefix.prefix_len(),
+            depth: 0,
+            pos: 0,
+        }
+    }
+
+    /// Validates a route.
+    ///
+    /// The route is given by its prefix and its origin AS. The origin is
+    /// `None` if it cannot be determined, e.g., because the AS path ends in
+    /// an AS_SET. Such a route is never valid.
+    pub fn validate(&self, prefix: Prefix, origin: Option<AsId>) -> RpkiState {
+        let mut res = RpkiState::NotFound;
+        for vrp in self.covering(prefix) {
+            if let Some(origin) = origin {
+                if vrp.matches(prefix, origin) {
+                    return RpkiState::Valid
+                }
+            }
+            res = RpkiState::Invalid
+        }
+        res
+    }
+}
+
+
+//--- FromIterator and Extend
+
+impl FromIterator<RouteOrigin> for VrpSet {
+    fn from_iter<I: IntoIterator<Item = RouteOrigin>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl Extend<RouteOrigin> for VrpSet {
+    fn extend<I: IntoIterator<Item = RouteOrigin>>(&mut self, iter: I) {
+        for origin in iter {
+            self.insert(origin);
+        }
+    }
+}
+
+
+//------------ Node ----------------------------------------------------------
+
+/// A node in the trie of a `VrpSet`.
+#[derive(Clone, Debug, Default)]
+struct Node {
+    /// The nodes for the next bit being zero and one, respectively.
+    children: [Option<Box<Node>>; 2],
+
+    /// The maximum length and AS of the route origins for this prefix.
+    origins: Vec<(u8, AsId)>,
+}
+
+/// Returns the bit at position `depth` counted from the top.
+fn bit(bits: u128, depth: u8) -> usize {
+    ((bits >> (127 - depth)) & 1) as usize
+}
+
+
+//------------ Covering ------------------------------------------------------
+
+/// An iterator over the route origins covering a prefix.
+#[derive(Clone, Debug)]
+pub struct Covering<'a> {
+    /// The current node or `None` if we are done.
+    node: Option<&'a Node>,
+
+    /// Whether the prefix is an IPv4 prefix.
+    v4: bool,
+
+    /// The bits of the prefix.
+    bits: u128,
+
+    /// The length of the prefix.
+    len: u8,
+
+    /// The depth of the current node.
+    depth: u8,
+
+    /// The index of the next origin in the current node.
+    pos: usize,
+}
+
+impl<'a> Iterator for Covering<'a> {
+    type Item = RouteOrigin;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.node?;
+            if let Some(&(max_len, asn)) = node.origins.get(self.pos) {
+                self.pos += 1;
+                return Some(RouteOrigin {
+                    prefix: Prefix::from_bits(self.v4, self.bits, self.depth),
+                    max_len,
+                    asn
+                })
+            }
+            if self.depth >= self.len {
+                self.node = None;
+                return None
+            }
+            self.node = node.children[bit(self.bits, self.depth)].as_deref();
+            self.depth += 1;
+            self.pos = 0;
+        }
+    }
+}
+
+
+//------------ validate ------------------------------------------------------
+
+/// Performs route origin validation for a route.
+///
+/// This is a shortcut for [`VrpSet::validate`].
+///
+/// [`VrpSet::validate`]: struct.VrpSet.html#method.validate
+pub fn validate(
+    prefix: Prefix, origin: Option<AsId>, vrps: &VrpSet
+) -> RpkiState {
+    vrps.validate(prefix, origin)
+}
+
+
+//------------ PrefixError ---------------------------------------------------
+
+/// A string or address didn’t contain a valid prefix.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrefixError;
+
+impl fmt::Display for PrefixError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid prefix")
+    }
+}
+
+impl error::Error for PrefixError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn prefix(s: &str) -> Prefix {
+        Prefix::from_str(s).unwrap()
+    }
+
+    fn origin(s: &str, max_len: u8, asn: u32) -> RouteOrigin {
+        RouteOrigin::new(prefix(s), max_len, asn.into()).unwrap()
+    }
+
+    #[test]
+    fn prefix_covers() {
+        let p = prefix("192.0.2.0/24");
+        assert!(p.covers(prefix("192.0.2.128/25")));
+        assert!(p.covers(prefix("192.0.2.0/24")));
+        assert!(!p.covers(prefix("192.0.2.0/23")));
+        assert!(!p.covers(prefix("192.0.3.0/24")));
+        assert!(!p.covers(prefix("c000:200::/24")));
+        assert_eq!(p.to_string(), "192.0.2.0/24");
+        assert!(prefix("0.0.0.0/0").covers(p));
+        assert!(Prefix::from_str("2001:db8::/32").is_ok());
+        assert!(Prefix::from_str("192.0.2.1/24").is_err());
+        assert!(Prefix::from_str("192.0.2.0/33").is_err());
+        assert!(Prefix::from_str("192.0.2.0").is_err());
+    }
+
+    #[test]
+    fn route_origin() {
+        assert!(RouteOrigin::new(prefix("192.0.2.0/24"), 23, 1.into())
+            .is_none());
+        assert!(RouteOrigin::new(prefix("192.0.2.0/24"), 33, 1.into())
+            .is_none());
+        assert!(RouteOrigin::new(prefix("2001:db8::/32"), 128, 1.into())
+            .is_some());
+    }
+
+    #[test]
+    fn ordering() {
+        let mut items: Vec<Payload> = vec![
+            origin("2001:db8::/32", 48, 64496).into(),
+            origin("192.0.2.0/24", 24, 64497).into(),
+            Aspa::new(64496.into(), vec![3.into(), 2.into()]).into(),
+            origin("192.0.2.0/24", 24, 64497).into(),
+            origin("192.0.2.0/24", 24, 64496).into(),
+        ];
+        items.sort();
+        items.dedup();
+        assert_eq!(items, vec![
+            origin("192.0.2.0/24", 24, 64496).into(),
+            origin("192.0.2.0/24", 24, 64497).into(),
+            origin("2001:db8::/32", 48, 64496).into(),
+            Aspa::new(64496.into(), vec![2.into(), 3.into()]).into(),
+        ]);
+    }
+
+    #[test]
+    fn origin_validation() {
+        let vrps: VrpSet = vec![
+            origin("192.0.2.0/24", 24, 64496),
+            origin("192.0.2.0/24", 24, 64496),
+            origin("198.51.100.0/22", 24, 64497),
+            origin("198.51.100.0/24", 24, 0),
+            origin("2001:db8::/32", 48, 64498),
+            origin("0.0.0.0/0", 32, 0),
+        ].into_iter().collect();
+        assert_eq!(vrps.len(), 5);
+
+        let as64496 = Some(64496.into());
+        let as64497 = Some(64497.into());
+        assert_eq!(
+            validate(prefix("192.0.2.0/24"), as64496, &vrps),
+            RpkiState::Valid
+        );
+        assert_eq!(
+            validate(prefix("192.0.2.0/25"), as64496, &vrps),
+            RpkiState::Invalid
+        );
+        assert_eq!(
+            validate(prefix("192.0.2.0/24"), as64497, &vrps),
+            RpkiState::Invalid
+        );
+        assert_eq!(
+            validate(prefix("192.0.2.0/24"), None, &vrps),
+            RpkiState::Invalid
+        );
+        assert_eq!(
+            validate(prefix("198.51.101.0/24"), as64497, &vrps),
+            RpkiState::Valid
+        );
+        assert_eq!(
+            validate(prefix("198.51.100.0/24"), as64497, &vrps),
+            RpkiState::Valid
+        );
+        assert_eq!(
+            validate(prefix("203.0.113.0/24"), as64497, &vrps),
+            RpkiState::Invalid
+        );
+        assert_eq!(
+            validate(prefix("2001:db8:1::/48"), Some(64498.into()), &vrps),
+            RpkiState::Valid
+        );
+        assert_eq!(
+            validate(prefix("2001:db9::/32"), Some(64498.into()), &vrps),
+            RpkiState::NotFound
+        );
+
+        let covering: Vec<_> = vrps.covering(
+            prefix("198.51.100.0/24")
+        ).collect();
+        assert_eq!(covering, vec![
+            origin("0.0.0.0/0", 32, 0),
+            origin("198.51.100.0/22", 24, 64497),
+            origin("198.51.100.0/24", 24, 0),
+        ]);
+    }
+}
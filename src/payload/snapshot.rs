@@ -0,0 +1,479 @@
+//! Complete sets of validated payload.
+
+use std::{error, fmt, io};
+use std::iter::FromIterator;
+use std::str::FromStr;
+use crate::resources::AsId;
+use super::{Aspa, Payload, PayloadDiff, Prefix, RouteOrigin, RouterKey};
+
+
+//------------ Snapshot ------------------------------------------------------
+
+/// A complete set of validated payload.
+///
+/// The snapshot keeps the route origins, router keys, and ASPAs in
+/// separate lists, each sorted and free of duplicates.
+///
+/// The route origins of a snapshot can be exported in the formats commonly
+/// used for exchanging them between tools: the JSON format with a `roas`
+/// array used by the RIPE NCC validator and others, CSV, and
+/// configuration snippets for OpenBGPD and BIRD 2. The JSON and CSV
+/// formats can be imported again. Router keys and ASPAs are not part of
+/// any of these formats. Reading and writing JSON requires the `json`
+/// feature.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Snapshot {
+    /// The route origins.
+    origins: Vec<RouteOrigin>,
+
+    /// The router keys.
+    router_keys: Vec<RouterKey>,
+
+    /// The ASPAs.
+    aspas: Vec<Aspa>,
+}
+
+impl Snapshot {
+    /// Creates a new, empty snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the route origins in ascending order.
+    pub fn origins(&self) -> &[RouteOrigin] {
+        &self.origins
+    }
+
+    /// Returns the router keys in ascending order.
+    pub fn router_keys(&self) -> &[RouterKey] {
+        &self.router_keys
+    }
+
+    /// Returns the ASPAs in ascending order.
+    pub fn aspas(&self) -> &[Aspa] {
+        &self.aspas
+    }
+
+    /// Returns the number of payload items in the snapshot.
+    pub fn len(&self) -> usize {
+        self.origins.len() + self.router_keys.len() + self.aspas.len()
+    }
+
+    /// Returns whether the snapshot is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns an iterator over all payload items.
+    ///
+    /// The items are returned in the order of `Payload`.
+    pub fn iter(&self) -> impl Iterator<Item = Payload> + '_ {
+        self.origins.iter().copied().map(Payload::Origin).chain(
+            self.router_keys.iter().cloned().map(Payload::RouterKey)
+        ).chain(
+            self.aspas.iter().cloned().map(Payload::Aspa)
+        )
+    }
+
+    /// Returns the changes necessary to get from this snapshot to `other`.
+    ///
+    /// The returned diff is not tagged with a serial number.
+    pub fn diff(&self, other: &Snapshot) -> PayloadDiff {
+        PayloadDiff::from_snapshots(self, other)
+    }
+
+    /// Sorts the items and removes duplicates.
+    fn normalize(&mut self) {
+        self.origins.sort();
+        self.origins.dedup();
+        self.router_keys.sort();
+        self.router_keys.dedup();
+        self.aspas.sort();
+        self.aspas.dedup();
+    }
+}
+
+
+/// # Exporting and Importing
+///
+impl Snapshot {
+    /// Writes the route origins as CSV.
+    ///
+    /// The output starts with a header line followed by one line per route
+    /// origin with the AS number, prefix, and maximum length.
+    pub fn write_csv<W: io::Write>(
+        &self, target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(target, "ASN,IP Prefix,Max Length")?;
+        for origin in &self.origins {
+            writeln!(
+                target, "{},{},{}",
+                origin.asn(), origin.prefix(), origin.max_len()
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Reads route origins from CSV.
+    ///
+    /// Each line has to contain the AS number, the prefix, and the maximum
+    /// length separated by commas. Any further fields, e.g., a trust anchor
+    /// name, are ignored. Empty lines and a header line are skipped.
+    pub fn read_csv<R: io::BufRead>(source: R) -> Result<Self, ReadError> {
+        let mut res = Snapshot::new();
+        for (index, line) in source.lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || (index == 0 && line.starts_with("ASN")) {
+                continue
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let origin = match (fields.next(), fields.next(), fields.next()) {
+                (Some(asn), Some(prefix), Some(max_len)) => {
+                    Self::parse_origin(asn, prefix, max_len)
+                }
+                _ => None
+            };
+            match origin {
+                Some(origin) => res.origins.push(origin),
+                None => return Err(ReadError::BadEntry(index + 1))
+            }
+        }
+        res.normalize();
+        Ok(res)
+    }
+
+    /// Writes the route origins as an OpenBGPD `roa-set`.
+    pub fn write_openbgpd<W: io::Write>(
+        &self, target: &mut W
+    ) -> Result<(), io::Error> {
+        writeln!(target, "roa-set {{")?;
+        for origin in &self.origins {
+            write!(target, "    {}", origin.prefix())?;
+            if origin.max_len() != origin.prefix().prefix_len() {
+                write!(target, " maxlen {}", origin.max_len())?;
+            }
+            writeln!(target, " source-as {}", u32::from(origin.asn()))?;
+        }
+        writeln!(target, "}}")
+    }
+
+    /// Writes the route origins as BIRD 2 ROA table entries.
+    ///
+    /// The output contains one `route` statement per route origin and can
+    /// be included into the definition of a static protocol for a ROA
+    /// table.
+    pub fn write_bird2<W: io::Write>(
+        &self, target: &mut W
+    ) -> Result<(), io::Error> {
+        for origin in &self.origins {
+            writeln!(
+                target, "route {} max {} as {};",
+                origin.prefix(), origin.max_len(), u32::from(origin.asn())
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Parses a route origin from its textual components.
+    fn parse_origin(
+        asn: &str, prefix: &str, max_len: &str
+    ) -> Option<RouteOrigin> {
+        RouteOrigin::new(
+            Prefix::from_str(prefix).ok()?,
+            u8::from_str(max_len).ok()?,
+            AsId::from_str(asn).ok()?,
+        )
+    }
+}
+
+#[cfg(feature = "json")]
+impl Snapshot {
+    /// Reads route origins from JSON.
+    ///
+    /// The JSON data has to be an object with a `roas` array. Each element
+    /// is an object with the `asn`, `prefix`, and `maxLength` fields. The
+    /// AS number can be given either as a number or as a string with or
+    /// without the `AS` prefix. Any other fields are ignored.
+    pub fn from_json(json: &str) -> Result<Self, ReadError> {
+        Self::from_raw(serde_json::from_str(json)?)
+    }
+
+    /// Reads route origins as JSON from a reader.
+    ///
+    /// See [`from_json`] for the format.
+    ///
+    /// [`from_json`]: #method.from_json
+    pub fn read_json<R: io::Read>(source: R) -> Result<Self, ReadError> {
+        Self::from_raw(serde_json::from_reader(source)?)
+    }
+
+    /// Returns the route origins as JSON.
+    pub fn to_json(&self) -> String {
+        // Serializing the raw types can’t fail.
+        serde_json::to_string_pretty(&self.to_raw()).unwrap()
+    }
+
+    /// Writes the route origins as JSON to a writer.
+    pub fn write_json<W: io::Write>(
+        &self, target: W
+    ) -> Result<(), io::Error> {
+        serde_json::to_writer_pretty(target, &self.to_raw())
+            .map_err(Into::into)
+    }
+
+    /// Converts the raw JSON data into a snapshot.
+    fn from_raw(raw: json::RawSnapshot) -> Result<Self, ReadError> {
+        let mut res = Snapshot::new();
+        for (index, roa) in raw.roas.into_iter().enumerate() {
+            let asn = match roa.asn {
+                json::RawAsn::Number(asn) => asn.to_string(),
+                json::RawAsn::String(asn) => asn,
+            };
+            match Self::parse_origin(
+                &asn, &roa.prefix, &roa.max_length.to_string()
+            ) {
+                Some(origin) => res.origins.push(origin),
+                None => return Err(ReadError::BadEntry(index))
+            }
+        }
+        res.normalize();
+        Ok(res)
+    }
+
+    /// Converts the snapshot into the raw JSON data.
+    fn to_raw(&self) -> json::RawSnapshot {
+        json::RawSnapshot {
+            roas: self.origins.iter().map(|origin| {
+                json::RawRoa {
+                    asn: json::RawAsn::String(origin.asn().to_string()),
+                    prefix: origin.prefix().to_string(),
+                    max_length: origin.max_len(),
+                }
+            }).collect()
+        }
+    }
+}
+
+
+//--- FromIterator and Extend
+
+impl FromIterator<Payload> for Snapshot {
+    fn from_iter<I: IntoIterator<Item = Payload>>(iter: I) -> Self {
+        let mut res = Snapshot::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl Extend<Payload> for Snapshot {
+    fn extend<I: IntoIterator<Item = Payload>>(&mut self, iter: I) {
+        for item in iter {
+            match item {
+                Payload::Origin(origin) => self.origins.push(origin),
+                Payload::RouterKey(key) => self.router_keys.push(key),
+                Payload::Aspa(aspa) => self.aspas.push(aspa),
+            }
+        }
+        self.normalize()
+    }
+}
+
+
+//------------ json ----------------------------------------------------------
+
+/// The raw types of the JSON format.
+#[cfg(feature = "json")]
+mod json {
+    use serde::{Deserialize, Serialize};
+
+    /// The JSON object.
+    #[derive(Deserialize, Serialize)]
+    pub struct RawSnapshot {
+        pub roas: Vec<RawRoa>,
+    }
+
+    /// A single route origin.
+    #[derive(Deserialize, Serialize)]
+    pub struct RawRoa {
+        pub asn: RawAsn,
+        pub prefix: String,
+        #[serde(rename = "maxLength")]
+        pub max_length: u8,
+    }
+
+    /// An AS number given either as a number or a string.
+    #[derive(Deserialize, Serialize)]
+    #[serde(untagged)]
+    pub enum RawAsn {
+        Number(u32),
+        String(String),
+    }
+}
+
+
+//------------ ReadError -----------------------------------------------------
+
+/// Reading a snapshot failed.
+#[derive(Debug)]
+pub enum ReadError {
+    /// Reading the data failed.
+    Io(io::Error),
+
+    /// The data wasn’t valid JSON or didn’t follow the format.
+    #[cfg(feature = "json")]
+    Json(serde_json::Error),
+
+    /// An entry was invalid.
+    ///
+    /// The value is the line number for CSV and the index in the `roas`
+    /// array for JSON.
+    BadEntry(usize),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        ReadError::Io(err)
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for ReadError {
+    fn from(err: serde_json::Error) -> Self {
+        ReadError::Json(err)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Io(ref err) => err.fmt(f),
+            #[cfg(feature = "json")]
+            ReadError::Json(ref err) => err.fmt(f),
+            ReadError::BadEntry(index) => {
+                write!(f, "invalid entry {}", index)
+            }
+        }
+    }
+}
+
+impl error::Error for ReadError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn snapshot() -> Snapshot {
+        [
+            ("2001:db8::/32", 48, 64497),
+            ("192.0.2.0/24", 24, 64496),
+            ("198.51.100.0/22", 24, 64496),
+            ("192.0.2.0/24", 24, 64496),
+        ].iter().map(|&(prefix, max_len, asn)| {
+            RouteOrigin::new(
+                Prefix::from_str(prefix).unwrap(), max_len, asn.into()
+            ).unwrap().into()
+        }).collect()
+    }
+
+    #[test]
+    fn collect() {
+        let snapshot = snapshot();
+        assert_eq!(snapshot.len(), 3);
+        assert_eq!(
+            snapshot.origins()[0].prefix().to_string(), "192.0.2.0/24"
+        );
+        assert_eq!(snapshot.iter().count(), 3);
+        assert!(Snapshot::new().is_empty());
+    }
+
+    #[test]
+    fn csv() {
+        let snapshot = snapshot();
+        let mut data = Vec::new();
+        snapshot.write_csv(&mut data).unwrap();
+        assert_eq!(
+            String::from_utf8(data.clone()).unwrap(),
+            "ASN,IP Prefix,Max Length\n\
+             AS64496,192.0.2.0/24,24\n\
+             AS64496,198.51.100.0/22,24\n\
+             AS64497,2001:db8::/32,48\n"
+        );
+        assert_eq!(Snapshot::read_csv(data.as_slice()).unwrap(), snapshot);
+
+        let data = b"ASN,IP Prefix,Max Length,Trust Anchor\n\
+                     AS64496,192.0.2.0/24,24,ripe\n\
+                     \n\
+                     64496, 198.51.100.0/22, 24\n";
+        assert_eq!(
+            Snapshot::read_csv(data.as_ref()).unwrap().origins().len(), 2
+        );
+        assert!(matches_bad_entry(
+            Snapshot::read_csv(b"AS64496,192.0.2.0/24,16\n".as_ref()), 1
+        ));
+        assert!(matches_bad_entry(
+            Snapshot::read_csv(b"ASN\nAS1,192.0.2.0/24\n".as_ref()), 2
+        ));
+    }
+
+    fn matches_bad_entry(
+        res: Result<Snapshot, ReadError>, line: usize
+    ) -> bool {
+        match res {
+            Err(ReadError::BadEntry(err)) => err == line,
+            _ => false
+        }
+    }
+
+    #[test]
+    fn router_configs() {
+        let snapshot = snapshot();
+        let mut data = Vec::new();
+        snapshot.write_openbgpd(&mut data).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "roa-set {\n\
+             \x20   192.0.2.0/24 source-as 64496\n\
+             \x20   198.51.100.0/22 maxlen 24 source-as 64496\n\
+             \x20   2001:db8::/32 maxlen 48 source-as 64497\n\
+             }\n"
+        );
+
+        let mut data = Vec::new();
+        snapshot.write_bird2(&mut data).unwrap();
+        assert_eq!(
+            String::from_utf8(data).unwrap(),
+            "route 192.0.2.0/24 max 24 as 64496;\n\
+             route 198.51.100.0/22 max 24 as 64496;\n\
+             route 2001:db8::/32 max 48 as 64497;\n"
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn json() {
+        let snapshot = snapshot();
+        assert_eq!(
+            Snapshot::from_json(&snapshot.to_json()).unwrap(), snapshot
+        );
+        let json = r#"{
+            "metadata": { "generated": 0 },
+            "roas": [
+                { "asn": "AS64496", "prefix": "192.0.2.0/24",
+                  "maxLength": 24, "ta": "ripe" },
+                { "asn": 64497, "prefix": "2001:db8::/32", "maxLength": 48 }
+            ]
+        }"#;
+        assert_eq!(Snapshot::from_json(json).unwrap().origins().len(), 2);
+        let json = r#"{ "roas": [
+            { "asn": "AS1", "prefix": "192.0.2.0/24", "maxLength": 23 }
+        ] }"#;
+        match Snapshot::from_json(json) {
+            Err(ReadError::BadEntry(0)) => { }
+            _ => panic!("expected bad entry")
+        }
+    }
+}
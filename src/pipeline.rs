@@ -0,0 +1,741 @@
+//! Ready-made relying party pipelines.
+//!
+//! The functions in this module combine the building blocks provided by
+//! the rest of the crate into complete relying party tasks. They make a
+//! number of choices on behalf of the user and offer only a few settings.
+//! Applications that need more control should use the underlying types
+//! directly.
+//!
+//! [`run_validation`] performs a validation run for all TALs in a
+//! directory over a local copy of the RPKI repositories. If the
+//! `rsync-fetch` feature is enabled, it can update that copy via rsync
+//! before the objects are used. The outcome is collected into a
+//! [`Report`] which also provides the combined validated payload.
+//!
+//! If the `rtr` feature is enabled, [`serve_rtr`] runs an RTR server that
+//! periodically asks the application for a new snapshot of validated
+//! payload, for instance by calling [`run_validation`], and serves it to
+//! routers. The history of snapshots needed to answer serial queries is
+//! kept by [`PayloadHistory`].
+//!
+//! Both functions are re-exported at the crate root.
+//!
+//! [`run_validation`]: fn.run_validation.html
+//! [`Report`]: struct.Report.html
+//! [`serve_rtr`]: fn.serve_rtr.html
+//! [`PayloadHistory`]: struct.PayloadHistory.html
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::payload::Snapshot;
+use crate::store::{MemoryStore, ValidatedCache};
+use crate::tal::{ReadError, Tal, TalInfo};
+use crate::validation::{
+    Error, Fallback, FallbackPolicy, LocalCache, ObjectSource, Outcome,
+    Validator
+};
+use crate::x509::{Clock, SystemClock};
+
+
+//------------ ValidationConfig ----------------------------------------------
+
+/// The configuration of a validation pipeline.
+///
+/// A configuration is created from the directory containing the TALs and
+/// the directory of the local copy of the repositories via [`new`]. The
+/// other settings start out with default values which can be changed via
+/// the setter methods.
+///
+/// The local copy is expected in the layout used by the rsync runner,
+/// i.e., each rsync module is kept in the directory
+/// `<cache>/<authority>/<module>` with the authority in lowercase.
+///
+/// [`new`]: #method.new
+#[derive(Clone, Debug)]
+pub struct ValidationConfig {
+    /// The directory containing the TALs.
+    tal_dir: PathBuf,
+
+    /// The directory of the local copy of the repositories.
+    cache_dir: PathBuf,
+
+    /// Whether to use strict validation.
+    strict: bool,
+
+    /// The number of worker threads.
+    threads: usize,
+
+    /// The store for objects of previously validated publication points.
+    previous: Option<Arc<ValidatedCache<MemoryStore>>>,
+
+    /// The clock providing the time to validate against.
+    clock: Arc<dyn Clock>,
+
+    /// Whether to update the local copy via rsync.
+    #[cfg(feature = "rsync-fetch")]
+    rsync: bool,
+}
+
+impl ValidationConfig {
+    /// Creates a new configuration.
+    ///
+    /// The configuration uses relaxed validation with a single thread
+    /// against the system time, doesn’t keep previously validated objects,
+    /// and doesn’t update the local copy.
+    pub fn new<T, C>(tal_dir: T, cache_dir: C) -> Self
+    where T: Into<PathBuf>, C: Into<PathBuf> {
+        ValidationConfig {
+            tal_dir: tal_dir.into(),
+            cache_dir: cache_dir.into(),
+            strict: false,
+            threads: 1,
+            previous: None,
+            clock: Arc::new(SystemClock),
+            #[cfg(feature = "rsync-fetch")]
+            rsync: false,
+        }
+    }
+
+    /// Returns the directory containing the TALs.
+    pub fn tal_dir(&self) -> &Path {
+        &self.tal_dir
+    }
+
+    /// Returns the directory of the local copy of the repositories.
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns whether strict validation is used.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets whether strict validation is used.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict
+    }
+
+    /// Returns the number of worker threads used for validation.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets the number of worker threads used for validation.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads
+    }
+
+    /// Returns whether previously validated objects are used.
+    pub fn use_previous(&self) -> bool {
+        self.previous.is_some()
+    }
+
+    /// Sets whether previously validated objects are used.
+    ///
+    /// If enabled, the objects of every publication point that passes
+    /// validation are kept in memory. If a later run with the same
+    /// configuration finds the point’s manifest stale, its objects not
+    /// matching the manifest, or its CRL missing, the kept objects are
+    /// used instead.
+    pub fn set_use_previous(&mut self, use_previous: bool) {
+        if !use_previous {
+            self.previous = None
+        }
+        else if self.previous.is_none() {
+            self.previous = Some(
+                Arc::new(ValidatedCache::new(MemoryStore::new()))
+            )
+        }
+    }
+
+    /// Returns the clock providing the time to validate against.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Sets the clock providing the time to validate against.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock
+    }
+
+    /// Returns whether the local copy is updated via rsync.
+    #[cfg(feature = "rsync-fetch")]
+    pub fn rsync(&self) -> bool {
+        self.rsync
+    }
+
+    /// Sets whether the local copy is updated via rsync.
+    ///
+    /// If enabled, each rsync module is updated once per run before its
+    /// first object is used. If updating a module fails, the objects
+    /// already present in the local copy are used.
+    #[cfg(feature = "rsync-fetch")]
+    pub fn set_rsync(&mut self, rsync: bool) {
+        self.rsync = rsync
+    }
+
+    /// Creates a validator for the given source.
+    fn validator<S>(&self, source: S) -> Validator<S> {
+        let mut res = Validator::new(source);
+        res.set_strict(self.strict);
+        res.set_threads(self.threads);
+        res.set_clock(self.clock.clone());
+        if let Some(ref previous) = self.previous {
+            let mut policy = FallbackPolicy::default();
+            policy.set_stale(Fallback::UsePrevious);
+            policy.set_hash_mismatch(Fallback::UsePrevious);
+            policy.set_crl_missing(Fallback::UsePrevious);
+            res.set_fallback_policy(policy);
+            res.set_validated_store(previous.clone());
+        }
+        res
+    }
+}
+
+
+//------------ run_validation ------------------------------------------------
+
+/// Performs a validation run for all TALs of a configuration.
+///
+/// Every file in the TAL directory is read as a TAL and validated via
+/// [`Validator::validate_tal_parallel`]. A TAL that cannot be validated
+/// doesn’t end the run but is recorded in the report. If reading the TALs
+/// fails, an error is returned instead.
+///
+/// [`Validator::validate_tal_parallel`]: ../validation/struct.Validator.html#method.validate_tal_parallel
+pub fn run_validation(
+    config: &ValidationConfig
+) -> Result<Report, ReadError> {
+    #[cfg(feature = "rsync-fetch")]
+    {
+        if config.rsync {
+            return run_with_source(
+                config, rsync::RsyncSource::new(&config.cache_dir)
+            )
+        }
+    }
+    run_with_source(config, LocalCache::new(&config.cache_dir))
+}
+
+/// Performs a validation run using the given object source.
+fn run_with_source<S>(
+    config: &ValidationConfig, source: S
+) -> Result<Report, ReadError>
+where S: ObjectSource + Clone + Send + Sync + 'static {
+    let validator = config.validator(source);
+    let mut report = Report::default();
+    let mut tals = Vec::new();
+    for tal in Tal::read_dir(&config.tal_dir)? {
+        tals.push(tal?);
+    }
+    tals.sort_by(|left, right| left.info().name().cmp(right.info().name()));
+    for tal in tals {
+        match validator.validate_tal_parallel(&tal) {
+            Ok(outcome) => {
+                report.snapshot.extend(outcome.payload().iter().cloned());
+                report.outcomes.push((tal.info().clone(), outcome));
+            }
+            Err(err) => report.failures.push((tal.info().clone(), err)),
+        }
+    }
+    Ok(report)
+}
+
+
+//------------ Report --------------------------------------------------------
+
+/// The result of a validation pipeline run.
+#[derive(Debug, Default)]
+pub struct Report {
+    /// The outcome for each successfully validated TAL.
+    outcomes: Vec<(Arc<TalInfo>, Outcome)>,
+
+    /// The TALs that could not be validated.
+    failures: Vec<(Arc<TalInfo>, Error)>,
+
+    /// The combined payload of all outcomes.
+    snapshot: Snapshot,
+}
+
+impl Report {
+    /// Returns the outcome for each validated TAL.
+    ///
+    /// The outcomes are ordered by TAL name.
+    pub fn outcomes(&self) -> &[(Arc<TalInfo>, Outcome)] {
+        &self.outcomes
+    }
+
+    /// Returns the TALs that could not be validated and why.
+    pub fn failures(&self) -> &[(Arc<TalInfo>, Error)] {
+        &self.failures
+    }
+
+    /// Returns the combined validated payload of all TALs.
+    pub fn snapshot(&self) -> &Snapshot {
+        &self.snapshot
+    }
+
+    /// Converts the report into the combined validated payload.
+    pub fn into_snapshot(self) -> Snapshot {
+        self.snapshot
+    }
+}
+
+
+//------------ rsync ---------------------------------------------------------
+
+/// Updating the local copy via rsync.
+#[cfg(feature = "rsync-fetch")]
+mod rsync {
+    use std::{fs, io};
+    use std::collections::HashSet;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use bytes::Bytes;
+    use log::warn;
+    use crate::rsync::Runner;
+    use crate::uri;
+    use crate::validation::ObjectSource;
+
+    /// An object source updating each rsync module before first use.
+    #[derive(Clone, Debug)]
+    pub struct RsyncSource {
+        /// The runner updating the modules.
+        runner: Arc<Runner>,
+
+        /// The modules updated so far.
+        updated: Arc<Mutex<HashSet<uri::RsyncModule>>>,
+    }
+
+    impl RsyncSource {
+        pub fn new(base: &Path) -> Self {
+            RsyncSource {
+                runner: Arc::new(Runner::new(base)),
+                updated: Default::default(),
+            }
+        }
+    }
+
+    impl ObjectSource for RsyncSource {
+        fn get(
+            &self, uri: &uri::Rsync
+        ) -> Result<Option<Bytes>, io::Error> {
+            let module = uri.module();
+            if !self.updated.lock().unwrap().contains(module) {
+                // The runner makes concurrent requests for the same module
+                // wait for a single rsync process.
+                if let Err(err) = self.runner.fetch(module) {
+                    warn!("Updating {} failed: {}", module, err);
+                }
+                self.updated.lock().unwrap().insert(module.clone());
+            }
+            let path = match self.runner.uri_path(uri) {
+                Some(path) => path,
+                None => return Ok(None)
+            };
+            match fs::read(path) {
+                Ok(content) => Ok(Some(content.into())),
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                    Ok(None)
+                }
+                Err(err) => Err(err)
+            }
+        }
+    }
+}
+
+
+//------------ serve_rtr -----------------------------------------------------
+
+#[cfg(feature = "rtr")]
+pub use self::rtr::{serve_rtr, PayloadHistory, RtrConfig};
+
+/// Serving validated payload via RTR.
+#[cfg(feature = "rtr")]
+mod rtr {
+    use std::{io, vec};
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, RwLock};
+    use std::time::Duration;
+    use log::error;
+    use tokio::net::TcpListener;
+    use crate::payload::Snapshot;
+    use crate::rtr::{
+        Action, NotifySender, Payload, Serial, Server, State, Timing,
+        VrpSource
+    };
+
+    //------------ RtrConfig -------------------------------------------------
+
+    /// The configuration of an RTR pipeline.
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub struct RtrConfig {
+        /// The address to listen on.
+        listen: SocketAddr,
+
+        /// The time between asking for new payload.
+        interval: Duration,
+
+        /// The number of earlier snapshots to keep.
+        history: usize,
+
+        /// The timing parameters sent to routers.
+        timing: Timing,
+    }
+
+    impl RtrConfig {
+        /// Creates a new configuration listening on the given address.
+        ///
+        /// The configuration asks for new payload every ten minutes,
+        /// keeps ten earlier snapshots, and uses the default timing
+        /// parameters.
+        pub fn new(listen: SocketAddr) -> Self {
+            RtrConfig {
+                listen,
+                interval: Duration::from_secs(600),
+                history: 10,
+                timing: Timing::default(),
+            }
+        }
+
+        /// Returns the address to listen on.
+        pub fn listen(&self) -> SocketAddr {
+            self.listen
+        }
+
+        /// Returns the time between asking for new payload.
+        pub fn interval(&self) -> Duration {
+            self.interval
+        }
+
+        /// Sets the time between asking for new payload.
+        ///
+        /// The time is counted from when the previous snapshot has been
+        /// produced.
+        pub fn set_interval(&mut self, interval: Duration) {
+            self.interval = interval
+        }
+
+        /// Returns the number of earlier snapshots to keep.
+        pub fn history(&self) -> usize {
+            self.history
+        }
+
+        /// Sets the number of earlier snapshots to keep.
+        ///
+        /// Routers with data older than the kept snapshots are sent a
+        /// Cache Reset and have to fetch the complete data again.
+        pub fn set_history(&mut self, history: usize) {
+            self.history = history
+        }
+
+        /// Returns the timing parameters sent to routers.
+        pub fn timing(&self) -> Timing {
+            self.timing
+        }
+
+        /// Sets the timing parameters sent to routers.
+        pub fn set_timing(&mut self, timing: Timing) {
+            self.timing = timing
+        }
+    }
+
+
+    //------------ serve_rtr -------------------------------------------------
+
+    /// Runs an RTR server for payload produced by a closure.
+    ///
+    /// The server listens on the address of the configuration. The closure
+    /// `source` is run on a blocking thread right away and then again
+    /// after each interval. Whenever it returns a snapshot that differs
+    /// from the current one, connected routers are notified. If it returns
+    /// `None`, the current data is kept. Until the first snapshot has been
+    /// produced, routers are told that no data is available.
+    ///
+    /// The function only returns if binding the listener or accepting a
+    /// connection fails. It must be run within a Tokio runtime.
+    pub async fn serve_rtr<F>(
+        config: &RtrConfig, source: F
+    ) -> Result<(), io::Error>
+    where F: FnMut() -> Option<Snapshot> + Send + 'static {
+        let listener = TcpListener::bind(config.listen).await?;
+        let history = PayloadHistory::new(config.history, config.timing);
+        let notify = NotifySender::new();
+        let updater = tokio::spawn(update(
+            source, history.clone(), notify.clone(), config.interval
+        ));
+        let res = Server::new(listener, notify, history).run().await;
+        updater.abort();
+        res
+    }
+
+    /// Updates the history with snapshots produced by `source`.
+    async fn update<F>(
+        mut source: F, history: PayloadHistory, notify: NotifySender,
+        interval: Duration,
+    )
+    where F: FnMut() -> Option<Snapshot> + Send + 'static {
+        loop {
+            let res = tokio::task::spawn_blocking(move || {
+                let snapshot = source();
+                (source, snapshot)
+            }).await;
+            source = match res {
+                Ok((source, Some(snapshot))) => {
+                    if history.update(snapshot) {
+                        notify.notify()
+                    }
+                    source
+                }
+                Ok((source, None)) => source,
+                Err(_) => {
+                    error!("Payload source panicked. Stopping updates.");
+                    return
+                }
+            };
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+
+    //------------ PayloadHistory --------------------------------------------
+
+    /// A payload source for RTR keeping a number of earlier snapshots.
+    ///
+    /// The history starts out empty. Each time a snapshot different from
+    /// the current one is added via [`update`], the serial number is
+    /// increased. Diffs are calculated from the kept snapshots when a
+    /// router asks for them. Since diffs don’t include ASPAs, a router is
+    /// sent a Cache Reset if the ASPAs have changed since its serial.
+    ///
+    /// Values of this type are cheap to clone. All clones share the same
+    /// history.
+    ///
+    /// [`update`]: #method.update
+    #[derive(Clone, Debug)]
+    pub struct PayloadHistory(Arc<RwLock<HistoryData>>);
+
+    /// The data of a payload history.
+    #[derive(Debug)]
+    struct HistoryData {
+        /// The state of the current snapshot.
+        state: State,
+
+        /// The current snapshot if there is one yet.
+        current: Option<Arc<Snapshot>>,
+
+        /// The earlier snapshots with their serial, most recent first.
+        earlier: VecDeque<(Serial, Arc<Snapshot>)>,
+
+        /// The maximum number of earlier snapshots.
+        size: usize,
+
+        /// The timing parameters sent to routers.
+        timing: Timing,
+    }
+
+    impl PayloadHistory {
+        /// Creates a new, empty history.
+        ///
+        /// At most `size` earlier snapshots are kept. The history uses a
+        /// new session ID as described by [`State::new`].
+        ///
+        /// [`State::new`]: ../rtr/state/struct.State.html#method.new
+        pub fn new(size: usize, timing: Timing) -> Self {
+            PayloadHistory(Arc::new(RwLock::new(HistoryData {
+                state: State::new(),
+                current: None,
+                earlier: VecDeque::new(),
+                size,
+                timing,
+            })))
+        }
+
+        /// Returns the current snapshot if there is one yet.
+        pub fn current(&self) -> Option<Arc<Snapshot>> {
+            self.0.read().unwrap().current.clone()
+        }
+
+        /// Updates the history with a new snapshot.
+        ///
+        /// Returns whether the snapshot differs from the current one and
+        /// routers should be notified.
+        pub fn update(&self, snapshot: Snapshot) -> bool {
+            let mut data = self.0.write().unwrap();
+            if let Some(current) = data.current.clone() {
+                if *current == snapshot {
+                    return false
+                }
+                let serial = data.state.serial();
+                data.earlier.push_front((serial, current));
+                let size = data.size;
+                data.earlier.truncate(size);
+                data.state.inc();
+            }
+            data.current = Some(Arc::new(snapshot));
+            true
+        }
+    }
+
+    impl VrpSource for PayloadHistory {
+        type FullIter = vec::IntoIter<Payload>;
+        type DiffIter = vec::IntoIter<(Action, Payload)>;
+
+        fn ready(&self) -> bool {
+            self.0.read().unwrap().current.is_some()
+        }
+
+        fn notify(&self) -> State {
+            self.0.read().unwrap().state
+        }
+
+        fn full(&self) -> (State, Self::FullIter) {
+            let data = self.0.read().unwrap();
+            let payload = match data.current {
+                Some(ref current) => current.iter().collect(),
+                None => Vec::new()
+            };
+            (data.state, payload.into_iter())
+        }
+
+        fn diff(&self, state: State) -> Option<(State, Self::DiffIter)> {
+            let data = self.0.read().unwrap();
+            let current = data.current.as_ref()?;
+            if state.session() != data.state.session() {
+                return None
+            }
+            if state.serial() == data.state.serial() {
+                return Some((data.state, Vec::new().into_iter()))
+            }
+            let earlier = &data.earlier.iter().find(|item| {
+                item.0 == state.serial()
+            })?.1;
+            if earlier.aspas() != current.aspas() {
+                return None
+            }
+            let diff = earlier.diff(current);
+            Some((data.state, diff.actions().collect::<Vec<_>>().into_iter()))
+        }
+
+        fn timing(&self) -> Timing {
+            self.0.read().unwrap().timing
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::fs;
+    use crate::x509::Time;
+    use super::*;
+
+    #[test]
+    fn run_validation() {
+        let base = std::env::temp_dir().join(
+            format!("rpki-pipeline-test-{}", std::process::id())
+        );
+        let tal_dir = base.join("tals");
+        let cache = base.join("cache").join("rpki.ripe.net");
+        fs::create_dir_all(&tal_dir).unwrap();
+        fs::create_dir_all(cache.join("ta")).unwrap();
+        fs::create_dir_all(cache.join("repository")).unwrap();
+        fs::write(
+            tal_dir.join("ripe.tal"),
+            include_bytes!("../test-data/ripe.tal").as_ref()
+        ).unwrap();
+        fs::write(
+            cache.join("ta/ripe-ncc-ta.cer"),
+            include_bytes!("../test-data/ta.cer").as_ref()
+        ).unwrap();
+        fs::write(
+            cache.join("repository/ripe-ncc-ta.mft"),
+            include_bytes!("../test-data/ta.mft").as_ref()
+        ).unwrap();
+        fs::write(
+            cache.join("repository/ripe-ncc-ta.crl"),
+            include_bytes!("../test-data/ta.crl").as_ref()
+        ).unwrap();
+        fs::write(
+            cache.join(
+                "repository/2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+            ),
+            include_bytes!("../test-data/ca1.cer").as_ref()
+        ).unwrap();
+
+        // A TAL for a trust anchor certificate that isn’t in the cache.
+        let ripe = Tal::read(
+            "ripe.tal", &mut include_bytes!("../test-data/ripe.tal").as_ref()
+        ).unwrap();
+        let other = Tal::new(
+            "other".into(),
+            vec!["rsync://example.net/ta/ta.cer".parse().unwrap()],
+            ripe.key_info().clone()
+        );
+        let mut file = fs::File::create(tal_dir.join("other.tal")).unwrap();
+        other.write(&mut file).unwrap();
+
+        let mut config = ValidationConfig::new(&tal_dir, base.join("cache"));
+        config.set_clock(Arc::new(Time::utc(2019, 4, 6, 12, 0, 0)));
+        config.set_use_previous(true);
+        let report = super::run_validation(&config).unwrap();
+        fs::remove_dir_all(&base).unwrap();
+
+        assert_eq!(report.outcomes().len(), 1);
+        assert_eq!(report.outcomes()[0].0.name(), "ripe");
+        assert_eq!(report.outcomes()[0].1.valid_points(), 1);
+        assert!(report.snapshot().is_empty());
+        assert_eq!(report.failures().len(), 1);
+        assert_eq!(report.failures()[0].0.name(), "other");
+        match report.failures()[0].1 {
+            Error::TrustAnchor => { }
+            ref err => panic!("unexpected error {}", err)
+        }
+    }
+
+    #[cfg(feature = "rtr")]
+    #[test]
+    fn payload_history() {
+        use crate::payload::{Payload, RouteOrigin};
+        use crate::rtr::{Action, Timing, VrpSource};
+
+        fn origin(asn: u32) -> Payload {
+            RouteOrigin::new(
+                "192.0.2.0/24".parse().unwrap(), 24, asn.into()
+            ).unwrap().into()
+        }
+
+        fn snapshot(asns: &[u32]) -> Snapshot {
+            asns.iter().map(|&asn| origin(asn)).collect()
+        }
+
+        let history = PayloadHistory::new(1, Timing::default());
+        assert!(!history.ready());
+        assert!(history.diff(history.notify()).is_none());
+
+        assert!(history.update(snapshot(&[1, 2])));
+        assert!(history.ready());
+        let first = history.notify();
+        assert_eq!(history.full().1.count(), 2);
+        assert!(!history.update(snapshot(&[1, 2])));
+        assert_eq!(history.notify(), first);
+
+        assert!(history.update(snapshot(&[2, 3])));
+        let second = history.notify();
+        assert_eq!(second.serial(), first.serial() + 1);
+        assert_eq!(
+            history.diff(first).unwrap().1.collect::<Vec<_>>(),
+            [(Action::Withdraw, origin(1)), (Action::Announce, origin(3))]
+        );
+        assert_eq!(history.diff(second).unwrap().1.count(), 0);
+
+        // Only one earlier snapshot is kept.
+        assert!(history.update(snapshot(&[3])));
+        assert!(history.diff(first).is_none());
+        assert_eq!(history.diff(second).unwrap().1.count(), 1);
+    }
+}
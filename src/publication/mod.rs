@@ -0,0 +1,418 @@
+//! The content of a repository as managed by a publication server.
+//!
+//! A publication server receives changes to the repositories it hosts as
+//! a sequence of transactions, each consisting of publish and withdraw
+//! elements as defined in RFC 8181. This module provides these elements as
+//! [`PublishElement`] and an in-memory model of a repository,
+//! [`Repository`], to which they can be applied.
+//!
+//! Applying elements never modifies a repository. Instead, a new
+//! repository is returned if all elements could be applied, so a failed
+//! transaction can simply be retried. Otherwise, all elements that
+//! conflict with the content of the repository are reported.
+//!
+//! The [`server`] module contains additional helpers for implementing a
+//! publication server, such as restricting publishers to their part of the
+//! repository.
+//!
+//! [`PublishElement`]: enum.PublishElement.html
+//! [`Repository`]: struct.Repository.html
+//! [`server`]: server/index.html
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use std::collections::hash_map;
+use bytes::Bytes;
+use crate::uri;
+use crate::rrdp::Hash;
+
+pub mod server;
+
+
+//------------ PublishElement ------------------------------------------------
+
+/// A single change to the content of a repository.
+///
+/// These are the publish and withdraw elements of an RFC 8181 publication
+/// query. A publish element with a hash replaces an existing object and is
+/// represented by the `Update` variant.
+#[derive(Clone, Debug)]
+pub enum PublishElement {
+    /// Publishes an object that must not yet exist.
+    Publish {
+        /// The rsync URI of the object.
+        uri: uri::Rsync,
+
+        /// The content of the object.
+        content: Bytes,
+    },
+
+    /// Replaces an existing object.
+    Update {
+        /// The rsync URI of the object.
+        uri: uri::Rsync,
+
+        /// The content of the object.
+        content: Bytes,
+
+        /// The hash of the object to be replaced.
+        hash: Hash,
+    },
+
+    /// Withdraws an existing object.
+    Withdraw {
+        /// The rsync URI of the object.
+        uri: uri::Rsync,
+
+        /// The hash of the object to be withdrawn.
+        hash: Hash,
+    },
+}
+
+impl PublishElement {
+    /// Returns the rsync URI of the object affected by the element.
+    pub fn uri(&self) -> &uri::Rsync {
+        match *self {
+            PublishElement::Publish { ref uri, .. } => uri,
+            PublishElement::Update { ref uri, .. } => uri,
+            PublishElement::Withdraw { ref uri, .. } => uri,
+        }
+    }
+}
+
+
+//------------ Repository ----------------------------------------------------
+
+/// The content of a repository.
+///
+/// The repository maps the rsync URI of each object to its hash and
+/// content.
+#[derive(Clone, Debug, Default)]
+pub struct Repository {
+    /// The objects of the repository.
+    objects: HashMap<uri::Rsync, (Hash, Bytes)>,
+}
+
+impl Repository {
+    /// Creates a new, empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the content of the object with the given URI.
+    pub fn get(&self, uri: &uri::Rsync) -> Option<&Bytes> {
+        self.objects.get(uri).map(|item| &item.1)
+    }
+
+    /// Returns the hash of the object with the given URI.
+    pub fn hash(&self, uri: &uri::Rsync) -> Option<Hash> {
+        self.objects.get(uri).map(|item| item.0)
+    }
+
+    /// Returns the number of objects in the repository.
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// Returns whether the repository is empty.
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// Returns an iterator over the URIs, hashes, and content of all
+    /// objects.
+    pub fn iter(&self) -> RepositoryIter<'_> {
+        RepositoryIter(self.objects.iter())
+    }
+
+    /// Applies the elements of a transaction.
+    ///
+    /// The elements are applied in order. If all of them can be applied,
+    /// returns the resulting repository. Otherwise returns all elements
+    /// that conflict with the content of the repository at the time they
+    /// would have been applied. In either case, `self` is left unchanged.
+    pub fn apply(
+        &self, elements: &[PublishElement]
+    ) -> Result<Self, ApplyError> {
+        let mut res = self.clone();
+        let mut conflicts = Vec::new();
+        for (index, element) in elements.iter().enumerate() {
+            if let Err(kind) = res.apply_element(element) {
+                conflicts.push(Conflict {
+                    index, uri: element.uri().clone(), kind
+                })
+            }
+        }
+        if conflicts.is_empty() {
+            Ok(res)
+        }
+        else {
+            Err(ApplyError { conflicts })
+        }
+    }
+
+    /// Applies a single element.
+    fn apply_element(
+        &mut self, element: &PublishElement
+    ) -> Result<(), ConflictKind> {
+        match *element {
+            PublishElement::Publish { ref uri, ref content } => {
+                if self.objects.contains_key(uri) {
+                    return Err(ConflictKind::ObjectAlreadyPresent)
+                }
+                self.objects.insert(
+                    uri.clone(), (Hash::from_data(content), content.clone())
+                );
+            }
+            PublishElement::Update { ref uri, ref content, hash } => {
+                self.check_hash(uri, hash)?;
+                self.objects.insert(
+                    uri.clone(), (Hash::from_data(content), content.clone())
+                );
+            }
+            PublishElement::Withdraw { ref uri, hash } => {
+                self.check_hash(uri, hash)?;
+                self.objects.remove(uri);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that an object exists and has the given hash.
+    fn check_hash(
+        &self, uri: &uri::Rsync, hash: Hash
+    ) -> Result<(), ConflictKind> {
+        match self.objects.get(uri) {
+            Some(item) if item.0 == hash => Ok(()),
+            Some(_) => Err(ConflictKind::NoObjectMatchingHash),
+            None => Err(ConflictKind::NoObjectPresent),
+        }
+    }
+}
+
+
+//------------ RepositoryIter ------------------------------------------------
+
+/// An iterator over the objects of a repository.
+#[derive(Clone, Debug)]
+pub struct RepositoryIter<'a>(
+    hash_map::Iter<'a, uri::Rsync, (Hash, Bytes)>
+);
+
+impl<'a> Iterator for RepositoryIter<'a> {
+    type Item = (&'a uri::Rsync, &'a Hash, &'a Bytes);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(uri, item)| (uri, &item.0, &item.1))
+    }
+}
+
+
+//------------ Conflict ------------------------------------------------------
+
+/// An element that conflicts with the content of a repository.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Conflict {
+    /// The index of the element in the transaction.
+    index: usize,
+
+    /// The URI of the element.
+    uri: uri::Rsync,
+
+    /// The kind of conflict.
+    kind: ConflictKind,
+}
+
+impl Conflict {
+    /// Returns the index of the element in the transaction.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the URI of the element.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the kind of conflict.
+    pub fn kind(&self) -> ConflictKind {
+        self.kind
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.uri, self.kind)
+    }
+}
+
+
+//------------ ConflictKind --------------------------------------------------
+
+/// The kind of conflict of an element.
+///
+/// The variants correspond to the error codes of RFC 8181.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ConflictKind {
+    /// An object to be published already exists.
+    ObjectAlreadyPresent,
+
+    /// An object to be updated or withdrawn does not exist.
+    NoObjectPresent,
+
+    /// An object to be updated or withdrawn has a different hash.
+    NoObjectMatchingHash,
+}
+
+impl ConflictKind {
+    /// Returns the RFC 8181 error code for the conflict.
+    pub fn error_code(self) -> &'static str {
+        match self {
+            ConflictKind::ObjectAlreadyPresent => "object_already_present",
+            ConflictKind::NoObjectPresent => "no_object_present",
+            ConflictKind::NoObjectMatchingHash => "no_object_matching_hash",
+        }
+    }
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ConflictKind::ObjectAlreadyPresent => "object already present",
+            ConflictKind::NoObjectPresent => "no object present",
+            ConflictKind::NoObjectMatchingHash => "no object matching hash",
+        })
+    }
+}
+
+
+//------------ ApplyError ----------------------------------------------------
+
+/// The elements of a transaction could not be applied.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ApplyError {
+    /// The conflicting elements in transaction order.
+    conflicts: Vec<Conflict>,
+}
+
+impl ApplyError {
+    /// Returns the conflicting elements in transaction order.
+    ///
+    /// There is always at least one conflict.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+}
+
+impl fmt::Display for ApplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // There always is at least one conflict.
+        self.conflicts[0].fmt(f)?;
+        if self.conflicts.len() > 1 {
+            write!(f, " and {} more conflicts", self.conflicts.len() - 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for ApplyError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    fn rsync(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    fn publish(uri: &str, content: &'static [u8]) -> PublishElement {
+        PublishElement::Publish {
+            uri: rsync(uri), content: Bytes::from_static(content)
+        }
+    }
+
+    #[test]
+    fn apply() {
+        let empty = Repository::new();
+        let transaction = [
+            publish("rsync://example.net/repo/a.roa", b"a"),
+            publish("rsync://example.net/repo/b.roa", b"b"),
+        ];
+        let repo = empty.apply(&transaction).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(repo.len(), 2);
+        assert_eq!(
+            repo.get(&rsync("rsync://example.net/repo/a.roa")),
+            Some(&Bytes::from_static(b"a"))
+        );
+        assert_eq!(
+            repo.hash(&rsync("rsync://example.net/repo/b.roa")),
+            Some(Hash::from_data(b"b"))
+        );
+        assert_eq!(repo.iter().count(), 2);
+
+        // Applying the same transaction again reports every element.
+        let err = repo.apply(&transaction).unwrap_err();
+        assert_eq!(
+            err.conflicts().iter().map(|item| {
+                (item.index(), item.kind())
+            }).collect::<Vec<_>>(),
+            [
+                (0, ConflictKind::ObjectAlreadyPresent),
+                (1, ConflictKind::ObjectAlreadyPresent),
+            ]
+        );
+        assert_eq!(repo.len(), 2);
+
+        let err = repo.apply(&[
+            PublishElement::Update {
+                uri: rsync("rsync://example.net/repo/a.roa"),
+                content: Bytes::from_static(b"c"),
+                hash: Hash::from_data(b"a"),
+            },
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/a.roa"),
+                hash: Hash::from_data(b"a"),
+            },
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/c.roa"),
+                hash: Hash::from_data(b"c"),
+            },
+        ]).unwrap_err();
+        assert_eq!(
+            err.conflicts().iter().map(|item| {
+                (item.index(), item.uri().path(), item.kind())
+            }).collect::<Vec<_>>(),
+            [
+                (1, "a.roa", ConflictKind::NoObjectMatchingHash),
+                (2, "c.roa", ConflictKind::NoObjectPresent),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "rsync://example.net/repo/a.roa: no object matching hash \
+             and 1 more conflicts"
+        );
+
+        let repo = repo.apply(&[
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/a.roa"),
+                hash: Hash::from_data(b"a"),
+            },
+            PublishElement::Update {
+                uri: rsync("rsync://example.net/repo/b.roa"),
+                content: Bytes::from_static(b"c"),
+                hash: Hash::from_data(b"b"),
+            },
+        ]).unwrap();
+        assert_eq!(repo.len(), 1);
+        assert_eq!(
+            repo.hash(&rsync("rsync://example.net/repo/b.roa")),
+            Some(Hash::from_data(b"c"))
+        );
+    }
+}
@@ -0,0 +1,211 @@
+//! Support for implementing a publication server.
+//!
+//! A publication server hosts the repositories of multiple publishers and
+//! must make sure that each publisher only modifies objects within its own
+//! part of the repository. In RFC 8183, the server assigns each publisher
+//! a base rsync URI, `sia_base`, in its repository response. This module
+//! provides [`UriJail`] which checks that all elements of a publication
+//! query are located below such a base URI.
+//!
+//! [`UriJail`]: struct.UriJail.html
+
+use std::{error, fmt};
+use crate::uri;
+use super::PublishElement;
+
+
+//------------ UriJail -------------------------------------------------------
+
+/// The part of a repository a publisher is allowed to modify.
+///
+/// The jail is defined by a base rsync URI. Only objects whose URI has
+/// this base URI as its parent according to [`uri::Rsync::is_parent_of`]
+/// are inside the jail. Since only URIs ending in a slash can be parents,
+/// a base URI without a trailing slash results in a jail that contains no
+/// objects at all.
+///
+/// [`uri::Rsync::is_parent_of`]: ../../uri/struct.Rsync.html#method.is_parent_of
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct UriJail {
+    /// The base URI of the jail.
+    base: uri::Rsync,
+}
+
+impl UriJail {
+    /// Creates a new jail from the publisher’s base URI.
+    pub fn new(base: uri::Rsync) -> Self {
+        UriJail { base }
+    }
+
+    /// Returns the base URI of the jail.
+    pub fn base(&self) -> &uri::Rsync {
+        &self.base
+    }
+
+    /// Returns whether an object URI is inside the jail.
+    pub fn contains(&self, uri: &uri::Rsync) -> bool {
+        self.base.is_parent_of(uri)
+    }
+
+    /// Checks that all elements of a query are inside the jail.
+    ///
+    /// If any element refers to an object outside of the jail, returns
+    /// all such elements. The query must then be rejected as a whole.
+    pub fn check(
+        &self, elements: &[PublishElement]
+    ) -> Result<(), JailError> {
+        let failures: Vec<_> = elements.iter().enumerate().filter_map(
+            |(index, element)| {
+                if self.contains(element.uri()) {
+                    None
+                }
+                else {
+                    Some(PermissionFailure {
+                        index, uri: element.uri().clone()
+                    })
+                }
+            }
+        ).collect();
+        if failures.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(JailError { failures })
+        }
+    }
+}
+
+
+//------------ PermissionFailure ---------------------------------------------
+
+/// An element that refers to an object outside of a jail.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PermissionFailure {
+    /// The index of the element in the query.
+    index: usize,
+
+    /// The URI of the element.
+    uri: uri::Rsync,
+}
+
+impl PermissionFailure {
+    /// The RFC 8181 error code for a permission failure.
+    pub const ERROR_CODE: &'static str = "permission_failure";
+
+    /// Returns the index of the element in the query.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the URI of the element.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the RFC 8181 error code for the failure.
+    pub fn error_code(&self) -> &'static str {
+        Self::ERROR_CODE
+    }
+}
+
+impl fmt::Display for PermissionFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: permission failure", self.uri)
+    }
+}
+
+
+//------------ JailError -----------------------------------------------------
+
+/// Elements of a query refer to objects outside of a jail.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct JailError {
+    /// The offending elements in query order.
+    failures: Vec<PermissionFailure>,
+}
+
+impl JailError {
+    /// Returns the offending elements in query order.
+    ///
+    /// There is always at least one failure.
+    pub fn failures(&self) -> &[PermissionFailure] {
+        &self.failures
+    }
+}
+
+impl fmt::Display for JailError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // There always is at least one failure.
+        self.failures[0].fmt(f)?;
+        if self.failures.len() > 1 {
+            write!(
+                f, " and {} more permission failures",
+                self.failures.len() - 1
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl error::Error for JailError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use bytes::Bytes;
+    use crate::rrdp::Hash;
+    use super::*;
+
+    fn rsync(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn check() {
+        let jail = UriJail::new(rsync("rsync://example.net/repo/alice/"));
+        assert!(jail.contains(&rsync("rsync://example.net/repo/alice/a.roa")));
+        assert!(jail.contains(&rsync("rsync://example.net/repo/alice/b/c")));
+        assert!(!jail.contains(&rsync("rsync://example.net/repo/bob/a.roa")));
+        assert!(!jail.contains(&rsync("rsync://example.net/repo/alice.roa")));
+        assert!(!jail.contains(&rsync("rsync://example.net/other/alice/a")));
+        assert!(!jail.contains(&rsync("rsync://example.org/repo/alice/a")));
+
+        let elements = [
+            PublishElement::Publish {
+                uri: rsync("rsync://example.net/repo/alice/a.roa"),
+                content: Bytes::from_static(b"a"),
+            },
+            PublishElement::Publish {
+                uri: rsync("rsync://example.net/repo/bob/a.roa"),
+                content: Bytes::from_static(b"a"),
+            },
+            PublishElement::Withdraw {
+                uri: rsync("rsync://example.net/repo/alicea.roa"),
+                hash: Hash::from_data(b"a"),
+            },
+        ];
+        assert_eq!(jail.check(&elements[..1]), Ok(()));
+        let err = jail.check(&elements).unwrap_err();
+        assert_eq!(
+            err.failures().iter().map(|item| {
+                (item.index(), item.uri().path(), item.error_code())
+            }).collect::<Vec<_>>(),
+            [
+                (1, "bob/a.roa", "permission_failure"),
+                (2, "alicea.roa", "permission_failure"),
+            ]
+        );
+        assert_eq!(
+            err.to_string(),
+            "rsync://example.net/repo/bob/a.roa: permission failure \
+             and 1 more permission failures"
+        );
+
+        // Without a trailing slash, nothing is inside the jail.
+        let jail = UriJail::new(rsync("rsync://example.net/repo/alice"));
+        assert!(!jail.contains(&rsync("rsync://example.net/repo/alice/a")));
+    }
+}
@@ -26,6 +26,7 @@ use crate::cert::Overclaim;
 use crate::x509::ValidationError;
 use super::chain::{Block, SharedChain};
 use super::choice::ResourcesChoice;
+use super::compact::{take_u32, Compact, CompactError};
 
 
 //------------ AsResources ---------------------------------------------------
@@ -318,15 +319,18 @@ impl AsBlocks {
         )
     }
 
-
-
+    /// Returns a new AsBlocks with the resources of this AsBlocks that are
+    /// not found in the other.
+    pub fn difference(&self, other: &Self) -> Self {
+        AsBlocks(SharedChain::from_owned(self.0.difference(&other.0)))
+    }
 }
 
 /// # Decoding and Encoding
 ///
 impl AsBlocks {
     /// Parses the content of a AS ID blocks sequence.
-    fn parse_content<S: decode::Source>(
+    pub(crate) fn parse_content<S: decode::Source>(
         content: &mut decode::Content<S>
     ) -> Result<Self, S::Err> {
         let cons = content.as_constructed()?;
@@ -474,7 +478,7 @@ impl AsBlock {
     pub fn min(&self) -> AsId {
         match *self {
             AsBlock::Id(id) => id,
-            AsBlock::Range(ref range) => range.min(),
+            AsBlock::Range(ref range) => AsRange::min(*range),
         }
     }
 
@@ -482,7 +486,7 @@ impl AsBlock {
     pub fn max(&self) -> AsId {
         match *self {
             AsBlock::Id(id) => id,
-            AsBlock::Range(ref range) => range.max(),
+            AsBlock::Range(ref range) => AsRange::max(*range),
         }
     }
 
@@ -564,8 +568,14 @@ impl AsBlock {
     }
     */
 
+    /// Returns an encoder for the block.
+    ///
+    /// A range containing a single AS number is encoded as that number.
     fn encode(self) -> impl encode::Values {
         match self {
+            AsBlock::Range(inner) if inner.min() == inner.max() => {
+                encode::Choice2::One(inner.min().encode())
+            }
             AsBlock::Id(inner) => encode::Choice2::One(inner.encode()),
             AsBlock::Range(inner) => encode::Choice2::Two(inner.encode()),
         }
@@ -651,6 +661,10 @@ impl Block for AsBlock {
     fn next(item: Self::Item) -> Option<Self::Item> {
         item.0.checked_add(1).map(AsId)
     }
+
+    fn prev(item: Self::Item) -> Option<Self::Item> {
+        item.0.checked_sub(1).map(AsId)
+    }
 }
 
 //--- Display
@@ -668,7 +682,9 @@ impl fmt::Display for AsBlock {
 //------------ AsRange -------------------------------------------------------
 
 /// A range of AS numbers.
-#[derive(Clone, Copy, Debug)]
+///
+/// Ranges are ordered by their smallest and then their largest AS number.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct AsRange {
     /// The smallest AS number that is part of the range.
     min: AsId,
@@ -751,9 +767,34 @@ impl Block for AsRange {
     fn next(item: Self::Item) -> Option<Self::Item> {
         item.0.checked_add(1).map(AsId)
     }
+
+    fn prev(item: Self::Item) -> Option<Self::Item> {
+        item.0.checked_sub(1).map(AsId)
+    }
 }
 
-//--- Display
+//--- FromStr and Display
+
+impl FromStr for AsRange {
+    type Err = FromStrError;
+
+    /// Parses a range from a string.
+    ///
+    /// The range can be given as two AS numbers separated by a hyphen,
+    /// e.g., `AS64496-AS64511`, or as a single AS number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (min, max) = match s.find('-') {
+            Some(pos) => (&s[..pos], &s[pos + 1..]),
+            None => (s, s)
+        };
+        let min = AsId::from_str(min).map_err(|_| FromStrError::BadRange)?;
+        let max = AsId::from_str(max).map_err(|_| FromStrError::BadRange)?;
+        if min > max {
+            return Err(FromStrError::BadRange)
+        }
+        Ok(AsRange { min, max })
+    }
+}
 
 impl fmt::Display for AsRange {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -762,6 +803,46 @@ impl fmt::Display for AsRange {
 }
 
 
+//--- Serialize and Deserialize
+
+impl Serialize for AsRange {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AsRange {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(de::Error::custom)
+    }
+}
+
+
+//--- Compact
+
+impl Compact for AsRange {
+    fn compose_compact(&self, target: &mut Vec<u8>) {
+        self.min.compose_compact(target);
+        self.max.compose_compact(target);
+    }
+
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError> {
+        let min = AsId::take_compact(data)?;
+        let max = AsId::take_compact(data)?;
+        if min > max {
+            return Err(CompactError)
+        }
+        Ok(AsRange { min, max })
+    }
+}
+
+
 //------------ AsId ----------------------------------------------------------
 
 /// An AS number.
@@ -862,6 +943,40 @@ impl fmt::Display for AsId {
 }
 
 
+//--- Serialize and Deserialize
+
+impl Serialize for AsId {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AsId {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(de::Error::custom)
+    }
+}
+
+
+//--- Compact
+
+impl Compact for AsId {
+    fn compose_compact(&self, target: &mut Vec<u8>) {
+        target.extend_from_slice(&self.0.to_be_bytes())
+    }
+
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError> {
+        take_u32(data).map(AsId)
+    }
+}
+
+
 //------------ FromStrError --------------------------------------------------
 
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -893,6 +1008,17 @@ impl error::Error for FromStrError { }
 mod test {
     use super::*;
 
+    #[test]
+    fn as_blocks_difference() {
+        let this = AsBlocks::from_str("AS1-AS100, AS200").unwrap();
+        let other = AsBlocks::from_str("AS10-AS19, AS100-AS200").unwrap();
+        assert_eq!(
+            this.difference(&other),
+            AsBlocks::from_str("AS1-AS9, AS20-AS99").unwrap()
+        );
+        assert!(other.difference(&other).is_empty());
+    }
+
     #[test]
     fn as_id_from_str() {
         let as1 = AsId::from_str("AS1").unwrap();
@@ -912,6 +1038,33 @@ mod test {
         assert_eq!(expected_str, &block.to_string())
     }
 
+    #[test]
+    fn as_range_values() {
+        let range = AsRange::from_str("AS64496-AS64511").unwrap();
+        assert_eq!(range.min(), AsId(64496));
+        assert_eq!(range.max(), AsId(64511));
+        assert_eq!(AsRange::from_str("64496").unwrap().max(), AsId(64496));
+        assert_eq!(
+            AsRange::from_str("AS2-AS1"), Err(FromStrError::BadRange)
+        );
+        assert!(range < AsRange::from_str("AS64496-AS64512").unwrap());
+
+        assert_eq!(
+            serde_json::to_string(&range).unwrap(), "\"AS64496-AS64511\""
+        );
+        assert_eq!(
+            serde_json::from_str::<AsId>("\"AS65000\"").unwrap(),
+            AsId(65000)
+        );
+        assert_eq!(range.to_compact(), b"\0\0\xfb\xf0\0\0\xfb\xff");
+        assert_eq!(AsRange::from_compact(&range.to_compact()), Ok(range));
+        assert_eq!(
+            AsRange::from_compact(b"\0\0\0\x02\0\0\0\x01"),
+            Err(CompactError)
+        );
+        assert_eq!(AsId::from_compact(b"\0\0\0"), Err(CompactError));
+    }
+
     #[test]
     fn as_block_from_wrong_range_str() {
         let expected_str = "AS1-";
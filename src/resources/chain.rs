@@ -31,6 +31,9 @@ pub trait Block: Clone {
     /// Returns the item immediately following the given item.
     fn next(item: Self::Item) -> Option<Self::Item>;
 
+    /// Returns the item immediately preceding the given item.
+    fn prev(item: Self::Item) -> Option<Self::Item>;
+
     /// Returns a pair of the smallest and largest item in the block.
     fn bounds(&self) -> (Self::Item, Self::Item) {
         (self.min(), self.max())
@@ -280,6 +283,51 @@ impl<T: Block> Chain<T> {
         Err(unsafe { OwnedChain::from_vec_unchecked(res) })
     }
 
+    /// Returns the parts of `self` that are not covered by `other`.
+    pub fn difference<C: AsRef<Chain<T>>>(&self, other: &C) -> OwnedChain<T> {
+        let mut other = other.as_ref().iter().peekable();
+        let mut res = Vec::new();
+
+        for block in self.iter() {
+            let (mut min, max) = block.bounds();
+            loop {
+                let sub = match other.peek() {
+                    Some(sub) => *sub,
+                    None => {
+                        res.push(T::new(min, max));
+                        break
+                    }
+                };
+                // Skip over other blocks before what is left of our block.
+                if sub.max() < min {
+                    other.next();
+                    continue
+                }
+                // The other block starts after our block: keep the rest.
+                if sub.min() > max {
+                    res.push(T::new(min, max));
+                    break
+                }
+                // The blocks overlap. Keep whatever is before the other
+                // block. Since sub.min() > min, there is a previous item.
+                if sub.min() > min {
+                    res.push(T::new(min, T::prev(sub.min()).unwrap()));
+                }
+                // If the other block reaches past our block, we are done
+                // with it but the other block may cover the next one, too.
+                if sub.max() >= max {
+                    break
+                }
+                // Since sub.max() < max, there is a next item.
+                min = T::next(sub.max()).unwrap();
+                other.next();
+            }
+        }
+
+        // The remaining parts are separated by either gaps between our
+        // blocks or by other blocks, so we have a valid chain.
+        unsafe { OwnedChain::from_vec_unchecked(res) }
+    }
 }
 
 
@@ -598,6 +646,7 @@ mod test {
         fn min(&self) -> u8 { self.0 }
         fn max(&self) -> u8 { self.1 }
         fn next(item: u8) -> Option<u8> { item.checked_add(1) }
+        fn prev(item: u8) -> Option<u8> { item.checked_sub(1) }
     }
 
     #[test]
@@ -648,6 +697,28 @@ mod test {
         );
     }
 
+    #[test]
+    fn difference() {
+        fn diff(left: &[(u8, u8)], right: &[(u8, u8)]) -> Vec<(u8, u8)> {
+            OwnedChain::from(left).difference(
+                &OwnedChain::from(right)
+            ).as_slice().into()
+        }
+
+        assert_eq!(diff(&[(1,4), (10,20)], &[]), [(1,4), (10,20)]);
+        assert_eq!(diff(&[], &[(1,4)]), []);
+        assert_eq!(diff(&[(1,4), (10,20)], &[(0,30)]), []);
+        assert_eq!(diff(&[(0,255)], &[(0,10), (255,255)]), [(11,254)]);
+        assert_eq!(
+            diff(&[(1,4), (10,20)], &[(3,12), (15,15), (19,25)]),
+            [(1,2), (13,14), (16,18)]
+        );
+        assert_eq!(
+            diff(&[(1,4), (6,8), (10,20)], &[(5,5), (9,9)]),
+            [(1,4), (6,8), (10,20)]
+        );
+    }
+
     #[test]
     fn is_encompassed() {
         let chain = OwnedChain::from([(1,4), (11,18), (23,48)].as_ref());
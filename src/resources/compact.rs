@@ -0,0 +1,87 @@
+//! A compact binary encoding for resource values.
+//!
+//! The encoding is meant for storing and exchanging large numbers of
+//! values efficiently, e.g., in caches or on the wire between processes
+//! of the same application. It is not defined by any standard.
+
+use std::{error, fmt};
+
+
+//------------ Compact -------------------------------------------------------
+
+/// A value with a compact binary encoding.
+///
+/// All integers are encoded in network byte order. Values have a self
+/// describing length, so encoded values can simply be concatenated.
+pub trait Compact: Sized {
+    /// Appends the encoded value to the end of `target`.
+    fn compose_compact(&self, target: &mut Vec<u8>);
+
+    /// Takes an encoded value from the beginning of `data`.
+    ///
+    /// Upon success, `data` is advanced past the value.
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError>;
+
+    /// Returns the encoded value.
+    fn to_compact(&self) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.compose_compact(&mut res);
+        res
+    }
+
+    /// Decodes a value that has to take up all of `data`.
+    fn from_compact(mut data: &[u8]) -> Result<Self, CompactError> {
+        let res = Self::take_compact(&mut data)?;
+        if data.is_empty() {
+            Ok(res)
+        }
+        else {
+            Err(CompactError)
+        }
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Takes `len` octets from the beginning of `data`.
+pub(crate) fn take_slice<'a>(
+    data: &mut &'a [u8], len: usize
+) -> Result<&'a [u8], CompactError> {
+    if data.len() < len {
+        return Err(CompactError)
+    }
+    let (head, tail) = data.split_at(len);
+    *data = tail;
+    Ok(head)
+}
+
+/// Takes a single octet from the beginning of `data`.
+pub(crate) fn take_u8(data: &mut &[u8]) -> Result<u8, CompactError> {
+    take_slice(data, 1).map(|slice| slice[0])
+}
+
+/// Takes a 32 bit unsigned integer from the beginning of `data`.
+pub(crate) fn take_u32(data: &mut &[u8]) -> Result<u32, CompactError> {
+    let slice = take_slice(data, 4)?;
+    Ok(
+        u32::from(slice[0]) << 24 | u32::from(slice[1]) << 16
+        | u32::from(slice[2]) << 8 | u32::from(slice[3])
+    )
+}
+
+
+//------------ CompactError --------------------------------------------------
+
+/// Data did not contain a correctly encoded value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct CompactError;
+
+impl fmt::Display for CompactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid compact encoding")
+    }
+}
+
+impl error::Error for CompactError { }
+
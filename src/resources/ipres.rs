@@ -57,6 +57,33 @@ impl IpResources {
     pub fn to_blocks(&self) -> Result<IpBlocks, ValidationError> {
         self.0.to_blocks()
     }
+
+    /// Returns the resources in their canonical form.
+    ///
+    /// See [`IpBlocks::normalize`] for details. Inherited resources are
+    /// returned unchanged.
+    ///
+    /// [`IpBlocks::normalize`]: struct.IpBlocks.html#method.normalize
+    pub fn normalize(&self) -> Self {
+        match self.0 {
+            ResourcesChoice::Inherit => Self::inherit(),
+            ResourcesChoice::Blocks(ref blocks) => {
+                Self::blocks(blocks.normalize())
+            }
+        }
+    }
+
+    /// Returns an IpResourcesForFamily for IPv4 for this,
+    /// to help formatting.
+    pub fn as_v4(&self) -> IpResourcesForFamily<'_> {
+        IpResourcesForFamily::v4(self)
+    }
+
+    /// Returns an IpResourcesForFamily for IPv6 for this,
+    /// to help formatting.
+    pub fn as_v6(&self) -> IpResourcesForFamily<'_> {
+        IpResourcesForFamily::v6(self)
+    }
 }
 
 impl IpResources {
@@ -157,6 +184,119 @@ impl IpResources {
 }
 
 
+//--- FromStr
+
+impl FromStr for IpResources {
+    type Err = FromStrError;
+
+    /// Parses either the word "inherit" or comma separated IpBlocks of a
+    /// single address family.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim() == "inherit" {
+            Ok(IpResources::inherit())
+        }
+        else {
+            IpBlocks::from_str(s).map(IpResources::blocks)
+        }
+    }
+}
+
+
+//------------ IpResourcesForFamily ------------------------------------------
+
+/// IpResources for a specific family, to help formatting
+pub struct IpResourcesForFamily<'a> {
+    family: AddressFamily,
+    resources: &'a IpResources
+}
+
+impl<'a> IpResourcesForFamily<'a> {
+    pub fn v4(resources: &'a IpResources) -> Self {
+        IpResourcesForFamily {
+            family: AddressFamily::Ipv4,
+            resources
+        }
+    }
+
+    pub fn v6(resources: &'a IpResources) -> Self {
+        IpResourcesForFamily {
+            family: AddressFamily::Ipv6,
+            resources
+        }
+    }
+}
+
+impl<'a> fmt::Display for IpResourcesForFamily<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.resources.0 {
+            ResourcesChoice::Inherit => f.write_str("inherit"),
+            ResourcesChoice::Blocks(ref blocks) => {
+                IpBlocksForFamily {
+                    family: self.family,
+                    blocks
+                }.fmt(f)
+            }
+        }
+    }
+}
+
+
+//------------ IpResourcesForFamilies ----------------------------------------
+
+/// The IpResources of both address families, to help formatting.
+///
+/// The resources are formatted as a comma separated list of the IPv4 blocks
+/// followed by the IPv6 blocks, e.g., `192.0.2.0/24, 2001:db8::/32`.
+/// Inherited resources are shown as `inherit` followed by the address
+/// family in parentheses.
+pub struct IpResourcesForFamilies<'a> {
+    v4: Option<&'a IpResources>,
+    v6: Option<&'a IpResources>,
+}
+
+impl<'a> IpResourcesForFamilies<'a> {
+    pub fn new(
+        v4: Option<&'a IpResources>,
+        v6: Option<&'a IpResources>
+    ) -> Self {
+        IpResourcesForFamilies { v4, v6 }
+    }
+}
+
+impl<'a> fmt::Display for IpResourcesForFamilies<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut first = true;
+        let families = [
+            (self.v4, AddressFamily::Ipv4, "IPv4"),
+            (self.v6, AddressFamily::Ipv6, "IPv6"),
+        ];
+        for &(resources, family, name) in families.iter() {
+            let blocks = match resources.map(|res| &res.0) {
+                Some(ResourcesChoice::Blocks(ref blocks)) => blocks,
+                Some(ResourcesChoice::Inherit) => {
+                    if !first {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "inherit ({})", name)?;
+                    first = false;
+                    continue
+                }
+                None => continue
+            };
+            if blocks.is_empty() {
+                continue
+            }
+            if !first {
+                f.write_str(", ")?;
+            }
+            IpBlocksForFamily { family, blocks }.fmt(f)?;
+            first = false;
+        }
+        Ok(())
+    }
+}
+
+
 //------------ IpResourcesBuilder --------------------------------------------
 
 #[derive(Clone, Debug)]
@@ -345,11 +485,29 @@ impl IpBlocks {
             )
         )
     }
+
+    /// Returns a new IpBlocks with the resources of this IpBlocks that are
+    /// not found in the other.
+    pub fn difference(&self, other: &Self) -> Self {
+        IpBlocks(SharedChain::from_owned(self.0.difference(&other.0)))
+    }
+
+    /// Returns the blocks in their canonical form.
+    ///
+    /// Overlapping and adjacent blocks are merged into a single block and
+    /// every block that can be expressed as a prefix is turned into one.
+    /// This is the minimal form required by RFC 3779 for the encoding of
+    /// resources.
+    pub fn normalize(&self) -> Self {
+        self.iter().map(|block| {
+            IpBlock::from((block.min(), block.max()))
+        }).collect()
+    }
 }
 
 impl IpBlocks {
     /// Parses the content of an address block sequence.
-    fn parse_content<S: decode::Source>(
+    pub(crate) fn parse_content<S: decode::Source>(
         content: &mut decode::Content<S>
     ) -> Result<Self, S::Err> {
         let cons = content.as_constructed()?;
@@ -566,9 +724,18 @@ impl IpBlock {
 
     /// Returns an encoder for the range.
     ///
-    /// This encoder will produce a `IPAddressOrRange` value.
+    /// This encoder will produce a `IPAddressOrRange` value. As required by
+    /// RFC 3779, a range that can be expressed as a prefix is encoded as
+    /// a prefix.
     pub fn encode(self) -> impl encode::Values {
-        match self {
+        let canonical = match self {
+            IpBlock::Range(range) => match range.into_prefix() {
+                Ok(prefix) => IpBlock::Prefix(prefix),
+                Err(range) => IpBlock::Range(range)
+            }
+            prefix => prefix
+        };
+        match canonical {
             IpBlock::Prefix(inner) => {
                 encode::Choice2::One(inner.encode())
             }
@@ -661,6 +828,10 @@ impl Block for IpBlock {
     fn next(item: Self::Item) -> Option<Self::Item> {
         item.0.checked_add(1).map(Addr)
     }
+
+    fn prev(item: Self::Item) -> Option<Self::Item> {
+        item.0.checked_sub(1).map(Addr)
+    }
 }
 
 
@@ -907,6 +1078,10 @@ impl Block for AddressRange {
     fn next(item: Self::Item) -> Option<Self::Item> {
         item.0.checked_add(1).map(Addr)
     }
+
+    fn prev(item: Self::Item) -> Option<Self::Item> {
+        item.0.checked_sub(1).map(Addr)
+    }
 }
 
 
@@ -1407,6 +1582,50 @@ mod test {
         assert_eq!(expected_str, &blocks.as_v6().to_string())
     }
 
+    #[test]
+    fn ip_blocks_normalize() {
+        let blocks = IpBlocks::from_str(
+            "10.0.0.0-10.0.0.255, 10.0.2.0/24, 10.0.3.0-10.0.3.255, \
+             10.0.2.128/25, 192.0.2.0-192.0.2.17"
+        ).unwrap().normalize();
+        assert_eq!(
+            blocks.as_v4().to_string(),
+            "10.0.0.0/24, 10.0.2.0/23, 192.0.2.0-192.0.2.17"
+        );
+        let kinds = [true, true, false];
+        for (block, &is_prefix) in blocks.iter().zip(kinds.iter()) {
+            match (block, is_prefix) {
+                (IpBlock::Prefix(_), true) | (IpBlock::Range(_), false) => { }
+                _ => panic!("block {:?} not canonical", block)
+            }
+        }
+        assert_eq!(
+            IpResources::inherit().normalize(), IpResources::inherit()
+        );
+    }
+
+    #[test]
+    fn ip_resources_for_families() {
+        let v4 = IpResources::from_str("192.0.2.0/24").unwrap();
+        let v6 = IpResources::from_str("2001:db8::/32").unwrap();
+        let empty = IpResources::blocks(IpBlocks::empty());
+        assert_eq!(
+            IpResourcesForFamilies::new(Some(&v4), Some(&v6)).to_string(),
+            "192.0.2.0/24, 2001:db8::/32"
+        );
+        assert_eq!(
+            IpResourcesForFamilies::new(Some(&empty), Some(&v6)).to_string(),
+            "2001:db8::/32"
+        );
+        assert_eq!(
+            IpResourcesForFamilies::new(
+                Some(&v4), Some(&IpResources::inherit())
+            ).to_string(),
+            "192.0.2.0/24, inherit (IPv6)"
+        );
+        assert_eq!(IpResourcesForFamilies::new(None, None).to_string(), "");
+    }
+
     #[test]
     fn ip_blocks_cannot_parse_mix() {
         let input = "10.0.0.0, ::1, 2001:db8::/32";
@@ -1575,6 +1794,43 @@ mod test {
         assert_eq!(expected, other.intersection(&this));
     }
 
+    #[test]
+    fn ip_blocks_difference() {
+        let this = IpBlocks::from_str("10.0.0.0/16, 192.168.0.0/16").unwrap();
+        let other = IpBlocks::from_str(
+            "10.0.0.0/24, 10.0.128.0-10.1.0.255, 192.168.1.0/24"
+        ).unwrap();
+        assert_eq!(
+            this.difference(&other).as_v4().to_string(),
+            "10.0.1.0-10.0.127.255, 192.168.0.0/24, \
+             192.168.2.0-192.168.255.255"
+        );
+        assert_eq!(
+            other.difference(&this).as_v4().to_string(), "10.1.0.0/24"
+        );
+        assert!(this.difference(&this).is_empty());
+        assert_eq!(this.difference(&IpBlocks::empty()), this);
+    }
+
+    #[test]
+    fn ip_resources_from_str() {
+        let res = IpResources::from_str("inherit").unwrap();
+        assert!(res.is_inherited());
+        assert_eq!(res.as_v4().to_string(), "inherit");
+
+        let res = IpResources::from_str("2001:db8::/32, ::1").unwrap();
+        assert_eq!(res.as_v6().to_string(), "::1, 2001:db8::/32");
+    }
+
+    #[test]
+    fn ip_block_encode_canonical() {
+        let block = IpBlock::from_v4_str("10.0.0.0-10.0.255.255").unwrap();
+        assert_eq!(
+            block.encode().to_captured(Mode::Der).as_slice(),
+            b"\x03\x03\x00\x0A\x00".as_ref()
+        );
+    }
+
     #[test]
     fn ip_block_from_v4_str() {
         fn check(s: &str, prefix: bool, min: &str, max: &str) {
@@ -9,21 +9,45 @@
 //! Delegation Extension and [`AsResources`] implements the Autonomous System
 //! Identifier Delegation Extension.
 //!
+//! In addition, the module provides value types for individual resources
+//! that can be used independently of certificates: [`AsId`] and
+//! [`AsRange`] for AS numbers, and [`MaxLenPrefix`] and [`IpRange`] for
+//! IP addresses. Together with [`payload::Prefix`], they can be parsed from
+//! and formatted into their common textual forms, are serialized via serde
+//! in these forms, and have a compact binary encoding provided through the
+//! [`Compact`] trait. Finally, [`PrefixMap`] allows efficiently looking up
+//! values by prefix, including finding covering and covered prefixes.
+//!
+//! [`AsId`]: struct.AsId.html
+//! [`AsRange`]: struct.AsRange.html
 //! [`AsResources`]: struct.AsResources.html
+//! [`Compact`]: trait.Compact.html
+//! [`IpRange`]: struct.IpRange.html
 //! [`IpResources`]: struct.IpResources.html
+//! [`MaxLenPrefix`]: struct.MaxLenPrefix.html
+//! [`PrefixMap`]: struct.PrefixMap.html
+//! [`payload::Prefix`]: ../payload/struct.Prefix.html
 //! [RFC 3779]: https://tools.ietf.org/html/rfc3779
 //! [RFC 6487]: https://tools.ietf.org/html/rfc6487
 
 pub use self::asres::{
-    AsBlock, AsBlocks, AsBlocksBuilder, AsId, AsResources, AsResourcesBuilder
+    AsBlock, AsBlocks, AsBlocksBuilder, AsId, AsRange, AsResources,
+    AsResourcesBuilder
 };
 pub use self::choice::ResourcesChoice;
+pub use self::compact::{Compact, CompactError};
 pub use self::ipres::{
     Addr, AddressFamily, IpBlock, IpBlocks, IpBlocksBuilder, IpBlocksForFamily,
-    IpResources, IpResourcesBuilder, Prefix
+    IpResources, IpResourcesBuilder, IpResourcesForFamilies,
+    IpResourcesForFamily, Prefix
 };
+pub use self::prefixmap::PrefixMap;
+pub use self::values::{IpRange, MaxLenPrefix, ParseValueError};
 
 mod asres;
 mod chain;
 mod choice;
+mod compact;
 mod ipres;
+mod prefixmap;
+mod values;
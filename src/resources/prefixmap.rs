@@ -0,0 +1,529 @@
+//! A map with IP prefixes as keys.
+
+use std::iter::FromIterator;
+use crate::payload::Prefix;
+
+
+//------------ PrefixMap -----------------------------------------------------
+
+/// A map from IP prefixes to values.
+///
+/// The map is a path-compressed binary trie per address family: each node
+/// stores the full prefix it represents, so runs of nodes with only one
+/// child collapse into a single node and a lookup visits at most one node
+/// per prefix present in the map along the path instead of one node per
+/// bit.
+///
+/// In addition to looking up values by their exact prefix, the map can
+/// find the most specific prefix covering a given prefix via
+/// [`longest_match`], all prefixes covering a prefix via [`covering`], and
+/// all prefixes covered by a prefix via [`covered`]. Iteration always
+/// happens in the order of the prefixes, i.e., less specific prefixes
+/// before more specific ones with the same address and IPv4 prefixes
+/// before IPv6 prefixes.
+///
+/// [`longest_match`]: #method.longest_match
+/// [`covering`]: #method.covering
+/// [`covered`]: #method.covered
+#[derive(Clone, Debug)]
+pub struct PrefixMap<T> {
+    /// The root node for IPv4 prefixes.
+    v4: Option<Box<Node<T>>>,
+
+    /// The root node for IPv6 prefixes.
+    v6: Option<Box<Node<T>>>,
+
+    /// The number of values in the map.
+    len: usize,
+}
+
+impl<T> PrefixMap<T> {
+    /// Creates a new, empty map.
+    pub fn new() -> Self {
+        PrefixMap { v4: None, v6: None, len: 0 }
+    }
+
+    /// Returns the number of values in the map.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a value for a prefix.
+    ///
+    /// Returns the previous value for the prefix if there was one.
+    pub fn insert(&mut self, prefix: Prefix, value: T) -> Option<T> {
+        let res = Node::insert(
+            self.root_mut(prefix), prefix.to_bits(), prefix.prefix_len(),
+            value
+        );
+        if res.is_none() {
+            self.len += 1;
+        }
+        res
+    }
+
+    /// Removes the value for a prefix and returns it.
+    pub fn remove(&mut self, prefix: Prefix) -> Option<T> {
+        let res = Node::remove(
+            self.root_mut(prefix), prefix.to_bits(), prefix.prefix_len()
+        );
+        if res.is_some() {
+            self.len -= 1;
+        }
+        res
+    }
+
+    /// Returns a reference to the value for exactly the given prefix.
+    pub fn get(&self, prefix: Prefix) -> Option<&T> {
+        let (bits, len) = (prefix.to_bits(), prefix.prefix_len());
+        let mut node = self.root(prefix)?;
+        loop {
+            if !node.covers(bits, len) {
+                return None
+            }
+            if node.len == len {
+                return node.value.as_ref()
+            }
+            node = node.child(bits)?;
+        }
+    }
+
+    /// Returns a mutable reference to the value for exactly the prefix.
+    pub fn get_mut(&mut self, prefix: Prefix) -> Option<&mut T> {
+        let (bits, len) = (prefix.to_bits(), prefix.prefix_len());
+        let mut node = self.root_mut(prefix).as_mut()?;
+        loop {
+            if !node.covers(bits, len) {
+                return None
+            }
+            if node.len == len {
+                return node.value.as_mut()
+            }
+            node = node.children[bit(bits, node.len)].as_mut()?;
+        }
+    }
+
+    /// Returns whether there is a value for exactly the given prefix.
+    pub fn contains(&self, prefix: Prefix) -> bool {
+        self.get(prefix).is_some()
+    }
+
+    /// Returns the most specific entry covering the given prefix.
+    ///
+    /// This includes an entry for the prefix itself.
+    pub fn longest_match(&self, prefix: Prefix) -> Option<(Prefix, &T)> {
+        self.covering(prefix).last()
+    }
+
+    /// Returns an iterator over all entries covering the given prefix.
+    ///
+    /// This includes an entry for the prefix itself. The entries are
+    /// returned from the least to the most specific.
+    pub fn covering(
+        &self, prefix: Prefix
+    ) -> impl Iterator<Item = (Prefix, &T)> + '_ {
+        Covering {
+            node: self.root(prefix),
+            v4: prefix.is_v4(),
+            bits: prefix.to_bits(),
+            len: prefix.prefix_len(),
+        }
+    }
+
+    /// Returns an iterator over all entries covered by the given prefix.
+    ///
+    /// This includes an entry for the prefix itself. The entries are
+    /// returned in order.
+    pub fn covered(
+        &self, prefix: Prefix
+    ) -> impl Iterator<Item = (Prefix, &T)> + '_ {
+        let (bits, len) = (prefix.to_bits(), prefix.prefix_len());
+        let mut node = self.root(prefix);
+        while let Some(item) = node {
+            if item.len >= len {
+                if item.bits & mask(len) != bits {
+                    node = None
+                }
+                break
+            }
+            if !item.covers(bits, len) {
+                node = None;
+                break
+            }
+            node = item.child(bits);
+        }
+        Iter {
+            stack: node.map(|node| (node, prefix.is_v4())).into_iter()
+                .collect()
+        }
+    }
+
+    /// Returns an iterator over all entries in order.
+    pub fn iter(&self) -> impl Iterator<Item = (Prefix, &T)> + '_ {
+        Iter {
+            stack: self.v6.as_deref().map(|node| (node, false)).into_iter()
+                .chain(self.v4.as_deref().map(|node| (node, true)))
+                .collect()
+        }
+    }
+
+    /// Returns the root node for the address family of a prefix.
+    fn root(&self, prefix: Prefix) -> Option<&Node<T>> {
+        if prefix.is_v4() {
+            self.v4.as_deref()
+        }
+        else {
+            self.v6.as_deref()
+        }
+    }
+
+    /// Returns the slot of the root node for the family of a prefix.
+    fn root_mut(&mut self, prefix: Prefix) -> &mut Option<Box<Node<T>>> {
+        if prefix.is_v4() {
+            &mut self.v4
+        }
+        else {
+            &mut self.v6
+        }
+    }
+}
+
+
+//--- Default
+
+impl<T> Default for PrefixMap<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//--- FromIterator and Extend
+
+impl<T> FromIterator<(Prefix, T)> for PrefixMap<T> {
+    fn from_iter<I: IntoIterator<Item = (Prefix, T)>>(iter: I) -> Self {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl<T> Extend<(Prefix, T)> for PrefixMap<T> {
+    fn extend<I: IntoIterator<Item = (Prefix, T)>>(&mut self, iter: I) {
+        for (prefix, value) in iter {
+            self.insert(prefix, value);
+        }
+    }
+}
+
+
+//------------ Node ----------------------------------------------------------
+
+/// A node in the trie of a `PrefixMap`.
+#[derive(Clone, Debug)]
+struct Node<T> {
+    /// The bits of the node’s prefix.
+    ///
+    /// IPv4 prefixes are kept in the top 32 bits. All bits beyond the
+    /// length are zero.
+    bits: u128,
+
+    /// The length of the node’s prefix.
+    len: u8,
+
+    /// The value for the node’s prefix if there is one.
+    value: Option<T>,
+
+    /// The subtrees for the next bit being zero and one, respectively.
+    children: [Option<Box<Node<T>>>; 2],
+}
+
+impl<T> Node<T> {
+    /// Creates a new node.
+    fn new(bits: u128, len: u8, value: Option<T>) -> Box<Self> {
+        Box::new(Node {
+            bits: bits & mask(len),
+            len,
+            value,
+            children: [None, None],
+        })
+    }
+
+    /// Returns whether the node’s prefix covers the given prefix.
+    fn covers(&self, bits: u128, len: u8) -> bool {
+        self.len <= len && bits & mask(self.len) == self.bits
+    }
+
+    /// Returns the child node on the way to the given prefix.
+    fn child(&self, bits: u128) -> Option<&Self> {
+        self.children[bit(bits, self.len)].as_deref()
+    }
+
+    /// Inserts a value into the subtree in `slot`.
+    fn insert(
+        slot: &mut Option<Box<Self>>, bits: u128, len: u8, value: T
+    ) -> Option<T> {
+        let common = match slot.as_mut() {
+            None => {
+                *slot = Some(Self::new(bits, len, Some(value)));
+                return None
+            }
+            Some(node) => {
+                let common = common_len(node.bits, node.len, bits, len);
+                if common == node.len {
+                    if len == node.len {
+                        return node.value.replace(value)
+                    }
+                    return Self::insert(
+                        &mut node.children[bit(bits, node.len)],
+                        bits, len, value
+                    )
+                }
+                common
+            }
+        };
+
+        // The new prefix branches off above the node, so we need a new
+        // node at the branching point.
+        let old = slot.take()?;
+        let mut node = if len == common {
+            Self::new(bits, len, Some(value))
+        }
+        else {
+            let mut node = Self::new(bits, common, None);
+            node.children[bit(bits, common)] = Some(
+                Self::new(bits, len, Some(value))
+            );
+            node
+        };
+        let old_bit = bit(old.bits, common);
+        node.children[old_bit] = Some(old);
+        *slot = Some(node);
+        None
+    }
+
+    /// Removes the value for a prefix from the subtree in `slot`.
+    fn remove(
+        slot: &mut Option<Box<Self>>, bits: u128, len: u8
+    ) -> Option<T> {
+        let node = slot.as_mut()?;
+        if !node.covers(bits, len) {
+            return None
+        }
+        let res = if node.len == len {
+            node.value.take()
+        }
+        else {
+            Self::remove(&mut node.children[bit(bits, node.len)], bits, len)
+        };
+
+        // Drop nodes that have become unnecessary.
+        if node.value.is_none() {
+            match node.children {
+                [None, None] => *slot = None,
+                [Some(_), None] => {
+                    let child = node.children[0].take();
+                    *slot = child;
+                }
+                [None, Some(_)] => {
+                    let child = node.children[1].take();
+                    *slot = child;
+                }
+                _ => { }
+            }
+        }
+        res
+    }
+}
+
+/// Returns the bit at position `depth` counted from the top.
+fn bit(bits: u128, depth: u8) -> usize {
+    ((bits >> (127 - depth)) & 1) as usize
+}
+
+/// Returns a mask with the top `len` bits set.
+fn mask(len: u8) -> u128 {
+    if len == 0 {
+        0
+    }
+    else {
+        !(!0 >> len)
+    }
+}
+
+/// Returns the length of the common part of two prefixes.
+fn common_len(left: u128, left_len: u8, right: u128, right_len: u8) -> u8 {
+    let diff = (left ^ right).leading_zeros();
+    (diff as u8).min(left_len).min(right_len)
+}
+
+
+//------------ Covering ------------------------------------------------------
+
+/// An iterator over the entries covering a prefix.
+struct Covering<'a, T> {
+    /// The next node to look at or `None` if we are done.
+    node: Option<&'a Node<T>>,
+
+    /// Whether the prefix is an IPv4 prefix.
+    v4: bool,
+
+    /// The bits of the prefix.
+    bits: u128,
+
+    /// The length of the prefix.
+    len: u8,
+}
+
+impl<'a, T> Iterator for Covering<'a, T> {
+    type Item = (Prefix, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let node = self.node?;
+            if !node.covers(self.bits, self.len) {
+                self.node = None;
+                return None
+            }
+            self.node = if node.len < self.len {
+                node.child(self.bits)
+            }
+            else {
+                None
+            };
+            if let Some(value) = node.value.as_ref() {
+                return Some((
+                    Prefix::from_bits(self.v4, node.bits, node.len), value
+                ))
+            }
+        }
+    }
+}
+
+
+//------------ Iter ----------------------------------------------------------
+
+/// An iterator over the entries of a subtree in order.
+struct Iter<'a, T> {
+    /// The nodes still to visit and whether they are IPv4 nodes.
+    stack: Vec<(&'a Node<T>, bool)>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = (Prefix, &'a T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, v4) = self.stack.pop()?;
+            for child in node.children.iter().rev() {
+                if let Some(child) = child.as_deref() {
+                    self.stack.push((child, v4))
+                }
+            }
+            if let Some(value) = node.value.as_ref() {
+                return Some((
+                    Prefix::from_bits(v4, node.bits, node.len), value
+                ))
+            }
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    fn p(s: &str) -> Prefix {
+        Prefix::from_str(s).unwrap()
+    }
+
+    fn map() -> PrefixMap<u32> {
+        [
+            ("10.0.0.0/8", 1),
+            ("10.1.0.0/16", 2),
+            ("10.1.2.0/24", 3),
+            ("10.128.0.0/9", 4),
+            ("192.0.2.0/24", 5),
+            ("0.0.0.0/0", 6),
+            ("2001:db8::/32", 7),
+            ("2001:db8:1::/48", 8),
+        ].iter().map(|&(prefix, value)| (p(prefix), value)).collect()
+    }
+
+    fn values<'a>(iter: impl Iterator<Item = (Prefix, &'a u32)>) -> Vec<u32> {
+        iter.map(|item| *item.1).collect()
+    }
+
+    #[test]
+    fn exact() {
+        let mut map = map();
+        assert_eq!(map.len(), 8);
+        assert_eq!(map.get(p("10.1.0.0/16")), Some(&2));
+        assert_eq!(map.get(p("10.1.0.0/17")), None);
+        assert_eq!(map.get(p("10.0.0.0/9")), None);
+        assert_eq!(map.get(p("2001:db8::/32")), Some(&7));
+        assert_eq!(map.get(p("::/0")), None);
+        assert_eq!(map.insert(p("10.1.0.0/16"), 12), Some(2));
+        *map.get_mut(p("10.1.2.0/24")).unwrap() += 10;
+        assert_eq!(map.get(p("10.1.2.0/24")), Some(&13));
+        assert_eq!(map.len(), 8);
+    }
+
+    #[test]
+    fn longest_match() {
+        let map = map();
+        assert_eq!(
+            map.longest_match(p("10.1.2.128/25")), Some((p("10.1.2.0/24"), &3))
+        );
+        assert_eq!(
+            map.longest_match(p("10.1.3.0/24")), Some((p("10.1.0.0/16"), &2))
+        );
+        assert_eq!(
+            map.longest_match(p("11.0.0.0/8")), Some((p("0.0.0.0/0"), &6))
+        );
+        assert_eq!(map.longest_match(p("2001:db9::/32")), None);
+        assert_eq!(
+            map.longest_match(p("2001:db8:1::/64")),
+            Some((p("2001:db8:1::/48"), &8))
+        );
+    }
+
+    #[test]
+    fn covering_and_covered() {
+        let map = map();
+        assert_eq!(values(map.covering(p("10.1.2.0/24"))), vec![6, 1, 2, 3]);
+        assert_eq!(values(map.covering(p("10.2.0.0/16"))), vec![6, 1]);
+        assert_eq!(values(map.covered(p("10.0.0.0/8"))), vec![1, 2, 3, 4]);
+        assert_eq!(values(map.covered(p("10.0.0.0/7"))), vec![1, 2, 3, 4]);
+        assert_eq!(values(map.covered(p("10.1.2.0/23"))), vec![3]);
+        assert_eq!(values(map.covered(p("10.2.0.0/16"))), Vec::<u32>::new());
+        assert_eq!(
+            values(map.covered(p("0.0.0.0/0"))), vec![6, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(values(map.iter()), vec![6, 1, 2, 3, 4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn remove() {
+        let mut map = map();
+        assert_eq!(map.remove(p("10.1.0.0/16")), Some(2));
+        assert_eq!(map.remove(p("10.1.0.0/16")), None);
+        assert_eq!(map.remove(p("10.0.0.0/9")), None);
+        assert_eq!(map.len(), 7);
+        assert_eq!(values(map.covered(p("10.0.0.0/8"))), vec![1, 3, 4]);
+        for (prefix, _) in map.clone().iter() {
+            assert!(map.remove(prefix).is_some());
+        }
+        assert!(map.is_empty());
+        assert!(map.v4.is_none() && map.v6.is_none());
+    }
+}
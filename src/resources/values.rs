@@ -0,0 +1,506 @@
+//! Value types for prefixes and address ranges.
+//!
+//! Unlike the types used in the certificate extensions, the types in this
+//! module carry their address family with them and can therefore be used
+//! on their own, e.g., in ROA payload, SLURM files, or configuration.
+
+use std::{error, fmt};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use crate::payload::Prefix;
+use super::compact::{take_slice, take_u8, Compact, CompactError};
+
+
+//------------ MaxLenPrefix --------------------------------------------------
+
+/// A prefix with an optional maximum length.
+///
+/// This is the prefix as it appears in ROAs: the prefix itself and the
+/// maximum length of more specific prefixes it also covers. The textual
+/// form appends the maximum length to the prefix separated by a hyphen,
+/// e.g., `192.0.2.0/24-26`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct MaxLenPrefix {
+    /// The prefix.
+    prefix: Prefix,
+
+    /// The maximum length if present.
+    max_len: Option<u8>,
+}
+
+impl MaxLenPrefix {
+    /// Creates a new value from a prefix and optional maximum length.
+    ///
+    /// Returns an error if the maximum length is smaller than the prefix
+    /// length or larger than the length of an address.
+    pub fn new(
+        prefix: Prefix, max_len: Option<u8>
+    ) -> Result<Self, ParseValueError> {
+        if let Some(max_len) = max_len {
+            let addr_len = if prefix.is_v4() { 32 } else { 128 };
+            if max_len < prefix.prefix_len() || max_len > addr_len {
+                return Err(ParseValueError::BadMaxLen)
+            }
+        }
+        Ok(MaxLenPrefix { prefix, max_len })
+    }
+
+    /// Returns the prefix.
+    pub fn prefix(self) -> Prefix {
+        self.prefix
+    }
+
+    /// Returns the maximum length if present.
+    pub fn max_len(self) -> Option<u8> {
+        self.max_len
+    }
+
+    /// Returns the effective maximum length.
+    ///
+    /// If no maximum length is present, this is the prefix length.
+    pub fn resolved_max_len(self) -> u8 {
+        self.max_len.unwrap_or_else(|| self.prefix.prefix_len())
+    }
+}
+
+
+//--- From
+
+impl From<Prefix> for MaxLenPrefix {
+    fn from(prefix: Prefix) -> Self {
+        MaxLenPrefix { prefix, max_len: None }
+    }
+}
+
+
+//--- FromStr and Display
+
+impl FromStr for MaxLenPrefix {
+    type Err = ParseValueError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, max_len) = match s.find('-') {
+            Some(pos) => {
+                let max_len = u8::from_str(&s[pos + 1..])
+                    .map_err(|_| ParseValueError::BadMaxLen)?;
+                (&s[..pos], Some(max_len))
+            }
+            None => (s, None)
+        };
+        let prefix = Prefix::from_str(prefix)
+            .map_err(|_| ParseValueError::BadPrefix)?;
+        Self::new(prefix, max_len)
+    }
+}
+
+impl fmt::Display for MaxLenPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.max_len {
+            Some(max_len) => write!(f, "{}-{}", self.prefix, max_len),
+            None => self.prefix.fmt(f)
+        }
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for MaxLenPrefix {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for MaxLenPrefix {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(de::Error::custom)
+    }
+}
+
+
+//--- Compact
+
+impl Compact for MaxLenPrefix {
+    /// Appends the encoded value.
+    ///
+    /// The value is encoded as the prefix followed by one octet with the
+    /// maximum length or 255 if there is none.
+    fn compose_compact(&self, target: &mut Vec<u8>) {
+        self.prefix.compose_compact(target);
+        target.push(self.max_len.unwrap_or(0xFF))
+    }
+
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError> {
+        let prefix = Prefix::take_compact(data)?;
+        let max_len = match take_u8(data)? {
+            0xFF => None,
+            max_len => Some(max_len)
+        };
+        Self::new(prefix, max_len).map_err(|_| CompactError)
+    }
+}
+
+
+//------------ IpRange -------------------------------------------------------
+
+/// A range of IP addresses.
+///
+/// Both ends of the range are inclusive and of the same address family.
+/// Ranges are ordered by their first and then their last address, with
+/// IPv4 ranges ordering before IPv6 ranges.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct IpRange {
+    /// The first address of the range.
+    min: IpAddr,
+
+    /// The last address of the range.
+    max: IpAddr,
+}
+
+impl IpRange {
+    /// Creates a new range from its first and last address.
+    ///
+    /// Returns an error if the addresses are of different families or if
+    /// `min` is larger than `max`.
+    pub fn new(min: IpAddr, max: IpAddr) -> Result<Self, ParseValueError> {
+        if min.is_ipv4() != max.is_ipv4() || min > max {
+            return Err(ParseValueError::BadRange)
+        }
+        Ok(IpRange { min, max })
+    }
+
+    /// Returns the first address of the range.
+    pub fn min(self) -> IpAddr {
+        self.min
+    }
+
+    /// Returns the last address of the range.
+    pub fn max(self) -> IpAddr {
+        self.max
+    }
+
+    /// Returns whether this is a range of IPv4 addresses.
+    pub fn is_v4(self) -> bool {
+        self.min.is_ipv4()
+    }
+
+    /// Returns whether the range contains the given address.
+    pub fn contains(self, addr: IpAddr) -> bool {
+        addr.is_ipv4() == self.is_v4() && self.min <= addr && addr <= self.max
+    }
+
+    /// Returns whether the range contains all of another range.
+    pub fn covers(self, other: IpRange) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+}
+
+
+//--- From
+
+impl From<Prefix> for IpRange {
+    fn from(prefix: Prefix) -> Self {
+        let len = u32::from(prefix.prefix_len());
+        let (min, max) = match prefix.addr() {
+            IpAddr::V4(addr) => {
+                let host = (!0u32).checked_shr(len).unwrap_or(0);
+                (
+                    IpAddr::from(addr),
+                    IpAddr::from(Ipv4Addr::from(u32::from(addr) | host))
+                )
+            }
+            IpAddr::V6(addr) => {
+                let host = (!0u128).checked_shr(len).unwrap_or(0);
+                (
+                    IpAddr::from(addr),
+                    IpAddr::from(Ipv6Addr::from(u128::from(addr) | host))
+                )
+            }
+        };
+        IpRange { min, max }
+    }
+}
+
+
+//--- FromStr and Display
+
+impl FromStr for IpRange {
+    type Err = ParseValueError;
+
+    /// Parses a range from a string.
+    ///
+    /// The range can be given as two addresses separated by a hyphen, as
+    /// a prefix, or as a single address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(pos) = s.find('-') {
+            let min = IpAddr::from_str(&s[..pos])
+                .map_err(|_| ParseValueError::BadRange)?;
+            let max = IpAddr::from_str(&s[pos + 1..])
+                .map_err(|_| ParseValueError::BadRange)?;
+            Self::new(min, max)
+        }
+        else if s.contains('/') {
+            Prefix::from_str(s).map(Into::into)
+                .map_err(|_| ParseValueError::BadPrefix)
+        }
+        else {
+            let addr = IpAddr::from_str(s)
+                .map_err(|_| ParseValueError::BadRange)?;
+            Ok(IpRange { min: addr, max: addr })
+        }
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.min, self.max)
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for IpRange {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S
+    ) -> Result<S::Ok, S::Error> {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for IpRange {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D
+    ) -> Result<Self, D::Error> {
+        let string = String::deserialize(deserializer)?;
+        Self::from_str(&string).map_err(de::Error::custom)
+    }
+}
+
+
+//--- Compact
+
+impl Compact for IpRange {
+    /// Appends the encoded value.
+    ///
+    /// The value is encoded as one octet with the address family, 4 or 6,
+    /// followed by the first and last address.
+    fn compose_compact(&self, target: &mut Vec<u8>) {
+        match (self.min, self.max) {
+            (IpAddr::V4(min), IpAddr::V4(max)) => {
+                target.push(4);
+                target.extend_from_slice(&min.octets());
+                target.extend_from_slice(&max.octets());
+            }
+            (IpAddr::V6(min), IpAddr::V6(max)) => {
+                target.push(6);
+                target.extend_from_slice(&min.octets());
+                target.extend_from_slice(&max.octets());
+            }
+            _ => unreachable!()
+        }
+    }
+
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError> {
+        let (min, max) = match take_u8(data)? {
+            4 => (take_v4(data, 4)?, take_v4(data, 4)?),
+            6 => (take_v6(data, 16)?, take_v6(data, 16)?),
+            _ => return Err(CompactError)
+        };
+        Self::new(min, max).map_err(|_| CompactError)
+    }
+}
+
+
+//------------ Compact for Prefix --------------------------------------------
+
+impl Compact for Prefix {
+    /// Appends the encoded value.
+    ///
+    /// The value is encoded as one octet with the address family, 4 or 6,
+    /// one octet with the prefix length, and as many octets of the address
+    /// as are necessary to contain the prefix.
+    fn compose_compact(&self, target: &mut Vec<u8>) {
+        let len = prefix_octets(self.prefix_len());
+        match self.addr() {
+            IpAddr::V4(addr) => {
+                target.push(4);
+                target.push(self.prefix_len());
+                target.extend_from_slice(&addr.octets()[..len]);
+            }
+            IpAddr::V6(addr) => {
+                target.push(6);
+                target.push(self.prefix_len());
+                target.extend_from_slice(&addr.octets()[..len]);
+            }
+        }
+    }
+
+    fn take_compact(data: &mut &[u8]) -> Result<Self, CompactError> {
+        let family = take_u8(data)?;
+        let prefix_len = take_u8(data)?;
+        let len = prefix_octets(prefix_len);
+        let addr = match family {
+            4 if prefix_len <= 32 => take_v4(data, len)?,
+            6 if prefix_len <= 128 => take_v6(data, len)?,
+            _ => return Err(CompactError)
+        };
+        Prefix::new(addr, prefix_len).map_err(|_| CompactError)
+    }
+}
+
+/// Returns the number of octets necessary for a prefix of the given length.
+fn prefix_octets(prefix_len: u8) -> usize {
+    usize::from(prefix_len / 8) + usize::from(prefix_len & 7 != 0)
+}
+
+/// Takes the first `len` octets of an IPv4 address from `data`.
+fn take_v4(data: &mut &[u8], len: usize) -> Result<IpAddr, CompactError> {
+    let mut octets = [0u8; 4];
+    octets[..len].copy_from_slice(take_slice(data, len)?);
+    Ok(Ipv4Addr::from(octets).into())
+}
+
+/// Takes the first `len` octets of an IPv6 address from `data`.
+fn take_v6(data: &mut &[u8], len: usize) -> Result<IpAddr, CompactError> {
+    let mut octets = [0u8; 16];
+    octets[..len].copy_from_slice(take_slice(data, len)?);
+    Ok(Ipv6Addr::from(octets).into())
+}
+
+
+//------------ ParseValueError -----------------------------------------------
+
+/// A value could not be created from its components or parsed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ParseValueError {
+    /// The prefix was invalid.
+    BadPrefix,
+
+    /// The maximum length was invalid or inconsistent with the prefix.
+    BadMaxLen,
+
+    /// The addresses of a range were invalid or inconsistent.
+    BadRange,
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ParseValueError::BadPrefix => "invalid prefix",
+            ParseValueError::BadMaxLen => "invalid maximum length",
+            ParseValueError::BadRange => "invalid address range",
+        })
+    }
+}
+
+impl error::Error for ParseValueError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn round_trip<T>(value: T)
+    where
+        T: Compact + fmt::Debug + Eq + Serialize,
+        T: for<'de> Deserialize<'de>
+    {
+        assert_eq!(T::from_compact(&value.to_compact()).unwrap(), value);
+        assert_eq!(
+            serde_json::from_str::<T>(
+                &serde_json::to_string(&value).unwrap()
+            ).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn max_len_prefix() {
+        let value = MaxLenPrefix::from_str("10.0.0.0/8-24").unwrap();
+        assert_eq!(value.prefix(), Prefix::from_str("10.0.0.0/8").unwrap());
+        assert_eq!(value.max_len(), Some(24));
+        assert_eq!(value.to_string(), "10.0.0.0/8-24");
+        assert_eq!(value.to_compact(), b"\x04\x08\x0a\x18");
+        round_trip(value);
+
+        let value = MaxLenPrefix::from_str("2001:db8::/32").unwrap();
+        assert_eq!(value.max_len(), None);
+        assert_eq!(value.resolved_max_len(), 32);
+        assert_eq!(value.to_string(), "2001:db8::/32");
+        round_trip(value);
+
+        assert_eq!(
+            MaxLenPrefix::from_str("10.0.0.0/8-7"),
+            Err(ParseValueError::BadMaxLen)
+        );
+        assert_eq!(
+            MaxLenPrefix::from_str("10.0.0.0/8-33"),
+            Err(ParseValueError::BadMaxLen)
+        );
+        assert_eq!(
+            MaxLenPrefix::from_str("10.0.0.1/8"),
+            Err(ParseValueError::BadPrefix)
+        );
+        assert!(
+            MaxLenPrefix::from_str("10.0.0.0/8").unwrap()
+            < MaxLenPrefix::from_str("10.0.0.0/8-24").unwrap()
+        );
+    }
+
+    #[test]
+    fn ip_range() {
+        let value = IpRange::from_str("192.0.2.0/24").unwrap();
+        assert_eq!(value.to_string(), "192.0.2.0-192.0.2.255");
+        assert!(value.contains(IpAddr::from_str("192.0.2.17").unwrap()));
+        assert!(!value.contains(IpAddr::from_str("192.0.3.0").unwrap()));
+        assert!(value.covers(IpRange::from_str("192.0.2.17").unwrap()));
+        round_trip(value);
+
+        let value = IpRange::from_str("2001:db8::-2001:db8::ff").unwrap();
+        assert!(!value.is_v4());
+        round_trip(value);
+        round_trip(IpRange::from_str("::/0").unwrap());
+
+        assert_eq!(
+            IpRange::from_str("192.0.2.1-192.0.2.0"),
+            Err(ParseValueError::BadRange)
+        );
+        assert_eq!(
+            IpRange::from_str("192.0.2.0-2001:db8::"),
+            Err(ParseValueError::BadRange)
+        );
+        assert!(
+            IpRange::from_str("192.0.2.0/24").unwrap()
+            < IpRange::from_str("::/0").unwrap()
+        );
+    }
+
+    #[test]
+    fn compact_errors() {
+        assert_eq!(
+            Prefix::from_compact(b"\x04\x18\xc0\x00"), Err(CompactError)
+        );
+        assert_eq!(Prefix::from_compact(b"\x04\x21"), Err(CompactError));
+        assert_eq!(Prefix::from_compact(b"\x05\x00"), Err(CompactError));
+        assert_eq!(
+            Prefix::from_compact(b"\x04\x18\xc0\x00\x02\x00"),
+            Err(CompactError)
+        );
+        assert_eq!(
+            Prefix::from_compact(b"\x04\x18\xc0\x00\x02").unwrap(),
+            Prefix::from_str("192.0.2.0/24").unwrap()
+        );
+    }
+}
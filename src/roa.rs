@@ -2,24 +2,24 @@
 //!
 //! For details, see RFC 6482.
 
-use std::mem;
+use std::{fmt, mem};
 use std::iter::FromIterator;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::sync::Arc;
 use bcder::{decode, encode};
-use bcder::{Captured, Mode, OctetString, Oid, Tag, xerr};
+use bcder::{Captured, ConstOid, Mode, OctetString, Tag, xerr};
 use bcder::encode::{PrimitiveContent, Values};
 use bytes::Bytes;
 use serde::{Serialize, Serializer, Deserialize, Deserializer};
-use crate::oid;
+use crate::{locate, oid};
 use crate::cert::{Cert, ResourceCert};
 use crate::crypto::{Signer, SigningError};
 use crate::resources::{
     Addr, AddressFamily, AsId, IpBlocks, IpResources, Prefix
 };
-use crate::sigobj::{SignedObject, SignedObjectBuilder};
+use crate::sigobj::{SignedContent, SignedObject, SignedObjectBuilder};
 use crate::tal::TalInfo;
-use crate::x509::ValidationError;
+use crate::x509::{DecodeMode, Time, ValidationError};
 
 
 //------------ Roa -----------------------------------------------------------
@@ -35,24 +35,70 @@ impl Roa {
         source: S,
         strict: bool
     ) -> Result<Self, S::Err> {
-        let signed = SignedObject::decode(source, strict)?;
-        if signed.content_type().ne(&oid::ROUTE_ORIGIN_AUTHZ) {
-            return Err(decode::Malformed.into())
-        }
-        let content = signed.decode_content(|cons| {
-            RouteOriginAttestation::take_from(cons)
-        })?;
+        Self::decode_with_mode(source, DecodeMode::from_strict(strict))
+    }
+
+    /// Decodes a ROA using the given decoding mode.
+    pub fn decode_with_mode<S: decode::Source>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        let (signed, content) = SignedObject::decode_with_content_and_mode(
+            source, mode
+        )?;
+        Ok(Roa { signed, content })
+    }
+
+    /// Decodes a ROA, locating any decoding error.
+    ///
+    /// This is the same as [`decode_with_mode`] but the error includes the
+    /// byte offset and ASN.1 path at which decoding failed.
+    ///
+    /// [`decode_with_mode`]: #method.decode_with_mode
+    pub fn decode_located(
+        data: Bytes,
+        mode: DecodeMode,
+    ) -> Result<Self, locate::Error> {
+        let (signed, content) = locate::decode_signed(
+            data, mode, &locate::ROUTE_ORIGIN_ATTESTATION
+        )?;
         Ok(Roa { signed, content })
     }
 
+    /// Returns the canonical encoding of the ROA content.
+    ///
+    /// Since every ROA is signed with a new one-off key, the signed object
+    /// itself can’t be reproduced. Instead, this returns the DER encoded
+    /// content. See [`RouteOriginAttestation::canonical`] for details.
+    ///
+    /// [`RouteOriginAttestation::canonical`]: struct.RouteOriginAttestation.html#method.canonical
+    pub fn canonical(&self) -> Bytes {
+        self.content.canonical()
+    }
+
     pub fn process<F>(
+        self,
+        issuer: &ResourceCert,
+        strict: bool,
+        check_crl: F
+    ) -> Result<RouteOriginAttestation, ValidationError>
+    where F: FnOnce(&Cert) -> Result<(), ValidationError> {
+        self.process_at(issuer, strict, Time::now(), check_crl)
+    }
+
+    /// Processes the ROA at the given time.
+    ///
+    /// This is the same as `process` but checks the validity of the EE
+    /// certificate against `now` rather than the current time.
+    pub fn process_at<F>(
         mut self,
         issuer: &ResourceCert,
         strict: bool,
+        now: Time,
         check_crl: F
     ) -> Result<RouteOriginAttestation, ValidationError>
     where F: FnOnce(&Cert) -> Result<(), ValidationError> {
-        let cert = self.signed.validate(issuer, strict)?;
+        let cert = self.signed.validate_at(issuer, strict, now)?;
         check_crl(cert.as_ref())?;
         self.content.validate(cert)?;
         Ok(self.content)
@@ -72,6 +118,19 @@ impl Roa {
     pub fn cert(&self) -> &Cert {
         self.signed.cert()
     }
+
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signed.signing_time()
+    }
+
+    /// Returns the binary signing time attribute if present.
+    ///
+    /// The value is the number of seconds since the Unix epoch as defined
+    /// in RFC 6019.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.signed.binary_signing_time()
+    }
 }
 
 
@@ -137,6 +196,59 @@ impl RouteOriginAttestation {
                     .map(|addr| FriendlyRoaIpAddress::new(addr, false))
             )
     }
+
+    /// Returns an iterator over the route origins authorized by the ROA.
+    ///
+    /// Each item is a triple of the prefix, the maximum length with the
+    /// default applied if it is missing, and the AS number.
+    pub fn iter_origins<'a>(
+        &'a self
+    ) -> impl Iterator<Item=(FriendlyRoaIpAddress, u8, AsId)> + 'a {
+        let as_id = self.as_id;
+        self.iter().map(move |addr| {
+            let max_length = addr.max_length();
+            (addr, max_length, as_id)
+        })
+    }
+
+    /// Returns an iterator over all entries with a problematic max length.
+    ///
+    /// An entry is flagged if its maximum length is outside the bounds
+    /// given by the prefix length and the address family or if expanding
+    /// it would result in more than `limit` individual prefixes. See
+    /// [`MaxLengthIssue`] for details.
+    ///
+    /// [`MaxLengthIssue`]: enum.MaxLengthIssue.html
+    pub fn max_length_issues<'a>(
+        &'a self, limit: usize
+    ) -> impl Iterator<Item=(FriendlyRoaIpAddress, MaxLengthIssue)> + 'a {
+        self.iter().filter_map(move |addr| {
+            addr.max_length_issue(limit).map(|issue| (addr, issue))
+        })
+    }
+
+    /// Returns an iterator over the individual VRPs of the ROA.
+    ///
+    /// Each entry is expanded into all prefixes between its prefix length
+    /// and its maximum length. The items are the same triples as returned
+    /// by [`iter_origins`] with the maximum length always equal to the
+    /// prefix length.
+    ///
+    /// Entries that have a [`MaxLengthIssue`] for the given `limit` are
+    /// skipped entirely, so no entry results in more than `limit` items.
+    /// Use [`max_length_issues`] to find out which entries were skipped.
+    ///
+    /// [`iter_origins`]: #method.iter_origins
+    /// [`max_length_issues`]: #method.max_length_issues
+    /// [`MaxLengthIssue`]: enum.MaxLengthIssue.html
+    pub fn iter_vrps<'a>(
+        &'a self, limit: usize
+    ) -> impl Iterator<Item=(FriendlyRoaIpAddress, u8, AsId)> + 'a {
+        let as_id = self.as_id;
+        self.iter().filter(move |addr| {
+            addr.max_length_issue(limit).is_none()
+        }).flat_map(move |addr| VrpExpansion::new(&addr, as_id))
+    }
 }
 
 impl RouteOriginAttestation {
@@ -188,13 +300,17 @@ impl RouteOriginAttestation {
         &mut self,
         cert: ResourceCert
     ) -> Result<(), ValidationError> {
+        // There must be at least one prefix.
+        if self.v4_addrs.is_empty() && self.v6_addrs.is_empty() {
+            return Err(ValidationError)
+        }
         if !self.v4_addrs.is_empty() {
             let blocks = cert.v4_resources();
             if blocks.is_empty() {
                 return Err(ValidationError)
             }
             for addr in self.v4_addrs.iter() {
-                if !blocks.contains_roa(&addr) {
+                if !addr.check_lengths(32) || !blocks.contains_roa(&addr) {
                     return Err(ValidationError)
                 }
             }
@@ -205,7 +321,7 @@ impl RouteOriginAttestation {
                 return Err(ValidationError)
             }
             for addr in self.v6_addrs.iter() {
-                if !blocks.contains_roa(&addr) {
+                if !addr.check_lengths(128) || !blocks.contains_roa(&addr) {
                     return Err(ValidationError)
                 }
             }
@@ -214,6 +330,21 @@ impl RouteOriginAttestation {
         Ok(())
     }
 
+    /// Returns the canonical encoding of the attestation.
+    ///
+    /// The attestation is DER encoded with the addresses of each family
+    /// in the order described by [`RoaIpAddressesBuilder`]. Thus,
+    /// attestations for the same AS number and set of addresses result in
+    /// the same octets.
+    ///
+    /// [`RoaIpAddressesBuilder`]: struct.RoaIpAddressesBuilder.html
+    pub fn canonical(&self) -> Bytes {
+        let mut builder = RoaBuilder::new(self.as_id);
+        self.v4_addrs.iter().for_each(|addr| builder.push_v4(addr));
+        self.v6_addrs.iter().for_each(|addr| builder.push_v6(addr));
+        builder.to_attestation().to_content_bytes()
+    }
+
     pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
         encode::sequence((
             // version is DEFAULT
@@ -228,6 +359,24 @@ impl RouteOriginAttestation {
 }
 
 
+
+//--- SignedContent
+
+impl SignedContent for RouteOriginAttestation {
+    const CONTENT_TYPE: ConstOid = oid::ROUTE_ORIGIN_AUTHZ;
+
+    fn take_content_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from(cons)
+    }
+
+    fn to_content_bytes(&self) -> Bytes {
+        self.encode_ref().to_captured(Mode::Der).into_bytes()
+    }
+}
+
+
 //------------ RoaIpAddresses ------------------------------------------------
 
 #[derive(Clone, Debug)]
@@ -313,9 +462,30 @@ impl RoaIpAddress {
         self.prefix
     }
 
+    /// Returns the maximum length if present.
+    pub fn max_length(&self) -> Option<u8> {
+        self.max_length
+    }
+
     pub fn range(&self) -> (Addr, Addr) {
         self.prefix.range()
     }
+
+    /// Checks that prefix and maximum length are acceptable.
+    ///
+    /// Both must not exceed `family_len`, the length of an address of the
+    /// address family, and the maximum length must not be smaller than the
+    /// prefix length.
+    fn check_lengths(&self, family_len: u8) -> bool {
+        let addr_len = self.prefix.addr_len();
+        if addr_len > family_len {
+            return false
+        }
+        match self.max_length {
+            Some(max_len) => max_len >= addr_len && max_len <= family_len,
+            None => true
+        }
+    }
 }
 
 impl RoaIpAddress {
@@ -389,6 +559,157 @@ impl FriendlyRoaIpAddress {
             self.addr.prefix.addr_len()
         )
     }
+
+    /// Returns the length of an address of the address family.
+    pub fn family_length(&self) -> u8 {
+        if self.v4 { 32 } else { 128 }
+    }
+
+    /// Returns the number of prefixes covered by the entry.
+    ///
+    /// This is the number of individual VRPs the entry expands into. The
+    /// value saturates at `u128::MAX`. If the maximum length is smaller
+    /// than the prefix length, returns zero.
+    pub fn vrp_count(&self) -> u128 {
+        let len = self.address_length();
+        let max_len = self.max_length();
+        if max_len < len {
+            return 0
+        }
+        // There are 2^(max_len - len + 1) - 1 prefixes.
+        match 1u128.checked_shl(u32::from(max_len - len) + 1) {
+            Some(count) => count - 1,
+            None => u128::MAX
+        }
+    }
+
+    /// Returns the problem with the maximum length of the entry if any.
+    ///
+    /// Expansion into more than `limit` prefixes is considered excessive.
+    pub fn max_length_issue(&self, limit: usize) -> Option<MaxLengthIssue> {
+        let family_len = self.family_length();
+        if self.address_length() > family_len
+            || self.max_length() > family_len
+        {
+            Some(MaxLengthIssue::ExceedsFamily)
+        }
+        else if self.max_length() < self.address_length() {
+            Some(MaxLengthIssue::BelowPrefixLength)
+        }
+        else {
+            let count = self.vrp_count();
+            if count > limit as u128 {
+                Some(MaxLengthIssue::ExcessiveExpansion(count))
+            }
+            else {
+                None
+            }
+        }
+    }
+}
+
+
+//------------ VrpExpansion --------------------------------------------------
+
+/// An iterator over the prefixes covered by a single ROA entry.
+///
+/// The entry must have valid lengths and expand into no more than
+/// `usize::MAX` prefixes.
+struct VrpExpansion {
+    /// The bits of the entry’s address.
+    bits: u128,
+
+    /// Is this an IPv4 entry?
+    v4: bool,
+
+    /// The prefix length of the entry.
+    len: u8,
+
+    /// The maximum length of the entry.
+    max_len: u8,
+
+    /// The prefix length of the next item.
+    cur_len: u8,
+
+    /// The index of the next item among those with `cur_len`.
+    index: u128,
+
+    /// The AS number of the ROA.
+    as_id: AsId,
+}
+
+impl VrpExpansion {
+    fn new(addr: &FriendlyRoaIpAddress, as_id: AsId) -> Self {
+        VrpExpansion {
+            bits: addr.addr.prefix.addr().to_bits(),
+            v4: addr.v4,
+            len: addr.address_length(),
+            max_len: addr.max_length(),
+            cur_len: addr.address_length(),
+            index: 0,
+            as_id
+        }
+    }
+}
+
+impl Iterator for VrpExpansion {
+    type Item = (FriendlyRoaIpAddress, u8, AsId);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.cur_len > self.max_len {
+            return None
+        }
+        let cur_len = self.cur_len;
+        let bits = self.bits | self.index.checked_shl(
+            128 - u32::from(cur_len)
+        ).unwrap_or(0);
+        let addr = FriendlyRoaIpAddress::new(
+            RoaIpAddress::new(Prefix::new(bits, cur_len), Some(cur_len)),
+            self.v4
+        );
+
+        // There are 2^(cur_len - len) prefixes with cur_len. Since the
+        // entry’s lengths are valid, cur_len is at most 128 and can be
+        // incremented safely.
+        self.index += 1;
+        if self.index >> u32::from(cur_len - self.len) != 0 {
+            self.index = 0;
+            self.cur_len += 1;
+        }
+        Some((addr, cur_len, self.as_id))
+    }
+}
+
+
+//------------ MaxLengthIssue ------------------------------------------------
+
+/// A problem with the maximum length of a ROA entry.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum MaxLengthIssue {
+    /// The prefix or maximum length exceed the length of an address.
+    ExceedsFamily,
+
+    /// The maximum length is smaller than the prefix length.
+    BelowPrefixLength,
+
+    /// The entry expands into the given excessive number of prefixes.
+    ExcessiveExpansion(u128),
+}
+
+impl fmt::Display for MaxLengthIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MaxLengthIssue::ExceedsFamily => {
+                f.write_str("max length exceeds address length")
+            }
+            MaxLengthIssue::BelowPrefixLength => {
+                f.write_str("max length smaller than prefix length")
+            }
+            MaxLengthIssue::ExcessiveExpansion(count) => {
+                write!(f, "max length expands into {} prefixes", count)
+            }
+        }
+    }
 }
 
 
@@ -542,12 +863,7 @@ impl RoaBuilder {
         assert!(v4.is_some() || v6.is_some());
         sigobj.set_v4_resources(v4);
         sigobj.set_v6_resources(v6);
-        let signed = sigobj.finalize(
-            Oid(oid::ROUTE_ORIGIN_AUTHZ.0.into()),
-            content.encode_ref().to_captured(Mode::Der).into_bytes(),
-            signer,
-            issuer_key,
-        )?;
+        let signed = sigobj.finalize_content(&content, signer, issuer_key)?;
         Ok(Roa { signed, content })
     }
 }
@@ -555,6 +871,12 @@ impl RoaBuilder {
 
 //------------ RoaIpAddressesBuilder -----------------------------------------
 
+/// A builder for the addresses of one address family of a ROA.
+///
+/// The builder keeps its addresses in canonical form: they are ordered by
+/// address, prefix length, and maximum length, duplicates are dropped,
+/// and a maximum length equal to the prefix length is left out. Thus, the
+/// same set of addresses always results in the same encoding.
 #[derive(Clone, Debug)]
 pub struct RoaIpAddressesBuilder {
     addrs: Vec<RoaIpAddress>,
@@ -567,8 +889,16 @@ impl RoaIpAddressesBuilder {
         }
     }
 
-    pub fn push(&mut self, addr: RoaIpAddress) {
-        self.addrs.push(addr)
+    pub fn push(&mut self, mut addr: RoaIpAddress) {
+        if addr.max_length == Some(addr.prefix.addr_len()) {
+            addr.max_length = None
+        }
+        let key = Self::sort_key(&addr);
+        if let Err(idx) = self.addrs.binary_search_by_key(
+            &key, Self::sort_key
+        ) {
+            self.addrs.insert(idx, addr)
+        }
     }
 
     pub fn push_addr(&mut self, addr: IpAddr, len: u8, max_len: Option<u8>) {
@@ -576,7 +906,15 @@ impl RoaIpAddressesBuilder {
     }
 
     pub fn extend_from_slice(&mut self, addrs: &[RoaIpAddress]) {
-        self.addrs.extend_from_slice(addrs)
+        self.extend(addrs.iter().cloned())
+    }
+
+    /// Returns the key for ordering the addresses.
+    fn sort_key(addr: &RoaIpAddress) -> (Addr, u8, u8) {
+        (
+            addr.prefix.addr(), addr.prefix.addr_len(),
+            addr.max_length.unwrap_or_else(|| addr.prefix.addr_len())
+        )
     }
 
     pub fn to_addresses(&self) -> RoaIpAddresses {
@@ -618,7 +956,7 @@ impl Default for RoaIpAddressesBuilder {
 impl Extend<RoaIpAddress> for RoaIpAddressesBuilder {
     fn extend<T>(&mut self, iter: T)
     where T: IntoIterator<Item=RoaIpAddress> {
-        self.addrs.extend(iter)
+        iter.into_iter().for_each(|addr| self.push(addr))
     }
 }
 
@@ -638,6 +976,128 @@ mod test {
             ).is_ok()
         )
     }
+
+    #[test]
+    fn check_lengths() {
+        let addr = |len, max| {
+            RoaIpAddress::new_addr(
+                Ipv4Addr::new(192, 0, 2, 0).into(), len, max
+            )
+        };
+        assert!(addr(24, None).check_lengths(32));
+        assert!(addr(24, Some(24)).check_lengths(32));
+        assert!(addr(24, Some(32)).check_lengths(32));
+        assert!(!addr(24, Some(23)).check_lengths(32));
+        assert!(!addr(24, Some(33)).check_lengths(32));
+        assert!(!addr(33, None).check_lengths(32));
+        assert!(addr(24, Some(48)).check_lengths(128));
+    }
+
+    #[test]
+    fn canonical() {
+        let mut left = RoaBuilder::new(64496.into());
+        left.push_v4_addr(Ipv4Addr::new(192, 0, 2, 0), 24, Some(24));
+        left.push_v4_addr(Ipv4Addr::new(10, 0, 0, 0), 8, Some(24));
+        left.push_v6_addr(
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32, None
+        );
+        let mut right = RoaBuilder::new(64496.into());
+        right.push_v6_addr(
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32, None
+        );
+        right.push_v4_addr(Ipv4Addr::new(10, 0, 0, 0), 8, Some(24));
+        right.push_v4_addr(Ipv4Addr::new(192, 0, 2, 0), 24, None);
+        right.push_v4_addr(Ipv4Addr::new(10, 0, 0, 0), 8, Some(24));
+        let left = left.to_attestation().to_content_bytes();
+        assert_eq!(left, right.to_attestation().to_content_bytes());
+
+        let decoded = Mode::Der.decode(
+            left.clone(), RouteOriginAttestation::take_from
+        ).unwrap();
+        assert_eq!(decoded.canonical(), left);
+        let addrs: Vec<_> = decoded.v4_addrs().iter().collect();
+        assert_eq!(
+            addrs,
+            [
+                RoaIpAddress::new_addr(
+                    Ipv4Addr::new(10, 0, 0, 0).into(), 8, Some(24)
+                ),
+                RoaIpAddress::new_addr(
+                    Ipv4Addr::new(192, 0, 2, 0).into(), 24, None
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn max_length_issues() {
+        let addr = |len, max, v4| {
+            FriendlyRoaIpAddress::new(
+                RoaIpAddress::new_addr(
+                    Ipv4Addr::new(10, 0, 0, 0).into(), len, max
+                ),
+                v4
+            )
+        };
+        assert_eq!(addr(24, None, true).vrp_count(), 1);
+        assert_eq!(addr(24, Some(26), true).vrp_count(), 7);
+        assert_eq!(addr(8, Some(24), true).vrp_count(), 131_071);
+        assert_eq!(addr(0, Some(128), false).vrp_count(), u128::MAX);
+        assert_eq!(addr(24, Some(23), true).vrp_count(), 0);
+
+        assert_eq!(addr(24, Some(32), true).max_length_issue(1000), None);
+        assert_eq!(
+            addr(24, Some(33), true).max_length_issue(1000),
+            Some(MaxLengthIssue::ExceedsFamily)
+        );
+        assert_eq!(addr(24, Some(33), false).max_length_issue(1023), None);
+        assert_eq!(
+            addr(24, Some(23), true).max_length_issue(1000),
+            Some(MaxLengthIssue::BelowPrefixLength)
+        );
+        assert_eq!(
+            addr(8, Some(24), true).max_length_issue(1000),
+            Some(MaxLengthIssue::ExcessiveExpansion(131_071))
+        );
+    }
+
+    #[test]
+    fn iter_vrps() {
+        let mut roa = RoaBuilder::new(64496.into());
+        roa.push_v4_addr(Ipv4Addr::new(192, 0, 2, 0), 24, Some(25));
+        roa.push_v4_addr(Ipv4Addr::new(10, 0, 0, 0), 8, Some(24));
+        roa.push_v4_addr(Ipv4Addr::new(0, 0, 0, 0), 0, None);
+        let roa = roa.to_attestation().encode_ref().to_captured(Mode::Der);
+        let roa = Mode::Der.decode(
+            roa.as_slice(), RouteOriginAttestation::take_from
+        ).unwrap();
+        let vrps: Vec<_> = roa.iter_vrps(10).map(|item| {
+            (item.0.address(), item.0.address_length(), item.1, item.2)
+        }).collect();
+        let vrp = |a, b, c, len| {
+            (
+                IpAddr::from(Ipv4Addr::new(a, b, c, 0)), len, len,
+                AsId::from(64496)
+            )
+        };
+        assert_eq!(
+            vrps,
+            [
+                vrp(0, 0, 0, 0),
+                vrp(192, 0, 2, 24), vrp(192, 0, 2, 25),
+                (
+                    IpAddr::from(Ipv4Addr::new(192, 0, 2, 128)), 25, 25,
+                    AsId::from(64496)
+                ),
+            ]
+        );
+        let issues: Vec<_> = roa.max_length_issues(10).map(|item| {
+            (item.0.address_length(), item.1)
+        }).collect();
+        assert_eq!(
+            issues, [(8, MaxLengthIssue::ExcessiveExpansion(131_071))]
+        );
+    }
 }
 
 #[cfg(all(test, feature="softkeys"))]
@@ -673,6 +1133,9 @@ mod signer_test {
 
         let mut roa = RoaBuilder::new(64496.into());
         roa.push_v4_addr(Ipv4Addr::new(192, 0, 2, 0), 24, None);
+        roa.push_v6_addr(
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0), 32, Some(48)
+        );
 
         let roa = roa.finalize(
             SignedObjectBuilder::new(
@@ -696,6 +1159,28 @@ mod signer_test {
     fn encode_roa() {
         make_roa();
     }
+
+    #[test]
+    fn iter_origins() {
+        let roa = make_roa();
+        let origins: Vec<_> = roa.content.iter_origins().map(|item| {
+            (item.0.address(), item.0.address_length(), item.1, item.2)
+        }).collect();
+        assert_eq!(
+            origins,
+            [
+                (
+                    IpAddr::from(Ipv4Addr::new(192, 0, 2, 0)), 24, 24,
+                    AsId::from(64496)
+                ),
+                (
+                    IpAddr::from(
+                        Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 0)
+                    ), 32, 48, AsId::from(64496)
+                ),
+            ]
+        );
+    }
         
 
     #[test]
@@ -0,0 +1,342 @@
+//! Fetching RRDP files over HTTPS.
+//!
+//! This module is only available if the `rrdp-fetch` feature is enabled.
+
+use std::time::Duration;
+use bytes::Bytes;
+use reqwest::{header, Client, RequestBuilder, Response, StatusCode};
+use crate::fetch::FetchError;
+use crate::util::http::{check_status, convert_error, read_body};
+use crate::uri;
+use super::{NotificationFile, UriAndHash};
+
+
+//------------ Fetcher -------------------------------------------------------
+
+/// An asynchronous fetcher for RRDP files.
+///
+/// The fetcher retrieves the notification, snapshot, and delta files of an
+/// RRDP repository. It enforces a limit on the size of each file and
+/// verifies the hashes of snapshot and delta files against those published
+/// in the notification file.
+///
+/// All failures are reported as [`FetchError`]s. Their
+/// [`should_fall_back`] method tells whether the repository should be
+/// fetched via rsync instead.
+///
+/// [`FetchError`]: ../fetch/enum.FetchError.html
+/// [`should_fall_back`]: ../fetch/enum.FetchError.html#method.should_fall_back
+#[derive(Clone, Debug)]
+pub struct Fetcher {
+    /// The HTTP client to use.
+    client: Client,
+
+    /// The maximum size of a file in octets.
+    max_size: u64,
+}
+
+impl Fetcher {
+    /// The default limit for the size of a file.
+    pub const DEFAULT_MAX_SIZE: u64 = 1 << 30;
+
+    /// The default timeout for a request.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Creates a new fetcher with the default settings.
+    pub fn new() -> Result<Self, FetchError> {
+        Client::builder()
+            .timeout(Self::DEFAULT_TIMEOUT)
+            .build()
+            .map(Self::with_client)
+            .map_err(convert_error)
+    }
+
+    /// Creates a new fetcher using the given HTTP client.
+    pub fn with_client(client: Client) -> Self {
+        Fetcher {
+            client,
+            max_size: Self::DEFAULT_MAX_SIZE,
+        }
+    }
+
+    /// Returns the maximum size of a file in octets.
+    pub fn max_size(&self) -> u64 {
+        self.max_size
+    }
+
+    /// Sets the maximum size of a file in octets.
+    pub fn set_max_size(&mut self, max_size: u64) {
+        self.max_size = max_size
+    }
+
+    /// Fetches and parses a notification file.
+    ///
+    /// If `cache` contains information from a previous fetch, a conditional
+    /// request is made. If the server reports that the file has not been
+    /// modified, returns `Ok(None)`. Otherwise returns the parsed file and
+    /// the cache information to use next time.
+    pub async fn notification(
+        &self,
+        uri: &uri::Https,
+        cache: Option<&CacheInfo>,
+    ) -> Result<Option<(NotificationFile, CacheInfo)>, FetchError> {
+        let response = self.notification_request(uri, cache)
+            .send().await.map_err(convert_error)?;
+        self.notification_response(response).await
+    }
+
+    /// Creates the request for a notification file.
+    fn notification_request(
+        &self, uri: &uri::Https, cache: Option<&CacheInfo>,
+    ) -> RequestBuilder {
+        let mut request = self.client.get(uri.as_str());
+        if let Some(cache) = cache {
+            if let Some(ref etag) = cache.etag {
+                request = request.header(
+                    header::IF_NONE_MATCH, etag.as_str()
+                );
+            }
+            if let Some(ref modified) = cache.last_modified {
+                request = request.header(
+                    header::IF_MODIFIED_SINCE, modified.as_str()
+                );
+            }
+        }
+        request
+    }
+
+    /// Processes the response for a notification file.
+    async fn notification_response(
+        &self, mut response: Response,
+    ) -> Result<Option<(NotificationFile, CacheInfo)>, FetchError> {
+        if response.status() == StatusCode::NOT_MODIFIED {
+            return Ok(None)
+        }
+        check_status(&response)?;
+        let cache = CacheInfo::from_headers(response.headers());
+        let data = read_body(&mut response, self.max_size).await?;
+        let notify = NotificationFile::parse(data.as_ref()).map_err(|_| {
+            FetchError::Malformed
+        })?;
+        Ok(Some((notify, cache)))
+    }
+
+    /// Fetches a snapshot or delta file and verifies its hash.
+    ///
+    /// Returns the raw content of the file which can then be processed via
+    /// the [`ProcessSnapshot`] or [`ProcessDelta`] traits.
+    ///
+    /// [`ProcessSnapshot`]: trait.ProcessSnapshot.html
+    /// [`ProcessDelta`]: trait.ProcessDelta.html
+    pub async fn file(&self, info: &UriAndHash) -> Result<Bytes, FetchError> {
+        let response = self.client.get(info.uri().as_str())
+            .send().await.map_err(convert_error)?;
+        self.file_response(info, response).await
+    }
+
+    /// Processes the response for a snapshot or delta file.
+    async fn file_response(
+        &self, info: &UriAndHash, mut response: Response,
+    ) -> Result<Bytes, FetchError> {
+        check_status(&response)?;
+        let data = read_body(&mut response, self.max_size).await?;
+        if !info.verify_hash(&data) {
+            return Err(FetchError::HashMismatch)
+        }
+        Ok(data)
+    }
+}
+
+
+//------------ CacheInfo -----------------------------------------------------
+
+/// Information for making conditional requests for a notification file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CacheInfo {
+    /// The value of the ETag header of the last response.
+    etag: Option<String>,
+
+    /// The value of the Last-Modified header of the last response.
+    last_modified: Option<String>,
+}
+
+impl CacheInfo {
+    /// Creates a new value from the given header values.
+    pub fn new(etag: Option<String>, last_modified: Option<String>) -> Self {
+        CacheInfo { etag, last_modified }
+    }
+
+    /// Returns the ETag of the last response if present.
+    pub fn etag(&self) -> Option<&str> {
+        self.etag.as_deref()
+    }
+
+    /// Returns the Last-Modified date of the last response if present.
+    pub fn last_modified(&self) -> Option<&str> {
+        self.last_modified.as_deref()
+    }
+
+    fn from_headers(headers: &header::HeaderMap) -> Self {
+        let get = |name| {
+            headers.get(name).and_then(|value: &header::HeaderValue| {
+                value.to_str().ok()
+            }).map(String::from)
+        };
+        CacheInfo {
+            etag: get(header::ETAG),
+            last_modified: get(header::LAST_MODIFIED),
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::future::Future;
+    use std::str::FromStr;
+    use super::*;
+    use super::super::Hash;
+
+    const NOTIFICATION: &[u8] = b"\
+        <notification xmlns=\"http://www.ripe.net/rpki/rrdp\" version=\"1\" \
+          session_id=\"9df4b597-af9e-4dca-bdda-719cce2c4e28\" serial=\"1\">\
+          <snapshot uri=\"https://host/snapshot.xml\" \
+            hash=\"\
+              5d7ba8bf26bad0e5ebcec57d3e50c8ba5c8d5a4c4be6c6c0d7d8c0d8f3ea2f4c\
+            \"/>\
+        </notification>";
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn response(
+        status: u16, headers: &[(&str, &str)], body: &'static [u8]
+    ) -> Response {
+        let mut res = http::Response::builder().status(status);
+        for &(name, value) in headers {
+            res = res.header(name, value);
+        }
+        res.body(body).unwrap().into()
+    }
+
+    fn https(s: &str) -> uri::Https {
+        uri::Https::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn conditional_request() {
+        let fetcher = Fetcher::new().unwrap();
+        let uri = https("https://host/notification.xml");
+
+        let request = fetcher.notification_request(
+            &uri, None
+        ).build().unwrap();
+        assert!(request.headers().get(header::IF_NONE_MATCH).is_none());
+        assert!(
+            request.headers().get(header::IF_MODIFIED_SINCE).is_none()
+        );
+
+        let cache = CacheInfo::new(
+            Some("\"abc\"".into()),
+            Some("Wed, 21 Oct 2015 07:28:00 GMT".into())
+        );
+        let request = fetcher.notification_request(
+            &uri, Some(&cache)
+        ).build().unwrap();
+        assert_eq!(
+            request.headers().get(header::IF_NONE_MATCH).unwrap(),
+            "\"abc\""
+        );
+        assert_eq!(
+            request.headers().get(header::IF_MODIFIED_SINCE).unwrap(),
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+    }
+
+    #[test]
+    fn notification_response() {
+        let fetcher = Fetcher::new().unwrap();
+
+        let (notify, cache) = block_on(fetcher.notification_response(
+            response(
+                200,
+                &[
+                    ("ETag", "\"abc\""),
+                    ("Last-Modified", "Wed, 21 Oct 2015 07:28:00 GMT"),
+                ],
+                NOTIFICATION
+            )
+        )).unwrap().unwrap();
+        assert_eq!(notify.serial, 1);
+        assert_eq!(cache.etag(), Some("\"abc\""));
+        assert_eq!(
+            cache.last_modified(), Some("Wed, 21 Oct 2015 07:28:00 GMT")
+        );
+
+        assert!(block_on(fetcher.notification_response(
+            response(304, &[], b"")
+        )).unwrap().is_none());
+        assert_eq!(
+            block_on(fetcher.notification_response(
+                response(404, &[], b"")
+            )).err(),
+            Some(FetchError::HttpStatus { status: 404, retry_after: None })
+        );
+        assert_eq!(
+            block_on(fetcher.notification_response(
+                response(200, &[], b"<notification/>")
+            )).err(),
+            Some(FetchError::Malformed)
+        );
+
+        let mut fetcher = fetcher;
+        fetcher.set_max_size(16);
+        assert_eq!(
+            block_on(fetcher.notification_response(
+                response(200, &[], NOTIFICATION)
+            )).err(),
+            Some(FetchError::SizeExceeded { limit: 16 })
+        );
+    }
+
+    #[test]
+    fn file_response() {
+        let mut fetcher = Fetcher::new().unwrap();
+        let uri = https("https://host/snapshot.xml");
+        let info = UriAndHash::new(uri, Hash::from_data(b"content"));
+
+        assert_eq!(
+            block_on(fetcher.file_response(
+                &info, response(200, &[], b"content")
+            )),
+            Ok(Bytes::from_static(b"content"))
+        );
+        assert_eq!(
+            block_on(fetcher.file_response(
+                &info, response(200, &[], b"other content")
+            )),
+            Err(FetchError::HashMismatch)
+        );
+        assert_eq!(
+            block_on(fetcher.file_response(
+                &info, response(500, &[], b"content")
+            )),
+            Err(FetchError::HttpStatus { status: 500, retry_after: None })
+        );
+
+        fetcher.set_max_size(4);
+        assert_eq!(
+            block_on(fetcher.file_response(
+                &info, response(200, &[], b"content")
+            )),
+            Err(FetchError::SizeExceeded { limit: 4 })
+        );
+    }
+}
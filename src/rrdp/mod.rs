@@ -1,12 +1,25 @@
 //! Parsing the XML representations.
 
-use std::{fmt, io, ops, str};
+use std::{error, fmt, io, str};
+use std::convert::TryFrom;
 use log::info;
-use ring::digest;
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
+use crate::crypto::{Digest, DigestAlgorithm};
 use crate::uri;
 use crate::xml::decode::{Reader, Name, Error};
 
+#[cfg(feature = "rrdp-fetch")]
+pub use self::fetch::{CacheInfo, Fetcher};
+pub use self::retry::{RetryPolicy, RetryState};
+pub use self::state::SessionState;
+
+#[cfg(feature = "rrdp-fetch")]
+mod fetch;
+mod retry;
+mod state;
+
 
 //------------ NotificationFile ----------------------------------------------
 
@@ -49,64 +62,67 @@ impl NotificationFile {
 
         let mut snapshot = None;
         let mut deltas = Vec::new();
-        while let Some(mut content) = outer.take_opt_element(&mut reader,
-                                                             |element| {
-            match element.name() {
-                SNAPSHOT => {
-                    if snapshot.is_some() {
-                        return Err(Error::Malformed)
-                    }
-                    let mut uri = None;
-                    let mut hash = None;
-                    element.attributes(|name, value| match name {
-                        b"uri" => {
-                            uri = Some(value.ascii_into()?);
-                            Ok(())
-                        }
-                        b"hash" => {
-                            hash = Some(value.ascii_into()?);
-                            Ok(())
+        while let Some(mut content) = outer.take_opt_element_in(
+            &mut reader, NS, |element| {
+                match element.name() {
+                    SNAPSHOT => {
+                        if snapshot.is_some() {
+                            return Err(Error::Malformed)
                         }
-                        _ => Err(Error::Malformed)
-                    })?;
-                    match (uri, hash) {
-                        (Some(uri), Some(hash)) => {
-                            snapshot = Some(UriAndHash::new(uri, hash));
-                            Ok(())
+                        let mut uri = None;
+                        let mut hash = None;
+                        element.attributes(|name, value| match name {
+                            b"uri" => {
+                                uri = Some(value.ascii_into()?);
+                                Ok(())
+                            }
+                            b"hash" => {
+                                hash = Some(value.ascii_into()?);
+                                Ok(())
+                            }
+                            _ => Err(Error::Malformed)
+                        })?;
+                        match (uri, hash) {
+                            (Some(uri), Some(hash)) => {
+                                snapshot = Some(UriAndHash::new(uri, hash));
+                                Ok(())
+                            }
+                            _ => Err(Error::Malformed)
                         }
-                        _ => Err(Error::Malformed)
                     }
-                }
-                DELTA => {
-                    let mut serial = None;
-                    let mut uri = None;
-                    let mut hash = None;
-                    element.attributes(|name, value| match name {
-                        b"serial" => {
-                            serial = Some(value.ascii_into()?);
-                            Ok(())
-                        }
-                        b"uri" => {
-                            uri = Some(value.ascii_into()?);
-                            Ok(())
-                        }
-                        b"hash" => {
-                            hash = Some(value.ascii_into()?);
-                            Ok(())
-                        }
-                        _ => Err(Error::Malformed)
-                    })?;
-                    match (serial, uri, hash) {
-                        (Some(serial), Some(uri), Some(hash)) => {
-                            deltas.push((serial, UriAndHash::new(uri, hash)));
-                            Ok(())
+                    DELTA => {
+                        let mut serial = None;
+                        let mut uri = None;
+                        let mut hash = None;
+                        element.attributes(|name, value| match name {
+                            b"serial" => {
+                                serial = Some(value.ascii_into()?);
+                                Ok(())
+                            }
+                            b"uri" => {
+                                uri = Some(value.ascii_into()?);
+                                Ok(())
+                            }
+                            b"hash" => {
+                                hash = Some(value.ascii_into()?);
+                                Ok(())
+                            }
+                            _ => Err(Error::Malformed)
+                        })?;
+                        match (serial, uri, hash) {
+                            (Some(serial), Some(uri), Some(hash)) => {
+                                deltas.push(
+                                    (serial, UriAndHash::new(uri, hash))
+                                );
+                                Ok(())
+                            }
+                            _ => Err(Error::Malformed)
                         }
-                        _ => Err(Error::Malformed)
                     }
+                    _ => Err(Error::Malformed)
                 }
-                _ => Err(Error::Malformed)
             }
-        })? {
+        )? {
             content.take_end(&mut reader)?;
         }
 
@@ -188,22 +204,24 @@ pub trait ProcessSnapshot {
 
         loop {
             let mut uri = None;
-            let inner = outer.take_opt_element(&mut reader, |element| {
-                if element.name() != PUBLISH {
-                info!("Bad inner: not publish");
-                    return Err(Error::Malformed)
-                }
-                element.attributes(|name, value| match name {
-                    b"uri" => {
-                        uri = Some(value.ascii_into()?);
-                        Ok(())
-                    }
-                    _ => {
-                        info!("Bad attribute on publish.");
-                        Err(Error::Malformed)
+            let inner = outer.take_opt_element_in(
+                &mut reader, NS, |element| {
+                    if element.name() != PUBLISH {
+                    info!("Bad inner: not publish");
+                        return Err(Error::Malformed)
                     }
-                })
-            })?;
+                    element.attributes(|name, value| match name {
+                        b"uri" => {
+                            uri = Some(value.ascii_into()?);
+                            Ok(())
+                        }
+                        _ => {
+                            info!("Bad attribute on publish.");
+                            Err(Error::Malformed)
+                        }
+                    })
+                }
+            )?;
             let mut inner = match inner {
                 Some(inner) => inner,
                 None => break
@@ -212,16 +230,8 @@ pub trait ProcessSnapshot {
                 Some(uri) => uri,
                 None => return Err(Error::Malformed.into())
             };
-            let data = inner.take_text(&mut reader, |text| {
-                let text: Vec<_> = text.to_ascii()?.as_bytes()
-                .iter().filter_map(|b| {
-                    if b.is_ascii_whitespace() { None }
-                    else { Some(*b) }
-                }).collect();
-                base64::decode(&text).map_err(|_| {
-                    Error::Malformed
-                })
-            })?;
+            let mut data = Vec::new();
+            inner.take_base64_into(&mut reader, &mut data)?;
             self.publish(uri, data)?;
             inner.take_end(&mut reader)?;
         }
@@ -247,14 +257,14 @@ pub trait ProcessDelta {
     fn publish(
         &mut self,
         uri: uri::Rsync,
-        hash: Option<DigestHex>,
+        hash: Option<Hash>,
         data: Vec<u8>,
     ) -> Result<(), Self::Err>;
 
     fn withdraw(
         &mut self,
         uri: uri::Rsync,
-        hash: DigestHex,
+        hash: Hash,
     ) -> Result<(), Self::Err>;
 
 
@@ -300,24 +310,26 @@ pub trait ProcessDelta {
             let mut action = None;
             let mut uri = None;
             let mut hash = None;
-            let inner = outer.take_opt_element(&mut reader, |element| {
-                match element.name() {
-                    PUBLISH => action = Some(Action::Publish),
-                    WITHDRAW => action = Some(Action::Withdraw),
-                    _ => return Err(Error::Malformed),
-                };
-                element.attributes(|name, value| match name {
-                    b"uri" => {
-                        uri = Some(value.ascii_into()?);
-                        Ok(())
-                    }
-                    b"hash" => {
-                        hash = Some(value.ascii_into()?);
-                        Ok(())
-                    }
-                    _ => Err(Error::Malformed)
-                })
-            })?;
+            let inner = outer.take_opt_element_in(
+                &mut reader, NS, |element| {
+                    match element.name() {
+                        PUBLISH => action = Some(Action::Publish),
+                        WITHDRAW => action = Some(Action::Withdraw),
+                        _ => return Err(Error::Malformed),
+                    };
+                    element.attributes(|name, value| match name {
+                        b"uri" => {
+                            uri = Some(value.ascii_into()?);
+                            Ok(())
+                        }
+                        b"hash" => {
+                            hash = Some(value.ascii_into()?);
+                            Ok(())
+                        }
+                        _ => Err(Error::Malformed)
+                    })
+                }
+            )?;
             let mut inner = match inner {
                 Some(inner) => inner,
                 None => break
@@ -328,15 +340,8 @@ pub trait ProcessDelta {
             };
             match action.unwrap() { // Or we'd have exited already.
                 Action::Publish => {
-                    let data = inner.take_text(&mut reader, |text| {
-                        let text: Vec<_> = text.to_ascii()?.as_bytes()
-                        .iter().filter_map(|b| {
-                            if b.is_ascii_whitespace() { None }
-                            else { Some(*b) }
-                        }).collect();
-                        base64::decode(&text)
-                            .map_err(|_| Error::Malformed)
-                    })?;
+                    let mut data = Vec::new();
+                    inner.take_base64_into(&mut reader, &mut data)?;
                     self.publish(uri, hash, data)?;
                 }
                 Action::Withdraw => {
@@ -362,11 +367,11 @@ pub trait ProcessDelta {
 #[derive(Clone, Debug)]
 pub struct UriAndHash {
     uri: uri::Https,
-    hash: DigestHex,
+    hash: Hash,
 }
 
 impl UriAndHash {
-    pub fn new(uri: uri::Https, hash: DigestHex) -> Self {
+    pub fn new(uri: uri::Https, hash: Hash) -> Self {
         UriAndHash { uri, hash }
     }
 
@@ -374,70 +379,169 @@ impl UriAndHash {
         &self.uri
     }
 
-    pub fn hash(&self) -> &DigestHex {
+    pub fn hash(&self) -> &Hash {
         &self.hash
     }
+
+    /// Returns whether `data` matches the hash.
+    pub fn verify_hash(&self, data: &[u8]) -> bool {
+        self.hash.matches(data)
+    }
 }
 
 
-//------------ DigestHex -----------------------------------------------------
+//------------ Hash ----------------------------------------------------------
+
+/// The SHA-256 hash of a file or object as used by RRDP.
+///
+/// RRDP uses SHA-256 for all hashes and represents them as a sequence of
+/// 64 hex digits. Parsing accepts both upper and lower case digits, while
+/// the value is always displayed and serialized using lower case digits.
+/// Since values are compared by their octets, the case of the original
+/// representation doesn’t matter.
+#[derive(Clone, Copy, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Hash([u8; 32]);
+
+impl Hash {
+    /// Calculates the hash of the given data.
+    pub fn from_data(data: &[u8]) -> Self {
+        let mut res = [0u8; 32];
+        res.copy_from_slice(DigestAlgorithm::Sha256.digest(data).as_ref());
+        Hash(res)
+    }
 
-/// A helper type to encode a digest as a sequence of hex-digits.
-#[derive(Clone, Debug)]
-pub struct DigestHex(Vec<u8>);
+    /// Returns the octets of the hash.
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_ref()
+    }
 
-impl From<Vec<u8>> for DigestHex {
-    fn from(value: Vec<u8>) -> DigestHex {
-        DigestHex(value)
+    /// Returns whether `data` matches the hash.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        ring::constant_time::verify_slices_are_equal(
+            self.0.as_ref(),
+            DigestAlgorithm::Sha256.digest(data).as_ref()
+        ).is_ok()
     }
 }
 
-impl From<digest::Digest> for DigestHex {
-    fn from(value: digest::Digest) -> DigestHex {
-        DigestHex(Vec::from(value.as_ref()))
+
+//--- From and TryFrom
+
+impl From<[u8; 32]> for Hash {
+    fn from(value: [u8; 32]) -> Self {
+        Hash(value)
     }
 }
 
-impl str::FromStr for DigestHex {
-    type Err = &'static str;
+impl From<Hash> for [u8; 32] {
+    fn from(value: Hash) -> Self {
+        value.0
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut res = Vec::new();
-        let mut s = s.chars();
-        while let Some(first) = s.next() {
-            let first = first.to_digit(16).ok_or("invalid digest")?;
-            let second = s.next().ok_or("invalid digest")?
-                .to_digit(16).ok_or("invalid digest")?;
+impl<'a> TryFrom<&'a [u8]> for Hash {
+    type Error = ParseHashError;
 
-            res.push((first << 4 | second) as u8);
-        }
-        Ok(DigestHex(res))
+    fn try_from(value: &'a [u8]) -> Result<Self, Self::Error> {
+        <[u8; 32]>::try_from(value).map(Hash).map_err(|_| ParseHashError)
     }
 }
 
-impl ops::Deref for DigestHex {
-    type Target = [u8];
+impl TryFrom<Digest> for Hash {
+    type Error = ParseHashError;
 
-    fn deref(&self) -> &[u8] {
-        self.0.as_ref()
+    fn try_from(value: Digest) -> Result<Self, Self::Error> {
+        Self::try_from(value.as_ref())
     }
 }
 
-impl AsRef<[u8]> for DigestHex {
+
+//--- FromStr
+
+impl str::FromStr for Hash {
+    type Err = ParseHashError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 {
+            return Err(ParseHashError)
+        }
+        let mut res = [0u8; 32];
+        let mut s = s.chars();
+        for item in res.iter_mut() {
+            // We checked the length, so there are enough characters.
+            let first = s.next().and_then(|ch| ch.to_digit(16));
+            let second = s.next().and_then(|ch| ch.to_digit(16));
+            match (first, second) {
+                (Some(first), Some(second)) => {
+                    *item = (first << 4 | second) as u8
+                }
+                _ => return Err(ParseHashError)
+            }
+        }
+        Ok(Hash(res))
+    }
+}
+
+
+//--- AsRef
+
+impl AsRef<[u8]> for Hash {
     fn as_ref(&self) -> &[u8] {
-        self.0.as_ref()
+        self.as_slice()
     }
 }
 
-impl fmt::Display for DigestHex {
+
+//--- Display and Debug
+
+impl fmt::Display for Hash {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        for &ch in self.0.as_slice() {
+        for &ch in self.0.iter() {
             write!(f, "{:02x}", ch)?;
         }
         Ok(())
     }
 }
 
+impl fmt::Debug for Hash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Hash({})", self)
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for Hash {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        self.to_string().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Hash {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+
+//------------ ParseHashError ------------------------------------------------
+
+/// A value was not a valid SHA-256 hash.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ParseHashError;
+
+impl fmt::Display for ParseHashError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("invalid SHA-256 hash")
+    }
+}
+
+impl error::Error for ParseHashError { }
+
 
 //------------ Action --------------------------------------------------------
 
@@ -461,6 +565,7 @@ const WITHDRAW: Name = Name::qualified(NS, b"withdraw");
 
 #[cfg(test)]
 mod test {
+    use std::str::FromStr;
     use super::*;
 
     pub struct Test;
@@ -499,7 +604,7 @@ mod test {
         fn publish(
             &mut self,
             _uri: uri::Rsync,
-            _hash: Option<DigestHex>,
+            _hash: Option<Hash>,
             _data: Vec<u8>,
         ) -> Result<(), Self::Err> {
             Ok(())
@@ -508,16 +613,27 @@ mod test {
         fn withdraw(
             &mut self,
             _uri: uri::Rsync,
-            _hash: DigestHex,
+            _hash: Hash,
         ) -> Result<(), Self::Err> {
             Ok(())
         }
     }
 
+    #[test]
+    fn verify_hash() {
+        let info = UriAndHash::new(
+            "https://rrdp.example.net/snapshot.xml".parse().unwrap(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e\
+             1b161e5c1fa7425e73043362938b9824".parse().unwrap()
+        );
+        assert!(info.verify_hash(b"hello"));
+        assert!(!info.verify_hash(b"hullo"));
+    }
+
     #[test]
     fn ripe_notification() {
         NotificationFile::parse(
-            include_bytes!("../test-data/ripe-notification.xml").as_ref()
+            include_bytes!("../../test-data/ripe-notification.xml").as_ref()
         ).unwrap();
     }
 
@@ -525,7 +641,7 @@ mod test {
     fn ripe_snapshot() {
         <Test as ProcessSnapshot>::process(
             &mut Test,
-            include_bytes!("../test-data/ripe-snapshot.xml").as_ref()
+            include_bytes!("../../test-data/ripe-snapshot.xml").as_ref()
         ).unwrap();
     }
 
@@ -533,7 +649,39 @@ mod test {
     fn ripe_delta() {
         <Test as ProcessDelta>::process(
             &mut Test,
-            include_bytes!("../test-data/ripe-delta.xml").as_ref()
+            include_bytes!("../../test-data/ripe-delta.xml").as_ref()
         ).unwrap();
     }
+
+    #[test]
+    fn hash() {
+        let lower: Hash = "2cf24dba5fb0a30e26e83b2ac5b9e29e\
+                           1b161e5c1fa7425e73043362938b9824".parse().unwrap();
+        let upper: Hash = "2CF24DBA5FB0A30E26E83B2AC5B9E29E\
+                           1B161E5C1FA7425E73043362938B9824".parse().unwrap();
+        assert_eq!(lower, upper);
+        assert_eq!(lower, Hash::from_data(b"hello"));
+        assert_eq!(
+            upper.to_string(),
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert_eq!(
+            serde_json::from_str::<Hash>(
+                &serde_json::to_string(&upper).unwrap()
+            ).unwrap(),
+            lower
+        );
+        assert_eq!(Hash::from_str("2cf24dba"), Err(ParseHashError));
+        assert_eq!(
+            Hash::from_str(
+                "2cf24dba5fb0a30e26e83b2ac5b9e29e\
+                 1b161e5c1fa7425e73043362938b98g4"
+            ),
+            Err(ParseHashError)
+        );
+        assert_eq!(
+            Hash::try_from(DigestAlgorithm::Sha1.digest(b"hello")),
+            Err(ParseHashError)
+        );
+    }
 }
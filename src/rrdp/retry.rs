@@ -0,0 +1,280 @@
+//! Scheduling the fetching of notification files.
+
+use std::cmp::min;
+use std::time::Duration;
+use ring::rand::{SecureRandom, SystemRandom};
+use crate::x509::Time;
+
+
+//------------ RetryPolicy ---------------------------------------------------
+
+/// A policy for when to fetch the notification file of a repository next.
+///
+/// Normally, the notification file is fetched every `interval`. If
+/// fetching fails, the delay is doubled with every consecutive failure up
+/// to `max_backoff`. Similarly, if the repository has not published a new
+/// serial number, the delay is doubled up to `max_idle` which by default
+/// equals the interval, i.e., there is no backoff for idle repositories.
+/// As soon as a new serial number is published, the delay returns to the
+/// interval.
+///
+/// A random jitter of up to `jitter` is added to every delay so that
+/// relying parties started at the same time don’t all hit a repository at
+/// once. Delays are never longer than a year.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// The regular interval between fetches.
+    interval: Duration,
+
+    /// The maximum delay after failures.
+    max_backoff: Duration,
+
+    /// The maximum delay while no new serial number is published.
+    max_idle: Duration,
+
+    /// The maximum jitter added to the delay.
+    jitter: Duration,
+}
+
+impl RetryPolicy {
+    /// The default interval between fetches.
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(600);
+
+    /// The default maximum delay after failures.
+    pub const DEFAULT_MAX_BACKOFF: Duration = Duration::from_secs(14_400);
+
+    /// Creates a new policy with the given interval.
+    ///
+    /// The maximum delay after failures is the larger of the interval and
+    /// [`DEFAULT_MAX_BACKOFF`]. There is no backoff for idle repositories.
+    /// The jitter is a tenth of the interval.
+    ///
+    /// [`DEFAULT_MAX_BACKOFF`]: #associatedconstant.DEFAULT_MAX_BACKOFF
+    pub fn new(interval: Duration) -> Self {
+        RetryPolicy {
+            interval,
+            max_backoff: Self::DEFAULT_MAX_BACKOFF.max(interval),
+            max_idle: interval,
+            jitter: interval / 10,
+        }
+    }
+
+    /// Returns the regular interval between fetches.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Returns the maximum delay after failures.
+    pub fn max_backoff(&self) -> Duration {
+        self.max_backoff
+    }
+
+    /// Sets the maximum delay after failures.
+    pub fn set_max_backoff(&mut self, max_backoff: Duration) {
+        self.max_backoff = max_backoff
+    }
+
+    /// Returns the maximum delay while no new serial is published.
+    pub fn max_idle(&self) -> Duration {
+        self.max_idle
+    }
+
+    /// Sets the maximum delay while no new serial is published.
+    pub fn set_max_idle(&mut self, max_idle: Duration) {
+        self.max_idle = max_idle
+    }
+
+    /// Returns the maximum jitter.
+    pub fn jitter(&self) -> Duration {
+        self.jitter
+    }
+
+    /// Sets the maximum jitter.
+    pub fn set_jitter(&mut self, jitter: Duration) {
+        self.jitter = jitter
+    }
+
+    /// Returns the delay before the next fetch without jitter.
+    pub fn delay(&self, state: &RetryState) -> Duration {
+        if state.failures > 0 {
+            backoff(self.interval, state.failures, self.max_backoff)
+        }
+        else {
+            backoff(self.interval, state.idle, self.max_idle)
+        }
+    }
+
+    /// Returns the time of the next fetch including a random jitter.
+    ///
+    /// If nothing has been fetched yet, this is `now`.
+    pub fn next_fetch(&self, state: &RetryState, now: Time) -> Time {
+        let mut random = [0u8; 4];
+        // If the system random generator fails, we go without jitter.
+        let random = SystemRandom::new().fill(&mut random).map(|_| {
+            u32::from_be_bytes(random)
+        }).unwrap_or(0);
+        self.next_fetch_with_random(state, now, random)
+    }
+
+    /// Returns the time of the next fetch using the given random value.
+    ///
+    /// The jitter is the maximum jitter scaled by `random / u32::MAX`.
+    fn next_fetch_with_random(
+        &self, state: &RetryState, now: Time, random: u32
+    ) -> Time {
+        let last = match state.last_fetch {
+            Some(last) => last,
+            None => return now,
+        };
+        let jitter = self.jitter.as_secs_f64()
+            * f64::from(random) / f64::from(u32::MAX);
+        let delay = min(
+            self.delay(state) + Duration::from_secs_f64(jitter),
+            MAX_DELAY
+        );
+        // MAX_DELAY is small enough for this to never fail.
+        last + chrono::Duration::from_std(delay).unwrap_or_else(|_| {
+            chrono::Duration::zero()
+        })
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_INTERVAL)
+    }
+}
+
+/// The longest delay used by a policy.
+///
+/// This keeps the time arithmetic from overflowing.
+const MAX_DELAY: Duration = Duration::from_secs(365 * 86_400);
+
+/// Doubles `interval` `count` times but returns at most `max`.
+fn backoff(interval: Duration, count: u32, max: Duration) -> Duration {
+    let factor = 1u32.checked_shl(count).unwrap_or(u32::MAX);
+    match interval.checked_mul(factor) {
+        Some(delay) => min(delay, max),
+        None => max
+    }
+}
+
+
+//------------ RetryState ----------------------------------------------------
+
+/// The fetch history of a repository needed for scheduling.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RetryState {
+    /// The time of the last fetch.
+    last_fetch: Option<Time>,
+
+    /// The number of consecutive failures.
+    failures: u32,
+
+    /// The number of consecutive fetches without a new serial number.
+    idle: u32,
+
+    /// The serial number seen last.
+    serial: Option<usize>,
+}
+
+impl RetryState {
+    /// Creates a new state for a repository that hasn’t been fetched.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the time of the last fetch.
+    pub fn last_fetch(&self) -> Option<Time> {
+        self.last_fetch
+    }
+
+    /// Returns the number of consecutive failures.
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// Records a successful fetch at `now` that resulted in `serial`.
+    ///
+    /// Returns whether the serial number is new.
+    pub fn success(&mut self, now: Time, serial: usize) -> bool {
+        self.last_fetch = Some(now);
+        self.failures = 0;
+        let new = self.serial != Some(serial);
+        if new {
+            self.idle = 0;
+            self.serial = Some(serial);
+        }
+        else {
+            self.idle = self.idle.saturating_add(1);
+        }
+        new
+    }
+
+    /// Records a failed fetch at `now`.
+    pub fn failure(&mut self, now: Time) {
+        self.last_fetch = Some(now);
+        self.failures = self.failures.saturating_add(1);
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn backoff_and_idle() {
+        let mut policy = RetryPolicy::new(MIN * 10);
+        policy.set_max_idle(MIN * 30);
+        let mut state = RetryState::new();
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        assert_eq!(policy.next_fetch(&state, now), now);
+
+        assert!(state.success(now, 10));
+        assert_eq!(policy.delay(&state), MIN * 10);
+        state.failure(now);
+        assert_eq!(policy.delay(&state), MIN * 20);
+        state.failure(now);
+        state.failure(now);
+        assert_eq!(policy.delay(&state), MIN * 80);
+        for _ in 0..40 {
+            state.failure(now);
+        }
+        assert_eq!(policy.delay(&state), MIN * 240);
+
+        // Success resets the failures but the serial is unchanged.
+        assert!(!state.success(now, 10));
+        assert_eq!(state.failures(), 0);
+        assert_eq!(policy.delay(&state), MIN * 20);
+        assert!(!state.success(now, 10));
+        assert_eq!(policy.delay(&state), MIN * 30);
+
+        // A new serial resets the idle backoff.
+        assert!(state.success(now, 11));
+        assert_eq!(policy.delay(&state), MIN * 10);
+    }
+
+    #[test]
+    fn jitter() {
+        let policy = RetryPolicy::new(MIN * 10);
+        let mut state = RetryState::new();
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        state.success(now, 1);
+        assert_eq!(
+            policy.next_fetch_with_random(&state, now, 0),
+            now + chrono::Duration::minutes(10)
+        );
+        assert_eq!(
+            policy.next_fetch_with_random(&state, now, u32::MAX),
+            now + chrono::Duration::minutes(11)
+        );
+        let next = policy.next_fetch(&state, now);
+        assert!(next >= now + chrono::Duration::minutes(10));
+        assert!(next <= now + chrono::Duration::minutes(11));
+    }
+}
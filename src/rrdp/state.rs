@@ -0,0 +1,312 @@
+//! Persisting the state of an RRDP session.
+
+use std::fmt;
+use std::str::FromStr;
+use serde::de;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use uuid::Uuid;
+use crate::x509::Time;
+use super::{Hash, NotificationFile};
+
+
+//------------ SessionState --------------------------------------------------
+
+/// The state of a relying party’s RRDP session with a repository.
+///
+/// In order to update its copy of a repository via deltas, a relying party
+/// needs to remember the session ID and serial number it last processed.
+/// It should also remember the hashes of the deltas it has seen in order
+/// to detect a repository changing already published deltas, in which
+/// case the snapshot needs to be used. Finally, the times of the last
+/// attempt to fetch the repository and of its last successful update are
+/// kept.
+///
+/// The state can be serialized and deserialized via serde. The serialized
+/// form carries a format version, currently [`VERSION`]. Deserializing
+/// converts older versions to the current one and rejects versions newer
+/// than it.
+///
+/// [`VERSION`]: #associatedconstant.VERSION
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionState {
+    /// The session ID of the last processed update.
+    session_id: Uuid,
+
+    /// The serial number of the last processed update.
+    serial: usize,
+
+    /// The serial numbers and hashes of the known deltas.
+    deltas: Vec<(usize, Hash)>,
+
+    /// The time of the last attempt to fetch the notification file.
+    last_fetch: Option<Time>,
+
+    /// The time of the last successful update.
+    last_update: Option<Time>,
+}
+
+impl SessionState {
+    /// The current version of the serialized format.
+    pub const VERSION: u32 = 1;
+
+    /// Creates a new state for the given session ID and serial number.
+    pub fn new(session_id: Uuid, serial: usize) -> Self {
+        SessionState {
+            session_id,
+            serial,
+            deltas: Vec::new(),
+            last_fetch: None,
+            last_update: None,
+        }
+    }
+
+    /// Creates a new state after processing a notification file.
+    pub fn from_notification(notify: &NotificationFile) -> Self {
+        let mut res = Self::new(notify.session_id, notify.serial);
+        res.deltas = delta_hashes(notify);
+        res
+    }
+
+    /// Returns the session ID of the last processed update.
+    pub fn session_id(&self) -> Uuid {
+        self.session_id
+    }
+
+    /// Returns the serial number of the last processed update.
+    pub fn serial(&self) -> usize {
+        self.serial
+    }
+
+    /// Returns the serial numbers and hashes of the known deltas.
+    pub fn deltas(&self) -> &[(usize, Hash)] {
+        &self.deltas
+    }
+
+    /// Returns the hash of the delta with the given serial number if known.
+    pub fn delta_hash(&self, serial: usize) -> Option<Hash> {
+        self.deltas.iter().find(|item| item.0 == serial).map(|item| item.1)
+    }
+
+    /// Returns the time of the last attempt to fetch the repository.
+    pub fn last_fetch(&self) -> Option<Time> {
+        self.last_fetch
+    }
+
+    /// Sets the time of the last attempt to fetch the repository.
+    pub fn set_last_fetch(&mut self, time: Time) {
+        self.last_fetch = Some(time)
+    }
+
+    /// Returns the time of the last successful update.
+    pub fn last_update(&self) -> Option<Time> {
+        self.last_update
+    }
+
+    /// Sets the time of the last successful update.
+    pub fn set_last_update(&mut self, time: Time) {
+        self.last_update = Some(time)
+    }
+
+    /// Returns whether the deltas of a notification file are consistent.
+    ///
+    /// This is the case if the notification file is for the same session
+    /// and all deltas both know about have the same hash. If it returns
+    /// `false`, the repository needs to be updated via its snapshot.
+    pub fn is_consistent(&self, notify: &NotificationFile) -> bool {
+        notify.session_id == self.session_id
+            && notify.deltas.iter().all(|(serial, delta)| {
+                self.delta_hash(*serial).map(|hash| {
+                    hash == *delta.hash()
+                }).unwrap_or(true)
+            })
+    }
+
+    /// Updates the state after processing a notification file at `now`.
+    pub fn update(&mut self, notify: &NotificationFile, now: Time) {
+        self.session_id = notify.session_id;
+        self.serial = notify.serial;
+        self.deltas = delta_hashes(notify);
+        self.last_update = Some(now);
+    }
+}
+
+/// Returns the serial numbers and hashes of a notification’s deltas.
+fn delta_hashes(notify: &NotificationFile) -> Vec<(usize, Hash)> {
+    notify.deltas.iter().map(|(serial, delta)| {
+        (*serial, *delta.hash())
+    }).collect()
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for SessionState {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        StoredState {
+            version: Self::VERSION,
+            session_id: self.session_id.to_string(),
+            serial: self.serial,
+            deltas: self.deltas.iter().map(|&(serial, hash)| {
+                StoredDelta { serial, hash }
+            }).collect(),
+            last_fetch: self.last_fetch,
+            last_update: self.last_update,
+        }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SessionState {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        StoredState::deserialize(deserializer)?.migrate().map_err(
+            de::Error::custom
+        )
+    }
+}
+
+
+//------------ StoredState ---------------------------------------------------
+
+/// The serialized form of the session state.
+///
+/// Fields added in later versions need to have a default so that older
+/// versions can be read.
+#[derive(Deserialize, Serialize)]
+struct StoredState {
+    version: u32,
+    session_id: String,
+    serial: usize,
+    #[serde(default)]
+    deltas: Vec<StoredDelta>,
+    #[serde(default)]
+    last_fetch: Option<Time>,
+    #[serde(default)]
+    last_update: Option<Time>,
+}
+
+impl StoredState {
+    /// Converts the stored state into the current session state.
+    fn migrate(self) -> Result<SessionState, StateError> {
+        if self.version == 0 || self.version > SessionState::VERSION {
+            return Err(StateError::Version(self.version))
+        }
+        Ok(SessionState {
+            session_id: Uuid::from_str(&self.session_id).map_err(|_| {
+                StateError::SessionId
+            })?,
+            serial: self.serial,
+            deltas: self.deltas.into_iter().map(|item| {
+                (item.serial, item.hash)
+            }).collect(),
+            last_fetch: self.last_fetch,
+            last_update: self.last_update,
+        })
+    }
+}
+
+/// The serialized form of a delta.
+#[derive(Deserialize, Serialize)]
+struct StoredDelta {
+    serial: usize,
+    hash: Hash,
+}
+
+
+//------------ StateError ----------------------------------------------------
+
+/// A serialized session state could not be converted.
+enum StateError {
+    /// The format version is not supported.
+    Version(u32),
+
+    /// The session ID is not a UUID.
+    SessionId,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::Version(version) => {
+                write!(f, "unsupported RRDP state version {}", version)
+            }
+            StateError::SessionId => f.write_str("invalid RRDP session ID"),
+        }
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn notification() -> NotificationFile {
+        NotificationFile::parse(
+            include_bytes!("../../test-data/ripe-notification.xml").as_ref()
+        ).unwrap()
+    }
+
+    #[test]
+    fn serde() {
+        let mut state = SessionState::from_notification(&notification());
+        state.set_last_fetch(Time::utc(2020, 1, 1, 12, 0, 0));
+        let json = serde_json::to_value(&state).unwrap();
+        assert_eq!(json["version"], 1);
+        assert_eq!(
+            json["session_id"], "a2d845c4-5b91-4015-a2b7-988c03ce232a"
+        );
+        assert_eq!(json["serial"], 1742);
+        assert_eq!(json["deltas"][0]["serial"], 1742);
+        assert_eq!(
+            json["deltas"][0]["hash"],
+            "fa2bdce6b32ddf7f61f91b4549abc61b\
+             6d6986fa91061b37c72f045fa1b7ba79"
+        );
+        assert_eq!(
+            serde_json::from_value::<SessionState>(json).unwrap(), state
+        );
+
+        let state: SessionState = serde_json::from_str(r#"{
+            "version": 1,
+            "session_id": "a2d845c4-5b91-4015-a2b7-988c03ce232a",
+            "serial": 12
+        }"#).unwrap();
+        assert_eq!(state.serial(), 12);
+        assert!(state.deltas().is_empty());
+        assert_eq!(state.last_update(), None);
+
+        assert!(serde_json::from_str::<SessionState>(r#"{
+            "version": 2,
+            "session_id": "a2d845c4-5b91-4015-a2b7-988c03ce232a",
+            "serial": 12
+        }"#).is_err());
+        assert!(serde_json::from_str::<SessionState>(r#"{
+            "version": 1,
+            "session_id": "foo",
+            "serial": 12
+        }"#).is_err());
+    }
+
+    #[test]
+    fn consistency() {
+        let notify = notification();
+        let mut state = SessionState::from_notification(&notify);
+        assert!(state.is_consistent(&notify));
+        assert_eq!(
+            state.delta_hash(1742), Some(*notify.deltas[0].1.hash())
+        );
+
+        state.deltas[1].1 = Hash::from_data(b"other");
+        assert!(!state.is_consistent(&notify));
+
+        let now = Time::utc(2020, 1, 1, 12, 0, 0);
+        state.update(&notify, now);
+        assert!(state.is_consistent(&notify));
+        assert_eq!(state.last_update(), Some(now));
+
+        let other = SessionState::new(Uuid::nil(), 1742);
+        assert!(!other.is_consistent(&notify));
+    }
+}
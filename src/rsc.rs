@@ -0,0 +1,944 @@
+//! RPKI Signed Checklists.
+//!
+//! Signed checklists allow the holder of RPKI resources to sign a list of
+//! hashes over arbitrary files. They are defined in [RFC 9323].
+//!
+//! A signed checklist is a signed object that isn’t published in the RPKI
+//! repository but rather distributed alongside the files it covers. This
+//! module provides the type [`Rsc`] for such an object and
+//! [`SignedChecklist`] for its content which consists of a set of
+//! resources and a [`Checklist`] with the hashes of the files. The
+//! function [`verify_files`] checks files stored in a local directory
+//! against such a list. New signed checklists can be created via
+//! [`RscBuilder`].
+//!
+//! [RFC 9323]: https://tools.ietf.org/html/rfc9323
+//! [`Rsc`]: struct.Rsc.html
+//! [`SignedChecklist`]: struct.SignedChecklist.html
+//! [`Checklist`]: struct.Checklist.html
+//! [`verify_files`]: fn.verify_files.html
+//! [`RscBuilder`]: struct.RscBuilder.html
+
+use std::{fs, io, slice};
+use std::path::{Path, PathBuf};
+use bcder::{decode, encode};
+use bcder::{Captured, ConstOid, Ia5String, Mode, OctetString, Tag, xerr};
+use bcder::encode::Values;
+use bytes::Bytes;
+use crate::oid;
+use crate::cert::{Cert, ResourceCert};
+use crate::crypto::{DigestAlgorithm, Signer, SigningError};
+use crate::resources::{
+    AddressFamily, AsBlocks, AsResources, IpBlocks, IpResources
+};
+use crate::sigobj::{SignedContent, SignedObject, SignedObjectBuilder};
+use crate::x509::{Time, ValidationError};
+
+
+//------------ Rsc -----------------------------------------------------------
+
+/// A decoded RPKI signed checklist.
+///
+/// In order to get to the content of the object, you need to validate it
+/// via the `validate` method.
+#[derive(Clone, Debug)]
+pub struct Rsc {
+    signed: SignedObject,
+    content: SignedChecklist,
+}
+
+impl Rsc {
+    /// Decodes a signed checklist from a source.
+    pub fn decode<S: decode::Source>(
+        source: S,
+        strict: bool
+    ) -> Result<Self, S::Err> {
+        let (signed, content) = SignedObject::decode_with_content(
+            source, strict
+        )?;
+        Ok(Rsc { signed, content })
+    }
+
+    /// Validates the object.
+    ///
+    /// You need to pass in the certificate of the issuing CA. If validation
+    /// succeeds, the result will be the EE certificate of the object and
+    /// its content. The files covered by the checklist can then be checked
+    /// via [`verify_files`].
+    ///
+    /// [`verify_files`]: fn.verify_files.html
+    pub fn validate(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+    ) -> Result<(ResourceCert, SignedChecklist), ValidationError> {
+        self.validate_at(cert, strict, Time::now())
+    }
+
+    /// Validates the object at the given time.
+    ///
+    /// In addition to the validation of the signed object, this checks
+    /// that the resources of the EE certificate are exactly those listed
+    /// in the checklist and that all file names are acceptable.
+    pub fn validate_at(
+        self,
+        cert: &ResourceCert,
+        strict: bool,
+        now: Time
+    ) -> Result<(ResourceCert, SignedChecklist), ValidationError> {
+        let cert = self.signed.validate_detached_at(cert, strict, now)?;
+        self.content.validate(&cert)?;
+        Ok((cert, self.content))
+    }
+
+    /// Returns a value encoder for a reference to the object.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        self.signed.encode_ref()
+    }
+
+    /// Returns a DER encoded Captured for this.
+    pub fn to_captured(&self) -> Captured {
+        self.encode_ref().to_captured(Mode::Der)
+    }
+
+    /// Returns a reference to the EE certificate of this object.
+    pub fn cert(&self) -> &Cert {
+        self.signed.cert()
+    }
+
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signed.signing_time()
+    }
+
+    /// Returns the binary signing time attribute if present.
+    ///
+    /// The value is the number of seconds since the Unix epoch as defined
+    /// in RFC 6019.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.signed.binary_signing_time()
+    }
+
+    /// Returns a reference to the content of the object.
+    pub fn content(&self) -> &SignedChecklist {
+        &self.content
+    }
+}
+
+
+//------------ SignedChecklist -----------------------------------------------
+
+/// The content of an RPKI signed checklist.
+///
+/// The content consists of the resources of the signer and the checklist
+/// itself. It is defined as follows:
+///
+/// ```txt
+/// RpkiSignedChecklist ::= SEQUENCE {
+///     version         [0] INTEGER DEFAULT 0,
+///     resources       ResourceBlock,
+///     digestAlgorithm DigestAlgorithmIdentifier,
+///     checkList       SEQUENCE (SIZE(1..MAX)) OF FileNameAndHash }
+///
+/// FileNameAndHash ::= SEQUENCE {
+///     fileName        PortableFilename OPTIONAL,
+///     hash            OCTET STRING }
+///
+/// ResourceBlock ::= SEQUENCE {
+///     asID            [0] ConstrainedASIdentifiers OPTIONAL,
+///     ipAddrBlocks    [1] ConstrainedIPAddrBlocks OPTIONAL }
+/// ```
+///
+/// The module uses implicit tagging. At least one of the resource types
+/// must be present and none of them may be empty.
+#[derive(Clone, Debug)]
+pub struct SignedChecklist {
+    /// The AS resources.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources.
+    v6_resources: IpBlocks,
+
+    /// The list of file names and hashes.
+    checklist: Checklist,
+}
+
+impl SignedChecklist {
+    /// Returns the AS resources of the checklist.
+    pub fn as_resources(&self) -> &AsBlocks {
+        &self.as_resources
+    }
+
+    /// Returns the IPv4 resources of the checklist.
+    pub fn v4_resources(&self) -> &IpBlocks {
+        &self.v4_resources
+    }
+
+    /// Returns the IPv6 resources of the checklist.
+    pub fn v6_resources(&self) -> &IpBlocks {
+        &self.v6_resources
+    }
+
+    /// Returns the list of file names and hashes.
+    pub fn checklist(&self) -> &Checklist {
+        &self.checklist
+    }
+
+    /// Takes the content from the beginning of an encoded value.
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        cons.take_sequence(|cons| {
+            cons.take_opt_primitive_if(Tag::CTX_0, |prim| {
+                if prim.to_u8()? != 0 {
+                    xerr!(return Err(decode::Malformed.into()))
+                }
+                Ok(())
+            })?;
+            let (as_resources, v4_resources, v6_resources) = {
+                cons.take_sequence(Self::take_resources)?
+            };
+            let algorithm = DigestAlgorithm::take_from(cons)?;
+            let mut entries = Vec::new();
+            cons.take_sequence(|cons| {
+                while let Some(entry) = cons.take_opt_sequence(|cons| {
+                    let file_name = cons.take_opt_value_if(
+                        Tag::IA5_STRING, Ia5String::from_content
+                    )?.map(Ia5String::into_bytes);
+                    let hash = OctetString::take_from(cons)?.into_bytes();
+                    if hash.len() != algorithm.digest_len() {
+                        xerr!(return Err(decode::Malformed.into()))
+                    }
+                    Ok(ChecklistEntry::new(file_name, hash))
+                })? {
+                    entries.push(entry)
+                }
+                Ok(())
+            })?;
+            if entries.is_empty() {
+                xerr!(return Err(decode::Malformed.into()))
+            }
+            Ok(SignedChecklist {
+                as_resources, v4_resources, v6_resources,
+                checklist: Checklist::new(algorithm, entries)
+            })
+        })
+    }
+
+    /// Takes the content of the resource block.
+    fn take_resources<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<(AsBlocks, IpBlocks, IpBlocks), S::Err> {
+        let as_resources = cons.take_opt_constructed_if(Tag::CTX_0, |cons| {
+            cons.take_value_if(Tag::CTX_0, AsBlocks::parse_content)
+        })?;
+        let ip_resources = cons.take_opt_constructed_if(Tag::CTX_1, |cons| {
+            let mut v4 = None;
+            let mut v6 = None;
+            while let Some(()) = cons.take_opt_sequence(|cons| {
+                let target = match AddressFamily::take_from(cons)? {
+                    AddressFamily::Ipv4 => &mut v4,
+                    AddressFamily::Ipv6 => &mut v6,
+                };
+                if target.is_some() {
+                    xerr!(return Err(decode::Malformed.into()))
+                }
+                let blocks = cons.take_value_if(
+                    Tag::SEQUENCE, IpBlocks::parse_content
+                )?;
+                if blocks.is_empty() {
+                    xerr!(return Err(decode::Malformed.into()))
+                }
+                *target = Some(blocks);
+                Ok(())
+            })? { }
+            if v4.is_none() && v6.is_none() {
+                xerr!(return Err(decode::Malformed.into()))
+            }
+            Ok((v4, v6))
+        })?;
+        if let Some(ref blocks) = as_resources {
+            if blocks.is_empty() {
+                xerr!(return Err(decode::Malformed.into()))
+            }
+        }
+        let (v4, v6) = match ip_resources {
+            Some(res) => res,
+            None if as_resources.is_some() => (None, None),
+            None => xerr!(return Err(decode::Malformed.into()))
+        };
+        Ok((
+            as_resources.unwrap_or_else(AsBlocks::empty),
+            v4.unwrap_or_else(IpBlocks::empty),
+            v6.unwrap_or_else(IpBlocks::empty),
+        ))
+    }
+
+    /// Checks the content against the EE certificate.
+    ///
+    /// The resources of the certificate must not be inherited and must
+    /// be exactly those of the checklist. All file names must be from the
+    /// portable filename character set.
+    fn validate(&self, cert: &ResourceCert) -> Result<(), ValidationError> {
+        let tbs = cert.as_cert();
+        if tbs.v4_resources().map(IpResources::is_inherited).unwrap_or(false)
+            || tbs.v6_resources().map(IpResources::is_inherited)
+                .unwrap_or(false)
+            || tbs.as_resources().map(AsResources::is_inherited)
+                .unwrap_or(false)
+        {
+            return Err(ValidationError)
+        }
+        if !same_ip(cert.v4_resources(), &self.v4_resources)
+            || !same_ip(cert.v6_resources(), &self.v6_resources)
+            || !cert.as_resources().contains(&self.as_resources)
+            || !self.as_resources.contains(cert.as_resources())
+        {
+            return Err(ValidationError)
+        }
+        for entry in &self.checklist {
+            if let Some(name) = entry.file_name() {
+                if checked_file_name(name).is_none() {
+                    return Err(ValidationError)
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a value encoder for a reference to the content.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        encode::sequence((
+            encode::sequence((
+                if self.as_resources.is_empty() {
+                    None
+                }
+                else {
+                    Some(encode::sequence_as(Tag::CTX_0,
+                        encode::sequence_as(Tag::CTX_0,
+                            self.as_resources.encode_ref()
+                        )
+                    ))
+                },
+                if self.v4_resources.is_empty()
+                    && self.v6_resources.is_empty()
+                {
+                    None
+                }
+                else {
+                    Some(encode::sequence_as(Tag::CTX_1, (
+                        Self::encode_family(
+                            AddressFamily::Ipv4, &self.v4_resources
+                        ),
+                        Self::encode_family(
+                            AddressFamily::Ipv6, &self.v6_resources
+                        ),
+                    )))
+                },
+            )),
+            self.checklist.digest_algorithm().encode(),
+            encode::sequence(encode::iter(
+                self.checklist.iter().map(ChecklistEntry::encode_ref)
+            ))
+        ))
+    }
+
+    /// Returns an encoder for one address family of the resource block.
+    fn encode_family(
+        family: AddressFamily, blocks: &IpBlocks
+    ) -> Option<impl encode::Values + '_> {
+        if blocks.is_empty() {
+            None
+        }
+        else {
+            Some(encode::sequence((family.encode(), blocks.encode_ref())))
+        }
+    }
+}
+
+
+//--- SignedContent
+
+impl SignedContent for SignedChecklist {
+    const CONTENT_TYPE: ConstOid = oid::CT_SIGNED_CHECKLIST;
+
+    fn take_content_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from(cons)
+    }
+
+    fn to_content_bytes(&self) -> Bytes {
+        self.encode_ref().to_captured(Mode::Der).into_bytes()
+    }
+}
+
+
+/// Returns whether two sets of IP blocks are the same.
+fn same_ip(left: &IpBlocks, right: &IpBlocks) -> bool {
+    left.contains(right) && right.contains(left)
+}
+
+
+//------------ RscBuilder ----------------------------------------------------
+
+/// A builder for RPKI signed checklists.
+///
+/// The builder collects the files to be covered by the checklist and the
+/// resources to sign with. At least one resource and one file need to be
+/// added before the checklist can be finalized.
+#[derive(Clone, Debug)]
+pub struct RscBuilder {
+    /// The digest algorithm for the hashes.
+    algorithm: DigestAlgorithm,
+
+    /// The entries of the checklist.
+    entries: Vec<ChecklistEntry>,
+
+    /// The AS resources.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources.
+    v6_resources: IpBlocks,
+}
+
+impl RscBuilder {
+    /// Creates a new, empty builder.
+    pub fn new() -> Self {
+        RscBuilder {
+            algorithm: DigestAlgorithm::default(),
+            entries: Vec::new(),
+            as_resources: AsBlocks::empty(),
+            v4_resources: IpBlocks::empty(),
+            v6_resources: IpBlocks::empty(),
+        }
+    }
+
+    /// Sets the AS resources of the checklist.
+    pub fn set_as_resources(&mut self, resources: AsBlocks) {
+        self.as_resources = resources
+    }
+
+    /// Sets the IPv4 resources of the checklist.
+    pub fn set_v4_resources(&mut self, resources: IpBlocks) {
+        self.v4_resources = resources
+    }
+
+    /// Sets the IPv6 resources of the checklist.
+    pub fn set_v6_resources(&mut self, resources: IpBlocks) {
+        self.v6_resources = resources
+    }
+
+    /// Adds an entry for the given data.
+    ///
+    /// If `file_name` is given, it must only contain characters of the
+    /// portable filename character set and must not start with a dot.
+    /// Returns `false` and leaves the builder unchanged otherwise.
+    pub fn add_data(&mut self, file_name: Option<&str>, data: &[u8]) -> bool {
+        if let Some(name) = file_name {
+            if checked_file_name(name.as_bytes()).is_none() {
+                return false
+            }
+        }
+        self.entries.push(ChecklistEntry::new(
+            file_name.map(|name| Bytes::copy_from_slice(name.as_bytes())),
+            Bytes::copy_from_slice(self.algorithm.digest(data).as_ref())
+        ));
+        true
+    }
+
+    /// Adds an entry for the file at the given path.
+    ///
+    /// The entry is added with the file name of the path. Returns an
+    /// error if the file cannot be read or its name is not acceptable.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let name = path.file_name().and_then(|name| name.to_str());
+        if name.is_none() || !self.add_data(name, &data) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput, "invalid file name"
+            ))
+        }
+        Ok(())
+    }
+
+    /// Returns the content for the current state of the builder.
+    pub fn to_checklist(&self) -> SignedChecklist {
+        SignedChecklist {
+            as_resources: self.as_resources.clone(),
+            v4_resources: self.v4_resources.clone(),
+            v6_resources: self.v6_resources.clone(),
+            checklist: Checklist::new(self.algorithm, self.entries.clone()),
+        }
+    }
+
+    /// Finalizes the builder into a signed checklist.
+    ///
+    /// The signed object builder must have been created via
+    /// `SignedObjectBuilder::new_detached`. The resources of the EE
+    /// certificate are set to those of the checklist.
+    ///
+    /// # Panic
+    ///
+    /// This method will panic if no resources or no files have been added
+    /// or if the signed object builder isn’t detached as this would lead
+    /// to an invalid object.
+    pub fn finalize<S: Signer>(
+        self,
+        mut sigobj: SignedObjectBuilder,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<Rsc, SigningError<S::Error>> {
+        assert!(!self.entries.is_empty());
+        assert!(
+            !self.as_resources.is_empty() || !self.v4_resources.is_empty()
+            || !self.v6_resources.is_empty()
+        );
+        assert!(sigobj.signed_object().is_none());
+        let content = self.to_checklist();
+        sigobj.set_v4_resources(ip_resources(&content.v4_resources));
+        sigobj.set_v6_resources(ip_resources(&content.v6_resources));
+        sigobj.set_as_resources(
+            if content.as_resources.is_empty() { None }
+            else { Some(AsResources::blocks(content.as_resources.clone())) }
+        );
+        let signed = sigobj.finalize_content(&content, signer, issuer_key)?;
+        Ok(Rsc { signed, content })
+    }
+}
+
+
+//--- Default
+
+impl Default for RscBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// Converts IP blocks into optional certificate resources.
+fn ip_resources(blocks: &IpBlocks) -> Option<IpResources> {
+    if blocks.is_empty() {
+        None
+    }
+    else {
+        Some(IpResources::blocks(blocks.clone()))
+    }
+}
+
+
+//------------ Checklist -----------------------------------------------------
+
+/// The list of files and their hashes of a signed checklist.
+#[derive(Clone, Debug)]
+pub struct Checklist {
+    /// The digest algorithm used for all hashes.
+    algorithm: DigestAlgorithm,
+
+    /// The entries of the list.
+    entries: Vec<ChecklistEntry>,
+}
+
+impl Checklist {
+    /// Creates a new checklist from an algorithm and a list of entries.
+    pub fn new(
+        algorithm: DigestAlgorithm,
+        entries: Vec<ChecklistEntry>
+    ) -> Self {
+        Checklist { algorithm, entries }
+    }
+
+    /// Returns the digest algorithm used for the hashes.
+    pub fn digest_algorithm(&self) -> DigestAlgorithm {
+        self.algorithm
+    }
+
+    /// Returns an iterator over the entries of the list.
+    pub fn iter(&self) -> slice::Iter<'_, ChecklistEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the number of entries in the list.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the list is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+
+//--- IntoIterator
+
+impl<'a> IntoIterator for &'a Checklist {
+    type Item = &'a ChecklistEntry;
+    type IntoIter = slice::Iter<'a, ChecklistEntry>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+
+//------------ ChecklistEntry ------------------------------------------------
+
+/// An entry of a checklist.
+///
+/// Each entry contains the hash of a file and, optionally, its name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChecklistEntry {
+    /// The name of the file if present.
+    file_name: Option<Bytes>,
+
+    /// The hash of the file’s content.
+    hash: Bytes,
+}
+
+impl ChecklistEntry {
+    /// Creates a new entry from an optional file name and a hash.
+    pub fn new(file_name: Option<Bytes>, hash: Bytes) -> Self {
+        ChecklistEntry { file_name, hash }
+    }
+
+    /// Returns the file name if present.
+    pub fn file_name(&self) -> Option<&Bytes> {
+        self.file_name.as_ref()
+    }
+
+    /// Returns the hash.
+    pub fn hash(&self) -> &Bytes {
+        &self.hash
+    }
+
+    /// Returns a value encoder for a reference to the entry.
+    pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
+        encode::sequence((
+            self.file_name.as_ref().map(|name| {
+                OctetString::encode_slice_as(name.as_ref(), Tag::IA5_STRING)
+            }),
+            OctetString::encode_slice(self.hash.as_ref()),
+        ))
+    }
+
+    /// Returns whether `data` matches the hash of the entry.
+    pub fn verify(&self, algorithm: DigestAlgorithm, data: &[u8]) -> bool {
+        ring::constant_time::verify_slices_are_equal(
+            self.hash.as_ref(),
+            algorithm.digest(data).as_ref()
+        ).is_ok()
+    }
+}
+
+
+//------------ verify_files --------------------------------------------------
+
+/// Checks files in a local directory against a checklist.
+///
+/// Entries with a file name are checked against the file of that name in
+/// `base_dir`. File names must only contain the characters of the portable
+/// filename character set, i.e., ASCII letters and digits, dot, dash, and
+/// underscore, and must not start with a dot. Other names are reported as
+/// [`FileStatus::InvalidName`] and never touched.
+///
+/// Entries without a file name are matched against all regular files
+/// directly in `base_dir` by their hash. Each file is matched at most once.
+///
+/// The function returns one result for each entry of the checklist in the
+/// order of the checklist. An error is returned only if reading the
+/// directory or a file fails for reasons other than the file not existing.
+///
+/// [`FileStatus::InvalidName`]: enum.FileStatus.html#variant.InvalidName
+pub fn verify_files<P: AsRef<Path>>(
+    checklist: &Checklist,
+    base_dir: P,
+) -> Result<Vec<FileCheck>, io::Error> {
+    let base_dir = base_dir.as_ref();
+    let algorithm = checklist.digest_algorithm();
+    let mut res = Vec::with_capacity(checklist.len());
+    let mut named = Vec::new();
+    let mut unnamed = false;
+
+    for entry in checklist {
+        let name = match entry.file_name {
+            Some(ref name) => name,
+            None => {
+                unnamed = true;
+                res.push(FileCheck::new(None, FileStatus::NoMatch));
+                continue
+            }
+        };
+        let name = match checked_file_name(name) {
+            Some(name) => name,
+            None => {
+                res.push(FileCheck::new(None, FileStatus::InvalidName));
+                continue
+            }
+        };
+        let path = base_dir.join(name);
+        let status = match fs::read(&path) {
+            Ok(data) => {
+                if entry.verify(algorithm, &data) {
+                    FileStatus::Match
+                }
+                else {
+                    FileStatus::Mismatch
+                }
+            }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                FileStatus::Missing
+            }
+            Err(err) => return Err(err)
+        };
+        named.push(path.clone());
+        res.push(FileCheck::new(Some(path), status));
+    }
+
+    if !unnamed {
+        return Ok(res)
+    }
+
+    // Files that have been checked by name are not considered for the
+    // unnamed entries.
+    for dir_entry in fs::read_dir(base_dir)? {
+        let dir_entry = dir_entry?;
+        if !dir_entry.file_type()?.is_file() {
+            continue
+        }
+        let path = dir_entry.path();
+        if named.contains(&path) {
+            continue
+        }
+        let digest = algorithm.digest(&fs::read(&path)?);
+        let found = checklist.iter().zip(res.iter_mut()).find(
+            |(entry, res)| {
+                entry.file_name.is_none()
+                && res.status == FileStatus::NoMatch
+                && entry.hash.as_ref() == digest.as_ref()
+            }
+        );
+        if let Some((_, res)) = found {
+            res.path = Some(path);
+            res.status = FileStatus::Match;
+        }
+    }
+    Ok(res)
+}
+
+/// Returns the file name as a str if it is acceptable.
+fn checked_file_name(name: &[u8]) -> Option<&str> {
+    if name.is_empty() || name[0] == b'.' {
+        return None
+    }
+    if !name.iter().all(|&ch| {
+        ch.is_ascii_alphanumeric() || ch == b'.' || ch == b'-' || ch == b'_'
+    }) {
+        return None
+    }
+    std::str::from_utf8(name).ok()
+}
+
+
+//------------ FileCheck -----------------------------------------------------
+
+/// The result of checking one entry of a checklist.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FileCheck {
+    /// The path of the file checked, if any.
+    path: Option<PathBuf>,
+
+    /// The outcome of the check.
+    status: FileStatus,
+}
+
+impl FileCheck {
+    fn new(path: Option<PathBuf>, status: FileStatus) -> Self {
+        FileCheck { path, status }
+    }
+
+    /// Returns the path of the file that was checked.
+    ///
+    /// This is `None` if the entry had an invalid file name or was a
+    /// name-less entry for which no matching file was found.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Returns the outcome of the check.
+    pub fn status(&self) -> FileStatus {
+        self.status
+    }
+
+    /// Returns whether the check succeeded.
+    pub fn is_match(&self) -> bool {
+        self.status == FileStatus::Match
+    }
+}
+
+
+//------------ FileStatus ----------------------------------------------------
+
+/// The outcome of checking a file against a checklist entry.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum FileStatus {
+    /// The file exists and its hash matches.
+    Match,
+
+    /// The file exists but its hash doesn’t match.
+    Mismatch,
+
+    /// The file named in the entry doesn’t exist.
+    Missing,
+
+    /// No file matching the hash of a name-less entry was found.
+    NoMatch,
+
+    /// The file name of the entry is not acceptable.
+    InvalidName,
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use super::*;
+
+    fn decode(data: &[u8]) -> Result<SignedChecklist, decode::Error> {
+        Mode::Der.decode(data, SignedChecklist::take_from)
+    }
+
+    #[test]
+    fn encode_decode() {
+        let mut builder = RscBuilder::new();
+        builder.set_as_resources(AsBlocks::from_str("AS64496").unwrap());
+        builder.set_v6_resources(
+            IpBlocks::from_str("2001:db8::/32").unwrap()
+        );
+        assert!(builder.add_data(Some("one.txt"), b"one"));
+        assert!(builder.add_data(None, b"two"));
+        assert!(!builder.add_data(Some("../passwd"), b"three"));
+        let content = builder.to_checklist();
+        let decoded = decode(content.to_content_bytes().as_ref()).unwrap();
+        assert_eq!(decoded.as_resources(), content.as_resources());
+        assert!(decoded.v4_resources().is_empty());
+        assert!(same_ip(decoded.v6_resources(), content.v6_resources()));
+        assert_eq!(decoded.checklist().len(), 2);
+        let entry = decoded.checklist().iter().next().unwrap();
+        assert_eq!(entry.file_name().unwrap().as_ref(), b"one.txt");
+        assert!(entry.verify(decoded.checklist().digest_algorithm(), b"one"));
+
+        // Without resources or files, the content is malformed.
+        let mut builder = RscBuilder::new();
+        builder.add_data(None, b"one");
+        assert!(decode(builder.to_checklist().to_content_bytes().as_ref())
+            .is_err()
+        );
+        let mut builder = RscBuilder::new();
+        builder.set_as_resources(AsBlocks::from_str("AS64496").unwrap());
+        assert!(decode(builder.to_checklist().to_content_bytes().as_ref())
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn verify_files() {
+        let dir = std::env::temp_dir().join(
+            format!("rpki-rsc-test-{}", std::process::id())
+        );
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("one.txt"), b"one").unwrap();
+        fs::write(dir.join("two.txt"), b"two").unwrap();
+        fs::write(dir.join("three.txt"), b"three").unwrap();
+
+        let alg = DigestAlgorithm::default();
+        let hash = |data: &[u8]| {
+            Bytes::copy_from_slice(alg.digest(data).as_ref())
+        };
+        let name = |name: &'static str| {
+            Some(Bytes::from_static(name.as_bytes()))
+        };
+        let list = Checklist::new(alg, vec![
+            ChecklistEntry::new(name("one.txt"), hash(b"one")),
+            ChecklistEntry::new(name("two.txt"), hash(b"one")),
+            ChecklistEntry::new(name("four.txt"), hash(b"four")),
+            ChecklistEntry::new(name("../one.txt"), hash(b"one")),
+            ChecklistEntry::new(None, hash(b"three")),
+            ChecklistEntry::new(None, hash(b"one")),
+        ]);
+        let res = super::verify_files(&list, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let status: Vec<_> = res.iter().map(FileCheck::status).collect();
+        assert_eq!(status, [
+            FileStatus::Match, FileStatus::Mismatch, FileStatus::Missing,
+            FileStatus::InvalidName, FileStatus::Match, FileStatus::NoMatch,
+        ]);
+        assert_eq!(res[4].path(), Some(dir.join("three.txt").as_path()));
+        assert_eq!(res[3].path(), None);
+    }
+}
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::str::FromStr;
+    use crate::cert::{KeyUsage, Overclaim, TbsCert};
+    use crate::crypto::{PublicKeyFormat, Signer};
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::resources::{AsId, Prefix};
+    use crate::uri;
+    use crate::tal::TalInfo;
+    use crate::x509::Validity;
+    use super::*;
+
+    #[test]
+    fn encode_rsc() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+
+        let mut cert = TbsCert::new(
+            12u64.into(), pubkey.to_subject_name(),
+            Validity::from_secs(86400), None, pubkey, KeyUsage::Ca,
+            Overclaim::Trim
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(uri.clone()));
+        cert.set_rpki_manifest(Some(uri.clone()));
+        cert.build_v4_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_v6_resource_blocks(|b| b.push(Prefix::new(0, 0)));
+        cert.build_as_resource_blocks(|b| b.push((AsId::MIN, AsId::MAX)));
+        let cert = cert.into_cert(&signer, &key).unwrap();
+
+        let mut builder = RscBuilder::new();
+        builder.set_v4_resources(IpBlocks::from_str("192.0.2.0/24").unwrap());
+        builder.add_data(Some("file.txt"), b"content");
+        let rsc = builder.finalize(
+            SignedObjectBuilder::new_detached(
+                12u64.into(), Validity::from_secs(86400), uri.clone(),
+                uri.clone()
+            ),
+            &signer, &key
+        ).unwrap();
+        let rsc = rsc.to_captured();
+
+        let rsc = Rsc::decode(rsc.as_slice(), true).unwrap();
+        assert!(rsc.cert().signed_object().is_none());
+        let cert = cert.validate_ta(
+            TalInfo::from_name("foo".into()).into_arc(), true
+        ).unwrap();
+        let (_, content) = rsc.validate(&cert, true).unwrap();
+        assert_eq!(content.checklist().len(), 1);
+    }
+}
@@ -0,0 +1,522 @@
+//! Fetching rsync repositories via the system’s rsync binary.
+//!
+//! The [`Runner`] type in this module runs an external rsync process in
+//! order to update a local copy of an rsync module. Each module is kept
+//! in its own directory below a base directory, derived from the module’s
+//! authority and name via [`Runner::module_path`].
+//!
+//! This module is only available if the `rsync-fetch` feature is enabled.
+//!
+//! [`Runner`]: struct.Runner.html
+//! [`Runner::module_path`]: struct.Runner.html#method.module_path
+
+use std::{error, fmt, fs};
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use crate::uri;
+use crate::util::path;
+
+
+//------------ Runner --------------------------------------------------------
+
+/// Updates local copies of rsync modules using the rsync binary.
+///
+/// The runner invokes rsync recursively, preserving modification times
+/// and symbolic links, deleting files no longer present in the module,
+/// suppressing the daemon’s message of the day, and limiting the time to
+/// wait for the connection to be established and for data to arrive.
+///
+/// A runner can be shared between threads. If a module is requested while
+/// a fetch of the same module is already in progress, the second request
+/// waits for the first one to finish and receives its result instead of
+/// starting a second rsync process.
+#[derive(Debug)]
+pub struct Runner {
+    /// The rsync command to run.
+    command: OsString,
+
+    /// The directory below which modules are stored.
+    base: PathBuf,
+
+    /// The timeout for establishing a connection.
+    contimeout: Duration,
+
+    /// The I/O timeout once connected.
+    timeout: Duration,
+
+    /// Whether to delete local files not present in the module.
+    delete: bool,
+
+    /// Additional arguments for rsync.
+    args: Vec<OsString>,
+
+    /// The fetches currently in progress.
+    running: Mutex<HashMap<uri::RsyncModule, Arc<Pending>>>,
+}
+
+impl Runner {
+    /// The default timeout for establishing a connection.
+    pub const DEFAULT_CONTIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The default I/O timeout.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+    /// Creates a new runner storing modules below `base`.
+    ///
+    /// The runner uses the `rsync` binary found via the search path.
+    pub fn new<P: Into<PathBuf>>(base: P) -> Self {
+        Runner {
+            command: "rsync".into(),
+            base: base.into(),
+            contimeout: Self::DEFAULT_CONTIMEOUT,
+            timeout: Self::DEFAULT_TIMEOUT,
+            delete: true,
+            args: Vec::new(),
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets the rsync command to run.
+    pub fn set_command<C: Into<OsString>>(&mut self, command: C) {
+        self.command = command.into()
+    }
+
+    /// Sets the timeout for establishing a connection.
+    ///
+    /// The timeout is given to rsync in whole seconds. A timeout of less
+    /// than one second disables it.
+    pub fn set_contimeout(&mut self, contimeout: Duration) {
+        self.contimeout = contimeout
+    }
+
+    /// Sets the I/O timeout.
+    ///
+    /// The timeout is given to rsync in whole seconds. A timeout of less
+    /// than one second disables it.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout
+    }
+
+    /// Sets whether local files not present in the module are deleted.
+    pub fn set_delete(&mut self, delete: bool) {
+        self.delete = delete
+    }
+
+    /// Sets additional arguments for rsync.
+    ///
+    /// These are given before the source and destination.
+    pub fn set_args<I, A>(&mut self, args: I)
+    where I: IntoIterator<Item = A>, A: Into<OsString> {
+        self.args = args.into_iter().map(Into::into).collect()
+    }
+
+    /// Returns the base directory.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Returns the local directory for a module.
+    ///
+    /// The directory is the lowercase authority followed by the module
+    /// name below the base directory. Returns `None` if the authority or
+    /// module name could escape the base directory, i.e., if either of
+    /// them is empty, `"."` or `".."`, or contains a slash or backslash.
+    pub fn module_path(&self, module: &uri::RsyncModule) -> Option<PathBuf> {
+        path::module_path(&self.base, module)
+    }
+
+    /// Returns the local path for an rsync URI.
+    ///
+    /// Returns `None` if the module path is not safe or the URI’s path
+    /// contains a segment that is empty, `"."`, or `".."`, or contains a
+    /// backslash.
+    pub fn uri_path(&self, uri: &uri::Rsync) -> Option<PathBuf> {
+        path::uri_path(&self.base, uri)
+    }
+
+    /// Updates the local copy of a module.
+    ///
+    /// Returns the local directory of the module upon success. If a fetch
+    /// of the module is already in progress, waits for it to finish and
+    /// returns its result.
+    pub fn fetch(
+        &self, module: &uri::RsyncModule
+    ) -> Result<PathBuf, RsyncError> {
+        let path = self.module_path(module).ok_or(RsyncError::BadModule)?;
+        let (pending, owner) = {
+            let mut running = self.running.lock().unwrap();
+            match running.get(module) {
+                Some(pending) => (pending.clone(), false),
+                None => {
+                    let pending = Arc::new(Pending::default());
+                    running.insert(module.clone(), pending.clone());
+                    (pending, true)
+                }
+            }
+        };
+        if !owner {
+            return pending.wait().map(|_| path)
+        }
+        let fetching = Fetching {
+            running: &self.running, module, pending: Some(pending)
+        };
+        let res = self.run(module, &path);
+        fetching.finish(res.clone());
+        res.map(|_| path)
+    }
+
+    /// Runs rsync for a module.
+    fn run(
+        &self, module: &uri::RsyncModule, path: &Path
+    ) -> Result<(), RsyncError> {
+        fs::create_dir_all(path).map_err(|err| {
+            RsyncError::Destination(err.to_string())
+        })?;
+        let output = Command::new(&self.command)
+            .args(self.args(module, path))
+            .output()
+            .map_err(|err| RsyncError::Spawn(err.to_string()))?;
+        if output.status.success() {
+            Ok(())
+        }
+        else {
+            Err(RsyncError::from_exit(
+                output.status.code(),
+                String::from_utf8_lossy(&output.stderr).trim().into()
+            ))
+        }
+    }
+
+    /// Returns the arguments for running rsync for a module.
+    fn args(
+        &self, module: &uri::RsyncModule, path: &Path
+    ) -> Vec<OsString> {
+        let mut res: Vec<OsString> = vec!["-rltz".into(), "--no-motd".into()];
+        if self.delete {
+            res.push("--delete".into());
+        }
+        if self.contimeout.as_secs() > 0 {
+            res.push(
+                format!("--contimeout={}", self.contimeout.as_secs()).into()
+            );
+        }
+        if self.timeout.as_secs() > 0 {
+            res.push(format!("--timeout={}", self.timeout.as_secs()).into());
+        }
+        res.extend(self.args.iter().cloned());
+        res.push(module.to_string().into());
+
+        // The trailing slash makes rsync copy into rather than below the
+        // destination.
+        let mut dest = path.as_os_str().to_owned();
+        dest.push("/");
+        res.push(dest);
+        res
+    }
+}
+
+
+//------------ Pending -------------------------------------------------------
+
+/// A fetch in progress.
+#[derive(Debug, Default)]
+struct Pending {
+    /// The result once the fetch has finished.
+    result: Mutex<Option<Result<(), RsyncError>>>,
+
+    /// Notifies waiting threads that the fetch has finished.
+    done: Condvar,
+}
+
+impl Pending {
+    /// Waits for the fetch to finish and returns its result.
+    fn wait(&self) -> Result<(), RsyncError> {
+        let mut result = self.result.lock().unwrap();
+        loop {
+            if let Some(ref res) = *result {
+                return res.clone()
+            }
+            result = self.done.wait(result).unwrap();
+        }
+    }
+
+    /// Sets the result of the fetch and wakes up all waiting threads.
+    fn finish(&self, res: Result<(), RsyncError>) {
+        *self.result.lock().unwrap() = Some(res);
+        self.done.notify_all();
+    }
+}
+
+
+//------------ Fetching ------------------------------------------------------
+
+/// A guard for a fetch run by the current thread.
+///
+/// When finished or dropped, the guard removes the fetch from the fetches
+/// in progress and hands the result to all waiting threads. If it is
+/// dropped without calling [`finish`], i.e., if running rsync panicked,
+/// the result is [`RsyncError::Aborted`].
+///
+/// [`finish`]: #method.finish
+/// [`RsyncError::Aborted`]: enum.RsyncError.html#variant.Aborted
+struct Fetching<'a> {
+    /// The fetches in progress of the runner.
+    running: &'a Mutex<HashMap<uri::RsyncModule, Arc<Pending>>>,
+
+    /// The module being fetched.
+    module: &'a uri::RsyncModule,
+
+    /// The fetch.
+    ///
+    /// This is `None` once the fetch has been finished.
+    pending: Option<Arc<Pending>>,
+}
+
+impl<'a> Fetching<'a> {
+    /// Finishes the fetch with the given result.
+    fn finish(mut self, res: Result<(), RsyncError>) {
+        self.finish_with(res)
+    }
+
+    /// Finishes the fetch unless that has happened already.
+    fn finish_with(&mut self, res: Result<(), RsyncError>) {
+        if let Some(pending) = self.pending.take() {
+            // We may be unwinding, so ignore a poisoned lock.
+            self.running.lock().unwrap_or_else(|err| {
+                err.into_inner()
+            }).remove(self.module);
+            pending.finish(res);
+        }
+    }
+}
+
+impl<'a> Drop for Fetching<'a> {
+    fn drop(&mut self) {
+        self.finish_with(Err(RsyncError::Aborted))
+    }
+}
+
+
+//------------ RsyncError ----------------------------------------------------
+
+/// Fetching an rsync module failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RsyncError {
+    /// The module’s authority or name cannot be used as a local path.
+    BadModule,
+
+    /// The local directory for the module could not be created.
+    Destination(String),
+
+    /// The rsync command could not be started.
+    Spawn(String),
+
+    /// The connection to the server could not be established.
+    ///
+    /// This covers rsync’s exit codes 5, 10, and 35.
+    Connect(String),
+
+    /// The server did not send data in time.
+    ///
+    /// This is rsync’s exit code 30.
+    Timeout(String),
+
+    /// Only some files could be transferred.
+    ///
+    /// This covers rsync’s exit codes 23 and 24.
+    Partial(String),
+
+    /// rsync failed for some other reason.
+    ///
+    /// Contains the exit code if rsync exited normally and its error
+    /// output.
+    Exit {
+        /// The exit code of the process.
+        code: Option<i32>,
+
+        /// The error output of the process.
+        stderr: String,
+    },
+
+    /// The fetch was aborted because running rsync panicked.
+    Aborted,
+}
+
+impl RsyncError {
+    /// Creates an error from rsync’s exit code and error output.
+    fn from_exit(code: Option<i32>, stderr: String) -> Self {
+        match code {
+            Some(5) | Some(10) | Some(35) => RsyncError::Connect(stderr),
+            Some(30) => RsyncError::Timeout(stderr),
+            Some(23) | Some(24) => RsyncError::Partial(stderr),
+            _ => RsyncError::Exit { code, stderr }
+        }
+    }
+}
+
+impl fmt::Display for RsyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RsyncError::BadModule => f.write_str("unsafe rsync module path"),
+            RsyncError::Destination(ref msg) => {
+                write!(f, "cannot create module directory: {}", msg)
+            }
+            RsyncError::Spawn(ref msg) => {
+                write!(f, "cannot run rsync: {}", msg)
+            }
+            RsyncError::Connect(ref msg) => {
+                write!(f, "rsync connection failed: {}", msg)
+            }
+            RsyncError::Timeout(ref msg) => {
+                write!(f, "rsync timed out: {}", msg)
+            }
+            RsyncError::Partial(ref msg) => {
+                write!(f, "rsync transfer incomplete: {}", msg)
+            }
+            RsyncError::Exit { code: Some(code), ref stderr } => {
+                write!(f, "rsync exited with code {}: {}", code, stderr)
+            }
+            RsyncError::Exit { code: None, ref stderr } => {
+                write!(f, "rsync terminated by signal: {}", stderr)
+            }
+            RsyncError::Aborted => f.write_str("rsync fetch aborted"),
+        }
+    }
+}
+
+impl error::Error for RsyncError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+    use bytes::Bytes;
+    use super::*;
+
+    fn module(
+        authority: &'static str, name: &'static str
+    ) -> uri::RsyncModule {
+        uri::RsyncModule::new(authority, name)
+    }
+
+    #[test]
+    fn paths() {
+        let runner = Runner::new("/var/rpki");
+        assert_eq!(
+            runner.module_path(&module("Example.NET", "repo")),
+            Some(PathBuf::from("/var/rpki/example.net/repo"))
+        );
+        assert_eq!(runner.module_path(&module("example.net", "..")), None);
+        assert_eq!(runner.module_path(&module("..", "repo")), None);
+        assert_eq!(runner.module_path(&module("example.net", "")), None);
+        assert_eq!(
+            runner.uri_path(
+                &uri::Rsync::from_str("rsync://example.net/repo/a/b.roa")
+                    .unwrap()
+            ),
+            Some(PathBuf::from("/var/rpki/example.net/repo/a/b.roa"))
+        );
+        assert_eq!(
+            runner.uri_path(
+                &uri::Rsync::from_str("rsync://example.net/repo/a/")
+                    .unwrap()
+            ),
+            Some(PathBuf::from("/var/rpki/example.net/repo/a"))
+        );
+        assert_eq!(
+            runner.uri_path(&uri::Rsync::new(
+                module("example.net", "repo"),
+                Bytes::from_static(b"a/../../x.roa")
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn args() {
+        let mut runner = Runner::new("/var/rpki");
+        runner.set_timeout(Duration::from_secs(0));
+        runner.set_args(vec!["--max-size=20M"]);
+        let module = module("example.net", "repo");
+        let path = runner.module_path(&module).unwrap();
+        assert_eq!(
+            runner.args(&module, &path),
+            [
+                "-rltz", "--no-motd", "--delete", "--contimeout=10",
+                "--max-size=20M", "rsync://example.net/repo/",
+                "/var/rpki/example.net/repo/"
+            ]
+        );
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(
+            RsyncError::from_exit(Some(35), "".into()),
+            RsyncError::Connect("".into())
+        );
+        assert_eq!(
+            RsyncError::from_exit(Some(30), "".into()),
+            RsyncError::Timeout("".into())
+        );
+        assert_eq!(
+            RsyncError::from_exit(Some(23), "".into()),
+            RsyncError::Partial("".into())
+        );
+        assert_eq!(
+            RsyncError::from_exit(Some(1), "syntax".into()),
+            RsyncError::Exit { code: Some(1), stderr: "syntax".into() }
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn fetch() {
+        let base = std::env::temp_dir().join(
+            format!("rpki-rsync-test-{}", std::process::id())
+        );
+        let mut runner = Runner::new(&base);
+        runner.set_command("true");
+        assert_eq!(
+            runner.fetch(&module("example.net", "repo")),
+            Ok(base.join("example.net/repo"))
+        );
+        assert!(base.join("example.net/repo").is_dir());
+        runner.set_command("false");
+        assert_eq!(
+            runner.fetch(&module("example.net", "repo")),
+            Err(RsyncError::Exit { code: Some(1), stderr: "".into() })
+        );
+        assert_eq!(
+            runner.fetch(&module("example.net", "..")),
+            Err(RsyncError::BadModule)
+        );
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn aborted_fetch() {
+        let runner = Runner::new("/var/rpki");
+        let module = module("example.net", "repo");
+        let pending = Arc::new(Pending::default());
+        runner.running.lock().unwrap().insert(
+            module.clone(), pending.clone()
+        );
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _fetching = Fetching {
+                running: &runner.running, module: &module,
+                pending: Some(pending.clone())
+            };
+            panic!("rsync panicked");
+        }));
+        assert!(res.is_err());
+        assert!(runner.running.lock().unwrap().is_empty());
+        assert_eq!(pending.wait(), Err(RsyncError::Aborted));
+    }
+}
@@ -0,0 +1,710 @@
+//! The router side of the RPKI-to-Router protocol.
+//!
+//! The [`Client`] connects to a cache, requests its data, and keeps a local
+//! copy of the data set. It is intended for monitoring caches and testing
+//! rather than for use in actual routers.
+//!
+//! The data can be requested explicitly via [`Client::update`]. Between
+//! updates, [`Client::wait`] waits until the cache announces new data or
+//! the refresh interval has passed. Alternatively, the client can be
+//! turned into a stream of [`Update`]s via [`Client::into_updates`] which
+//! does all this in a task of its own.
+//!
+//! [`Client`]: struct.Client.html
+//! [`Client::update`]: struct.Client.html#method.update
+//! [`Client::wait`]: struct.Client.html#method.wait
+//! [`Client::into_updates`]: struct.Client.html#method.into_updates
+//! [`Update`]: struct.Update.html
+
+use std::{error, fmt, io};
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use bytes::Bytes;
+use futures_core::Stream;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader
+};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout};
+use super::payload::{Action, Payload, Timing};
+use super::pdu::{read_raw, ErrorCode, Pdu, MAX_VERSION};
+use super::state::{Serial, State};
+
+
+//------------ Client --------------------------------------------------------
+
+/// An RTR client.
+pub struct Client<Sock> {
+    /// The socket to the cache.
+    sock: BufReader<Sock>,
+
+    /// The protocol version used by the client.
+    version: u8,
+
+    /// Whether the protocol version has been agreed upon with the cache.
+    negotiated: bool,
+
+    /// The state of the local data set if there is one.
+    state: Option<State>,
+
+    /// The timing parameters last received from the cache.
+    timing: Timing,
+
+    /// The local data set.
+    payload: Arc<HashSet<Payload>>,
+
+    /// The octets of the PDU last received.
+    ///
+    /// These are included in error reports sent to the cache.
+    last: Bytes,
+}
+
+impl Client<TcpStream> {
+    /// Connects to a cache via TCP.
+    pub async fn connect<A: ToSocketAddrs>(
+        addr: A
+    ) -> Result<Self, io::Error> {
+        TcpStream::connect(addr).await.map(Self::new)
+    }
+}
+
+impl<Sock: AsyncRead> Client<Sock> {
+    /// Creates a new client using the highest supported protocol version.
+    pub fn new(sock: Sock) -> Self {
+        Self::with_version(sock, MAX_VERSION)
+    }
+
+    /// Creates a new client using the given protocol version.
+    ///
+    /// # Panics
+    ///
+    /// The function panics if `version` is larger than the highest
+    /// supported protocol version.
+    pub fn with_version(sock: Sock, version: u8) -> Self {
+        assert!(version <= MAX_VERSION);
+        Client {
+            sock: BufReader::new(sock),
+            version,
+            negotiated: false,
+            state: None,
+            timing: Timing::default(),
+            payload: Arc::new(HashSet::new()),
+            last: Bytes::new(),
+        }
+    }
+}
+
+impl<Sock> Client<Sock> {
+    /// Continues with a new connection to the cache.
+    ///
+    /// The client keeps its data set, so the next update will attempt a
+    /// serial query, and the protocol version which may have been lowered
+    /// when the cache didn’t support the previous one.
+    pub fn reconnect<T: AsyncRead>(self, sock: T) -> Client<T> {
+        Client {
+            sock: BufReader::new(sock),
+            version: self.version,
+            negotiated: false,
+            state: self.state,
+            timing: self.timing,
+            payload: self.payload,
+            last: Bytes::new(),
+        }
+    }
+
+    /// Returns the protocol version used by the client.
+    ///
+    /// If the cache doesn’t support the version the client was created
+    /// with, it will answer the first query with an error report in the
+    /// highest version it does support. The client then lowers its version
+    /// to that version and returns the error. Because error reports end
+    /// the session, a new connection needs to be established and used via
+    /// [`reconnect`].
+    ///
+    /// [`reconnect`]: #method.reconnect
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Returns the state of the local data set.
+    ///
+    /// Returns `None` if the client hasn’t received any data yet.
+    pub fn state(&self) -> Option<State> {
+        self.state
+    }
+
+    /// Returns the timing parameters last received from the cache.
+    ///
+    /// Until the cache has sent timing parameters, returns the defaults.
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+
+    /// Returns the local data set.
+    pub fn payload(&self) -> &HashSet<Payload> {
+        &self.payload
+    }
+}
+
+impl<Sock: AsyncRead + AsyncWrite + Unpin> Client<Sock> {
+    /// Updates the local data set.
+    ///
+    /// If the client already has data, sends a serial query and applies
+    /// the changes received. If it hasn’t or the cache isn’t able to
+    /// provide the changes, sends a reset query and replaces the data set.
+    ///
+    /// If the cache violates the protocol, an error report is sent to it
+    /// and the local data set is discarded. After any error other than
+    /// the cache reporting that it has no data yet, the client should not
+    /// be used any more.
+    pub async fn update(&mut self) -> Result<Update, Error> {
+        if let Some(state) = self.state {
+            if let Some(update) = self.serial(state).await? {
+                return Ok(update)
+            }
+        }
+        self.reset().await
+    }
+
+    /// Replaces the local data set with the complete data of the cache.
+    pub async fn reset(&mut self) -> Result<Update, Error> {
+        self.send(&Pdu::ResetQuery).await?;
+        let session = match self.cache_response().await? {
+            Some(session) => session,
+            None => {
+                return Err(self.unexpected("unexpected cache reset").await)
+            }
+        };
+        let mut payload = HashSet::new();
+        let state = loop {
+            match self.recv().await? {
+                Pdu::Payload { action: Action::Announce, payload: item } => {
+                    if !apply(&mut payload, Action::Announce, item) {
+                        return Err(self.corrupt(
+                            ErrorCode::DuplicateAnnouncement,
+                            "duplicate announcement"
+                        ).await)
+                    }
+                }
+                Pdu::Payload { action: Action::Withdraw, .. } => {
+                    return Err(self.corrupt(
+                        ErrorCode::WithdrawalOfUnknownRecord,
+                        "withdrawal in reset response"
+                    ).await)
+                }
+                Pdu::EndOfData { session: eod, serial, timing } => {
+                    break self.end_of_data(session, eod, serial, timing).await?
+                }
+                _ => return Err(self.unexpected("unexpected PDU").await)
+            }
+        };
+
+        let mut changes: Vec<_> = self.payload.difference(&payload).map(
+            |item| (Action::Withdraw, item.clone())
+        ).collect();
+        changes.extend(payload.difference(&self.payload).map(
+            |item| (Action::Announce, item.clone())
+        ));
+        self.payload = Arc::new(payload);
+        Ok(self.finish(state, true, changes))
+    }
+
+    /// Waits until new data should be requested.
+    ///
+    /// Returns when the cache announces new data via a Serial Notify PDU
+    /// or when the refresh interval received from the cache has passed.
+    pub async fn wait(&mut self) -> Result<(), Error> {
+        let refresh = Duration::from_secs(self.timing.refresh.into());
+        // Waiting for the buffer to fill is cancel safe: whatever arrives
+        // stays in the buffer.
+        match timeout(refresh, self.sock.fill_buf()).await {
+            Ok(Ok(_)) => { }
+            Ok(Err(err)) => return Err(err.into()),
+            Err(_) => return Ok(())
+        }
+        match self.recv().await? {
+            Pdu::SerialNotify { .. } => Ok(()),
+            _ => Err(self.unexpected("unexpected PDU").await)
+        }
+    }
+
+    /// Applies the changes since the given state.
+    ///
+    /// Returns `Ok(None)` if the cache can’t provide the changes.
+    async fn serial(
+        &mut self, state: State
+    ) -> Result<Option<Update>, Error> {
+        self.send(&Pdu::SerialQuery {
+            session: state.session(), serial: state.serial()
+        }).await?;
+        let session = match self.cache_response().await? {
+            Some(session) => session,
+            None => return Ok(None)
+        };
+        if session != state.session() {
+            return Err(self.corrupt(
+                ErrorCode::CorruptData, "session ID changed"
+            ).await)
+        }
+        let mut changes = Vec::new();
+        let state = loop {
+            match self.recv().await? {
+                Pdu::Payload { action, payload } => {
+                    let set = Arc::make_mut(&mut self.payload);
+                    if !apply(set, action, payload.clone()) {
+                        let (code, text) = if action.is_announce() {
+                            (
+                                ErrorCode::DuplicateAnnouncement,
+                                "duplicate announcement"
+                            )
+                        }
+                        else {
+                            (
+                                ErrorCode::WithdrawalOfUnknownRecord,
+                                "withdrawal of unknown record"
+                            )
+                        };
+                        return Err(self.corrupt(code, text).await)
+                    }
+                    changes.push((action, payload));
+                }
+                Pdu::EndOfData { session: eod, serial, timing } => {
+                    break self.end_of_data(session, eod, serial, timing).await?
+                }
+                _ => return Err(self.unexpected("unexpected PDU").await)
+            }
+        };
+        Ok(Some(self.finish(state, false, changes)))
+    }
+
+    /// Receives the response to a query.
+    ///
+    /// Returns the session ID of a Cache Response PDU or `None` for a Cache
+    /// Reset PDU. Serial Notify PDUs received in the meantime are ignored.
+    async fn cache_response(&mut self) -> Result<Option<u16>, Error> {
+        loop {
+            match self.recv().await? {
+                Pdu::CacheResponse { session } => return Ok(Some(session)),
+                Pdu::CacheReset => return Ok(None),
+                Pdu::SerialNotify { .. } => { }
+                _ => return Err(self.unexpected("unexpected PDU").await)
+            }
+        }
+    }
+
+    /// Processes an End of Data PDU.
+    async fn end_of_data(
+        &mut self,
+        session: u16,
+        eod: u16,
+        serial: Serial,
+        timing: Option<Timing>
+    ) -> Result<State, Error> {
+        if session != eod {
+            return Err(self.corrupt(
+                ErrorCode::CorruptData, "session ID changed"
+            ).await)
+        }
+        if let Some(timing) = timing {
+            self.timing = timing
+        }
+        Ok(State::from_parts(session, serial))
+    }
+
+    /// Finishes an update.
+    fn finish(
+        &mut self,
+        state: State,
+        reset: bool,
+        changes: Vec<(Action, Payload)>
+    ) -> Update {
+        self.state = Some(state);
+        Update { state, reset, changes, payload: self.payload.clone() }
+    }
+
+    /// Receives a PDU.
+    ///
+    /// Error reports are turned into errors.
+    async fn recv(&mut self) -> Result<Pdu, Error> {
+        self.last = match read_raw(&mut self.sock).await? {
+            Some(data) => data,
+            None => return Err(Error::Closed)
+        };
+        let (version, pdu) = match Pdu::decode(&self.last) {
+            Ok(some) => some,
+            Err(err) => return Err(self.report(err.code(), err.text()).await)
+        };
+        if version != self.version {
+            if self.negotiated || version > self.version {
+                return Err(self.report(
+                    ErrorCode::UnexpectedProtocolVersion,
+                    "unexpected protocol version"
+                ).await)
+            }
+            // The cache doesn’t support our version and answered with
+            // the highest version it does support.
+            self.version = version;
+        }
+        match pdu {
+            Pdu::ErrorReport { code, text, .. } => {
+                Err(Error::Report { code, text })
+            }
+            pdu => {
+                self.negotiated = true;
+                Ok(pdu)
+            }
+        }
+    }
+
+    /// Reports an unexpected PDU to the cache.
+    async fn unexpected(&mut self, text: &'static str) -> Error {
+        self.corrupt(ErrorCode::CorruptData, text).await
+    }
+
+    /// Discards the local data and reports an error to the cache.
+    async fn corrupt(&mut self, code: ErrorCode, text: &'static str) -> Error {
+        self.state = None;
+        self.payload = Arc::new(HashSet::new());
+        self.report(code, text).await
+    }
+
+    /// Sends an error report for the last PDU to the cache.
+    async fn report(&mut self, code: ErrorCode, text: &'static str) -> Error {
+        let pdu = Pdu::ErrorReport {
+            code, pdu: self.last.clone(), text: text.into()
+        };
+        match self.send(&pdu).await {
+            Ok(()) => Error::Protocol { code, text },
+            Err(err) => err.into()
+        }
+    }
+
+    /// Sends a PDU to the cache.
+    async fn send(&mut self, pdu: &Pdu) -> Result<(), io::Error> {
+        self.sock.write_all(&pdu.to_vec(self.version)).await?;
+        self.sock.flush().await
+    }
+}
+
+impl<Sock> Client<Sock>
+where Sock: AsyncRead + AsyncWrite + Send + Unpin + 'static {
+    /// Converts the client into a stream of updates.
+    ///
+    /// The client is moved into a newly spawned task that updates the data
+    /// whenever [`wait`] returns. If the cache has no data yet, it retries
+    /// after the retry interval. The stream ends after the first error
+    /// other than that.
+    ///
+    /// This must be called within a Tokio runtime.
+    ///
+    /// [`wait`]: #method.wait
+    pub fn into_updates(self) -> Updates {
+        let (tx, rx) = mpsc::channel(1);
+        let task = tokio::spawn(self.run_updates(tx));
+        Updates { rx, task }
+    }
+
+    /// Runs the task behind the updates stream.
+    async fn run_updates(mut self, tx: mpsc::Sender<Result<Update, Error>>) {
+        loop {
+            let res = self.update().await;
+            let retry = match res {
+                Ok(_) => false,
+                Err(ref err) if !err.is_fatal() => true,
+                Err(_) => {
+                    let _ = tx.send(res).await;
+                    return
+                }
+            };
+            if tx.send(res).await.is_err() {
+                return
+            }
+            if retry {
+                sleep(Duration::from_secs(self.timing.retry.into())).await;
+            }
+            else if let Err(err) = self.wait().await {
+                let _ = tx.send(Err(err)).await;
+                return
+            }
+        }
+    }
+}
+
+
+//------------ apply ---------------------------------------------------------
+
+/// Applies a change to a data set.
+///
+/// Announcing an ASPA replaces an ASPA for the same customer AS. Returns
+/// `false` if the change is a duplicate announcement or a withdrawal of an
+/// unknown item.
+fn apply(set: &mut HashSet<Payload>, action: Action, item: Payload) -> bool {
+    let existing = match item {
+        Payload::Aspa(_) => {
+            set.iter().find(|other| item.same_item(other)).cloned()
+        }
+        _ => None
+    };
+    match (action, existing) {
+        (Action::Announce, Some(existing)) => {
+            set.remove(&existing);
+            set.insert(item)
+        }
+        (Action::Announce, None) => set.insert(item),
+        (Action::Withdraw, Some(existing)) => set.remove(&existing),
+        (Action::Withdraw, None) => set.remove(&item),
+    }
+}
+
+
+//------------ Update --------------------------------------------------------
+
+/// The result of updating the local data set.
+#[derive(Clone, Debug)]
+pub struct Update {
+    /// The state of the data set after the update.
+    state: State,
+
+    /// Whether the data set was replaced entirely.
+    reset: bool,
+
+    /// The changes to the data set.
+    changes: Vec<(Action, Payload)>,
+
+    /// The data set after the update.
+    payload: Arc<HashSet<Payload>>,
+}
+
+impl Update {
+    /// Returns the state of the data set after the update.
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    /// Returns whether the data set was replaced via a reset query.
+    pub fn is_reset(&self) -> bool {
+        self.reset
+    }
+
+    /// Returns the changes to the data set.
+    ///
+    /// For a reset, these are determined by comparing the old and new data
+    /// sets, so they are always relative to the previous data.
+    pub fn changes(&self) -> &[(Action, Payload)] {
+        &self.changes
+    }
+
+    /// Returns the data set after the update.
+    pub fn payload(&self) -> &HashSet<Payload> {
+        &self.payload
+    }
+}
+
+
+//------------ Updates -------------------------------------------------------
+
+/// A stream of updates of a client’s data set.
+///
+/// Dropping the stream stops the task running the client.
+#[derive(Debug)]
+pub struct Updates {
+    /// The receiving end of the updates.
+    rx: mpsc::Receiver<Result<Update, Error>>,
+
+    /// The task running the client.
+    task: JoinHandle<()>,
+}
+
+impl Updates {
+    /// Returns the next update.
+    ///
+    /// Returns `None` once the stream has ended.
+    pub async fn next(&mut self) -> Option<Result<Update, Error>> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for Updates {
+    type Item = Result<Update, Error>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>, cx: &mut Context
+    ) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for Updates {
+    fn drop(&mut self) {
+        self.task.abort()
+    }
+}
+
+
+//------------ Error ---------------------------------------------------------
+
+/// An error happened while talking to a cache.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading from or writing to the socket failed.
+    Io(io::Error),
+
+    /// The cache closed the connection.
+    Closed,
+
+    /// The cache sent an error report.
+    Report {
+        /// The error code of the report.
+        code: ErrorCode,
+
+        /// The diagnostic text of the report.
+        text: String,
+    },
+
+    /// The cache violated the protocol.
+    ///
+    /// An error report with the code and text has been sent to the cache.
+    Protocol {
+        /// The error code sent to the cache.
+        code: ErrorCode,
+
+        /// The diagnostic text sent to the cache.
+        text: &'static str,
+    },
+}
+
+impl Error {
+    /// Returns whether the error ends the session.
+    ///
+    /// The only non-fatal error is the cache reporting that it has no data
+    /// yet.
+    pub fn is_fatal(&self) -> bool {
+        match *self {
+            Error::Report { code, .. } => code.is_fatal(),
+            _ => true
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::Closed => f.write_str("connection closed by cache"),
+            Error::Report { code, ref text } => {
+                write!(f, "cache reported error: {}: {}", code, text)
+            }
+            Error::Protocol { code, text } => {
+                write!(f, "protocol error: {}: {}", code, text)
+            }
+        }
+    }
+}
+
+impl error::Error for Error { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use crate::rtr::server::{serve, NotifySender};
+    use crate::rtr::server::test::{origin, Source};
+    use super::*;
+
+    #[test]
+    fn client_server() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all().build().unwrap();
+        rt.block_on(async {
+            let source = Source::new();
+            let notify = NotifySender::new();
+            let (client, server) = tokio::io::duplex(4096);
+            let _server = tokio::spawn(
+                serve(server, source.clone(), notify.subscribe())
+            );
+            let mut client = Client::new(client);
+
+            // No data yet.
+            match client.update().await {
+                Err(err) => assert!(!err.is_fatal()),
+                Ok(_) => panic!("unexpected update")
+            }
+            assert_eq!(client.state(), None);
+
+            source.push(64496);
+            let update = client.update().await.unwrap();
+            assert!(update.is_reset());
+            assert_eq!(update.state(), State::from_parts(12, Serial(1)));
+            assert_eq!(
+                update.changes(), &[(Action::Announce, origin(64496))][..]
+            );
+
+            let mut updates = client.into_updates();
+            let update = updates.next().await.unwrap().unwrap();
+            assert!(update.is_reset());
+            assert!(update.changes().is_empty());
+
+            source.push(64497);
+            notify.notify();
+            let update = updates.next().await.unwrap().unwrap();
+            assert!(!update.is_reset());
+            assert_eq!(update.state().serial(), Serial(2));
+            assert_eq!(
+                update.changes(), &[(Action::Announce, origin(64497))][..]
+            );
+            assert_eq!(update.payload().len(), 2);
+        });
+    }
+
+    #[test]
+    fn downgrade() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all().build().unwrap();
+        rt.block_on(async {
+            // A cache only speaking version 1 rejects our query.
+            let (client, mut cache) = tokio::io::duplex(4096);
+            let mut client = Client::new(client);
+            let query = tokio::spawn(async move {
+                let data = read_raw(&mut cache).await.unwrap().unwrap();
+                assert_eq!(data[0], 2);
+                cache.write_all(&Pdu::ErrorReport {
+                    code: ErrorCode::UnsupportedProtocolVersion,
+                    pdu: data,
+                    text: String::new()
+                }.to_vec(1)).await.unwrap();
+            });
+            match client.update().await {
+                Err(Error::Report { code, .. }) => {
+                    assert_eq!(code, ErrorCode::UnsupportedProtocolVersion)
+                }
+                res => panic!("unexpected {:?}", res)
+            }
+            query.await.unwrap();
+            assert_eq!(client.version(), 1);
+
+            // The next connection uses version 1.
+            let source = Source::new();
+            source.push(64496);
+            let (sock, server) = tokio::io::duplex(4096);
+            let _server = tokio::spawn(
+                serve(server, source, NotifySender::new().subscribe())
+            );
+            let mut client = client.reconnect(sock);
+            let update = client.update().await.unwrap();
+            assert_eq!(client.version(), 1);
+            assert_eq!(update.payload().len(), 1);
+        });
+    }
+}
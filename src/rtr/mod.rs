@@ -0,0 +1,32 @@
+//! The RPKI-to-Router protocol.
+//!
+//! The RPKI-to-Router protocol (RTR) defined in [RFC 8210] is used by
+//! relying party software, called a cache in RTR, to hand validated RPKI
+//! data to routers. This module implements the cache side of the
+//! protocol atop Tokio as well as a client for monitoring and testing.
+//!
+//! The sub-module [`pdu`] implements encoding and decoding of the protocol
+//! data units, [`payload`] defines the data exchanged, and [`state`] the
+//! session ID and serial number identifying a version of the data. The
+//! server itself is provided by [`server`] and the client by [`client`].
+//!
+//! The module is only available if the `rtr` feature is enabled.
+//!
+//! [`pdu`]: pdu/index.html
+//! [`payload`]: payload/index.html
+//! [`state`]: state/index.html
+//! [`server`]: server/index.html
+//! [`client`]: client/index.html
+//! [RFC 8210]: https://tools.ietf.org/html/rfc8210
+
+pub use self::client::{Client, Update};
+pub use self::payload::{Action, Aspa, Payload, RouteOrigin, Timing};
+pub use self::pdu::Pdu;
+pub use self::server::{NotifySender, Server, VrpSource};
+pub use self::state::{Serial, State};
+
+pub mod client;
+pub mod payload;
+pub mod pdu;
+pub mod server;
+pub mod state;
@@ -0,0 +1,75 @@
+//! The data transported via the RPKI-to-Router protocol.
+//!
+//! Relying party software hands its validated data to routers as a set of
+//! payload items. The types for these items are defined in the crate’s
+//! [`payload`] module and re-exported here. This module adds the types for
+//! the actions performed on them when sending differences and for the
+//! timing parameters of a session.
+//!
+//! [`payload`]: ../../payload/index.html
+
+pub use crate::payload::{Aspa, Payload, Prefix, RouteOrigin, RouterKey};
+
+
+//------------ Action --------------------------------------------------------
+
+/// What to do with a payload item.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Action {
+    /// The item is added to the set.
+    Announce,
+
+    /// The item is removed from the set.
+    Withdraw,
+}
+
+impl Action {
+    /// Returns whether the action is to announce an item.
+    pub fn is_announce(self) -> bool {
+        self == Action::Announce
+    }
+
+    /// Returns the action encoded in the flags field of a PDU.
+    pub fn from_flags(flags: u8) -> Self {
+        if flags & 1 == 1 {
+            Action::Announce
+        }
+        else {
+            Action::Withdraw
+        }
+    }
+
+    /// Returns the value of the flags field of a PDU for the action.
+    pub fn into_flags(self) -> u8 {
+        match self {
+            Action::Announce => 1,
+            Action::Withdraw => 0,
+        }
+    }
+}
+
+
+//------------ Timing --------------------------------------------------------
+
+/// The timing parameters of a data exchange.
+///
+/// These values are sent by the cache to the router as part of the End of
+/// Data PDU in protocol version 1 and later. All values are in seconds.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Timing {
+    /// The number of seconds until the router should query for new data.
+    pub refresh: u32,
+
+    /// The number of seconds to wait before retrying a failed query.
+    pub retry: u32,
+
+    /// The number of seconds after which data expires without a refresh.
+    pub expire: u32,
+}
+
+impl Default for Timing {
+    /// Returns the default values recommended in section 6 of RFC 8210.
+    fn default() -> Self {
+        Timing { refresh: 3600, retry: 600, expire: 7200 }
+    }
+}
@@ -0,0 +1,760 @@
+//! The protocol data units of the RPKI-to-Router protocol.
+//!
+//! This module implements encoding and decoding of the PDUs defined in
+//! [RFC 8210] for protocol version 1, those of version 0 defined in
+//! [RFC 6810], as well as version 2 which adds the ASPA PDU and is
+//! currently being defined in [draft-ietf-sidrops-8210bis].
+//!
+//! All PDUs start with a common eight octet header that contains the
+//! protocol version, the PDU type, a type-specific 16 bit field, and the
+//! length of the entire PDU. Reading a PDU from a stream therefore happens
+//! in two steps: [`read_raw`] reads the octets of a single PDU, and
+//! [`Pdu::decode`] then decodes them. This allows the octets of a broken
+//! PDU to be returned to the sender as part of an error report.
+//!
+//! [`read_raw`]: fn.read_raw.html
+//! [`Pdu::decode`]: enum.Pdu.html#method.decode
+//! [RFC 6810]: https://tools.ietf.org/html/rfc6810
+//! [RFC 8210]: https://tools.ietf.org/html/rfc8210
+//! [draft-ietf-sidrops-8210bis]: https://datatracker.ietf.org/doc/draft-ietf-sidrops-8210bis/
+
+use std::{error, fmt, io};
+use std::convert::TryFrom;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use bytes::Bytes;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use crate::cert::RouterKey;
+use crate::crypto::KeyIdentifier;
+use super::payload::{
+    Action, Aspa, Payload, Prefix, RouteOrigin, Timing
+};
+use super::state::Serial;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// The highest protocol version supported.
+pub const MAX_VERSION: u8 = 2;
+
+/// The maximum length of a PDU accepted by `read_raw`.
+pub const MAX_PDU_LEN: u32 = 0xFFFF;
+
+/// The length of the PDU header.
+const HEADER_LEN: usize = 8;
+
+
+//------------ Pdu -----------------------------------------------------------
+
+/// A single RTR protocol data unit.
+///
+/// The protocol version isn’t part of the PDU but needs to be given when
+/// encoding and is returned separately when decoding.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Pdu {
+    /// The cache has new data.
+    SerialNotify {
+        /// The session ID of the cache.
+        session: u16,
+
+        /// The serial number of the new data.
+        serial: Serial,
+    },
+
+    /// The router asks for the changes since the given serial number.
+    SerialQuery {
+        /// The session ID of the router’s data.
+        session: u16,
+
+        /// The serial number of the router’s data.
+        serial: Serial,
+    },
+
+    /// The router asks for the complete data set.
+    ResetQuery,
+
+    /// The cache starts sending data.
+    CacheResponse {
+        /// The session ID of the cache.
+        session: u16,
+    },
+
+    /// A payload item is announced or withdrawn.
+    ///
+    /// Route origins are encoded as IPv4 or IPv6 Prefix PDUs, router keys
+    /// as Router Key PDUs, and ASPAs as ASPA PDUs. An ASPA is withdrawn
+    /// by its customer AS only, so the providers of a withdrawn ASPA are
+    /// not encoded and are empty when decoded.
+    Payload {
+        /// Whether the item is announced or withdrawn.
+        action: Action,
+
+        /// The payload item.
+        payload: Payload,
+    },
+
+    /// The cache has finished sending data.
+    EndOfData {
+        /// The session ID of the cache.
+        session: u16,
+
+        /// The serial number of the data just sent.
+        serial: Serial,
+
+        /// The timing parameters.
+        ///
+        /// These are only present in protocol version 1 and later. When
+        /// encoding for these versions, the default timing is used if
+        /// the field is `None`.
+        timing: Option<Timing>,
+    },
+
+    /// The cache cannot provide the changes requested.
+    CacheReset,
+
+    /// An error has occurred.
+    ErrorReport {
+        /// The error code.
+        code: ErrorCode,
+
+        /// The erroneous PDU, if any.
+        pdu: Bytes,
+
+        /// A diagnostic message, possibly empty.
+        text: String,
+    },
+}
+
+impl Pdu {
+    /// Creates an error report for a decoding error.
+    pub fn error_report(err: DecodeError, pdu: Bytes) -> Self {
+        Pdu::ErrorReport {
+            code: err.code(),
+            pdu,
+            text: err.text().into(),
+        }
+    }
+
+    /// Returns the PDU type.
+    pub fn pdu_type(&self) -> u8 {
+        match *self {
+            Pdu::SerialNotify { .. } => 0,
+            Pdu::SerialQuery { .. } => 1,
+            Pdu::ResetQuery => 2,
+            Pdu::CacheResponse { .. } => 3,
+            Pdu::Payload { ref payload, .. } => match *payload {
+                Payload::Origin(origin) if origin.is_v4() => 4,
+                Payload::Origin(_) => 6,
+                Payload::RouterKey(_) => 9,
+                Payload::Aspa(_) => 11,
+            }
+            Pdu::EndOfData { .. } => 7,
+            Pdu::CacheReset => 8,
+            Pdu::ErrorReport { .. } => 10,
+        }
+    }
+
+    /// Appends the encoded PDU for the given version to `target`.
+    pub fn encode(&self, version: u8, target: &mut Vec<u8>) {
+        let start = target.len();
+        target.push(version);
+        target.push(self.pdu_type());
+        match *self {
+            Pdu::SerialNotify { session, serial }
+            | Pdu::SerialQuery { session, serial } => {
+                target.extend_from_slice(&session.to_be_bytes());
+                target.extend_from_slice(&[0; 4]);
+                target.extend_from_slice(&serial.0.to_be_bytes());
+            }
+            Pdu::ResetQuery | Pdu::CacheReset => {
+                target.extend_from_slice(&[0; 6]);
+            }
+            Pdu::CacheResponse { session } => {
+                target.extend_from_slice(&session.to_be_bytes());
+                target.extend_from_slice(&[0; 4]);
+            }
+            Pdu::Payload { action, ref payload } => match *payload {
+                Payload::Origin(origin) => {
+                    target.extend_from_slice(&[0; 6]);
+                    target.push(action.into_flags());
+                    target.push(origin.prefix().prefix_len());
+                    target.push(origin.max_len());
+                    target.push(0);
+                    match origin.prefix().addr() {
+                        IpAddr::V4(addr) => {
+                            target.extend_from_slice(&addr.octets())
+                        }
+                        IpAddr::V6(addr) => {
+                            target.extend_from_slice(&addr.octets())
+                        }
+                    }
+                    target.extend_from_slice(
+                        &u32::from(origin.asn()).to_be_bytes()
+                    );
+                }
+                Payload::RouterKey(ref key) => {
+                    target.push(action.into_flags());
+                    target.extend_from_slice(&[0; 5]);
+                    target.extend_from_slice(key.key_identifier().as_ref());
+                    target.extend_from_slice(
+                        &u32::from(key.asn()).to_be_bytes()
+                    );
+                    target.extend_from_slice(key.key_info());
+                }
+                Payload::Aspa(ref aspa) => {
+                    target.push(action.into_flags());
+                    target.extend_from_slice(&[0; 5]);
+                    target.extend_from_slice(
+                        &u32::from(aspa.customer()).to_be_bytes()
+                    );
+                    if action.is_announce() {
+                        for provider in aspa.providers() {
+                            target.extend_from_slice(
+                                &u32::from(*provider).to_be_bytes()
+                            );
+                        }
+                    }
+                }
+            }
+            Pdu::EndOfData { session, serial, timing } => {
+                target.extend_from_slice(&session.to_be_bytes());
+                target.extend_from_slice(&[0; 4]);
+                target.extend_from_slice(&serial.0.to_be_bytes());
+                if version > 0 {
+                    let timing = timing.unwrap_or_default();
+                    target.extend_from_slice(&timing.refresh.to_be_bytes());
+                    target.extend_from_slice(&timing.retry.to_be_bytes());
+                    target.extend_from_slice(&timing.expire.to_be_bytes());
+                }
+            }
+            Pdu::ErrorReport { code, ref pdu, ref text } => {
+                target.extend_from_slice(&code.to_u16().to_be_bytes());
+                target.extend_from_slice(&[0; 4]);
+                target.extend_from_slice(&(pdu.len() as u32).to_be_bytes());
+                target.extend_from_slice(pdu);
+                target.extend_from_slice(
+                    &(text.len() as u32).to_be_bytes()
+                );
+                target.extend_from_slice(text.as_bytes());
+            }
+        }
+        let len = ((target.len() - start) as u32).to_be_bytes();
+        target[start + 4..start + 8].copy_from_slice(&len);
+    }
+
+    /// Returns the encoded PDU for the given version.
+    pub fn to_vec(&self, version: u8) -> Vec<u8> {
+        let mut res = Vec::new();
+        self.encode(version, &mut res);
+        res
+    }
+
+    /// Decodes a PDU from the octets of a single PDU.
+    ///
+    /// Returns the protocol version and the PDU. The error returned if
+    /// decoding fails contains the error code to use in an error report.
+    pub fn decode(data: &[u8]) -> Result<(u8, Self), DecodeError> {
+        if data.len() < HEADER_LEN || read_u32(data, 4) as usize != data.len()
+        {
+            return Err(DecodeError::CORRUPT_LENGTH)
+        }
+        let version = data[0];
+        if version > MAX_VERSION {
+            return Err(DecodeError::UNSUPPORTED_VERSION)
+        }
+        let field = read_u16(data, 2);
+        let pdu = match data[1] {
+            0 => {
+                check_len(data, 12)?;
+                Pdu::SerialNotify {
+                    session: field, serial: Serial(read_u32(data, 8))
+                }
+            }
+            1 => {
+                check_len(data, 12)?;
+                Pdu::SerialQuery {
+                    session: field, serial: Serial(read_u32(data, 8))
+                }
+            }
+            2 => {
+                check_len(data, 8)?;
+                Pdu::ResetQuery
+            }
+            3 => {
+                check_len(data, 8)?;
+                Pdu::CacheResponse { session: field }
+            }
+            4 => {
+                check_len(data, 20)?;
+                let mut addr = [0; 4];
+                addr.copy_from_slice(&data[12..16]);
+                Self::decode_origin(data, Ipv4Addr::from(addr).into(), 16)?
+            }
+            6 => {
+                check_len(data, 32)?;
+                let mut addr = [0; 16];
+                addr.copy_from_slice(&data[12..28]);
+                Self::decode_origin(data, Ipv6Addr::from(addr).into(), 28)?
+            }
+            7 => {
+                if version == 0 {
+                    check_len(data, 12)?;
+                    Pdu::EndOfData {
+                        session: field,
+                        serial: Serial(read_u32(data, 8)),
+                        timing: None,
+                    }
+                }
+                else {
+                    check_len(data, 24)?;
+                    Pdu::EndOfData {
+                        session: field,
+                        serial: Serial(read_u32(data, 8)),
+                        timing: Some(Timing {
+                            refresh: read_u32(data, 12),
+                            retry: read_u32(data, 16),
+                            expire: read_u32(data, 20),
+                        })
+                    }
+                }
+            }
+            8 => {
+                check_len(data, 8)?;
+                Pdu::CacheReset
+            }
+            9 if version > 0 => {
+                if data.len() < 32 {
+                    return Err(DecodeError::CORRUPT_LENGTH)
+                }
+                let key_identifier = KeyIdentifier::try_from(&data[8..28])
+                    .map_err(|_| DecodeError::CORRUPT_LENGTH)?;
+                Pdu::Payload {
+                    action: Action::from_flags(data[2]),
+                    payload: RouterKey::new(
+                        read_u32(data, 28).into(),
+                        key_identifier,
+                        Bytes::copy_from_slice(&data[32..])
+                    ).into()
+                }
+            }
+            10 => Self::decode_error_report(data, field)?,
+            11 if version > 1 => {
+                if data.len() < 12 || data.len() & 0x03 != 0 {
+                    return Err(DecodeError::CORRUPT_LENGTH)
+                }
+                let action = Action::from_flags(data[2]);
+                if !action.is_announce() && data.len() != 12 {
+                    return Err(DecodeError::CORRUPT_LENGTH)
+                }
+                Pdu::Payload {
+                    action,
+                    payload: Aspa::new(
+                        read_u32(data, 8).into(),
+                        data[12..].chunks(4).map(|chunk| {
+                            read_u32(chunk, 0).into()
+                        }).collect()
+                    ).into()
+                }
+            }
+            _ => return Err(DecodeError::UNSUPPORTED_TYPE)
+        };
+        Ok((version, pdu))
+    }
+
+    /// Decodes the remainder of an IPv4 or IPv6 Prefix PDU.
+    fn decode_origin(
+        data: &[u8], addr: IpAddr, asn_pos: usize
+    ) -> Result<Self, DecodeError> {
+        let asn = read_u32(data, asn_pos).into();
+        let origin = Prefix::new(addr, data[9]).ok().and_then(|prefix| {
+            RouteOrigin::new(prefix, data[10], asn)
+        }).ok_or(DecodeError::CORRUPT_PREFIX)?;
+        Ok(Pdu::Payload {
+            action: Action::from_flags(data[8]),
+            payload: origin.into()
+        })
+    }
+
+    /// Decodes the remainder of an Error Report PDU.
+    fn decode_error_report(
+        data: &[u8], code: u16
+    ) -> Result<Self, DecodeError> {
+        if data.len() < 16 {
+            return Err(DecodeError::CORRUPT_LENGTH)
+        }
+        let pdu_len = read_u32(data, 8) as usize;
+        let text_pos = match pdu_len.checked_add(12) {
+            Some(pos) if pos + 4 <= data.len() => pos,
+            _ => return Err(DecodeError::CORRUPT_LENGTH)
+        };
+        let text_len = read_u32(data, text_pos) as usize;
+        if text_pos + 4 + text_len != data.len() {
+            return Err(DecodeError::CORRUPT_LENGTH)
+        }
+        let text = String::from_utf8(data[text_pos + 4..].to_vec())
+            .map_err(|_| DecodeError::CORRUPT_TEXT)?;
+        Ok(Pdu::ErrorReport {
+            code: ErrorCode::from_u16(code),
+            pdu: Bytes::copy_from_slice(&data[12..12 + pdu_len]),
+            text
+        })
+    }
+}
+
+
+//------------ read_raw ------------------------------------------------------
+
+/// Reads the octets of a single PDU from a stream.
+///
+/// Returns `Ok(None)` if the stream ends before the first octet of a PDU.
+///
+/// If the length given in the header is shorter than a header or longer
+/// than [`MAX_PDU_LEN`], only the header is read and returned. Decoding
+/// it will fail with a corrupt data error.
+///
+/// [`MAX_PDU_LEN`]: constant.MAX_PDU_LEN.html
+pub async fn read_raw<R: AsyncRead + Unpin>(
+    reader: &mut R
+) -> Result<Option<Bytes>, io::Error> {
+    let mut header = [0u8; HEADER_LEN];
+    let read = reader.read(&mut header).await?;
+    if read == 0 {
+        return Ok(None)
+    }
+    reader.read_exact(&mut header[read..]).await?;
+    let len = read_u32(&header, 4);
+    if len < HEADER_LEN as u32 || len > MAX_PDU_LEN {
+        return Ok(Some(Bytes::copy_from_slice(&header)))
+    }
+    let mut res = vec![0u8; len as usize];
+    res[..HEADER_LEN].copy_from_slice(&header);
+    reader.read_exact(&mut res[HEADER_LEN..]).await?;
+    Ok(Some(res.into()))
+}
+
+
+//------------ ErrorCode -----------------------------------------------------
+
+/// The error codes of an Error Report PDU.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorCode {
+    /// A received PDU was corrupt.
+    CorruptData,
+
+    /// The sender experienced an internal error.
+    InternalError,
+
+    /// The cache has no data yet.
+    NoDataAvailable,
+
+    /// The request isn’t valid.
+    InvalidRequest,
+
+    /// The protocol version isn’t supported.
+    UnsupportedProtocolVersion,
+
+    /// The PDU type isn’t supported.
+    UnsupportedPduType,
+
+    /// An unknown item was withdrawn.
+    WithdrawalOfUnknownRecord,
+
+    /// An item was announced twice.
+    DuplicateAnnouncement,
+
+    /// A PDU used a different version than the session.
+    UnexpectedProtocolVersion,
+
+    /// Some other error code.
+    Other(u16),
+}
+
+impl ErrorCode {
+    /// Returns the error code for its numerical value.
+    pub fn from_u16(code: u16) -> Self {
+        match code {
+            0 => ErrorCode::CorruptData,
+            1 => ErrorCode::InternalError,
+            2 => ErrorCode::NoDataAvailable,
+            3 => ErrorCode::InvalidRequest,
+            4 => ErrorCode::UnsupportedProtocolVersion,
+            5 => ErrorCode::UnsupportedPduType,
+            6 => ErrorCode::WithdrawalOfUnknownRecord,
+            7 => ErrorCode::DuplicateAnnouncement,
+            8 => ErrorCode::UnexpectedProtocolVersion,
+            code => ErrorCode::Other(code),
+        }
+    }
+
+    /// Returns the numerical value of the error code.
+    pub fn to_u16(self) -> u16 {
+        match self {
+            ErrorCode::CorruptData => 0,
+            ErrorCode::InternalError => 1,
+            ErrorCode::NoDataAvailable => 2,
+            ErrorCode::InvalidRequest => 3,
+            ErrorCode::UnsupportedProtocolVersion => 4,
+            ErrorCode::UnsupportedPduType => 5,
+            ErrorCode::WithdrawalOfUnknownRecord => 6,
+            ErrorCode::DuplicateAnnouncement => 7,
+            ErrorCode::UnexpectedProtocolVersion => 8,
+            ErrorCode::Other(code) => code,
+        }
+    }
+
+    /// Returns whether the error is fatal, i.e., ends the session.
+    ///
+    /// All errors except for “No Data Available” are fatal.
+    pub fn is_fatal(self) -> bool {
+        self != ErrorCode::NoDataAvailable
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ErrorCode::CorruptData => f.write_str("corrupt data"),
+            ErrorCode::InternalError => f.write_str("internal error"),
+            ErrorCode::NoDataAvailable => f.write_str("no data available"),
+            ErrorCode::InvalidRequest => f.write_str("invalid request"),
+            ErrorCode::UnsupportedProtocolVersion => {
+                f.write_str("unsupported protocol version")
+            }
+            ErrorCode::UnsupportedPduType => {
+                f.write_str("unsupported PDU type")
+            }
+            ErrorCode::WithdrawalOfUnknownRecord => {
+                f.write_str("withdrawal of unknown record")
+            }
+            ErrorCode::DuplicateAnnouncement => {
+                f.write_str("duplicate announcement received")
+            }
+            ErrorCode::UnexpectedProtocolVersion => {
+                f.write_str("unexpected protocol version")
+            }
+            ErrorCode::Other(code) => write!(f, "error code {}", code),
+        }
+    }
+}
+
+
+//------------ DecodeError ---------------------------------------------------
+
+/// A PDU could not be decoded.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DecodeError {
+    /// The error code for the error report.
+    code: ErrorCode,
+
+    /// The diagnostic text for the error report.
+    text: &'static str,
+}
+
+impl DecodeError {
+    const CORRUPT_LENGTH: Self = DecodeError {
+        code: ErrorCode::CorruptData, text: "invalid PDU length"
+    };
+    const CORRUPT_PREFIX: Self = DecodeError {
+        code: ErrorCode::CorruptData, text: "invalid prefix length"
+    };
+    const CORRUPT_TEXT: Self = DecodeError {
+        code: ErrorCode::CorruptData, text: "invalid error text"
+    };
+    const UNSUPPORTED_VERSION: Self = DecodeError {
+        code: ErrorCode::UnsupportedProtocolVersion,
+        text: "unsupported protocol version"
+    };
+    const UNSUPPORTED_TYPE: Self = DecodeError {
+        code: ErrorCode::UnsupportedPduType, text: "unsupported PDU type"
+    };
+
+    /// Returns the error code to use in an error report.
+    pub fn code(self) -> ErrorCode {
+        self.code
+    }
+
+    /// Returns the diagnostic text to use in an error report.
+    pub fn text(self) -> &'static str {
+        self.text
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+impl error::Error for DecodeError { }
+
+
+//------------ Helpers -------------------------------------------------------
+
+/// Checks that the PDU has exactly the given length.
+fn check_len(data: &[u8], len: usize) -> Result<(), DecodeError> {
+    if data.len() == len {
+        Ok(())
+    }
+    else {
+        Err(DecodeError::CORRUPT_LENGTH)
+    }
+}
+
+/// Reads a big-endian `u16` at the given position.
+fn read_u16(data: &[u8], pos: usize) -> u16 {
+    u16::from_be_bytes([data[pos], data[pos + 1]])
+}
+
+/// Reads a big-endian `u32` at the given position.
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_be_bytes([
+        data[pos], data[pos + 1], data[pos + 2], data[pos + 3]
+    ])
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn origin(addr: &str, len: u8, max_len: u8, asn: u32) -> Payload {
+        RouteOrigin::new(
+            Prefix::new(addr.parse().unwrap(), len).unwrap(),
+            max_len, asn.into()
+        ).unwrap().into()
+    }
+
+    fn aspa(providers: &[u32]) -> Payload {
+        Aspa::new(
+            64496.into(),
+            providers.iter().map(|&asn| asn.into()).collect()
+        ).into()
+    }
+
+    fn round_trip(version: u8, pdu: Pdu) {
+        let encoded = pdu.to_vec(version);
+        assert_eq!(Pdu::decode(&encoded), Ok((version, pdu)));
+    }
+
+    #[test]
+    fn encode_decode() {
+        let key = RouterKey::new(
+            64496.into(),
+            KeyIdentifier::try_from(&[7u8; 20][..]).unwrap(),
+            Bytes::from_static(b"key info")
+        );
+        for pdu in [
+            Pdu::SerialNotify { session: 12, serial: Serial(7) },
+            Pdu::SerialQuery { session: 12, serial: Serial(7) },
+            Pdu::ResetQuery,
+            Pdu::CacheResponse { session: 12 },
+            Pdu::Payload {
+                action: Action::Announce,
+                payload: origin("192.0.2.0", 24, 26, 64496)
+            },
+            Pdu::Payload {
+                action: Action::Withdraw,
+                payload: origin("2001:db8::", 32, 48, 64497)
+            },
+            Pdu::Payload {
+                action: Action::Announce, payload: key.clone().into()
+            },
+            Pdu::EndOfData {
+                session: 12, serial: Serial(7),
+                timing: Some(Timing::default())
+            },
+            Pdu::CacheReset,
+            Pdu::ErrorReport {
+                code: ErrorCode::NoDataAvailable,
+                pdu: Bytes::from_static(b"\x01\x02\0\0\0\0\0\x08"),
+                text: "not yet".into()
+            },
+        ].iter() {
+            round_trip(1, pdu.clone());
+            round_trip(2, pdu.clone());
+        }
+        round_trip(0, Pdu::EndOfData {
+            session: 12, serial: Serial(7), timing: None
+        });
+        round_trip(2, Pdu::Payload {
+            action: Action::Announce, payload: aspa(&[64497, 64498])
+        });
+        round_trip(2, Pdu::Payload {
+            action: Action::Withdraw, payload: aspa(&[])
+        });
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(
+            Pdu::Payload {
+                action: Action::Announce,
+                payload: origin("192.0.2.0", 24, 26, 64496)
+            }.to_vec(1),
+            b"\x01\x04\0\0\0\0\0\x14\x01\x18\x1a\0\xc0\0\x02\0\0\0\xfb\xf0"
+        );
+        assert_eq!(
+            Pdu::Payload {
+                action: Action::Announce, payload: aspa(&[64497])
+            }.to_vec(2),
+            b"\x02\x0b\x01\0\0\0\0\x10\0\0\xfb\xf0\0\0\xfb\xf1"
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let mut pdu = Pdu::ResetQuery.to_vec(1);
+        pdu[0] = 3;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::UnsupportedProtocolVersion
+        );
+        assert_eq!(
+            Pdu::decode(&pdu[..6]).unwrap_err().code(),
+            ErrorCode::CorruptData
+        );
+        let mut pdu = Pdu::ResetQuery.to_vec(1);
+        pdu[1] = 42;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::UnsupportedPduType
+        );
+        let mut pdu = Pdu::Payload {
+            action: Action::Announce,
+            payload: origin("192.0.2.0", 24, 26, 64496)
+        }.to_vec(1);
+        pdu[10] = 33;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::CorruptData
+        );
+        let mut pdu = Pdu::Payload {
+            action: Action::Announce,
+            payload: RouterKey::new(
+                64496.into(),
+                KeyIdentifier::try_from(&[7u8; 20][..]).unwrap(),
+                Bytes::from_static(b"key info")
+            ).into()
+        }.to_vec(1);
+        pdu[0] = 0;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::UnsupportedPduType
+        );
+        let mut pdu = Pdu::Payload {
+            action: Action::Announce, payload: aspa(&[64497])
+        }.to_vec(2);
+        pdu[0] = 1;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::UnsupportedPduType
+        );
+        pdu[0] = 2;
+        pdu[2] = 0;
+        assert_eq!(
+            Pdu::decode(&pdu).unwrap_err().code(),
+            ErrorCode::CorruptData
+        );
+    }
+}
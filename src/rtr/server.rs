@@ -0,0 +1,606 @@
+//! The cache side of the RPKI-to-Router protocol.
+//!
+//! The [`Server`] accepts connections from routers on a TCP listener and
+//! serves each of them in its own task. The data is provided by the
+//! application through the [`VrpSource`] trait. Whenever the data changes,
+//! the application informs the server via a [`NotifySender`] which causes
+//! a Serial Notify PDU to be sent to all connected routers.
+//!
+//! Connections over other transports, e.g., SSH or TLS, can be served via
+//! the [`serve`] function.
+//!
+//! [`Server`]: struct.Server.html
+//! [`VrpSource`]: trait.VrpSource.html
+//! [`NotifySender`]: struct.NotifySender.html
+//! [`serve`]: fn.serve.html
+
+use std::{cmp, io};
+use bytes::Bytes;
+use log::debug;
+use tokio::io::{
+    AsyncRead, AsyncWrite, AsyncWriteExt, BufWriter, ReadHalf, WriteHalf
+};
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, mpsc};
+use super::payload::{Action, Payload, Timing};
+use super::pdu::{read_raw, ErrorCode, Pdu, MAX_VERSION};
+use super::state::State;
+
+
+//------------ VrpSource -----------------------------------------------------
+
+/// A source of the data served to routers.
+///
+/// The source is cloned for every connection, so it should be cheap to
+/// clone, e.g., by keeping the actual data behind an `Arc`.
+pub trait VrpSource: Clone + Send + Sync + 'static {
+    /// An iterator over the complete data set.
+    type FullIter: Iterator<Item = Payload> + Send;
+
+    /// An iterator over the changes between two data sets.
+    type DiffIter: Iterator<Item = (Action, Payload)> + Send;
+
+    /// Returns whether the source has data yet.
+    ///
+    /// If this returns `false`, queries are answered with a “No Data
+    /// Available” error.
+    fn ready(&self) -> bool;
+
+    /// Returns the current state of the data.
+    fn notify(&self) -> State;
+
+    /// Returns the current state and an iterator over the complete data.
+    fn full(&self) -> (State, Self::FullIter);
+
+    /// Returns the changes since the given state.
+    ///
+    /// Returns the current state and an iterator over the changes. If the
+    /// changes cannot be provided, e.g., because the session ID differs or
+    /// the serial number is too old, returns `None` which causes a Cache
+    /// Reset PDU to be sent.
+    fn diff(&self, state: State) -> Option<(State, Self::DiffIter)>;
+
+    /// Returns the timing parameters sent to routers.
+    fn timing(&self) -> Timing {
+        Timing::default()
+    }
+}
+
+
+//------------ NotifySender --------------------------------------------------
+
+/// Informs all connections about new data.
+#[derive(Clone, Debug)]
+pub struct NotifySender(broadcast::Sender<()>);
+
+impl NotifySender {
+    /// Creates a new notify sender.
+    pub fn new() -> Self {
+        NotifySender(broadcast::channel(1).0)
+    }
+
+    /// Informs all connections that new data is available.
+    pub fn notify(&self) {
+        // An error only means that there are no connections.
+        let _ = self.0.send(());
+    }
+
+    /// Creates a new receiver for notifications.
+    pub fn subscribe(&self) -> NotifyReceiver {
+        NotifyReceiver(self.0.subscribe())
+    }
+}
+
+impl Default for NotifySender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//------------ NotifyReceiver ------------------------------------------------
+
+/// Receives notifications about new data for a single connection.
+#[derive(Debug)]
+pub struct NotifyReceiver(broadcast::Receiver<()>);
+
+impl NotifyReceiver {
+    /// Waits for the next notification.
+    ///
+    /// Returns `false` if the sender has been dropped.
+    async fn recv(&mut self) -> bool {
+        match self.0.recv().await {
+            Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => true,
+            Err(broadcast::error::RecvError::Closed) => false,
+        }
+    }
+}
+
+
+//------------ Server --------------------------------------------------------
+
+/// An RTR server accepting connections on a TCP listener.
+pub struct Server<S> {
+    /// The listener to accept connections on.
+    listener: TcpListener,
+
+    /// The sender for notifications.
+    notify: NotifySender,
+
+    /// The source of the data.
+    source: S,
+}
+
+impl<S: VrpSource> Server<S> {
+    /// Creates a new server.
+    pub fn new(
+        listener: TcpListener, notify: NotifySender, source: S
+    ) -> Self {
+        Server { listener, notify, source }
+    }
+
+    /// Runs the server.
+    ///
+    /// Every accepted connection is served in a newly spawned task. The
+    /// method only returns if accepting a connection fails.
+    pub async fn run(self) -> Result<(), io::Error> {
+        loop {
+            let (sock, addr) = self.listener.accept().await?;
+            let source = self.source.clone();
+            let notify = self.notify.subscribe();
+            tokio::spawn(async move {
+                if let Err(err) = serve(sock, source, notify).await {
+                    debug!("RTR connection from {} failed: {}", addr, err);
+                }
+            });
+        }
+    }
+}
+
+
+//------------ serve ---------------------------------------------------------
+
+/// Serves a single RTR connection.
+///
+/// The function returns when the router closes the connection, after a
+/// fatal error was reported to or by the router, or when reading from or
+/// writing to the socket fails.
+///
+/// The protocol version of the session is that of the router’s first
+/// query. If the router uses a version higher than [`MAX_VERSION`], it is
+/// sent an error report in the highest supported version so it can
+/// downgrade and reconnect. Payload items not supported by the session’s
+/// version, i.e., router keys in version 0 and ASPAs before version 2, are
+/// not sent.
+///
+/// [`MAX_VERSION`]: ../pdu/constant.MAX_VERSION.html
+///
+/// This spawns tasks, so it must be run within a Tokio runtime.
+pub async fn serve<Sock, S>(
+    sock: Sock, source: S, notify: NotifyReceiver
+) -> Result<(), io::Error>
+where
+    Sock: AsyncRead + AsyncWrite + Send + 'static,
+    S: VrpSource
+{
+    let (read, write) = tokio::io::split(sock);
+    let (tx, mut rx) = mpsc::channel(4);
+    let reader = tokio::spawn(read_queries(read, tx.clone()));
+    let notifier = tokio::spawn(forward_notify(notify, tx));
+    let res = Connection::new(write, source).run(&mut rx).await;
+    reader.abort();
+    notifier.abort();
+    res
+}
+
+/// Reads PDUs from the socket and forwards them to the connection.
+async fn read_queries<Sock: AsyncRead>(
+    mut read: ReadHalf<Sock>, tx: mpsc::Sender<Event>
+) {
+    loop {
+        let res = read_raw(&mut read).await;
+        let done = res.as_ref().ok().and_then(Option::as_ref).is_none();
+        if tx.send(Event::Query(res)).await.is_err() || done {
+            return
+        }
+    }
+}
+
+/// Forwards notifications to the connection.
+async fn forward_notify(
+    mut notify: NotifyReceiver, tx: mpsc::Sender<Event>
+) {
+    while notify.recv().await {
+        if tx.send(Event::Notify).await.is_err() {
+            return
+        }
+    }
+}
+
+
+//------------ Event ---------------------------------------------------------
+
+/// Something a connection needs to react to.
+enum Event {
+    /// The result of reading a PDU from the router.
+    Query(Result<Option<Bytes>, io::Error>),
+
+    /// New data is available.
+    Notify,
+}
+
+
+//------------ Connection ----------------------------------------------------
+
+/// The state of a single connection.
+struct Connection<Sock, S> {
+    /// The socket to write to.
+    sock: BufWriter<WriteHalf<Sock>>,
+
+    /// The source of the data.
+    source: S,
+
+    /// The protocol version of the session once negotiated.
+    version: Option<u8>,
+
+    /// A buffer for encoding PDUs.
+    buf: Vec<u8>,
+}
+
+impl<Sock: AsyncWrite, S: VrpSource> Connection<Sock, S> {
+    /// Creates a new connection.
+    fn new(sock: WriteHalf<Sock>, source: S) -> Self {
+        Connection {
+            sock: BufWriter::new(sock),
+            source,
+            version: None,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Runs the connection until it ends.
+    async fn run(
+        &mut self, rx: &mut mpsc::Receiver<Event>
+    ) -> Result<(), io::Error> {
+        while let Some(event) = rx.recv().await {
+            match event {
+                Event::Query(Ok(Some(data))) => {
+                    let proceed = self.query(data).await?;
+                    self.sock.flush().await?;
+                    if !proceed {
+                        break
+                    }
+                }
+                Event::Query(Ok(None)) => break,
+                Event::Query(Err(err)) => return Err(err),
+                Event::Notify => {
+                    // Notifications are only sent once the router has
+                    // told us which version it speaks.
+                    if self.version.is_some() {
+                        let state = self.source.notify();
+                        self.send(&Pdu::SerialNotify {
+                            session: state.session(),
+                            serial: state.serial(),
+                        }).await?;
+                        self.sock.flush().await?;
+                    }
+                }
+            }
+        }
+        self.sock.shutdown().await
+    }
+
+    /// Processes a query PDU.
+    ///
+    /// Returns whether the connection should continue.
+    async fn query(&mut self, data: Bytes) -> Result<bool, io::Error> {
+        let (version, pdu) = match Pdu::decode(&data) {
+            Ok(some) => some,
+            Err(err) => {
+                if self.version.is_none() {
+                    self.version = Some(cmp::min(data[0], MAX_VERSION));
+                }
+                self.send(&Pdu::error_report(err, data)).await?;
+                return Ok(false)
+            }
+        };
+        match self.version {
+            Some(current) if current != version => {
+                self.error(
+                    ErrorCode::UnexpectedProtocolVersion, data,
+                    "protocol version changed during session"
+                ).await?;
+                return Ok(false)
+            }
+            Some(_) => { }
+            None => self.version = Some(version)
+        }
+        match pdu {
+            Pdu::SerialQuery { session, serial } => {
+                self.serial_query(State::from_parts(session, serial)).await?;
+                Ok(true)
+            }
+            Pdu::ResetQuery => {
+                self.reset_query().await?;
+                Ok(true)
+            }
+            Pdu::ErrorReport { code, text, .. } => {
+                debug!("RTR router reported error: {}: {}", code, text);
+                Ok(!code.is_fatal())
+            }
+            _ => {
+                self.error(
+                    ErrorCode::InvalidRequest, data, "unexpected PDU type"
+                ).await?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Answers a serial query.
+    async fn serial_query(&mut self, state: State) -> Result<(), io::Error> {
+        if !self.source.ready() {
+            return self.no_data().await
+        }
+        let (state, diff) = match self.source.diff(state) {
+            Some(some) => some,
+            None => return self.send(&Pdu::CacheReset).await
+        };
+        self.send(&Pdu::CacheResponse { session: state.session() }).await?;
+        for (action, payload) in diff {
+            self.send_payload(action, payload).await?;
+        }
+        self.end_of_data(state).await
+    }
+
+    /// Answers a reset query.
+    async fn reset_query(&mut self) -> Result<(), io::Error> {
+        if !self.source.ready() {
+            return self.no_data().await
+        }
+        let (state, full) = self.source.full();
+        self.send(&Pdu::CacheResponse { session: state.session() }).await?;
+        for payload in full {
+            self.send_payload(Action::Announce, payload).await?;
+        }
+        self.end_of_data(state).await
+    }
+
+    /// Sends a payload item if the session’s version supports it.
+    async fn send_payload(
+        &mut self, action: Action, payload: Payload
+    ) -> Result<(), io::Error> {
+        if payload.min_version() > self.version() {
+            return Ok(())
+        }
+        self.send(&Pdu::Payload { action, payload }).await
+    }
+
+    /// Sends an End of Data PDU.
+    async fn end_of_data(&mut self, state: State) -> Result<(), io::Error> {
+        let timing = self.source.timing();
+        self.send(&Pdu::EndOfData {
+            session: state.session(),
+            serial: state.serial(),
+            timing: Some(timing),
+        }).await
+    }
+
+    /// Sends a No Data Available error report.
+    async fn no_data(&mut self) -> Result<(), io::Error> {
+        self.error(
+            ErrorCode::NoDataAvailable, Bytes::new(), "no data available"
+        ).await
+    }
+
+    /// Sends an error report.
+    async fn error(
+        &mut self, code: ErrorCode, pdu: Bytes, text: &str
+    ) -> Result<(), io::Error> {
+        self.send(&Pdu::ErrorReport { code, pdu, text: text.into() }).await
+    }
+
+    /// Sends a PDU.
+    async fn send(&mut self, pdu: &Pdu) -> Result<(), io::Error> {
+        self.buf.clear();
+        pdu.encode(self.version(), &mut self.buf);
+        self.sock.write_all(&self.buf).await
+    }
+
+    /// Returns the protocol version to use.
+    fn version(&self) -> u8 {
+        self.version.unwrap_or(MAX_VERSION)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{iter, vec};
+    use std::sync::{Arc, Mutex};
+    use tokio::io::DuplexStream;
+    use crate::rtr::payload::RouteOrigin;
+    use crate::rtr::state::Serial;
+    use super::*;
+
+    /// A source that can only provide the diff to the previous serial.
+    #[derive(Clone)]
+    pub(crate) struct Source(Arc<Mutex<(State, Vec<Payload>)>>);
+
+    impl Source {
+        pub(crate) fn new() -> Self {
+            Source(Arc::new(Mutex::new((
+                State::from_parts(12, Serial(0)), Vec::new()
+            ))))
+        }
+
+        pub(crate) fn push(&self, asn: u32) {
+            let mut data = self.0.lock().unwrap();
+            data.0.inc();
+            data.1.push(origin(asn));
+        }
+    }
+
+    impl VrpSource for Source {
+        type FullIter = vec::IntoIter<Payload>;
+        type DiffIter = iter::Once<(Action, Payload)>;
+
+        fn ready(&self) -> bool {
+            !self.0.lock().unwrap().1.is_empty()
+        }
+
+        fn notify(&self) -> State {
+            self.0.lock().unwrap().0
+        }
+
+        fn full(&self) -> (State, Self::FullIter) {
+            let data = self.0.lock().unwrap();
+            (data.0, data.1.clone().into_iter())
+        }
+
+        fn diff(&self, state: State) -> Option<(State, Self::DiffIter)> {
+            // We can only provide the difference to the previous serial.
+            let data = self.0.lock().unwrap();
+            if state.session() != data.0.session()
+                || state.serial() + 1 != data.0.serial()
+            {
+                return None
+            }
+            Some((
+                data.0,
+                iter::once((Action::Announce, data.1.last()?.clone()))
+            ))
+        }
+    }
+
+    pub(crate) fn origin(asn: u32) -> Payload {
+        RouteOrigin::new(
+            "192.0.2.0/24".parse().unwrap(), 24, asn.into()
+        ).unwrap().into()
+    }
+
+    async fn send(sock: &mut DuplexStream, pdu: Pdu) {
+        sock.write_all(&pdu.to_vec(1)).await.unwrap();
+    }
+
+    async fn recv(sock: &mut DuplexStream) -> Pdu {
+        let data = read_raw(sock).await.unwrap().unwrap();
+        let (version, pdu) = Pdu::decode(&data).unwrap();
+        assert_eq!(version, 1);
+        pdu
+    }
+
+    #[test]
+    fn session() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all().build().unwrap();
+        rt.block_on(async {
+            let source = Source::new();
+            let notify = NotifySender::new();
+            let (mut client, server) = tokio::io::duplex(4096);
+            let task = tokio::spawn(
+                serve(server, source.clone(), notify.subscribe())
+            );
+
+            // No data yet.
+            send(&mut client, Pdu::ResetQuery).await;
+            match recv(&mut client).await {
+                Pdu::ErrorReport { code, .. } => {
+                    assert_eq!(code, ErrorCode::NoDataAvailable)
+                }
+                pdu => panic!("unexpected {:?}", pdu)
+            }
+
+            // Full data.
+            source.push(64496);
+            send(&mut client, Pdu::ResetQuery).await;
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::CacheResponse { session: 12 }
+            );
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::Payload {
+                    action: Action::Announce, payload: origin(64496)
+                }
+            );
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::EndOfData {
+                    session: 12, serial: Serial(1),
+                    timing: Some(Timing::default())
+                }
+            );
+
+            // Notify and diff.
+            source.push(64497);
+            notify.notify();
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::SerialNotify { session: 12, serial: Serial(2) }
+            );
+            send(&mut client, Pdu::SerialQuery {
+                session: 12, serial: Serial(1)
+            }).await;
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::CacheResponse { session: 12 }
+            );
+            assert_eq!(
+                recv(&mut client).await,
+                Pdu::Payload {
+                    action: Action::Announce, payload: origin(64497)
+                }
+            );
+            assert!(match recv(&mut client).await {
+                Pdu::EndOfData { serial, .. } => serial == Serial(2),
+                _ => false
+            });
+
+            // Too old for a diff.
+            send(&mut client, Pdu::SerialQuery {
+                session: 12, serial: Serial(0)
+            }).await;
+            assert_eq!(recv(&mut client).await, Pdu::CacheReset);
+
+            // Changing the version ends the session.
+            client.write_all(&Pdu::ResetQuery.to_vec(0)).await.unwrap();
+            match recv(&mut client).await {
+                Pdu::ErrorReport { code, .. } => {
+                    assert_eq!(code, ErrorCode::UnexpectedProtocolVersion)
+                }
+                pdu => panic!("unexpected {:?}", pdu)
+            }
+            assert!(read_raw(&mut client).await.unwrap().is_none());
+            task.await.unwrap().unwrap();
+        });
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all().build().unwrap();
+        rt.block_on(async {
+            let (mut client, server) = tokio::io::duplex(4096);
+            let task = tokio::spawn(serve(
+                server, Source::new(), NotifySender::new().subscribe()
+            ));
+            let mut query = Pdu::ResetQuery.to_vec(MAX_VERSION);
+            query[0] = MAX_VERSION + 1;
+            client.write_all(&query).await.unwrap();
+
+            // The error is reported in the highest supported version.
+            let data = read_raw(&mut client).await.unwrap().unwrap();
+            match Pdu::decode(&data).unwrap() {
+                (MAX_VERSION, Pdu::ErrorReport { code, .. }) => {
+                    assert_eq!(code, ErrorCode::UnsupportedProtocolVersion)
+                }
+                res => panic!("unexpected {:?}", res)
+            }
+            assert!(read_raw(&mut client).await.unwrap().is_none());
+            task.await.unwrap().unwrap();
+        });
+    }
+}
@@ -0,0 +1,78 @@
+//! The state of a data set exchanged via RTR.
+//!
+//! A cache identifies the version of its data set through a session ID and
+//! a serial number. The session ID changes whenever the cache loses its
+//! history, e.g., when it is restarted. The serial number increases with
+//! every change of the data set.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub use crate::payload::Serial;
+
+
+//------------ State ---------------------------------------------------------
+
+/// The session ID and serial number of a data set.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct State {
+    /// The session ID.
+    session: u16,
+
+    /// The serial number.
+    serial: Serial,
+}
+
+impl State {
+    /// Creates a new state with a fresh session ID.
+    ///
+    /// The session ID is derived from the current time so that restarting
+    /// a cache results in a different session. The serial number starts
+    /// at zero.
+    pub fn new() -> Self {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_secs()).unwrap_or(0);
+        Self::from_parts(secs as u16, Serial(0))
+    }
+
+    /// Creates a state from a session ID and serial number.
+    pub fn from_parts(session: u16, serial: Serial) -> Self {
+        State { session, serial }
+    }
+
+    /// Returns the session ID.
+    pub fn session(self) -> u16 {
+        self.session
+    }
+
+    /// Returns the serial number.
+    pub fn serial(self) -> Serial {
+        self.serial
+    }
+
+    /// Increases the serial number by one.
+    pub fn inc(&mut self) {
+        self.serial = self.serial + 1
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn state_inc() {
+        let mut state = State::from_parts(7, Serial(0xFFFF_FFFF));
+        state.inc();
+        assert_eq!(state.serial(), Serial(0));
+        assert_eq!(state.session(), 7);
+    }
+}
@@ -0,0 +1,323 @@
+//! Replay protection for signed protocol messages.
+//!
+//! The provisioning protocol of [RFC 6492] and the publication protocol of
+//! [RFC 8181] both wrap their XML messages in a CMS signed-data structure
+//! which can be decoded as a [`SignedObject`]. A valid signature alone
+//! does not protect a server against an attacker resending a message it
+//! has observed earlier. This module therefore provides [`ReplayCheck`]
+//! which checks that the signing time of a message lies within a window
+//! around the current time and is later than that of the last message
+//! accepted from the same sender.
+//!
+//! The signing time of the last accepted message per sender is kept by a
+//! [`ReplayPolicy`]. A server that needs the state to survive restarts can
+//! implement the trait on top of its persistent storage. For everything
+//! else, [`MemoryReplayPolicy`] keeps the state in memory.
+//!
+//! [RFC 6492]: https://tools.ietf.org/html/rfc6492
+//! [RFC 8181]: https://tools.ietf.org/html/rfc8181
+//! [`SignedObject`]: ../sigobj/struct.SignedObject.html
+//! [`ReplayCheck`]: struct.ReplayCheck.html
+//! [`ReplayPolicy`]: trait.ReplayPolicy.html
+//! [`MemoryReplayPolicy`]: struct.MemoryReplayPolicy.html
+
+use std::{error, fmt};
+use std::collections::HashMap;
+use std::sync::Arc;
+use chrono::{Duration, TimeZone, Utc};
+use crate::sigobj::SignedObject;
+use crate::x509::{Clock, SystemClock, Time};
+
+
+//------------ ReplayPolicy --------------------------------------------------
+
+/// Storage for the signing time of the last message of each sender.
+///
+/// Senders are identified by the handle the server uses for them, i.e.,
+/// the child handle for the provisioning protocol and the publisher handle
+/// for the publication protocol.
+pub trait ReplayPolicy {
+    /// The error type returned when accessing the storage fails.
+    type Error;
+
+    /// Returns the signing time of the last message accepted from `sender`.
+    fn last_seen(&self, sender: &str) -> Result<Option<Time>, Self::Error>;
+
+    /// Records the signing time of a message accepted from `sender`.
+    fn set_last_seen(
+        &mut self, sender: &str, time: Time
+    ) -> Result<(), Self::Error>;
+}
+
+
+//------------ MemoryReplayPolicy --------------------------------------------
+
+/// A replay policy keeping its state in memory.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReplayPolicy {
+    /// The last signing time for each sender.
+    last_seen: HashMap<String, Time>,
+}
+
+impl MemoryReplayPolicy {
+    /// Creates a new policy that hasn’t seen any messages yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Forgets the state of a sender.
+    ///
+    /// This should be called when a sender is removed from the server.
+    pub fn remove(&mut self, sender: &str) -> Option<Time> {
+        self.last_seen.remove(sender)
+    }
+}
+
+impl ReplayPolicy for MemoryReplayPolicy {
+    type Error = std::convert::Infallible;
+
+    fn last_seen(&self, sender: &str) -> Result<Option<Time>, Self::Error> {
+        Ok(self.last_seen.get(sender).cloned())
+    }
+
+    fn set_last_seen(
+        &mut self, sender: &str, time: Time
+    ) -> Result<(), Self::Error> {
+        self.last_seen.insert(sender.into(), time);
+        Ok(())
+    }
+}
+
+
+//------------ ReplayCheck ---------------------------------------------------
+
+/// Checks the signing time of protocol messages.
+///
+/// A message is accepted if its signing time is no more than the maximum
+/// age before the current time and no more than the maximum skew after it.
+/// In addition, the signing time must be later than that of the message
+/// last accepted from the same sender. Since signing times only have a
+/// resolution of a second, a sender cannot send more than one message per
+/// second.
+///
+/// The check should only be performed for messages with a valid
+/// signature, since accepting a message updates the state of the sender.
+#[derive(Clone, Debug)]
+pub struct ReplayCheck<P> {
+    /// The storage for the last seen signing times.
+    policy: P,
+
+    /// The maximum age of a message.
+    max_age: Duration,
+
+    /// The maximum time a message can be signed in the future.
+    max_skew: Duration,
+
+    /// The clock providing the time messages are received at.
+    clock: Arc<dyn Clock>,
+}
+
+impl<P: ReplayPolicy> ReplayCheck<P> {
+    /// Creates a new check using the given policy.
+    ///
+    /// Both the maximum age and the maximum skew are initially set to five
+    /// minutes. Messages are checked against the system time.
+    pub fn new(policy: P) -> Self {
+        ReplayCheck {
+            policy,
+            max_age: Duration::minutes(5),
+            max_skew: Duration::minutes(5),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    /// Returns a reference to the policy.
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Returns a mutable reference to the policy.
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    /// Converts the check into its policy.
+    pub fn into_policy(self) -> P {
+        self.policy
+    }
+
+    /// Returns the maximum age of a message.
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Sets the maximum age of a message.
+    pub fn set_max_age(&mut self, max_age: Duration) {
+        self.max_age = max_age
+    }
+
+    /// Returns the maximum time a message can be signed in the future.
+    pub fn max_skew(&self) -> Duration {
+        self.max_skew
+    }
+
+    /// Sets the maximum time a message can be signed in the future.
+    pub fn set_max_skew(&mut self, max_skew: Duration) {
+        self.max_skew = max_skew
+    }
+
+    /// Sets the clock providing the time messages are received at.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock
+    }
+
+    /// Checks a message received from `sender` now.
+    ///
+    /// The current time is taken from the check’s clock. The signing time
+    /// is taken from the signing-time attribute or, if that is missing,
+    /// the binary-signing-time attribute.
+    pub fn check(
+        &mut self, sender: &str, msg: &SignedObject
+    ) -> Result<(), ReplayError<P::Error>> {
+        let now = self.clock.now();
+        self.check_at(sender, msg, now)
+    }
+
+    /// Checks a message received from `sender` at the given time.
+    pub fn check_at(
+        &mut self, sender: &str, msg: &SignedObject, now: Time
+    ) -> Result<(), ReplayError<P::Error>> {
+        let signing_time = match msg.signing_time() {
+            Some(time) => time,
+            None => {
+                match msg.binary_signing_time().and_then(|secs| {
+                    Utc.timestamp_opt(secs as i64, 0).single()
+                }) {
+                    Some(time) => Time::new(time),
+                    None => return Err(ReplayError::MissingSigningTime)
+                }
+            }
+        };
+        self.check_signing_time(sender, signing_time, now)
+    }
+
+    /// Checks a signing time of a message received from `sender`.
+    ///
+    /// If the signing time is acceptable, it is recorded as the last seen
+    /// signing time for the sender.
+    pub fn check_signing_time(
+        &mut self, sender: &str, signing_time: Time, now: Time
+    ) -> Result<(), ReplayError<P::Error>> {
+        if signing_time < now - self.max_age {
+            return Err(ReplayError::Stale)
+        }
+        if signing_time > now + self.max_skew {
+            return Err(ReplayError::Future)
+        }
+        if let Some(last) = self.policy.last_seen(sender).map_err(
+            ReplayError::Policy
+        )? {
+            if signing_time <= last {
+                return Err(ReplayError::Replayed)
+            }
+        }
+        self.policy.set_last_seen(
+            sender, signing_time
+        ).map_err(ReplayError::Policy)
+    }
+}
+
+
+//------------ ReplayError ---------------------------------------------------
+
+/// A message was rejected by a replay check.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ReplayError<E> {
+    /// The message had no signing time.
+    MissingSigningTime,
+
+    /// The message was signed too long ago.
+    Stale,
+
+    /// The message was signed too far in the future.
+    Future,
+
+    /// The message was not signed after the last message of the sender.
+    Replayed,
+
+    /// Accessing the policy’s storage failed.
+    Policy(E),
+}
+
+impl<E: fmt::Display> fmt::Display for ReplayError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplayError::MissingSigningTime => {
+                f.write_str("message without signing time")
+            }
+            ReplayError::Stale => f.write_str("message signed too long ago"),
+            ReplayError::Future => f.write_str("message signed in the future"),
+            ReplayError::Replayed => {
+                f.write_str("message not newer than previous message")
+            }
+            ReplayError::Policy(ref err) => {
+                write!(f, "replay policy error: {}", err)
+            }
+        }
+    }
+}
+
+impl<E: fmt::Debug + fmt::Display> error::Error for ReplayError<E> { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn check_signing_time() {
+        let mut check = ReplayCheck::new(MemoryReplayPolicy::new());
+        let now = Time::utc(2021, 3, 1, 12, 0, 0);
+        let minutes = Duration::minutes;
+
+        assert_eq!(
+            check.check_signing_time("alice", now - minutes(6), now),
+            Err(ReplayError::Stale)
+        );
+        assert_eq!(
+            check.check_signing_time("alice", now + minutes(6), now),
+            Err(ReplayError::Future)
+        );
+        assert_eq!(check.policy().last_seen("alice"), Ok(None));
+
+        assert_eq!(
+            check.check_signing_time("alice", now - minutes(1), now),
+            Ok(())
+        );
+        assert_eq!(
+            check.check_signing_time("alice", now - minutes(1), now),
+            Err(ReplayError::Replayed)
+        );
+        assert_eq!(
+            check.check_signing_time("alice", now - minutes(2), now),
+            Err(ReplayError::Replayed)
+        );
+        assert_eq!(
+            check.check_signing_time("bob", now - minutes(2), now),
+            Ok(())
+        );
+        assert_eq!(check.check_signing_time("alice", now, now), Ok(()));
+        assert_eq!(check.policy().last_seen("alice"), Ok(Some(now)));
+
+        check.set_max_age(minutes(10));
+        assert_eq!(
+            check.check_signing_time("carol", now - minutes(6), now),
+            Ok(())
+        );
+        assert_eq!(
+            check.policy_mut().remove("carol"), Some(now - minutes(6))
+        );
+    }
+}
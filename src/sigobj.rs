@@ -1,8 +1,28 @@
-// Signed objects.
+//! Signed objects.
+//!
+//! All RPKI objects other than certificates and CRLs use the same template
+//! defined in [RFC 6488]: a CMS signed-data structure with exactly one
+//! signer whose EE certificate is included, a restricted set of signed
+//! attributes, and a content whose type is identified by the eContentType.
+//!
+//! This module implements this template once. The type [`SignedObject`]
+//! decodes, validates, and encodes the template while leaving the content
+//! as an octet string. The [`SignedObjectBuilder`] creates new signed
+//! objects including their EE certificate. Types for the content of a
+//! particular kind of object implement the [`SignedContent`] trait which
+//! allows them to use the generic methods `decode_with_content` and
+//! `finalize_content`. The kind of a signed object
+//! can be determined via [`ObjectType`].
+//!
+//! [RFC 6488]: https://tools.ietf.org/html/rfc6488
+//! [`SignedObject`]: struct.SignedObject.html
+//! [`SignedObjectBuilder`]: struct.SignedObjectBuilder.html
+//! [`SignedContent`]: trait.SignedContent.html
+//! [`ObjectType`]: enum.ObjectType.html
 
 use std::{cmp, io};
 use bcder::{decode, encode};
-use bcder::{Captured, Mode, OctetString, Oid, Tag, xerr};
+use bcder::{Captured, ConstOid, Mode, OctetString, Oid, Tag, xerr};
 use bcder::encode::PrimitiveContent;
 use bcder::string::OctetStringSource;
 use bytes::Bytes;
@@ -16,7 +36,9 @@ use crate::resources::{
     AsBlocksBuilder, AsResources, AsResourcesBuilder, IpBlocksBuilder,
     IpResources, IpResourcesBuilder
 };
-use crate::x509::{Name, Serial, Time, ValidationError, Validity, update_once};
+use crate::x509::{
+    DecodeMode, Name, Serial, Time, ValidationError, Validity, update_once
+};
 
 
 //------------ SignedObject --------------------------------------------------
@@ -65,28 +87,121 @@ impl SignedObject {
         Mode::Der.decode(self.content.to_source(), op)
     }
 
+    /// Decodes the object’s content as the given content type.
+    ///
+    /// Fails if the object’s content type is not that of `T`.
+    pub fn content_as<T: SignedContent>(&self) -> Result<T, decode::Error> {
+        if self.content_type != T::CONTENT_TYPE {
+            return Err(decode::Malformed)
+        }
+        self.decode_content(T::take_content_from)
+    }
+
+    /// Returns the kind of object based on its content type.
+    pub fn object_type(&self) -> ObjectType {
+        ObjectType::from_content_type(&self.content_type)
+    }
+
     /// Returns a reference to the certificate the object is signed with.
     pub fn cert(&self) -> &Cert {
         &self.cert
     }
+
+    /// Returns the digest algorithm used by the object.
+    pub fn digest_algorithm(&self) -> DigestAlgorithm {
+        self.digest_algorithm
+    }
+
+    /// Returns the key identifier of the signer.
+    pub fn signer_key_identifier(&self) -> KeyIdentifier {
+        self.sid
+    }
+
+    /// Returns a reference to the signature over the signed attributes.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Returns a reference to the message digest attribute.
+    pub fn message_digest(&self) -> &MessageDigest {
+        &self.message_digest
+    }
+
+    /// Returns the signing time attribute if present.
+    pub fn signing_time(&self) -> Option<Time> {
+        self.signing_time
+    }
+
+    /// Returns the binary signing time attribute if present.
+    pub fn binary_signing_time(&self) -> Option<u64> {
+        self.binary_signing_time
+    }
 }
 
 /// # Decoding, Validation, and Encoding
 ///
 impl SignedObject {
     /// Decodes a signed object from the given source.
+    ///
+    /// If `strict` is `true`, the object is decoded in strict mode,
+    /// otherwise in lenient mode.
     pub fn decode<S: decode::Source>(
         source: S,
         strict: bool
     ) -> Result<Self, S::Err> {
-        if strict { Mode::Der }
+        Self::decode_with_mode(source, DecodeMode::from_strict(strict))
+    }
+
+    /// Decodes a signed object using the given decoding mode.
+    ///
+    /// In strict mode, the object must be DER encoded. Otherwise BER is
+    /// accepted, too.
+    pub fn decode_with_mode<S: decode::Source>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        if mode.is_strict() { Mode::Der }
         else { Mode::Ber }
-            .decode(source, Self::take_from)
+            .decode(source, |cons| Self::take_from_with_mode(cons, mode))
+    }
+
+    /// Decodes a signed object with a specific type of content.
+    ///
+    /// Decoding fails if the content type of the object is not that of
+    /// `T` or the content can’t be decoded.
+    pub fn decode_with_content<S: decode::Source, T: SignedContent>(
+        source: S,
+        strict: bool
+    ) -> Result<(Self, T), S::Err> {
+        Self::decode_with_content_and_mode(
+            source, DecodeMode::from_strict(strict)
+        )
+    }
+
+    /// Decodes a signed object with a specific type of content and mode.
+    pub fn decode_with_content_and_mode<S, T>(
+        source: S,
+        mode: DecodeMode,
+    ) -> Result<(Self, T), S::Err>
+    where S: decode::Source, T: SignedContent {
+        let signed = Self::decode_with_mode(source, mode)?;
+        let content = signed.content_as()?;
+        Ok((signed, content))
     }
 
     /// Takes a signed object from an encoded constructed value.
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::default())
+    }
+
+    /// Takes a signed object using the given decoding mode.
+    ///
+    /// The mode is used for decoding the EE certificate.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Self, S::Err> {
         cons.take_sequence(|cons| { // ContentInfo
             oid::SIGNED_DATA.skip_if(cons)?; // contentType
@@ -108,7 +223,7 @@ impl SignedObject {
                     };
                     let cert = cons.take_constructed_if( // certificates
                         Tag::CTX_0,
-                        Cert::take_from
+                        |cons| Cert::take_from_with_mode(cons, mode)
                     )?;
                     // no crls
                     let (sid, attrs, signature) = { // signerInfos
@@ -173,11 +288,46 @@ impl SignedObject {
         strict: bool,
         now: Time,
     ) -> Result<ResourceCert, ValidationError> {
+        issuer.algorithm_policy().check_signature(
+            self.signature.algorithm()
+        )?;
         self.verify_compliance(strict)?;
         self.verify_signature(strict)?;
         self.cert.validate_ee_at(issuer, strict, now)
     }
 
+    /// Validates a detached signed object.
+    ///
+    /// Detached objects, such as signed checklists, are not published in
+    /// the RPKI repository and their EE certificate has no Subject
+    /// Information Access extension. Apart from that, they are validated
+    /// like any other signed object.
+    ///
+    /// Upon success, the method returns the validated EE certificate of the
+    /// object.
+    pub fn validate_detached(
+        self,
+        issuer: &ResourceCert,
+        strict: bool,
+    ) -> Result<ResourceCert, ValidationError> {
+        self.validate_detached_at(issuer, strict, Time::now())
+    }
+
+    /// Validates a detached signed object at the given time.
+    pub fn validate_detached_at(
+        self,
+        issuer: &ResourceCert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, ValidationError> {
+        issuer.algorithm_policy().check_signature(
+            self.signature.algorithm()
+        )?;
+        self.verify_compliance(strict)?;
+        self.verify_signature(strict)?;
+        self.cert.validate_detached_ee_at(issuer, strict, now)
+    }
+
     /// Validates that the signed object complies with the specification.
     ///
     /// This is item 1 of [RFC 6488]`s section 3.
@@ -254,6 +404,67 @@ impl SignedObject {
 }
 
 
+//------------ SignedContent -------------------------------------------------
+
+/// The content of a particular kind of signed object.
+///
+/// The trait connects the eContentType with the type representing the
+/// decoded content.
+pub trait SignedContent: Sized {
+    /// The eContentType of signed objects with this content.
+    const CONTENT_TYPE: ConstOid;
+
+    /// Takes the content from the beginning of an encoded value.
+    fn take_content_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err>;
+
+    /// Returns the DER encoded content.
+    fn to_content_bytes(&self) -> Bytes;
+}
+
+
+//------------ ObjectType ----------------------------------------------------
+
+/// The kind of a signed object as determined by its content type.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ObjectType {
+    /// A route origin authorization, RFC 6482.
+    Roa,
+
+    /// A manifest, RFC 9286.
+    Manifest,
+
+    /// A Ghostbusters record, RFC 6493.
+    Ghostbusters,
+
+    /// A signed checklist, RFC 9323.
+    SignedChecklist,
+
+    /// An AS provider attestation.
+    Aspa,
+
+    /// Any other content type.
+    Unknown,
+}
+
+impl ObjectType {
+    /// Determines the object type from the content type.
+    pub fn from_content_type<T: AsRef<[u8]>>(oid: &Oid<T>) -> Self {
+        CONTENT_TYPES.get(oid).cloned().unwrap_or(ObjectType::Unknown)
+    }
+}
+
+/// The object types of the known content types.
+const CONTENT_TYPES: oid::OidTable<ObjectType> = oid::OidTable::new(&[
+    (oid::ROUTE_ORIGIN_AUTHZ, ObjectType::Roa),
+    (oid::CT_RPKI_MANIFEST, ObjectType::Manifest),
+    (oid::CT_RPKI_GHOSTBUSTERS, ObjectType::Ghostbusters),
+    (oid::CT_SIGNED_CHECKLIST, ObjectType::SignedChecklist),
+    (oid::CT_ASPA, ObjectType::Aspa),
+]);
+
+
 //------------ SignedAttrs ---------------------------------------------------
 
 /// A private helper type that contains the raw signed attributes content.
@@ -598,8 +809,8 @@ pub struct SignedObjectBuilder {
 
     /// The URI of the signed object itself.
     ///
-    /// Must be provided.
-    signed_object: uri::Rsync,
+    /// Must be provided unless the object is detached.
+    signed_object: Option<uri::Rsync>,
 
     /// The IPv4 resources of the EE certificate.
     ///
@@ -643,7 +854,35 @@ impl SignedObjectBuilder {
             subject: None,
             crl_uri,
             ca_issuer,
-            signed_object,
+            signed_object: Some(signed_object),
+            v4_resources: None,
+            v6_resources: None,
+            as_resources: None,
+            signing_time: None,
+            binary_signing_time: None,
+        }
+    }
+
+    /// Creates a builder for a detached signed object.
+    ///
+    /// Detached objects are not published in the RPKI repository. Their
+    /// EE certificate will not contain a Subject Information Access
+    /// extension.
+    pub fn new_detached(
+        serial_number: Serial,
+        validity: Validity,
+        crl_uri: uri::Rsync,
+        ca_issuer: uri::Rsync,
+    ) -> Self {
+        Self {
+            digest_algorithm: DigestAlgorithm::default(),
+            serial_number,
+            validity,
+            issuer: None,
+            subject: None,
+            crl_uri,
+            ca_issuer,
+            signed_object: None,
             v4_resources: None,
             v6_resources: None,
             as_resources: None,
@@ -708,12 +947,15 @@ impl SignedObjectBuilder {
         self.ca_issuer = uri
     }
 
-    pub fn signed_object(&self) -> &uri::Rsync {
-        &self.signed_object
+    /// Returns the URI of the signed object.
+    ///
+    /// Returns `None` if the object is detached.
+    pub fn signed_object(&self) -> Option<&uri::Rsync> {
+        self.signed_object.as_ref()
     }
 
     pub fn set_signed_object(&mut self, uri: uri::Rsync) {
-        self.signed_object = uri
+        self.signed_object = Some(uri)
     }
 
     /// Returns a reference to the IPv4 address resources if present.
@@ -810,6 +1052,21 @@ impl SignedObjectBuilder {
         self.binary_signing_time = time
     }
 
+    /// Creates a signed object with the given content.
+    pub fn finalize_content<S: Signer, T: SignedContent>(
+        self,
+        content: &T,
+        signer: &S,
+        issuer_key: &S::KeyId,
+    ) -> Result<SignedObject, SigningError<S::Error>> {
+        self.finalize(
+            Oid(T::CONTENT_TYPE.0.into()),
+            content.to_content_bytes(),
+            signer,
+            issuer_key
+        )
+    }
+
     pub fn finalize<S: Signer>(
         self,
         content_type: Oid<Bytes>,
@@ -847,7 +1104,7 @@ impl SignedObjectBuilder {
         cert.set_authority_key_identifier(Some(issuer_pub.key_identifier()));
         cert.set_crl_uri(Some(self.crl_uri));
         cert.set_ca_issuer(Some(self.ca_issuer));
-        cert.set_signed_object(Some(self.signed_object));
+        cert.set_signed_object(self.signed_object);
         cert.set_v4_resources(self.v4_resources);
         cert.set_v6_resources(self.v6_resources);
         cert.set_as_resources(self.as_resources);
@@ -918,6 +1175,8 @@ impl io::Write for StartOfValue {
 
 #[cfg(test)]
 mod test {
+    use crate::manifest::ManifestContent;
+    use crate::roa::RouteOriginAttestation;
     use crate::tal::TalInfo;
     use super::*;
 
@@ -933,6 +1192,9 @@ mod test {
             include_bytes!("../test-data/ta.mft").as_ref(),
             false
         ).unwrap();
+        assert_eq!(obj.object_type(), ObjectType::Manifest);
+        assert!(obj.content_as::<ManifestContent>().is_ok());
+        assert!(obj.content_as::<RouteOriginAttestation>().is_err());
         obj.validate_at(&issuer, false, at).unwrap();
         let obj = SignedObject::decode(
             include_bytes!("../test-data/ca1.mft").as_ref(),
@@ -940,6 +1202,18 @@ mod test {
         ).unwrap();
         assert!(obj.validate_at(&issuer, false, at).is_err());
     }
+
+    #[test]
+    fn object_type_from_content_type() {
+        assert!(CONTENT_TYPES.is_sorted());
+        assert_eq!(
+            ObjectType::from_content_type(&oid::CT_ASPA), ObjectType::Aspa
+        );
+        assert_eq!(
+            ObjectType::from_content_type(&oid::SIGNED_DATA),
+            ObjectType::Unknown
+        );
+    }
 }
 
 #[cfg(all(test, feature="softkeys"))]
@@ -993,6 +1267,44 @@ mod signer_test {
         ).unwrap();
         sigobj.validate(&cert, true).unwrap();
     }
+
+    #[test]
+    fn signing_times() {
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let uri = uri::Rsync::from_str("rsync://example.com/m/p").unwrap();
+        let time = Time::utc(2021, 3, 1, 12, 0, 0);
+
+        let encode = |signing_time, binary_signing_time| {
+            let mut sigobj = SignedObjectBuilder::new(
+                12u64.into(), Validity::from_secs(86400), uri.clone(),
+                uri.clone(), uri.clone()
+            );
+            sigobj.set_v4_resources_inherit();
+            sigobj.set_signing_time(signing_time);
+            sigobj.set_binary_signing_time(binary_signing_time);
+            let sigobj = sigobj.finalize(
+                Oid(oid::SIGNED_DATA.0.into()),
+                Bytes::from(b"1234".as_ref()),
+                &signer,
+                &key,
+            ).unwrap();
+            let sigobj = sigobj.encode_ref().to_captured(Mode::Der);
+            SignedObject::decode(sigobj.as_slice(), true).unwrap()
+        };
+
+        let sigobj = encode(None, None);
+        assert_eq!(sigobj.signing_time(), None);
+        assert_eq!(sigobj.binary_signing_time(), None);
+
+        let sigobj = encode(None, Some(time.timestamp() as u64));
+        assert_eq!(sigobj.signing_time(), None);
+        assert_eq!(sigobj.binary_signing_time(), Some(1_614_600_000));
+
+        let sigobj = encode(Some(time), Some(time.timestamp() as u64));
+        assert_eq!(sigobj.signing_time(), Some(time));
+        assert_eq!(sigobj.binary_signing_time(), Some(1_614_600_000));
+    }
 }
 
 
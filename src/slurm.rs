@@ -0,0 +1,867 @@
+//! Local exceptions via SLURM.
+//!
+//! Simplified Local Internet Number Resource Management with the RPKI
+//! (SLURM) defined in [RFC 8416] allows an operator to override the data
+//! produced by RPKI validation. A SLURM file contains filters that remove
+//! validated payload and assertions that add payload.
+//!
+//! The type [`SlurmFile`] represents the content of such a file. It can be
+//! read from and written to JSON, checked for internal conflicts, and
+//! applied to a set of [`Payload`] items.
+//!
+//! The module is only available if the `slurm` feature is enabled.
+//!
+//! [RFC 8416]: https://tools.ietf.org/html/rfc8416
+//! [`SlurmFile`]: struct.SlurmFile.html
+//! [`Payload`]: ../payload/enum.Payload.html
+
+use std::{error, fmt, io};
+use std::collections::HashSet;
+use std::str::FromStr;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use crate::cert::RouterKey;
+use crate::crypto::KeyIdentifier;
+use crate::resources::AsId;
+use crate::payload::{Payload, Prefix, PrefixError, RouteOrigin};
+
+
+//------------ SlurmFile -----------------------------------------------------
+
+/// The content of a SLURM file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct SlurmFile {
+    /// The filters removing validated payload.
+    filters: ValidationOutputFilters,
+
+    /// The assertions adding payload.
+    assertions: LocallyAddedAssertions,
+}
+
+impl SlurmFile {
+    /// Creates a new SLURM file from filters and assertions.
+    pub fn new(
+        filters: ValidationOutputFilters,
+        assertions: LocallyAddedAssertions,
+    ) -> Self {
+        SlurmFile { filters, assertions }
+    }
+
+    /// Reads a SLURM file from its JSON representation.
+    ///
+    /// The file is checked via [`validate`] after reading.
+    ///
+    /// [`validate`]: #method.validate
+    pub fn from_json(json: &str) -> Result<Self, ReadError> {
+        Self::from_raw(serde_json::from_str(json)?)
+    }
+
+    /// Reads a SLURM file from a reader.
+    ///
+    /// The file is checked via [`validate`] after reading.
+    ///
+    /// [`validate`]: #method.validate
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Self, ReadError> {
+        Self::from_raw(serde_json::from_reader(reader)?)
+    }
+
+    /// Returns the JSON representation of the file.
+    pub fn to_json(&self) -> String {
+        // Serializing the raw types can’t fail.
+        serde_json::to_string_pretty(&self.to_raw()).unwrap()
+    }
+
+    /// Writes the JSON representation of the file to a writer.
+    pub fn write<W: io::Write>(&self, writer: W) -> Result<(), io::Error> {
+        serde_json::to_writer_pretty(writer, &self.to_raw())
+            .map_err(Into::into)
+    }
+
+    /// Returns the filters of the file.
+    pub fn filters(&self) -> &ValidationOutputFilters {
+        &self.filters
+    }
+
+    /// Returns the assertions of the file.
+    pub fn assertions(&self) -> &LocallyAddedAssertions {
+        &self.assertions
+    }
+
+    /// Checks that the file is internally consistent.
+    ///
+    /// Each filter has to match on at least one field, maximum prefix
+    /// lengths have to fit the prefix, and no assertion may be removed
+    /// again by a filter of the same file.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        for (index, filter) in self.filters.prefix.iter().enumerate() {
+            if filter.prefix.is_none() && filter.asn.is_none() {
+                return Err(ValidationError::EmptyPrefixFilter(index))
+            }
+        }
+        for (index, filter) in self.filters.bgpsec.iter().enumerate() {
+            if filter.asn.is_none() && filter.ski.is_none() {
+                return Err(ValidationError::EmptyBgpsecFilter(index))
+            }
+        }
+        for (index, assertion) in self.assertions.prefix.iter().enumerate() {
+            let origin = match assertion.to_origin() {
+                Some(origin) => origin,
+                None => return Err(ValidationError::BadMaxLength(index))
+            };
+            let filter = self.filters.prefix.iter().position(|filter| {
+                filter.matches(&origin)
+            });
+            if let Some(filter) = filter {
+                return Err(ValidationError::PrefixConflict {
+                    assertion: index, filter
+                })
+            }
+        }
+        for (index, assertion) in self.assertions.bgpsec.iter().enumerate() {
+            let key = assertion.to_router_key();
+            let filter = self.filters.bgpsec.iter().position(|filter| {
+                filter.matches(&key)
+            });
+            if let Some(filter) = filter {
+                return Err(ValidationError::BgpsecConflict {
+                    assertion: index, filter
+                })
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns whether a payload item is removed by the file’s filters.
+    ///
+    /// Payload types not covered by SLURM are never removed.
+    pub fn is_filtered(&self, item: &Payload) -> bool {
+        match *item {
+            Payload::Origin(ref origin) => {
+                self.filters.prefix.iter().any(|filter| {
+                    filter.matches(origin)
+                })
+            }
+            Payload::RouterKey(ref key) => {
+                self.filters.bgpsec.iter().any(|filter| filter.matches(key))
+            }
+            _ => false
+        }
+    }
+
+    /// Returns an iterator over the payload added by the file.
+    ///
+    /// Prefix assertions with an invalid maximum length are skipped.
+    pub fn asserted<'a>(&'a self) -> impl Iterator<Item = Payload> + 'a {
+        self.assertions.prefix.iter().filter_map(|assertion| {
+            assertion.to_origin().map(Into::into)
+        }).chain(self.assertions.bgpsec.iter().map(|assertion| {
+            assertion.to_router_key().into()
+        }))
+    }
+
+    /// Applies the file to a set of validated payload.
+    ///
+    /// Removes all items matched by a filter and then adds all items
+    /// asserted by the file.
+    pub fn apply<I: IntoIterator<Item = Payload>>(
+        &self, payload: I
+    ) -> HashSet<Payload> {
+        let mut res: HashSet<_> = payload.into_iter().filter(|item| {
+            !self.is_filtered(item)
+        }).collect();
+        res.extend(self.asserted());
+        res
+    }
+
+    /// Converts the raw JSON data into a file and validates it.
+    fn from_raw(raw: RawFile) -> Result<Self, ReadError> {
+        if raw.slurm_version != 1 {
+            return Err(ReadError::UnsupportedVersion(raw.slurm_version))
+        }
+        let filters = raw.validation_output_filters;
+        let assertions = raw.locally_added_assertions;
+        let res = SlurmFile {
+            filters: ValidationOutputFilters {
+                prefix: filters.prefix_filters.into_iter()
+                    .map(PrefixFilter::from_raw)
+                    .collect::<Result<_, _>>()?,
+                bgpsec: filters.bgpsec_filters.into_iter()
+                    .map(BgpsecFilter::from_raw)
+                    .collect::<Result<_, _>>()?,
+            },
+            assertions: LocallyAddedAssertions {
+                prefix: assertions.prefix_assertions.into_iter()
+                    .map(PrefixAssertion::from_raw)
+                    .collect::<Result<_, _>>()?,
+                bgpsec: assertions.bgpsec_assertions.into_iter()
+                    .map(BgpsecAssertion::from_raw)
+                    .collect::<Result<_, _>>()?,
+            }
+        };
+        res.validate()?;
+        Ok(res)
+    }
+
+    /// Converts the file into the raw JSON data.
+    fn to_raw(&self) -> RawFile {
+        RawFile {
+            slurm_version: 1,
+            validation_output_filters: RawFilters {
+                prefix_filters: self.filters.prefix.iter()
+                    .map(PrefixFilter::to_raw).collect(),
+                bgpsec_filters: self.filters.bgpsec.iter()
+                    .map(BgpsecFilter::to_raw).collect(),
+            },
+            locally_added_assertions: RawAssertions {
+                prefix_assertions: self.assertions.prefix.iter()
+                    .map(PrefixAssertion::to_raw).collect(),
+                bgpsec_assertions: self.assertions.bgpsec.iter()
+                    .map(BgpsecAssertion::to_raw).collect(),
+            }
+        }
+    }
+}
+
+
+//------------ ValidationOutputFilters ---------------------------------------
+
+/// The filters of a SLURM file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ValidationOutputFilters {
+    /// The filters for route origins.
+    pub prefix: Vec<PrefixFilter>,
+
+    /// The filters for router keys.
+    pub bgpsec: Vec<BgpsecFilter>,
+}
+
+
+//------------ LocallyAddedAssertions ----------------------------------------
+
+/// The assertions of a SLURM file.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct LocallyAddedAssertions {
+    /// The assertions for route origins.
+    pub prefix: Vec<PrefixAssertion>,
+
+    /// The assertions for router keys.
+    pub bgpsec: Vec<BgpsecAssertion>,
+}
+
+
+//------------ PrefixFilter --------------------------------------------------
+
+/// A filter for route origins.
+///
+/// The filter removes all route origins whose prefix is covered by the
+/// filter’s prefix and whose AS number is the filter’s AS number. If either
+/// is missing, it matches any value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrefixFilter {
+    /// The prefix to match.
+    prefix: Option<Prefix>,
+
+    /// The AS number to match.
+    asn: Option<AsId>,
+
+    /// An optional comment.
+    comment: Option<String>,
+}
+
+impl PrefixFilter {
+    /// Creates a new prefix filter.
+    pub fn new(
+        prefix: Option<Prefix>, asn: Option<AsId>, comment: Option<String>
+    ) -> Self {
+        PrefixFilter { prefix, asn, comment }
+    }
+
+    /// Returns the prefix to match.
+    pub fn prefix(&self) -> Option<Prefix> {
+        self.prefix
+    }
+
+    /// Returns the AS number to match.
+    pub fn asn(&self) -> Option<AsId> {
+        self.asn
+    }
+
+    /// Returns the comment.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns whether the filter matches a route origin.
+    pub fn matches(&self, origin: &RouteOrigin) -> bool {
+        if let Some(prefix) = self.prefix {
+            if !prefix.covers(origin.prefix()) {
+                return false
+            }
+        }
+        if let Some(asn) = self.asn {
+            if asn != origin.asn() {
+                return false
+            }
+        }
+        true
+    }
+
+    fn from_raw(raw: RawPrefixFilter) -> Result<Self, ReadError> {
+        Ok(PrefixFilter {
+            prefix: match raw.prefix {
+                Some(prefix) => Some(Prefix::from_str(&prefix)?),
+                None => None,
+            },
+            asn: raw.asn.map(Into::into),
+            comment: raw.comment,
+        })
+    }
+
+    fn to_raw(&self) -> RawPrefixFilter {
+        RawPrefixFilter {
+            prefix: self.prefix.map(|prefix| prefix.to_string()),
+            asn: self.asn.map(Into::into),
+            comment: self.comment.clone(),
+        }
+    }
+}
+
+
+//------------ BgpsecFilter --------------------------------------------------
+
+/// A filter for router keys.
+///
+/// The filter removes all router keys with the filter’s AS number and
+/// subject key identifier. If either is missing, it matches any value.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BgpsecFilter {
+    /// The AS number to match.
+    asn: Option<AsId>,
+
+    /// The subject key identifier to match.
+    ski: Option<KeyIdentifier>,
+
+    /// An optional comment.
+    comment: Option<String>,
+}
+
+impl BgpsecFilter {
+    /// Creates a new BGPsec filter.
+    pub fn new(
+        asn: Option<AsId>,
+        ski: Option<KeyIdentifier>,
+        comment: Option<String>
+    ) -> Self {
+        BgpsecFilter { asn, ski, comment }
+    }
+
+    /// Returns the AS number to match.
+    pub fn asn(&self) -> Option<AsId> {
+        self.asn
+    }
+
+    /// Returns the subject key identifier to match.
+    pub fn ski(&self) -> Option<KeyIdentifier> {
+        self.ski
+    }
+
+    /// Returns the comment.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns whether the filter matches a router key.
+    pub fn matches(&self, key: &RouterKey) -> bool {
+        if let Some(asn) = self.asn {
+            if asn != key.asn() {
+                return false
+            }
+        }
+        if let Some(ski) = self.ski {
+            if ski != key.key_identifier() {
+                return false
+            }
+        }
+        true
+    }
+
+    fn from_raw(raw: RawBgpsecFilter) -> Result<Self, ReadError> {
+        Ok(BgpsecFilter {
+            asn: raw.asn.map(Into::into),
+            ski: match raw.ski {
+                Some(ski) => Some(decode_ski(&ski)?),
+                None => None,
+            },
+            comment: raw.comment,
+        })
+    }
+
+    fn to_raw(&self) -> RawBgpsecFilter {
+        RawBgpsecFilter {
+            asn: self.asn.map(Into::into),
+            ski: self.ski.map(|ski| ski.to_base64url()),
+            comment: self.comment.clone(),
+        }
+    }
+}
+
+
+//------------ PrefixAssertion -----------------------------------------------
+
+/// An assertion adding a route origin.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrefixAssertion {
+    /// The prefix of the route origin.
+    prefix: Prefix,
+
+    /// The AS number of the route origin.
+    asn: AsId,
+
+    /// The maximum prefix length.
+    ///
+    /// If this is missing, the length of the prefix is used.
+    max_len: Option<u8>,
+
+    /// An optional comment.
+    comment: Option<String>,
+}
+
+impl PrefixAssertion {
+    /// Creates a new prefix assertion.
+    pub fn new(
+        prefix: Prefix,
+        asn: AsId,
+        max_len: Option<u8>,
+        comment: Option<String>
+    ) -> Self {
+        PrefixAssertion { prefix, asn, max_len, comment }
+    }
+
+    /// Returns the prefix of the route origin.
+    pub fn prefix(&self) -> Prefix {
+        self.prefix
+    }
+
+    /// Returns the AS number of the route origin.
+    pub fn asn(&self) -> AsId {
+        self.asn
+    }
+
+    /// Returns the maximum prefix length if given.
+    pub fn max_len(&self) -> Option<u8> {
+        self.max_len
+    }
+
+    /// Returns the comment.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the route origin added by the assertion.
+    ///
+    /// Returns `None` if the maximum length is invalid for the prefix.
+    pub fn to_origin(&self) -> Option<RouteOrigin> {
+        RouteOrigin::new(
+            self.prefix,
+            self.max_len.unwrap_or_else(|| self.prefix.prefix_len()),
+            self.asn
+        )
+    }
+
+    fn from_raw(raw: RawPrefixAssertion) -> Result<Self, ReadError> {
+        Ok(PrefixAssertion {
+            prefix: Prefix::from_str(&raw.prefix)?,
+            asn: raw.asn.into(),
+            max_len: raw.max_prefix_length,
+            comment: raw.comment,
+        })
+    }
+
+    fn to_raw(&self) -> RawPrefixAssertion {
+        RawPrefixAssertion {
+            prefix: self.prefix.to_string(),
+            asn: self.asn.into(),
+            max_prefix_length: self.max_len,
+            comment: self.comment.clone(),
+        }
+    }
+}
+
+
+//------------ BgpsecAssertion -----------------------------------------------
+
+/// An assertion adding a router key.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BgpsecAssertion {
+    /// The AS number of the router key.
+    asn: AsId,
+
+    /// The subject key identifier of the router key.
+    ski: KeyIdentifier,
+
+    /// The encoded subject public key info of the router key.
+    key_info: Bytes,
+
+    /// An optional comment.
+    comment: Option<String>,
+}
+
+impl BgpsecAssertion {
+    /// Creates a new BGPsec assertion.
+    pub fn new(
+        asn: AsId,
+        ski: KeyIdentifier,
+        key_info: Bytes,
+        comment: Option<String>
+    ) -> Self {
+        BgpsecAssertion { asn, ski, key_info, comment }
+    }
+
+    /// Returns the AS number of the router key.
+    pub fn asn(&self) -> AsId {
+        self.asn
+    }
+
+    /// Returns the subject key identifier of the router key.
+    pub fn ski(&self) -> KeyIdentifier {
+        self.ski
+    }
+
+    /// Returns the encoded subject public key info of the router key.
+    pub fn key_info(&self) -> &Bytes {
+        &self.key_info
+    }
+
+    /// Returns the comment.
+    pub fn comment(&self) -> Option<&str> {
+        self.comment.as_deref()
+    }
+
+    /// Returns the router key added by the assertion.
+    pub fn to_router_key(&self) -> RouterKey {
+        RouterKey::new(self.asn, self.ski, self.key_info.clone())
+    }
+
+    fn from_raw(raw: RawBgpsecAssertion) -> Result<Self, ReadError> {
+        Ok(BgpsecAssertion {
+            asn: raw.asn.into(),
+            ski: decode_ski(&raw.ski)?,
+            key_info: base64::decode_config(
+                &raw.router_public_key, base64::URL_SAFE_NO_PAD
+            ).map_err(|_| ReadError::BadRouterPublicKey)?.into(),
+            comment: raw.comment,
+        })
+    }
+
+    fn to_raw(&self) -> RawBgpsecAssertion {
+        RawBgpsecAssertion {
+            asn: self.asn.into(),
+            ski: self.ski.to_base64url(),
+            router_public_key: base64::encode_config(
+                &self.key_info, base64::URL_SAFE_NO_PAD
+            ),
+            comment: self.comment.clone(),
+        }
+    }
+}
+
+
+//------------ Raw JSON Types ------------------------------------------------
+
+/// The JSON representation of a SLURM file.
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawFile {
+    slurm_version: u32,
+    validation_output_filters: RawFilters,
+    locally_added_assertions: RawAssertions,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawFilters {
+    prefix_filters: Vec<RawPrefixFilter>,
+    bgpsec_filters: Vec<RawBgpsecFilter>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawAssertions {
+    prefix_assertions: Vec<RawPrefixAssertion>,
+    bgpsec_assertions: Vec<RawBgpsecAssertion>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawPrefixFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asn: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawBgpsecFilter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    asn: Option<u32>,
+    #[serde(
+        rename = "SKI", default, skip_serializing_if = "Option::is_none"
+    )]
+    ski: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawPrefixAssertion {
+    prefix: String,
+    asn: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_prefix_length: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct RawBgpsecAssertion {
+    asn: u32,
+    #[serde(rename = "SKI")]
+    ski: String,
+    router_public_key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    comment: Option<String>,
+}
+
+/// Decodes a subject key identifier in base64url.
+fn decode_ski(ski: &str) -> Result<KeyIdentifier, ReadError> {
+    KeyIdentifier::from_base64url(ski).map_err(|_| ReadError::BadSki)
+}
+
+
+//============ Errors ========================================================
+
+//------------ ValidationError -----------------------------------------------
+
+/// A SLURM file is not internally consistent.
+///
+/// The values are the indexes of the offending filters or assertions.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// A prefix filter matches neither prefix nor AS number.
+    EmptyPrefixFilter(usize),
+
+    /// A BGPsec filter matches neither AS number nor key identifier.
+    EmptyBgpsecFilter(usize),
+
+    /// The maximum length of a prefix assertion is invalid.
+    BadMaxLength(usize),
+
+    /// A prefix assertion is removed by a prefix filter.
+    PrefixConflict { assertion: usize, filter: usize },
+
+    /// A BGPsec assertion is removed by a BGPsec filter.
+    BgpsecConflict { assertion: usize, filter: usize },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::EmptyPrefixFilter(index) => {
+                write!(f, "prefix filter {} is empty", index)
+            }
+            ValidationError::EmptyBgpsecFilter(index) => {
+                write!(f, "BGPsec filter {} is empty", index)
+            }
+            ValidationError::BadMaxLength(index) => {
+                write!(
+                    f, "prefix assertion {} has invalid max length", index
+                )
+            }
+            ValidationError::PrefixConflict { assertion, filter } => {
+                write!(
+                    f, "prefix assertion {} is removed by prefix filter {}",
+                    assertion, filter
+                )
+            }
+            ValidationError::BgpsecConflict { assertion, filter } => {
+                write!(
+                    f, "BGPsec assertion {} is removed by BGPsec filter {}",
+                    assertion, filter
+                )
+            }
+        }
+    }
+}
+
+impl error::Error for ValidationError { }
+
+
+//------------ ReadError -----------------------------------------------------
+
+/// Reading a SLURM file failed.
+#[derive(Debug)]
+pub enum ReadError {
+    /// The file wasn’t valid JSON or didn’t follow the SLURM schema.
+    Json(serde_json::Error),
+
+    /// The file has an unsupported SLURM version.
+    UnsupportedVersion(u32),
+
+    /// A prefix was invalid.
+    BadPrefix,
+
+    /// A subject key identifier was invalid.
+    BadSki,
+
+    /// A router public key wasn’t valid base64url.
+    BadRouterPublicKey,
+
+    /// The file is not internally consistent.
+    Invalid(ValidationError),
+}
+
+impl From<serde_json::Error> for ReadError {
+    fn from(err: serde_json::Error) -> Self {
+        ReadError::Json(err)
+    }
+}
+
+impl From<PrefixError> for ReadError {
+    fn from(_: PrefixError) -> Self {
+        ReadError::BadPrefix
+    }
+}
+
+impl From<ValidationError> for ReadError {
+    fn from(err: ValidationError) -> Self {
+        ReadError::Invalid(err)
+    }
+}
+
+impl fmt::Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReadError::Json(ref err) => err.fmt(f),
+            ReadError::UnsupportedVersion(version) => {
+                write!(f, "unsupported SLURM version {}", version)
+            }
+            ReadError::BadPrefix => f.write_str("invalid prefix"),
+            ReadError::BadSki => f.write_str("invalid SKI"),
+            ReadError::BadRouterPublicKey => {
+                f.write_str("invalid router public key")
+            }
+            ReadError::Invalid(ref err) => err.fmt(f),
+        }
+    }
+}
+
+impl error::Error for ReadError { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const EXAMPLE: &str = r#"{
+        "slurmVersion": 1,
+        "validationOutputFilters": {
+            "prefixFilters": [
+                { "prefix": "192.0.2.0/24", "comment": "All VRPs" },
+                { "asn": 64496 },
+                { "prefix": "198.51.100.0/24", "asn": 64497 }
+            ],
+            "bgpsecFilters": [
+                { "asn": 64496 },
+                { "SKI": "Zm9vYmFyYmF6Zm9vYmFyYmF6LWE" }
+            ]
+        },
+        "locallyAddedAssertions": {
+            "prefixAssertions": [
+                { "asn": 64499, "prefix": "198.51.100.0/24" },
+                {
+                    "asn": 64498, "prefix": "2001:db8::/32",
+                    "maxPrefixLength": 48, "comment": "Local"
+                }
+            ],
+            "bgpsecAssertions": [
+                {
+                    "asn": 64497, "SKI": "Zm9vYmFyYmF6Zm9vYmFyYmF6LWI",
+                    "routerPublicKey": "a2V5"
+                }
+            ]
+        }
+    }"#;
+
+    fn origin(prefix: &str, max_len: u8, asn: u32) -> Payload {
+        RouteOrigin::new(
+            Prefix::from_str(prefix).unwrap(), max_len, asn.into()
+        ).unwrap().into()
+    }
+
+    #[test]
+    fn read_apply_write() {
+        let slurm = SlurmFile::from_json(EXAMPLE).unwrap();
+        assert_eq!(slurm.filters().prefix.len(), 3);
+        assert_eq!(slurm.filters().bgpsec.len(), 2);
+        assert_eq!(slurm.assertions().prefix[1].max_len(), Some(48));
+        assert_eq!(
+            slurm.assertions().bgpsec[0].key_info().as_ref(), b"key"
+        );
+
+        let kept = origin("203.0.113.0/24", 24, 64497);
+        let res = slurm.apply(vec![
+            origin("192.0.2.0/25", 25, 64497),
+            origin("203.0.113.0/24", 24, 64496),
+            origin("198.51.100.0/24", 24, 64497),
+            kept.clone(),
+        ]);
+        assert_eq!(res.len(), 4);
+        assert!(res.contains(&kept));
+        assert!(res.contains(&origin("198.51.100.0/24", 24, 64499)));
+        assert!(res.contains(&origin("2001:db8::/32", 48, 64498)));
+
+        assert_eq!(SlurmFile::from_json(&slurm.to_json()).unwrap(), slurm);
+    }
+
+    #[test]
+    fn invalid() {
+        match SlurmFile::from_json(&EXAMPLE.replace(
+            "\"slurmVersion\": 1", "\"slurmVersion\": 2"
+        )) {
+            Err(ReadError::UnsupportedVersion(2)) => { }
+            res => panic!("unexpected {:?}", res)
+        }
+        match SlurmFile::from_json(&EXAMPLE.replace(
+            "{ \"asn\": 64496 }", "{ \"comment\": \"empty\" }"
+        )) {
+            Err(ReadError::Invalid(
+                ValidationError::EmptyPrefixFilter(1)
+            )) => { }
+            res => panic!("unexpected {:?}", res)
+        }
+        match SlurmFile::from_json(&EXAMPLE.replace(
+            "\"asn\": 64499", "\"asn\": 64497"
+        )) {
+            Err(ReadError::Invalid(ValidationError::PrefixConflict {
+                assertion: 0, filter: 2
+            })) => { }
+            res => panic!("unexpected {:?}", res)
+        }
+        match SlurmFile::from_json(&EXAMPLE.replace(
+            "\"maxPrefixLength\": 48", "\"maxPrefixLength\": 24"
+        )) {
+            Err(ReadError::Invalid(ValidationError::BadMaxLength(1))) => { }
+            res => panic!("unexpected {:?}", res)
+        }
+        assert!(SlurmFile::from_json(&EXAMPLE.replace(
+            "\"comment\": \"Local\"", "\"remark\": \"Local\""
+        )).is_err());
+    }
+}
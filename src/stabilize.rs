@@ -0,0 +1,289 @@
+//! Suppressing flapping validated payload.
+//!
+//! When a CA publishes inconsistently, for instance because it is being
+//! updated in a non-atomic way or some of its objects are intermittently
+//! unavailable, the validated payload derived from it may appear and
+//! disappear in consecutive validation runs. Passing these changes on to
+//! routers results in needless churn in the routing system.
+//!
+//! The [`Stabilizer`] in this module can be placed between validation and
+//! the consumer of its output. It keeps a short history of each item and
+//! holds on to the last published state of items that changed state more
+//! than once within a configurable number of runs. Items that change only
+//! once within that window, i.e., genuinely new or withdrawn items, pass
+//! through immediately. An item that disappears again shortly after it
+//! first appeared, however, is kept until its appearance has left the
+//! window.
+//!
+//! The stabilizer is generic over the payload type, so it can be used for
+//! validated ROA payload (VRPs) as well as any other kind of payload.
+//!
+//! [`Stabilizer`]: struct.Stabilizer.html
+
+use std::collections::HashMap;
+use std::collections::hash_map;
+use std::hash::Hash;
+
+
+//------------ Stabilizer ----------------------------------------------------
+
+/// A layer suppressing payload items flapping in and out.
+#[derive(Clone, Debug)]
+pub struct Stabilizer<T: Eq + Hash> {
+    /// The number of runs to consider.
+    window: u32,
+
+    /// The history of all items seen during the window.
+    items: HashMap<T, History>,
+
+    /// The accumulated metrics.
+    metrics: StabilizerMetrics,
+}
+
+impl<T: Clone + Eq + Hash> Stabilizer<T> {
+    /// The maximum number of runs that can be considered.
+    pub const MAX_WINDOW: u32 = 64;
+
+    /// Creates a new stabilizer considering the given number of runs.
+    ///
+    /// An item is considered flapping if it changes state at least twice
+    /// within `window` runs. The window is capped at `MAX_WINDOW`. A window
+    /// of less than three runs disables suppression since an item can’t
+    /// change state twice within it.
+    pub fn new(window: u32) -> Self {
+        Stabilizer {
+            window: window.min(Self::MAX_WINDOW),
+            items: HashMap::new(),
+            metrics: StabilizerMetrics::default(),
+        }
+    }
+
+    /// Returns the number of runs considered.
+    pub fn window(&self) -> u32 {
+        self.window
+    }
+
+    /// Processes the outcome of a validation run.
+    ///
+    /// The iterator needs to produce the complete set of items of the run.
+    /// Afterwards, the stabilized set is available via [`iter`].
+    ///
+    /// Returns the metrics for this run.
+    ///
+    /// [`iter`]: #method.iter
+    pub fn update<I: IntoIterator<Item = T>>(
+        &mut self, current: I
+    ) -> RunMetrics {
+        for item in self.items.values_mut() {
+            item.shift();
+        }
+        for item in current {
+            self.items.entry(item).or_default().mark_present();
+        }
+
+        let mut res = RunMetrics::default();
+        let mask = window_mask(self.window);
+        self.items.retain(|_, item| {
+            let present = item.is_present();
+            if item.transitions(mask) < 2 {
+                item.published = present;
+            }
+            else {
+                res.flapping += 1;
+                if present != item.published {
+                    if present {
+                        res.suppressed_announcements += 1;
+                    }
+                    else {
+                        res.suppressed_withdrawals += 1;
+                    }
+                }
+            }
+            item.published || item.seen & mask != 0
+        });
+        self.metrics.runs += 1;
+        self.metrics.suppressed_announcements += res.suppressed_announcements;
+        self.metrics.suppressed_withdrawals += res.suppressed_withdrawals;
+        res
+    }
+
+    /// Returns whether an item is part of the stabilized set.
+    pub fn contains(&self, item: &T) -> bool {
+        self.items.get(item).map(|item| item.published).unwrap_or(false)
+    }
+
+    /// Returns an iterator over the stabilized set.
+    pub fn iter(&self) -> StabilizedIter<'_, T> {
+        StabilizedIter(self.items.iter())
+    }
+
+    /// Returns the metrics accumulated over all runs.
+    pub fn metrics(&self) -> StabilizerMetrics {
+        self.metrics
+    }
+}
+
+/// Returns the bit mask for the history bits covering the window.
+fn window_mask(window: u32) -> u64 {
+    if window >= 64 {
+        !0
+    }
+    else {
+        (1 << window) - 1
+    }
+}
+
+
+//------------ History -------------------------------------------------------
+
+/// The history of an item.
+#[derive(Clone, Copy, Debug, Default)]
+struct History {
+    /// Whether the item was present in each run.
+    ///
+    /// The lowest bit is the current run, the next bit the run before that,
+    /// and so on.
+    seen: u64,
+
+    /// Whether the item is currently part of the stabilized set.
+    published: bool,
+}
+
+impl History {
+    fn shift(&mut self) {
+        self.seen <<= 1
+    }
+
+    fn mark_present(&mut self) {
+        self.seen |= 1
+    }
+
+    fn is_present(self) -> bool {
+        self.seen & 1 != 0
+    }
+
+    /// Returns the number of state changes within the window.
+    fn transitions(self, mask: u64) -> u32 {
+        // Each bit of seen ^ (seen >> 1) marks a change between two
+        // consecutive runs. Only changes between runs both within the
+        // window count, so we need to drop the topmost bit of the mask.
+        ((self.seen ^ (self.seen >> 1)) & (mask >> 1)).count_ones()
+    }
+}
+
+
+//------------ StabilizedIter ------------------------------------------------
+
+/// An iterator over the stabilized set of items.
+pub struct StabilizedIter<'a, T>(hash_map::Iter<'a, T, History>);
+
+impl<'a, T> Iterator for StabilizedIter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (key, item) = self.0.next()?;
+            if item.published {
+                return Some(key)
+            }
+        }
+    }
+}
+
+
+//------------ RunMetrics ----------------------------------------------------
+
+/// Metrics for a single run of the stabilizer.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RunMetrics {
+    /// The number of items currently considered flapping.
+    pub flapping: usize,
+
+    /// The number of items present in the run but suppressed.
+    pub suppressed_announcements: usize,
+
+    /// The number of items missing in the run but still published.
+    pub suppressed_withdrawals: usize,
+}
+
+
+//------------ StabilizerMetrics ---------------------------------------------
+
+/// Metrics accumulated over all runs of a stabilizer.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct StabilizerMetrics {
+    /// The number of runs processed.
+    pub runs: u64,
+
+    /// The total number of suppressed announcements.
+    pub suppressed_announcements: usize,
+
+    /// The total number of suppressed withdrawals.
+    pub suppressed_withdrawals: usize,
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn stabilize() {
+        let mut stab = Stabilizer::new(4);
+
+        // New items pass through immediately.
+        stab.update(vec![1, 2]);
+        assert!(stab.contains(&1) && stab.contains(&2));
+
+        // Item 2 disappears right after appearing. Its withdrawal is held
+        // back until the appearance has left the window.
+        let metrics = stab.update(vec![1]);
+        assert!(stab.contains(&2));
+        assert_eq!(metrics.flapping, 1);
+        assert_eq!(metrics.suppressed_withdrawals, 1);
+        let metrics = stab.update(vec![1]);
+        assert!(stab.contains(&2));
+        assert_eq!(metrics.suppressed_withdrawals, 1);
+        stab.update(vec![1]);
+        assert!(!stab.contains(&2));
+        assert_eq!(stab.iter().collect::<Vec<_>>(), [&1]);
+
+        // Now item 2 has been forgotten.
+        stab.update(vec![1]);
+        assert_eq!(stab.items.len(), 1);
+
+        // Stable item 1 disappears and immediately comes back.
+        stab.update(vec![]);
+        assert!(!stab.contains(&1));
+        let metrics = stab.update(vec![1]);
+        assert!(!stab.contains(&1));
+        assert_eq!(metrics.suppressed_announcements, 1);
+
+        assert_eq!(
+            stab.metrics(),
+            StabilizerMetrics {
+                runs: 7,
+                suppressed_announcements: 1,
+                suppressed_withdrawals: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn small_window() {
+        let mut stab = Stabilizer::new(2);
+        for i in 0..10 {
+            if i % 2 == 0 {
+                stab.update(vec![1]);
+                assert!(stab.contains(&1));
+            }
+            else {
+                stab.update(vec![]);
+                assert!(!stab.contains(&1));
+            }
+        }
+        assert_eq!(stab.metrics().suppressed_announcements, 0);
+    }
+}
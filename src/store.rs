@@ -0,0 +1,912 @@
+//! Storing repository objects.
+//!
+//! This module defines the [`ObjectStore`] trait, the interface through
+//! which the components of a relying party or publication server keep the
+//! objects of RPKI repositories. Objects are identified by their rsync
+//! URI. Along with their content, the store keeps their SHA-256 hash and
+//! the time they were stored.
+//!
+//! Three implementations are provided: [`MemoryStore`] keeps all objects
+//! in memory, [`FileStore`] keeps each object in a file of its own, and
+//! [`DedupStore`] keeps the content of identical objects only once.
+//!
+//! On top of an object store, [`ValidatedCache`] keeps the objects of the
+//! last successful validation of each publication point for use by the
+//! validator.
+//!
+//! [`ObjectStore`]: trait.ObjectStore.html
+//! [`MemoryStore`]: struct.MemoryStore.html
+//! [`FileStore`]: struct.FileStore.html
+//! [`DedupStore`]: struct.DedupStore.html
+//! [`ValidatedCache`]: struct.ValidatedCache.html
+
+use std::{fs, io};
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use bytes::Bytes;
+use chrono::{TimeZone, Utc};
+use crate::uri;
+use crate::rrdp::Hash;
+use crate::util::path::uri_path;
+use crate::validation::{ObjectSource, ValidatedStore};
+use crate::x509::Time;
+
+
+//------------ ObjectStore ---------------------------------------------------
+
+/// A store for the objects of RPKI repositories.
+///
+/// All methods take a shared reference so that a store can be used by
+/// several components at once. Implementations use interior mutability
+/// where necessary.
+pub trait ObjectStore {
+    /// Returns the object with the given URI if present.
+    fn get(
+        &self, uri: &uri::Rsync
+    ) -> Result<Option<StoredObject>, io::Error>;
+
+    /// Stores an object under the given URI.
+    ///
+    /// An object already stored under the URI is replaced. The time is
+    /// recorded with the object.
+    fn put(
+        &self, uri: &uri::Rsync, content: Bytes, time: Time
+    ) -> Result<(), io::Error>;
+
+    /// Deletes the object with the given URI.
+    ///
+    /// Returns whether an object was present.
+    fn delete(&self, uri: &uri::Rsync) -> Result<bool, io::Error>;
+
+    /// Returns the URIs and information of all objects below a URI.
+    ///
+    /// An object is listed if `prefix` is a parent of its URI as defined
+    /// by [`uri::Rsync::is_parent_of`]. The order of the objects is not
+    /// defined.
+    ///
+    /// [`uri::Rsync::is_parent_of`]: ../uri/struct.Rsync.html#method.is_parent_of
+    fn list(
+        &self, prefix: &uri::Rsync
+    ) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error>;
+}
+
+
+//------------ StoredObject --------------------------------------------------
+
+/// An object retrieved from a store.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StoredObject {
+    /// The content of the object.
+    content: Bytes,
+
+    /// The hash and storage time of the object.
+    info: ObjectInfo,
+}
+
+impl StoredObject {
+    /// Creates a new object from its content and storage time.
+    pub fn new(content: Bytes, time: Time) -> Self {
+        StoredObject {
+            info: ObjectInfo::new(Hash::from_data(&content), time),
+            content,
+        }
+    }
+
+    /// Returns the content of the object.
+    pub fn content(&self) -> &Bytes {
+        &self.content
+    }
+
+    /// Converts the object into its content.
+    pub fn into_content(self) -> Bytes {
+        self.content
+    }
+
+    /// Returns the hash and storage time of the object.
+    pub fn info(&self) -> ObjectInfo {
+        self.info
+    }
+
+    /// Returns the SHA-256 hash of the object’s content.
+    pub fn hash(&self) -> Hash {
+        self.info.hash
+    }
+
+    /// Returns the time the object was stored.
+    pub fn time(&self) -> Time {
+        self.info.time
+    }
+}
+
+
+//------------ ObjectInfo ----------------------------------------------------
+
+/// Information about a stored object.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ObjectInfo {
+    /// The SHA-256 hash of the object’s content.
+    hash: Hash,
+
+    /// The time the object was stored.
+    time: Time,
+}
+
+impl ObjectInfo {
+    /// Creates new information from a hash and a storage time.
+    pub fn new(hash: Hash, time: Time) -> Self {
+        ObjectInfo { hash, time }
+    }
+
+    /// Returns the SHA-256 hash of the object’s content.
+    pub fn hash(&self) -> Hash {
+        self.hash
+    }
+
+    /// Returns the time the object was stored.
+    pub fn time(&self) -> Time {
+        self.time
+    }
+}
+
+
+//------------ MemoryStore ---------------------------------------------------
+
+/// An object store keeping all objects in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    /// The stored objects.
+    objects: RwLock<HashMap<uri::Rsync, StoredObject>>,
+}
+
+impl MemoryStore {
+    /// Creates a new, empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of objects in the store.
+    pub fn len(&self) -> usize {
+        self.objects.read().unwrap().len()
+    }
+
+    /// Returns whether the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.objects.read().unwrap().is_empty()
+    }
+}
+
+impl ObjectStore for MemoryStore {
+    fn get(
+        &self, uri: &uri::Rsync
+    ) -> Result<Option<StoredObject>, io::Error> {
+        Ok(self.objects.read().unwrap().get(uri).cloned())
+    }
+
+    fn put(
+        &self, uri: &uri::Rsync, content: Bytes, time: Time
+    ) -> Result<(), io::Error> {
+        self.objects.write().unwrap().insert(
+            uri.clone(), StoredObject::new(content, time)
+        );
+        Ok(())
+    }
+
+    fn delete(&self, uri: &uri::Rsync) -> Result<bool, io::Error> {
+        Ok(self.objects.write().unwrap().remove(uri).is_some())
+    }
+
+    fn list(
+        &self, prefix: &uri::Rsync
+    ) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error> {
+        Ok(self.objects.read().unwrap().iter().filter_map(|(uri, obj)| {
+            if prefix.is_parent_of(uri) {
+                Some((uri.clone(), obj.info))
+            }
+            else {
+                None
+            }
+        }).collect())
+    }
+}
+
+
+//------------ FileStore -----------------------------------------------------
+
+/// An object store keeping each object in a file.
+///
+/// The files are kept below the `objects` directory of the store’s base
+/// directory. Their paths are formed from the lowercase authority, the
+/// module name, and the path of the URI. URIs that would result in a path
+/// outside this directory are rejected with an error of kind
+/// `InvalidInput`.
+///
+/// Each file starts with a header containing a format version, the
+/// storage time, and the hash of the content, followed by the content
+/// itself. When reading an object, its content is checked against the
+/// hash. Objects are written to a temporary file in the `tmp` directory of
+/// the base directory first and then moved into place so that a reader
+/// never sees a partially written object.
+#[derive(Debug)]
+pub struct FileStore {
+    /// The object files.
+    files: FileTree,
+}
+
+impl FileStore {
+    /// Creates a new store using the given base directory.
+    ///
+    /// The directory is created if necessary.
+    pub fn new<P: AsRef<Path>>(base: P) -> Result<Self, io::Error> {
+        let base = base.as_ref();
+        Ok(FileStore {
+            files: FileTree::new(base.join("objects"), base.join("tmp"))?,
+        })
+    }
+}
+
+impl ObjectStore for FileStore {
+    fn get(
+        &self, uri: &uri::Rsync
+    ) -> Result<Option<StoredObject>, io::Error> {
+        let (mut file, info) = match self.files.open(uri)? {
+            Some(some) => some,
+            None => return Ok(None)
+        };
+        let mut content = Vec::new();
+        file.read_to_end(&mut content)?;
+        if !info.hash.matches(&content) {
+            return Err(invalid_data("object file content doesn’t match hash"))
+        }
+        Ok(Some(StoredObject { content: content.into(), info }))
+    }
+
+    fn put(
+        &self, uri: &uri::Rsync, content: Bytes, time: Time
+    ) -> Result<(), io::Error> {
+        let info = ObjectInfo::new(Hash::from_data(&content), time);
+        self.files.write(uri, info, &content)
+    }
+
+    fn delete(&self, uri: &uri::Rsync) -> Result<bool, io::Error> {
+        self.files.remove(uri)
+    }
+
+    fn list(
+        &self, prefix: &uri::Rsync
+    ) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error> {
+        self.files.list(prefix)
+    }
+}
+
+
+//------------ DedupStore ----------------------------------------------------
+
+/// An object store keeping the content of identical objects only once.
+///
+/// The content of objects is kept in blob files below the `blobs`
+/// directory of the store’s base directory, named after the hex
+/// representation of the content’s SHA-256 hash. For each URI, an index
+/// file below the `index` directory records the hash and storage time of
+/// the object. Its path is formed in the same way as for a [`FileStore`].
+/// Objects published with the same content under several URIs, such as
+/// the same CRL under several names, thus only use up space once.
+///
+/// Deleting or replacing an object only removes or updates its index
+/// file. Blobs no longer referenced by any index file are removed by
+/// [`gc`].
+///
+/// [`FileStore`]: struct.FileStore.html
+/// [`gc`]: #method.gc
+#[derive(Debug)]
+pub struct DedupStore {
+    /// The index files.
+    index: FileTree,
+
+    /// The directory containing the blobs.
+    blobs: PathBuf,
+
+    /// A lock keeping garbage collection from running concurrently.
+    ///
+    /// All other operations hold a read lock while garbage collection
+    /// holds the write lock.
+    gc_lock: RwLock<()>,
+}
+
+impl DedupStore {
+    /// Creates a new store using the given base directory.
+    ///
+    /// The directory is created if necessary.
+    pub fn new<P: AsRef<Path>>(base: P) -> Result<Self, io::Error> {
+        let base = base.as_ref();
+        let blobs = base.join("blobs");
+        fs::create_dir_all(&blobs)?;
+        Ok(DedupStore {
+            index: FileTree::new(base.join("index"), base.join("tmp"))?,
+            blobs,
+            gc_lock: RwLock::new(()),
+        })
+    }
+
+    /// Removes all blobs not referenced by any object.
+    ///
+    /// Returns the number of blobs removed. All other operations on the
+    /// store wait until garbage collection has finished.
+    pub fn gc(&self) -> Result<usize, io::Error> {
+        let _lock = self.gc_lock.write().unwrap();
+        let referenced: HashSet<_> = self.index.list_all()?.into_iter().map(
+            |(_, info)| info.hash
+        ).collect();
+        let mut res = 0;
+        for dir in fs::read_dir(&self.blobs)? {
+            let dir = dir?;
+            if !dir.file_type()?.is_dir() {
+                continue
+            }
+            for entry in fs::read_dir(dir.path())? {
+                let entry = entry?;
+                // Skip anything that isn’t named like a blob.
+                let hash = match entry.file_name().to_str().map(
+                    Hash::from_str
+                ) {
+                    Some(Ok(hash)) => hash,
+                    _ => continue,
+                };
+                if !referenced.contains(&hash) {
+                    fs::remove_file(entry.path())?;
+                    res += 1;
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Returns the path of the blob with the given hash.
+    fn blob_path(&self, hash: Hash) -> PathBuf {
+        let hash = hash.to_string();
+        self.blobs.join(&hash[..2]).join(hash)
+    }
+}
+
+impl ObjectStore for DedupStore {
+    fn get(
+        &self, uri: &uri::Rsync
+    ) -> Result<Option<StoredObject>, io::Error> {
+        let _lock = self.gc_lock.read().unwrap();
+        let info = match self.index.open(uri)? {
+            Some((_, info)) => info,
+            None => return Ok(None)
+        };
+        let content = match fs::read(self.blob_path(info.hash)) {
+            Ok(content) => content,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Err(invalid_data("missing blob for object"))
+            }
+            Err(err) => return Err(err)
+        };
+        if !info.hash.matches(&content) {
+            return Err(invalid_data("blob content doesn’t match hash"))
+        }
+        Ok(Some(StoredObject { content: content.into(), info }))
+    }
+
+    fn put(
+        &self, uri: &uri::Rsync, content: Bytes, time: Time
+    ) -> Result<(), io::Error> {
+        let _lock = self.gc_lock.read().unwrap();
+        let info = ObjectInfo::new(Hash::from_data(&content), time);
+        let blob = self.blob_path(info.hash);
+        if !blob.exists() {
+            if let Some(dir) = blob.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            self.index.write_file(&blob, &[&content])?;
+        }
+        self.index.write(uri, info, b"")
+    }
+
+    fn delete(&self, uri: &uri::Rsync) -> Result<bool, io::Error> {
+        let _lock = self.gc_lock.read().unwrap();
+        self.index.remove(uri)
+    }
+
+    fn list(
+        &self, prefix: &uri::Rsync
+    ) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error> {
+        let _lock = self.gc_lock.read().unwrap();
+        self.index.list(prefix)
+    }
+}
+
+
+//------------ ValidatedCache ------------------------------------------------
+
+/// A cache of the objects of successfully validated publication points.
+///
+/// The cache keeps the last known-good set of objects for each
+/// publication point, identified by its manifest URI, together with the
+/// time they were validated. It implements [`ValidatedStore`], so it can
+/// be given to the validator via [`Validator::set_validated_store`]. The
+/// validator then records the objects of all publication points it could
+/// validate and falls back to them if a later fetch fails.
+///
+/// The objects themselves are kept in an object store. The assignment of
+/// objects to publication points is only kept in memory. If a persistent
+/// store is used, objects recorded by an earlier process are still
+/// available but they aren’t removed when a point is updated.
+///
+/// [`ValidatedStore`]: ../validation/trait.ValidatedStore.html
+/// [`Validator::set_validated_store`]: ../validation/struct.Validator.html#method.set_validated_store
+#[derive(Debug, Default)]
+pub struct ValidatedCache<S = MemoryStore> {
+    /// The store for the objects.
+    store: S,
+
+    /// The publication points recorded so far by manifest URI.
+    points: RwLock<HashMap<uri::Rsync, ValidatedPoint>>,
+}
+
+impl<S> ValidatedCache<S> {
+    /// Creates a new cache keeping its objects in the given store.
+    pub fn new(store: S) -> Self {
+        ValidatedCache { store, points: Default::default() }
+    }
+
+    /// Returns a reference to the underlying object store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Returns the publication point with the given manifest URI.
+    pub fn point(&self, manifest: &uri::Rsync) -> Option<ValidatedPoint> {
+        self.points.read().unwrap().get(manifest).cloned()
+    }
+}
+
+impl<S: ObjectStore> ValidatedCache<S> {
+    /// Records the objects of a validated publication point.
+    ///
+    /// All objects are stored with the time `time`. Objects previously
+    /// recorded for the publication point that aren’t part of `objects`
+    /// are deleted from the store.
+    pub fn update(
+        &self,
+        manifest: &uri::Rsync,
+        objects: &[(uri::Rsync, Bytes)],
+        time: Time,
+    ) -> Result<(), io::Error> {
+        let mut points = self.points.write().unwrap();
+        for (uri, content) in objects {
+            self.store.put(uri, content.clone(), time)?;
+        }
+        let uris: Vec<_> = objects.iter().map(|item| item.0.clone()).collect();
+        if let Some(old) = points.get(manifest) {
+            for uri in &old.objects {
+                if !uris.contains(uri) {
+                    self.store.delete(uri)?;
+                }
+            }
+        }
+        points.insert(
+            manifest.clone(), ValidatedPoint { time, objects: uris }
+        );
+        Ok(())
+    }
+
+    /// Removes a publication point and deletes its objects.
+    ///
+    /// Returns whether the publication point was present.
+    pub fn remove(&self, manifest: &uri::Rsync) -> Result<bool, io::Error> {
+        let mut points = self.points.write().unwrap();
+        match points.remove(manifest) {
+            Some(point) => {
+                for uri in &point.objects {
+                    self.store.delete(uri)?;
+                }
+                Ok(true)
+            }
+            None => Ok(false)
+        }
+    }
+}
+
+impl<S: ObjectStore> ObjectSource for ValidatedCache<S> {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        Ok(self.store.get(uri)?.map(StoredObject::into_content))
+    }
+}
+
+impl<S: ObjectStore> ValidatedStore for ValidatedCache<S> {
+    fn update_point(
+        &self,
+        manifest: &uri::Rsync,
+        objects: &[(uri::Rsync, Bytes)],
+        time: Time,
+    ) -> Result<(), io::Error> {
+        self.update(manifest, objects, time)
+    }
+}
+
+
+//------------ ValidatedPoint ------------------------------------------------
+
+/// A publication point recorded in a [`ValidatedCache`].
+///
+/// [`ValidatedCache`]: struct.ValidatedCache.html
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidatedPoint {
+    /// The time the objects were validated.
+    time: Time,
+
+    /// The URIs of the objects.
+    objects: Vec<uri::Rsync>,
+}
+
+impl ValidatedPoint {
+    /// Returns the time the objects were validated.
+    pub fn time(&self) -> Time {
+        self.time
+    }
+
+    /// Returns the URIs of the objects of the publication point.
+    pub fn objects(&self) -> &[uri::Rsync] {
+        &self.objects
+    }
+}
+
+
+//------------ FileTree ------------------------------------------------------
+
+/// A directory tree with a file for each rsync URI.
+///
+/// Each file starts with a header containing a format version, the time,
+/// and the hash of an object. It may be followed by the object’s content.
+#[derive(Debug)]
+struct FileTree {
+    /// The root directory of the tree.
+    root: PathBuf,
+
+    /// The directory for temporary files.
+    tmp: PathBuf,
+
+    /// A counter for creating unique temporary file names.
+    tmp_counter: AtomicUsize,
+}
+
+impl FileTree {
+    /// The version of the file format.
+    const VERSION: u8 = 1;
+
+    /// The length of the header of a file.
+    const HEADER_LEN: usize = 1 + 8 + 32;
+
+    /// Creates a new tree, creating the directories if necessary.
+    fn new(root: PathBuf, tmp: PathBuf) -> Result<Self, io::Error> {
+        fs::create_dir_all(&root)?;
+        fs::create_dir_all(&tmp)?;
+        Ok(FileTree { root, tmp, tmp_counter: AtomicUsize::new(0) })
+    }
+
+    /// Returns the path of the file for a URI.
+    fn path(&self, uri: &uri::Rsync) -> Result<PathBuf, io::Error> {
+        uri_path(&self.root, uri).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "unsafe rsync URI")
+        })
+    }
+
+    /// Opens the file for a URI and reads its header.
+    ///
+    /// Upon success, the file is positioned after the header.
+    fn open(
+        &self, uri: &uri::Rsync
+    ) -> Result<Option<(fs::File, ObjectInfo)>, io::Error> {
+        let mut file = match fs::File::open(self.path(uri)?) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err)
+        };
+        let info = Self::read_header(&mut file)?;
+        Ok(Some((file, info)))
+    }
+
+    /// Writes the file for a URI.
+    fn write(
+        &self, uri: &uri::Rsync, info: ObjectInfo, content: &[u8]
+    ) -> Result<(), io::Error> {
+        let path = self.path(uri)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut header = Vec::with_capacity(Self::HEADER_LEN);
+        header.push(Self::VERSION);
+        header.extend_from_slice(&info.time.timestamp().to_be_bytes());
+        header.extend_from_slice(info.hash.as_slice());
+        self.write_file(&path, &[&header, content])
+    }
+
+    /// Atomically writes a file with the given parts as its content.
+    ///
+    /// The file is written to the temporary directory first and then
+    /// moved into place so that a reader never sees a partially written
+    /// file.
+    fn write_file(
+        &self, path: &Path, parts: &[&[u8]]
+    ) -> Result<(), io::Error> {
+        let tmp_path = self.tmp.join(format!(
+            "{}-{}", std::process::id(),
+            self.tmp_counter.fetch_add(1, Ordering::Relaxed)
+        ));
+        let mut file = fs::File::create(&tmp_path)?;
+        for part in parts {
+            file.write_all(part)?;
+        }
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&tmp_path, path)
+    }
+
+    /// Removes the file for a URI.
+    ///
+    /// Returns whether the file was present.
+    fn remove(&self, uri: &uri::Rsync) -> Result<bool, io::Error> {
+        match fs::remove_file(self.path(uri)?) {
+            Ok(()) => Ok(true),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(false)
+            }
+            Err(err) => Err(err)
+        }
+    }
+
+    /// Returns the URIs and headers of all files below a URI.
+    fn list(
+        &self, prefix: &uri::Rsync
+    ) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error> {
+        let mut res = Vec::new();
+        if !prefix.path().is_empty() && !prefix.path().ends_with('/') {
+            return Ok(res)
+        }
+        Self::list_dir(
+            &self.path(prefix)?, &prefix.to_string(), &mut res
+        )?;
+        Ok(res)
+    }
+
+    /// Returns the URIs and headers of all files.
+    fn list_all(&self) -> Result<Vec<(uri::Rsync, ObjectInfo)>, io::Error> {
+        let mut res = Vec::new();
+        Self::list_dir(&self.root, "rsync://", &mut res)?;
+        Ok(res)
+    }
+
+    /// Reads the header of a file.
+    fn read_header(file: &mut fs::File) -> Result<ObjectInfo, io::Error> {
+        let mut header = [0u8; Self::HEADER_LEN];
+        file.read_exact(&mut header)?;
+        if header[0] != Self::VERSION {
+            return Err(invalid_data("unsupported object file version"))
+        }
+        let mut secs = [0u8; 8];
+        secs.copy_from_slice(&header[1..9]);
+        let time = Utc.timestamp_opt(i64::from_be_bytes(secs), 0).single()
+            .ok_or_else(|| invalid_data("invalid time in object file"))?;
+        // The slice has the right length, so this can’t fail.
+        let hash = Hash::try_from(&header[9..]).map_err(|_| {
+            invalid_data("invalid hash in object file")
+        })?;
+        Ok(ObjectInfo::new(hash, Time::new(time)))
+    }
+
+    /// Collects the URIs and headers of all files below a directory.
+    fn list_dir(
+        dir: &Path, prefix: &str, res: &mut Vec<(uri::Rsync, ObjectInfo)>
+    ) -> Result<(), io::Error> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                return Ok(())
+            }
+            Err(err) => return Err(err)
+        };
+        for entry in entries {
+            let entry = entry?;
+            // Names that can’t be part of a URI can’t have been stored by
+            // us, so we skip them.
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if entry.file_type()?.is_dir() {
+                Self::list_dir(
+                    &entry.path(), &format!("{}{}/", prefix, name), res
+                )?;
+            }
+            else {
+                let uri = match uri::Rsync::from_str(
+                    &format!("{}{}", prefix, name)
+                ) {
+                    Ok(uri) => uri,
+                    Err(_) => continue,
+                };
+                let info = Self::read_header(
+                    &mut fs::File::open(entry.path())?
+                )?;
+                res.push((uri, info));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates an error for a corrupt object file.
+fn invalid_data(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rsync(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    fn check_store<S: ObjectStore>(store: &S) {
+        let time = Time::utc(2020, 1, 1, 12, 0, 0);
+        let a = rsync("rsync://example.net/repo/ca/a.roa");
+        let b = rsync("rsync://example.net/repo/ca/sub/b.roa");
+        let c = rsync("rsync://example.net/other/c.roa");
+
+        assert_eq!(store.get(&a).unwrap(), None);
+        store.put(&a, Bytes::from_static(b"a"), time).unwrap();
+        store.put(&b, Bytes::from_static(b"b"), time).unwrap();
+        store.put(&c, Bytes::from_static(b"c"), time).unwrap();
+        let obj = store.get(&a).unwrap().unwrap();
+        assert_eq!(obj.content().as_ref(), b"a");
+        assert_eq!(obj.hash(), Hash::from_data(b"a"));
+        assert_eq!(obj.time(), time);
+
+        let later = Time::utc(2020, 1, 2, 12, 0, 0);
+        store.put(&a, Bytes::from_static(b"aa"), later).unwrap();
+        let obj = store.get(&a).unwrap().unwrap();
+        assert_eq!(obj.content().as_ref(), b"aa");
+        assert_eq!(obj.time(), later);
+
+        let mut list: Vec<_> = store.list(
+            &rsync("rsync://example.net/repo/")
+        ).unwrap().into_iter().map(|(uri, info)| {
+            (uri.path().to_string(), info.hash())
+        }).collect();
+        list.sort();
+        assert_eq!(
+            list,
+            [
+                ("ca/a.roa".to_string(), Hash::from_data(b"aa")),
+                ("ca/sub/b.roa".to_string(), Hash::from_data(b"b")),
+            ]
+        );
+        assert_eq!(
+            store.list(&rsync("rsync://example.net/repo/ca/sub/")).unwrap(),
+            [(b.clone(), ObjectInfo::new(Hash::from_data(b"b"), time))]
+        );
+        assert!(
+            store.list(&rsync("rsync://example.net/repo/ca")).unwrap()
+                .is_empty()
+        );
+        assert!(
+            store.list(&rsync("rsync://example.net/none/")).unwrap()
+                .is_empty()
+        );
+
+        assert!(store.delete(&b).unwrap());
+        assert!(!store.delete(&b).unwrap());
+        assert_eq!(store.get(&b).unwrap(), None);
+    }
+
+    #[test]
+    fn memory_store() {
+        let store = MemoryStore::new();
+        check_store(&store);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn file_store() {
+        let base = std::env::temp_dir().join(
+            format!("rpki-store-test-{}", std::process::id())
+        );
+        let store = FileStore::new(&base).unwrap();
+        check_store(&store);
+        assert_eq!(
+            store.put(
+                &uri::Rsync::new(
+                    uri::RsyncModule::new("example.net", "repo"),
+                    Bytes::from_static(b"../x.roa")
+                ),
+                Bytes::new(), Time::now()
+            ).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn dedup_store() {
+        let base = std::env::temp_dir().join(
+            format!("rpki-dedup-store-test-{}", std::process::id())
+        );
+        let store = DedupStore::new(&base).unwrap();
+        check_store(&store);
+
+        // check_store replaced the content of a.roa and deleted b.roa.
+        assert_eq!(store.gc().unwrap(), 2);
+        assert_eq!(store.gc().unwrap(), 0);
+        assert_eq!(
+            store.get(&rsync("rsync://example.net/repo/ca/a.roa"))
+                .unwrap().unwrap().content().as_ref(),
+            b"aa"
+        );
+
+        let time = Time::utc(2020, 1, 1, 12, 0, 0);
+        let x = rsync("rsync://example.net/repo/x.crl");
+        let y = rsync("rsync://example.net/repo/y.crl");
+        store.put(&x, Bytes::from_static(b"crl"), time).unwrap();
+        store.put(&y, Bytes::from_static(b"crl"), time).unwrap();
+        assert!(store.delete(&x).unwrap());
+        assert_eq!(store.gc().unwrap(), 0);
+        assert_eq!(
+            store.get(&y).unwrap().unwrap().content().as_ref(), b"crl"
+        );
+        assert!(store.delete(&y).unwrap());
+        assert_eq!(store.gc().unwrap(), 1);
+        fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn validated_cache() {
+        let cache = ValidatedCache::new(MemoryStore::new());
+        let time = Time::utc(2020, 1, 1, 12, 0, 0);
+        let mft = rsync("rsync://example.net/repo/ca/ca.mft");
+        let a = rsync("rsync://example.net/repo/ca/a.roa");
+        let b = rsync("rsync://example.net/repo/ca/b.roa");
+        let objects = [
+            (mft.clone(), Bytes::from_static(b"mft")),
+            (a.clone(), Bytes::from_static(b"a")),
+        ];
+        cache.update(&mft, &objects, time).unwrap();
+        assert_eq!(
+            ObjectSource::get(&cache, &a).unwrap(),
+            Some(Bytes::from_static(b"a"))
+        );
+        let point = cache.point(&mft).unwrap();
+        assert_eq!(point.time(), time);
+        assert_eq!(point.objects(), [mft.clone(), a.clone()]);
+
+        // Updating replaces the objects.
+        let later = Time::utc(2020, 1, 2, 12, 0, 0);
+        let objects = [
+            (mft.clone(), Bytes::from_static(b"mft2")),
+            (b.clone(), Bytes::from_static(b"b")),
+        ];
+        cache.update_point(&mft, &objects, later).unwrap();
+        assert_eq!(ObjectSource::get(&cache, &a).unwrap(), None);
+        assert_eq!(
+            cache.store().get(&b).unwrap().unwrap().time(), later
+        );
+        assert_eq!(cache.store().len(), 2);
+
+        assert!(cache.remove(&mft).unwrap());
+        assert!(!cache.remove(&mft).unwrap());
+        assert!(cache.point(&mft).is_none());
+        assert!(cache.store().is_empty());
+    }
+}
@@ -0,0 +1,430 @@
+//! Operating a trust anchor.
+//!
+//! A trust anchor is the root of an RPKI tree. It consists of a key, a
+//! self-signed trust anchor certificate for that key which is published
+//! in a repository, and a trust anchor locator (TAL) as defined in
+//! [RFC 8630] that tells relying parties where to find the certificate and
+//! which key it must have.
+//!
+//! This module provides [`TrustAnchor`] which keeps all of these together
+//! and [`TrustAnchorBuilder`] for creating a new one. This is mostly useful
+//! for testbeds and private RPKI deployments.
+//!
+//! [RFC 8630]: https://tools.ietf.org/html/rfc8630
+//! [`TrustAnchor`]: struct.TrustAnchor.html
+//! [`TrustAnchorBuilder`]: struct.TrustAnchorBuilder.html
+
+use std::iter;
+use crate::uri;
+use crate::cert::{Cert, KeyUsage, Overclaim, TbsCert};
+use crate::crypto::{PublicKey, PublicKeyFormat, Signer, SigningError};
+use crate::resources::{
+    AsBlock, AsBlocks, AsId, AsResources, IpBlocks, IpResources, Prefix
+};
+use crate::tal::{Tal, TalUri};
+use crate::x509::{Serial, Validity};
+
+
+//------------ TrustAnchorBuilder --------------------------------------------
+
+/// A builder for a new trust anchor.
+///
+/// The builder is created with the information that is always required.
+/// Everything else has defaults that can be changed via the various
+/// setters. By default, the trust anchor certificate will contain all
+/// resources, be valid for one year, and certify an RSA key.
+///
+/// The trust anchor is created via [`build`] which creates the key in the
+/// given signer and issues the first trust anchor certificate.
+///
+/// [`build`]: #method.build
+#[derive(Clone, Debug)]
+pub struct TrustAnchorBuilder {
+    /// The name of the trust anchor used for the TAL.
+    name: String,
+
+    /// The URIs where the trust anchor certificate will be published.
+    tal_uris: Vec<TalUri>,
+
+    /// The CA repository URI of the trust anchor.
+    ca_repository: uri::Rsync,
+
+    /// The manifest URI of the trust anchor.
+    rpki_manifest: uri::Rsync,
+
+    /// The RRDP notification URI of the trust anchor if present.
+    rpki_notify: Option<uri::Https>,
+
+    /// The format of the key to create.
+    key_format: PublicKeyFormat,
+
+    /// The validity of the trust anchor certificate.
+    validity: Validity,
+
+    /// The AS resources of the trust anchor.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources of the trust anchor.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources of the trust anchor.
+    v6_resources: IpBlocks,
+}
+
+impl TrustAnchorBuilder {
+    /// Creates a new builder.
+    ///
+    /// The trust anchor will be called `name` and its certificate is
+    /// expected to be published at `tal_uris` in order of preference. The
+    /// trust anchor publishes its objects in the repository `ca_repository`
+    /// and its manifest at `rpki_manifest`.
+    pub fn new(
+        name: String,
+        tal_uris: Vec<TalUri>,
+        ca_repository: uri::Rsync,
+        rpki_manifest: uri::Rsync,
+    ) -> Self {
+        TrustAnchorBuilder {
+            name,
+            tal_uris,
+            ca_repository,
+            rpki_manifest,
+            rpki_notify: None,
+            key_format: PublicKeyFormat::default(),
+            validity: Validity::from_secs(365 * 86400),
+            as_resources: iter::once(
+                AsBlock::from((AsId::MIN, AsId::MAX))
+            ).collect(),
+            v4_resources: iter::once(Prefix::new(0, 0).into()).collect(),
+            v6_resources: iter::once(Prefix::new(0, 0).into()).collect(),
+        }
+    }
+
+    /// Sets the RRDP notification URI of the trust anchor.
+    pub fn set_rpki_notify(&mut self, uri: Option<uri::Https>) {
+        self.rpki_notify = uri
+    }
+
+    /// Sets the format of the key to be created.
+    pub fn set_key_format(&mut self, format: PublicKeyFormat) {
+        self.key_format = format
+    }
+
+    /// Sets the validity of the trust anchor certificate.
+    pub fn set_validity(&mut self, validity: Validity) {
+        self.validity = validity
+    }
+
+    /// Sets the resources of the trust anchor.
+    ///
+    /// If a resource type is empty, it will be left out of the
+    /// certificate.
+    pub fn set_resources(
+        &mut self,
+        as_resources: AsBlocks,
+        v4_resources: IpBlocks,
+        v6_resources: IpBlocks,
+    ) {
+        self.as_resources = as_resources;
+        self.v4_resources = v4_resources;
+        self.v6_resources = v6_resources;
+    }
+
+    /// Builds the trust anchor.
+    ///
+    /// Creates a new key in `signer` and issues the trust anchor
+    /// certificate with it.
+    pub fn build<S: Signer>(
+        self, signer: &mut S
+    ) -> Result<TrustAnchor<S::KeyId>, SigningError<S::Error>> {
+        let key = signer.create_key(self.key_format)?;
+        let public_key = signer.get_key_info(&key)?;
+        let cert = TrustAnchor::<S::KeyId>::issue(
+            signer, &key, &public_key,
+            &self.ca_repository, &self.rpki_manifest,
+            self.rpki_notify.as_ref(), self.validity,
+            &self.as_resources, &self.v4_resources, &self.v6_resources,
+        )?;
+        Ok(TrustAnchor {
+            key,
+            public_key,
+            name: self.name,
+            tal_uris: self.tal_uris,
+            ca_repository: self.ca_repository,
+            rpki_manifest: self.rpki_manifest,
+            rpki_notify: self.rpki_notify,
+            as_resources: self.as_resources,
+            v4_resources: self.v4_resources,
+            v6_resources: self.v6_resources,
+            cert,
+        })
+    }
+}
+
+
+//------------ TrustAnchor ---------------------------------------------------
+
+/// A trust anchor.
+///
+/// The type keeps the identifier of the trust anchor’s key in the signer,
+/// the information that goes into the trust anchor certificate, and the
+/// current certificate. It is created via a [`TrustAnchorBuilder`].
+///
+/// The URIs can be changed via the setters. The TAL returned by [`tal`]
+/// will reflect these changes immediately while the certificate needs to
+/// be reissued via [`reissue`].
+///
+/// [`TrustAnchorBuilder`]: struct.TrustAnchorBuilder.html
+/// [`tal`]: #method.tal
+/// [`reissue`]: #method.reissue
+#[derive(Clone, Debug)]
+pub struct TrustAnchor<K> {
+    /// The identifier of the key in the signer.
+    key: K,
+
+    /// The public key of the trust anchor.
+    public_key: PublicKey,
+
+    /// The name of the trust anchor used for the TAL.
+    name: String,
+
+    /// The URIs where the trust anchor certificate is published.
+    tal_uris: Vec<TalUri>,
+
+    /// The CA repository URI of the trust anchor.
+    ca_repository: uri::Rsync,
+
+    /// The manifest URI of the trust anchor.
+    rpki_manifest: uri::Rsync,
+
+    /// The RRDP notification URI of the trust anchor if present.
+    rpki_notify: Option<uri::Https>,
+
+    /// The AS resources of the trust anchor.
+    as_resources: AsBlocks,
+
+    /// The IPv4 resources of the trust anchor.
+    v4_resources: IpBlocks,
+
+    /// The IPv6 resources of the trust anchor.
+    v6_resources: IpBlocks,
+
+    /// The current trust anchor certificate.
+    cert: Cert,
+}
+
+impl<K> TrustAnchor<K> {
+    /// Returns the identifier of the trust anchor’s key in the signer.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns the public key of the trust anchor.
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the name of the trust anchor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the current trust anchor certificate.
+    pub fn cert(&self) -> &Cert {
+        &self.cert
+    }
+
+    /// Returns the URIs where the trust anchor certificate is published.
+    pub fn tal_uris(&self) -> &[TalUri] {
+        &self.tal_uris
+    }
+
+    /// Sets the URIs where the trust anchor certificate is published.
+    pub fn set_tal_uris(&mut self, uris: Vec<TalUri>) {
+        self.tal_uris = uris
+    }
+
+    /// Returns the CA repository URI of the trust anchor.
+    pub fn ca_repository(&self) -> &uri::Rsync {
+        &self.ca_repository
+    }
+
+    /// Sets the CA repository URI of the trust anchor.
+    pub fn set_ca_repository(&mut self, uri: uri::Rsync) {
+        self.ca_repository = uri
+    }
+
+    /// Returns the manifest URI of the trust anchor.
+    pub fn rpki_manifest(&self) -> &uri::Rsync {
+        &self.rpki_manifest
+    }
+
+    /// Sets the manifest URI of the trust anchor.
+    pub fn set_rpki_manifest(&mut self, uri: uri::Rsync) {
+        self.rpki_manifest = uri
+    }
+
+    /// Returns the RRDP notification URI of the trust anchor if present.
+    pub fn rpki_notify(&self) -> Option<&uri::Https> {
+        self.rpki_notify.as_ref()
+    }
+
+    /// Sets the RRDP notification URI of the trust anchor.
+    pub fn set_rpki_notify(&mut self, uri: Option<uri::Https>) {
+        self.rpki_notify = uri
+    }
+
+    /// Returns the TAL for the trust anchor.
+    pub fn tal(&self) -> Tal {
+        Tal::new(
+            self.name.clone(), self.tal_uris.clone(), self.public_key.clone()
+        )
+    }
+
+    /// Reissues the trust anchor certificate.
+    ///
+    /// The new certificate will contain the current URIs and be valid for
+    /// `validity`. It receives a new random serial number. Returns the
+    /// new certificate which replaces the current one.
+    pub fn reissue<S: Signer<KeyId = K>>(
+        &mut self, signer: &S, validity: Validity
+    ) -> Result<&Cert, SigningError<S::Error>> {
+        self.cert = Self::issue(
+            signer, &self.key, &self.public_key,
+            &self.ca_repository, &self.rpki_manifest,
+            self.rpki_notify.as_ref(), validity,
+            &self.as_resources, &self.v4_resources, &self.v6_resources,
+        )?;
+        Ok(&self.cert)
+    }
+
+    /// Issues a trust anchor certificate.
+    #[allow(clippy::too_many_arguments)]
+    fn issue<S: Signer<KeyId = K>>(
+        signer: &S,
+        key: &K,
+        public_key: &PublicKey,
+        ca_repository: &uri::Rsync,
+        rpki_manifest: &uri::Rsync,
+        rpki_notify: Option<&uri::Https>,
+        validity: Validity,
+        as_resources: &AsBlocks,
+        v4_resources: &IpBlocks,
+        v6_resources: &IpBlocks,
+    ) -> Result<Cert, SigningError<S::Error>> {
+        let mut cert = TbsCert::new(
+            Serial::random(signer)?, public_key.to_subject_name(), validity,
+            None, public_key.clone(), KeyUsage::Ca, Overclaim::Refuse
+        );
+        cert.set_basic_ca(Some(true));
+        cert.set_ca_repository(Some(ca_repository.clone()));
+        cert.set_rpki_manifest(Some(rpki_manifest.clone()));
+        cert.set_rpki_notify(rpki_notify.cloned());
+        if !as_resources.is_empty() {
+            cert.set_as_resources(
+                Some(AsResources::blocks(as_resources.clone()))
+            );
+        }
+        if !v4_resources.is_empty() {
+            cert.set_v4_resources(
+                Some(IpResources::blocks(v4_resources.clone()))
+            );
+        }
+        if !v6_resources.is_empty() {
+            cert.set_v6_resources(
+                Some(IpResources::blocks(v6_resources.clone()))
+            );
+        }
+        cert.into_cert(signer, key)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+    use crate::crypto::softsigner::OpenSslSigner;
+    use crate::x509::Time;
+    use super::*;
+
+    fn builder() -> TrustAnchorBuilder {
+        TrustAnchorBuilder::new(
+            "test".into(),
+            vec![
+                TalUri::from_str("https://example.com/ta.cer").unwrap(),
+                TalUri::from_str("rsync://example.com/ta/ta.cer").unwrap(),
+            ],
+            uri::Rsync::from_str("rsync://example.com/repo/").unwrap(),
+            uri::Rsync::from_str("rsync://example.com/repo/ta.mft").unwrap(),
+        )
+    }
+
+    #[test]
+    fn build_and_reissue() {
+        let mut signer = OpenSslSigner::new();
+        let mut ta = builder().build(&mut signer).unwrap();
+        let tal = ta.tal();
+        assert_eq!(tal.uris().count(), 2);
+        assert_eq!(tal.key_info(), ta.public_key());
+
+        let cert = ta.tal().validate_cert_at(
+            ta.cert().clone(), true, Time::now()
+        ).unwrap();
+        assert!(cert.as_resources().contains(
+            &iter::once(AsBlock::from((AsId::MIN, AsId::MAX))).collect()
+        ));
+        assert!(cert.rpki_notify().is_none());
+
+        // Reissue with changed URIs.
+        let serial = ta.cert().serial_number();
+        let repo = uri::Rsync::from_str("rsync://example.net/repo/").unwrap();
+        ta.set_ca_repository(repo.clone());
+        ta.set_rpki_notify(Some(
+            uri::Https::from_str("https://example.net/notify.xml").unwrap()
+        ));
+        ta.set_tal_uris(vec![
+            TalUri::from_str("https://example.net/ta.cer").unwrap()
+        ]);
+        ta.reissue(&signer, Validity::from_secs(86400)).unwrap();
+        assert_ne!(ta.cert().serial_number(), serial);
+        assert_eq!(ta.cert().ca_repository(), Some(&repo));
+        assert!(ta.cert().rpki_notify().is_some());
+        assert_eq!(ta.tal().uris().count(), 1);
+        ta.tal().validate_cert_at(
+            ta.cert().clone(), true, Time::now()
+        ).unwrap();
+
+        // The TAL survives a round trip.
+        let mut data = Vec::new();
+        ta.tal().write(&mut data).unwrap();
+        let tal = Tal::read_named("test".into(), &mut data.as_slice());
+        assert_eq!(tal.unwrap().key_info(), ta.public_key());
+    }
+
+    #[test]
+    fn configured_resources() {
+        let mut signer = OpenSslSigner::new();
+        let mut builder = builder();
+        builder.set_resources(
+            iter::once(
+                AsBlock::from((AsId::from(64496), AsId::from(64511)))
+            ).collect(),
+            iter::once(
+                Prefix::new(Ipv4Addr::new(192, 0, 2, 0), 24).into()
+            ).collect(),
+            IpBlocks::empty(),
+        );
+        let ta = builder.build(&mut signer).unwrap();
+        assert!(ta.cert().v6_resources().is_none());
+        let cert = ta.tal().validate_cert_at(
+            ta.cert().clone(), true, Time::now()
+        ).unwrap();
+        assert!(!cert.as_resources().contains(
+            &iter::once(AsBlock::from(AsId::from(65000))).collect()
+        ));
+        assert!(cert.v6_resources().is_empty());
+    }
+}
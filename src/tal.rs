@@ -1,17 +1,29 @@
 //! Trust Anchor Locators
+//!
+//! Trust anchor locators (TALs) are small files that tell a relying party
+//! where to find the certificate of a trust anchor and which key it must
+//! have. Their format is defined in [RFC 8630].
+//!
+//! The type [`Tal`] represents such a TAL. It can be read from and written
+//! to files and can be used to validate a trust anchor certificate.
+//!
+//! [RFC 8630]: https://tools.ietf.org/html/rfc8630
+//! [`Tal`]: struct.Tal.html
 
 use std::{fmt, str};
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fs::{read_dir, DirEntry, File, ReadDir};
-use std::io::{self, Read};
+use std::io::{self, Read, Write};
 use std::path::Path;
 use std::sync::Arc;
 use bytes::Bytes;
 use bcder::decode;
 use log::{debug, error};
 use serde::{Deserialize, Serialize};
+use crate::cert::{Cert, ResourceCert};
 use crate::crypto::PublicKey;
+use crate::x509::{Time, ValidationError};
 use super::uri;
 
 
@@ -19,12 +31,27 @@ use super::uri;
 
 #[derive(Clone, Debug)]
 pub struct Tal {
+    comments: Vec<String>,
     uris: Vec<TalUri>,
     key_info: PublicKey,
     info: Arc<TalInfo>,
 }
 
 impl Tal {
+    /// Creates a new TAL for a locally operated trust anchor.
+    ///
+    /// The TAL will point to the trust anchor certificate at the given
+    /// URIs, which must be in order of preference, and requires it to have
+    /// the public key `key_info`.
+    pub fn new(name: String, uris: Vec<TalUri>, key_info: PublicKey) -> Self {
+        Tal {
+            comments: Vec::new(),
+            uris,
+            key_info,
+            info: Arc::new(TalInfo::from_name(name))
+        }
+    }
+
     pub fn read_dir<P: AsRef<Path>>(path: P) -> Result<TalIter, io::Error> {
         read_dir(path).map(TalIter)
     }
@@ -49,9 +76,10 @@ impl Tal {
         reader.read_to_end(&mut data)?;
 
         let mut data = data.as_slice();
+        let mut comments = Vec::new();
         let mut uris = Vec::new();
         while let Some(&b'#') = data.first() {
-            Self::skip_line(&mut data)?;
+            comments.push(Self::take_comment(&mut data)?);
         }
         while let Some(uri) = Self::take_uri(&mut data)? {
             uris.push(uri)
@@ -63,6 +91,7 @@ impl Tal {
         let key_info = base64::decode(&data)?;
         let key_info = PublicKey::decode(key_info.as_ref())?;
         Ok(Tal {
+            comments,
             uris,
             key_info,
             info: Arc::new(TalInfo::from_name(name))
@@ -82,11 +111,11 @@ impl Tal {
         })
     }
 
-    fn skip_line(data: &mut &[u8]) -> Result<(), ReadError> {
+    fn take_comment(data: &mut &[u8]) -> Result<String, ReadError> {
         let mut split = data.splitn(2, |&ch| ch == b'\n');
-        let _ = split.next().ok_or(ReadError::UnexpectedEof)?;
+        let line = split.next().ok_or(ReadError::UnexpectedEof)?;
         *data = split.next().ok_or(ReadError::UnexpectedEof)?;
-        Ok(())
+        Ok(String::from_utf8_lossy(&line[1..]).trim().into())
     }
 
     fn take_uri(data: &mut &[u8]) -> Result<Option<TalUri>, ReadError> {
@@ -106,10 +135,34 @@ impl Tal {
 }
 
 impl Tal {
+    /// Returns the comments at the beginning of the TAL.
+    ///
+    /// The leading hash sign and surrounding white space are removed.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Adds a comment line to be included when writing the TAL.
+    pub fn add_comment(&mut self, comment: String) {
+        self.comments.push(comment)
+    }
+
     pub fn uris(&self) -> ::std::slice::Iter<TalUri> {
         self.uris.iter()
     }
 
+    /// Returns the IPNS URI of the TAL if present.
+    ///
+    /// The IPNS URI names the keys under which the trust anchor
+    /// certificate and its repository are published via IPFS. If the TAL
+    /// contains more than one IPNS URI, the first one is returned.
+    pub fn ipns(&self) -> Option<&uri::Ipns> {
+        self.uris.iter().find_map(|uri| match *uri {
+            TalUri::Ipns(ref uri) => Some(uri),
+            _ => None
+        })
+    }
+
     pub fn key_info(&self) -> &PublicKey {
         &self.key_info
     }
@@ -119,6 +172,55 @@ impl Tal {
     }
 }
 
+impl Tal {
+    /// Validates a trust anchor certificate fetched via this TAL.
+    ///
+    /// The certificate must have the public key given in the TAL and must
+    /// be a valid trust anchor certificate as described in section 3 of
+    /// RFC 8630.
+    pub fn validate_cert(
+        &self,
+        cert: Cert,
+        strict: bool
+    ) -> Result<ResourceCert, ValidationError> {
+        self.validate_cert_at(cert, strict, Time::now())
+    }
+
+    /// Validates a trust anchor certificate at the given time.
+    pub fn validate_cert_at(
+        &self,
+        cert: Cert,
+        strict: bool,
+        now: Time,
+    ) -> Result<ResourceCert, ValidationError> {
+        if cert.subject_public_key_info() != &self.key_info {
+            return Err(ValidationError)
+        }
+        cert.validate_ta_at(self.info.clone(), strict, now)
+    }
+
+    /// Writes the TAL to a target.
+    ///
+    /// The TAL is written in the format defined in RFC 8630: comments
+    /// first, followed by the URIs, an empty line, and the base64 encoded
+    /// public key, broken into lines of 64 characters.
+    pub fn write<W: Write>(&self, target: &mut W) -> Result<(), io::Error> {
+        for comment in &self.comments {
+            writeln!(target, "# {}", comment)?;
+        }
+        for uri in &self.uris {
+            writeln!(target, "{}", uri)?;
+        }
+        writeln!(target)?;
+        let key = base64::encode(self.key_info.to_info_bytes());
+        for line in key.as_bytes().chunks(64) {
+            target.write_all(line)?;
+            writeln!(target)?;
+        }
+        Ok(())
+    }
+}
+
 
 //------------ TalIter -------------------------------------------------------
 
@@ -200,6 +302,14 @@ impl TalUri {
             _ => false
         }
     }
+
+    #[allow(clippy::match_like_matches_macro)] // matches! needs Rust 1.42.
+    pub fn is_ipns(&self) -> bool {
+        match *self {
+            TalUri::Ipns(_) => true,
+            _ => false
+        }
+    }
 }
 
 
@@ -383,7 +493,55 @@ mod test {
         } else {
             panic!("IPNS URIs expected");
         };
+        assert!(tal.uris.first().unwrap().is_ipns());
+        assert_eq!(
+            tal.ipns().map(uri::Ipns::get_repo_publish_key),
+            Some("QmcKcxt4cUwiA3CM1SLpGJLQpPYkuF6GWo6bsLLyt5cNuj")
+        );
+
+        let tal = include_bytes!("../test-data/ripe.tal");
+        let tal = Tal::read("ripe.tal", &mut tal.as_ref()).unwrap();
+        assert_eq!(tal.ipns(), None);
+    }
 
+    #[test]
+    fn write_and_validate() {
+        let data = include_bytes!("../test-data/ripe.tal");
+        let tal = Tal::read("ripe.tal", &mut data.as_ref()).unwrap();
+        let mut tal = Tal::new(
+            "local".into(),
+            vec![
+                TalUri::from_slice(b"https://example.com/ta.cer").unwrap(),
+                TalUri::from_slice(b"rsync://example.com/ta/ta.cer").unwrap(),
+                TalUri::from_slice(b"ipns/ta-key/repo-key").unwrap(),
+            ],
+            tal.key_info().clone()
+        );
+        tal.add_comment("A local trust anchor.".into());
+
+        let mut written = Vec::new();
+        tal.write(&mut written).unwrap();
+        let read = Tal::read("local.tal", &mut written.as_slice()).unwrap();
+        assert_eq!(read.comments(), ["A local trust anchor."]);
+        assert_eq!(read.uris, tal.uris);
+        assert_eq!(
+            read.ipns().map(uri::Ipns::as_str), Some("ipns/ta-key/repo-key")
+        );
+        assert_eq!(read.key_info(), tal.key_info());
+
+        let cert = Cert::decode(Bytes::from_static(
+            include_bytes!("../test-data/ta.cer")
+        )).unwrap();
+        let at = Time::utc(2019, 5, 1, 0, 0, 0);
+        read.validate_cert_at(cert.clone(), false, at).unwrap();
+
+        let other = Tal::new(
+            "other".into(), Vec::new(),
+            Cert::decode(Bytes::from_static(
+                include_bytes!("../test-data/ca1.cer")
+            )).unwrap().subject_public_key_info().clone()
+        );
+        assert!(other.validate_cert_at(cert, false, at).is_err());
     }
 
     #[test]
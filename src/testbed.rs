@@ -0,0 +1,864 @@
+//! Generating complete RPKI repositories for testing.
+//!
+//! This module allows creating a small but complete RPKI tree
+//! programmatically. This is useful for integration tests of relying party
+//! software which otherwise would have to ship with fixture files that
+//! expire after a while.
+//!
+//! The tree is created via [`TestbedBuilder`] and consists of a trust
+//! anchor, two levels of CAs below it, and ROAs issued by the CAs on the
+//! lower level. Each CA publishes a manifest and a CRL. The resulting
+//! [`Testbed`] contains the TAL, all published objects by their rsync URI,
+//! the RRDP notification and snapshot files for the repository, and the
+//! route origins a relying party should produce from it.
+//!
+//! The structure of the tree, i.e., names, URIs, resources, serial numbers,
+//! and times, is derived from a seed and thus the same for the same seed.
+//! Keys and signatures are created by the signer given when building the
+//! tree, so the objects will only be identical between runs if the signer
+//! creates keys deterministically.
+//!
+//! The module is only available if the `testbed` feature is enabled. It is
+//! intended to be used by enabling the feature in the dev-dependencies.
+//!
+//! [`TestbedBuilder`]: struct.TestbedBuilder.html
+//! [`Testbed`]: struct.Testbed.html
+
+use std::{fs, io, iter};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
+use std::str::FromStr;
+use bytes::Bytes;
+use chrono::Duration;
+use uuid::Uuid;
+use crate::uri;
+use crate::cert::{KeyUsage, Overclaim, TbsCert};
+use crate::crl::CrlBuilder;
+use crate::crypto::{
+    DigestAlgorithm, PublicKey, PublicKeyFormat, Signer, SigningError
+};
+use crate::manifest::{FileAndHash, ManifestContent};
+use crate::payload::{self, RouteOrigin};
+use crate::resources::{
+    AsBlock, AsBlocks, AsId, AsResources, IpBlock, IpBlocks, IpResources,
+    Prefix
+};
+use crate::roa::RoaBuilder;
+use crate::rrdp::Hash;
+use crate::sigobj::SignedObjectBuilder;
+use crate::tal::{Tal, TalUri};
+use crate::validation::{LocalCache, ObjectSource};
+use crate::x509::{Serial, Time, Validity};
+
+
+//------------ TestbedBuilder ------------------------------------------------
+
+/// A builder for a test repository.
+///
+/// By default, the trust anchor has two children, each of which has two
+/// children again which each issue two ROAs. The objects are published
+/// below `rsync://testbed.example/repo/` and the RRDP files below
+/// `https://testbed.example/rrdp/`. All objects are valid from one hour
+/// before the current time.
+#[derive(Clone, Debug)]
+pub struct TestbedBuilder {
+    /// The seed for deriving the structure.
+    seed: u64,
+
+    /// The time the objects are created at.
+    now: Time,
+
+    /// The number of children of each CA above the lowest level.
+    children: u8,
+
+    /// The number of ROAs issued by each CA on the lowest level.
+    roas: u8,
+
+    /// The manifest number used by all CAs.
+    manifest_number: u64,
+
+    /// The base URI for publishing objects.
+    rsync_base: uri::Rsync,
+
+    /// The base URI for the RRDP files.
+    rrdp_base: uri::Https,
+}
+
+impl TestbedBuilder {
+    /// Creates a new builder using the given seed.
+    pub fn new(seed: u64) -> Self {
+        TestbedBuilder {
+            seed,
+            now: Time::now(),
+            children: 2,
+            roas: 2,
+            manifest_number: 1,
+            rsync_base: uri::Rsync::from_str(
+                "rsync://testbed.example/repo/"
+            ).unwrap(),
+            rrdp_base: uri::Https::from_str(
+                "https://testbed.example/rrdp/"
+            ).unwrap(),
+        }
+    }
+
+    /// Sets the time the objects are created at.
+    ///
+    /// Certificates are valid from an hour before this time for a year,
+    /// CRLs and manifests for a day. In order to get identical trees, the
+    /// time needs to be set explicitly.
+    pub fn set_now(&mut self, now: Time) {
+        self.now = now
+    }
+
+    /// Sets the number of children of each CA above the lowest level.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if `children` is zero or larger than 16.
+    pub fn set_children(&mut self, children: u8) {
+        assert!(children > 0 && children <= 16);
+        self.children = children
+    }
+
+    /// Sets the number of ROAs issued by each CA on the lowest level.
+    ///
+    /// # Panics
+    ///
+    /// The method panics if `roas` is larger than 16.
+    pub fn set_roas(&mut self, roas: u8) {
+        assert!(roas <= 16);
+        self.roas = roas
+    }
+
+    /// Sets the manifest number used by all CAs.
+    ///
+    /// The number defaults to 1. Together with a signer that creates the
+    /// same keys again, this allows creating updated or outdated versions
+    /// of a tree.
+    pub fn set_manifest_number(&mut self, number: u64) {
+        self.manifest_number = number
+    }
+
+    /// Sets the base URIs for the rsync and RRDP repositories.
+    ///
+    /// Both URIs should end in a slash.
+    pub fn set_base_uris(&mut self, rsync: uri::Rsync, rrdp: uri::Https) {
+        self.rsync_base = rsync;
+        self.rrdp_base = rrdp;
+    }
+
+    /// Builds the test repository.
+    ///
+    /// All keys are created in `signer`.
+    pub fn build<S: Signer>(
+        &self, signer: &mut S
+    ) -> Result<Testbed, SigningError<S::Error>> {
+        Generator::new(self, signer).generate()
+    }
+}
+
+
+//------------ Testbed -------------------------------------------------------
+
+/// A generated test repository.
+///
+/// The type implements [`ObjectSource`] so it can be used directly with
+/// the [`Validator`]. Alternatively, the objects can be written into a
+/// directory with the layout expected by [`LocalCache`] via
+/// [`write_rsync`].
+///
+/// [`ObjectSource`]: ../validation/trait.ObjectSource.html
+/// [`Validator`]: ../validation/struct.Validator.html
+/// [`LocalCache`]: ../validation/struct.LocalCache.html
+/// [`write_rsync`]: #method.write_rsync
+#[derive(Clone, Debug)]
+pub struct Testbed {
+    /// The TAL of the trust anchor.
+    tal: Tal,
+
+    /// All published objects, including the trust anchor certificate.
+    objects: HashMap<uri::Rsync, Bytes>,
+
+    /// The route origins authorized by the ROAs.
+    origins: Vec<RouteOrigin>,
+
+    /// The URI of the RRDP notification file.
+    notification_uri: uri::Https,
+
+    /// The content of the RRDP notification file.
+    notification: Bytes,
+
+    /// The URI of the RRDP snapshot file.
+    snapshot_uri: uri::Https,
+
+    /// The content of the RRDP snapshot file.
+    snapshot: Bytes,
+}
+
+impl Testbed {
+    /// Returns the TAL of the trust anchor.
+    pub fn tal(&self) -> &Tal {
+        &self.tal
+    }
+
+    /// Returns the published objects by their rsync URI.
+    pub fn objects(&self) -> &HashMap<uri::Rsync, Bytes> {
+        &self.objects
+    }
+
+    /// Returns the route origins a relying party should produce.
+    ///
+    /// The route origins are sorted and free of duplicates.
+    pub fn origins(&self) -> &[RouteOrigin] {
+        &self.origins
+    }
+
+    /// Returns the URI of the RRDP notification file.
+    pub fn notification_uri(&self) -> &uri::Https {
+        &self.notification_uri
+    }
+
+    /// Returns the content of the RRDP notification file.
+    pub fn notification(&self) -> &Bytes {
+        &self.notification
+    }
+
+    /// Returns the URI of the RRDP snapshot file.
+    pub fn snapshot_uri(&self) -> &uri::Https {
+        &self.snapshot_uri
+    }
+
+    /// Returns the content of the RRDP snapshot file.
+    pub fn snapshot(&self) -> &Bytes {
+        &self.snapshot
+    }
+
+    /// Writes all objects into a local rsync cache at `base`.
+    ///
+    /// The objects are written using the layout of [`LocalCache`].
+    ///
+    /// [`LocalCache`]: ../validation/struct.LocalCache.html
+    pub fn write_rsync<P: AsRef<Path>>(
+        &self, base: P
+    ) -> Result<(), io::Error> {
+        let cache = LocalCache::new(base.as_ref());
+        for (uri, content) in &self.objects {
+            let path = match cache.path(uri) {
+                Some(path) => path,
+                None => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid object URI {}", uri)
+                    ))
+                }
+            };
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(path, content)?;
+        }
+        Ok(())
+    }
+}
+
+impl ObjectSource for Testbed {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        Ok(self.objects.get(uri).cloned())
+    }
+}
+
+
+//------------ Generator -----------------------------------------------------
+
+/// The state while generating a test repository.
+struct Generator<'a, S: Signer> {
+    /// The configuration.
+    config: &'a TestbedBuilder,
+
+    /// The signer for creating keys and signing.
+    signer: &'a mut S,
+
+    /// The random number generator for deriving the structure.
+    rng: SplitMix64,
+
+    /// The published objects.
+    objects: HashMap<uri::Rsync, Bytes>,
+
+    /// The authorized route origins.
+    origins: Vec<RouteOrigin>,
+}
+
+/// A CA in the tree.
+struct Ca<K> {
+    /// The name of the CA used to derive its URIs.
+    name: String,
+
+    /// The key of the CA.
+    key: K,
+
+    /// The public key of the CA.
+    public_key: PublicKey,
+
+    /// The rsync URI of the certificate of the CA.
+    cert_uri: uri::Rsync,
+
+    /// The AS resources of the CA.
+    as_resources: AsBlocks,
+
+    /// The IPv4 prefix of the CA.
+    v4: (u32, u8),
+
+    /// The IPv6 prefix of the CA.
+    v6: (u128, u8),
+
+    /// The objects to be published by the CA.
+    products: Vec<(String, Bytes)>,
+}
+
+impl<'a, S: Signer> Generator<'a, S> {
+    fn new(config: &'a TestbedBuilder, signer: &'a mut S) -> Self {
+        Generator {
+            config,
+            signer,
+            rng: SplitMix64(config.seed),
+            objects: HashMap::new(),
+            origins: Vec::new(),
+        }
+    }
+
+    fn generate(mut self) -> Result<Testbed, SigningError<S::Error>> {
+        let session_id = Uuid::from_bytes(
+            (u128::from(self.rng.next()) << 64
+                | u128::from(self.rng.next())).to_be_bytes()
+        );
+
+        // The trust anchor holds all resources. Its children are allocated
+        // from 10.0.0.0/8, 2001:db8::/32 and a private four octet AS number
+        // range.
+        let mut ta = self.create_ca(
+            "ta".into(), self.config.rsync_base.join(b"ta.cer"),
+            iter::once(AsBlock::from((AsId::MIN, AsId::MAX))).collect(),
+            (0, 0), (0, 0)
+        )?;
+        let ta_cert = self.issue_cert(&ta, None)?;
+        self.objects.insert(ta.cert_uri.clone(), ta_cert);
+
+        let children = u32::from(self.config.children);
+        let v4_base = self.rng.below(256 - children);
+        let as_base = 4_200_000_000 + self.rng.below(10_000) * 1000;
+        for i in 0..children {
+            let v4_octet = v4_base + i;
+            let as_min = as_base + i * 10_000;
+            let mut ca = self.create_child(
+                &mut ta, format!("ca{}", i),
+                (as_min, as_min + 9_999),
+                (0x0a00_0000 | v4_octet << 16, 16),
+                (0x2001_0db8 << 96 | u128::from(v4_octet) << 80, 48)
+            )?;
+            let (ca_v4, ca_v6) = (ca.v4.0, ca.v6.0);
+            for j in 0..children {
+                let as_min = as_min + j * 100;
+                let mut leaf = self.create_child(
+                    &mut ca, format!("ca{}-{}", i, j),
+                    (as_min, as_min + 99),
+                    (ca_v4 | j << 12, 20),
+                    (ca_v6 | u128::from(j) << 72, 56)
+                )?;
+                for k in 0..u32::from(self.config.roas) {
+                    self.issue_roa(&mut leaf, k)?;
+                }
+                self.publish(leaf)?;
+            }
+            self.publish(ca)?;
+        }
+        let tal = Tal::new(
+            "testbed".into(),
+            vec![TalUri::Rsync(ta.cert_uri.clone())],
+            ta.public_key.clone()
+        );
+        self.publish(ta)?;
+
+        self.origins.sort();
+        self.origins.dedup();
+        let (notification_uri, notification, snapshot_uri, snapshot) =
+            self.rrdp(session_id);
+        Ok(Testbed {
+            tal,
+            objects: self.objects,
+            origins: self.origins,
+            notification_uri,
+            notification,
+            snapshot_uri,
+            snapshot,
+        })
+    }
+
+    /// Creates a new CA.
+    fn create_ca(
+        &mut self,
+        name: String,
+        cert_uri: uri::Rsync,
+        as_resources: AsBlocks,
+        v4: (u32, u8),
+        v6: (u128, u8),
+    ) -> Result<Ca<S::KeyId>, SigningError<S::Error>> {
+        let key = self.signer.create_key(PublicKeyFormat::default())?;
+        let public_key = self.signer.get_key_info(&key)?;
+        Ok(Ca {
+            name, key, public_key, cert_uri, as_resources, v4, v6,
+            products: Vec::new(),
+        })
+    }
+
+    /// Creates a child CA and adds its certificate to the parent.
+    fn create_child(
+        &mut self,
+        parent: &mut Ca<S::KeyId>,
+        name: String,
+        asns: (u32, u32),
+        v4: (u32, u8),
+        v6: (u128, u8),
+    ) -> Result<Ca<S::KeyId>, SigningError<S::Error>> {
+        let file = format!("{}.cer", name);
+        let ca = self.create_ca(
+            name, self.pp_uri(&parent.name).join(file.as_bytes()),
+            iter::once(
+                AsBlock::from((AsId::from(asns.0), AsId::from(asns.1)))
+            ).collect(),
+            v4, v6
+        )?;
+        let cert = self.issue_cert(&ca, Some(parent))?;
+        parent.products.push((file, cert));
+        Ok(ca)
+    }
+
+    /// Issues the certificate for a CA.
+    ///
+    /// If `issuer` is `None`, the certificate is self-signed.
+    fn issue_cert(
+        &mut self, ca: &Ca<S::KeyId>, issuer: Option<&Ca<S::KeyId>>
+    ) -> Result<Bytes, SigningError<S::Error>> {
+        let (issuer_name, issuer_key) = match issuer {
+            Some(issuer) => (
+                issuer.public_key.to_subject_name(), &issuer.key
+            ),
+            None => (ca.public_key.to_subject_name(), &ca.key)
+        };
+        let mut cert = TbsCert::new(
+            self.serial(), issuer_name,
+            Validity::new(
+                self.not_before(), self.config.now + Duration::days(365)
+            ),
+            None, ca.public_key.clone(), KeyUsage::Ca, Overclaim::Refuse
+        );
+        cert.set_basic_ca(Some(true));
+        if let Some(issuer) = issuer {
+            cert.set_authority_key_identifier(
+                Some(issuer.public_key.key_identifier())
+            );
+            cert.set_crl_uri(Some(self.crl_uri(&issuer.name)));
+            cert.set_ca_issuer(Some(issuer.cert_uri.clone()));
+        }
+        cert.set_ca_repository(Some(self.pp_uri(&ca.name)));
+        cert.set_rpki_manifest(Some(self.manifest_uri(&ca.name)));
+        cert.set_rpki_notify(Some(self.notification_uri()));
+        cert.set_as_resources(
+            Some(AsResources::blocks(ca.as_resources.clone()))
+        );
+        cert.set_v4_resources(Some(IpResources::blocks(
+            iter::once(IpBlock::from(
+                Prefix::new(Ipv4Addr::from(ca.v4.0), ca.v4.1)
+            )).collect()
+        )));
+        cert.set_v6_resources(Some(IpResources::blocks(
+            iter::once(IpBlock::from(
+                Prefix::new(Ipv6Addr::from(ca.v6.0), ca.v6.1)
+            )).collect::<IpBlocks>()
+        )));
+        let cert = cert.into_cert(self.signer, issuer_key)?;
+        Ok(cert.to_captured().into_bytes())
+    }
+
+    /// Issues the ROA with the given index for a CA on the lowest level.
+    ///
+    /// The ROA authorizes the `index`th /24 and /64 of the CA’s prefixes
+    /// for one of the CA’s AS numbers.
+    fn issue_roa(
+        &mut self, ca: &mut Ca<S::KeyId>, index: u32
+    ) -> Result<(), SigningError<S::Error>> {
+        let asn = AsId::from(
+            u32::from(ca.as_resources.iter().next().unwrap().min())
+                + self.rng.below(100)
+        );
+        let v4 = Ipv4Addr::from(ca.v4.0 | index << 8);
+        let v4_max = 24 + self.rng.below(3) as u8;
+        let v6 = Ipv6Addr::from(ca.v6.0 | u128::from(index) << 64);
+
+        let mut roa = RoaBuilder::new(asn);
+        roa.push_v4_addr(v4, 24, Some(v4_max));
+        roa.push_v6_addr(v6, 64, None);
+        let file = format!("roa{}.roa", index);
+        let roa = roa.finalize(
+            self.sigobj_builder(ca, &file), self.signer, &ca.key
+        )?;
+        ca.products.push((file, roa.to_captured().into_bytes()));
+
+        self.origins.extend(
+            payload::Prefix::new(IpAddr::V4(v4), 24).ok().and_then(|pfx| {
+                RouteOrigin::new(pfx, v4_max, asn)
+            })
+        );
+        self.origins.extend(
+            payload::Prefix::new(IpAddr::V6(v6), 64).ok().and_then(|pfx| {
+                RouteOrigin::new(pfx, 64, asn)
+            })
+        );
+        Ok(())
+    }
+
+    /// Publishes the objects of a CA together with its CRL and manifest.
+    fn publish(
+        &mut self, mut ca: Ca<S::KeyId>
+    ) -> Result<(), SigningError<S::Error>> {
+        let this_update = self.not_before();
+        let next_update = self.config.now + Duration::days(1);
+        let crl = CrlBuilder::new(
+            this_update, next_update, 1u64.into()
+        ).to_crl(self.signer, &ca.key)?;
+        ca.products.push(("crl.crl".into(), crl.to_captured().into_bytes()));
+
+        let files: Vec<_> = ca.products.iter().map(|(name, content)| {
+            FileAndHash::new(
+                Bytes::copy_from_slice(name.as_bytes()),
+                Bytes::copy_from_slice(
+                    DigestAlgorithm::default().digest(content).as_ref()
+                )
+            )
+        }).collect();
+        let manifest = ManifestContent::new(
+            self.config.manifest_number.into(), this_update, next_update,
+            DigestAlgorithm::default(), files.iter()
+        ).into_manifest(
+            self.sigobj_builder(&ca, "manifest.mft"), self.signer, &ca.key
+        )?;
+
+        let pp = self.pp_uri(&ca.name);
+        for (name, content) in ca.products {
+            self.objects.insert(pp.join(name.as_bytes()), content);
+        }
+        self.objects.insert(
+            self.manifest_uri(&ca.name),
+            manifest.to_captured().into_bytes()
+        );
+        Ok(())
+    }
+
+    /// Creates the RRDP notification and snapshot files.
+    fn rrdp(
+        &self, session_id: Uuid
+    ) -> (uri::Https, Bytes, uri::Https, Bytes) {
+        let mut objects: Vec<_> = self.objects.iter().map(|(uri, content)| {
+            (uri.to_string(), content)
+        }).collect();
+        objects.sort();
+
+        let mut snapshot = String::new();
+        writeln!(
+            snapshot,
+            "<snapshot xmlns=\"{}\" version=\"1\" session_id=\"{}\" \
+             serial=\"1\">",
+            RRDP_NS, session_id
+        ).unwrap();
+        for (uri, content) in objects {
+            writeln!(
+                snapshot, "  <publish uri=\"{}\">{}</publish>",
+                uri, base64::encode(content)
+            ).unwrap();
+        }
+        snapshot.push_str("</snapshot>\n");
+        let snapshot = Bytes::from(snapshot);
+        let snapshot_uri = self.config.rrdp_base.join(
+            format!("{}/1/snapshot.xml", session_id).as_bytes()
+        );
+
+        let mut notification = String::new();
+        writeln!(
+            notification,
+            "<notification xmlns=\"{}\" version=\"1\" session_id=\"{}\" \
+             serial=\"1\">",
+            RRDP_NS, session_id
+        ).unwrap();
+        writeln!(
+            notification, "  <snapshot uri=\"{}\" hash=\"{}\"/>",
+            snapshot_uri, Hash::from_data(&snapshot)
+        ).unwrap();
+        notification.push_str("</notification>\n");
+
+        (
+            self.notification_uri(), Bytes::from(notification),
+            snapshot_uri, snapshot
+        )
+    }
+
+    /// Returns a signed object builder for an object published by a CA.
+    fn sigobj_builder(
+        &mut self, ca: &Ca<S::KeyId>, file: &str
+    ) -> SignedObjectBuilder {
+        let mut res = SignedObjectBuilder::new(
+            self.serial(),
+            Validity::new(
+                self.not_before(), self.config.now + Duration::days(1)
+            ),
+            self.crl_uri(&ca.name),
+            ca.cert_uri.clone(),
+            self.pp_uri(&ca.name).join(file.as_bytes()),
+        );
+        res.set_signing_time(Some(self.config.now));
+        res
+    }
+
+    /// Returns a new serial number.
+    fn serial(&mut self) -> Serial {
+        Serial::from((self.rng.next() >> 1) + 1)
+    }
+
+    /// Returns the start of the validity of all objects.
+    fn not_before(&self) -> Time {
+        self.config.now - Duration::hours(1)
+    }
+
+    /// Returns the URI of the publication point of the named CA.
+    fn pp_uri(&self, name: &str) -> uri::Rsync {
+        self.config.rsync_base.join(format!("{}/", name).as_bytes())
+    }
+
+    /// Returns the URI of the CRL of the named CA.
+    fn crl_uri(&self, name: &str) -> uri::Rsync {
+        self.pp_uri(name).join(b"crl.crl")
+    }
+
+    /// Returns the URI of the manifest of the named CA.
+    fn manifest_uri(&self, name: &str) -> uri::Rsync {
+        self.pp_uri(name).join(b"manifest.mft")
+    }
+
+    /// Returns the URI of the RRDP notification file.
+    fn notification_uri(&self) -> uri::Https {
+        self.config.rrdp_base.join(b"notification.xml")
+    }
+}
+
+/// The XML namespace of RRDP.
+const RRDP_NS: &str = "http://www.ripe.net/rpki/rrdp";
+
+
+//------------ SplitMix64 ----------------------------------------------------
+
+/// A simple deterministic pseudo-random number generator.
+///
+/// This is the SplitMix64 generator which is good enough for deriving the
+/// structure of the test repository from a seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    /// Returns the next random number.
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a random number smaller than `limit`.
+    fn below(&mut self, limit: u32) -> u32 {
+        (self.next() % u64::from(limit)) as u32
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(all(test, feature="softkeys"))]
+mod signer_test {
+    use std::sync::Arc;
+    use crate::crypto::{Signature, SignatureAlgorithm};
+    use crate::crypto::signer::KeyError;
+    use crate::crypto::softsigner::{KeyId, OpenSslSigner};
+    use crate::manifest::check::FailureReason;
+    use crate::payload::Payload;
+    use crate::rrdp::NotificationFile;
+    use crate::store::{MemoryStore, ValidatedCache};
+    use crate::validation::{Fallback, Validator};
+    use super::*;
+
+    /// A signer that hands out the keys it has created before again.
+    #[derive(Default)]
+    struct ReplaySigner {
+        signer: OpenSslSigner,
+        keys: Vec<KeyId>,
+        next: Option<usize>,
+    }
+
+    impl ReplaySigner {
+        /// Starts handing out the created keys from the beginning.
+        fn replay(&mut self) {
+            self.next = Some(0)
+        }
+    }
+
+    impl Signer for ReplaySigner {
+        type KeyId = KeyId;
+        type Error = io::Error;
+
+        fn create_key(
+            &mut self, algorithm: PublicKeyFormat
+        ) -> Result<KeyId, io::Error> {
+            match self.next {
+                Some(ref mut next) => {
+                    *next += 1;
+                    Ok(self.keys[*next - 1])
+                }
+                None => {
+                    let key = self.signer.create_key(algorithm)?;
+                    self.keys.push(key);
+                    Ok(key)
+                }
+            }
+        }
+
+        fn get_key_info(
+            &self, key: &KeyId
+        ) -> Result<PublicKey, KeyError<io::Error>> {
+            self.signer.get_key_info(key)
+        }
+
+        fn destroy_key(
+            &mut self, key: &KeyId
+        ) -> Result<(), KeyError<io::Error>> {
+            self.signer.destroy_key(key)
+        }
+
+        fn sign<D: AsRef<[u8]> + ?Sized>(
+            &self, key: &KeyId, algorithm: SignatureAlgorithm, data: &D
+        ) -> Result<Signature, SigningError<io::Error>> {
+            self.signer.sign(key, algorithm, data)
+        }
+
+        fn sign_one_off<D: AsRef<[u8]> + ?Sized>(
+            &self, algorithm: SignatureAlgorithm, data: &D
+        ) -> Result<(Signature, PublicKey), io::Error> {
+            self.signer.sign_one_off(algorithm, data)
+        }
+
+        fn rand(&self, target: &mut [u8]) -> Result<(), io::Error> {
+            self.signer.rand(target)
+        }
+    }
+
+    #[test]
+    fn validate_testbed() {
+        let mut signer = OpenSslSigner::new();
+        let mut builder = TestbedBuilder::new(12);
+        builder.set_now(Time::now());
+        let testbed = builder.build(&mut signer).unwrap();
+
+        // TA, 2 + 4 CAs, each with CRL and manifest, 8 ROAs.
+        assert_eq!(testbed.objects().len(), 1 + 6 + 14 + 8);
+        assert_eq!(testbed.origins().len(), 16);
+
+        let outcome = Validator::new(&testbed).validate_tal(
+            testbed.tal()
+        ).unwrap();
+        assert!(outcome.failures().is_empty());
+        let origins: Vec<_> = outcome.payload().iter().filter_map(|item| {
+            match *item {
+                Payload::Origin(origin) => Some(origin),
+                _ => None
+            }
+        }).collect();
+        assert_eq!(origins, testbed.origins());
+
+        let notify = NotificationFile::parse(
+            testbed.notification().as_ref()
+        ).unwrap();
+        assert_eq!(notify.serial, 1);
+        assert_eq!(notify.snapshot.uri(), testbed.snapshot_uri());
+        assert!(notify.snapshot.verify_hash(testbed.snapshot()));
+    }
+
+    #[test]
+    fn deterministic_structure() {
+        let now = Time::utc(2021, 6, 1, 0, 0, 0);
+        let mut builder = TestbedBuilder::new(7);
+        builder.set_now(now);
+        builder.set_children(1);
+        builder.set_roas(3);
+        let one = builder.build(&mut OpenSslSigner::new()).unwrap();
+        let two = builder.build(&mut OpenSslSigner::new()).unwrap();
+        assert_eq!(one.origins(), two.origins());
+        assert_eq!(one.snapshot_uri(), two.snapshot_uri());
+        let mut one_uris: Vec<_> = one.objects().keys().collect();
+        let mut two_uris: Vec<_> = two.objects().keys().collect();
+        one_uris.sort_by_key(|uri| uri.to_string());
+        two_uris.sort_by_key(|uri| uri.to_string());
+        assert_eq!(one_uris, two_uris);
+
+        let mut builder = TestbedBuilder::new(8);
+        builder.set_now(now);
+        builder.set_children(1);
+        builder.set_roas(3);
+        let other = builder.build(&mut OpenSslSigner::new()).unwrap();
+        assert_ne!(one.origins(), other.origins());
+    }
+
+    #[test]
+    fn replayed_manifest() {
+        let mut signer = ReplaySigner::default();
+        let mut builder = TestbedBuilder::new(5);
+        builder.set_now(Time::now());
+        builder.set_children(1);
+        builder.set_manifest_number(2);
+        let current = builder.build(&mut signer).unwrap();
+        builder.set_manifest_number(1);
+        signer.replay();
+        let replayed = builder.build(&mut signer).unwrap();
+        assert_eq!(current.tal().key_info(), replayed.tal().key_info());
+
+        let cache = Arc::new(ValidatedCache::new(MemoryStore::new()));
+        let mut validator = Validator::new(&current);
+        validator.set_validated_store(cache.clone());
+        let outcome = validator.validate_tal(current.tal()).unwrap();
+        assert!(outcome.failures().is_empty());
+
+        // Validating the same manifests again isn’t a regression.
+        let outcome = validator.validate_tal(current.tal()).unwrap();
+        assert!(outcome.report().publication_points().all(|report| {
+            report.fallback().is_none()
+        }));
+
+        // The replayed manifests have a lower number, so the cached points
+        // are used instead.
+        let mut validator = Validator::new(&replayed);
+        validator.set_validated_store(cache.clone());
+        let outcome = validator.validate_tal(replayed.tal()).unwrap();
+        assert!(outcome.failures().is_empty());
+        assert_eq!(outcome.valid_points(), 3);
+        for report in outcome.report().publication_points() {
+            assert_eq!(report.fallback(), Some(Fallback::UsePrevious));
+            assert_eq!(
+                report.fallback_reason(),
+                Some(FailureReason::NumberRegression)
+            );
+        }
+        // The cache still has the current manifests.
+        for (uri, bytes) in current.objects() {
+            if uri.to_string().ends_with(".mft") {
+                assert_eq!(cache.get(uri).unwrap().as_ref(), Some(bytes));
+            }
+        }
+    }
+}
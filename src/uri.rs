@@ -1,7 +1,17 @@
 //! URIs.
+//!
+//! All URI types reject URIs longer than [`DEFAULT_MAX_LEN`] bytes when
+//! created from bytes or strings, including during deserialization. A
+//! different maximum can be given to the `from_bytes_with_max_len`
+//! functions of the types.
+//!
+//! [`DEFAULT_MAX_LEN`]: constant.DEFAULT_MAX_LEN.html
 
 use std::{error, fmt, hash, io, str};
+use std::collections::{HashMap, HashSet};
 use std::convert::TryFrom;
+use std::iter::FromIterator;
+use std::net::Ipv4Addr;
 use std::str::FromStr;
 use bcder::encode;
 use bcder::{Mode, Tag};
@@ -10,6 +20,23 @@ use bytes::{Buf, BufMut, Bytes, BytesMut};
 use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::path::PathBuf;
+use crate::cid::{self, Cid};
+
+
+//------------ Maximum Length ------------------------------------------------
+
+/// The default maximum length of a URI in bytes.
+pub const DEFAULT_MAX_LEN: usize = 2048;
+
+/// Checks that the URI in `bytes` doesn’t exceed `max_len` bytes.
+fn check_len(bytes: &[u8], max_len: usize) -> Result<(), Error> {
+    if bytes.len() > max_len {
+        Err(Error::TooLong)
+    }
+    else {
+        Ok(())
+    }
+}
 
 
 //------------ Rsync ---------------------------------------------------------
@@ -56,7 +83,17 @@ impl Rsync {
             .join(self.path())
     }
 
-    pub fn from_bytes(mut bytes: Bytes) -> Result<Self, Error> {
+    pub fn from_bytes(bytes: Bytes) -> Result<Self, Error> {
+        Self::from_bytes_with_max_len(bytes, DEFAULT_MAX_LEN)
+    }
+
+    /// Creates an rsync URI from bytes of at most `max_len` bytes.
+    ///
+    /// Returns `Error::TooLong` if `bytes` is longer than that.
+    pub fn from_bytes_with_max_len(
+        mut bytes: Bytes, max_len: usize
+    ) -> Result<Self, Error> {
+        check_len(&bytes, max_len)?;
         if !is_uri_ascii(&bytes) {
             return Err(Error::NotAscii)
         }
@@ -92,6 +129,41 @@ impl Rsync {
         })
     }
 
+    /// Creates an rsync URI from bytes with a strictly checked authority.
+    ///
+    /// In addition to the checks performed by [`from_bytes`], the
+    /// authority must consist of a legal DNS host name or an IPv4 address,
+    /// optionally followed by a colon and a port number. Returns
+    /// `Error::BadAuthority` if this is not the case.
+    ///
+    /// [`from_bytes`]: #method.from_bytes
+    pub fn from_bytes_strict(bytes: Bytes) -> Result<Self, Error> {
+        let res = Self::from_bytes(bytes)?;
+        res.check_authority()?;
+        Ok(res)
+    }
+
+    /// Creates an rsync URI from a string with a strictly checked authority.
+    ///
+    /// See [`from_bytes_strict`] for the checks performed.
+    ///
+    /// [`from_bytes_strict`]: #method.from_bytes_strict
+    pub fn from_str_strict(s: &str) -> Result<Self, Error> {
+        Self::from_bytes_strict(Bytes::copy_from_slice(s.as_ref()))
+    }
+
+    /// Checks that the authority is a host name with an optional port.
+    ///
+    /// The host name must either be a DNS name with labels of one to 63
+    /// letters, digits, or hyphens not starting or ending with a hyphen
+    /// and a total length of at most 253 characters, or an IPv4 address.
+    /// IPv6 address literals are never accepted since their brackets are
+    /// not allowed in rsync URIs by this crate. The port must be a decimal
+    /// number of at most 65535.
+    pub fn check_authority(&self) -> Result<(), Error> {
+        check_authority(self.module.authority.as_ref())
+    }
+
     /// Moves the URI to its own memory.
     ///
     /// Values use shared memory in order to allow cheap copying which may
@@ -103,6 +175,18 @@ impl Rsync {
         self.path = Bytes::copy_from_slice(self.path.as_ref());
     }
 
+    /// Replaces the authority and module with values from an interner.
+    ///
+    /// The path is moved to its own memory as with [`unshare`]. See
+    /// [`Interner`] for details.
+    ///
+    /// [`unshare`]: #method.unshare
+    /// [`Interner`]: struct.Interner.html
+    pub fn intern_into(&mut self, interner: &mut Interner) {
+        self.module.intern_into(interner);
+        self.path = Bytes::copy_from_slice(self.path.as_ref());
+    }
+
     fn check_path(path: &[u8]) -> Result<(), Error> {
         // Don’t allow ".." anywhere. Don’t allow empty segments except at the
         // end.
@@ -321,6 +405,16 @@ impl RsyncModule {
         self.module = Bytes::copy_from_slice(self.module.as_ref());
     }
 
+    /// Replaces the authority and module with values from an interner.
+    ///
+    /// See [`Interner`] for details.
+    ///
+    /// [`Interner`]: struct.Interner.html
+    pub fn intern_into(&mut self, interner: &mut Interner) {
+        self.authority = interner.intern_bytes(&self.authority);
+        self.module = interner.intern_bytes(&self.module);
+    }
+
 
     pub fn to_uri(&self) -> Rsync {
         Rsync {
@@ -384,6 +478,16 @@ impl Ipns {
     }
 
     pub fn from_bytes(bytes: Bytes) -> Result<Self, Error> {
+        Self::from_bytes_with_max_len(bytes, DEFAULT_MAX_LEN)
+    }
+
+    /// Creates an IPNS URI from bytes of at most `max_len` bytes.
+    ///
+    /// Returns `Error::TooLong` if `bytes` is longer than that.
+    pub fn from_bytes_with_max_len(
+        bytes: Bytes, max_len: usize
+    ) -> Result<Self, Error> {
+        check_len(&bytes, max_len)?;
         if !is_uri_ascii(&bytes) {
             return Err(Error::NotAscii)
         }
@@ -409,6 +513,74 @@ impl Ipns {
         let parts = self.as_str().split("/").collect::<Vec<&str>>();
         parts[2]
     }
+
+    /// Returns the trust anchor publish key as a CID.
+    pub fn ta_publish_cid(&self) -> Result<Cid, cid::Error> {
+        self.segment_cid(0)
+    }
+
+    /// Returns the repository publish key as a CID.
+    pub fn repo_publish_cid(&self) -> Result<Cid, cid::Error> {
+        self.segment_cid(1)
+    }
+
+    /// Returns the path segment with the given index as a CID.
+    fn segment_cid(&self, idx: usize) -> Result<Cid, cid::Error> {
+        let segment = self.segments().nth(idx).ok_or(cid::Error::BadLength)?;
+        Cid::from_str(
+            str::from_utf8(segment).map_err(|_| cid::Error::BadEncoding)?
+        )
+    }
+
+    /// Returns an iterator over the path segments after the scheme.
+    fn segments(&self) -> impl Iterator<Item = &[u8]> {
+        self.uri[5..].split(|&ch| ch == b'/')
+    }
+}
+
+/// A path segment of an IPNS URI for comparison.
+///
+/// Segments that are CIDs are compared by their content type and hash so
+/// that different versions and encodings of the same CID are equal. All
+/// other segments are compared ignoring ASCII case.
+enum IpnsSegment<'a> {
+    Cid(Cid),
+    Other(&'a [u8]),
+}
+
+impl<'a> IpnsSegment<'a> {
+    fn new(segment: &'a [u8]) -> Self {
+        match str::from_utf8(segment).ok().and_then(|s| s.parse().ok()) {
+            Some(cid) => IpnsSegment::Cid(cid),
+            None => IpnsSegment::Other(segment)
+        }
+    }
+
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (IpnsSegment::Cid(left), IpnsSegment::Cid(right)) => {
+                left.same_content(right)
+            }
+            (IpnsSegment::Other(left), IpnsSegment::Other(right)) => {
+                left.eq_ignore_ascii_case(right)
+            }
+            _ => false
+        }
+    }
+
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        match *self {
+            IpnsSegment::Cid(ref cid) => {
+                hash::Hash::hash(&cid.codec(), state);
+                hash::Hash::hash(cid.hash(), state);
+            }
+            IpnsSegment::Other(segment) => {
+                for ch in segment {
+                    hash::Hash::hash(&ch.to_ascii_lowercase(), state)
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for Ipns {
@@ -455,9 +627,19 @@ impl str::FromStr for Ipns {
 
 impl PartialEq for Ipns {
     fn eq(&self, other: &Self) -> bool {
-        self.uri[..].eq_ignore_ascii_case(
-            &other.uri[..]
-        )
+        let mut left = self.segments();
+        let mut right = other.segments();
+        loop {
+            match (left.next(), right.next()) {
+                (Some(left), Some(right)) => {
+                    if !IpnsSegment::new(left).eq(&IpnsSegment::new(right)) {
+                        return false
+                    }
+                }
+                (None, None) => return true,
+                _ => return false
+            }
+        }
     }
 }
 
@@ -467,10 +649,9 @@ impl Eq for Ipns { }
 
 impl hash::Hash for Ipns {
     fn hash<H: hash::Hasher>(&self, state: &mut H) {
-        for ch in self.uri[..].iter() {
-            ch.to_ascii_lowercase().hash(state)
+        for segment in self.segments() {
+            IpnsSegment::new(segment).hash(state)
         }
-        self.uri[..].hash(state)
     }
 }
 
@@ -509,6 +690,16 @@ impl Https {
     }
 
     pub fn from_bytes(bytes: Bytes) -> Result<Self, Error> {
+        Self::from_bytes_with_max_len(bytes, DEFAULT_MAX_LEN)
+    }
+
+    /// Creates an HTTPS URI from bytes of at most `max_len` bytes.
+    ///
+    /// Returns `Error::TooLong` if `bytes` is longer than that.
+    pub fn from_bytes_with_max_len(
+        bytes: Bytes, max_len: usize
+    ) -> Result<Self, Error> {
+        check_len(&bytes, max_len)?;
         if !is_uri_ascii(&bytes) {
             return Err(Error::NotAscii)
         }
@@ -552,6 +743,63 @@ impl Https {
         &self.uri[self.path_idx..]
     }
 
+    /// Returns whether two URIs are equal ignoring the case of the path.
+    ///
+    /// The default comparison of HTTPS URIs only ignores the case of the
+    /// scheme and host name. Some repositories serve the same file under
+    /// paths that only differ in case, so this method allows a looser
+    /// comparison. Use [`HttpsIgnoreCase`] for using this comparison in
+    /// collections.
+    ///
+    /// [`HttpsIgnoreCase`]: struct.HttpsIgnoreCase.html
+    pub fn eq_ignore_path_case(&self, other: &Self) -> bool {
+        self.uri.eq_ignore_ascii_case(&other.uri)
+    }
+
+    /// Returns the part of the URI following `base`.
+    ///
+    /// The scheme and host of both URIs need to be equal and the path of
+    /// `base` must be a prefix of the path of `self`. If the two URIs are
+    /// equal, the returned string is empty. Otherwise, returns `None`.
+    pub fn relative_to(&self, base: &Https) -> Option<&str> {
+        if !self.uri[..self.path_idx].eq_ignore_ascii_case(
+            &base.uri[..base.path_idx]
+        ) {
+            return None
+        }
+        let path = &self.as_str()[self.path_idx..];
+        let base = &base.as_str()[base.path_idx..];
+        if path.len() < base.len() {
+            return None
+        }
+        let (head, tail) = path.split_at(base.len());
+        if head == base {
+            Some(tail)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Replaces the prefix `from` of the URI with `to`.
+    ///
+    /// The part of the URI relative to `from` as returned by
+    /// [`relative_to`] is appended to `to` as is. Thus, `from` and `to`
+    /// should either both end in a slash or both not. Returns `None` if
+    /// `from` is not a prefix of the URI.
+    ///
+    /// [`relative_to`]: #method.relative_to
+    pub fn rebase(&self, from: &Https, to: &Https) -> Option<Https> {
+        let rel = self.relative_to(from)?;
+        let mut res = BytesMut::with_capacity(to.uri.len() + rel.len());
+        res.put_slice(to.uri.as_ref());
+        res.put_slice(rel.as_bytes());
+        Some(Https {
+            uri: res.freeze(),
+            path_idx: to.path_idx
+        })
+    }
+
     /// This function will join this URI and the given path. If the current
     /// URI does not end with a trailing '/', it will be injected.
     pub fn join(&self, path: &[u8]) -> Self {
@@ -687,6 +935,278 @@ impl fmt::Display for Https {
 }
 
 
+//------------ HttpsIgnoreCase -----------------------------------------------
+
+/// An HTTPS URI compared and hashed ignoring the case of the path.
+///
+/// This wraps an [`Https`] URI but uses [`Https::eq_ignore_path_case`] for
+/// comparison and a matching hash function. The URI itself keeps its
+/// original case.
+///
+/// [`Https`]: struct.Https.html
+/// [`Https::eq_ignore_path_case`]: struct.Https.html#method.eq_ignore_path_case
+#[derive(Clone, Debug)]
+pub struct HttpsIgnoreCase(Https);
+
+impl HttpsIgnoreCase {
+    /// Creates a new value from an HTTPS URI.
+    pub fn new(uri: Https) -> Self {
+        HttpsIgnoreCase(uri)
+    }
+
+    /// Returns a reference to the URI.
+    pub fn as_https(&self) -> &Https {
+        &self.0
+    }
+
+    /// Converts the value into the URI.
+    pub fn into_https(self) -> Https {
+        self.0
+    }
+}
+
+
+//--- From and AsRef
+
+impl From<Https> for HttpsIgnoreCase {
+    fn from(uri: Https) -> Self {
+        Self::new(uri)
+    }
+}
+
+impl AsRef<Https> for HttpsIgnoreCase {
+    fn as_ref(&self) -> &Https {
+        self.as_https()
+    }
+}
+
+
+//--- PartialEq and Eq
+
+impl PartialEq for HttpsIgnoreCase {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.eq_ignore_path_case(&other.0)
+    }
+}
+
+impl Eq for HttpsIgnoreCase { }
+
+
+//--- Hash
+
+impl hash::Hash for HttpsIgnoreCase {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        for ch in self.0.uri.iter() {
+            ch.to_ascii_lowercase().hash(state)
+        }
+    }
+}
+
+
+//--- Display
+
+impl fmt::Display for HttpsIgnoreCase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+
+//------------ Interner ------------------------------------------------------
+
+/// A pool for sharing the memory of URIs.
+///
+/// A validator keeps a large number of rsync URIs around, many of which
+/// share the same authority and module. Each of them normally keeps its own
+/// copy of these, often as part of the much larger buffer the URI was
+/// decoded from. The interner keeps one copy of each distinct authority
+/// and module and lets URIs use it via [`Rsync::intern_into`] and
+/// [`RsyncModule::intern_into`]. Whole URIs can be deduplicated via
+/// [`intern_rsync`].
+///
+/// Values are reference counted, so the interner can be dropped at any
+/// time without affecting the URIs that used it.
+///
+/// [`Rsync::intern_into`]: struct.Rsync.html#method.intern_into
+/// [`RsyncModule::intern_into`]: struct.RsyncModule.html#method.intern_into
+/// [`intern_rsync`]: #method.intern_rsync
+#[derive(Clone, Debug, Default)]
+pub struct Interner {
+    /// The interned byte values.
+    bytes: HashSet<Bytes>,
+
+    /// The interned rsync URIs.
+    rsync: HashSet<Rsync>,
+}
+
+impl Interner {
+    /// Creates a new, empty interner.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns a shared copy of a bytes value.
+    ///
+    /// If the interner doesn’t have the value yet, it is moved to its own
+    /// memory and added.
+    pub fn intern_bytes(&mut self, bytes: &Bytes) -> Bytes {
+        if let Some(res) = self.bytes.get(bytes) {
+            return res.clone()
+        }
+        let res = Bytes::copy_from_slice(bytes.as_ref());
+        self.bytes.insert(res.clone());
+        res
+    }
+
+    /// Returns a shared copy of an rsync URI.
+    ///
+    /// If the interner doesn’t have the URI yet, its authority and module
+    /// are interned and it is added.
+    pub fn intern_rsync(&mut self, uri: &Rsync) -> Rsync {
+        if let Some(res) = self.rsync.get(uri) {
+            return res.clone()
+        }
+        let mut res = uri.clone();
+        res.intern_into(self);
+        self.rsync.insert(res.clone());
+        res
+    }
+
+    /// Returns the number of distinct bytes values.
+    pub fn bytes_len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Returns the number of distinct rsync URIs.
+    pub fn rsync_len(&self) -> usize {
+        self.rsync.len()
+    }
+}
+
+
+//------------ Rewriter ------------------------------------------------------
+
+/// A table mapping rsync URIs to HTTPS URIs.
+///
+/// Validators fetching all content via HTTPS mirrors or IPFS gateways need
+/// to translate the rsync URIs found in certificates and manifests. The
+/// rewriter contains a set of rules, each mapping an rsync prefix – either
+/// a module or a directory within a module – to an HTTPS base URI. An rsync
+/// URI is rewritten by replacing the longest matching prefix with its base
+/// URI.
+///
+/// The rewriter can be serialized and deserialized as a map from prefix
+/// to base URI, allowing the table to be loaded from, e.g., a JSON or TOML
+/// file.
+#[derive(Clone, Debug, Default)]
+pub struct Rewriter {
+    /// The rules ordered by decreasing length of the prefix path.
+    ///
+    /// Both the prefix path and the base URI end in a slash unless the
+    /// prefix path is empty.
+    rules: Vec<(Rsync, Https)>,
+}
+
+impl Rewriter {
+    /// Creates a new, empty rewriter.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a rule mapping `prefix` to `base`.
+    ///
+    /// Both URIs are treated as directories, i.e., a trailing slash is
+    /// assumed if missing. An existing rule for the same prefix is
+    /// replaced.
+    pub fn insert(&mut self, prefix: Rsync, base: Https) {
+        let prefix = if prefix.path.is_empty() || prefix.ends_with("/") {
+            prefix
+        }
+        else {
+            prefix.join(b"")
+        };
+        let base = if base.uri.ends_with(b"/") {
+            base
+        }
+        else {
+            let mut uri = BytesMut::with_capacity(base.uri.len() + 1);
+            uri.put_slice(base.uri.as_ref());
+            uri.put_slice(b"/");
+            Https { uri: uri.freeze(), path_idx: base.path_idx }
+        };
+        self.rules.retain(|(item, _)| *item != prefix);
+        let idx = self.rules.iter().position(|(item, _)| {
+            item.path.len() < prefix.path.len()
+        }).unwrap_or(self.rules.len());
+        self.rules.insert(idx, (prefix, base));
+    }
+
+    /// Returns the number of rules.
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    /// Returns whether there are no rules.
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Rewrites an rsync URI.
+    ///
+    /// Returns `None` if there is no rule for the URI.
+    pub fn rewrite(&self, uri: &Rsync) -> Option<Https> {
+        self.rules.iter().find(|(prefix, _)| {
+            prefix.is_parent_of(uri)
+        }).map(|(prefix, base)| {
+            // relative_to succeeds since prefix is a parent of uri.
+            base.join(uri.relative_to(prefix).unwrap_or_default())
+        })
+    }
+}
+
+
+//--- FromIterator and Extend
+
+impl FromIterator<(Rsync, Https)> for Rewriter {
+    fn from_iter<I>(iter: I) -> Self
+    where I: IntoIterator<Item = (Rsync, Https)> {
+        let mut res = Self::new();
+        res.extend(iter);
+        res
+    }
+}
+
+impl Extend<(Rsync, Https)> for Rewriter {
+    fn extend<I>(&mut self, iter: I)
+    where I: IntoIterator<Item = (Rsync, Https)> {
+        for (prefix, base) in iter {
+            self.insert(prefix, base)
+        }
+    }
+}
+
+
+//--- Serialize and Deserialize
+
+impl Serialize for Rewriter {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where S: Serializer {
+        serializer.collect_map(
+            self.rules.iter().map(|(prefix, base)| (prefix, base))
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Rewriter {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        HashMap::<Rsync, Https>::deserialize(deserializer).map(|rules| {
+            rules.into_iter().collect()
+        })
+    }
+}
+
+
 //------------ Scheme --------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -820,6 +1340,49 @@ pub fn is_uri_ascii<S: AsRef<[u8]>>(slice: S) -> bool {
 }
 
 
+/// Checks that an authority consists of a host and an optional port.
+fn check_authority(authority: &[u8]) -> Result<(), Error> {
+    let mut parts = authority.splitn(2, |ch| *ch == b':');
+    let host = parts.next().unwrap_or_default();
+    if let Some(port) = parts.next() {
+        if port.is_empty() || port.len() > 5
+            || !port.iter().all(u8::is_ascii_digit)
+            || port.iter().fold(0u32, |res, ch| {
+                res * 10 + u32::from(ch - b'0')
+            }) > 0xFFFF
+        {
+            return Err(Error::BadAuthority)
+        }
+    }
+    if host.is_empty() || host.len() > 253 {
+        return Err(Error::BadAuthority)
+    }
+    for label in host.split(|ch| *ch == b'.') {
+        if label.is_empty() || label.len() > 63
+            || label.starts_with(b"-") || label.ends_with(b"-")
+            || !label.iter().all(|ch| {
+                ch.is_ascii_alphanumeric() || *ch == b'-'
+            })
+        {
+            return Err(Error::BadAuthority)
+        }
+    }
+
+    // A numeric last label is only allowed in an IPv4 address.
+    let last = host.rsplit(|ch| *ch == b'.').next().unwrap_or_default();
+    if last.iter().all(u8::is_ascii_digit) {
+        // The host is all ASCII, so from_utf8 won’t fail.
+        let valid = str::from_utf8(host).ok().and_then(|host| {
+            Ipv4Addr::from_str(host).ok()
+        }).is_some();
+        if !valid {
+            return Err(Error::BadAuthority)
+        }
+    }
+    Ok(())
+}
+
+
 //------------ Error ---------------------------------------------------------
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
@@ -829,6 +1392,8 @@ pub enum Error {
     BadScheme,
     DotSegments,
     EmptySegments,
+    BadAuthority,
+    TooLong,
 }
 
 impl fmt::Display for Error {
@@ -839,6 +1404,8 @@ impl fmt::Display for Error {
             Error::BadScheme => "bad URI scheme",
             Error::DotSegments => "URI with dot path segments",
             Error::EmptySegments => "URI with emtpy path segments",
+            Error::BadAuthority => "bad host name or port in URI",
+            Error::TooLong => "URI too long",
         })
     }
 }
@@ -866,6 +1433,38 @@ mod tests {
 
     }
 
+    #[test]
+    fn ipns_eq() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::{Hash, Hasher};
+
+        fn hash(ipns: &Ipns) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            ipns.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let v0 = Ipns::from_str(
+            "ipns/QmUNLLsPACCz1vLxQVkXqqLX5R1X345qqfHbsf67hvA3Nn/repo"
+        ).unwrap();
+        let v1 = Ipns::from_str(
+            "IPNS/bafybeiczsscdsbs7ffqz55asqdf3smv6klcw3gofszvwlyarci47bgf354\
+             /REPO"
+        ).unwrap();
+        let lower = Ipns::from_str(
+            "ipns/qmunllspaccz1vlxqvkxqqlx5r1x345qqfhbsf67hva3nn/repo"
+        ).unwrap();
+        assert_eq!(v0, v1);
+        assert_eq!(hash(&v0), hash(&v1));
+        assert_ne!(v0, lower);
+        assert!(
+            v0.ta_publish_cid().unwrap().same_content(
+                &v1.ta_publish_cid().unwrap()
+            )
+        );
+        assert!(v0.repo_publish_cid().is_err());
+    }
+
     #[test]
     fn rsync_check_uri() {
         assert!(Rsync::from_slice(b"rsync://host/module/foo/bar").is_ok());
@@ -1037,4 +1636,216 @@ mod tests {
         assert_eq!(base_uri_no_trailing_slash.join(sub), expected);
         assert_eq!(base_uri_trailing_slash.join(sub), expected);
     }
+
+    #[test]
+    fn rewriter() {
+        fn rsync(s: &str) -> Rsync {
+            Rsync::from_str(s).unwrap()
+        }
+        fn https(s: &str) -> Https {
+            Https::from_str(s).unwrap()
+        }
+
+        let rewriter: Rewriter = serde_json::from_str(r#"{
+            "rsync://example.net/repo/": "https://mirror.example.net",
+            "rsync://example.net/repo/ca/sub":
+                "https://gw.example.com/ipfs/QmRoot"
+        }"#).unwrap();
+        assert_eq!(rewriter.len(), 2);
+        assert_eq!(
+            rewriter.rewrite(&rsync("rsync://example.net/repo/ca/a.roa")),
+            Some(https("https://mirror.example.net/ca/a.roa"))
+        );
+        assert_eq!(
+            rewriter.rewrite(&rsync("rsync://example.net/repo/ca/sub/b.roa")),
+            Some(https("https://gw.example.com/ipfs/QmRoot/b.roa"))
+        );
+        assert_eq!(
+            rewriter.rewrite(&rsync("rsync://example.net/repo/ca/subway")),
+            Some(https("https://mirror.example.net/ca/subway"))
+        );
+        assert_eq!(
+            rewriter.rewrite(&rsync("rsync://example.net/other/a.roa")),
+            None
+        );
+
+        let mut rewriter = Rewriter::new();
+        rewriter.insert(
+            rsync("rsync://example.net/repo/"),
+            https("https://mirror.example.net/")
+        );
+        rewriter.insert(
+            rsync("rsync://example.net/repo/"),
+            https("https://example.org/repo/")
+        );
+        assert_eq!(rewriter.len(), 1);
+        assert_eq!(
+            rewriter.rewrite(&rsync("rsync://example.net/repo/a.roa")),
+            Some(https("https://example.org/repo/a.roa"))
+        );
+
+        let json = serde_json::to_string(&rewriter).unwrap();
+        assert_eq!(
+            json,
+            r#"{"rsync://example.net/repo/":"https://example.org/repo/"}"#
+        );
+    }
+
+    #[test]
+    fn https_ignore_path_case() {
+        use std::collections::HashSet;
+
+        let lower = Https::from_str("https://example.com/rrdp/a").unwrap();
+        let upper = Https::from_str("https://EXAMPLE.com/RRDP/a").unwrap();
+        let other = Https::from_str("https://example.com/rrdp/b").unwrap();
+        assert_ne!(lower, upper);
+        assert!(lower.eq_ignore_path_case(&upper));
+        assert!(!lower.eq_ignore_path_case(&other));
+
+        let mut set = HashSet::new();
+        assert!(set.insert(HttpsIgnoreCase::new(lower.clone())));
+        assert!(!set.insert(HttpsIgnoreCase::new(upper.clone())));
+        assert!(set.insert(HttpsIgnoreCase::new(other)));
+        assert_eq!(
+            set.get(&HttpsIgnoreCase::new(upper)).map(AsRef::as_ref),
+            Some(&lower)
+        );
+    }
+
+    #[test]
+    fn https_relative_to_and_rebase() {
+        let https = |s: &str| Https::from_str(s).unwrap();
+
+        let uri = https("https://rrdp.example.com/staging/1/delta.xml");
+        let staging = https("https://RRDP.example.com/staging/");
+        assert_eq!(uri.relative_to(&staging), Some("1/delta.xml"));
+        assert_eq!(uri.relative_to(&uri), Some(""));
+        assert_eq!(
+            uri.relative_to(&https("https://rrdp.example.com")),
+            Some("/staging/1/delta.xml")
+        );
+        assert_eq!(
+            uri.relative_to(&https("https://rrdp.example.com/prod/")),
+            None
+        );
+        assert_eq!(
+            uri.relative_to(&https("https://example.com/staging/")),
+            None
+        );
+
+        let prod = https("https://rrdp.example.net/");
+        let rebased = uri.rebase(&staging, &prod).unwrap();
+        assert_eq!(rebased, https("https://rrdp.example.net/1/delta.xml"));
+        assert_eq!(rebased.authority(), "rrdp.example.net");
+        assert_eq!(rebased.relative_to(&prod), Some("1/delta.xml"));
+        assert_eq!(uri.rebase(&prod, &staging), None);
+    }
+
+    #[test]
+    fn rsync_strict_authority() {
+        for uri in &[
+            "rsync://example.net/repo/",
+            "rsync://rpki-1.Example.NET:873/repo/a.roa",
+            "rsync://192.0.2.1/repo/",
+            "rsync://192.0.2.1:65535/repo/",
+            "rsync://localhost/repo/",
+        ] {
+            assert!(Rsync::from_str_strict(uri).is_ok(), "{}", uri);
+        }
+        for uri in &[
+            "rsync://exa_mple.net/repo/",
+            "rsync://example..net/repo/",
+            "rsync://example.net./repo/",
+            "rsync://-example.net/repo/",
+            "rsync://example-.net/repo/",
+            "rsync://user@example.net/repo/",
+            "rsync://example.net:/repo/",
+            "rsync://example.net:65536/repo/",
+            "rsync://example.net:8x/repo/",
+            "rsync://example.net:1:2/repo/",
+            "rsync://192.0.2.256/repo/",
+            "rsync://example.123/repo/",
+            "rsync://%41/repo/",
+        ] {
+            assert!(Rsync::from_str(uri).is_ok(), "{}", uri);
+            assert_eq!(
+                Rsync::from_str_strict(uri), Err(Error::BadAuthority),
+                "{}", uri
+            );
+        }
+
+        let label = "a".repeat(64);
+        let uri = format!("rsync://{}.net/repo/", label);
+        assert_eq!(Rsync::from_str_strict(&uri), Err(Error::BadAuthority));
+        let host = vec!["a".repeat(63); 4].join(".");
+        let uri = format!("rsync://{}/repo/", host);
+        assert_eq!(Rsync::from_str_strict(&uri), Err(Error::BadAuthority));
+        assert_eq!(
+            Rsync::from_str_strict("rsync://example.net"),
+            Err(Error::BadUri)
+        );
+    }
+
+    #[test]
+    fn max_len() {
+        let path = "a".repeat(DEFAULT_MAX_LEN);
+        let long = format!("rsync://example.net/repo/{}", path);
+        let short = &long[..DEFAULT_MAX_LEN];
+        assert!(Rsync::from_str(short).is_ok());
+        assert_eq!(Rsync::from_str(&long), Err(Error::TooLong));
+
+        let long = format!("ipns/ta-key/{}", path);
+        let short = &long[..DEFAULT_MAX_LEN];
+        assert!(Ipns::from_str(short).is_ok());
+        assert_eq!(Ipns::from_str(&long), Err(Error::TooLong));
+
+        let long = format!("https://example.net/{}", path);
+        let short = &long[..DEFAULT_MAX_LEN];
+        assert!(Https::from_str(short).is_ok());
+        assert_eq!(Https::from_str(&long), Err(Error::TooLong));
+        let err = serde_json::from_str::<Https>(
+            &serde_json::to_string(&long).unwrap()
+        ).unwrap_err();
+        assert!(err.to_string().contains("URI too long"));
+
+        let uri = Bytes::from_static(b"https://example.net/abc");
+        assert!(Https::from_bytes_with_max_len(uri.clone(), 23).is_ok());
+        assert_eq!(
+            Https::from_bytes_with_max_len(uri, 22), Err(Error::TooLong)
+        );
+        let uri = Bytes::from_static(b"rsync://example.net/repo/abc");
+        assert!(Rsync::from_bytes_with_max_len(uri.clone(), 28).is_ok());
+        assert_eq!(
+            Rsync::from_bytes_with_max_len(uri, 27), Err(Error::TooLong)
+        );
+        let uri = Bytes::from_static(b"ipns/ta-key/abc");
+        assert!(Ipns::from_bytes_with_max_len(uri.clone(), 15).is_ok());
+        assert_eq!(
+            Ipns::from_bytes_with_max_len(uri, 14), Err(Error::TooLong)
+        );
+    }
+
+    #[test]
+    fn interner() {
+        let mut interner = Interner::new();
+        let mut one = Rsync::from_str("rsync://example.net/repo/a").unwrap();
+        let mut two = Rsync::from_str("rsync://example.net/repo/b").unwrap();
+        one.intern_into(&mut interner);
+        two.intern_into(&mut interner);
+        assert_eq!(one.to_string(), "rsync://example.net/repo/a");
+        assert_eq!(
+            one.module.authority.as_ptr(), two.module.authority.as_ptr()
+        );
+        assert_eq!(one.module.module.as_ptr(), two.module.module.as_ptr());
+        assert_eq!(interner.bytes_len(), 2);
+
+        let three = interner.intern_rsync(&one);
+        let four = interner.intern_rsync(
+            &Rsync::from_str("rsync://example.net/repo/a").unwrap()
+        );
+        assert_eq!(three, four);
+        assert_eq!(three.path.as_ptr(), four.path.as_ptr());
+        assert_eq!(interner.rsync_len(), 1);
+        assert_eq!(interner.bytes_len(), 2);
+    }
 }
@@ -0,0 +1,186 @@
+//! Helpers for fetching data over HTTP.
+//!
+//! This module is only available if the `reqwest` dependency is enabled.
+
+use std::error;
+use bytes::Bytes;
+use reqwest::header;
+use crate::fetch::FetchError;
+
+
+//------------ Constants -----------------------------------------------------
+
+/// Phrases in error messages that indicate a failed TLS handshake.
+///
+/// Besides the obvious ones, rustls reports a server not speaking TLS as
+/// a corrupt message and a handshake rejected by the server as an alert.
+const TLS_PHRASES: &[&str] = &[
+    "certificate", "tls", "corrupt message", "fatal alert",
+];
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Checks that a response has a successful status.
+pub fn check_status(response: &reqwest::Response) -> Result<(), FetchError> {
+    if response.status().is_success() {
+        return Ok(())
+    }
+    Err(FetchError::http_status(
+        response.status().as_u16(),
+        response.headers().get(header::RETRY_AFTER).and_then(|value| {
+            value.to_str().ok()
+        })
+    ))
+}
+
+/// Reads the body of a response, enforcing a size limit.
+pub async fn read_body(
+    response: &mut reqwest::Response,
+    max_size: u64,
+) -> Result<Bytes, FetchError> {
+    let exceeded = FetchError::SizeExceeded { limit: max_size };
+    if let Some(len) = response.content_length() {
+        if len > max_size {
+            return Err(exceeded)
+        }
+    }
+    let mut res = Vec::new();
+    while let Some(chunk) = response.chunk().await.map_err(convert_error)? {
+        if (res.len() + chunk.len()) as u64 > max_size {
+            return Err(exceeded)
+        }
+        res.extend_from_slice(chunk.as_ref());
+    }
+    Ok(res.into())
+}
+
+/// Converts an HTTP client error into a fetch error.
+///
+/// The client doesn’t tell us directly whether resolving the host name or
+/// the TLS handshake failed, so we look for tell-tale phrases in the
+/// messages of the error’s sources. This is best-effort only as the
+/// messages are up to the client’s dependencies. Any other failure to
+/// talk to the server is reported as [`FetchError::Connect`]. The tests
+/// pin the messages of the current dependencies.
+///
+/// [`FetchError::Connect`]: ../../fetch/enum.FetchError.html#variant.Connect
+pub fn convert_error(err: reqwest::Error) -> FetchError {
+    if err.is_timeout() {
+        return FetchError::Timeout
+    }
+    if let Some(status) = err.status() {
+        return FetchError::http_status(status.as_u16(), None)
+    }
+    let mut msg = err.to_string();
+    let mut source = error::Error::source(&err);
+    while let Some(err) = source {
+        msg = err.to_string();
+        let lower = msg.to_ascii_lowercase();
+        if lower.contains("dns error") {
+            return FetchError::Dns(msg)
+        }
+        if TLS_PHRASES.iter().any(|phrase| lower.contains(phrase)) {
+            return FetchError::Tls(msg)
+        }
+        source = err.source();
+    }
+    if err.is_connect() {
+        FetchError::Connect(msg)
+    }
+    else if err.is_body() || err.is_decode() {
+        FetchError::Malformed
+    }
+    else {
+        FetchError::Connect(msg)
+    }
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+    use super::*;
+
+    /// Sends a GET request to `url` and returns the resulting error.
+    fn request_error(url: &str) -> reqwest::Error {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(500))
+            .build().unwrap();
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            client.get(url).send().await
+        }).unwrap_err()
+    }
+
+    /// Starts a server that answers the first request with `response`.
+    ///
+    /// If `response` is `None`, the server never answers. Returns the
+    /// port of the server.
+    fn serve(response: Option<&'static [u8]>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || {
+            let (mut sock, _) = listener.accept().unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = sock.read(&mut buf);
+            match response {
+                Some(response) => {
+                    let _ = sock.write_all(response);
+                }
+                None => thread::sleep(Duration::from_secs(5)),
+            }
+        });
+        port
+    }
+
+    #[test]
+    fn convert_dns_error() {
+        match convert_error(request_error("https://nonexistent.invalid/")) {
+            FetchError::Dns(msg) => assert!(msg.contains("dns error")),
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn convert_connect_error() {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap()
+            .local_addr().unwrap().port();
+        let url = format!("https://127.0.0.1:{}/", port);
+        match convert_error(request_error(&url)) {
+            FetchError::Connect(_) => { }
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn convert_tls_error() {
+        // A server speaking plain HTTP fails the handshake.
+        let port = serve(Some(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n"
+        ));
+        let url = format!("https://127.0.0.1:{}/", port);
+        match convert_error(request_error(&url)) {
+            FetchError::Tls(msg) => assert!(msg.contains("corrupt message")),
+            err => panic!("{:?}", err),
+        }
+    }
+
+    #[test]
+    fn convert_timeout() {
+        let port = serve(None);
+        let url = format!("https://127.0.0.1:{}/", port);
+        match convert_error(request_error(&url)) {
+            FetchError::Timeout => { }
+            err => panic!("{:?}", err),
+        }
+    }
+}
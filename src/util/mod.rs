@@ -1 +1,4 @@
 pub mod hex;
+#[cfg(feature = "reqwest")]
+pub mod http;
+pub mod path;
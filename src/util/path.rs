@@ -0,0 +1,45 @@
+//! Mapping rsync URIs to local paths.
+
+use std::path::{Path, PathBuf};
+use crate::uri;
+
+
+/// Returns the local directory for an rsync module below `base`.
+///
+/// The directory is the lowercase authority followed by the module name.
+/// Returns `None` if the authority or module name could escape the base
+/// directory.
+pub fn module_path(base: &Path, module: &uri::RsyncModule) -> Option<PathBuf> {
+    let authority = module.authority().to_ascii_lowercase();
+    if !is_safe_segment(&authority) || !is_safe_segment(module.module()) {
+        return None
+    }
+    Some(base.join(authority).join(module.module()))
+}
+
+/// Returns the local path for an rsync URI below `base`.
+///
+/// A trailing slash of the URI’s path is ignored. Returns `None` if any
+/// part of the URI could escape the base directory.
+pub fn uri_path(base: &Path, uri: &uri::Rsync) -> Option<PathBuf> {
+    let mut res = module_path(base, uri.module())?;
+    let path = uri.path().trim_end_matches('/');
+    if !path.is_empty() {
+        for segment in path.split('/') {
+            if !is_safe_segment(segment) {
+                return None
+            }
+            res.push(segment);
+        }
+    }
+    Some(res)
+}
+
+/// Returns whether a path segment stays within its parent directory.
+///
+/// This is not the case if it is empty, `"."` or `".."`, or contains a
+/// slash or backslash.
+fn is_safe_segment(segment: &str) -> bool {
+    !segment.is_empty() && segment != "." && segment != ".."
+        && !segment.contains('/') && !segment.contains('\\')
+}
@@ -0,0 +1,2026 @@
+//! Top-down validation of the RPKI.
+//!
+//! This module ties together the validation steps provided elsewhere in
+//! the crate into a complete validation run as described in [RFC 6487],
+//! [RFC 6488], and [RFC 9286]. Starting from a trust anchor certificate,
+//! the [`Validator`] processes the publication point of each CA via its
+//! manifest, checks the CRL, validates all objects listed on the manifest,
+//! and then descends into the child CAs it found.
+//!
+//! The validator doesn’t fetch any data itself. Instead, it reads objects
+//! through the [`ObjectSource`] trait which maps rsync URIs to the content
+//! of the objects. This allows running validation over a local rsync cache
+//! via [`LocalCache`], over data collected via RRDP or IPFS, or over an
+//! in-memory map of objects.
+//!
+//! Validation can also be spread over a pool of worker threads via the
+//! `_parallel` variants of the validation methods. These limit the number
+//! of publication points processed at the same time for each repository
+//! host.
+//!
+//! The result of a validation run is an [`Outcome`] that contains the
+//! validated payload and the publication points that could not be used.
+//! It also contains a [`ValidationReport`] with the details of what
+//! happened to each publication point and object. The report is defined
+//! in the [`report`] sub-module.
+//!
+//! [RFC 6487]: https://tools.ietf.org/html/rfc6487
+//! [RFC 6488]: https://tools.ietf.org/html/rfc6488
+//! [RFC 9286]: https://tools.ietf.org/html/rfc9286
+//! [`Validator`]: struct.Validator.html
+//! [`ObjectSource`]: trait.ObjectSource.html
+//! [`LocalCache`]: struct.LocalCache.html
+//! [`Outcome`]: struct.Outcome.html
+//! [`ValidationReport`]: report/struct.ValidationReport.html
+//! [`report`]: report/index.html
+
+use std::{error, fmt, fs, io};
+use std::cmp::min;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use serde::Serialize;
+use crate::uri;
+use crate::cert::{Cert, Overclaim, ResourceCert};
+use crate::crl::Crl;
+use crate::crypto::DigestAlgorithm;
+use crate::gbr::Gbr;
+use crate::hooks::TrimmedResources;
+use crate::manifest::{Manifest, ManifestContent, ManifestHash};
+use crate::manifest::check::{
+    FailedFetch, FailureReason, ManifestCheck, ManifestPolicy, ObjectStatus
+};
+use crate::payload::{Payload, Prefix, RouteOrigin, RouterKey};
+use crate::roa::Roa;
+use crate::tal::{Tal, TalUri};
+use crate::x509::{Clock, DecodeMode, Serial, SystemClock, Time};
+use self::report::{Code, Issue, ObjectReport, PointReport};
+
+pub use self::report::ValidationReport;
+
+pub mod report;
+mod parallel;
+
+
+//------------ Configuration Constants ---------------------------------------
+
+/// The default limit of concurrently processed points per host.
+const DEFAULT_HOST_LIMIT: usize = 4;
+
+
+//------------ ObjectSource --------------------------------------------------
+
+/// A source of RPKI objects.
+///
+/// Types implementing this trait provide the content of the objects
+/// published in the RPKI repository identified by their rsync URI.
+pub trait ObjectSource {
+    /// Returns the content of the object with the given URI.
+    ///
+    /// If the object is not available, returns `Ok(None)`. An error should
+    /// only be returned if the source itself has failed. It aborts the
+    /// validation run.
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error>;
+}
+
+impl<T: ObjectSource + ?Sized> ObjectSource for &T {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        (*self).get(uri)
+    }
+}
+
+impl<T: ObjectSource + ?Sized> ObjectSource for Arc<T> {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        self.as_ref().get(uri)
+    }
+}
+
+impl ObjectSource for HashMap<uri::Rsync, Bytes> {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        Ok(HashMap::get(self, uri).cloned())
+    }
+}
+
+
+//------------ ValidatedStore ------------------------------------------------
+
+/// A store for the objects of successfully validated publication points.
+///
+/// Section 6.6 of RFC 9286 asks relying parties to keep using the objects
+/// of the last successful fetch of a publication point if a new fetch
+/// fails. Types implementing this trait keep these objects. The validator
+/// records the objects of every publication point it processed without
+/// any problems via [`update_point`]. As an [`ObjectSource`], the store
+/// then provides these objects when the validator falls back to them.
+///
+/// The [`store::ValidatedCache`] type implements this trait on top of an
+/// object store.
+///
+/// [`update_point`]: #tymethod.update_point
+/// [`ObjectSource`]: trait.ObjectSource.html
+/// [`store::ValidatedCache`]: ../store/struct.ValidatedCache.html
+pub trait ValidatedStore: ObjectSource {
+    /// Records the objects of a successfully validated publication point.
+    ///
+    /// The publication point is identified by its manifest URI. The
+    /// objects are all the objects used, including the manifest and CRL.
+    /// They replace all objects previously recorded for the point. The
+    /// time is the time the objects have been validated against.
+    fn update_point(
+        &self,
+        manifest: &uri::Rsync,
+        objects: &[(uri::Rsync, Bytes)],
+        time: Time,
+    ) -> Result<(), io::Error>;
+}
+
+
+//------------ LocalCache ----------------------------------------------------
+
+/// An object source reading from a local copy of rsync repositories.
+///
+/// The objects are expected in a directory tree below a base directory
+/// where the first level is the lowercase authority of the rsync URI, the
+/// second level its module, and the path of the URI follows below. This is
+/// the layout produced by running rsync for each module into the directory
+/// `<base>/<authority>/<module>`.
+#[derive(Clone, Debug)]
+pub struct LocalCache {
+    /// The base directory of the cache.
+    base: PathBuf,
+}
+
+impl LocalCache {
+    /// Creates a new local cache using the given base directory.
+    pub fn new<P: Into<PathBuf>>(base: P) -> Self {
+        LocalCache { base: base.into() }
+    }
+
+    /// Returns the base directory of the cache.
+    pub fn base(&self) -> &PathBuf {
+        &self.base
+    }
+
+    /// Returns the path of the file for the given URI.
+    ///
+    /// Returns `None` if the authority, module, or path of the URI contain
+    /// components that would lead outside of the cache.
+    pub fn path(&self, uri: &uri::Rsync) -> Option<PathBuf> {
+        crate::util::path::uri_path(&self.base, uri)
+    }
+}
+
+impl ObjectSource for LocalCache {
+    fn get(&self, uri: &uri::Rsync) -> Result<Option<Bytes>, io::Error> {
+        let path = match self.path(uri) {
+            Some(path) => path,
+            None => return Ok(None)
+        };
+        match fs::read(path) {
+            Ok(content) => Ok(Some(content.into())),
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+                Ok(None)
+            }
+            Err(err) => Err(err)
+        }
+    }
+}
+
+
+//------------ Validator -----------------------------------------------------
+
+/// Performs top-down validation of the RPKI.
+///
+/// A validator is created from an object source via [`new`]. It uses
+/// default settings that can be changed through the various setter
+/// methods. A validation run is started either from a TAL via
+/// [`validate_tal`] or from an already validated trust anchor certificate
+/// via [`validate_ta`].
+///
+/// Each publication point is processed according to section 6 of
+/// RFC 9286 using the state machine provided by [`ManifestCheck`]. If any
+/// object of a publication point is missing or invalid, none of its
+/// objects are used and none of its child CAs are processed. For some
+/// common failures, this can be relaxed via a [`FallbackPolicy`].
+///
+/// A CA may have been issued more than one certificate for the same key,
+/// e.g., because it has been re-signed by its parent or has more than one
+/// parent. All these certificates are candidates for the issuer of the
+/// objects of the CA’s publication point. The validator keeps track of the
+/// publication points by the subject key identifier of the CA. If
+/// processing a publication point fails, it is tried again with every
+/// further certificate for the same key until one succeeds. The report of
+/// each publication point contains the chain of certificates used and the
+/// number of candidates tried.
+///
+/// [`new`]: #method.new
+/// [`validate_tal`]: #method.validate_tal
+/// [`validate_ta`]: #method.validate_ta
+/// [`ManifestCheck`]: ../manifest/check/struct.ManifestCheck.html
+/// [`FallbackPolicy`]: struct.FallbackPolicy.html
+#[derive(Clone, Debug)]
+pub struct Validator<S> {
+    /// The source for the objects.
+    source: S,
+
+    /// Whether to use strict validation.
+    strict: bool,
+
+    /// Whether to accept certificates using the reconsidered algorithm.
+    reconsidered: bool,
+
+    /// The manifest policy to apply.
+    policy: ManifestPolicy,
+
+    /// The strategies for failed publication points.
+    fallback: FallbackPolicy,
+
+    /// The source for the objects of the last successful fetch.
+    previous: Option<PreviousSource>,
+
+    /// The store for the objects of successfully validated points.
+    validated: Option<ValidatedStoreRef>,
+
+    /// The clock providing the time to validate against.
+    clock: Arc<dyn Clock>,
+
+    /// The options for the time of a validation run.
+    options: ValidationOptions,
+
+    /// The number of worker threads for parallel validation.
+    threads: usize,
+
+    /// The maximum number of publication points per host processed at once.
+    host_limit: usize,
+}
+
+impl<S> Validator<S> {
+    /// Creates a new validator using the given object source.
+    ///
+    /// The validator uses relaxed validation, accepts certificates using
+    /// the reconsidered validation algorithm, uses the default manifest
+    /// policy, and validates against the system time.
+    pub fn new(source: S) -> Self {
+        Validator {
+            source,
+            strict: false,
+            reconsidered: true,
+            policy: ManifestPolicy::default(),
+            fallback: FallbackPolicy::default(),
+            previous: None,
+            validated: None,
+            clock: Arc::new(SystemClock),
+            options: ValidationOptions::default(),
+            threads: 1,
+            host_limit: DEFAULT_HOST_LIMIT,
+        }
+    }
+
+    /// Returns a reference to the object source.
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Returns whether strict validation is used.
+    pub fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Sets whether strict validation is used.
+    ///
+    /// In strict mode, objects are also decoded in strict mode. Otherwise
+    /// they are decoded in lenient mode.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict
+    }
+
+    /// Returns the decoding mode for objects.
+    fn mode(&self) -> DecodeMode {
+        DecodeMode::from_strict(self.strict)
+    }
+
+    /// Checks a certificate against the publication point’s CRL.
+    ///
+    /// The certificate must refer to the CRL and must not have been
+    /// revoked. If the CRL is not available, only the former is checked.
+    /// In historical validation, revocations after the validation time are
+    /// ignored.
+    fn check_crl(
+        &self, cert: &Cert, crl_uri: &uri::Rsync, crl: Option<&Crl>
+    ) -> Result<(), Code> {
+        if cert.crl_uri() != Some(crl_uri) {
+            return Err(Code::CrlMismatch)
+        }
+        let crl = match crl {
+            Some(crl) => crl,
+            None => return Ok(())
+        };
+        let revoked = match self.options.time() {
+            Some(time) => crl.contains_at(cert.serial_number(), time),
+            None => crl.contains(cert.serial_number())
+        };
+        if revoked {
+            Err(Code::Revoked)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Checks that the overclaim mode of a certificate is acceptable.
+    fn check_overclaim(&self, cert: &Cert) -> Result<(), Code> {
+        if !self.reconsidered && cert.overclaim() == Overclaim::Trim {
+            Err(Code::Reconsidered)
+        }
+        else {
+            Ok(())
+        }
+    }
+
+    /// Returns whether the reconsidered validation algorithm is accepted.
+    pub fn reconsidered(&self) -> bool {
+        self.reconsidered
+    }
+
+    /// Sets whether the reconsidered validation algorithm is accepted.
+    ///
+    /// [RFC 8360] defines an alternative validation algorithm under which
+    /// a certificate that claims more resources than its issuer holds is
+    /// trimmed down to the issuer’s resources rather than rejected. A
+    /// certificate opts into the algorithm through its certificate policy.
+    /// If this option is disabled, such certificates are rejected. This
+    /// applies to CA and router certificates as well as to the EE
+    /// certificates of signed objects.
+    ///
+    /// [RFC 8360]: https://tools.ietf.org/html/rfc8360
+    pub fn set_reconsidered(&mut self, reconsidered: bool) {
+        self.reconsidered = reconsidered
+    }
+
+    /// Returns the manifest policy.
+    pub fn manifest_policy(&self) -> ManifestPolicy {
+        self.policy
+    }
+
+    /// Sets the manifest policy.
+    pub fn set_manifest_policy(&mut self, policy: ManifestPolicy) {
+        self.policy = policy
+    }
+
+    /// Returns the fallback policy for failed publication points.
+    pub fn fallback_policy(&self) -> FallbackPolicy {
+        self.fallback
+    }
+
+    /// Sets the fallback policy for failed publication points.
+    ///
+    /// By default, publication points are rejected in all cases covered
+    /// by the policy. In order to use the previous objects of publication
+    /// points, a source for them needs to be set via
+    /// [`set_previous_source`].
+    ///
+    /// [`set_previous_source`]: #method.set_previous_source
+    pub fn set_fallback_policy(&mut self, policy: FallbackPolicy) {
+        self.fallback = policy
+    }
+
+    /// Sets the source for the objects of the last successful fetch.
+    ///
+    /// This source is used for publication points that failed for a
+    /// reason for which the fallback policy asks to use the previous
+    /// objects. The publication point is then processed again using only
+    /// objects from this source. If this fails, too, the publication point
+    /// is rejected.
+    pub fn set_previous_source(
+        &mut self, source: Arc<dyn ObjectSource + Send + Sync>
+    ) {
+        self.previous = Some(PreviousSource(source))
+    }
+
+    /// Sets the store for the objects of successfully validated points.
+    ///
+    /// The objects of every publication point processed without any
+    /// problems are recorded in the store. The store is also used as the
+    /// source for the objects of the last successful fetch, replacing a
+    /// source set via [`set_previous_source`].
+    ///
+    /// [`set_previous_source`]: #method.set_previous_source
+    pub fn set_validated_store<T>(&mut self, store: Arc<T>)
+    where T: ValidatedStore + Send + Sync + 'static {
+        self.validated = Some(ValidatedStoreRef(store.clone()));
+        self.previous = Some(PreviousSource(store));
+    }
+
+    /// Returns the time objects are validated against.
+    ///
+    /// This is the time given in the validation options if they ask for
+    /// historical validation or the current time of the validator’s clock
+    /// otherwise.
+    pub fn now(&self) -> Time {
+        match self.options.time() {
+            Some(time) => time,
+            None => self.clock.now()
+        }
+    }
+
+    /// Sets a fixed time objects are validated against.
+    pub fn set_now(&mut self, now: Time) {
+        self.clock = Arc::new(now)
+    }
+
+    /// Returns the clock providing the time to validate against.
+    pub fn clock(&self) -> &Arc<dyn Clock> {
+        &self.clock
+    }
+
+    /// Sets the clock providing the time to validate against.
+    ///
+    /// The clock is consulted for every check, so the time advances during
+    /// a validation run unless the clock is stopped.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock
+    }
+
+    /// Returns the options for the time of a validation run.
+    pub fn options(&self) -> ValidationOptions {
+        self.options
+    }
+
+    /// Sets the options for the time of a validation run.
+    ///
+    /// See [`ValidationOptions`] for how historical validation differs
+    /// from regular validation.
+    ///
+    /// [`ValidationOptions`]: struct.ValidationOptions.html
+    pub fn set_options(&mut self, options: ValidationOptions) {
+        self.options = options
+    }
+
+    /// Returns the number of worker threads used for parallel validation.
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Sets the number of worker threads used for parallel validation.
+    ///
+    /// The value is only used by [`validate_tal_parallel`] and
+    /// [`validate_ta_parallel`]. A value of zero is treated as one.
+    ///
+    /// [`validate_tal_parallel`]: #method.validate_tal_parallel
+    /// [`validate_ta_parallel`]: #method.validate_ta_parallel
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads
+    }
+
+    /// Returns the limit of concurrently processed points per host.
+    pub fn host_limit(&self) -> usize {
+        self.host_limit
+    }
+
+    /// Sets the limit of concurrently processed points per host.
+    ///
+    /// During parallel validation, at most this many publication points
+    /// whose manifest URIs share the same authority are processed at the
+    /// same time. This avoids hammering a single repository server with
+    /// requests if the object source fetches data on demand. A value of
+    /// zero is treated as one.
+    pub fn set_host_limit(&mut self, limit: usize) {
+        self.host_limit = limit
+    }
+}
+
+impl<S: ObjectSource> Validator<S> {
+    /// Performs a validation run for a TAL.
+    ///
+    /// The trust anchor certificate is taken from the first rsync URI of
+    /// the TAL for which the source provides a valid certificate. If there
+    /// is none, the run fails with `Error::TrustAnchor`.
+    pub fn validate_tal(&self, tal: &Tal) -> Result<Outcome, Error> {
+        let ta = self.find_ta(tal)?;
+        self.validate_ta(ta).map_err(Into::into)
+    }
+
+    /// Finds and validates the trust anchor certificate of a TAL.
+    fn find_ta(&self, tal: &Tal) -> Result<ResourceCert, Error> {
+        for uri in tal.uris() {
+            let uri = match *uri {
+                TalUri::Rsync(ref uri) => uri,
+                _ => continue
+            };
+            let bytes = match self.source.get(uri)? {
+                Some(bytes) => bytes,
+                None => continue
+            };
+            let cert = match Cert::decode_with_mode(bytes, self.mode()) {
+                Ok(cert) => cert,
+                Err(_) => continue
+            };
+            if let Ok(cert) = tal.validate_cert_at(
+                cert, self.strict, self.now()
+            ) {
+                return Ok(cert)
+            }
+        }
+        Err(Error::TrustAnchor)
+    }
+
+    /// Performs a validation run starting at a trust anchor certificate.
+    ///
+    /// The certificate must already have been validated as a trust anchor,
+    /// e.g., via `Tal::validate_cert`.
+    ///
+    /// The payload of the outcome is sorted and free of duplicates.
+    pub fn validate_ta(
+        &self, ta: ResourceCert
+    ) -> Result<Outcome, io::Error> {
+        let start = Instant::now();
+        let mut outcome = Outcome::new();
+        outcome.report.set_as_of(self.options.time());
+        let mut seen = HashMap::new();
+        let mut queue = vec![Task::new(ta)];
+        while let Some(task) = queue.pop() {
+            let manifest_uri = match task.ca.rpki_manifest() {
+                Some(uri) => uri.clone(),
+                None => continue
+            };
+
+            // Certificates may form loops in a broken or malicious
+            // repository, so we only process the publication point of a
+            // CA key again if all earlier candidates have failed. Since
+            // only successfully processed points produce candidates, this
+            // terminates.
+            let key = task.ca.subject_key_identifier();
+            if seen.get(&key) == Some(&true) {
+                continue
+            }
+            let (res, report) = self.process_point(&task, &manifest_uri)?;
+            seen.insert(key, res.is_ok());
+            let children = outcome.add_point(manifest_uri, res, report);
+            queue.extend(children.into_iter().rev());
+        }
+        outcome.finish(start.elapsed());
+        Ok(outcome)
+    }
+
+    /// Processes the publication point of the CA of a task.
+    ///
+    /// Returns the outcome of processing together with its report. An
+    /// error is only returned if the object source has failed.
+    fn process_point(
+        &self, task: &Task, manifest_uri: &uri::Rsync
+    ) -> Result<(Result<PubPoint, FailedFetch>, PointReport), io::Error> {
+        let start = Instant::now();
+        let mut report = PointReport::new(
+            manifest_uri.clone(), task.chain.clone()
+        );
+        let ca = &task.ca;
+        let previous = match self.previous {
+            Some(ref previous) if !self.options.is_historical() => {
+                Some(previous.0.as_ref() as &dyn ObjectSource)
+            }
+            _ => None
+        };
+        let res = match self.try_process_point(
+            &self.source, self.fallback, previous,
+            task, manifest_uri, &mut report
+        ) {
+            Ok(point) => {
+                if let Some(ref validated) = self.validated {
+                    if report.fallback().is_none()
+                        && !self.options.is_historical()
+                    {
+                        validated.0.update_point(
+                            manifest_uri, &point.objects, self.now()
+                        )?;
+                    }
+                }
+                Ok(point)
+            }
+            Err(PointError::Io(err)) => return Err(err),
+            Err(PointError::Failed(err)) => {
+                if let Some(point) = self.use_previous(
+                    task, manifest_uri, err.reason(), &mut report
+                )? {
+                    report.set_duration(start.elapsed());
+                    return Ok((Ok(point), report))
+                }
+                let object = match (err.file(), ca.ca_repository()) {
+                    (Some(file), Some(repository)) if is_file_name(file) => {
+                        Some(repository.join(file))
+                    }
+                    _ => None
+                };
+                report.set_failure(err.reason(), object);
+                Err(err)
+            }
+        };
+        report.set_duration(start.elapsed());
+        Ok((res, report))
+    }
+
+    /// Processes a failed publication point using the previous objects.
+    ///
+    /// If the fallback policy asks for the previous objects to be used in
+    /// case of a failure for `reason` and a source for them is available,
+    /// processes the publication point again using that source. If this
+    /// succeeds, replaces `report` and returns the publication point.
+    fn use_previous(
+        &self,
+        task: &Task,
+        manifest_uri: &uri::Rsync,
+        reason: FailureReason,
+        report: &mut PointReport,
+    ) -> Result<Option<PubPoint>, io::Error> {
+        if self.fallback.strategy(reason) != Some(Fallback::UsePrevious) {
+            return Ok(None)
+        }
+        let previous = match self.previous {
+            Some(ref previous) => previous,
+            None => return Ok(None)
+        };
+        let mut previous_report = PointReport::new(
+            manifest_uri.clone(), task.chain.clone()
+        );
+        match self.try_process_point(
+            previous.0.as_ref(), FallbackPolicy::default(), None,
+            task, manifest_uri, &mut previous_report
+        ) {
+            Ok(point) => {
+                previous_report.set_fallback(Fallback::UsePrevious, reason);
+                *report = previous_report;
+                Ok(Some(point))
+            }
+            Err(PointError::Io(err)) => Err(err),
+            Err(PointError::Failed(_)) => Ok(None)
+        }
+    }
+
+    /// Processes the publication point of a CA.
+    ///
+    /// Objects are read from `source`. They are added to `report` as they
+    /// are processed. Once the CRL has been validated, all remaining
+    /// objects are processed even if one of them has already caused the
+    /// publication point to fail so that the report is complete.
+    ///
+    /// Failures for which `fallback` asks for a warning only are recorded
+    /// in the report and processing continues.
+    ///
+    /// If `previous` is given, the manifest number is checked against that
+    /// of the manifest for the point in this source.
+    ///
+    /// Each valid object is reported with the earliest expiry along its
+    /// chain, i.e., the smaller of the task’s expiry and the object’s own
+    /// expiry.
+    fn try_process_point(
+        &self,
+        source: &dyn ObjectSource,
+        fallback: FallbackPolicy,
+        previous: Option<&dyn ObjectSource>,
+        task: &Task,
+        manifest_uri: &uri::Rsync,
+        report: &mut PointReport,
+    ) -> Result<PubPoint, PointError> {
+        let (ca, expires) = (&task.ca, task.expires);
+        let check = ManifestCheck::new(self.policy, self.now(), None);
+        let repository = match ca.ca_repository() {
+            Some(uri) => uri,
+            None => return Err(check.invalid().into())
+        };
+
+        // RFC 9286, sections 6.1 and 6.2: acquire and validate the
+        // manifest.
+        let manifest = match source.get(manifest_uri)? {
+            Some(bytes) => bytes,
+            None => {
+                report.push(ObjectReport::rejected(
+                    manifest_uri.clone(), ObjectStatus::Missing, Code::Missing
+                ));
+                return Err(check.missing().into())
+            }
+        };
+        let mut point = PubPoint::default();
+        point.objects.push((manifest_uri.clone(), manifest.clone()));
+        let previous = match previous {
+            Some(previous) => {
+                self.previous_number(previous, manifest_uri, &manifest)?
+            }
+            None => None
+        };
+        let (ee, content) = match self.process_manifest(ca, manifest) {
+            Ok(some) => some,
+            Err(code) => {
+                report.push(ObjectReport::rejected(
+                    manifest_uri.clone(), ObjectStatus::Invalid, code
+                ));
+                return Err(check.invalid().into())
+            }
+        };
+        let check = ManifestCheck::new(self.policy, self.now(), previous);
+        report.push(ObjectReport::new(
+            manifest_uri.clone(), ObjectStatus::Valid, Vec::new()
+        ).with_expires(Some(min(
+            expires, min(ee.validity().not_after(), content.next_update())
+        ))));
+
+        // RFC 9286, sections 6.3 and 6.4: check the manifest content.
+        let stale = self.now() > content.next_update();
+        let crl_check = if stale && fallback.stale == Fallback::Warn {
+            report.manifest_issue(Issue::warning(Code::StaleManifest));
+            report.set_fallback(Fallback::Warn, FailureReason::Stale);
+            check.check_manifest(&content, true)?
+        }
+        else {
+            check.valid(&content)?
+        };
+        let alg = content.file_hash_alg();
+        let hashes: HashMap<_, _> = content.iter().map(|item| {
+            item.into_pair()
+        }).collect();
+
+        // RFC 9286, section 6.4: acquire and validate the CRL.
+        let crl_name = crl_check.crl_name().clone();
+        if !is_file_name(&crl_name) {
+            report.manifest_issue(Code::BadFileName);
+            return Err(
+                crl_check.crl(ObjectStatus::Invalid).unwrap_err().into()
+            )
+        }
+        let crl_uri = repository.join(&crl_name);
+        let crl = match self.fetch(
+            source, &crl_uri, &hashes[&crl_name], alg
+        )? {
+            Ok(bytes) => {
+                point.objects.push((crl_uri.clone(), bytes.clone()));
+                self.process_crl(
+                    ca, bytes, fallback.stale == Fallback::Warn
+                ).map_err(|code| {
+                    (ObjectStatus::Invalid, code)
+                })
+            }
+            Err(status) => Err(status)
+        };
+        let (crl, files) = match crl {
+            Ok(crl) => {
+                let mut issues = Vec::new();
+                if crl.next_update() < self.now() {
+                    issues.push(Issue::warning(Code::StaleCrl));
+                    report.set_fallback(Fallback::Warn, FailureReason::Stale);
+                }
+                report.push(ObjectReport::new(
+                    crl_uri.clone(), ObjectStatus::Valid, issues
+                ).with_expires(Some(min(expires, crl.next_update()))));
+                (Some(crl), crl_check.crl(ObjectStatus::Valid)?)
+            }
+            Err((status, code)) => {
+                report.push(
+                    ObjectReport::rejected(crl_uri.clone(), status, code)
+                );
+                let err = crl_check.clone().crl(status).unwrap_err();
+                let unavailable = status == ObjectStatus::Missing
+                    || status == ObjectStatus::HashMismatch;
+                if !unavailable || fallback.crl_missing != Fallback::Warn {
+                    return Err(err.into())
+                }
+                // We go on without the CRL as if it were valid.
+                report.set_fallback(Fallback::Warn, err.reason());
+                (None, crl_check.crl(ObjectStatus::Valid)?)
+            }
+        };
+
+        // The manifest’s EE certificate must not have been revoked.
+        if let Err(code) = self.check_crl(&ee, &crl_uri, crl.as_ref()) {
+            report.reject_manifest(code);
+            return Err(
+                FailedFetch::new(FailureReason::ManifestInvalid).into()
+            )
+        }
+
+        // RFC 9286, section 6.5: acquire and validate all other files.
+        let mut names: Vec<_> = files.pending().cloned().collect();
+        names.sort();
+        let mut files = Ok(files);
+        for name in names {
+            if !is_file_name(&name) {
+                report.manifest_issue(Code::BadFileName);
+                files = files.and_then(|files| {
+                    files.file(&name, ObjectStatus::Invalid)
+                });
+                continue
+            }
+            let uri = repository.join(&name);
+            let mut issues = Vec::new();
+            let mut object_expires = None;
+            let status = match self.fetch(
+                source, &uri, &hashes[&name], alg
+            )? {
+                Ok(bytes) => {
+                    point.objects.push((uri.clone(), bytes.clone()));
+                    match self.process_object(
+                        task, &uri, &name, bytes, &crl_uri, crl.as_ref(),
+                        &mut point, &mut issues
+                    ) {
+                        Ok(res) => {
+                            object_expires = res.map(|res| min(expires, res));
+                            if crl.is_none() {
+                                issues.push(Code::NoRevocationCheck.into());
+                            }
+                            ObjectStatus::Valid
+                        }
+                        Err(code) => {
+                            issues.push(code.into());
+                            ObjectStatus::Invalid
+                        }
+                    }
+                }
+                Err((status, code)) => {
+                    issues.push(code.into());
+                    status
+                }
+            };
+            report.push(
+                ObjectReport::new(uri, status, issues).with_expires(
+                    object_expires
+                )
+            );
+            if status == ObjectStatus::HashMismatch
+                && fallback.hash_mismatch == Fallback::Warn
+            {
+                report.set_fallback(
+                    Fallback::Warn, FailureReason::FileHashMismatch
+                );
+                files = files.map(|files| files.ignore(&name));
+            }
+            else {
+                files = files.and_then(|files| files.file(&name, status));
+            }
+        }
+        files?.finish()?;
+        Ok(point)
+    }
+
+    /// Fetches a file listed on a manifest and checks its hash.
+    ///
+    /// The inner error is the status of the object if it isn’t usable and
+    /// the code of the issue.
+    fn fetch(
+        &self,
+        source: &dyn ObjectSource,
+        uri: &uri::Rsync,
+        hash: &Bytes,
+        alg: DigestAlgorithm,
+    ) -> Result<Result<Bytes, (ObjectStatus, Code)>, io::Error> {
+        let bytes = match source.get(uri)? {
+            Some(bytes) => bytes,
+            None => {
+                return Ok(Err((ObjectStatus::Missing, Code::Missing)))
+            }
+        };
+        if ManifestHash::new(hash.clone(), alg).verify(&bytes).is_err() {
+            return Ok(Err((ObjectStatus::HashMismatch, Code::HashMismatch)))
+        }
+        Ok(Ok(bytes))
+    }
+
+    /// Returns the number of the previous manifest of a publication point.
+    ///
+    /// The manifest is taken from `source`. Returns `None` if there is no
+    /// such manifest, it is identical to `current`, or it can’t be
+    /// decoded.
+    fn previous_number(
+        &self,
+        source: &dyn ObjectSource,
+        manifest_uri: &uri::Rsync,
+        current: &Bytes,
+    ) -> Result<Option<Serial>, io::Error> {
+        let bytes = match source.get(manifest_uri)? {
+            Some(bytes) if bytes != current => bytes,
+            _ => return Ok(None)
+        };
+        Ok(Manifest::decode(bytes, self.strict).ok().map(|manifest| {
+            manifest.content().manifest_number()
+        }))
+    }
+
+    /// Validates the manifest of a publication point.
+    fn process_manifest(
+        &self, ca: &ResourceCert, bytes: Bytes
+    ) -> Result<(ResourceCert, ManifestContent), Code> {
+        let manifest = Manifest::decode(bytes, self.strict).map_err(|_| {
+            Code::Malformed
+        })?;
+        self.check_overclaim(manifest.cert())?;
+        manifest.validate_at(ca, self.strict, self.now()).map_err(|_| {
+            Code::Invalid
+        })
+    }
+
+    /// Validates the CRL of a publication point.
+    ///
+    /// If `allow_stale` is `true`, a CRL whose nextUpdate time has passed
+    /// is accepted.
+    fn process_crl(
+        &self, ca: &ResourceCert, bytes: Bytes, allow_stale: bool
+    ) -> Result<Crl, Code> {
+        let crl = Crl::decode_with_mode(bytes, self.mode()).map_err(|_| {
+            Code::Malformed
+        })?;
+        ca.algorithm_policy().check_signature(crl.signature())?;
+        crl.validate(ca.subject_public_key_info())?;
+        if *crl.authority_key_identifier() != ca.subject_key_identifier() {
+            return Err(Code::Invalid)
+        }
+        if !allow_stale && crl.next_update() < self.now() {
+            return Err(Code::StaleCrl)
+        }
+        Ok(crl)
+    }
+
+    /// Validates an object listed on the manifest.
+    ///
+    /// The kind of object is determined by its file name extension. Files
+    /// of unknown type are ignored with a warning added to `issues`.
+    ///
+    /// Returns the time the object expires if it has an EE certificate or
+    /// is a certificate itself. Child CAs are added to `point` as tasks
+    /// derived from `task`.
+    #[allow(clippy::too_many_arguments)]
+    fn process_object(
+        &self,
+        task: &Task,
+        uri: &uri::Rsync,
+        name: &[u8],
+        bytes: Bytes,
+        crl_uri: &uri::Rsync,
+        crl: Option<&Crl>,
+        point: &mut PubPoint,
+        issues: &mut Vec<Issue>,
+    ) -> Result<Option<Time>, Code> {
+        let ca = &task.ca;
+        if name.ends_with(b".cer") {
+            self.process_cert(
+                task, uri, bytes, crl_uri, crl, point, issues
+            ).map(Some)
+        }
+        else if name.ends_with(b".roa") {
+            self.process_roa(ca, bytes, crl_uri, crl, point).map(Some)
+        }
+        else if name.ends_with(b".asa") {
+            self.process_aspa(ca, bytes, crl_uri, crl, point, issues)
+        }
+        else if name.ends_with(b".gbr") {
+            let gbr = Gbr::decode(bytes, self.strict).map_err(|_| {
+                Code::Malformed
+            })?;
+            self.check_crl(gbr.cert(), crl_uri, crl)?;
+            self.check_overclaim(gbr.cert())?;
+            let expires = gbr.cert().validity().not_after();
+            gbr.validate_at(ca, self.strict, self.now())?;
+            Ok(Some(expires))
+        }
+        else if name.ends_with(b".crl") {
+            // The manifest check makes sure there is only one CRL which
+            // has already been processed. Any other CRL is an error.
+            Err(Code::ExtraCrl)
+        }
+        else {
+            issues.push(Code::UnknownType.into());
+            Ok(None)
+        }
+    }
+
+    /// Validates a CA or router certificate.
+    ///
+    /// Returns the time the certificate expires.
+    #[allow(clippy::too_many_arguments)]
+    fn process_cert(
+        &self,
+        task: &Task,
+        uri: &uri::Rsync,
+        bytes: Bytes,
+        crl_uri: &uri::Rsync,
+        crl: Option<&Crl>,
+        point: &mut PubPoint,
+        issues: &mut Vec<Issue>,
+    ) -> Result<Time, Code> {
+        let cert = Cert::decode_with_mode(bytes, self.mode()).map_err(|_| {
+            Code::Malformed
+        })?;
+        self.check_crl(&cert, crl_uri, crl)?;
+        self.check_overclaim(&cert)?;
+        let ca = &task.ca;
+        let cert_expires = cert.validity().not_after();
+        if cert.is_bgpsec_router() {
+            let cert = cert.validate_router_at(ca, self.strict, self.now())?;
+            point.payload.extend(RouterKey::from_cert(&cert).map(Into::into));
+        }
+        else {
+            let cert = cert.validate_ca_at(ca, self.strict, self.now())?;
+            if TrimmedResources::from_cert(&cert).is_some() {
+                issues.push(Code::ResourcesTrimmed.into());
+            }
+            point.children.push(task.child(cert, uri.clone()));
+        }
+        Ok(cert_expires)
+    }
+
+    /// Validates a ROA.
+    ///
+    /// Returns the time the ROA’s EE certificate expires.
+    fn process_roa(
+        &self,
+        ca: &ResourceCert,
+        bytes: Bytes,
+        crl_uri: &uri::Rsync,
+        crl: Option<&Crl>,
+        point: &mut PubPoint,
+    ) -> Result<Time, Code> {
+        let roa = Roa::decode(bytes, self.strict).map_err(|_| {
+            Code::Malformed
+        })?;
+        self.check_crl(roa.cert(), crl_uri, crl)?;
+        self.check_overclaim(roa.cert())?;
+        let expires = roa.cert().validity().not_after();
+        let roa = roa.process_at(ca, self.strict, self.now(), |_| Ok(()))?;
+        let mut payload = Vec::new();
+        for (addr, max_len, asn) in roa.iter_origins() {
+            let prefix = Prefix::new(
+                addr.address(), addr.address_length()
+            ).map_err(|_| Code::Invalid)?;
+            payload.push(
+                RouteOrigin::new(prefix, max_len, asn).ok_or(
+                    Code::Invalid
+                )?.into()
+            );
+        }
+        point.payload.extend(payload);
+        Ok(expires)
+    }
+
+    /// Validates an ASPA object.
+    #[cfg(feature = "aspa")]
+    fn process_aspa(
+        &self,
+        ca: &ResourceCert,
+        bytes: Bytes,
+        crl_uri: &uri::Rsync,
+        crl: Option<&Crl>,
+        point: &mut PubPoint,
+        _issues: &mut Vec<Issue>,
+    ) -> Result<Option<Time>, Code> {
+        let aspa = crate::aspa::Aspa::decode(bytes, self.strict).map_err(
+            |_| Code::Malformed
+        )?;
+        self.check_crl(aspa.cert(), crl_uri, crl)?;
+        self.check_overclaim(aspa.cert())?;
+        let expires = aspa.cert().validity().not_after();
+        let (_, content) = aspa.validate_at(ca, self.strict, self.now())?;
+        point.payload.push(
+            crate::payload::Aspa::new(
+                content.customer_as(),
+                content.provider_as_set().cloned().collect()
+            ).into()
+        );
+        Ok(Some(expires))
+    }
+
+    /// Ignores an ASPA object.
+    ///
+    /// Without the `aspa` feature, ASPA objects are treated like any other
+    /// object of unknown type.
+    #[cfg(not(feature = "aspa"))]
+    fn process_aspa(
+        &self,
+        _ca: &ResourceCert,
+        _bytes: Bytes,
+        _crl_uri: &uri::Rsync,
+        _crl: Option<&Crl>,
+        _point: &mut PubPoint,
+        issues: &mut Vec<Issue>,
+    ) -> Result<Option<Time>, Code> {
+        issues.push(Code::UnknownType.into());
+        Ok(None)
+    }
+}
+
+
+//------------ Fallback ------------------------------------------------------
+
+/// What to do with a publication point that can’t be used as is.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fallback {
+    /// Reject the publication point.
+    ///
+    /// This is what RFC 9286 requires and the default.
+    Reject,
+
+    /// Use the publication point regardless but report a warning.
+    Warn,
+
+    /// Use the objects of the last successful fetch instead.
+    ///
+    /// If these aren’t available or aren’t valid anymore, the publication
+    /// point is rejected.
+    UsePrevious,
+}
+
+#[allow(clippy::derivable_impls)] // #[default] requires Rust 1.62.
+impl Default for Fallback {
+    fn default() -> Self {
+        Fallback::Reject
+    }
+}
+
+
+//------------ FallbackPolicy ------------------------------------------------
+
+/// The strategies for dealing with publication points that failed.
+///
+/// Section 6.6 of RFC 9286 requires a publication point to be rejected if
+/// anything is wrong with it. In practice, operators may prefer to keep
+/// using data for some of the more common failure cases. For each of
+/// these cases, the policy defines a [`Fallback`] strategy.
+///
+/// [`Fallback`]: enum.Fallback.html
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct FallbackPolicy {
+    /// What to do if the manifest is stale.
+    stale: Fallback,
+
+    /// What to do if a file other than the CRL doesn’t match its hash.
+    hash_mismatch: Fallback,
+
+    /// What to do if the CRL is missing or doesn’t match its hash.
+    crl_missing: Fallback,
+}
+
+impl FallbackPolicy {
+    /// Returns the strategy for stale manifests.
+    pub fn stale(self) -> Fallback {
+        self.stale
+    }
+
+    /// Sets the strategy for stale manifests.
+    ///
+    /// With [`Fallback::Warn`], a manifest whose nextUpdate time has passed
+    /// is used regardless.
+    ///
+    /// [`Fallback::Warn`]: enum.Fallback.html#variant.Warn
+    pub fn set_stale(&mut self, value: Fallback) {
+        self.stale = value
+    }
+
+    /// Returns the strategy for files not matching the manifest hash.
+    pub fn hash_mismatch(self) -> Fallback {
+        self.hash_mismatch
+    }
+
+    /// Sets the strategy for files not matching the manifest hash.
+    ///
+    /// With [`Fallback::Warn`], a file other than the CRL that doesn’t
+    /// match its manifest hash is ignored and all other files are used.
+    ///
+    /// [`Fallback::Warn`]: enum.Fallback.html#variant.Warn
+    pub fn set_hash_mismatch(&mut self, value: Fallback) {
+        self.hash_mismatch = value
+    }
+
+    /// Returns the strategy for a missing CRL.
+    pub fn crl_missing(self) -> Fallback {
+        self.crl_missing
+    }
+
+    /// Sets the strategy for a missing CRL.
+    ///
+    /// The strategy is used if the CRL is missing or doesn’t match its
+    /// manifest hash. With [`Fallback::Warn`], all objects are used
+    /// without checking whether they have been revoked.
+    ///
+    /// [`Fallback::Warn`]: enum.Fallback.html#variant.Warn
+    pub fn set_crl_missing(&mut self, value: Fallback) {
+        self.crl_missing = value
+    }
+
+    /// Returns the strategy for a publication point failed for `reason`.
+    ///
+    /// Returns `None` if the policy doesn’t cover the reason, in which case
+    /// the publication point is always rejected.
+    ///
+    /// A manifest number regression is only a failure if the manifest
+    /// policy asks for the cached objects to be used, so the previous
+    /// objects are always used for it.
+    pub fn strategy(self, reason: FailureReason) -> Option<Fallback> {
+        match reason {
+            FailureReason::NumberRegression => Some(Fallback::UsePrevious),
+            FailureReason::Stale => Some(self.stale),
+            FailureReason::FileHashMismatch => Some(self.hash_mismatch),
+            FailureReason::CrlMissing | FailureReason::CrlHashMismatch => {
+                Some(self.crl_missing)
+            }
+            _ => None
+        }
+    }
+}
+
+
+//------------ ValidationOptions ---------------------------------------------
+
+/// Options for the time a validation run is performed for.
+///
+/// By default, a validation run checks objects against the current time
+/// of the validator’s clock. Via [`at_time`], the run is instead performed
+/// as of a given past time, e.g., to audit what was valid when a routing
+/// incident occurred. In such a historical validation,
+///
+/// * the validity of certificates and objects as well as the staleness of
+///   CRLs and manifests are checked against the given time,
+/// * certificates revoked by a CRL after the given time are considered
+///   not yet revoked, and
+/// * the store for validated objects is not updated.
+///
+/// The object source needs to provide the objects as they were published
+/// at the given time. The time is recorded in the report of the run.
+///
+/// [`at_time`]: #method.at_time
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ValidationOptions {
+    /// The time of a historical validation.
+    time: Option<Time>,
+}
+
+impl ValidationOptions {
+    /// Returns the options for validating at the current time.
+    pub fn current() -> Self {
+        Self::default()
+    }
+
+    /// Returns the options for validating as of the given time.
+    pub fn at_time(time: Time) -> Self {
+        ValidationOptions { time: Some(time) }
+    }
+
+    /// Returns the time of a historical validation.
+    pub fn time(self) -> Option<Time> {
+        self.time
+    }
+
+    /// Returns whether the options ask for a historical validation.
+    pub fn is_historical(self) -> bool {
+        self.time.is_some()
+    }
+}
+
+
+//------------ PreviousSource ------------------------------------------------
+
+/// The source for the objects of the last successful fetch.
+#[derive(Clone)]
+struct PreviousSource(Arc<dyn ObjectSource + Send + Sync>);
+
+impl fmt::Debug for PreviousSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PreviousSource")
+    }
+}
+
+
+//------------ ValidatedStoreRef ---------------------------------------------
+
+/// The store for the objects of successfully validated points.
+#[derive(Clone)]
+struct ValidatedStoreRef(Arc<dyn ValidatedStore + Send + Sync>);
+
+impl fmt::Debug for ValidatedStoreRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ValidatedStoreRef")
+    }
+}
+
+
+//------------ Outcome -------------------------------------------------------
+
+/// The outcome of a validation run.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    /// The validated payload.
+    payload: Vec<Payload>,
+
+    /// The number of publication points successfully processed.
+    points: usize,
+
+    /// The publication points that failed, keyed by manifest URI.
+    failures: Vec<(uri::Rsync, FailedFetch)>,
+
+    /// The detailed report of the run.
+    report: ValidationReport,
+
+    /// The index of the report of each publication point in `report`.
+    reports: HashMap<uri::Rsync, usize>,
+}
+
+impl Outcome {
+    /// Creates a new, empty outcome.
+    fn new() -> Self {
+        Outcome {
+            payload: Vec::new(),
+            points: 0,
+            failures: Vec::new(),
+            report: ValidationReport::new(),
+            reports: HashMap::new(),
+        }
+    }
+
+    /// Adds the result of processing a publication point.
+    ///
+    /// If the publication point has been processed before using a
+    /// different certificate for the CA, its earlier report and failure
+    /// are replaced.
+    ///
+    /// Returns the tasks for the child CAs of the publication point if it
+    /// was valid.
+    fn add_point(
+        &mut self,
+        manifest_uri: uri::Rsync,
+        res: Result<PubPoint, FailedFetch>,
+        report: PointReport,
+    ) -> Vec<Task> {
+        match self.reports.get(&manifest_uri) {
+            Some(&index) => {
+                self.report.retry(index, report);
+                self.failures.retain(|item| item.0 != manifest_uri);
+            }
+            None => {
+                self.reports.insert(
+                    manifest_uri.clone(), self.report.push(report)
+                );
+            }
+        }
+        match res {
+            Ok(point) => {
+                self.points += 1;
+                self.payload.extend(point.payload);
+                point.children
+            }
+            Err(err) => {
+                self.failures.push((manifest_uri, err));
+                Vec::new()
+            }
+        }
+    }
+
+    /// Finishes the outcome of a run that took `duration`.
+    fn finish(&mut self, duration: Duration) {
+        self.payload.sort();
+        self.payload.dedup();
+        self.report.set_duration(duration);
+    }
+
+    /// Returns the validated payload.
+    pub fn payload(&self) -> &[Payload] {
+        &self.payload
+    }
+
+    /// Converts the outcome into the validated payload.
+    pub fn into_payload(self) -> Vec<Payload> {
+        self.payload
+    }
+
+    /// Returns the number of publication points successfully processed.
+    pub fn valid_points(&self) -> usize {
+        self.points
+    }
+
+    /// Returns the publication points that could not be used.
+    ///
+    /// Each item contains the URI of the publication point’s manifest and
+    /// the reason for the failure.
+    pub fn failures(&self) -> &[(uri::Rsync, FailedFetch)] {
+        &self.failures
+    }
+
+    /// Returns the detailed report of the validation run.
+    pub fn report(&self) -> &ValidationReport {
+        &self.report
+    }
+
+    /// Converts the outcome into the payload and the report.
+    pub fn into_parts(self) -> (Vec<Payload>, ValidationReport) {
+        (self.payload, self.report)
+    }
+}
+
+
+//------------ PubPoint ------------------------------------------------------
+
+/// The data gathered from a successfully processed publication point.
+#[derive(Clone, Debug, Default)]
+struct PubPoint {
+    /// The payload of the publication point’s objects.
+    payload: Vec<Payload>,
+
+    /// The tasks for the child CAs.
+    children: Vec<Task>,
+
+    /// The URIs and content of all objects of the publication point.
+    objects: Vec<(uri::Rsync, Bytes)>,
+}
+
+
+//------------ Task ----------------------------------------------------------
+
+/// A CA whose publication point needs processing.
+#[derive(Clone, Debug)]
+struct Task {
+    /// The validated certificate of the CA.
+    ca: ResourceCert,
+
+    /// The earliest expiry of the certificates in the chain.
+    ///
+    /// This includes the trust anchor and the CA certificate itself.
+    expires: Time,
+
+    /// The URIs of the CA certificates below the trust anchor.
+    ///
+    /// The last element is the URI of the CA’s own certificate.
+    chain: Vec<uri::Rsync>,
+}
+
+impl Task {
+    /// Creates the task for a trust anchor.
+    fn new(ta: ResourceCert) -> Self {
+        Task {
+            expires: ta.validity().not_after(),
+            ca: ta,
+            chain: Vec::new(),
+        }
+    }
+
+    /// Creates the task for a child CA found at `uri`.
+    fn child(&self, ca: ResourceCert, uri: uri::Rsync) -> Self {
+        let mut chain = self.chain.clone();
+        chain.push(uri);
+        Task {
+            expires: min(self.expires, ca.validity().not_after()),
+            ca,
+            chain,
+        }
+    }
+}
+
+
+//------------ PointError ----------------------------------------------------
+
+/// Processing a publication point has ended early.
+#[derive(Debug)]
+enum PointError {
+    /// The object source has failed.
+    Io(io::Error),
+
+    /// The publication point can’t be used.
+    Failed(FailedFetch),
+}
+
+impl From<io::Error> for PointError {
+    fn from(err: io::Error) -> Self {
+        PointError::Io(err)
+    }
+}
+
+impl From<FailedFetch> for PointError {
+    fn from(err: FailedFetch) -> Self {
+        PointError::Failed(err)
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Returns whether a file name on a manifest is acceptable.
+///
+/// Section 4.2.2 of RFC 9286 limits file names to letters, digits, hyphen,
+/// and underscore followed by a single period and a three letter
+/// extension. We are a bit more lenient and only make sure the name can’t
+/// be used to escape the publication point.
+fn is_file_name(name: &[u8]) -> bool {
+    !name.is_empty() && name[0] != b'.' && name.iter().all(|&ch| {
+        ch.is_ascii_alphanumeric() || ch == b'-' || ch == b'_' || ch == b'.'
+    })
+}
+
+
+//------------ Error ---------------------------------------------------------
+
+/// A validation run has failed.
+#[derive(Debug)]
+pub enum Error {
+    /// The object source has failed.
+    Io(io::Error),
+
+    /// No valid trust anchor certificate was found.
+    TrustAnchor,
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::TrustAnchor => {
+                f.write_str("no valid trust anchor certificate")
+            }
+        }
+    }
+}
+
+impl error::Error for Error { }
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::str::FromStr;
+    use crate::tal::TalInfo;
+    use super::*;
+    use super::report::Severity;
+
+    fn uri(s: &str) -> uri::Rsync {
+        uri::Rsync::from_str(s).unwrap()
+    }
+
+    pub(crate) fn ripe_source() -> HashMap<uri::Rsync, Bytes> {
+        let mut res = HashMap::new();
+        res.insert(
+            uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.mft"),
+            Bytes::from_static(include_bytes!("../../test-data/ta.mft"))
+        );
+        res.insert(
+            uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl"),
+            Bytes::from_static(include_bytes!("../../test-data/ta.crl"))
+        );
+        res.insert(
+            uri(
+                "rsync://rpki.ripe.net/repository/\
+                 2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+            ),
+            Bytes::from_static(include_bytes!("../../test-data/ca1.cer"))
+        );
+        res
+    }
+
+    pub(crate) fn ripe_ta(at: Time) -> ResourceCert {
+        Cert::decode(
+            include_bytes!("../../test-data/ta.cer").as_ref()
+        ).unwrap().validate_ta_at(
+            TalInfo::from_name("ripe".into()).into_arc(), false, at
+        ).unwrap()
+    }
+
+    #[test]
+    fn validate_ripe() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(ripe_source());
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+
+        // The TA’s publication point is complete and leads us to the
+        // child CA whose manifest we don’t have.
+        assert_eq!(outcome.valid_points(), 1);
+        assert!(outcome.payload().is_empty());
+        assert_eq!(outcome.failures().len(), 1);
+        assert_eq!(
+            outcome.failures()[0].0,
+            uri(
+                "rsync://rpki.ripe.net/repository/aca/\
+                 Kn3R14fXk-TIr1bhl9Tu2Sr2uhM.mft"
+            )
+        );
+        assert_eq!(
+            outcome.failures()[0].1.reason(), FailureReason::ManifestMissing
+        );
+
+        let points: Vec<_> = outcome.report().publication_points().collect();
+        assert_eq!(points.len(), 2);
+        assert!(points[0].is_valid());
+        assert!(points[0].chain().is_empty());
+        assert_eq!(points[0].candidates(), 1);
+        assert_eq!(
+            points[1].chain(),
+            [uri(
+                "rsync://rpki.ripe.net/repository/\
+                 2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+            )]
+        );
+        let objects: Vec<_> = points[0].objects().map(|object| {
+            (object.uri().to_string(), object.status())
+        }).collect();
+        assert_eq!(
+            objects,
+            [
+                (
+                    "rsync://rpki.ripe.net/repository/ripe-ncc-ta.mft".into(),
+                    ObjectStatus::Valid
+                ),
+                (
+                    "rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl".into(),
+                    ObjectStatus::Valid
+                ),
+                (
+                    "rsync://rpki.ripe.net/repository/\
+                     2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer".into(),
+                    ObjectStatus::Valid
+                ),
+            ]
+        );
+        assert!(!points[1].is_valid());
+        assert_eq!(
+            points[1].failure(), Some(FailureReason::ManifestMissing)
+        );
+        let issues: Vec<_> = outcome.report().issues(
+            Severity::Error
+        ).map(|(_, issue)| issue.code()).collect();
+        assert_eq!(issues, [Code::Missing]);
+
+        let json = serde_json::to_value(outcome.report()).unwrap();
+        let point = &json["publication_points"][1];
+        assert_eq!(point["failure"], "manifest-missing");
+        assert_eq!(point["objects"][0]["status"], "missing");
+        assert_eq!(point["objects"][0]["issues"][0]["severity"], "error");
+        assert_eq!(point["objects"][0]["issues"][0]["code"], "missing");
+    }
+
+    #[test]
+    fn validate_ripe_expiry() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(ripe_source());
+        validator.set_now(at);
+        let ta = ripe_ta(at);
+        let ta_expires = ta.validity().not_after();
+        let outcome = validator.validate_ta(ta).unwrap();
+        let report = outcome.report();
+
+        let point = report.publication_points().next().unwrap();
+        let expires: Vec<_> = point.objects().map(|object| {
+            object.expires().unwrap()
+        }).collect();
+        assert_eq!(expires.len(), 3);
+        assert!(expires.iter().all(|&expires| expires <= ta_expires));
+        let crl = Crl::decode(
+            include_bytes!("../../test-data/ta.crl").as_ref()
+        ).unwrap();
+        assert_eq!(expires[1], min(ta_expires, crl.next_update()));
+
+        let earliest = *expires.iter().min().unwrap();
+        assert_eq!(point.earliest_expiry(), Some(earliest));
+        assert_eq!(report.earliest_expiry(), Some(earliest));
+        assert_eq!(report.expiring(at).count(), 0);
+        assert_eq!(
+            report.expiring(earliest).collect::<Vec<_>>(),
+            point.objects().filter(|object| {
+                object.expires() == Some(earliest)
+            }).map(|object| (object.uri(), earliest)).collect::<Vec<_>>()
+        );
+        assert_eq!(report.expiring(ta_expires).count(), 3);
+    }
+
+    #[test]
+    fn validate_ripe_clock() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(ripe_source());
+        validator.set_clock(Arc::new(at));
+        assert_eq!(validator.now(), at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+
+        // Once the CRL and manifest are stale, the point is rejected.
+        let crl = Crl::decode(
+            include_bytes!("../../test-data/ta.crl").as_ref()
+        ).unwrap();
+        validator.set_clock(Arc::new(
+            crl.next_update() + chrono::Duration::days(1)
+        ));
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 0);
+    }
+
+    #[test]
+    fn validate_ripe_broken() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+
+        // Missing CRL.
+        let mut source = ripe_source();
+        source.remove(
+            &uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl")
+        );
+        let mut validator = Validator::new(source);
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 0);
+        assert_eq!(
+            outcome.failures()[0].1.reason(), FailureReason::CrlMissing
+        );
+
+        // Child certificate doesn’t match the hash.
+        let mut source = ripe_source();
+        source.insert(
+            uri(
+                "rsync://rpki.ripe.net/repository/\
+                 2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+            ),
+            Bytes::from_static(include_bytes!("../../test-data/ta.cer"))
+        );
+        let mut validator = Validator::new(source);
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 0);
+        assert_eq!(
+            outcome.failures()[0].1.reason(),
+            FailureReason::FileHashMismatch
+        );
+        let point = outcome.report().publication_points().next().unwrap();
+        assert_eq!(
+            point.failed_object().unwrap().to_string(),
+            "rsync://rpki.ripe.net/repository/\
+             2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+        );
+    }
+
+    #[test]
+    fn fallback() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let ca_uri = uri(
+            "rsync://rpki.ripe.net/repository/\
+             2a7dd1d787d793e4c8af56e197d4eed92af6ba13.cer"
+        );
+        let crl_uri = uri(
+            "rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl"
+        );
+
+        // Hash mismatch: the child certificate is ignored.
+        let mut source = ripe_source();
+        source.insert(
+            ca_uri.clone(),
+            Bytes::from_static(include_bytes!("../../test-data/ta.cer"))
+        );
+        let mut validator = Validator::new(source);
+        validator.set_now(at);
+        let mut policy = FallbackPolicy::default();
+        policy.set_hash_mismatch(Fallback::Warn);
+        validator.set_fallback_policy(policy);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+        assert!(outcome.failures().is_empty());
+        let point = outcome.report().publication_points().next().unwrap();
+        assert_eq!(point.fallback(), Some(Fallback::Warn));
+        assert_eq!(
+            point.fallback_reason(), Some(FailureReason::FileHashMismatch)
+        );
+        assert_eq!(
+            point.objects().nth(2).unwrap().status(),
+            ObjectStatus::HashMismatch
+        );
+
+        // Missing CRL: objects are used without revocation check.
+        let mut source = ripe_source();
+        source.remove(&crl_uri);
+        let source = Arc::new(source);
+        let mut validator = Validator::new(source.clone());
+        validator.set_now(at);
+        let mut policy = FallbackPolicy::default();
+        policy.set_crl_missing(Fallback::Warn);
+        validator.set_fallback_policy(policy);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+        let point = outcome.report().publication_points().next().unwrap();
+        assert_eq!(point.fallback(), Some(Fallback::Warn));
+        assert_eq!(point.fallback_reason(), Some(FailureReason::CrlMissing));
+        let object = point.objects().nth(2).unwrap();
+        assert_eq!(object.status(), ObjectStatus::Valid);
+        assert_eq!(object.issues()[0].code(), Code::NoRevocationCheck);
+        assert_eq!(object.issues()[0].severity(), Severity::Warning);
+
+        // Missing CRL: use the previous objects if available.
+        policy.set_crl_missing(Fallback::UsePrevious);
+        validator.set_fallback_policy(policy);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 0);
+        assert_eq!(
+            outcome.failures()[0].1.reason(), FailureReason::CrlMissing
+        );
+        validator.set_previous_source(Arc::new(ripe_source()));
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+        let point = outcome.report().publication_points().next().unwrap();
+        assert_eq!(point.fallback(), Some(Fallback::UsePrevious));
+        assert_eq!(point.fallback_reason(), Some(FailureReason::CrlMissing));
+        assert!(point.objects().all(|object| {
+            object.status() == ObjectStatus::Valid
+        }));
+
+        // Stale manifests are rejected by default.
+        let at = Time::utc(2019, 6, 1, 0, 0, 0);
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 0);
+    }
+
+    #[test]
+    fn validated_store() {
+        use crate::store::{MemoryStore, ValidatedCache};
+
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let cache = Arc::new(ValidatedCache::new(MemoryStore::new()));
+        let manifest = uri(
+            "rsync://rpki.ripe.net/repository/ripe-ncc-ta.mft"
+        );
+
+        // A successful run records the objects of the valid point.
+        let mut validator = Validator::new(ripe_source());
+        validator.set_now(at);
+        validator.set_validated_store(cache.clone());
+        validator.validate_ta(ripe_ta(at)).unwrap();
+        let point = cache.point(&manifest).unwrap();
+        assert_eq!(point.time(), at);
+        assert_eq!(point.objects().len(), 3);
+
+        // A run with a missing CRL falls back to the recorded objects.
+        let mut source = ripe_source();
+        source.remove(
+            &uri("rsync://rpki.ripe.net/repository/ripe-ncc-ta.crl")
+        );
+        let mut validator = Validator::new(source);
+        validator.set_now(at);
+        let mut policy = FallbackPolicy::default();
+        policy.set_crl_missing(Fallback::UsePrevious);
+        validator.set_fallback_policy(policy);
+        validator.set_validated_store(cache.clone());
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+        let report = outcome.report().publication_points().next().unwrap();
+        assert_eq!(report.fallback(), Some(Fallback::UsePrevious));
+
+        // The cache isn’t updated from the fallback.
+        assert_eq!(cache.point(&manifest).unwrap(), point);
+    }
+
+    #[test]
+    fn validate_ripe_historical() {
+        use crate::store::{MemoryStore, ValidatedCache};
+
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let cache = Arc::new(ValidatedCache::new(MemoryStore::new()));
+        let manifest = uri(
+            "rsync://rpki.ripe.net/repository/ripe-ncc-ta.mft"
+        );
+
+        let mut validator = Validator::new(ripe_source());
+        validator.set_options(ValidationOptions::at_time(at));
+        validator.set_validated_store(cache.clone());
+        assert_eq!(validator.now(), at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.valid_points(), 1);
+        assert_eq!(outcome.report().as_of(), Some(at));
+
+        // Historical runs leave the validated store alone.
+        assert!(cache.point(&manifest).is_none());
+
+        let mut validator = Validator::new(ripe_source());
+        validator.set_now(at);
+        let outcome = validator.validate_ta(ripe_ta(at)).unwrap();
+        assert_eq!(outcome.report().as_of(), None);
+    }
+
+    #[test]
+    fn fallback_strategy() {
+        let mut policy = FallbackPolicy::default();
+        policy.set_stale(Fallback::Warn);
+        policy.set_crl_missing(Fallback::UsePrevious);
+        assert_eq!(
+            policy.strategy(FailureReason::Stale), Some(Fallback::Warn)
+        );
+        assert_eq!(
+            policy.strategy(FailureReason::FileHashMismatch),
+            Some(Fallback::Reject)
+        );
+        assert_eq!(
+            policy.strategy(FailureReason::CrlHashMismatch),
+            Some(Fallback::UsePrevious)
+        );
+        assert_eq!(
+            policy.strategy(FailureReason::NumberRegression),
+            Some(Fallback::UsePrevious)
+        );
+        assert_eq!(policy.strategy(FailureReason::FileInvalid), None);
+    }
+
+    #[test]
+    fn retry_point() {
+        let manifest = uri("rsync://example.net/repo/ca.mft");
+        let first = vec![uri("rsync://example.net/ta/ca-1.cer")];
+        let second = vec![uri("rsync://example.net/ta/ca-2.cer")];
+        let mut outcome = Outcome::new();
+        outcome.add_point(
+            manifest.clone(),
+            Err(FailedFetch::new(FailureReason::ManifestInvalid)),
+            PointReport::new(manifest.clone(), first)
+        );
+        assert_eq!(outcome.valid_points(), 0);
+        assert_eq!(outcome.failures().len(), 1);
+
+        outcome.add_point(
+            manifest.clone(),
+            Ok(PubPoint::default()),
+            PointReport::new(manifest.clone(), second.clone())
+        );
+        assert_eq!(outcome.valid_points(), 1);
+        assert!(outcome.failures().is_empty());
+        let points: Vec<_> = outcome.report().publication_points().collect();
+        assert_eq!(points.len(), 1);
+        assert!(points[0].is_valid());
+        assert_eq!(points[0].chain(), second.as_slice());
+        assert_eq!(points[0].candidates(), 2);
+    }
+
+    #[test]
+    #[cfg(feature = "softkeys")]
+    fn check_overclaim() {
+        use crate::cert::{KeyUsage, TbsCert};
+        use crate::crypto::{PublicKeyFormat, Signer};
+        use crate::crypto::softsigner::OpenSslSigner;
+        use crate::resources::AsId;
+        use crate::x509::Validity;
+
+        let mut signer = OpenSslSigner::new();
+        let key = signer.create_key(PublicKeyFormat::default()).unwrap();
+        let pubkey = signer.get_key_info(&key).unwrap();
+        let cert = |overclaim| {
+            let mut cert = TbsCert::new(
+                12u64.into(), pubkey.to_subject_name(),
+                Validity::from_secs(86400), None, pubkey.clone(),
+                KeyUsage::Ca, overclaim
+            );
+            let uri = uri("rsync://example.com/m/p");
+            cert.set_basic_ca(Some(true));
+            cert.set_ca_repository(Some(uri.clone()));
+            cert.set_rpki_manifest(Some(uri));
+            cert.build_as_resource_blocks(|b| {
+                b.push((AsId::MIN, AsId::MAX))
+            });
+            let cert = cert.into_cert(&signer, &key).unwrap().to_captured();
+            Cert::decode(cert.as_slice()).unwrap()
+        };
+        let refuse = cert(Overclaim::Refuse);
+        let trim = cert(Overclaim::Trim);
+
+        let mut validator = Validator::new(ripe_source());
+        assert_eq!(validator.check_overclaim(&refuse), Ok(()));
+        assert_eq!(validator.check_overclaim(&trim), Ok(()));
+        validator.set_reconsidered(false);
+        assert_eq!(validator.check_overclaim(&refuse), Ok(()));
+        assert_eq!(
+            validator.check_overclaim(&trim), Err(Code::Reconsidered)
+        );
+    }
+
+    #[test]
+    fn local_cache_path() {
+        let cache = LocalCache::new("/cache");
+        assert_eq!(
+            cache.path(&uri("rsync://host/module/a/b.cer")),
+            Some(PathBuf::from("/cache/host/module/a/b.cer"))
+        );
+        assert_eq!(
+            cache.path(&uri("rsync://Host.Example/module/a/b.cer")),
+            Some(PathBuf::from("/cache/host.example/module/a/b.cer"))
+        );
+        assert_eq!(
+            cache.path(&uri::Rsync::new(
+                uri::RsyncModule::new("host", ".."),
+                Bytes::from_static(b"b.cer")
+            )),
+            None
+        );
+        assert_eq!(
+            cache.path(&uri::Rsync::new(
+                uri::RsyncModule::new("host", "module"),
+                Bytes::from_static(b"../b.cer")
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn file_names() {
+        assert!(is_file_name(b"Kn3R14fXk-TIr1bhl9Tu2Sr2uhM.crl"));
+        assert!(!is_file_name(b""));
+        assert!(!is_file_name(b"../foo.cer"));
+        assert!(!is_file_name(b"a/b.cer"));
+    }
+}
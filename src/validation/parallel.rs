@@ -0,0 +1,375 @@
+//! Parallel validation.
+//!
+//! This module adds parallel versions of the validation methods to
+//! [`Validator`]. The publication points of the CAs are processed by a
+//! pool of worker threads that all take their work from a shared queue.
+//! Whenever a worker becomes idle, it picks the oldest queued CA whose
+//! repository host has capacity left. This way, a single slow repository
+//! ties up at most a limited number of workers while the others carry on
+//! with the rest of the tree.
+//!
+//! [`Validator`]: ../struct.Validator.html
+
+use std::{io, mem, panic, thread};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::Instant;
+use crate::uri;
+use crate::cert::ResourceCert;
+use crate::crypto::KeyIdentifier;
+use crate::tal::Tal;
+use super::{Error, ObjectSource, Outcome, Task, Validator};
+
+
+//------------ Validator -----------------------------------------------------
+
+impl<S> Validator<S>
+where S: ObjectSource + Clone + Send + Sync + 'static {
+    /// Performs a parallel validation run for a TAL.
+    ///
+    /// This is the parallel version of [`validate_tal`]. The outcome is
+    /// the same as for the sequential run except that the publication
+    /// points in the report and the failures are ordered by manifest URI
+    /// rather than in processing order.
+    ///
+    /// [`validate_tal`]: #method.validate_tal
+    pub fn validate_tal_parallel(
+        &self, tal: &Tal
+    ) -> Result<Outcome, Error> {
+        let ta = self.find_ta(tal)?;
+        self.validate_ta_parallel(ta).map_err(Into::into)
+    }
+
+    /// Performs a parallel validation run from a trust anchor certificate.
+    ///
+    /// This is the parallel version of [`validate_ta`]. It uses the number
+    /// of worker threads set via [`set_threads`] and processes at most the
+    /// number of publication points set via [`set_host_limit`] for each
+    /// repository host at the same time.
+    ///
+    /// The object source is cloned for each worker thread, so it should be
+    /// cheap to clone, e.g., by keeping shared data behind an `Arc`.
+    ///
+    /// [`validate_ta`]: #method.validate_ta
+    /// [`set_threads`]: #method.set_threads
+    /// [`set_host_limit`]: #method.set_host_limit
+    pub fn validate_ta_parallel(
+        &self, ta: ResourceCert
+    ) -> Result<Outcome, io::Error> {
+        let start = Instant::now();
+        let shared = Arc::new(Shared::new(ta));
+        let workers: Vec<_> = (0..self.threads.max(1)).map(|_| {
+            let validator = self.clone();
+            let shared = shared.clone();
+            thread::spawn(move || validator.work(&shared))
+        }).collect();
+        for worker in workers {
+            if let Err(err) = worker.join() {
+                panic::resume_unwind(err)
+            }
+        }
+
+        let mut state = shared.lock();
+        if let Some(err) = state.error.take() {
+            return Err(err)
+        }
+        let mut outcome = mem::replace(&mut state.outcome, Outcome::new());
+        outcome.failures.sort_by(|left, right| {
+            left.0.to_string().cmp(&right.0.to_string())
+        });
+        outcome.report.sort();
+        outcome.report.set_as_of(self.options.time());
+        outcome.finish(start.elapsed());
+        Ok(outcome)
+    }
+
+    /// Runs a worker thread until the queue has been exhausted.
+    fn work(&self, shared: &Shared) {
+        let limit = self.host_limit.max(1);
+        let mut state = shared.lock();
+        loop {
+            if state.error.is_some() {
+                break
+            }
+            let (task, manifest_uri) = match state.next_task(limit) {
+                Some(task) => task,
+                None => {
+                    if state.busy == 0 && state.queue.is_empty() {
+                        break
+                    }
+                    state = shared.wait(state);
+                    continue
+                }
+            };
+            let host = manifest_uri.authority().to_string();
+            state.busy += 1;
+            *state.hosts.entry(host.clone()).or_insert(0) += 1;
+            drop(state);
+
+            let processing = Processing { shared, host: Some(host) };
+            let res = self.process_point(&task, &manifest_uri);
+            state = processing.finish();
+            match res {
+                Ok((res, report)) => {
+                    state.seen.insert(
+                        task.ca.subject_key_identifier(),
+                        if res.is_ok() { Seen::Valid } else { Seen::Failed }
+                    );
+                    let children = state.outcome.add_point(
+                        manifest_uri, res, report
+                    );
+                    state.queue.extend(children);
+                }
+                Err(err) => state.error = Some(err)
+            }
+            shared.cond.notify_all();
+        }
+        shared.cond.notify_all();
+    }
+}
+
+
+//------------ Shared --------------------------------------------------------
+
+/// The data shared between all worker threads.
+struct Shared {
+    /// The state of the validation run.
+    state: Mutex<State>,
+
+    /// Signals changes to the state to waiting workers.
+    cond: Condvar,
+}
+
+impl Shared {
+    /// Creates the shared data for a run starting at `ta`.
+    fn new(ta: ResourceCert) -> Self {
+        let mut queue = VecDeque::new();
+        queue.push_back(Task::new(ta));
+        Shared {
+            state: Mutex::new(State {
+                queue,
+                seen: HashMap::new(),
+                hosts: HashMap::new(),
+                busy: 0,
+                outcome: Outcome::new(),
+                error: None,
+            }),
+            cond: Condvar::new(),
+        }
+    }
+
+    /// Acquires the state.
+    ///
+    /// A worker only panics if processing panics which happens outside of
+    /// the lock. The [`Processing`] guard then restores the counters, so
+    /// the state stays consistent and we can ignore lock poisoning.
+    ///
+    /// [`Processing`]: struct.Processing.html
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Waits for a change of the state.
+    fn wait<'a>(&self, guard: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.cond.wait(guard).unwrap_or_else(|err| err.into_inner())
+    }
+}
+
+
+//------------ State ---------------------------------------------------------
+
+/// The state of a parallel validation run.
+struct State {
+    /// The CAs whose publication points still need processing.
+    queue: VecDeque<Task>,
+
+    /// The state of all CA keys picked up so far.
+    seen: HashMap<KeyIdentifier, Seen>,
+
+    /// The number of publication points currently processed per host.
+    hosts: HashMap<String, usize>,
+
+    /// The number of publication points currently processed.
+    busy: usize,
+
+    /// The outcome collected so far.
+    outcome: Outcome,
+
+    /// An error returned by the object source.
+    error: Option<io::Error>,
+}
+
+impl State {
+    /// Takes the next CA to process from the queue.
+    ///
+    /// Returns the oldest CA whose host has fewer than `limit` publication
+    /// points in processing together with its manifest URI. CAs without a
+    /// manifest URI or whose key has been processed successfully already
+    /// are dropped from the queue along the way. CAs whose key is
+    /// currently being processed stay in the queue until the outcome is
+    /// known.
+    fn next_task(
+        &mut self, limit: usize
+    ) -> Option<(Task, uri::Rsync)> {
+        let mut idx = 0;
+        while idx < self.queue.len() {
+            let manifest_uri = match self.queue[idx].ca.rpki_manifest() {
+                Some(uri) => uri.clone(),
+                None => {
+                    self.queue.remove(idx);
+                    continue
+                }
+            };
+            let key = self.queue[idx].ca.subject_key_identifier();
+            match self.seen.get(&key) {
+                Some(Seen::Valid) => {
+                    self.queue.remove(idx);
+                    continue
+                }
+                Some(Seen::Busy) => {
+                    idx += 1;
+                    continue
+                }
+                _ => { }
+            }
+            let busy = self.hosts.get(manifest_uri.authority()).cloned()
+                .unwrap_or(0);
+            if busy < limit {
+                self.seen.insert(key, Seen::Busy);
+                return self.queue.remove(idx).map(|task| {
+                    (task, manifest_uri)
+                })
+            }
+            idx += 1;
+        }
+        None
+    }
+
+    /// Releases a publication point on `host` from processing.
+    fn release(&mut self, host: &str) {
+        self.busy -= 1;
+        if let Some(count) = self.hosts.get_mut(host) {
+            *count -= 1;
+        }
+    }
+}
+
+
+//------------ Processing ----------------------------------------------------
+
+/// A guard for a publication point being processed by a worker.
+///
+/// The guard releases the point’s share of the busy counters. If it is
+/// dropped without calling [`finish`], processing has panicked. In this
+/// case, it also sets an error so that all other workers stop and the
+/// panic can be resumed when joining the worker.
+///
+/// [`finish`]: #method.finish
+struct Processing<'a> {
+    /// The data shared between the workers.
+    shared: &'a Shared,
+
+    /// The host of the publication point.
+    ///
+    /// This is `None` once the counters have been released.
+    host: Option<String>,
+}
+
+impl<'a> Processing<'a> {
+    /// Finishes processing and returns the locked state.
+    fn finish(mut self) -> MutexGuard<'a, State> {
+        let mut state = self.shared.lock();
+        if let Some(host) = self.host.take() {
+            state.release(&host);
+        }
+        state
+    }
+}
+
+impl<'a> Drop for Processing<'a> {
+    #[allow(clippy::io_other_error)] // io::Error::other needs Rust 1.74.
+    fn drop(&mut self) {
+        if let Some(host) = self.host.take() {
+            let mut state = self.shared.lock();
+            state.release(&host);
+            state.error = Some(io::Error::new(
+                io::ErrorKind::Other, "validation worker panicked"
+            ));
+            self.shared.cond.notify_all();
+        }
+    }
+}
+
+
+//------------ Seen ----------------------------------------------------------
+
+/// The processing state of a CA key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Seen {
+    /// A publication point for the key is currently being processed.
+    Busy,
+
+    /// A publication point for the key has been processed successfully.
+    Valid,
+
+    /// All publication points for the key processed so far have failed.
+    Failed,
+}
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use crate::x509::Time;
+    use super::super::test::{ripe_source, ripe_ta};
+    use super::*;
+
+    /// An object source that panics.
+    #[derive(Clone)]
+    struct PanicSource;
+
+    impl ObjectSource for PanicSource {
+        fn get(
+            &self, _uri: &uri::Rsync
+        ) -> Result<Option<Bytes>, io::Error> {
+            panic!("object source panicked")
+        }
+    }
+
+    #[test]
+    fn validate_ripe_parallel() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(Arc::new(ripe_source()));
+        validator.set_now(at);
+        let sequential = validator.validate_ta(ripe_ta(at)).unwrap();
+
+        for &(threads, limit) in &[(1, 1), (4, 1), (4, 4)] {
+            validator.set_threads(threads);
+            validator.set_host_limit(limit);
+            let parallel = validator.validate_ta_parallel(
+                ripe_ta(at)
+            ).unwrap();
+            assert_eq!(parallel.payload(), sequential.payload());
+            assert_eq!(parallel.valid_points(), sequential.valid_points());
+            assert_eq!(parallel.failures(), sequential.failures());
+            assert_eq!(
+                parallel.report().publication_points().count(),
+                sequential.report().publication_points().count()
+            );
+        }
+    }
+
+    #[test]
+    fn worker_panic() {
+        let at = Time::utc(2019, 4, 6, 12, 0, 0);
+        let mut validator = Validator::new(PanicSource);
+        validator.set_now(at);
+        validator.set_threads(4);
+        let res = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            validator.validate_ta_parallel(ripe_ta(at))
+        }));
+        assert!(res.is_err());
+    }
+}
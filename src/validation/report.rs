@@ -0,0 +1,566 @@
+//! A machine-readable report of a validation run.
+//!
+//! During a validation run, the [`Validator`] records what happened to each
+//! publication point and each object it encountered in a
+//! [`ValidationReport`]. All types in this module implement `Serialize`,
+//! so the report can be written out as JSON and compared between runs.
+//!
+//! Problems with individual objects are described by an [`Issue`] which
+//! combines a typed [`Code`] with its [`Severity`].
+//!
+//! [`Validator`]: ../struct.Validator.html
+//! [`ValidationReport`]: struct.ValidationReport.html
+//! [`Issue`]: struct.Issue.html
+//! [`Code`]: enum.Code.html
+//! [`Severity`]: enum.Severity.html
+
+use std::{fmt, slice};
+use std::time::Duration;
+use serde::{Serialize, Serializer};
+use crate::uri;
+use crate::manifest::check::{FailureReason, ObjectStatus};
+use crate::x509::{Time, ValidationError};
+use super::Fallback;
+
+
+//------------ ValidationReport ----------------------------------------------
+
+/// The report of a complete validation run.
+#[derive(Clone, Debug, Serialize)]
+pub struct ValidationReport {
+    /// The time the validation run started.
+    started: Time,
+
+    /// The time a historical validation run was performed for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    as_of: Option<Time>,
+
+    /// The wall-clock time the run took.
+    #[serde(serialize_with = "serialize_duration")]
+    duration: Duration,
+
+    /// The reports for all publication points in processing order.
+    publication_points: Vec<PointReport>,
+}
+
+impl ValidationReport {
+    /// Creates a new, empty report for a run starting now.
+    pub(super) fn new() -> Self {
+        ValidationReport {
+            started: Time::now(),
+            as_of: None,
+            duration: Duration::default(),
+            publication_points: Vec::new(),
+        }
+    }
+
+    /// Returns the time the validation run started.
+    pub fn started(&self) -> Time {
+        self.started
+    }
+
+    /// Returns the time a historical validation run was performed for.
+    ///
+    /// This is `None` if the run validated against the current time.
+    pub fn as_of(&self) -> Option<Time> {
+        self.as_of
+    }
+
+    /// Sets the time a historical validation run was performed for.
+    pub(super) fn set_as_of(&mut self, as_of: Option<Time>) {
+        self.as_of = as_of
+    }
+
+    /// Returns the wall-clock time the validation run took.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns an iterator over the publication point reports.
+    pub fn publication_points(&self) -> slice::Iter<'_, PointReport> {
+        self.publication_points.iter()
+    }
+
+    /// Returns an iterator over all issues of the given severity or worse.
+    ///
+    /// Each item is a pair of the URI of the object and the issue.
+    pub fn issues(
+        &self, severity: Severity
+    ) -> impl Iterator<Item = (&uri::Rsync, &Issue)> {
+        self.publication_points.iter().flat_map(|point| {
+            point.objects.iter()
+        }).flat_map(move |object| {
+            object.issues.iter().filter(move |issue| {
+                issue.severity >= severity
+            }).map(move |issue| (&object.uri, issue))
+        })
+    }
+
+    /// Returns the earliest expiry of all objects of the run.
+    ///
+    /// See [`ObjectReport::expires`] for what the expiry of an object is.
+    ///
+    /// [`ObjectReport::expires`]: struct.ObjectReport.html#method.expires
+    pub fn earliest_expiry(&self) -> Option<Time> {
+        self.publication_points.iter().filter_map(|point| {
+            point.earliest_expiry()
+        }).min()
+    }
+
+    /// Returns an iterator over all objects expiring no later than `until`.
+    ///
+    /// Each item is a pair of the URI of the object and its expiry. In
+    /// order to find all objects expiring within a certain window, pass
+    /// the validation time plus the window.
+    pub fn expiring(
+        &self, until: Time
+    ) -> impl Iterator<Item = (&uri::Rsync, Time)> {
+        self.publication_points.iter().flat_map(|point| {
+            point.objects.iter()
+        }).filter_map(move |object| {
+            match object.expires {
+                Some(expires) if expires <= until => {
+                    Some((&object.uri, expires))
+                }
+                _ => None
+            }
+        })
+    }
+
+    /// Sets the duration of the run.
+    pub(super) fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration
+    }
+
+    /// Adds the report for a publication point.
+    ///
+    /// Returns the index of the report.
+    pub(super) fn push(&mut self, point: PointReport) -> usize {
+        self.publication_points.push(point);
+        self.publication_points.len() - 1
+    }
+
+    /// Replaces the report at `index` with that of another attempt.
+    pub(super) fn retry(&mut self, index: usize, mut point: PointReport) {
+        point.candidates += self.publication_points[index].candidates;
+        self.publication_points[index] = point;
+    }
+
+    /// Sorts the publication point reports by manifest URI.
+    pub(super) fn sort(&mut self) {
+        self.publication_points.sort_by(|left, right| {
+            left.manifest.to_string().cmp(&right.manifest.to_string())
+        })
+    }
+}
+
+
+//------------ PointReport ---------------------------------------------------
+
+/// The report for a single publication point.
+#[derive(Clone, Debug, Serialize)]
+pub struct PointReport {
+    /// The URI of the publication point’s manifest.
+    manifest: uri::Rsync,
+
+    /// Why the publication point could not be used, if it couldn’t.
+    failure: Option<FailureReason>,
+
+    /// The URI of the object that caused the failure, if applicable.
+    failed_object: Option<uri::Rsync>,
+
+    /// The wall-clock time processing the publication point took.
+    #[serde(serialize_with = "serialize_duration")]
+    duration: Duration,
+
+    /// The fallback strategy applied to the publication point, if any.
+    fallback: Option<Fallback>,
+
+    /// The failure that caused the fallback strategy to be applied.
+    fallback_reason: Option<FailureReason>,
+
+    /// The URIs of the CA certificates used for the publication point.
+    chain: Vec<uri::Rsync>,
+
+    /// The number of certificates of the CA tried.
+    candidates: usize,
+
+    /// The reports for the objects of the publication point.
+    objects: Vec<ObjectReport>,
+}
+
+impl PointReport {
+    /// Creates a new report for the publication point with the manifest.
+    ///
+    /// The chain contains the URIs of the CA certificates from below the
+    /// trust anchor to the CA of the publication point.
+    pub(super) fn new(manifest: uri::Rsync, chain: Vec<uri::Rsync>) -> Self {
+        PointReport {
+            manifest,
+            failure: None,
+            failed_object: None,
+            duration: Duration::default(),
+            fallback: None,
+            fallback_reason: None,
+            chain,
+            candidates: 1,
+            objects: Vec::new(),
+        }
+    }
+
+    /// Returns the URI of the publication point’s manifest.
+    pub fn manifest(&self) -> &uri::Rsync {
+        &self.manifest
+    }
+
+    /// Returns whether the publication point was successfully processed.
+    pub fn is_valid(&self) -> bool {
+        self.failure.is_none()
+    }
+
+    /// Returns the reason why the publication point couldn’t be used.
+    pub fn failure(&self) -> Option<FailureReason> {
+        self.failure
+    }
+
+    /// Returns the URI of the object that caused the failure.
+    pub fn failed_object(&self) -> Option<&uri::Rsync> {
+        self.failed_object.as_ref()
+    }
+
+    /// Returns the wall-clock time processing the publication point took.
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    /// Returns the fallback strategy applied to the publication point.
+    ///
+    /// If this is `Some(_)`, the publication point was used even though
+    /// it failed for the reason given by [`fallback_reason`]. If the
+    /// strategy is [`Fallback::UsePrevious`], the objects reported are
+    /// those of the last successful fetch.
+    ///
+    /// [`fallback_reason`]: #method.fallback_reason
+    /// [`Fallback::UsePrevious`]: ../enum.Fallback.html#variant.UsePrevious
+    pub fn fallback(&self) -> Option<Fallback> {
+        self.fallback
+    }
+
+    /// Returns the reason why a fallback strategy was applied.
+    pub fn fallback_reason(&self) -> Option<FailureReason> {
+        self.fallback_reason
+    }
+
+    /// Returns the URIs of the CA certificates used for the point.
+    ///
+    /// The chain starts with the certificate issued by the trust anchor
+    /// and ends with the certificate of the publication point’s CA. It is
+    /// empty for the trust anchor’s own publication point.
+    ///
+    /// If more than one certificate for the CA was tried, this is the
+    /// chain of the last attempt, i.e., the one that succeeded if any did.
+    pub fn chain(&self) -> &[uri::Rsync] {
+        &self.chain
+    }
+
+    /// Returns the number of certificates for the CA that were tried.
+    pub fn candidates(&self) -> usize {
+        self.candidates
+    }
+
+    /// Returns an iterator over the reports of the objects.
+    pub fn objects(&self) -> slice::Iter<'_, ObjectReport> {
+        self.objects.iter()
+    }
+
+    /// Returns the earliest expiry of all objects of the point.
+    pub fn earliest_expiry(&self) -> Option<Time> {
+        self.objects.iter().filter_map(|object| object.expires).min()
+    }
+
+    /// Marks the publication point as failed.
+    pub(super) fn set_failure(
+        &mut self, reason: FailureReason, object: Option<uri::Rsync>
+    ) {
+        self.failure = Some(reason);
+        self.failed_object = object;
+    }
+
+    /// Records that a fallback strategy was applied.
+    ///
+    /// Only the first strategy applied is kept.
+    pub(super) fn set_fallback(
+        &mut self, fallback: Fallback, reason: FailureReason
+    ) {
+        if self.fallback.is_none() {
+            self.fallback = Some(fallback);
+            self.fallback_reason = Some(reason);
+        }
+    }
+
+    /// Sets the duration of processing.
+    pub(super) fn set_duration(&mut self, duration: Duration) {
+        self.duration = duration
+    }
+
+    /// Adds the report for an object.
+    pub(super) fn push(&mut self, object: ObjectReport) {
+        self.objects.push(object)
+    }
+
+    /// Adds an issue to the manifest’s report.
+    ///
+    /// The manifest is always the first object reported.
+    pub(super) fn manifest_issue(&mut self, issue: impl Into<Issue>) {
+        if let Some(manifest) = self.objects.first_mut() {
+            manifest.issues.push(issue.into())
+        }
+    }
+
+    /// Marks the manifest as invalid.
+    pub(super) fn reject_manifest(&mut self, code: Code) {
+        if let Some(manifest) = self.objects.first_mut() {
+            manifest.status = ObjectStatus::Invalid;
+            manifest.issues.push(code.into())
+        }
+    }
+}
+
+
+//------------ ObjectReport --------------------------------------------------
+
+/// The report for a single object.
+#[derive(Clone, Debug, Serialize)]
+pub struct ObjectReport {
+    /// The URI of the object.
+    uri: uri::Rsync,
+
+    /// The outcome of acquiring and validating the object.
+    status: ObjectStatus,
+
+    /// The issues found with the object.
+    issues: Vec<Issue>,
+
+    /// The earliest expiry along the object’s chain if it is valid.
+    expires: Option<Time>,
+}
+
+impl ObjectReport {
+    /// Creates a new object report.
+    pub(super) fn new(
+        uri: uri::Rsync, status: ObjectStatus, issues: Vec<Issue>
+    ) -> Self {
+        ObjectReport { uri, status, issues, expires: None }
+    }
+
+    /// Creates a new report for an object rejected because of `code`.
+    pub(super) fn rejected(
+        uri: uri::Rsync, status: ObjectStatus, code: Code
+    ) -> Self {
+        ObjectReport {
+            uri, status, issues: vec![code.into()], expires: None
+        }
+    }
+
+    /// Sets the earliest expiry along the object’s chain.
+    pub(super) fn with_expires(mut self, expires: Option<Time>) -> Self {
+        self.expires = expires;
+        self
+    }
+
+    /// Returns the URI of the object.
+    pub fn uri(&self) -> &uri::Rsync {
+        &self.uri
+    }
+
+    /// Returns the outcome of acquiring and validating the object.
+    pub fn status(&self) -> ObjectStatus {
+        self.status
+    }
+
+    /// Returns the issues found with the object.
+    pub fn issues(&self) -> &[Issue] {
+        &self.issues
+    }
+
+    /// Returns the earliest expiry along the object’s chain.
+    ///
+    /// This is the earliest time at which the object itself or any of the
+    /// certificates between it and the trust anchor expire. For manifests
+    /// and CRLs, their next update time is considered, too. Only valid
+    /// objects with a certificate or a next update time have an expiry.
+    pub fn expires(&self) -> Option<Time> {
+        self.expires
+    }
+}
+
+
+//------------ Issue ---------------------------------------------------------
+
+/// A problem found with an object.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+pub struct Issue {
+    /// How bad the problem is.
+    severity: Severity,
+
+    /// What the problem is.
+    code: Code,
+}
+
+impl Issue {
+    /// Creates a warning with the given code.
+    ///
+    /// This is used when local policy decides to use an object despite an
+    /// issue that normally causes it to be rejected.
+    pub(super) fn warning(code: Code) -> Self {
+        Issue { severity: Severity::Warning, code }
+    }
+
+    /// Returns the severity of the issue.
+    pub fn severity(self) -> Severity {
+        self.severity
+    }
+
+    /// Returns the code of the issue.
+    pub fn code(self) -> Code {
+        self.code
+    }
+}
+
+impl From<Code> for Issue {
+    fn from(code: Code) -> Self {
+        Issue { severity: code.severity(), code }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.code)
+    }
+}
+
+
+//------------ Severity ------------------------------------------------------
+
+/// The severity of an issue.
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// The object was used but something about it is noteworthy.
+    Warning,
+
+    /// The object was rejected.
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+
+//------------ Code ----------------------------------------------------------
+
+/// The kind of an issue.
+///
+/// When serialized, the codes are represented by their kebab-case names,
+/// e.g., `hash-mismatch`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Code {
+    /// The object is listed on the manifest but could not be found.
+    Missing,
+
+    /// The object’s hash does not match the manifest.
+    HashMismatch,
+
+    /// The manifest lists a file name that is not allowed.
+    BadFileName,
+
+    /// The object could not be decoded.
+    Malformed,
+
+    /// The object failed validation.
+    Invalid,
+
+    /// The object’s certificate has been revoked.
+    Revoked,
+
+    /// The object’s certificate refers to a different CRL.
+    CrlMismatch,
+
+    /// The CRL’s nextUpdate time has passed.
+    StaleCrl,
+
+    /// The manifest’s nextUpdate time has passed.
+    StaleManifest,
+
+    /// The object was used without a CRL to check for its revocation.
+    NoRevocationCheck,
+
+    /// The certificate uses the reconsidered algorithm which is disabled.
+    Reconsidered,
+
+    /// The manifest lists a CRL in addition to the publication point’s CRL.
+    ExtraCrl,
+
+    /// Resources of a certificate were trimmed to those of its issuer.
+    ResourcesTrimmed,
+
+    /// The object is of unknown type and was ignored.
+    UnknownType,
+}
+
+impl Code {
+    /// Returns the severity of an issue with this code.
+    pub fn severity(self) -> Severity {
+        match self {
+            Code::NoRevocationCheck | Code::ResourcesTrimmed
+                | Code::UnknownType => Severity::Warning,
+            _ => Severity::Error
+        }
+    }
+}
+
+impl From<ValidationError> for Code {
+    fn from(_: ValidationError) -> Self {
+        Code::Invalid
+    }
+}
+
+impl fmt::Display for Code {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Code::Missing => "object missing",
+            Code::HashMismatch => "hash mismatch",
+            Code::BadFileName => "bad file name",
+            Code::Malformed => "malformed object",
+            Code::Invalid => "invalid object",
+            Code::Revoked => "certificate revoked",
+            Code::CrlMismatch => "certificate refers to wrong CRL",
+            Code::StaleCrl => "CRL stale",
+            Code::StaleManifest => "manifest stale",
+            Code::NoRevocationCheck => "revocation not checked",
+            Code::Reconsidered => "reconsidered algorithm not accepted",
+            Code::ExtraCrl => "additional CRL",
+            Code::ResourcesTrimmed => "resources trimmed",
+            Code::UnknownType => "unknown object type",
+        })
+    }
+}
+
+
+//------------ Helper Functions ----------------------------------------------
+
+/// Serializes a duration as a floating point number of seconds.
+fn serialize_duration<S: Serializer>(
+    duration: &Duration, serializer: S
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
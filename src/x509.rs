@@ -3,21 +3,23 @@
 use std::{error, fmt, io, ops, str};
 use std::cmp::{min, max};
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::SystemTime;
 use bcder::{decode, encode};
 use bcder::{
     BitString, Captured, ConstOid, Mode, OctetString, Oid, Tag, Unsigned, xerr
 };
 use bcder::string::PrintableString;
-use bcder::decode::Source;
 use bcder::encode::PrimitiveContent;
 use chrono::{
     Datelike, DateTime, Duration, LocalResult, Timelike, TimeZone, Utc
 };
+use log::warn;
 use serde::de;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use crate::crypto::{
-    PublicKey, Signature, SignatureAlgorithm, Signer, VerificationError
+    KeyIdentifier, PublicKey, Signature, SignatureAlgorithm, Signer,
+    VerificationError
 };
 use crate::oid;
 
@@ -71,8 +73,83 @@ pub fn encode_extension<V: encode::Values>(
 }
 
 
+//------------ DecodeMode ----------------------------------------------------
+
+/// How to deal with violations of the RPKI profile when decoding.
+///
+/// Many objects deployed in the RPKI deviate from the profile in ways that
+/// are considered benign, such as names using the wrong string types or
+/// time values in non-standard formats. Relying parties typically need to
+/// accept these, while test suites for CAs want them rejected. The
+/// decoding mode decides which is done.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum DecodeMode {
+    /// Profile violations are rejected.
+    Strict,
+
+    /// Profile violations are accepted but logged as warnings.
+    Warn,
+
+    /// Profile violations are silently accepted.
+    Lenient,
+}
+
+impl DecodeMode {
+    /// Returns the mode corresponding to the `strict` flag.
+    ///
+    /// This is `Strict` if the flag is `true` and `Lenient` otherwise.
+    pub fn from_strict(strict: bool) -> Self {
+        if strict { DecodeMode::Strict }
+        else { DecodeMode::Lenient }
+    }
+
+    /// Returns whether the mode is strict.
+    pub fn is_strict(self) -> bool {
+        self == DecodeMode::Strict
+    }
+
+    /// Handles a profile violation described by `what`.
+    ///
+    /// Returns an error in strict mode, logs a warning in warn mode, and
+    /// does nothing in lenient mode.
+    pub fn violation(self, what: &str) -> Result<(), decode::Error> {
+        match self {
+            DecodeMode::Strict => xerr!(Err(decode::Malformed)),
+            DecodeMode::Warn => {
+                warn!("RPKI profile violation: {}", what);
+                Ok(())
+            }
+            DecodeMode::Lenient => Ok(())
+        }
+    }
+}
+
+#[allow(clippy::derivable_impls)] // #[default] requires Rust 1.62.
+impl Default for DecodeMode {
+    fn default() -> Self {
+        DecodeMode::Lenient
+    }
+}
+
+
 //------------ Name ----------------------------------------------------------
 
+/// An X.509 distinguished name.
+///
+/// Names are used as the issuer and subject of certificates and as the
+/// issuer of CRLs. Section 4.4 of RFC 6487 limits names in the RPKI to
+/// exactly one CommonName attribute encoded as a PrintableString and an
+/// optional serialNumber attribute. This restriction is checked by
+/// [`validate_rpki`]. Names for new objects can be derived from a key
+/// identifier via [`from_key_identifier`].
+///
+/// Two names are equal if they have the same attributes in the same
+/// order. Following section 7.1 of RFC 5280 in a simplified form, string
+/// values are compared ignoring ASCII case as well as leading, trailing,
+/// and repeated white space.
+///
+/// [`validate_rpki`]: #method.validate_rpki
+/// [`from_key_identifier`]: #method.from_key_identifier
 #[derive(Clone, Debug)]
 pub struct Name(Captured);
 
@@ -81,6 +158,29 @@ impl Name {
         Name(captured)
     }
 
+    /// Decodes a name from a source.
+    pub fn decode<S: decode::Source>(source: S) -> Result<Self, S::Err> {
+        Mode::Der.decode(source, Self::take_from)
+    }
+
+    /// Takes a name using the given decoding mode.
+    ///
+    /// A name that violates the restrictions of section 4.4 of RFC 6487
+    /// enforced by [`validate_rpki`] is a profile violation handled
+    /// according to `mode`.
+    ///
+    /// [`validate_rpki`]: #method.validate_rpki
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
+    ) -> Result<Self, S::Err> {
+        let res = Self::take_from(cons)?;
+        if mode != DecodeMode::Lenient && res.validate_rpki(true).is_err() {
+            mode.violation("name violates the RPKI profile")?;
+        }
+        Ok(res)
+    }
+
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
     ) -> Result<Self, S::Err> {
@@ -103,40 +203,45 @@ impl Name {
         }).map(Name)
     }
 
+    /// Checks that the name follows section 4.4 of RFC 6487.
+    ///
+    /// If `strict` is `true`, the name must contain exactly one CommonName
+    /// attribute and may contain one serialNumber attribute, both encoded
+    /// as PrintableString, and no other attributes. Otherwise, any name is
+    /// accepted.
     pub fn validate_rpki(&self, strict: bool) -> Result<(), ValidationError> {
         if strict {
+            let mut cn = false;
+            let mut sn = false;
             self.0.clone().decode(|cons| {
-                let mut cn = false;
-                let mut sn = false;
                 cons.take_sequence(|cons| {
                     while let Some(()) = cons.take_opt_set(|cons| {
                         while let Some(()) = cons.take_opt_sequence(|cons| {
                             let id = Oid::take_from(cons)?;
-                            if id == oid::AT_COMMON_NAME {
-                                if cn {
-                                    xerr!(
-                                        return Err(decode::Error::Malformed)
-                                    )
-                                }
-                                let _ = PrintableString::take_from(cons)?;
-                                cn = true;
+                            let seen = if id == oid::AT_COMMON_NAME {
+                                &mut cn
                             }
                             else if id == oid::AT_SERIAL_NUMBER {
-                                if sn {
-                                    xerr!(
-                                        return Err(decode::Error::Malformed)
-                                    )
-                                }
-                                let _ = PrintableString::take_from(cons)?;
-                                sn = true;
+                                &mut sn
+                            }
+                            else {
+                                xerr!(return Err(decode::Error::Malformed))
+                            };
+                            if *seen {
+                                xerr!(return Err(decode::Error::Malformed))
                             }
+                            let _ = PrintableString::take_from(cons)?;
+                            *seen = true;
                             Ok(())
                         })? { }
                         Ok(())
                     })? {}
                     Ok(())
                 })
-            })?
+            })?;
+            if !cn {
+                return Err(ValidationError)
+            }
         }
         Ok(())
     }
@@ -158,7 +263,15 @@ impl Name {
     /// the recommendations in RFC6487 sections 4.4, 4.5
     /// and 8.
     pub fn from_pub_key(key_info: &PublicKey) -> Self {
-        let enc = key_info.key_identifier().into_hex();
+        Self::from_key_identifier(&key_info.key_identifier())
+    }
+
+    /// Derives a name from a key identifier.
+    ///
+    /// The name consists of a single CommonName attribute with the
+    /// upper case hex representation of the key identifier.
+    pub fn from_key_identifier(key_id: &KeyIdentifier) -> Self {
+        let enc = key_id.into_hex();
         let values = encode::sequence(
             encode::set(
                 encode::sequence((
@@ -170,9 +283,158 @@ impl Name {
         Name(Captured::from_values(Mode::Der, values))
     }
 
+    /// Returns the value of the first CommonName attribute.
+    ///
+    /// Returns `None` if there is no such attribute or it isn’t a string.
+    pub fn common_name(&self) -> Option<String> {
+        self.string_attribute(&oid::AT_COMMON_NAME)
+    }
+
+    /// Returns the value of the first serialNumber attribute.
+    ///
+    /// Returns `None` if there is no such attribute or it isn’t a string.
+    pub fn serial_number(&self) -> Option<String> {
+        self.string_attribute(&oid::AT_SERIAL_NUMBER)
+    }
+
     pub fn encode_ref<'a>(&'a self) -> impl encode::Values + 'a {
         &self.0
     }
+
+    /// Returns the value of the first string attribute of the given type.
+    fn string_attribute(&self, id: &Oid<&[u8]>) -> Option<String> {
+        self.attributes(false).ok()?.into_iter().flatten().find_map(
+            |attr| {
+                match attr.value {
+                    AttributeValue::String(value)
+                        if attr.id.as_ref() == id.as_ref() => Some(value),
+                    _ => None
+                }
+            }
+        )
+    }
+
+    /// Returns the attributes of each relative distinguished name.
+    ///
+    /// If `normalize` is `true`, string values are normalized for
+    /// comparison.
+    fn attributes(
+        &self, normalize: bool
+    ) -> Result<Vec<Vec<Attribute>>, decode::Error> {
+        self.0.clone().decode(|cons| {
+            cons.take_sequence(|cons| {
+                let mut res = Vec::new();
+                while let Some(rdn) = cons.take_opt_set(|cons| {
+                    let mut rdn = Vec::new();
+                    while let Some(attr) = cons.take_opt_sequence(|cons| {
+                        Ok(Attribute {
+                            id: Oid::take_from(cons)?,
+                            value: AttributeValue::take_from(
+                                cons, normalize
+                            )?,
+                        })
+                    })? {
+                        rdn.push(attr)
+                    }
+                    Ok(rdn)
+                })? {
+                    res.push(rdn)
+                }
+                Ok(res)
+            })
+        })
+    }
+}
+
+
+//--- PartialEq and Eq
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Self) -> bool {
+        if self.0.as_slice() == other.0.as_slice() {
+            return true
+        }
+        match (self.attributes(true), other.attributes(true)) {
+            (Ok(left), Ok(right)) => left == right,
+            _ => false
+        }
+    }
+}
+
+impl Eq for Name { }
+
+
+//------------ Attribute -----------------------------------------------------
+
+/// A single attribute of a name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+struct Attribute {
+    /// The attribute type.
+    id: Oid,
+
+    /// The attribute value.
+    value: AttributeValue,
+}
+
+
+//------------ AttributeValue ------------------------------------------------
+
+/// The value of an attribute of a name.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum AttributeValue {
+    /// A PrintableString, UTF8String, or IA5String.
+    String(String),
+
+    /// Any other value as its complete encoding.
+    Other(Vec<u8>),
+}
+
+impl AttributeValue {
+    /// Takes a value, normalizing strings if `normalize` is `true`.
+    fn take_from<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        normalize: bool,
+    ) -> Result<Self, S::Err> {
+        let value = cons.capture_one()?;
+        let is_string = match value.as_slice().first() {
+            Some(&tag) => {
+                tag == 0x13 // PrintableString
+                || tag == 0x0c // UTF8String
+                || tag == 0x16 // IA5String
+            }
+            None => false
+        };
+        if is_string {
+            let content = value.clone().decode(|cons| {
+                cons.take_value(|_, content| {
+                    content.as_primitive()?.take_all()
+                })
+            });
+            if let Ok(content) = content {
+                if let Ok(content) = String::from_utf8(content.to_vec()) {
+                    return Ok(AttributeValue::String(
+                        if normalize {
+                            normalize_string(&content)
+                        }
+                        else {
+                            content
+                        }
+                    ))
+                }
+            }
+        }
+        Ok(AttributeValue::Other(value.as_slice().to_vec()))
+    }
+}
+
+/// Normalizes a string value for comparison.
+///
+/// Removes leading and trailing white space, replaces each sequence of
+/// white space by a single space, and converts ASCII letters to lower
+/// case.
+fn normalize_string(s: &str) -> String {
+    s.split_whitespace().map(str::to_ascii_lowercase)
+        .collect::<Vec<_>>().join(" ")
 }
 
 
@@ -463,6 +725,44 @@ impl SignedData {
         })
     }
 
+    /// Takes the content of signed data, decoding the data via `op`.
+    ///
+    /// The data is captured while `op` decodes it rather than being
+    /// decoded from the captured value later. This way, any error in the
+    /// data happens while reading `cons`.
+    pub fn from_constructed_with<S, F, T>(
+        cons: &mut decode::Constructed<S>,
+        op: F
+    ) -> Result<(Self, T), S::Err>
+    where
+        S: decode::Source,
+        F: FnOnce(
+            &mut decode::Constructed<
+                decode::CaptureSource<decode::LimitedSource<S>>
+            >
+        ) -> Result<T, S::Err>
+    {
+        let mut res = None;
+        let data = cons.capture(|cons| {
+            res = Some(op(cons)?);
+            Ok(())
+        })?;
+        let res = match res {
+            Some(res) => res,
+            None => xerr!(return Err(decode::Malformed.into()))
+        };
+        Ok((
+            SignedData {
+                data,
+                signature: Signature::new(
+                    SignatureAlgorithm::x509_take_from(cons)?,
+                    BitString::take_from(cons)?.octet_bytes()
+                )
+            },
+            res
+        ))
+    }
+
     pub fn data(&self) -> &Captured {
         &self.data
     }
@@ -584,87 +884,154 @@ impl Time {
         self.0.timestamp()
     }
 
+    /// Takes a time value from the beginning of a constructed value.
+    ///
+    /// The value can be either a UTCTime or a GeneralizedTime. Both must
+    /// be in the restricted format required by section 4.1.2.5 of
+    /// RFC 5280.
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::Strict)
+    }
+
+    /// Takes a time value, tolerating common encoding errors.
+    ///
+    /// See [`from_content`] for which errors are tolerated.
+    ///
+    /// [`from_content`]: #method.from_content
+    pub fn take_from_lenient<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::Lenient)
+    }
+
+    /// Takes a time value using the given decoding mode.
+    ///
+    /// Values not in the format required by RFC 5280 are a profile
+    /// violation handled according to `mode`.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Self, S::Err> {
         cons.take_primitive(|tag, prim| {
-            match tag {
-                Tag::UTC_TIME => {
-                    // RFC 5280 requires the format YYMMDDHHMMSSZ
-                    let year = read_two_char(prim)? as i32;
-                    let year = if year >= 50 { year + 1900 }
-                               else { year + 2000 };
-                    let res = (
-                        year,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                    );
-                    if prim.take_u8()? != b'Z' {
-                        return Err(decode::Malformed.into())
-                    }
-                    Self::from_parts(res).map_err(Into::into)
-                }
-                Tag::GENERALIZED_TIME => {
-                    // RFC 5280 requires the format YYYYMMDDHHMMSSZ
-                    let res = (
-                        read_four_char(prim)? as i32,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                        read_two_char(prim)?,
-                    );
-                    if prim.take_u8()? != b'Z' {
-                        return Err(decode::Malformed.into())
-                    }
-                    Self::from_parts(res).map_err(Into::into)
-                }
-                _ => {
-                    xerr!(Err(decode::Malformed.into()))
-                }
-            }
+            let content = prim.take_all()?;
+            Self::from_content_with_mode(
+                tag, content.as_ref(), mode
+            ).map_err(Into::into)
         })
     }
 
+    /// Takes an optional time value from the beginning of a value.
     pub fn take_opt_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Option<Self>, S::Err> {
+        Self::take_opt_from_with_mode(cons, DecodeMode::Strict)
+    }
+
+    /// Takes an optional time value using the given decoding mode.
+    pub fn take_opt_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Option<Self>, S::Err> {
         let res = cons.take_opt_primitive_if(Tag::UTC_TIME, |prim| {
-            let year = read_two_char(prim)? as i32;
-            let year = if year >= 50 { year + 1900 }
-                       else { year + 2000 };
-            let res = (
-                year,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-            );
-            if prim.take_u8()? != b'Z' {
-                return Err(decode::Malformed.into())
-            }
-            Self::from_parts(res).map_err(Into::into)
+            let content = prim.take_all()?;
+            Self::from_content_with_mode(
+                Tag::UTC_TIME, content.as_ref(), mode
+            ).map_err(Into::into)
         })?;
         if let Some(res) = res {
             return Ok(Some(res))
         }
         cons.take_opt_primitive_if(Tag::GENERALIZED_TIME, |prim| {
-            let res = (
-                read_four_char(prim)? as i32,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-                read_two_char(prim)?,
-            );
-            if prim.take_u8()? != b'Z' {
-                return Err(decode::Malformed.into())
+            let content = prim.take_all()?;
+            Self::from_content_with_mode(
+                Tag::GENERALIZED_TIME, content.as_ref(), mode
+            ).map_err(Into::into)
+        })
+    }
+
+    /// Decodes the content of a time value using the given decoding mode.
+    fn from_content_with_mode(
+        tag: Tag, content: &[u8], mode: DecodeMode
+    ) -> Result<Self, decode::Error> {
+        match Self::from_content(tag, content, false) {
+            Ok(res) => Ok(res),
+            Err(err) if mode.is_strict() => Err(err),
+            Err(_) => {
+                let res = Self::from_content(tag, content, true)?;
+                mode.violation("time value not in RFC 5280 format")?;
+                Ok(res)
+            }
+        }
+    }
+
+    /// Decodes the content of a UTCTime or GeneralizedTime value.
+    ///
+    /// RFC 5280 requires the formats YYMMDDHHMMSSZ and YYYYMMDDHHMMSSZ,
+    /// respectively. If `lenient` is `false`, only these are accepted.
+    /// Otherwise, the following deviations seen in deployed objects are
+    /// tolerated:
+    ///
+    /// * missing seconds,
+    /// * fractional seconds in a GeneralizedTime,
+    /// * a leap second, which is replaced by second 59,
+    /// * a time zone offset of the form +HHMM or -HHMM instead of Z, and
+    /// * a missing time zone which is taken to be UTC.
+    pub fn from_content(
+        tag: Tag, content: &[u8], lenient: bool
+    ) -> Result<Self, decode::Error> {
+        let mut parser = TimeParser(content);
+        let year = if tag == Tag::UTC_TIME {
+            let year = parser.digits(2)? as i32;
+            if year >= 50 { year + 1900 } else { year + 2000 }
+        }
+        else if tag == Tag::GENERALIZED_TIME {
+            parser.digits(4)? as i32
+        }
+        else {
+            xerr!(return Err(decode::Malformed))
+        };
+        let month = parser.digits(2)?;
+        let day = parser.digits(2)?;
+        let hour = parser.digits(2)?;
+        let minute = parser.digits(2)?;
+        let mut second = if lenient && !parser.peek_digit() {
+            0
+        }
+        else {
+            parser.digits(2)?
+        };
+        if lenient {
+            if second == 60 {
+                second = 59
             }
-            Self::from_parts(res).map_err(Into::into)
+            if tag == Tag::GENERALIZED_TIME && parser.fraction() {
+                parser.digits(1)?;
+                while parser.peek_digit() {
+                    parser.digits(1)?;
+                }
+            }
+        }
+        let offset = match parser.take() {
+            Some(b'Z') => 0,
+            Some(ch) if lenient && (ch == b'+' || ch == b'-') => {
+                let hours = parser.digits(2)?;
+                let minutes = parser.digits(2)?;
+                if hours > 23 || minutes > 59 {
+                    xerr!(return Err(decode::Malformed))
+                }
+                let offset = i64::from(hours * 60 + minutes);
+                if ch == b'+' { offset } else { -offset }
+            }
+            None if lenient => 0,
+            _ => xerr!(return Err(decode::Malformed))
+        };
+        if !parser.0.is_empty() {
+            xerr!(return Err(decode::Malformed))
+        }
+        Self::from_parts((year, month, day, hour, minute, second)).map(|time| {
+            time - Duration::minutes(offset)
         })
     }
 
@@ -799,37 +1166,97 @@ impl ops::Sub<Duration> for Time {
 }
 
 
-fn read_two_char<S: decode::Source>(source: &mut S) -> Result<u32, S::Err> {
-    let mut s = [0u8; 2];
-    s[0] = source.take_u8()?;
-    s[1] = source.take_u8()?;
-    let s = match str::from_utf8(&s[..]) {
-        Ok(s) => s,
-        Err(_err) => {
-            xerr!(return Err(decode::Malformed.into()))
-        }
-    };
-    u32::from_str(s).map_err(|_err| {
-        xerr!(decode::Malformed.into())
-    })
+//------------ Clock ---------------------------------------------------------
+
+/// A source for the current time.
+///
+/// Components that perform time-dependent checks over a longer period,
+/// such as the validator, take the time from a clock. By default, this is
+/// [`SystemClock`]. Since a [`Time`] is a clock that always returns that
+/// time, checks can be performed as of an arbitrary time, e.g., for
+/// testing expiry or for forensic analysis.
+///
+/// [`SystemClock`]: struct.SystemClock.html
+/// [`Time`]: struct.Time.html
+pub trait Clock: fmt::Debug + Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Time;
 }
 
+impl Clock for Time {
+    fn now(&self) -> Time {
+        *self
+    }
+}
 
-fn read_four_char<S: decode::Source>(source: &mut S) -> Result<u32, S::Err> {
-    let mut s = [0u8; 4];
-    s[0] = source.take_u8()?;
-    s[1] = source.take_u8()?;
-    s[2] = source.take_u8()?;
-    s[3] = source.take_u8()?;
-    let s = match str::from_utf8(&s[..]) {
-        Ok(s) => s,
-        Err(_err) => {
-            xerr!(return Err(decode::Malformed.into()))
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Time {
+        (*self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Time {
+        self.as_ref().now()
+    }
+}
+
+
+//------------ SystemClock ---------------------------------------------------
+
+/// A clock returning the system time.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Time {
+        Time::now()
+    }
+}
+
+
+//------------ TimeParser ----------------------------------------------------
+
+/// A helper for parsing the content of time values.
+struct TimeParser<'a>(&'a [u8]);
+
+impl<'a> TimeParser<'a> {
+    /// Takes the next octet.
+    fn take(&mut self) -> Option<u8> {
+        let (first, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*first)
+    }
+
+    /// Returns whether the next octet is a digit.
+    fn peek_digit(&self) -> bool {
+        self.0.first().map(u8::is_ascii_digit).unwrap_or(false)
+    }
+
+    /// Takes a decimal fraction separator if present.
+    fn fraction(&mut self) -> bool {
+        match self.0.first() {
+            Some(b'.') | Some(b',') => {
+                self.0 = &self.0[1..];
+                true
+            }
+            _ => false
         }
-    };
-    u32::from_str(s).map_err(|_err| {
-        xerr!(decode::Malformed.into())
-    })
+    }
+
+    /// Takes a number of exactly `len` decimal digits.
+    fn digits(&mut self, len: usize) -> Result<u32, decode::Error> {
+        let mut res = 0;
+        for _ in 0..len {
+            match self.take() {
+                Some(ch) if ch.is_ascii_digit() => {
+                    res = res * 10 + u32::from(ch - b'0')
+                }
+                _ => xerr!(return Err(decode::Malformed))
+            }
+        }
+        Ok(res)
+    }
 }
 
 
@@ -893,13 +1320,23 @@ impl Validity {
     }
 
     pub fn from_duration(duration: Duration) -> Self {
-        let not_before = Time::now();
-        let not_after = Time::new(Utc::now() + duration);
+        Self::from_clock(&SystemClock, duration)
+    }
+
+    /// Creates a validity starting at the current time of `clock`.
+    ///
+    /// The validity ends after `duration`. If the duration is negative,
+    /// it ends at the current time and starts that long before.
+    pub fn from_clock<C: Clock + ?Sized>(
+        clock: &C, duration: Duration
+    ) -> Self {
+        let not_before = clock.now();
+        let not_after = not_before + duration;
         if not_before < not_after {
             Validity { not_before, not_after }
         }
         else {
-            Validity { not_after, not_before }
+            Validity { not_before: not_after, not_after: not_before }
         }
     }
 
@@ -922,13 +1359,60 @@ impl Validity {
         )
     }
 
+    /// Returns whether `time` is within the validity period.
+    pub fn contains(self, time: Time) -> bool {
+        self.not_before <= time && time <= self.not_after
+    }
+
+    /// Returns the period during which both validities are valid.
+    ///
+    /// Unlike [`trim`], this returns `None` if the two periods don’t
+    /// overlap at all.
+    ///
+    /// [`trim`]: #method.trim
+    pub fn overlap(self, other: Self) -> Option<Self> {
+        let res = self.trim(other);
+        if res.not_before <= res.not_after {
+            Some(res)
+        }
+        else {
+            None
+        }
+    }
+
+    /// Returns how much of the validity period is left at `now`.
+    ///
+    /// Returns a zero duration if the period has already ended.
+    pub fn remaining(self, now: Time) -> Duration {
+        max(self.not_after.0.signed_duration_since(now.0), Duration::zero())
+    }
+
     pub fn take_from<S: decode::Source>(
         cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::Strict)
+    }
+
+    /// Takes a validity, tolerating common time encoding errors.
+    ///
+    /// See [`Time::from_content`] for which errors are tolerated.
+    ///
+    /// [`Time::from_content`]: struct.Time.html#method.from_content
+    pub fn take_from_lenient<S: decode::Source>(
+        cons: &mut decode::Constructed<S>
+    ) -> Result<Self, S::Err> {
+        Self::take_from_with_mode(cons, DecodeMode::Lenient)
+    }
+
+    /// Takes a validity using the given decoding mode for the time values.
+    pub fn take_from_with_mode<S: decode::Source>(
+        cons: &mut decode::Constructed<S>,
+        mode: DecodeMode,
     ) -> Result<Self, S::Err> {
         cons.take_sequence(|cons| {
             Ok(Validity::new(
-                Time::take_from(cons)?,
-                Time::take_from(cons)?,
+                Time::take_from_with_mode(cons, mode)?,
+                Time::take_from_with_mode(cons, mode)?,
             ))
         })
     }
@@ -1035,6 +1519,127 @@ mod test {
         assert_eq!(data.as_ref(), AsRef::<[u8]>::as_ref(&encoded));
     }
 
+    #[test]
+    fn clock() {
+        let now = Time::utc(2020, 1, 1, 0, 0, 0);
+        let clock: Arc<dyn Clock> = Arc::new(now);
+        assert_eq!(clock.now(), now);
+
+        let validity = Validity::from_clock(&clock, Duration::days(1));
+        assert_eq!(validity.not_before(), now);
+        assert_eq!(validity.not_after(), Time::utc(2020, 1, 2, 0, 0, 0));
+        let validity = Validity::from_clock(&now, Duration::days(-1));
+        assert_eq!(validity.not_before(), Time::utc(2019, 12, 31, 0, 0, 0));
+        assert_eq!(validity.not_after(), now);
+
+        assert!(SystemClock.now() > now);
+    }
+
+    #[test]
+    fn validity_arithmetic() {
+        let first = Validity::new(
+            Time::utc(2020, 1, 1, 0, 0, 0), Time::utc(2020, 6, 1, 0, 0, 0)
+        );
+        let second = Validity::new(
+            Time::utc(2020, 3, 1, 0, 0, 0), Time::utc(2021, 1, 1, 0, 0, 0)
+        );
+        let third = Validity::new(
+            Time::utc(2020, 7, 1, 0, 0, 0), Time::utc(2021, 1, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            first.overlap(second),
+            Some(Validity::new(
+                Time::utc(2020, 3, 1, 0, 0, 0),
+                Time::utc(2020, 6, 1, 0, 0, 0)
+            ))
+        );
+        assert_eq!(first.overlap(second), second.overlap(first));
+        assert_eq!(first.overlap(third), None);
+
+        assert_eq!(
+            first.remaining(Time::utc(2020, 5, 31, 0, 0, 0)),
+            Duration::days(1)
+        );
+        assert_eq!(
+            first.remaining(Time::utc(2020, 6, 2, 0, 0, 0)),
+            Duration::zero()
+        );
+    }
+
+    type Attr = (Oid<&'static [u8]>, Tag, &'static str);
+
+    fn name(attrs: &[Attr]) -> Name {
+        let mut res = Vec::new();
+        encode::sequence(
+            attrs.iter().map(|(id, tag, value)| {
+                encode::set(
+                    encode::sequence((
+                        id.encode(), value.as_bytes().encode_as(*tag)
+                    ))
+                )
+            }).collect::<Vec<_>>()
+        ).write_encoded(Mode::Der, &mut res).unwrap();
+        Name::decode(res.as_slice()).unwrap()
+    }
+
+    fn cn(value: &'static str) -> Attr {
+        (oid::AT_COMMON_NAME, Tag::PRINTABLE_STRING, value)
+    }
+
+    fn sn() -> Attr {
+        (oid::AT_SERIAL_NUMBER, Tag::PRINTABLE_STRING, "12")
+    }
+
+    #[test]
+    fn name_validate_rpki() {
+        let cert = include_bytes!("../test-data/ta.cer");
+        let cert = crate::cert::Cert::decode(cert.as_ref()).unwrap();
+        assert!(cert.subject().validate_rpki(true).is_ok());
+        assert!(cert.subject().common_name().is_some());
+
+        assert!(name(&[cn("foo"), sn()]).validate_rpki(true).is_ok());
+        assert!(name(&[sn()]).validate_rpki(true).is_err());
+        assert!(name(&[sn()]).validate_rpki(false).is_ok());
+        assert!(name(&[cn("foo"), cn("bar")]).validate_rpki(true).is_err());
+        assert!(
+            name(&[
+                cn("foo"), (Oid(&[85, 4, 6]), Tag::PRINTABLE_STRING, "NL")
+            ]).validate_rpki(true).is_err()
+        );
+        assert!(
+            name(&[
+                (oid::AT_COMMON_NAME, Tag::UTF8_STRING, "foo")
+            ]).validate_rpki(true).is_err()
+        );
+    }
+
+    #[test]
+    fn name_from_key_identifier() {
+        let key_id = KeyIdentifier::from_str(
+            "FBFF000102030405060708090A0B0C0D0E0F1011"
+        ).unwrap();
+        let name = Name::from_key_identifier(&key_id);
+        assert!(name.validate_rpki(true).is_ok());
+        assert_eq!(name.common_name(), Some(key_id.to_string()));
+        assert_eq!(name.serial_number(), None);
+        assert_eq!(name, Name::from_key_identifier(&key_id));
+    }
+
+    #[test]
+    fn name_eq() {
+        assert_eq!(name(&[cn("Foo Bar")]), name(&[cn("  foo   BAR ")]));
+        assert_eq!(
+            name(&[cn("foo")]),
+            name(&[(oid::AT_COMMON_NAME, Tag::UTF8_STRING, "FOO")])
+        );
+        assert_ne!(name(&[cn("foo")]), name(&[cn("foobar")]));
+        assert_ne!(name(&[cn("foo"), sn()]), name(&[sn(), cn("foo")]));
+        assert_ne!(name(&[cn("foo")]), name(&[cn("foo"), sn()]));
+        assert_eq!(
+            name(&[cn("foo"), sn()]).serial_number().unwrap(), "12"
+        );
+    }
+
     #[test]
     fn serial_from_slice() {
         assert_eq!(
@@ -1188,5 +1793,90 @@ mod test {
             57
         );
     }
-}
 
+    #[test]
+    fn time_from_content() {
+        let utc = |s: &str, lenient| {
+            Time::from_content(Tag::UTC_TIME, s.as_bytes(), lenient)
+        };
+        let gen = |s: &str, lenient| {
+            Time::from_content(Tag::GENERALIZED_TIME, s.as_bytes(), lenient)
+        };
+        let at = Time::utc(2019, 4, 6, 12, 30, 15);
+
+        assert_eq!(utc("190406123015Z", false).unwrap(), at);
+        assert_eq!(gen("20190406123015Z", false).unwrap(), at);
+        assert_eq!(
+            utc("500101000000Z", false).unwrap(),
+            Time::utc(1950, 1, 1, 0, 0, 0)
+        );
+        assert!(utc("1904061230Z", false).is_err());
+        assert!(utc("190406123015", false).is_err());
+        assert!(utc("190406123015+0100", false).is_err());
+        assert!(gen("20190406123015.5Z", false).is_err());
+        assert!(utc("190406123060Z", false).is_err());
+        assert!(utc("190406123015Z0", false).is_err());
+
+        assert_eq!(
+            utc("1904061230Z", true).unwrap(),
+            Time::utc(2019, 4, 6, 12, 30, 0)
+        );
+        assert_eq!(utc("190406123015", true).unwrap(), at);
+        assert_eq!(utc("190406143015+0200", true).unwrap(), at);
+        assert_eq!(utc("190406103015-0200", true).unwrap(), at);
+        assert_eq!(gen("20190406123015.123Z", true).unwrap(), at);
+        assert_eq!(gen("20190406123015,5Z", true).unwrap(), at);
+        assert_eq!(
+            utc("190406123060Z", true).unwrap(),
+            Time::utc(2019, 4, 6, 12, 30, 59)
+        );
+        assert!(utc("19040612301aZ", true).is_err());
+        assert!(utc("190406123015.5Z", true).is_err());
+        assert!(gen("20190406123015.Z", true).is_err());
+        assert!(utc("190406123015+2400", true).is_err());
+        assert!(utc("190406123015Z0", true).is_err());
+    }
+
+    #[test]
+    fn time_take_from_with_mode() {
+        let take = |data: &'static [u8], mode| {
+            Constructed::decode(data, Mode::Der, |cons| {
+                Time::take_from_with_mode(cons, mode)
+            })
+        };
+        let at = Time::utc(2019, 4, 6, 12, 30, 0);
+        let good = b"\x17\x0d190406123000Z";
+        let bad = b"\x17\x0b1904061230Z";
+        for &mode in &[
+            DecodeMode::Strict, DecodeMode::Warn, DecodeMode::Lenient
+        ] {
+            assert_eq!(take(good, mode).unwrap(), at);
+        }
+        assert!(take(bad, DecodeMode::Strict).is_err());
+        assert_eq!(take(bad, DecodeMode::Warn).unwrap(), at);
+        assert_eq!(take(bad, DecodeMode::Lenient).unwrap(), at);
+        assert!(take(b"\x17\x0b1904061230X", DecodeMode::Lenient).is_err());
+    }
+
+    #[test]
+    fn validity_take_from_lenient() {
+        let data = b"\x30\x1d\
+            \x17\x0b1904061230Z\
+            \x18\x0e20200406123015";
+        assert!(
+            Constructed::decode(
+                data.as_ref(), Mode::Der, Validity::take_from
+            ).is_err()
+        );
+        let validity = Constructed::decode(
+            data.as_ref(), Mode::Der, Validity::take_from_lenient
+        ).unwrap();
+        assert_eq!(validity.not_before(), Time::utc(2019, 4, 6, 12, 30, 0));
+        assert_eq!(validity.not_after(), Time::utc(2020, 4, 6, 12, 30, 15));
+        assert!(validity.contains(Time::utc(2019, 10, 1, 0, 0, 0)));
+        assert!(!validity.contains(Time::utc(2019, 4, 6, 12, 29, 59)));
+        assert!(
+            validity.validate_at(Time::utc(2020, 4, 7, 0, 0, 0)).is_err()
+        );
+    }
+}
@@ -4,38 +4,64 @@ use std::borrow::Cow;
 use bytes::Bytes;
 use quick_xml::events::{BytesStart, Event};
 
+
+//------------ Reader --------------------------------------------------------
+
 /// An XML reader.
 ///
 /// This struct holds all state necessary for parsing an XML documnet.
+///
+/// Since the documents parsed are typically received from untrusted
+/// sources, the reader enforces the [`Limits`] given upon creation and
+/// rejects documents with a document type declaration. Without the latter,
+/// no entities other than the predefined ones can be declared, so there is
+/// no entity expansion to worry about.
+///
+/// [`Limits`]: struct.Limits.html
 pub struct Reader<R: io::BufRead> {
-    reader: quick_xml::Reader<R>,
+    reader: quick_xml::Reader<io::Take<R>>,
     buf: Vec<u8>,
     ns_buf: Vec<u8>,
+    limits: Limits,
+    depth: usize,
 }
 
 impl<R: io::BufRead> Reader<R> {
     /// Creates a new reader from an underlying reader.
+    ///
+    /// The reader will use the default limits.
     pub fn new(reader: R) -> Self {
+        Self::with_limits(reader, Limits::default())
+    }
+
+    /// Creates a new reader enforcing the given limits.
+    pub fn with_limits(reader: R, limits: Limits) -> Self {
+        // We allow one extra octet so we can tell a document of exactly
+        // the maximum size from one that is too large.
+        let reader = reader.take(
+            limits.max_size.map(|size| {
+                size.saturating_add(1)
+            }).unwrap_or(u64::MAX)
+        );
         let mut reader = quick_xml::Reader::from_reader(reader);
         reader.trim_text(true);
         Reader {
             reader,
             buf: Vec::new(),
             ns_buf: Vec::new(),
+            limits,
+            depth: 0,
         }
     }
 
     /// Parse the start of the document.
     ///
     /// This is like `Content::take_element` except that it also happily
-    /// skips over XML and doctype declarations.
+    /// skips over the XML declaration.
     pub fn start<F, E>(&mut self, op: F) -> Result<Content, E>
     where F: FnOnce(Element) -> Result<(), E>, E: From<Error> {
         loop {
-            self.buf.clear();
-            let (ns, event) = self.reader.read_namespaced_event(
-                &mut self.buf, &mut self.ns_buf
-            ).map_err(Into::into)?;
+            let (ns, event) = self.next_event()?;
             match event {
                 Event::Start(start) => {
                     op(Element::new(start, ns))?;
@@ -49,7 +75,7 @@ impl<R: io::BufRead> Reader<R> {
                         Content { empty: true }
                     )
                 }
-                Event::Comment(_) | Event::Decl(_) | Event::DocType(_) => { }
+                Event::Comment(_) | Event::Decl(_) => { }
                 _ => return Err(Error::Malformed.into())
             }
         }
@@ -60,14 +86,153 @@ impl<R: io::BufRead> Reader<R> {
     /// This checks that the next non-comment event to be the end of file.
     pub fn end(&mut self) -> Result<(), Error> {
         loop {
-            self.buf.clear();
-            match self.reader.read_event(&mut self.buf)? {
+            match self.next_event()?.1 {
                 Event::Eof => return Ok(()),
                 Event::Comment(_) => { }
                 _ => return Err(Error::Malformed)
             }
         }
     }
+
+    /// Skips over the rest of an element whose start has been read.
+    fn skip_element(&mut self) -> Result<(), Error> {
+        let mut depth = 1;
+        while depth > 0 {
+            match self.next_event()?.1 {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                Event::Eof => return Err(Error::Malformed),
+                _ => { }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the next event and checks it against the limits.
+    fn next_event(
+        &mut self
+    ) -> Result<(Option<&[u8]>, Event<'_>), Error> {
+        self.buf.clear();
+        let (ns, event) = self.reader.read_namespaced_event(
+            &mut self.buf, &mut self.ns_buf
+        )?;
+        if let Some(max) = self.limits.max_size {
+            if self.reader.buffer_position() as u64 > max {
+                return Err(Error::TooLarge)
+            }
+        }
+        match event {
+            Event::Start(ref start) => {
+                self.depth += 1;
+                if self.depth > self.limits.max_depth {
+                    return Err(Error::TooDeep)
+                }
+                self.limits.check_attributes(start)?;
+            }
+            Event::Empty(ref start) => {
+                if self.depth >= self.limits.max_depth {
+                    return Err(Error::TooDeep)
+                }
+                self.limits.check_attributes(start)?;
+            }
+            Event::End(_) => {
+                self.depth = self.depth.saturating_sub(1);
+            }
+            Event::DocType(_) => return Err(Error::DocType),
+            _ => { }
+        }
+        Ok((ns, event))
+    }
+}
+
+
+//------------ Limits --------------------------------------------------------
+
+/// The limits enforced by a reader.
+///
+/// The default limits are generous enough for all documents used in the
+/// RPKI while preventing resource exhaustion through maliciously crafted
+/// documents. The overall size of a document is not limited by default
+/// since RRDP snapshots can legitimately become very large.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Limits {
+    /// The maximum nesting depth of elements.
+    max_depth: usize,
+
+    /// The maximum number of attributes of an element.
+    max_attributes: usize,
+
+    /// The maximum length of an attribute value in octets.
+    max_attribute_len: usize,
+
+    /// The maximum size of the document in octets.
+    max_size: Option<u64>,
+}
+
+impl Limits {
+    /// Returns the maximum nesting depth of elements.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Sets the maximum nesting depth of elements.
+    pub fn set_max_depth(&mut self, max_depth: usize) {
+        self.max_depth = max_depth
+    }
+
+    /// Returns the maximum number of attributes of an element.
+    pub fn max_attributes(&self) -> usize {
+        self.max_attributes
+    }
+
+    /// Sets the maximum number of attributes of an element.
+    pub fn set_max_attributes(&mut self, max_attributes: usize) {
+        self.max_attributes = max_attributes
+    }
+
+    /// Returns the maximum length of an attribute value in octets.
+    pub fn max_attribute_len(&self) -> usize {
+        self.max_attribute_len
+    }
+
+    /// Sets the maximum length of an attribute value in octets.
+    pub fn set_max_attribute_len(&mut self, max_attribute_len: usize) {
+        self.max_attribute_len = max_attribute_len
+    }
+
+    /// Returns the maximum size of the document in octets, if limited.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
+    }
+
+    /// Sets the maximum size of the document in octets.
+    pub fn set_max_size(&mut self, max_size: Option<u64>) {
+        self.max_size = max_size
+    }
+
+    /// Checks the attributes of an element against the limits.
+    fn check_attributes(&self, start: &BytesStart) -> Result<(), Error> {
+        for (idx, attr) in start.attributes().enumerate() {
+            if idx >= self.max_attributes {
+                return Err(Error::TooManyAttributes)
+            }
+            if attr?.value.len() > self.max_attribute_len {
+                return Err(Error::AttributeTooLong)
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_attributes: DEFAULT_MAX_ATTRIBUTES,
+            max_attribute_len: DEFAULT_MAX_ATTRIBUTE_LEN,
+            max_size: None,
+        }
+    }
 }
 
 
@@ -92,13 +257,13 @@ impl<'b, 'n> Element<'b, 'n> {
 
     /// Processes the attributes of the element.
     ///
-    /// We don’t support qualified attributes. We will also not check for
-    /// those.
+    /// Namespace declarations are skipped. We don’t support qualified
+    /// attributes. We will also not check for those.
     pub fn attributes<F, E>(&self, mut op: F) -> Result<(), E>
     where F: FnMut(&[u8], AttrValue) -> Result<(), E>, E: From<Error> {
         for attr in self.start.attributes() {
             let attr = attr.map_err(Into::into)?;
-            if attr.key == b"xmlns" {
+            if attr.key == b"xmlns" || attr.key.starts_with(b"xmlns:") {
                 continue
             }
             op(attr.key, AttrValue(attr))?;
@@ -126,10 +291,7 @@ impl Content {
         }
 
         loop {
-            reader.buf.clear();
-            let (ns, event) = reader.reader.read_namespaced_event(
-                &mut reader.buf, &mut reader.ns_buf
-            ).map_err(Into::into)?;
+            let (ns, event) = reader.next_event()?;
             match event {
                 Event::Start(start) => {
                     op(Element::new(start, ns))?;
@@ -160,10 +322,7 @@ impl Content {
         }
 
         loop {
-            reader.buf.clear();
-            let (ns, event) = reader.reader.read_namespaced_event(
-                &mut reader.buf, &mut reader.ns_buf
-            ).map_err(Into::into)?;
+            let (ns, event) = reader.next_event()?;
             match event {
                 Event::Start(start) => {
                     op(Element::new(start, ns))?;
@@ -187,6 +346,55 @@ impl Content {
         }
     }
 
+    /// Takes an optional element from the given namespace.
+    ///
+    /// Elements from other namespaces are considered extensions and are
+    /// skipped together with all their content. Otherwise, this is like
+    /// `take_opt_element`.
+    pub fn take_opt_element_in<R, F, E>(
+        &mut self,
+        reader: &mut Reader<R>,
+        namespace: &[u8],
+        op: F
+    ) -> Result<Option<Content>, E>
+    where R: io::BufRead, F: FnOnce(Element) -> Result<(), E>, E: From<Error> {
+        if self.empty {
+            return Ok(None)
+        }
+
+        loop {
+            let skip = {
+                let (ns, event) = reader.next_event()?;
+                let foreign = ns != Some(namespace);
+                match event {
+                    Event::Start(_) if foreign => true,
+                    Event::Empty(_) if foreign => false,
+                    Event::Start(start) => {
+                        op(Element::new(start, ns))?;
+                        return Ok(Some(
+                            Content { empty: false }
+                        ))
+                    }
+                    Event::Empty(start) => {
+                        op(Element::new(start, ns))?;
+                        return Ok(Some(
+                            Content { empty: true }
+                        ))
+                    }
+                    Event::End(_) => {
+                        self.empty = true;
+                        return Ok(None)
+                    }
+                    Event::Comment(_) => false,
+                    _ => return Err(Error::Malformed.into())
+                }
+            };
+            if skip {
+                reader.skip_element()?;
+            }
+        }
+    }
+
     pub fn take_text<R, F, T, E>(
         &mut self,
         reader: &mut Reader<R>,
@@ -198,10 +406,7 @@ impl Content {
         }
 
         loop {
-            reader.buf.clear();
-            let event = reader.reader.read_event(
-                &mut reader.buf
-            ).map_err(Into::into)?;
+            let event = reader.next_event()?.1;
             match event {
                 Event::Text(text) => {
                     return op(Text(text))
@@ -212,6 +417,19 @@ impl Content {
         }
     }
 
+    /// Takes base64 encoded text and writes the decoded data to `target`.
+    ///
+    /// Whitespace within the text is ignored. The text is decoded in
+    /// chunks, so no full copy of the encoded or decoded data is made.
+    pub fn take_base64_into<R, W>(
+        &mut self,
+        reader: &mut Reader<R>,
+        target: &mut W,
+    ) -> Result<(), Error>
+    where R: io::BufRead, W: io::Write {
+        self.take_text(reader, |text| text.base64_into(target))
+    }
+
     pub fn take_end<R: io::BufRead>(
         &mut self,
         reader: &mut Reader<R>
@@ -221,8 +439,7 @@ impl Content {
         }
 
         loop {
-            reader.buf.clear();
-            match reader.reader.read_event(&mut reader.buf)? {
+            match reader.next_event()?.1 {
                 Event::End(_) => {
                     self.empty = true;
                     return Ok(())
@@ -265,6 +482,21 @@ impl<'n, 'l> Name<'n, 'l> {
             local
         }
     }
+
+    /// Returns the namespace of the name if it is qualified.
+    pub fn namespace(&self) -> Option<&'n [u8]> {
+        self.namespace
+    }
+
+    /// Returns the local part of the name.
+    pub fn local(&self) -> &'l [u8] {
+        self.local
+    }
+
+    /// Returns whether the name is qualified with the given namespace.
+    pub fn is_in(&self, namespace: &[u8]) -> bool {
+        self.namespace == Some(namespace)
+    }
 }
 
 impl<'n, 'l> fmt::Debug for Name<'n, 'l> {
@@ -321,6 +553,43 @@ impl<'a> Text<'a> {
             }
         }
     }
+
+    /// Decodes the text as base64 and writes the data to `target`.
+    ///
+    /// Whitespace within the text is ignored.
+    pub fn base64_into<W: io::Write>(
+        &self, target: &mut W
+    ) -> Result<(), Error> {
+        let text = self.0.unescaped()?;
+        let mut chunk = [0u8; BASE64_CHUNK_LEN];
+        let mut data = [0u8; BASE64_CHUNK_LEN / 4 * 3];
+        let mut len = 0;
+        let mut padded = false;
+        for &ch in text.iter().filter(|ch| !ch.is_ascii_whitespace()) {
+            // Padding is only allowed at the very end.
+            if padded {
+                return Err(Error::Malformed)
+            }
+            chunk[len] = ch;
+            len += 1;
+            if len == chunk.len() {
+                padded = ch == b'=';
+                Self::write_base64_chunk(&chunk, &mut data, target)?;
+                len = 0;
+            }
+        }
+        Self::write_base64_chunk(&chunk[..len], &mut data, target)
+    }
+
+    /// Decodes a chunk of base64 and writes it to `target`.
+    fn write_base64_chunk<W: io::Write>(
+        chunk: &[u8], data: &mut [u8], target: &mut W
+    ) -> Result<(), Error> {
+        let len = base64::decode_config_slice(
+            chunk, base64::STANDARD, data
+        ).map_err(|_| Error::Malformed)?;
+        target.write_all(&data[..len]).map_err(Error::Io)
+    }
 }
 
 
@@ -328,8 +597,29 @@ impl<'a> Text<'a> {
 
 #[derive(Debug)]
 pub enum Error {
+    /// The XML parser failed.
     Xml(quick_xml::Error),
+
+    /// Writing decoded data failed.
+    Io(io::Error),
+
+    /// The document does not have the expected structure.
     Malformed,
+
+    /// The document contains a document type declaration.
+    DocType,
+
+    /// The elements are nested too deeply.
+    TooDeep,
+
+    /// An element has too many attributes.
+    TooManyAttributes,
+
+    /// An attribute value is too long.
+    AttributeTooLong,
+
+    /// The document is too large.
+    TooLarge,
 }
 
 impl From<quick_xml::Error> for Error {
@@ -342,9 +632,219 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             Error::Xml(ref err) => err.fmt(f),
+            Error::Io(ref err) => err.fmt(f),
             Error::Malformed => f.write_str("malformed XML"),
+            Error::DocType => {
+                f.write_str("document type declarations not allowed")
+            }
+            Error::TooDeep => f.write_str("elements nested too deeply"),
+            Error::TooManyAttributes => {
+                f.write_str("too many attributes in element")
+            }
+            Error::AttributeTooLong => f.write_str("attribute value too long"),
+            Error::TooLarge => f.write_str("document too large"),
         }
     }
 }
 
-impl error::Error for Error { } 
+impl error::Error for Error { }
+
+
+//------------ Configuration Constants ---------------------------------------
+
+/// The default maximum nesting depth of elements.
+const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// The default maximum number of attributes of an element.
+const DEFAULT_MAX_ATTRIBUTES: usize = 32;
+
+/// The default maximum length of an attribute value in octets.
+const DEFAULT_MAX_ATTRIBUTE_LEN: usize = 64 * 1024;
+
+/// The number of base64 characters decoded at once.
+///
+/// This must be a multiple of four.
+const BASE64_CHUNK_LEN: usize = 4096;
+
+
+//============ Tests =========================================================
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Parses a document consisting of nested elements of any name.
+    fn parse(data: &[u8], limits: Limits) -> Result<(), Error> {
+        let mut reader = Reader::with_limits(data, limits);
+        let outer = reader.start(any_element)?;
+        take_content(outer, &mut reader)?;
+        reader.end()
+    }
+
+    fn take_content<R: io::BufRead>(
+        mut content: Content, reader: &mut Reader<R>
+    ) -> Result<(), Error> {
+        while let Some(inner) = content.take_opt_element(
+            reader, any_element
+        )? {
+            take_content(inner, reader)?;
+        }
+        content.take_end(reader)
+    }
+
+    fn any_element(element: Element) -> Result<(), Error> {
+        element.attributes(|_, _| Ok(()))
+    }
+
+    #[test]
+    fn doctype() {
+        assert!(parse(b"<a><b/></a>", Limits::default()).is_ok());
+        assert!(matches_err(
+            parse(
+                b"<!DOCTYPE a [<!ENTITY b \"c\">]><a>&b;</a>",
+                Limits::default()
+            ),
+            Error::DocType
+        ));
+    }
+
+    #[test]
+    fn depth() {
+        let mut limits = Limits::default();
+        limits.set_max_depth(3);
+        assert!(parse(b"<a><b><c/></b></a>", limits).is_ok());
+        assert!(parse(b"<a><b><c></c></b></a>", limits).is_ok());
+        assert!(matches_err(
+            parse(b"<a><b><c><d/></c></b></a>", limits), Error::TooDeep
+        ));
+        assert!(matches_err(
+            parse(b"<a><b><c><d></d></c></b></a>", limits), Error::TooDeep
+        ));
+        assert!(parse(b"<a><b><c/></b><b><c/></b></a>", limits).is_ok());
+    }
+
+    #[test]
+    fn attributes() {
+        let mut limits = Limits::default();
+        limits.set_max_attributes(2);
+        limits.set_max_attribute_len(4);
+        assert!(parse(b"<a x=\"1\" y=\"1234\"/>", limits).is_ok());
+        assert!(matches_err(
+            parse(b"<a x=\"1\" y=\"2\" z=\"3\"/>", limits),
+            Error::TooManyAttributes
+        ));
+        assert!(matches_err(
+            parse(b"<a><b x=\"12345\"/></a>", limits),
+            Error::AttributeTooLong
+        ));
+    }
+
+    #[test]
+    fn size() {
+        let data = b"<a><b/></a>";
+        let mut limits = Limits::default();
+        limits.set_max_size(Some(data.len() as u64));
+        assert!(parse(data, limits).is_ok());
+        limits.set_max_size(Some(data.len() as u64 - 1));
+        assert!(matches_err(parse(data, limits), Error::TooLarge));
+        limits.set_max_size(Some(u64::MAX));
+        assert!(parse(data, limits).is_ok());
+    }
+
+    #[test]
+    fn base64() {
+        fn decode(data: &[u8]) -> Result<Vec<u8>, Error> {
+            let mut reader = Reader::new(data);
+            let mut outer = reader.start(any_element)?;
+            let mut res = Vec::new();
+            outer.take_base64_into(&mut reader, &mut res)?;
+            outer.take_end(&mut reader)?;
+            reader.end()?;
+            Ok(res)
+        }
+
+        assert_eq!(decode(b"<a>aGVsbG8=</a>").unwrap(), b"hello");
+        assert_eq!(
+            decode(b"<a>\n  aGVs\n  bG8g\r\n d29y bGQ=\n</a>").unwrap(),
+            b"hello world"
+        );
+        assert!(decode(b"<a>aGVsbG8=aGVs</a>").is_err());
+        assert!(decode(b"<a>aGVs*G8=</a>").is_err());
+
+        let data: Vec<u8> = (0..10000u32).map(|x| x as u8).collect();
+        let encoded = base64::encode(&data);
+        let mut xml = b"<a>".to_vec();
+        for line in encoded.as_bytes().chunks(76) {
+            xml.extend_from_slice(line);
+            xml.push(b'\n');
+        }
+        xml.extend_from_slice(b"</a>");
+        assert_eq!(decode(&xml).unwrap(), data);
+    }
+
+    #[test]
+    fn namespaces() {
+        const NS: &[u8] = b"urn:test";
+        const A: Name = Name::qualified(NS, b"a");
+        const B: Name = Name::qualified(NS, b"b");
+
+        fn parse(data: &[u8]) -> Result<usize, Error> {
+            let mut reader = Reader::new(data);
+            let mut outer = reader.start(|element| {
+                if element.name() != A {
+                    return Err(Error::Malformed)
+                }
+                element.attributes(|_, _| Err(Error::Malformed))
+            })?;
+            let mut count = 0;
+            while let Some(mut inner) = outer.take_opt_element_in(
+                &mut reader, NS, |element| {
+                    if element.name() != B {
+                        return Err(Error::Malformed)
+                    }
+                    Ok(())
+                }
+            )? {
+                count += 1;
+                inner.take_end(&mut reader)?;
+            }
+            outer.take_end(&mut reader)?;
+            reader.end()?;
+            Ok(count)
+        }
+
+        assert_eq!(
+            parse(b"<a xmlns=\"urn:test\"><b/><b></b></a>").unwrap(), 2
+        );
+        assert_eq!(
+            parse(
+                b"<t:a xmlns:t=\"urn:test\" xmlns:x=\"urn:other\">\
+                  <t:b/><x:c><x:d>text</x:d><t:b/></x:c><x:e/><t:b/>\
+                  </t:a>"
+            ).unwrap(),
+            2
+        );
+        assert!(parse(b"<a xmlns=\"urn:other\"><b/></a>").is_err());
+        assert!(
+            parse(
+                b"<a xmlns=\"urn:test\"><b xmlns=\"urn:other\"/><c/></a>"
+            ).is_err()
+        );
+        assert!(
+            parse(b"<a xmlns=\"urn:test\"><b/><c/></a>").is_err()
+        );
+        assert!(B.is_in(NS));
+        assert_eq!(B.local(), b"b");
+        assert_eq!(Name::unqualified(b"b").namespace(), None);
+    }
+
+    fn matches_err(res: Result<(), Error>, expected: Error) -> bool {
+        match res {
+            Err(err) => {
+                std::mem::discriminant(&err)
+                    == std::mem::discriminant(&expected)
+            }
+            Ok(()) => false
+        }
+    }
+}